@@ -0,0 +1,47 @@
+use criterion::{black_box, criterion_group, Criterion};
+use hebi::*;
+
+const SOURCE: &str = indoc::indoc! {
+  r#"#!hebi
+    fn run(prefix):
+      t := {}
+      keys := []
+      i := 0
+      while i < 100:
+        key := prefix + to_str(i)
+        keys.push(key)
+        t[key] = i
+        i += 1
+
+      total := 0
+      for j in 0..100:
+        for key in keys:
+          total += t[key]
+      return total
+  "#,
+};
+
+fn bench_keys(c: &mut Criterion, name: &str, prefix: &str) {
+  c.bench_function(name, |b| {
+    let mut hebi = Hebi::new();
+    hebi.eval(SOURCE).unwrap();
+
+    let chunk = hebi.compile(&format!("run(\"{prefix}\")")).unwrap();
+
+    b.iter(|| {
+      black_box(hebi.run(chunk.clone()).unwrap());
+    })
+  });
+}
+
+// keys which fit in a `Str` are stored inline, the rest are heap-allocated,
+// so comparing these two shows the cost of the extra indirection
+pub fn table_short_keys(c: &mut Criterion) {
+  bench_keys(c, "table access (short keys)", "key_");
+}
+
+pub fn table_long_keys(c: &mut Criterion) {
+  bench_keys(c, "table access (long keys)", "a_much_longer_table_key_");
+}
+
+criterion_group!(bench, table_short_keys, table_long_keys);
@@ -4,6 +4,7 @@ mod benches {
   pub mod fib;
   pub mod primes;
   pub mod startup;
+  pub mod table;
 }
 
 #[cfg(enable_slow_bench)]
@@ -11,10 +12,12 @@ criterion_main! {
   benches::fib::bench,
   benches::startup::bench,
   benches::primes::bench,
+  benches::table::bench,
 }
 
 #[cfg(not(enable_slow_bench))]
 criterion_main! {
   benches::fib::bench,
   benches::startup::bench,
+  benches::table::bench,
 }
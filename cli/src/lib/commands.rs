@@ -1,4 +1,9 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use anyhow::Context;
 use clap::{Args, Subcommand};
+use hebi::analysis::{analyze, lint, LintConfig, Rule};
 
 use crate::common::InputArgs;
 
@@ -6,20 +11,36 @@ use crate::common::InputArgs;
 pub enum Command {
   /// Execute a Hebi file [default]
   Run(RunArgs),
-  /// Disassemble a Hebi file.
-  #[clap(visible_alias = "dis")]
+  /// Disassemble a Hebi file, with each block of instructions preceded by
+  /// the line of source it was compiled from.
+  #[clap(visible_aliases = ["dis", "disasm"])]
   Disassemble(InputArgs),
+  /// Compile a Hebi file, and report any errors.
+  Compile(InputArgs),
+  /// Format Hebi files in place.
+  Fmt(FmtArgs),
+  /// Check a Hebi file for syntax errors and likely mistakes.
+  Check(CheckArgs),
+  /// Start an interactive prompt, which evaluates each statement as it is
+  /// entered.
   Repl,
 }
 impl Command {
   pub fn run(input: InputArgs) -> Self {
-    Self::Run(RunArgs { input, dump: false })
+    Self::Run(RunArgs {
+      input,
+      dump: false,
+      args: vec![],
+    })
   }
 
   pub fn execute(self) -> anyhow::Result<()> {
     match self {
       Self::Run(args) => handle_run(args),
       Self::Disassemble(args) => handle_disassemble(args),
+      Self::Compile(args) => handle_compile(args),
+      Self::Fmt(args) => handle_fmt(args),
+      Self::Check(args) => handle_check(args),
       Self::Repl => handle_repl(),
     }
   }
@@ -32,17 +53,42 @@ pub struct RunArgs {
   dump: bool,
   #[clap(flatten)]
   input: InputArgs,
+  /// Arguments passed to the script, which it may read from `argv`.
+  #[arg(last = true, value_name = "ARGS")]
+  args: Vec<String>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct FmtArgs {
+  /// Fail if any file is not formatted, instead of rewriting it.
+  #[clap(long, default_value_t = false)]
+  check: bool,
+  /// The files to format. If none are provided, the code is read from stdin,
+  /// and the formatted code is written to stdout.
+  #[arg(value_name = "FILE")]
+  files: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct CheckArgs {
+  /// Disable a lint rule, such as `unused-variable`. May be repeated.
+  #[clap(long, value_name = "RULE")]
+  allow: Vec<Rule>,
+  /// Fail if there are any lints, not only if there are syntax errors.
+  #[clap(long, default_value_t = false)]
+  deny_warnings: bool,
+  #[clap(flatten)]
+  input: InputArgs,
 }
 
 fn handle_repl() -> anyhow::Result<()> {
-  crate::repl::run().map_err(|e| anyhow::anyhow!(e))?;
-  Ok(())
+  crate::repl::run()
 }
 
 fn handle_run(args: RunArgs) -> anyhow::Result<()> {
   let source = args.input.source()?;
 
-  let mut hebi = crate::hebi::build_hebi();
+  let mut hebi = crate::hebi::build_hebi(args.args);
   match hebi.eval(&source) {
     Ok(_) => {
       if args.dump {
@@ -53,6 +99,7 @@ fn handle_run(args: RunArgs) -> anyhow::Result<()> {
       if args.dump {
         eprintln!("{:#?}", hebi);
       }
+      crate::hebi::exit_if_requested(&e);
       crate::hebi::report_errors(&source, e);
       anyhow::bail!("Failed to run {}", args.input.name());
     }
@@ -63,7 +110,7 @@ fn handle_run(args: RunArgs) -> anyhow::Result<()> {
 fn handle_disassemble(input: InputArgs) -> anyhow::Result<()> {
   let source = input.source()?;
 
-  let hebi = crate::hebi::build_hebi();
+  let hebi = crate::hebi::build_hebi(vec![]);
   let chunk = match hebi.compile(&source) {
     Ok(chunk) => chunk,
     Err(e) => {
@@ -72,7 +119,109 @@ fn handle_disassemble(input: InputArgs) -> anyhow::Result<()> {
     }
   };
 
-  println!("{}", chunk.disassemble());
+  println!("{}", chunk.disassemble_with_source());
+
+  Ok(())
+}
+
+fn handle_compile(input: InputArgs) -> anyhow::Result<()> {
+  let source = input.source()?;
+
+  let hebi = crate::hebi::build_hebi(vec![]);
+  if let Err(e) = hebi.compile_program(&source) {
+    crate::hebi::report_errors(&source, e);
+    anyhow::bail!("Failed to compile {}", input.name());
+  }
+
+  Ok(())
+}
+
+fn handle_fmt(args: FmtArgs) -> anyhow::Result<()> {
+  if args.files.is_empty() {
+    let mut source = String::new();
+    std::io::stdin()
+      .read_to_string(&mut source)
+      .with_context(|| "Failed to read from stdin")?;
+    match hebi::fmt::format(&source) {
+      Ok(formatted) => {
+        if args.check && formatted != source {
+          anyhow::bail!("script is not formatted");
+        }
+        if !args.check {
+          print!("{formatted}");
+        }
+      }
+      Err(e) => {
+        crate::hebi::report_errors(&source, e);
+        anyhow::bail!("Failed to format script");
+      }
+    }
+    return Ok(());
+  }
+
+  let mut unformatted = vec![];
+  let mut failed = false;
+  for path in args.files.iter() {
+    let source = std::fs::read_to_string(path)
+      .with_context(|| format!("Failed to read file at {}", path.display()))?;
+    let formatted = match hebi::fmt::format(&source) {
+      Ok(formatted) => formatted,
+      Err(e) => {
+        crate::hebi::report_errors(&source, e);
+        eprintln!("Failed to format {}", path.display());
+        failed = true;
+        continue;
+      }
+    };
+    if formatted == source {
+      continue;
+    }
+    if args.check {
+      unformatted.push(path);
+    } else {
+      std::fs::write(path, formatted)
+        .with_context(|| format!("Failed to write file at {}", path.display()))?;
+    }
+  }
 
+  for path in unformatted.iter() {
+    println!("{}", path.display());
+  }
+  if failed {
+    anyhow::bail!("Failed to format some files");
+  }
+  if !unformatted.is_empty() {
+    anyhow::bail!("{} file(s) are not formatted", unformatted.len());
+  }
+  Ok(())
+}
+
+fn handle_check(args: CheckArgs) -> anyhow::Result<()> {
+  let source = args.input.source()?;
+
+  let config = args
+    .allow
+    .iter()
+    .fold(LintConfig::new(), |config, rule| config.allow(*rule));
+  let analysis = analyze(&source);
+  let lints = lint(&analysis, &config);
+
+  for e in analysis.diagnostics.iter() {
+    crate::hebi::report_diagnostic(&source, "error", e);
+  }
+  for lint in lints.iter() {
+    crate::hebi::report_diagnostic(
+      &source,
+      &format!("warning[{}]", lint.rule),
+      &lint.diagnostic,
+    );
+  }
+
+  if !analysis.diagnostics.is_empty() {
+    anyhow::bail!("{} has syntax errors", args.input.name());
+  }
+  if args.deny_warnings && !lints.is_empty() {
+    anyhow::bail!("{} has {} warning(s)", args.input.name(), lints.len());
+  }
   Ok(())
 }
@@ -1,52 +1,35 @@
 use hebi::{Hebi, NativeModule, Scope};
 
-pub fn build_hebi() -> Hebi {
-  let mut hebi = Hebi::new();
+pub fn build_hebi(args: Vec<String>) -> Hebi {
+  let mut hebi = Hebi::builder()
+    .args(args)
+    .with_builtins()
+    .allow_fs(true)
+    .allow_os(true)
+    .finish();
   hebi.register(&self::io::build());
-  hebi.register(&self::parsing::build());
   hebi
 }
 
 pub fn report_errors(source: &str, e: hebi::Error) {
-  let color = supports_color::on(supports_color::Stream::Stderr)
-    .map(|c| c.has_basic)
-    .unwrap_or(false);
-  eprintln!("{}", e.report(source, color));
+  eprintln!("{}", e.report(source, use_color()));
 }
 
-mod parsing {
-  use hebi::{IntoValue, Value};
-
-  use super::*;
-
-  pub fn build() -> hebi::NativeModule {
-    NativeModule::builder("parsing")
-      .function("int", parse_int)
-      .function("float", parse_float)
-      .finish()
-  }
-
-  fn parse_int(scope: Scope<'_>) -> hebi::Result<Value> {
-    let value = scope.param::<String>(0)?;
-    let result = value
-      .as_str()
-      .parse::<i32>()
-      .map(|n| n.into_value(scope.global()))
-      .unwrap_or_else(|_| None::<i32>.into_value(scope.global()));
-
-    result
+/// Exit the process if the script called `os.exit`.
+pub fn exit_if_requested(e: &hebi::Error) {
+  if let hebi::ErrorKind::Exit(code) = e.kind() {
+    std::process::exit(*code);
   }
+}
 
-  fn parse_float(scope: Scope<'_>) -> hebi::Result<Value> {
-    let value = scope.param::<String>(0)?;
-    let result = value
-      .as_str()
-      .parse::<f64>()
-      .map(|n| n.into_value(scope.global()))
-      .unwrap_or_else(|_| None::<f64>.into_value(scope.global()));
+pub fn report_diagnostic(source: &str, prefix: &str, e: &hebi::span::SpannedError) {
+  eprintln!("{prefix}: {}", e.report(source, use_color()));
+}
 
-    result
-  }
+fn use_color() -> bool {
+  supports_color::on(supports_color::Stream::Stderr)
+    .map(|c| c.has_basic)
+    .unwrap_or(false)
 }
 
 mod io {
@@ -1,112 +1,361 @@
-use std::error::Error;
-use std::fmt::Display;
-use std::io;
-use std::io::Write;
-use std::time::Duration;
-
-use crossterm::cursor::MoveTo;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crossterm::cursor::MoveToColumn;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::style::Print;
-use crossterm::terminal::{Clear, ClearType};
-use crossterm::{cursor, terminal, QueueableCommand};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::QueueableCommand;
+use hebi::analysis::analyze;
+use hebi::Hebi;
 
-type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync + 'static>>;
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
 
-/* macro_rules! fail {
-  ($($tt:tt)*) => {
-    return Err(<Box<dyn Error + Send + Sync + 'static>>::from(format!($($tt)*)));
+pub fn run() -> anyhow::Result<()> {
+  let mut hebi = crate::hebi::build_hebi(vec![]);
+  let mut history = History::load();
+  let interactive = atty::is(atty::Stream::Stdin);
+  if interactive {
+    println!("Press Ctrl-D to exit.");
   }
-} */
 
-struct Line<'a>(&'a [char]);
+  while let Some(source) = read_input(&mut hebi, &mut history, interactive)? {
+    if source.trim().is_empty() {
+      continue;
+    }
+    match hebi.eval(&source) {
+      Ok(value) => {
+        if !value.is_none() {
+          println!("{value}");
+        }
+      }
+      Err(e) => {
+        crate::hebi::exit_if_requested(&e);
+        crate::hebi::report_errors(&source, e);
+      }
+    }
+  }
 
-impl<'a> Display for Line<'a> {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    for char in self.0.iter() {
-      write!(f, "{char}")?;
+  Ok(())
+}
+
+/// Read lines until they form a complete statement. Returns `None` once
+/// there is no more input.
+fn read_input(
+  hebi: &mut Hebi,
+  history: &mut History,
+  interactive: bool,
+) -> anyhow::Result<Option<String>> {
+  let mut source = String::new();
+  loop {
+    let (prompt, indent) = if source.is_empty() {
+      (PROMPT, String::new())
+    } else {
+      (CONTINUATION_PROMPT, next_indent(&source))
+    };
+    let input = if interactive {
+      Editor::new(prompt, indent, hebi, history).read()?
+    } else {
+      read_plain()?
+    };
+    let line = match input {
+      Input::Line(line) => line,
+      Input::Interrupted => return Ok(Some(String::new())),
+      Input::Eof if source.is_empty() => return Ok(None),
+      Input::Eof => return Ok(Some(source)),
+    };
+
+    // an empty line ends a block
+    let is_blank = line.trim().is_empty();
+    if is_blank && !source.is_empty() {
+      return Ok(Some(source));
+    }
+    if !is_blank {
+      history.push(&line);
+    }
+    source.push_str(&line);
+    source.push('\n');
+    if !needs_more_input(&source) {
+      return Ok(Some(source));
+    }
+  }
+}
+
+/// Whether `source` is the beginning of a statement, which should continue
+/// on the next line.
+///
+/// Once a block has been started, lines are read until an empty line, even
+/// if the block could already be compiled, because there is no other way to
+/// tell whether it continues.
+fn needs_more_input(source: &str) -> bool {
+  if source.lines().any(|line| line.trim_end().ends_with(':')) {
+    return true;
+  }
+  // the parser fails at the end of the input if it is incomplete, for example
+  // when it expects an indented block, or the closing bracket of a list
+  let end = source.trim_end().len();
+  analyze(source)
+    .diagnostics
+    .iter()
+    .any(|diagnostic| diagnostic.span.start >= end)
+}
+
+/// The indentation of the line after the last line of `source`, which is
+/// indented further if the last line begins a block.
+fn next_indent(source: &str) -> String {
+  let Some(last) = source.lines().rev().find(|line| !line.trim().is_empty()) else {
+    return String::new();
+  };
+  let mut indent = last[..last.len() - last.trim_start().len()].to_string();
+  if last.trim_end().ends_with(':') {
+    indent.push_str("  ");
+  }
+  indent
+}
+
+enum Input {
+  Line(String),
+  /// The line was discarded with Ctrl-C.
+  Interrupted,
+  Eof,
+}
+
+/// Read a line from stdin when it is not a terminal.
+fn read_plain() -> io::Result<Input> {
+  let mut line = String::new();
+  if io::stdin().lock().read_line(&mut line)? == 0 {
+    return Ok(Input::Eof);
+  }
+  while line.ends_with(['\n', '\r']) {
+    line.pop();
+  }
+  Ok(Input::Line(line))
+}
+
+/// Lines which were entered previously, which are saved to the file in
+/// `HEBI_HISTORY`, or `.hebi_history` in the home directory.
+struct History {
+  path: Option<PathBuf>,
+  entries: Vec<String>,
+}
+
+impl History {
+  fn load() -> Self {
+    let path = std::env::var_os("HEBI_HISTORY")
+      .map(PathBuf::from)
+      .or_else(|| {
+        std::env::var_os("HOME")
+          .or_else(|| std::env::var_os("USERPROFILE"))
+          .map(|home| PathBuf::from(home).join(".hebi_history"))
+      });
+    let entries = path
+      .as_ref()
+      .and_then(|path| fs::read_to_string(path).ok())
+      .map(|history| history.lines().map(String::from).collect())
+      .unwrap_or_default();
+    Self { path, entries }
+  }
+
+  fn push(&mut self, line: &str) {
+    if self.entries.last().is_some_and(|last| last == line) {
+      return;
+    }
+    self.entries.push(line.to_string());
+    if let Some(path) = &self.path {
+      // the REPL is still usable if the history can't be saved
+      let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{line}"));
     }
-    Ok(())
   }
 }
 
-fn count_lines_with_wrapping(buffer: &[char], size: (u16, u16)) -> usize {
-  let mut lines = 0;
-  // for line in buffer {
-  lines += buffer.len() / size.0 as usize;
-  // }
-  lines
+/// Edits a single line in raw mode.
+struct Editor<'a> {
+  prompt: &'static str,
+  buffer: Vec<char>,
+  cursor: usize,
+  hebi: &'a mut Hebi,
+  history: &'a History,
+  /// The history entry which is currently shown, or `history.entries.len()`
+  /// if it's the line being entered.
+  history_index: usize,
+  /// The line being entered, while a history entry is shown instead.
+  draft: Vec<char>,
 }
 
-pub fn run() -> Result<()> {
-  terminal::enable_raw_mode()?;
+impl<'a> Editor<'a> {
+  fn new(prompt: &'static str, text: String, hebi: &'a mut Hebi, history: &'a History) -> Self {
+    let buffer = text.chars().collect::<Vec<_>>();
+    Self {
+      prompt,
+      cursor: buffer.len(),
+      buffer,
+      hebi,
+      history,
+      history_index: history.entries.len(),
+      draft: vec![],
+    }
+  }
 
-  let mut stdout = io::stdout();
-  {
-    let w = &mut stdout;
-    let mut buffer = Vec::<char>::new();
-    let anchor = cursor::position()?;
+  fn read(mut self) -> io::Result<Input> {
+    terminal::enable_raw_mode()?;
+    let result = self.read_raw();
+    terminal::disable_raw_mode()?;
+    println!();
+    result
+  }
 
+  fn read_raw(&mut self) -> io::Result<Input> {
+    let mut stdout = io::stdout();
+    self.render(&mut stdout)?;
     loop {
-      event::poll(Duration::from_millis(10))?;
-
-      let mut position = cursor::position()?;
-      let start_lines = count_lines_with_wrapping(&buffer, terminal::size()?);
-
-      match event::read()? {
-        Event::Key(KeyEvent {
-          code: KeyCode::Char('c'),
-          modifiers: KeyModifiers::CONTROL,
-          ..
-        })
-        | Event::Key(KeyEvent {
-          code: KeyCode::Char('d'),
-          modifiers: KeyModifiers::CONTROL,
-          ..
-        }) => {
-          break;
+      let Event::Key(key) = event::read()? else {
+        continue;
+      };
+      if key.kind == KeyEventKind::Release {
+        continue;
+      }
+      let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+      match key.code {
+        KeyCode::Char('c') if ctrl => return Ok(Input::Interrupted),
+        KeyCode::Char('d') if ctrl => {
+          if self.buffer.is_empty() {
+            return Ok(Input::Eof);
+          }
+          if self.cursor < self.buffer.len() {
+            self.buffer.remove(self.cursor);
+          }
         }
-        Event::Key(KeyEvent {
-          code: KeyCode::Char(c),
-          ..
-        }) => {
-          buffer.insert(position.0 as usize, c);
-          position.0 += 1;
+        KeyCode::Char('a') if ctrl => self.cursor = 0,
+        KeyCode::Char('e') if ctrl => self.cursor = self.buffer.len(),
+        KeyCode::Char('u') if ctrl => {
+          self.buffer.drain(..self.cursor);
+          self.cursor = 0;
         }
-        Event::Key(KeyEvent {
-          code: KeyCode::Backspace,
-          ..
-        }) => {
-          if position.0 > 0 {
-            buffer.remove(position.0 as usize - 1);
-            position.0 -= 1;
-          }
+        KeyCode::Char(_) if ctrl => {}
+        KeyCode::Char(c) => {
+          self.buffer.insert(self.cursor, c);
+          self.cursor += 1;
+        }
+        KeyCode::Enter => return Ok(Input::Line(self.buffer.iter().collect())),
+        KeyCode::Backspace if self.cursor > 0 => {
+          self.cursor -= 1;
+          self.buffer.remove(self.cursor);
+        }
+        KeyCode::Delete if self.cursor < self.buffer.len() => {
+          self.buffer.remove(self.cursor);
         }
-        // Event::Key(_) => todo!(),
-        // Event::Paste(_) => todo!(),
-        // Event::Resize(_, _) => todo!(),
+        KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+        KeyCode::Right => self.cursor = (self.cursor + 1).min(self.buffer.len()),
+        KeyCode::Home => self.cursor = 0,
+        KeyCode::End => self.cursor = self.buffer.len(),
+        KeyCode::Up => self.show_history(self.history_index.saturating_sub(1)),
+        KeyCode::Down => self.show_history(self.history_index + 1),
+        KeyCode::Tab => self.complete(&mut stdout)?,
         _ => {}
       }
+      self.render(&mut stdout)?;
+    }
+  }
 
-      let end_lines = count_lines_with_wrapping(&buffer, terminal::size()?);
+  fn render(&self, w: &mut impl Write) -> io::Result<()> {
+    w.queue(MoveToColumn(0))?
+      .queue(Clear(ClearType::CurrentLine))?
+      .queue(Print(self.prompt))?
+      .queue(Print(self.buffer.iter().collect::<String>()))?
+      .queue(MoveToColumn((self.prompt.len() + self.cursor) as u16))?;
+    w.flush()
+  }
 
-      /* if start_lines != end_lines {
-        if start_lines > end_lines {
-          anchor -=
-        }
-      } */
+  fn show_history(&mut self, index: usize) {
+    let len = self.history.entries.len();
+    if index > len || index == self.history_index {
+      return;
+    }
+    if self.history_index == len {
+      self.draft = std::mem::take(&mut self.buffer);
+    }
+    self.buffer = if index == len {
+      std::mem::take(&mut self.draft)
+    } else {
+      self.history.entries[index].chars().collect()
+    };
+    self.history_index = index;
+    self.cursor = self.buffer.len();
+  }
 
-      w.queue(MoveTo(anchor.0, anchor.1))?;
-      w.queue(Clear(ClearType::FromCursorDown))?;
-      w.queue(Print(Line(&buffer)))?;
-      w.queue(MoveTo(position.0, position.1))?;
+  /// Complete the name before the cursor. If there are multiple candidates
+  /// which don't share a longer prefix, they are listed below the line.
+  fn complete(&mut self, w: &mut impl Write) -> io::Result<()> {
+    let start = self.buffer[..self.cursor]
+      .iter()
+      .rposition(|c| !is_name_char(*c))
+      .map_or(0, |i| i + 1);
+    let head = self.buffer[..start].iter().collect::<String>();
+    let word = self.buffer[start..self.cursor].iter().collect::<String>();
+    let (path, partial) = match word.rsplit_once('.') {
+      Some((path, partial)) => (Some(path), partial),
+      None => (None, word.as_str()),
+    };
 
-      write!(w, "{}", start_lines.abs_diff(end_lines))?;
+    let mut matches = candidates(self.hebi, &head, path)
+      .into_iter()
+      .filter(|name| name.starts_with(partial))
+      .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    let Some(first) = matches.first() else {
+      return Ok(());
+    };
 
-      w.flush()?;
+    let common = matches.iter().fold(first.as_str(), |common, name| {
+      let len = common
+        .char_indices()
+        .zip(name.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(common.len().min(name.len()), |((i, _), _)| i);
+      &common[..len]
+    });
+    if common.len() > partial.len() {
+      for c in common[partial.len()..].chars() {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+      }
+    } else if matches.len() > 1 {
+      w.queue(Print("\r\n"))?
+        .queue(Print(matches.join("  ")))?
+        .queue(Print("\r\n"))?;
     }
+    Ok(())
   }
+}
 
-  terminal::disable_raw_mode()?;
+fn is_name_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '.'
+}
 
-  Ok(())
+/// The names which may be completed after `head`, the text before the name.
+/// If the name is preceded by `path` and a `.`, then these are the fields of
+/// the value of `path`.
+fn candidates(hebi: &mut Hebi, head: &str, path: Option<&str>) -> Vec<String> {
+  if matches!(head.trim(), "import" | "from") {
+    return hebi.global().module_names();
+  }
+  match path {
+    // `path` only contains names and dots, so it can't call anything
+    Some(path) => match hebi.eval(path) {
+      Ok(value) => value.field_names(),
+      Err(_) => vec![],
+    },
+    None => hebi
+      .global()
+      .entries()
+      .map(|(name, _)| name.as_str().to_string())
+      .collect(),
+  }
 }
@@ -0,0 +1,269 @@
+//! Derive macros for converting Rust types to and from Hebi values.
+//!
+//! - Structs with named fields become tables, with one entry per field.
+//!   They may be converted back from a table or a class instance.
+//! - Structs with a single unnamed field are converted as the field itself.
+//! - Unit structs become `none`.
+//! - Enums become tables with a `type` entry holding the name of the variant.
+//!   Fields of struct variants are stored next to it, and the field of a
+//!   tuple variant with a single field is stored under `value`.
+//!
+//! These are re-exported by the `hebi` crate when its `derive` feature is
+//! enabled, and should be used from there.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+  parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Generics, Ident, Lifetime,
+  Result,
+};
+
+/// The key which holds the variant name of an enum.
+const TAG: &str = "type";
+
+/// The key which holds the field of a tuple variant.
+const VALUE: &str = "value";
+
+#[proc_macro_derive(ToHebi)]
+pub fn derive_to_hebi(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  to_hebi(&input)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+#[proc_macro_derive(FromHebi)]
+pub fn derive_from_hebi(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  from_hebi(&input)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn to_hebi(input: &DeriveInput) -> Result<TokenStream2> {
+  let name = &input.ident;
+  let body = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => {
+        let names = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+        into_table(names.map(|name| (name, quote!(self.#name))), None)
+      }
+      Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+        quote!(::hebi::IntoValue::into_value(self.0, global))
+      }
+      Fields::Unit => quote!(::hebi::IntoValue::into_value((), global)),
+      Fields::Unnamed(fields) => return Err(unsupported_tuple(fields)),
+    },
+    Data::Enum(data) => {
+      let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+          let ident = &variant.ident;
+          let tag = ident.to_string();
+          Ok(match &variant.fields {
+            Fields::Named(fields) => {
+              let names = fields
+                .named
+                .iter()
+                .map(|f| f.ident.as_ref().unwrap())
+                .collect::<Vec<_>>();
+              // bound under a different name, so they can't shadow `global`
+              let bindings = names.iter().map(|name| format_ident!("__{name}"));
+              let table = into_table(
+                names.iter().map(|name| {
+                  let binding = format_ident!("__{name}");
+                  (*name, quote!(#binding))
+                }),
+                Some(&tag),
+              );
+              quote!(#name::#ident { #(#names: #bindings),* } => #table)
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+              let field = Ident::new(VALUE, Span::call_site());
+              let table = into_table([(&field, quote!(__value))], Some(&tag));
+              quote!(#name::#ident(__value) => #table)
+            }
+            Fields::Unit => {
+              let table = into_table([], Some(&tag));
+              quote!(#name::#ident => #table)
+            }
+            Fields::Unnamed(fields) => return Err(unsupported_tuple(fields)),
+          })
+        })
+        .collect::<Result<Vec<_>>>()?;
+      quote!(match self { #(#arms,)* })
+    }
+    Data::Union(data) => {
+      return Err(Error::new_spanned(
+        data.union_token,
+        "`ToHebi` cannot be derived for unions",
+      ))
+    }
+  };
+
+  let (impl_generics, ty_generics, where_clause) = with_bounds(&input.generics, "IntoValue");
+  Ok(quote! {
+    impl #impl_generics ::hebi::IntoValue<'__cx> for #name #ty_generics #where_clause {
+      fn into_value(
+        self,
+        global: ::hebi::Global<'__cx>,
+      ) -> ::hebi::Result<::hebi::Value<'__cx>> {
+        #body
+      }
+    }
+  })
+}
+
+/// Build a table from `fields`, each of which is a key and an expression which
+/// evaluates to its value. If `tag` is set, it is stored under the `type` key.
+fn into_table<'a>(
+  fields: impl IntoIterator<Item = (&'a Ident, TokenStream2)>,
+  tag: Option<&str>,
+) -> TokenStream2 {
+  let tag = tag.map(|tag| {
+    quote! {
+      table.insert(
+        global.new_string(#TAG),
+        ::hebi::IntoValue::into_value(global.new_string(#tag), global.clone())?,
+      );
+    }
+  });
+  let fields = fields.into_iter().map(|(name, value)| {
+    let key = name.to_string();
+    quote! {
+      table.insert(
+        global.new_string(#key),
+        ::hebi::IntoValue::into_value(#value, global.clone())?,
+      );
+    }
+  });
+  quote!({
+    let table = global.new_table(0);
+    #tag
+    #(#fields)*
+    ::hebi::IntoValue::into_value(table, global)
+  })
+}
+
+fn from_hebi(input: &DeriveInput) -> Result<TokenStream2> {
+  let name = &input.ident;
+  let type_name = name.to_string();
+  let body = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => {
+        let fields = from_fields(
+          fields.named.iter().map(|f| f.ident.as_ref().unwrap()),
+          &type_name,
+        );
+        quote!(Ok(Self { #(#fields),* }))
+      }
+      Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+        quote!(Ok(Self(::hebi::FromValue::from_value(value, global)?)))
+      }
+      Fields::Unit => quote!({
+        let _ = (value, global);
+        Ok(Self)
+      }),
+      Fields::Unnamed(fields) => return Err(unsupported_tuple(fields)),
+    },
+    Data::Enum(data) => {
+      let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+          let ident = &variant.ident;
+          let tag = ident.to_string();
+          let type_name = format!("{name}::{ident}");
+          Ok(match &variant.fields {
+            Fields::Named(fields) => {
+              let fields = from_fields(
+                fields.named.iter().map(|f| f.ident.as_ref().unwrap()),
+                &type_name,
+              );
+              quote!(#tag => Ok(Self::#ident { #(#fields),* }))
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+              #tag => Ok(Self::#ident(
+                ::hebi::__derive::field(&value, &global, #type_name, #VALUE)?,
+              ))
+            },
+            Fields::Unit => quote!(#tag => Ok(Self::#ident)),
+            Fields::Unnamed(fields) => return Err(unsupported_tuple(fields)),
+          })
+        })
+        .collect::<Result<Vec<_>>>()?;
+      quote! {
+        let tag: String = ::hebi::__derive::field(&value, &global, #type_name, #TAG)?;
+        match tag.as_str() {
+          #(#arms,)*
+          tag => Err(::hebi::__derive::unknown_variant(#type_name, tag)),
+        }
+      }
+    }
+    Data::Union(data) => {
+      return Err(Error::new_spanned(
+        data.union_token,
+        "`FromHebi` cannot be derived for unions",
+      ))
+    }
+  };
+
+  let (impl_generics, ty_generics, where_clause) = with_bounds(&input.generics, "FromValue");
+  Ok(quote! {
+    impl #impl_generics ::hebi::FromValue<'__cx> for #name #ty_generics #where_clause {
+      fn from_value(
+        value: ::hebi::Value<'__cx>,
+        global: ::hebi::Global<'__cx>,
+      ) -> ::hebi::Result<Self> {
+        #body
+      }
+    }
+  })
+}
+
+fn from_fields<'a>(
+  fields: impl Iterator<Item = &'a Ident> + 'a,
+  type_name: &'a str,
+) -> impl Iterator<Item = TokenStream2> + 'a {
+  fields.map(move |name| {
+    let key = name.to_string();
+    quote!(#name: ::hebi::__derive::field(&value, &global, #type_name, #key)?)
+  })
+}
+
+/// Add the `'__cx` lifetime used by the conversion traits, and require every
+/// type parameter to implement `bound`.
+fn with_bounds(
+  generics: &Generics,
+  bound: &str,
+) -> (TokenStream2, TokenStream2, Option<TokenStream2>) {
+  let bound = format_ident!("{bound}");
+  let cx = Lifetime::new("'__cx", Span::call_site());
+
+  let mut impl_generics = generics.clone();
+  impl_generics.params.insert(0, parse_quote!(#cx));
+  let where_clause = impl_generics.make_where_clause();
+  for param in generics.type_params() {
+    let ident = &param.ident;
+    where_clause
+      .predicates
+      .push(parse_quote!(#ident: ::hebi::#bound<#cx>));
+  }
+
+  let (impl_generics_tokens, _, where_clause) = impl_generics.split_for_impl();
+  let (_, ty_generics, _) = generics.split_for_impl();
+  (
+    quote!(#impl_generics_tokens),
+    quote!(#ty_generics),
+    where_clause.map(|w| quote!(#w)),
+  )
+}
+
+fn unsupported_tuple(fields: &syn::FieldsUnnamed) -> Error {
+  Error::new_spanned(
+    fields,
+    "only tuple structs and variants with a single field are supported",
+  )
+}
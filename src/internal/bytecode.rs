@@ -2,3 +2,5 @@ pub mod builder;
 pub mod disasm;
 pub mod opcode;
 pub mod operands;
+pub mod optimizer;
+pub mod verify;
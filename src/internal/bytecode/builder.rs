@@ -1,13 +1,17 @@
 use std::cell::{Cell, RefCell};
 use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 use indexmap::IndexMap;
 
 use super::opcode::symbolic::*;
 use super::opcode::{self as op, Instruction, Opcode};
 use super::operands::{Operand, Width};
-use crate::internal::object::{Any, ClassDescriptor, FunctionDescriptor, Ptr, Str};
-use crate::internal::value::constant::{Constant, NonNaNFloat};
+#[cfg(feature = "bigint")]
+use crate::internal::object::bigint::BigInt;
+use crate::internal::object::int::BoxedInt;
+use crate::internal::object::{Any, Bytes, ClassDescriptor, FunctionDescriptor, Ptr, Str};
+use crate::internal::value::constant::{Constant, JumpTable, KwNames, NonNaNFloat};
 use crate::span::Span;
 
 #[derive(Default)]
@@ -99,6 +103,13 @@ pub struct LoopHeader {
   offset: Cell<Option<usize>>,
 }
 
+/// The cases of a `switch` instruction, see `emit_switch`.
+pub struct SwitchTable {
+  referrer_offset: usize,
+  index: op::Constant,
+  cases: JumpTable,
+}
+
 impl BytecodeBuilder {
   pub fn new() -> Self {
     Self {
@@ -167,7 +178,13 @@ impl BytecodeBuilder {
     )
   }
 
-  pub fn emit_jump_if_false(&mut self, label: &impl Label, span: impl Into<Span>) {
+  /// Emit a jump which is taken if `cond` is falsey. See `emit_jump`.
+  pub fn emit_jump_if_false(
+    &mut self,
+    label: &impl Label,
+    cond: op::Register,
+    span: impl Into<Span>,
+  ) {
     assert!(
       !label.is_used(),
       "more than one instruction refers to label {}",
@@ -182,6 +199,7 @@ impl BytecodeBuilder {
     self.write(
       JumpIfFalse {
         offset: op::Offset(offset.0),
+        cond,
       },
       span.into(),
     )
@@ -211,12 +229,61 @@ impl BytecodeBuilder {
     )
   }
 
+  /// Emit a jump to one of the cases of a jump table, selected by the string
+  /// in `src`. If `src` is not a string, or there is no case for it, execution
+  /// continues at the next instruction.
+  ///
+  /// Cases are added using `bind_switch_case`, and the table must be written
+  /// to the constant pool using `finish_switch`.
+  pub fn emit_switch(&mut self, src: op::Register, span: impl Into<Span>) -> SwitchTable {
+    self.unbound_jumps += 1;
+    let referrer_offset = self.bytecode.len();
+    let index = self.constant_pool_builder().reserve();
+    self.write(Switch { src, table: index }, span.into());
+    SwitchTable {
+      referrer_offset,
+      index,
+      cases: JumpTable::new(),
+    }
+  }
+
+  /// Make the case for `key` in `table` jump to the current offset.
+  pub fn bind_switch_case(&mut self, table: &mut SwitchTable, key: impl Into<String>) {
+    let offset = op::Offset((self.bytecode.len() - table.referrer_offset) as u32);
+    if !JumpTable::insert(&mut table.cases, key, offset) {
+      panic!("jump table has more than one case for the same key");
+    }
+  }
+
+  pub fn finish_switch(&mut self, table: SwitchTable) {
+    self
+      .constant_pool_builder()
+      .commit(table.cases, table.index);
+    self.unbound_jumps -= 1;
+  }
+
+  /// Whether any jump was emitted to a label which is not bound yet.
+  pub fn has_unbound_jumps(&self) -> bool {
+    self.unbound_jumps > 0
+  }
+
   pub fn constant_pool_builder(&mut self) -> &mut ConstantPoolBuilder {
     &mut self.constant_pool_builder
   }
 
   pub fn finish(self) -> (Vec<u8>, Vec<Constant>) {
-    (self.bytecode, self.constant_pool_builder.constants)
+    let (bytecode, constants, _) = self.finish_with_spans();
+    (bytecode, constants)
+  }
+
+  /// Like `finish`, but also returns the span of each emitted instruction,
+  /// in the order they appear in the bytecode.
+  pub fn finish_with_spans(self) -> (Vec<u8>, Vec<Constant>, Vec<Span>) {
+    (
+      self.bytecode,
+      self.constant_pool_builder.constants,
+      self.spans,
+    )
   }
 
   fn patch_jump(&mut self, referrer_offset: usize, relative_offset: op::Offset) {
@@ -301,6 +368,7 @@ pub struct ConstantPoolBuilder {
   constants: Vec<Constant>,
   ptr_map: IndexMap<PtrHash, usize>,
   float_map: IndexMap<NonNaNFloat, usize>,
+  int_map: IndexMap<i64, usize>,
 }
 
 impl ConstantPoolBuilder {
@@ -309,6 +377,7 @@ impl ConstantPoolBuilder {
       constants: Vec::new(),
       ptr_map: IndexMap::new(),
       float_map: IndexMap::new(),
+      int_map: IndexMap::new(),
     }
   }
 
@@ -361,6 +430,9 @@ macro_rules! insert_constant_object {
 insert_constant_object!(Str, String);
 insert_constant_object!(FunctionDescriptor, Function);
 insert_constant_object!(ClassDescriptor, Class);
+insert_constant_object!(Bytes, Bytes);
+#[cfg(feature = "bigint")]
+insert_constant_object!(BigInt, BigInt);
 
 impl private::Sealed for NonNaNFloat {}
 impl InsertConstant for NonNaNFloat {
@@ -380,6 +452,24 @@ impl InsertConstant for NonNaNFloat {
   }
 }
 
+impl private::Sealed for Ptr<BoxedInt> {}
+impl InsertConstant for Ptr<BoxedInt> {
+  fn insert(self, builder: &mut ConstantPoolBuilder) -> op::Constant {
+    let index = if let Some(index) = builder.int_map.get(&self.value()).copied() {
+      index
+    } else {
+      let index = builder.constants.len();
+      builder.int_map.insert(self.value(), index);
+      builder.constants.push(Constant::Int(self));
+      index
+    };
+    op::Constant(index as u32)
+  }
+  fn insert_at(self, builder: &mut ConstantPoolBuilder, constant: op::Constant) {
+    builder.constants[constant.index()] = Constant::Int(self);
+  }
+}
+
 impl private::Sealed for op::Offset {}
 impl InsertConstant for op::Offset {
   fn insert(self, builder: &mut ConstantPoolBuilder) -> op::Constant {
@@ -393,5 +483,31 @@ impl InsertConstant for op::Offset {
   }
 }
 
+impl private::Sealed for JumpTable {}
+impl InsertConstant for JumpTable {
+  fn insert(self, builder: &mut ConstantPoolBuilder) -> op::Constant {
+    let index = op::Constant(builder.constants.len() as u32);
+    builder.constants.push(Constant::JumpTable(Rc::new(self)));
+    index
+  }
+
+  fn insert_at(self, builder: &mut ConstantPoolBuilder, constant: op::Constant) {
+    builder.constants[constant.index()] = Constant::JumpTable(Rc::new(self));
+  }
+}
+
+impl private::Sealed for KwNames {}
+impl InsertConstant for KwNames {
+  fn insert(self, builder: &mut ConstantPoolBuilder) -> op::Constant {
+    let index = op::Constant(builder.constants.len() as u32);
+    builder.constants.push(Constant::KwNames(Rc::new(self)));
+    index
+  }
+
+  fn insert_at(self, builder: &mut ConstantPoolBuilder, constant: op::Constant) {
+    builder.constants[constant.index()] = Constant::KwNames(Rc::new(self));
+  }
+}
+
 #[cfg(all(test, not(feature = "__miri")))]
 mod tests;
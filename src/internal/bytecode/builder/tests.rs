@@ -6,26 +6,32 @@ use crate::internal::bytecode::disasm::Disassembly;
 fn basic_emit() {
   let mut builder = BytecodeBuilder::new();
 
-  builder.emit(LoadSmi { value: op::Smi(10) }, 0..0);
-  builder.emit(Store {
-    reg: op::Register(0) 
+  builder.emit(LoadSmi {
+    dst: op::Register(0),
+    value: op::Smi(10),
+  }, 0..0);
+  builder.emit(LoadSmi {
+    dst: op::Register(1),
+    value: op::Smi(5),
   }, 0..0);
-  builder.emit(LoadSmi { value: op::Smi(5) }, 0..0);
   builder.emit(Add {
+    dst: op::Register(0),
     lhs: op::Register(0),
+    rhs: op::Register(1),
+  }, 0..0);
+  builder.emit(Print {
+    src: op::Register(0),
   }, 0..0);
-  builder.emit(Print, 0..0);
 
   let (bytecode, constants) = builder.finish();
 
   assert_eq!(
     bytecode,
     [
-      Opcode::LoadSmi as u8, 10i8.to_le_bytes()[0],
-      Opcode::Store as u8, /*register*/ 0,
-      Opcode::LoadSmi as u8, 5i8.to_le_bytes()[0],
-      Opcode::Add as u8, /*lhs*/ 0,
-      Opcode::Print as u8,
+      Opcode::LoadSmi as u8, /*dst*/ 0, 10i8.to_le_bytes()[0],
+      Opcode::LoadSmi as u8, /*dst*/ 1, 5i8.to_le_bytes()[0],
+      Opcode::Add as u8, /*dst*/ 0, /*lhs*/ 0, /*rhs*/ 1,
+      Opcode::Print as u8, /*src*/ 0,
     ],
   );
 
@@ -40,18 +46,18 @@ fn emit_constant() {
   let a = builder.constant_pool_builder().insert(NonNaNFloat::from(10.0));
   let b = builder.constant_pool_builder().insert(NonNaNFloat::from(5.0));
   let c = builder.constant_pool_builder().insert(NonNaNFloat::from(10.0));
-  builder.emit(LoadConst { idx: a }, 0..0);
-  builder.emit(LoadConst { idx: b }, 0..0);
-  builder.emit(LoadConst { idx: c }, 0..0);
+  builder.emit(LoadConst { dst: op::Register(0), idx: a }, 0..0);
+  builder.emit(LoadConst { dst: op::Register(1), idx: b }, 0..0);
+  builder.emit(LoadConst { dst: op::Register(2), idx: c }, 0..0);
 
   let (bytecode, constants) = builder.finish();
 
   assert_eq!(
     bytecode,
     [
-      Opcode::LoadConst as u8, /*index*/ 0,
-      Opcode::LoadConst as u8, /*index*/ 1,
-      Opcode::LoadConst as u8, /*index*/ 0,
+      Opcode::LoadConst as u8, /*dst*/ 0, /*index*/ 0,
+      Opcode::LoadConst as u8, /*dst*/ 1, /*index*/ 1,
+      Opcode::LoadConst as u8, /*dst*/ 2, /*index*/ 0,
     ],
   );
 
@@ -77,7 +83,7 @@ fn emit_forward_jump_8bit() {
   builder.emit(Nop, 0..0);
   builder.emit(Nop, 0..0);
   builder.bind_label(test);
-  builder.emit(Return, 0..0);
+  builder.emit(Return { src: op::Register(0) }, 0..0);
 
   let (bytecode, constants ) = builder.finish();
 
@@ -92,7 +98,7 @@ fn emit_forward_jump_8bit() {
       Opcode::Nop as u8,
       Opcode::Nop as u8,
       Opcode::Nop as u8,
-      Opcode::Return as u8,
+      Opcode::Return as u8, /*src*/ 0,
     ],
   );
   
@@ -109,13 +115,18 @@ fn emit_forward_jump_8bit_overflow() {
     builder.emit(Nop, 0..0);
   }
   builder.bind_label(test);
-  builder.emit(Return, 0..0);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
 
   let (bytecode, constants) = builder.finish();
 
   assert_eq!(bytecode[..2], [Opcode::JumpConst as u8, /* index */ 0],);
   assert!(bytecode[2..256].iter().all(|v| *v == Opcode::Nop as u8));
-  assert_eq!(bytecode[256..], [Opcode::Return as u8]);
+  assert_eq!(bytecode[256..], [Opcode::Return as u8, 0]);
   assert_eq!(constants.last().unwrap().as_offset().unwrap().0, 256);
 }
 
@@ -138,7 +149,12 @@ fn emit_forward_jump_16bit() {
   }
   let jump_target = builder.bytecode.len() as u16;
   builder.bind_label(test);
-  builder.emit(Return, 0..0);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
 
   let (bytecode, _) = builder.finish();
 
@@ -155,7 +171,7 @@ fn emit_forward_jump_16bit() {
   assert!(bytecode[jump_len..jump_len + num_nops]
     .iter()
     .all(|v| *v == Opcode::Nop as u8));
-  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8]);
+  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8, 0]);
 }
 
 #[test]
@@ -177,7 +193,12 @@ fn emit_forward_jump_16bit_overflow() {
   }
   let jump_target = builder.bytecode.len() as u32;
   builder.bind_label(test);
-  builder.emit(Return, 0..0);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
 
   let (bytecode, constants) = builder.finish();
 
@@ -194,7 +215,7 @@ fn emit_forward_jump_16bit_overflow() {
   assert!(bytecode[jump_len..jump_len + num_nops]
     .iter()
     .all(|v| *v == Opcode::Nop as u8));
-  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8]);
+  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8, 0]);
   assert_eq!(
     constants.last().unwrap().as_offset().unwrap().0,
     jump_target
@@ -220,7 +241,12 @@ fn emit_forward_jump_32bit() {
   }
   let jump_target = builder.bytecode.len() as u32;
   builder.bind_label(test);
-  builder.emit(Return, 0..0);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
 
   let (bytecode, _) = builder.finish();
 
@@ -239,7 +265,7 @@ fn emit_forward_jump_32bit() {
   assert!(bytecode[jump_len..jump_len + num_nops]
     .iter()
     .all(|v| *v == Opcode::Nop as u8));
-  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8]);
+  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8, 0]);
 }
 
 #[test]
@@ -253,7 +279,12 @@ fn emit_jump_loop() {
   builder.emit(Nop, 0..0);
   builder.emit(Nop, 0..0);
   builder.emit_jump_loop(&start, 0..0);
-  builder.emit(Return, 0..0);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
 
   let (bytecode, constants) = builder.finish();
 
@@ -268,6 +299,7 @@ fn emit_jump_loop() {
       Opcode::JumpLoop as u8,
       /* offset */ (4 - 1),
       Opcode::Return as u8,
+      /*src*/ 0,
     ]
   );
 
@@ -286,7 +318,7 @@ fn emit_multi_label() {
   }
   builder.emit(Nop, 0..0);
   builder.bind_label(labels);
-  builder.emit(Return, 0..0);
+  builder.emit(Return { src: op::Register(0) }, 0..0);
 
   let (bytecode, constants) = builder.finish();
 
@@ -302,9 +334,188 @@ fn emit_multi_label() {
       Opcode::Nop as u8,
       Opcode::Jump as u8, /*offset*/ 3,
       Opcode::Nop as u8,
-      Opcode::Return as u8,
+      Opcode::Return as u8, /*src*/ 0,
+    ]
+  );
+
+  assert_snapshot!(Disassembly::new(&bytecode, &constants, 0, true).to_string());
+}
+
+#[rustfmt::skip]
+#[test]
+fn emit_switch() {
+  let mut builder = BytecodeBuilder::new();
+
+  let mut table = builder.emit_switch(op::Register(0), 0..0);
+  assert!(builder.has_unbound_jumps());
+  builder.emit(Nop, 0..0);
+  builder.bind_switch_case(&mut table, "a");
+  builder.emit(Nop, 0..0);
+  builder.bind_switch_case(&mut table, "b");
+  builder.finish_switch(table);
+  assert!(!builder.has_unbound_jumps());
+  builder.emit(Return { src: op::Register(0) }, 0..0);
+
+  let (bytecode, constants) = builder.finish();
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::Switch as u8, /*src*/ 0, /*table*/ 0,
+      Opcode::Nop as u8,
+      Opcode::Nop as u8,
+      Opcode::Return as u8, /*src*/ 0,
     ]
   );
+  let table = constants[0].as_jump_table().unwrap();
+  assert_eq!(table.get("a"), Some(op::Offset(4)));
+  assert_eq!(table.get("b"), Some(op::Offset(5)));
+  assert_eq!(table.get("c"), None);
 
   assert_snapshot!(Disassembly::new(&bytecode, &constants, 0, true).to_string());
 }
+
+#[test]
+fn emit_forward_jump_8bit_max() {
+  let mut builder = BytecodeBuilder::new();
+
+  let test = builder.label("test");
+  builder.emit_jump(&test, 0..0);
+  for _ in 0..(255 - 2) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
+
+  let (bytecode, constants) = builder.finish();
+
+  // the largest offset which still fits in the jump instruction
+  assert_eq!(bytecode[..2], [Opcode::Jump as u8, /* offset */ 255]);
+  assert_eq!(bytecode[255..], [Opcode::Return as u8, 0]);
+  assert!(matches!(constants[..], [Constant::Reserved]));
+}
+
+#[test]
+fn emit_forward_jump_16bit_max() {
+  let mut builder = BytecodeBuilder::new();
+
+  // fill constant pool to force 16-bit index
+  for _ in 0..u8::MAX as u16 + 1 {
+    let _ = builder.constant_pool_builder().insert(op::Offset(0));
+  }
+
+  let test = builder.label("test");
+  builder.emit_jump(&test, 0..0);
+
+  let jump_len = 4;
+  let num_nops = 65535 - jump_len;
+  for _ in 0..num_nops {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(
+    Return {
+      src: op::Register(0),
+    },
+    0..0,
+  );
+
+  let (bytecode, _) = builder.finish();
+
+  assert_eq!(
+    bytecode[..jump_len],
+    [
+      Opcode::Wide16 as u8,
+      Opcode::Jump as u8,
+      // offset:
+      0xFF,
+      0xFF,
+    ],
+  );
+  assert_eq!(bytecode[jump_len + num_nops..], [Opcode::Return as u8, 0]);
+}
+
+#[rustfmt::skip]
+#[test]
+fn emit_jump_if_false_8bit_overflow() {
+  let mut builder = BytecodeBuilder::new();
+
+  let test = builder.label("test");
+  builder.emit_jump_if_false(&test, op::Register(5), 0..0);
+  for _ in 0..(256 - 3) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(Return { src: op::Register(0) }, 0..0);
+
+  let (bytecode, constants) = builder.finish();
+
+  // the opcode changes, but the condition stays the same
+  assert_eq!(
+    bytecode[..3],
+    [Opcode::JumpIfFalseConst as u8, /*index*/ 0, /*cond*/ 5],
+  );
+  assert_eq!(bytecode[256..], [Opcode::Return as u8, 0]);
+  assert_eq!(constants[0].as_offset().unwrap().0, 256);
+}
+
+#[rustfmt::skip]
+#[test]
+fn emit_jump_if_false_wide_register() {
+  let mut builder = BytecodeBuilder::new();
+
+  // the register widens the instruction, which leaves room for a larger offset
+  // even though the reserved constant index fits in a byte
+  let test = builder.label("test");
+  builder.emit_jump_if_false(&test, op::Register(256), 0..0);
+  for _ in 0..300 {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(Return { src: op::Register(0) }, 0..0);
+
+  let (bytecode, constants) = builder.finish();
+
+  let offset = (6u16 + 300).to_le_bytes();
+  assert_eq!(
+    bytecode[..6],
+    [
+      Opcode::Wide16 as u8, Opcode::JumpIfFalse as u8,
+      /*offset*/ offset[0], offset[1],
+      /*cond*/ 0, 1,
+    ],
+  );
+  assert_eq!(bytecode[306..], [Opcode::Return as u8, 0]);
+  assert!(matches!(constants[..], [Constant::Reserved]));
+}
+
+#[test]
+fn emit_jump_loop_16bit() {
+  let mut builder = BytecodeBuilder::new();
+
+  let start = builder.loop_header();
+  builder.bind_loop_header(&start);
+  for _ in 0..256 {
+    builder.emit(Nop, 0..0);
+  }
+  builder.emit_jump_loop(&start, 0..0);
+
+  let (bytecode, constants) = builder.finish();
+
+  assert!(constants.is_empty());
+  assert_eq!(
+    bytecode[256..],
+    [
+      Opcode::Wide16 as u8,
+      Opcode::JumpLoop as u8,
+      // offset:
+      0x00,
+      0x01,
+    ]
+  );
+}
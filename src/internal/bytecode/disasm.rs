@@ -2,6 +2,7 @@ use std::fmt::Display;
 
 use super::opcode::{symbolic, Width};
 use crate::internal::value::constant::Constant;
+use crate::span::Span;
 use crate::util::{num_digits, JoinIter};
 
 pub struct Instruction<'a> {
@@ -46,6 +47,7 @@ pub struct Disassembly<'a> {
   constants: &'a [Constant],
   padding: usize,
   offsets: bool,
+  source: Option<SourceMap<'a>>,
 }
 
 impl<'a> Disassembly<'a> {
@@ -55,8 +57,62 @@ impl<'a> Disassembly<'a> {
       constants,
       padding,
       offsets,
+      source: None,
     }
   }
+
+  /// Interleave the disassembly with lines from `source`.
+  ///
+  /// `spans` must hold the span of each instruction in `bytecode`, in order.
+  /// Whenever the source line of the current instruction changes, that line is
+  /// printed above it, similar to `objdump -S`.
+  pub fn with_source(mut self, spans: &'a [Span], source: &'a str) -> Self {
+    self.source = Some(SourceMap::new(spans, source));
+    self
+  }
+}
+
+struct SourceMap<'a> {
+  spans: &'a [Span],
+  source: &'a str,
+  /// Byte offset of the start of each line in `source`.
+  line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+  fn new(spans: &'a [Span], source: &'a str) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    Self {
+      spans,
+      source,
+      line_starts,
+    }
+  }
+
+  /// Returns the 0-based index of the line containing the start of the span of
+  /// the `n`th instruction, if it is known.
+  fn line_of(&self, n: usize) -> Option<usize> {
+    let span = self.spans.get(n)?;
+    if span.is_empty() || span.start > self.source.len() {
+      return None;
+    }
+    match self.line_starts.binary_search(&span.start) {
+      Ok(line) => Some(line),
+      Err(next_line) => Some(next_line - 1),
+    }
+  }
+
+  fn line(&self, line: usize) -> &'a str {
+    let start = self.line_starts[line];
+    let end = self
+      .line_starts
+      .get(line + 1)
+      .map(|end| end - 1)
+      .unwrap_or(self.source.len());
+    self.source[start..end].trim_end()
+  }
 }
 
 impl<'a> Display for Disassembly<'a> {
@@ -64,7 +120,25 @@ impl<'a> Display for Disassembly<'a> {
     let mut current_remainder = self.bytecode;
     let mut offset = 0;
     let offset_width = num_digits(self.bytecode.len());
+    let mut index = 0;
+    let mut current_line = None;
     while !current_remainder.is_empty() {
+      if let Some(source) = &self.source {
+        let line = source.line_of(index);
+        if let Some(line) = line.filter(|_| line != current_line) {
+          writeln!(
+            f,
+            "{:padding$}# {}: {}",
+            "",
+            line + 1,
+            source.line(line),
+            padding = self.padding
+          )?;
+          current_line = Some(line);
+        }
+      }
+      index += 1;
+
       let (instruction, remainder) = symbolic::decode(current_remainder).ok_or(std::fmt::Error)?;
       let size = (remainder.as_ptr() as usize) - (current_remainder.as_ptr() as usize);
       current_remainder = remainder;
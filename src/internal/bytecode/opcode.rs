@@ -10,73 +10,110 @@ pub use super::operands::Width;
 // TODO: update `docs/emit.md` instruction list once this stops changing
 // TODO: decoding should return strongly typed operands
 
-instructions! {
-  patch_registers, symbolic, decode, Opcode;
-  Nop,
-  Wide16,
-  Wide32,
-  Load(reg: Register),
-  Store(reg: Register),
-  LoadConst(idx: Constant),
-  LoadUpvalue(idx: Upvalue),
-  StoreUpvalue(idx: Upvalue),
-  LoadModuleVar(idx: ModuleVar),
-  StoreModuleVar(idx: ModuleVar),
-  LoadGlobal(name: Constant),
-  StoreGlobal(name: Constant),
-  LoadField(name: Constant),
-  LoadFieldOpt(name: Constant),
-  StoreField(obj: Register, name: Constant),
-  LoadIndex(obj: Register),
-  LoadIndexOpt(obj: Register),
-  StoreIndex(obj: Register, key: Register),
-  LoadSelf,
-  LoadSuper,
-  LoadNone,
-  LoadTrue,
-  LoadFalse,
-  LoadSmi(value: Smi),
-  MakeFn(desc: Constant),
-  MakeClass(desc: Constant),
-  MakeClassDerived(desc: Constant),
-  MakeDataClass(desc: Constant, parts: Register),
-  MakeDataClassDerived(desc: Constant, parts: Register),
-  // TODO: MakeListConst / MakeTableConst for statically known values
-  MakeList(start: Register, count: Count),
-  MakeListEmpty,
-  MakeTable(start: Register, count: Count),
-  MakeTableEmpty,
-  Jump(offset: Offset),
-  JumpConst(offset: Constant),
-  JumpLoop(offset: Offset),
-  JumpIfFalse(offset: Offset),
-  JumpIfFalseConst(offset: Constant),
-  Add(lhs: Register),
-  Sub(lhs: Register),
-  Mul(lhs: Register),
-  Div(lhs: Register),
-  Rem(lhs: Register),
-  Pow(lhs: Register),
-  Inv,
-  Not,
-  CmpEq(lhs: Register),
-  CmpNe(lhs: Register),
-  CmpGt(lhs: Register),
-  CmpGe(lhs: Register),
-  CmpLt(lhs: Register),
-  CmpLe(lhs: Register),
-  CmpType(lhs: Register),
-  Contains(lhs: Register),
-  IsNone,
-  Print,
-  PrintN(start: Register, count: Count),
-  Call(callee: Register, args: Count),
-  Call0,
-  Import(path: Constant),
-  FinalizeModule,
-  Return,
-  Yield,
+/// Invokes `$m!` with the list of all instructions and their operands,
+/// optionally preceded by `$args` and a `;`.
+///
+/// Each instruction reads its operands before writing to `dst`, so `dst` may
+/// be the same register as any of them.
+///
+/// A fused instruction is followed by `= [First(..), Second(..)]`, which
+/// names the pair of instructions it replaces and maps its operands to theirs.
+/// It may be followed by `if a == b`, if two operands of the pair must be the
+/// same for them to be fused.
+///
+/// This is the single source of truth for the instruction set, anything which
+/// has to be generated per instruction should be driven by it.
+macro_rules! for_each_instruction {
+  ($m:ident $(, $($args:tt)*)?) => {
+    $m! {
+      $($($args)*;)?
+      Nop,
+      Wide16,
+      Wide32,
+      Mov(dst: Register, src: Register),
+      LoadConst(dst: Register, idx: Constant),
+      LoadUpvalue(dst: Register, idx: Upvalue),
+      StoreUpvalue(idx: Upvalue, src: Register),
+      LoadModuleVar(dst: Register, idx: ModuleVar),
+      StoreModuleVar(idx: ModuleVar, src: Register),
+      LoadGlobal(dst: Register, name: Constant),
+      StoreGlobal(name: Constant, src: Register),
+      LoadField(dst: Register, obj: Register, name: Constant),
+      LoadFieldOpt(dst: Register, obj: Register, name: Constant),
+      StoreField(obj: Register, name: Constant, src: Register),
+      LoadIndex(dst: Register, obj: Register, key: Register),
+      LoadIndexOpt(dst: Register, obj: Register, key: Register),
+      StoreIndex(obj: Register, key: Register, src: Register),
+      LoadSelf(dst: Register),
+      LoadSuper(dst: Register),
+      LoadNone(dst: Register),
+      LoadTrue(dst: Register),
+      LoadFalse(dst: Register),
+      LoadSmi(dst: Register, value: Smi),
+      MakeFn(dst: Register, desc: Constant),
+      MakeClass(dst: Register, desc: Constant),
+      MakeClassDerived(dst: Register, desc: Constant, parent: Register),
+      MakeDataClass(dst: Register, desc: Constant, parts: Register),
+      MakeDataClassDerived(dst: Register, desc: Constant, parts: Register),
+      // TODO: MakeListConst / MakeTableConst for statically known values
+      MakeList(dst: Register, start: Register, count: Count),
+      MakeListEmpty(dst: Register),
+      MakeTuple(dst: Register, start: Register, count: Count),
+      MakeTupleEmpty(dst: Register),
+      Unpack(start: Register, count: Count, src: Register),
+      MakeTable(dst: Register, start: Register, count: Count),
+      MakeTableEmpty(dst: Register),
+      MakeBytes(dst: Register, data: Constant),
+      Jump(offset: Offset),
+      JumpConst(offset: Constant),
+      JumpLoop(offset: Offset),
+      JumpIfFalse(offset: Offset, cond: Register),
+      JumpIfFalseConst(offset: Constant, cond: Register),
+      Switch(src: Register, table: Constant),
+      Add(dst: Register, lhs: Register, rhs: Register),
+      Sub(dst: Register, lhs: Register, rhs: Register),
+      Mul(dst: Register, lhs: Register, rhs: Register),
+      Div(dst: Register, lhs: Register, rhs: Register),
+      FloorDiv(dst: Register, lhs: Register, rhs: Register),
+      Rem(dst: Register, lhs: Register, rhs: Register),
+      Pow(dst: Register, lhs: Register, rhs: Register),
+      Inv(dst: Register, src: Register),
+      Not(dst: Register, src: Register),
+      CmpEq(dst: Register, lhs: Register, rhs: Register),
+      CmpNe(dst: Register, lhs: Register, rhs: Register),
+      CmpGt(dst: Register, lhs: Register, rhs: Register),
+      CmpGe(dst: Register, lhs: Register, rhs: Register),
+      CmpLt(dst: Register, lhs: Register, rhs: Register),
+      CmpLe(dst: Register, lhs: Register, rhs: Register),
+      CmpType(dst: Register, lhs: Register, rhs: Register),
+      Contains(dst: Register, lhs: Register, rhs: Register),
+      IsNone(dst: Register, src: Register),
+      AppendStr(dst: Register, lhs: Register, rhs: Register),
+      BuildStr(dst: Register, src: Register),
+      Print(src: Register),
+      PrintN(start: Register, count: Count),
+      Call(dst: Register, callee: Register, args: Count),
+      CallKw(dst: Register, callee: Register, args: Count, names: Constant),
+      Import(dst: Register, path: Constant),
+      FinalizeModule(dst: Register),
+      Return(src: Register),
+      Yield(src: Register),
+      Raise(src: Register),
+      Defer(callee: Register, args: Count),
+      HasDeferred(dst: Register),
+      CallDeferred(dst: Register),
+      CheckParam(param: Register, name: Constant, hint: Constant),
+      // Fused instructions, which do the same thing as the two instructions in
+      // brackets. These are only emitted by the optimizer.
+      CmpLtJumpIfFalse(dst: Register, lhs: Register, rhs: Register, offset: Offset)
+        = [CmpLt(dst, lhs, rhs), JumpIfFalse(offset, cond)] if cond == dst,
+    }
+  };
 }
+#[cfg(feature = "threaded-dispatch")]
+pub(crate) use for_each_instruction;
+
+for_each_instruction!(instructions, patch_registers, symbolic, decode, Opcode);
 
 operand_type!(Register, u32, "r{v}");
 operand_type!(Constant, u32, "[{v}]");
@@ -174,7 +211,14 @@ pub trait Instruction: disasm::Disassemble + private::Sealed {
   /// This writes the prefix, opcode, and operands.
   ///
   /// Only writes the prefix if the operands overflow.
-  fn encode(&self, buf: &mut Vec<u8>);
+  fn encode(&self, buf: &mut Vec<u8>) {
+    self.encode_wide(buf, Width::Normal)
+  }
+
+  /// Encode the instruction into `buf` with operands of at least `width`.
+  ///
+  /// The operands are still widened past `width` if they don't fit in it.
+  fn encode_wide(&self, buf: &mut Vec<u8>, width: Width);
 
   fn is_jump(&self) -> bool {
     matches!(
@@ -184,15 +228,121 @@ pub trait Instruction: disasm::Disassemble + private::Sealed {
         | Opcode::JumpLoop
         | Opcode::JumpIfFalse
         | Opcode::JumpIfFalseConst
+        | Opcode::Switch
+        | Opcode::CmpLtJumpIfFalse
     )
   }
 }
 
+/// Receives the operands of an instruction from
+/// [`AnyInstruction::visit_operands`][symbolic::AnyInstruction::visit_operands].
+#[allow(unused_variables)]
+pub trait OperandVisitor {
+  fn visit_register(&mut self, register: Register) {}
+  fn visit_constant(&mut self, constant: Constant) {}
+  fn visit_upvalue(&mut self, upvalue: Upvalue) {}
+  fn visit_module_var(&mut self, module_var: ModuleVar) {}
+  fn visit_offset(&mut self, offset: Offset) {}
+  fn visit_smi(&mut self, smi: Smi) {}
+  fn visit_count(&mut self, count: Count) {}
+}
+
+/// Why an instruction failed [`check_encoding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingError {
+  /// The encoded instruction has a different length than its width implies.
+  Length {
+    width: Width,
+    expected: usize,
+    actual: usize,
+  },
+  /// The encoded instruction could not be decoded.
+  Decode { width: Width },
+  /// The instruction was decoded, but it is not the one which was encoded.
+  Mismatch { width: Width },
+  /// Encoding the decoded instruction again yielded different bytes.
+  Reencode { width: Width },
+}
+
+impl std::fmt::Display for EncodingError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      EncodingError::Length {
+        width,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "{width:?} instruction is {actual} bytes long, expected {expected}"
+      ),
+      EncodingError::Decode { width } => write!(f, "failed to decode {width:?} instruction"),
+      EncodingError::Mismatch { width } => {
+        write!(
+          f,
+          "{width:?} instruction decoded to a different instruction"
+        )
+      }
+      EncodingError::Reencode { width } => {
+        write!(
+          f,
+          "{width:?} instruction encoded differently the second time"
+        )
+      }
+    }
+  }
+}
+
+impl std::error::Error for EncodingError {}
+
+/// Check that `instruction` is decoded back to itself after being encoded at
+/// every width which can hold its operands.
+///
+/// Anything which produces bytecode without going through the builder can use
+/// this to make sure its instructions will be read the way they were written.
+pub fn check_encoding(instruction: &symbolic::AnyInstruction) -> Result<(), EncodingError> {
+  let opcode = instruction.opcode();
+  for width in Width::ALL {
+    if width < instruction.width() {
+      continue;
+    }
+
+    let mut buf = Vec::new();
+    instruction.encode_wide(&mut buf, width);
+
+    let prefix = !width.is_normal() as usize;
+    let expected = prefix + 1 + opcode.operand_count() * width.size();
+    if buf.len() != expected {
+      return Err(EncodingError::Length {
+        width,
+        expected,
+        actual: buf.len(),
+      });
+    }
+
+    let Some((decoded, remainder)) = symbolic::AnyInstruction::decode(&buf) else {
+      return Err(EncodingError::Decode { width });
+    };
+    if !remainder.is_empty() {
+      return Err(EncodingError::Decode { width });
+    }
+    if decoded != *instruction {
+      return Err(EncodingError::Mismatch { width });
+    }
+
+    let mut reencoded = Vec::new();
+    decoded.encode_wide(&mut reencoded, width);
+    if reencoded != buf {
+      return Err(EncodingError::Reencode { width });
+    }
+  }
+  Ok(())
+}
+
 fn read_instruction(buf: &[u8]) -> Option<(Width, Opcode, &[u8])> {
   let width = Width::decode(buf);
   let (opcode, operands) = match width {
     Width::Normal => (buf[0], &buf[1..]),
-    Width::Wide16 | Width::Wide32 => (buf[1], &buf[2..]),
+    Width::Wide16 | Width::Wide32 => (*buf.get(1)?, &buf[2..]),
   };
   let opcode = Opcode::try_from(opcode).ok()?;
   Some((width, opcode, operands))
@@ -1,12 +1,12 @@
 macro_rules! __struct {
   ($name:ident) => {
     #[allow(dead_code)]
-    #[derive(Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct $name;
   };
   ($name:ident ($($operand:ident : $ty:ty),+)) => {
     #[allow(dead_code)]
-    #[derive(Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct $name {
       $(pub $operand : $ty),+
     }
@@ -44,8 +44,30 @@ macro_rules! __patch_register {
   ($width:ident, $map:ident, $buf:expr, $ty:ident) => {};
 }
 
+macro_rules! __map_constant {
+  ($f:ident, $operand:expr, Constant) => {
+    *$operand = $f(*$operand);
+  };
+  ($f:ident, $operand:expr, $ty:ident) => {};
+}
+
+macro_rules! __fuse {
+  (
+    $first:ident, $second:ident, $name:ident ($($operand:ident),+)
+    = [$a:ident $(($($a_operand:ident),+))?, $b:ident $(($($b_operand:ident),+))?]
+    $(if $lhs:ident == $rhs:ident)?
+  ) => {
+    if let (AnyInstruction::$a($a { $($($a_operand),+)? }), AnyInstruction::$b($b { $($($b_operand),+)? })) = ($first, $second) {
+      if true $(&& $lhs.0 == $rhs.0)? {
+        return Some(AnyInstruction::$name($name { $($operand),+ }));
+      }
+    }
+  };
+  ($first:ident, $second:ident, $name:ident $(($($operand:ident),+))?) => {};
+}
+
 macro_rules! instructions {
-  ($patch_registers:ident, $symbolic:ident, $decode:ident, $Opcode:ident; $($name:ident $(($($operand:ident : $ty:ident),+))?),* $(,)?) => {
+  ($patch_registers:ident, $symbolic:ident, $decode:ident, $Opcode:ident; $($name:ident $(($($operand:ident : $ty:ident),+))? $(= $fused:tt $(if $lhs:ident == $rhs:ident)?)?),* $(,)?) => {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
     #[repr(u8)]
     pub enum $Opcode {
@@ -59,6 +81,13 @@ macro_rules! instructions {
           Err(()) => panic!("illegal instruction 0x{v:X}"),
         }
       }
+
+      /// The number of operands the instruction has, not counting the prefix.
+      pub fn operand_count(&self) -> usize {
+        match self {
+          $($Opcode::$name => __count!($($($operand)+)?)),*
+        }
+      }
     }
 
     impl TryFrom<u8> for $Opcode {
@@ -91,10 +120,10 @@ macro_rules! instructions {
             $Opcode::$name
           }
           #[inline]
-          fn encode(&self, buf: &mut Vec<u8>) {
+          fn encode_wide(&self, buf: &mut Vec<u8>, width: Width) {
             let Self { $($($operand,)+)? } = *self;
             let operands = ($($($operand,)+)?);
-            let width = operands.width();
+            let width = operands.width() | width;
             width.encode(buf);
             buf.push($Opcode::$name as u8);
             operands.encode(buf, width);
@@ -121,22 +150,108 @@ macro_rules! instructions {
         }
       )*
 
-      #[allow(unused_parens)]
-      pub fn decode(buf: &[u8]) -> Option<(Box<dyn Instruction>, &[u8])> {
-        assert!(!buf.is_empty());
+      /// Any instruction, with its operands.
+      #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+      pub enum AnyInstruction {
+        $($name($name)),*
+      }
 
-        let (width, opcode, operands) = read_instruction(buf)?;
+      impl AnyInstruction {
+        #[allow(unused_parens)]
+        /// Decode the instruction at the start of `buf`, and return it together
+        /// with the rest of `buf`.
+        ///
+        /// Returns `None` if the opcode is invalid or the instruction is cut off.
+        pub fn decode(buf: &[u8]) -> Option<(AnyInstruction, &[u8])> {
+          assert!(!buf.is_empty());
 
-        match opcode {
-          $(
-            $Opcode::$name => {
-              let ($($($operand,)+)?) = <<$name as Operands>::Operands>::decode(operands, width);
-              let instruction = Box::new($name { $($($operand: <$ty>::new($operand.0),)+)? });
-              let remainder = &operands[__count!($($($operand)+)?) * width.size()..];
-              return Some((instruction, remainder));
-            }
-          )*
+          let (width, opcode, operands) = read_instruction(buf)?;
+          if operands.len() < opcode.operand_count() * width.size() {
+            return None;
+          }
+
+          match opcode {
+            $(
+              $Opcode::$name => {
+                let ($($($operand,)+)?) = <<$name as Operands>::Operands>::decode(operands, width);
+                let instruction = AnyInstruction::$name($name { $($($operand: <$ty>::new($operand.0),)+)? });
+                let remainder = &operands[__count!($($($operand)+)?) * width.size()..];
+                return Some((instruction, remainder));
+              }
+            )*
+          }
+        }
+
+        pub fn opcode(&self) -> $Opcode {
+          match self {
+            $(AnyInstruction::$name(_) => $Opcode::$name),*
+          }
         }
+
+        /// The narrowest width which can hold all of the operands.
+        pub fn width(&self) -> Width {
+          match self {
+            $(
+              #[allow(unused_variables)]
+              AnyInstruction::$name($name { $($($operand),+)? }) => ($($(*$operand,)+)?).width()
+            ),*
+          }
+        }
+
+        pub fn encode(&self, buf: &mut Vec<u8>) {
+          match self {
+            $(AnyInstruction::$name(instruction) => instruction.encode(buf)),*
+          }
+        }
+
+        /// See [`Instruction::encode_wide`].
+        pub fn encode_wide(&self, buf: &mut Vec<u8>, width: Width) {
+          match self {
+            $(AnyInstruction::$name(instruction) => instruction.encode_wide(buf, width)),*
+          }
+        }
+
+        /// Call the method of `visitor` for the type of each operand, in order.
+        pub fn visit_operands(&self, visitor: &mut impl OperandVisitor) {
+          match self {
+            $(
+              #[allow(unused_variables)]
+              AnyInstruction::$name($name { $($($operand),+)? }) => {
+                $($(::paste::paste!(visitor.[<visit_ $ty:snake>](*$operand));)+)?
+              }
+            ),*
+          }
+        }
+
+        /// Replace each operand of type `Constant` with the result of `f`.
+        pub fn map_constants(&mut self, mut f: impl FnMut(Constant) -> Constant) {
+          match self {
+            $(
+              #[allow(unused_variables)]
+              AnyInstruction::$name(instruction) => {
+                $($(__map_constant!(f, &mut instruction.$operand, $ty);)+)?
+              }
+            )*
+          }
+        }
+
+        /// If there is an instruction which does the same thing as `first`
+        /// followed by `second`, return it.
+        pub fn fuse(first: AnyInstruction, second: AnyInstruction) -> Option<AnyInstruction> {
+          $(__fuse!(first, second, $name $(($($operand),+))? $(= $fused $(if $lhs == $rhs)?)?);)*
+          None
+        }
+
+        fn into_boxed(self) -> Box<dyn Instruction> {
+          match self {
+            $(AnyInstruction::$name(instruction) => Box::new(instruction)),*
+          }
+        }
+      }
+
+      pub fn decode(buf: &[u8]) -> Option<(Box<dyn Instruction>, &[u8])> {
+        let (instruction, remainder) = AnyInstruction::decode(buf)?;
+        Some((instruction.into_boxed(), remainder))
       }
     }
 
@@ -168,7 +283,7 @@ macro_rules! instructions {
 
 macro_rules! operand_type {
   ($name:ident, $inner:ty, $fmt:literal) => {
-    #[derive(Default, Debug, Clone, Copy)]
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
     #[must_use]
     pub struct $name(pub $inner);
 
@@ -7,31 +7,39 @@ use crate::internal::bytecode::builder::BytecodeBuilder;
 fn register_patching() {
   let mut builder = BytecodeBuilder::new();
 
-  builder.emit(Load {
-    reg: Register(0) 
+  builder.emit(Mov {
+    dst: Register(0),
+    src: Register(0),
   }, 0..0);
-  builder.emit(Load {
-    reg: Register(1) 
+  builder.emit(Mov {
+    dst: Register(1),
+    src: Register(0),
   }, 0..0);
-  builder.emit(Load {
-    reg: Register(256) 
+  builder.emit(Mov {
+    dst: Register(256),
+    src: Register(0),
   }, 0..0);
-  builder.emit(Load {
-    reg: Register(65536) 
+  builder.emit(Mov {
+    dst: Register(65536),
+    src: Register(0),
   }, 0..0);
   builder.emit(MakeDataClass {
+    dst: Register(0),
     desc: Constant(0),
     parts: Register(0),
   }, 0..0);
   builder.emit(MakeDataClass {
+    dst: Register(0),
     desc: Constant(0),
     parts: Register(1),
   }, 0..0);
   builder.emit(MakeDataClass {
+    dst: Register(0),
     desc: Constant(0),
     parts: Register(256),
   }, 0..0);
   builder.emit(MakeDataClass {
+    dst: Register(0),
     desc: Constant(0),
     parts: Register(65536),
   }, 0..0);
@@ -43,14 +51,14 @@ fn register_patching() {
   assert_eq!(
     bytecode,
     [
-      Opcode::Load as u8, /*register*/ 0,
-      Opcode::Load as u8, /*register*/ 1,
-      Opcode::Wide16 as u8, Opcode::Load as u8, /*register*/ 0, 1,
-      Opcode::Wide32 as u8, Opcode::Load as u8, /*register*/ 0, 0, 1, 0,
-      Opcode::MakeDataClass as u8, /*path*/ 0, /*destination*/ 0,
-      Opcode::MakeDataClass as u8, /*path*/ 0, /*destination*/ 1,
-      Opcode::Wide16 as u8, Opcode::MakeDataClass as u8, /*path*/ 0, 0, /*destination*/ 0, 1,
-      Opcode::Wide32 as u8, Opcode::MakeDataClass as u8, /*path*/ 0, 0, 0, 0, /*destination*/ 0, 0, 1, 0,
+      Opcode::Mov as u8, /*dst*/ 0, /*src*/ 0,
+      Opcode::Mov as u8, /*dst*/ 1, /*src*/ 0,
+      Opcode::Wide16 as u8, Opcode::Mov as u8, /*dst*/ 0, 1, /*src*/ 0, 0,
+      Opcode::Wide32 as u8, Opcode::Mov as u8, /*dst*/ 0, 0, 1, 0, /*src*/ 0, 0, 0, 0,
+      Opcode::MakeDataClass as u8, /*dst*/ 0, /*desc*/ 0, /*parts*/ 0,
+      Opcode::MakeDataClass as u8, /*dst*/ 0, /*desc*/ 0, /*parts*/ 1,
+      Opcode::Wide16 as u8, Opcode::MakeDataClass as u8, /*dst*/ 0, 0, /*desc*/ 0, 0, /*parts*/ 0, 1,
+      Opcode::Wide32 as u8, Opcode::MakeDataClass as u8, /*dst*/ 0, 0, 0, 0, /*desc*/ 0, 0, 0, 0, /*parts*/ 0, 0, 1, 0,
     ]
   );
 
@@ -59,14 +67,123 @@ fn register_patching() {
   assert_eq!(
     bytecode,
     [
-      Opcode::Load as u8, /*register*/ 127,
-      Opcode::Load as u8, /*register*/ 127,
-      Opcode::Wide16 as u8, Opcode::Load as u8, /*register*/ 127, 0,
-      Opcode::Wide32 as u8, Opcode::Load as u8, /*register*/ 127, 0, 0, 0,
-      Opcode::MakeDataClass as u8, /*path*/ 0, /*destination*/ 127,
-      Opcode::MakeDataClass as u8, /*path*/ 0, /*destination*/ 127,
-      Opcode::Wide16 as u8, Opcode::MakeDataClass as u8, /*path*/ 0, 0, /*destination*/ 127, 0,
-      Opcode::Wide32 as u8, Opcode::MakeDataClass as u8, /*path*/ 0, 0, 0, 0, /*destination*/ 127, 0, 0, 0,
+      Opcode::Mov as u8, /*dst*/ 127, /*src*/ 127,
+      Opcode::Mov as u8, /*dst*/ 127, /*src*/ 127,
+      Opcode::Wide16 as u8, Opcode::Mov as u8, /*dst*/ 127, 0, /*src*/ 127, 0,
+      Opcode::Wide32 as u8, Opcode::Mov as u8, /*dst*/ 127, 0, 0, 0, /*src*/ 127, 0, 0, 0,
+      Opcode::MakeDataClass as u8, /*dst*/ 127, /*desc*/ 0, /*parts*/ 127,
+      Opcode::MakeDataClass as u8, /*dst*/ 127, /*desc*/ 0, /*parts*/ 127,
+      Opcode::Wide16 as u8, Opcode::MakeDataClass as u8, /*dst*/ 127, 0, /*desc*/ 0, 0, /*parts*/ 127, 0,
+      Opcode::Wide32 as u8, Opcode::MakeDataClass as u8, /*dst*/ 127, 0, 0, 0, /*desc*/ 0, 0, 0, 0, /*parts*/ 127, 0, 0, 0,
     ]
   );
 }
+
+/// Operand values around the boundaries of every width which fit in `width`,
+/// as little-endian bytes.
+fn boundary_operands(width: Width) -> Vec<Vec<u8>> {
+  let values: [u32; 13] = [
+    0,
+    1,
+    0x7F,
+    0x80,
+    0xFF,
+    0x100,
+    0x7FFF,
+    0x8000,
+    0xFFFF,
+    0x1_0000,
+    0x7FFF_FFFF,
+    0x8000_0000,
+    0xFFFF_FFFF,
+  ];
+  values
+    .into_iter()
+    .filter(|v| width.size() == 4 || *v < 1 << (8 * width.size()))
+    .map(|v| v.to_le_bytes()[..width.size()].to_vec())
+    .collect()
+}
+
+#[test]
+fn encode_decode_every_instruction() {
+  let opcodes = (0..=u8::MAX)
+    .filter_map(|v| Opcode::try_from(v).ok())
+    // these are prefixes, not instructions
+    .filter(|v| !matches!(v, Opcode::Wide16 | Opcode::Wide32));
+
+  for opcode in opcodes {
+    for width in Width::ALL {
+      let values = boundary_operands(width);
+      for start in 0..values.len() {
+        let mut buf = vec![];
+        width.encode(&mut buf);
+        buf.push(opcode as u8);
+        for i in 0..opcode.operand_count() {
+          // use a different value for each operand, so that mixing them up is caught
+          buf.extend_from_slice(&values[(start + i) % values.len()]);
+        }
+
+        let (instruction, remainder) = AnyInstruction::decode(&buf)
+          .unwrap_or_else(|| panic!("failed to decode {opcode:?} from {buf:?}"));
+        assert!(remainder.is_empty(), "{opcode:?} from {buf:?}");
+        assert_eq!(instruction.opcode(), opcode);
+        assert!(instruction.width() <= width, "{instruction:?}");
+
+        let mut encoded = vec![];
+        instruction.encode_wide(&mut encoded, width);
+        assert_eq!(encoded, buf, "{instruction:?} at {width:?}");
+
+        let mut narrowest = vec![];
+        instruction.encode(&mut narrowest);
+        assert!(narrowest.len() <= buf.len());
+        assert_eq!(AnyInstruction::decode(&narrowest).unwrap().0, instruction);
+
+        if let Err(e) = check_encoding(&instruction) {
+          panic!("{instruction:?}: {e}");
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn decode_truncated_instruction() {
+  for width in Width::ALL {
+    let mut buf = vec![];
+    Add {
+      dst: Register(2),
+      lhs: Register(0),
+      rhs: Register(1),
+    }
+    .encode_wide(&mut buf, width);
+
+    for len in 1..buf.len() {
+      assert!(AnyInstruction::decode(&buf[..len]).is_none(), "{width:?}");
+    }
+    assert!(AnyInstruction::decode(&buf).is_some());
+  }
+}
+
+#[test]
+fn decode_invalid_opcode() {
+  assert!(AnyInstruction::decode(&[u8::MAX]).is_none());
+  assert!(AnyInstruction::decode(&[Opcode::Wide16 as u8]).is_none());
+  assert!(AnyInstruction::decode(&[Opcode::Wide32 as u8, u8::MAX, 0, 0, 0, 0]).is_none());
+}
+
+#[test]
+fn operands_encode_into() {
+  for width in Width::ALL {
+    let operands = (Register(1), Constant(2), Smi(-3), Offset(4));
+
+    let mut expected = vec![];
+    operands.encode(&mut expected, width);
+
+    let mut buf = vec![0u8; expected.len()];
+    operands.encode_into(&mut buf, width);
+    assert_eq!(buf, expected, "{width:?}");
+
+    let decoded = <(Register, Constant, Smi, Offset)>::decode(&buf, width);
+    assert_eq!(decoded, operands);
+  }
+}
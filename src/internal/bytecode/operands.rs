@@ -11,6 +11,8 @@ pub enum Width {
 }
 
 impl Width {
+  pub const ALL: [Width; 3] = [Width::Normal, Width::Wide16, Width::Wide32];
+
   pub fn is_normal(&self) -> bool {
     matches!(self, Width::Normal)
   }
@@ -139,9 +141,9 @@ impl Operand for u32 {
   #[inline]
   fn encode_into(&self, buf: &mut [u8], width: Width) {
     match width {
-      Width::Normal => encode_into!(buf, *self, i8),
-      Width::Wide16 => encode_into!(buf, *self, i16),
-      Width::Wide32 => encode_into!(buf, *self, i32),
+      Width::Normal => encode_into!(buf, *self, u8),
+      Width::Wide16 => encode_into!(buf, *self, u16),
+      Width::Wide32 => encode_into!(buf, *self, u32),
     }
   }
 
@@ -210,7 +212,7 @@ macro_rules! impl_for_tuple {
         let mut offset = 0;
         $(
           ($ty).encode_into(&mut buf[offset..], width);
-          offset += 1;
+          offset += width.size();
         )+
         let _ = offset;
       }
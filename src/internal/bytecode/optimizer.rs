@@ -0,0 +1,750 @@
+//! Peephole optimizer which runs over the bytecode of a single function after
+//! it has been emitted and its registers have been allocated.
+//!
+//! The bytecode is decoded into a list of instructions, where each jump refers
+//! to the index of its target instead of a byte offset. This makes it possible
+//! to remove and replace instructions without having to worry about jump
+//! offsets, which are only recomputed when the instructions are encoded again.
+//!
+//! Once nothing else can be removed, common pairs of instructions are replaced
+//! by a single fused instruction, which saves a trip through the dispatch loop.
+
+use std::rc::Rc;
+
+use super::opcode::symbolic::*;
+use super::opcode::{self as op};
+use super::operands::{Operand, Width};
+use crate::internal::value::constant::{Constant, JumpTable};
+use crate::span::Span;
+
+struct Node {
+  instruction: AnyInstruction,
+  span: Span,
+  /// If this instruction is a jump, the index of the node it jumps to.
+  ///
+  /// A jump to the end of the function is represented by the number of nodes.
+  target: Option<usize>,
+  /// If this instruction is a `switch`, the key of each case and the index of
+  /// the node it jumps to.
+  cases: Vec<(String, usize)>,
+}
+
+/// Optimize the bytecode of a single function.
+///
+/// `spans` must hold the span of each instruction in `bytecode`, in order.
+/// The returned constant pool only contains constants which are still used,
+/// and never contains duplicates.
+pub fn optimize(
+  bytecode: Vec<u8>,
+  constants: Vec<Constant>,
+  spans: Vec<Span>,
+) -> (Vec<u8>, Vec<Constant>, Vec<Span>) {
+  let Some(mut nodes) = decode(&bytecode, &constants, &spans) else {
+    return (bytecode, constants, spans);
+  };
+
+  while peephole(&mut nodes) {}
+  fuse(&mut nodes);
+
+  let mut constants = compact_constants(&mut nodes, &constants);
+  let (bytecode, spans) = encode(&nodes, &mut constants);
+  (bytecode, constants, spans)
+}
+
+fn decode(bytecode: &[u8], constants: &[Constant], spans: &[Span]) -> Option<Vec<Node>> {
+  let mut nodes = Vec::with_capacity(spans.len());
+  let mut offsets = Vec::with_capacity(spans.len() + 1);
+  let mut jumps = Vec::new();
+
+  let mut remainder = bytecode;
+  let mut spans = spans.iter();
+  while !remainder.is_empty() {
+    let offset = bytecode.len() - remainder.len();
+    let (instruction, next) = AnyInstruction::decode(remainder)?;
+    remainder = next;
+
+    // the `*Const` variants are only used for offsets which do not fit in the
+    // operand, but jumps are encoded again with the full offset anyway
+    let const_offset = |idx: op::Constant| constants.get(idx.index())?.as_offset().copied();
+    let (instruction, target) = match instruction {
+      AnyInstruction::Jump(Jump { offset: relative })
+      | AnyInstruction::JumpIfFalse(JumpIfFalse {
+        offset: relative, ..
+      })
+      | AnyInstruction::CmpLtJumpIfFalse(CmpLtJumpIfFalse {
+        offset: relative, ..
+      }) => (instruction, Some(offset.checked_add(relative.value())?)),
+      AnyInstruction::JumpConst(JumpConst { offset: idx }) => (
+        AnyInstruction::Jump(Jump {
+          offset: op::Offset(0),
+        }),
+        Some(offset.checked_add(const_offset(idx)?.value())?),
+      ),
+      AnyInstruction::JumpIfFalseConst(JumpIfFalseConst { offset: idx, cond }) => (
+        AnyInstruction::JumpIfFalse(JumpIfFalse {
+          offset: op::Offset(0),
+          cond,
+        }),
+        Some(offset.checked_add(const_offset(idx)?.value())?),
+      ),
+      AnyInstruction::JumpLoop(JumpLoop { offset: relative }) => {
+        (instruction, Some(offset.checked_sub(relative.value())?))
+      }
+      instruction => (instruction, None),
+    };
+    // case targets are byte offsets until all of the nodes are decoded
+    let cases = match instruction {
+      AnyInstruction::Switch(Switch { table, .. }) => constants
+        .get(table.index())?
+        .as_jump_table()?
+        .iter()
+        .map(|(key, case)| Some((key.to_string(), offset.checked_add(case.value())?)))
+        .collect::<Option<Vec<_>>>()?,
+      _ => Vec::new(),
+    };
+
+    if let Some(target) = target {
+      jumps.push((nodes.len(), target));
+    }
+    offsets.push(offset);
+    nodes.push(Node {
+      instruction,
+      span: spans.next().copied().unwrap_or_default(),
+      target: None,
+      cases,
+    });
+  }
+  offsets.push(bytecode.len());
+
+  // a jump into the middle of an instruction means we've misread something,
+  // in which case the bytecode is left alone
+  for (index, target) in jumps {
+    nodes[index].target = Some(offsets.binary_search(&target).ok()?);
+  }
+  for node in nodes.iter_mut() {
+    for (_, target) in node.cases.iter_mut() {
+      *target = offsets.binary_search(target).ok()?;
+    }
+  }
+
+  Some(nodes)
+}
+
+/// Run a single pass over `nodes`, and return `true` if anything changed.
+fn peephole(nodes: &mut Vec<Node>) -> bool {
+  let is_target = jump_targets(nodes);
+  // `true` if the node at `i` exists and execution may only reach it from the
+  // node before it
+  let len = nodes.len();
+  let is_straight = |i: usize| i < len && !is_target[i];
+
+  let mut removed = vec![false; nodes.len()];
+  let mut changed = false;
+  let mut i = 0;
+  while i < nodes.len() {
+    let instruction = nodes[i].instruction;
+    let next = nodes.get(i + 1).map(|node| node.instruction);
+
+    if let AnyInstruction::Nop(_) = instruction {
+      removed[i] = true;
+    } else if nodes[i].target == Some(i + 1)
+      && matches!(
+        instruction,
+        AnyInstruction::Jump(_) | AnyInstruction::JumpIfFalse(_)
+      )
+    {
+      // jump over nothing
+      removed[i] = true;
+    } else if matches!(instruction, AnyInstruction::Mov(Mov { dst, src }) if dst.0 == src.0) {
+      // mov r, r
+      removed[i] = true;
+    } else if let Some(folded) = (is_straight(i + 1) && is_straight(i + 2))
+      .then(|| fold_binary(&nodes[i..i + 3]))
+      .flatten()
+    {
+      // <lhs> a; <rhs> b; <op> r, a, b
+      // `a` and `b` still hold the operands, but they are likely to be removed
+      // in the next pass
+      nodes[i + 2].instruction = folded;
+      changed = true;
+    } else if let Some(folded) = is_straight(i + 1)
+      .then(|| fold_unary(instruction, next?))
+      .flatten()
+    {
+      // <value> a; <op> r, a
+      nodes[i + 1].instruction = folded;
+      changed = true;
+    } else if let Some(retargeted) = is_straight(i + 1).then(|| coalesce_mov(nodes, i)).flatten() {
+      // <op> t, ...; mov r, t
+      nodes[i].instruction = retargeted;
+      removed[i + 1] = true;
+      i += 2;
+      changed = true;
+      continue;
+    } else if is_pure(instruction)
+      && written(instruction).is_some_and(|dst| is_dead(nodes, i + 1, dst))
+    {
+      // <op> r, ... where `r` is never read
+      removed[i] = true;
+    }
+
+    changed |= removed[i];
+    i += 1;
+  }
+
+  if changed {
+    remove(nodes, &removed);
+  }
+  changed
+}
+
+/// Replace pairs of instructions with fused instructions.
+///
+/// The second instruction of a pair must not be a jump target, because the
+/// fused instruction can only be entered from the start.
+fn fuse(nodes: &mut Vec<Node>) {
+  let is_target = jump_targets(nodes);
+
+  let mut removed = vec![false; nodes.len()];
+  let mut i = 0;
+  while i + 1 < nodes.len() {
+    let fused = (!is_target[i + 1])
+      .then(|| AnyInstruction::fuse(nodes[i].instruction, nodes[i + 1].instruction))
+      .flatten();
+    let Some(fused) = fused else {
+      i += 1;
+      continue;
+    };
+
+    // keep the span of the first part, which is the one that may fail
+    let span = nodes[i].span;
+    let target = nodes[i + 1].target;
+    nodes[i] = Node {
+      instruction: fused,
+      span,
+      target,
+      cases: Vec::new(),
+    };
+    removed[i + 1] = true;
+    i += 2;
+  }
+
+  if removed.contains(&true) {
+    remove(nodes, &removed);
+  }
+}
+
+/// Returns `true` for each node which any jump may land on, followed by
+/// whether the end of the function is a jump target.
+fn jump_targets(nodes: &[Node]) -> Vec<bool> {
+  let mut is_target = vec![false; nodes.len() + 1];
+  for node in nodes {
+    let cases = node.cases.iter().map(|(_, target)| target);
+    for &target in node.target.iter().chain(cases) {
+      is_target[target] = true;
+    }
+  }
+  is_target
+}
+
+/// Remove each node for which `removed` is `true`.
+///
+/// Jumps to a removed node will jump to the next node which is not removed.
+fn remove(nodes: &mut Vec<Node>, removed: &[bool]) {
+  let mut new_index = Vec::with_capacity(nodes.len() + 1);
+  let mut count = 0;
+  for &removed in removed {
+    new_index.push(count);
+    count += !removed as usize;
+  }
+  new_index.push(count);
+
+  let mut index = 0;
+  nodes.retain(|_| {
+    let keep = !removed[index];
+    index += 1;
+    keep
+  });
+  for node in nodes.iter_mut() {
+    let cases = node.cases.iter_mut().map(|(_, target)| target);
+    for target in node.target.iter_mut().chain(cases) {
+      *target = new_index[*target];
+    }
+  }
+}
+
+/// A value which is known at compile time.
+#[derive(Clone, Copy)]
+enum Literal {
+  None,
+  Bool(bool),
+  Int(i32),
+}
+
+impl Literal {
+  fn from_instruction(instruction: AnyInstruction) -> Option<Self> {
+    match instruction {
+      AnyInstruction::LoadNone(_) => Some(Literal::None),
+      AnyInstruction::LoadTrue(_) => Some(Literal::Bool(true)),
+      AnyInstruction::LoadFalse(_) => Some(Literal::Bool(false)),
+      AnyInstruction::LoadSmi(LoadSmi { value, .. }) => Some(Literal::Int(value.value())),
+      _ => None,
+    }
+  }
+
+  fn into_instruction(self, dst: op::Register) -> AnyInstruction {
+    match self {
+      Literal::None => AnyInstruction::LoadNone(LoadNone { dst }),
+      Literal::Bool(true) => AnyInstruction::LoadTrue(LoadTrue { dst }),
+      Literal::Bool(false) => AnyInstruction::LoadFalse(LoadFalse { dst }),
+      Literal::Int(value) => AnyInstruction::LoadSmi(LoadSmi {
+        dst,
+        value: op::Smi(value),
+      }),
+    }
+  }
+}
+
+/// Fold `<lhs> a; <rhs> b; <op> r, a, b` where both operands are integers.
+///
+/// The operands may be in either order, or even be the same register.
+fn fold_binary(nodes: &[Node]) -> Option<AnyInstruction> {
+  let [a, b, binary] = nodes else {
+    return None;
+  };
+  // the last write to `reg` determines its value
+  let int = |reg: op::Register| {
+    let node = [b, a]
+      .into_iter()
+      .find(|node| writes(node.instruction, reg))?;
+    match Literal::from_instruction(node.instruction)? {
+      Literal::Int(value) => Some(value as i64),
+      _ => None,
+    }
+  };
+
+  let (dst, lhs, rhs) = match binary.instruction {
+    AnyInstruction::Add(Add { dst, lhs, rhs })
+    | AnyInstruction::Sub(Sub { dst, lhs, rhs })
+    | AnyInstruction::Mul(Mul { dst, lhs, rhs })
+    | AnyInstruction::CmpEq(CmpEq { dst, lhs, rhs })
+    | AnyInstruction::CmpNe(CmpNe { dst, lhs, rhs })
+    | AnyInstruction::CmpGt(CmpGt { dst, lhs, rhs })
+    | AnyInstruction::CmpGe(CmpGe { dst, lhs, rhs })
+    | AnyInstruction::CmpLt(CmpLt { dst, lhs, rhs })
+    | AnyInstruction::CmpLe(CmpLe { dst, lhs, rhs }) => (dst, int(lhs)?, int(rhs)?),
+    _ => return None,
+  };
+  let value = match binary.instruction {
+    AnyInstruction::Add(_) => Literal::Int((lhs + rhs).try_into().ok()?),
+    AnyInstruction::Sub(_) => Literal::Int((lhs - rhs).try_into().ok()?),
+    AnyInstruction::Mul(_) => Literal::Int((lhs * rhs).try_into().ok()?),
+    AnyInstruction::CmpEq(_) => Literal::Bool(lhs == rhs),
+    AnyInstruction::CmpNe(_) => Literal::Bool(lhs != rhs),
+    AnyInstruction::CmpGt(_) => Literal::Bool(lhs > rhs),
+    AnyInstruction::CmpGe(_) => Literal::Bool(lhs >= rhs),
+    AnyInstruction::CmpLt(_) => Literal::Bool(lhs < rhs),
+    AnyInstruction::CmpLe(_) => Literal::Bool(lhs <= rhs),
+    _ => unreachable!(),
+  };
+  Some(value.into_instruction(dst))
+}
+
+/// Fold `<value> a; <op> r, a`.
+fn fold_unary(value: AnyInstruction, unary: AnyInstruction) -> Option<AnyInstruction> {
+  let (dst, src) = match unary {
+    AnyInstruction::Not(Not { dst, src })
+    | AnyInstruction::Inv(Inv { dst, src })
+    | AnyInstruction::IsNone(IsNone { dst, src }) => (dst, src),
+    _ => return None,
+  };
+  if written(value)?.0 != src.0 {
+    return None;
+  }
+  let value = match (unary, Literal::from_instruction(value)?) {
+    (AnyInstruction::Not(_), Literal::None) => Literal::Bool(true),
+    (AnyInstruction::Not(_), Literal::Bool(value)) => Literal::Bool(!value),
+    (AnyInstruction::Not(_), Literal::Int(value)) => Literal::Bool(value == 0),
+    (AnyInstruction::Inv(_), Literal::Int(value)) => Literal::Int(value.checked_neg()?),
+    (AnyInstruction::IsNone(_), value) => Literal::Bool(matches!(value, Literal::None)),
+    _ => return None,
+  };
+  Some(value.into_instruction(dst))
+}
+
+/// Rewrite `<op> t, ...; mov r, t` at `i` into `<op> r, ...`, if nothing else
+/// reads `t`.
+///
+/// This relies on instructions reading their operands before writing to their
+/// destination, so it is fine for `<op>` to also read `r`.
+fn coalesce_mov(nodes: &[Node], i: usize) -> Option<AnyInstruction> {
+  let AnyInstruction::Mov(Mov { dst: r, src: t }) = nodes.get(i + 1)?.instruction else {
+    return None;
+  };
+  if nodes[i].target.is_some() || !is_dead(nodes, i + 2, t) {
+    return None;
+  }
+  let mut instruction = nodes[i].instruction;
+  let dst = dst(&mut instruction).filter(|dst| dst.0 == t.0)?;
+  *dst = r;
+  Some(instruction)
+}
+
+/// Instructions which only write to their destination, and have no other
+/// effects.
+fn is_pure(instruction: AnyInstruction) -> bool {
+  matches!(
+    instruction,
+    AnyInstruction::Mov(_)
+      | AnyInstruction::LoadConst(_)
+      | AnyInstruction::LoadNone(_)
+      | AnyInstruction::LoadTrue(_)
+      | AnyInstruction::LoadFalse(_)
+      | AnyInstruction::LoadSmi(_)
+      | AnyInstruction::MakeListEmpty(_)
+      | AnyInstruction::MakeTupleEmpty(_)
+      | AnyInstruction::MakeTableEmpty(_)
+  )
+}
+
+/// Returns the destination register of `instruction`, if it has one.
+fn dst(instruction: &mut AnyInstruction) -> Option<&mut op::Register> {
+  match instruction {
+    AnyInstruction::Mov(Mov { dst, .. })
+    | AnyInstruction::LoadConst(LoadConst { dst, .. })
+    | AnyInstruction::LoadUpvalue(LoadUpvalue { dst, .. })
+    | AnyInstruction::LoadModuleVar(LoadModuleVar { dst, .. })
+    | AnyInstruction::LoadGlobal(LoadGlobal { dst, .. })
+    | AnyInstruction::LoadField(LoadField { dst, .. })
+    | AnyInstruction::LoadFieldOpt(LoadFieldOpt { dst, .. })
+    | AnyInstruction::LoadIndex(LoadIndex { dst, .. })
+    | AnyInstruction::LoadIndexOpt(LoadIndexOpt { dst, .. })
+    | AnyInstruction::LoadSelf(LoadSelf { dst })
+    | AnyInstruction::LoadSuper(LoadSuper { dst })
+    | AnyInstruction::LoadNone(LoadNone { dst })
+    | AnyInstruction::LoadTrue(LoadTrue { dst })
+    | AnyInstruction::LoadFalse(LoadFalse { dst })
+    | AnyInstruction::LoadSmi(LoadSmi { dst, .. })
+    | AnyInstruction::MakeFn(MakeFn { dst, .. })
+    | AnyInstruction::MakeClass(MakeClass { dst, .. })
+    | AnyInstruction::MakeClassDerived(MakeClassDerived { dst, .. })
+    | AnyInstruction::MakeDataClass(MakeDataClass { dst, .. })
+    | AnyInstruction::MakeDataClassDerived(MakeDataClassDerived { dst, .. })
+    | AnyInstruction::MakeList(MakeList { dst, .. })
+    | AnyInstruction::MakeTuple(MakeTuple { dst, .. })
+    | AnyInstruction::MakeTable(MakeTable { dst, .. })
+    | AnyInstruction::MakeListEmpty(MakeListEmpty { dst })
+    | AnyInstruction::MakeTupleEmpty(MakeTupleEmpty { dst })
+    | AnyInstruction::MakeTableEmpty(MakeTableEmpty { dst })
+    | AnyInstruction::MakeBytes(MakeBytes { dst, .. })
+    | AnyInstruction::Add(Add { dst, .. })
+    | AnyInstruction::Sub(Sub { dst, .. })
+    | AnyInstruction::Mul(Mul { dst, .. })
+    | AnyInstruction::Div(Div { dst, .. })
+    | AnyInstruction::FloorDiv(FloorDiv { dst, .. })
+    | AnyInstruction::Rem(Rem { dst, .. })
+    | AnyInstruction::Pow(Pow { dst, .. })
+    | AnyInstruction::Inv(Inv { dst, .. })
+    | AnyInstruction::Not(Not { dst, .. })
+    | AnyInstruction::CmpEq(CmpEq { dst, .. })
+    | AnyInstruction::CmpNe(CmpNe { dst, .. })
+    | AnyInstruction::CmpGt(CmpGt { dst, .. })
+    | AnyInstruction::CmpGe(CmpGe { dst, .. })
+    | AnyInstruction::CmpLt(CmpLt { dst, .. })
+    | AnyInstruction::CmpLe(CmpLe { dst, .. })
+    | AnyInstruction::CmpType(CmpType { dst, .. })
+    | AnyInstruction::Contains(Contains { dst, .. })
+    | AnyInstruction::IsNone(IsNone { dst, .. })
+    | AnyInstruction::AppendStr(AppendStr { dst, .. })
+    | AnyInstruction::BuildStr(BuildStr { dst, .. })
+    | AnyInstruction::Call(Call { dst, .. })
+    | AnyInstruction::CallKw(CallKw { dst, .. })
+    | AnyInstruction::Import(Import { dst, .. })
+    | AnyInstruction::FinalizeModule(FinalizeModule { dst })
+    | AnyInstruction::HasDeferred(HasDeferred { dst })
+    | AnyInstruction::CallDeferred(CallDeferred { dst })
+    | AnyInstruction::CmpLtJumpIfFalse(CmpLtJumpIfFalse { dst, .. }) => Some(dst),
+    _ => None,
+  }
+}
+
+fn written(mut instruction: AnyInstruction) -> Option<op::Register> {
+  dst(&mut instruction).copied()
+}
+
+/// Returns `true` if `instruction` writes to `reg`, either as its destination
+/// or as one of the registers written by `Unpack`.
+fn writes(instruction: AnyInstruction, reg: op::Register) -> bool {
+  match instruction {
+    AnyInstruction::Unpack(Unpack { start, count, .. }) => {
+      (start.0..start.0 + count.0).contains(&reg.0)
+    }
+    instruction => written(instruction).is_some_and(|dst| dst.0 == reg.0),
+  }
+}
+
+/// Returns `true` if `instruction` may read `reg`.
+///
+/// Instructions which create functions may capture any register as an
+/// upvalue, so they are assumed to read all of them.
+fn reads(instruction: AnyInstruction, reg: op::Register) -> bool {
+  let reg = reg.0;
+  match instruction {
+    AnyInstruction::Mov(Mov { src, .. })
+    | AnyInstruction::StoreUpvalue(StoreUpvalue { src, .. })
+    | AnyInstruction::StoreModuleVar(StoreModuleVar { src, .. })
+    | AnyInstruction::StoreGlobal(StoreGlobal { src, .. })
+    | AnyInstruction::LoadField(LoadField { obj: src, .. })
+    | AnyInstruction::LoadFieldOpt(LoadFieldOpt { obj: src, .. })
+    | AnyInstruction::Inv(Inv { src, .. })
+    | AnyInstruction::Not(Not { src, .. })
+    | AnyInstruction::IsNone(IsNone { src, .. })
+    | AnyInstruction::BuildStr(BuildStr { src, .. })
+    | AnyInstruction::Unpack(Unpack { src, .. })
+    | AnyInstruction::JumpIfFalse(JumpIfFalse { cond: src, .. })
+    | AnyInstruction::JumpIfFalseConst(JumpIfFalseConst { cond: src, .. })
+    | AnyInstruction::Switch(Switch { src, .. })
+    | AnyInstruction::Print(Print { src })
+    | AnyInstruction::Return(Return { src })
+    | AnyInstruction::Yield(Yield { src })
+    | AnyInstruction::Raise(Raise { src })
+    | AnyInstruction::CheckParam(CheckParam { param: src, .. }) => src.0 == reg,
+
+    AnyInstruction::StoreField(StoreField { obj: a, src: b, .. })
+    | AnyInstruction::LoadIndex(LoadIndex { obj: a, key: b, .. })
+    | AnyInstruction::LoadIndexOpt(LoadIndexOpt { obj: a, key: b, .. })
+    | AnyInstruction::Add(Add { lhs: a, rhs: b, .. })
+    | AnyInstruction::Sub(Sub { lhs: a, rhs: b, .. })
+    | AnyInstruction::Mul(Mul { lhs: a, rhs: b, .. })
+    | AnyInstruction::Div(Div { lhs: a, rhs: b, .. })
+    | AnyInstruction::FloorDiv(FloorDiv { lhs: a, rhs: b, .. })
+    | AnyInstruction::Rem(Rem { lhs: a, rhs: b, .. })
+    | AnyInstruction::Pow(Pow { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpEq(CmpEq { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpNe(CmpNe { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpGt(CmpGt { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpGe(CmpGe { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpLt(CmpLt { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpLe(CmpLe { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpType(CmpType { lhs: a, rhs: b, .. })
+    | AnyInstruction::Contains(Contains { lhs: a, rhs: b, .. })
+    | AnyInstruction::AppendStr(AppendStr { lhs: a, rhs: b, .. })
+    | AnyInstruction::CmpLtJumpIfFalse(CmpLtJumpIfFalse { lhs: a, rhs: b, .. }) => {
+      a.0 == reg || b.0 == reg
+    }
+    AnyInstruction::StoreIndex(StoreIndex { obj, key, src }) => {
+      obj.0 == reg || key.0 == reg || src.0 == reg
+    }
+
+    AnyInstruction::MakeList(MakeList { start, count, .. })
+    | AnyInstruction::MakeTuple(MakeTuple { start, count, .. })
+    | AnyInstruction::PrintN(PrintN { start, count }) => {
+      (start.0..start.0 + count.0).contains(&reg)
+    }
+    AnyInstruction::MakeTable(MakeTable { start, count, .. }) => {
+      (start.0..start.0 + 2 * count.0).contains(&reg)
+    }
+    // the callee is followed by the arguments
+    AnyInstruction::Call(Call { callee, args, .. })
+    | AnyInstruction::CallKw(CallKw { callee, args, .. })
+    | AnyInstruction::Defer(Defer { callee, args }) => {
+      (callee.0..=callee.0 + args.0).contains(&reg)
+    }
+    // `self` is always in the first register
+    AnyInstruction::LoadSelf(_) | AnyInstruction::LoadSuper(_) => reg == 0,
+
+    AnyInstruction::Nop(_)
+    | AnyInstruction::Wide16(_)
+    | AnyInstruction::Wide32(_)
+    | AnyInstruction::LoadConst(_)
+    | AnyInstruction::LoadUpvalue(_)
+    | AnyInstruction::LoadModuleVar(_)
+    | AnyInstruction::LoadGlobal(_)
+    | AnyInstruction::LoadNone(_)
+    | AnyInstruction::LoadTrue(_)
+    | AnyInstruction::LoadFalse(_)
+    | AnyInstruction::LoadSmi(_)
+    | AnyInstruction::MakeListEmpty(_)
+    | AnyInstruction::MakeTupleEmpty(_)
+    | AnyInstruction::MakeTableEmpty(_)
+    | AnyInstruction::MakeBytes(_)
+    | AnyInstruction::Jump(_)
+    | AnyInstruction::JumpConst(_)
+    | AnyInstruction::JumpLoop(_)
+    | AnyInstruction::HasDeferred(_) => false,
+
+    _ => true,
+  }
+}
+
+/// Returns `true` if the value in `reg` is never read when execution continues
+/// from `start`.
+///
+/// This only looks at straight-line code, so any jump is assumed to read
+/// `reg`.
+fn is_dead(nodes: &[Node], start: usize, reg: op::Register) -> bool {
+  for node in &nodes[start.min(nodes.len())..] {
+    if node.target.is_some() || !node.cases.is_empty() || reads(node.instruction, reg) {
+      return false;
+    }
+    match node.instruction {
+      AnyInstruction::Return(_) | AnyInstruction::Raise(_) => return true,
+      instruction if writes(instruction, reg) => return true,
+      _ => {}
+    }
+  }
+  false
+}
+
+/// Build a new constant pool which only contains the constants used by
+/// `nodes`, with duplicates merged, and update the instructions to use it.
+fn compact_constants(nodes: &mut [Node], constants: &[Constant]) -> Vec<Constant> {
+  let mut pool: Vec<Constant> = Vec::new();
+  let mut new_index: Vec<Option<u32>> = vec![None; constants.len()];
+  for node in nodes.iter_mut() {
+    node.instruction.map_constants(|idx| {
+      let index = *new_index[idx.index()].get_or_insert_with(|| {
+        let constant = &constants[idx.index()];
+        match pool
+          .iter()
+          .position(|other| is_same_constant(constant, other))
+        {
+          Some(index) => index as u32,
+          None => {
+            pool.push(constant.clone());
+            (pool.len() - 1) as u32
+          }
+        }
+      });
+      op::Constant(index)
+    });
+  }
+  pool
+}
+
+fn is_same_constant(a: &Constant, b: &Constant) -> bool {
+  match (a, b) {
+    (Constant::String(a), Constant::String(b)) => a.as_str() == b.as_str(),
+    (Constant::Float(a), Constant::Float(b)) => a.value().to_bits() == b.value().to_bits(),
+    (Constant::Int(a), Constant::Int(b)) => a.value() == b.value(),
+    (Constant::Function(a), Constant::Function(b)) => a.ptr_eq(b),
+    (Constant::Class(a), Constant::Class(b)) => a.ptr_eq(b),
+    (Constant::Bytes(a), Constant::Bytes(b)) => a.ptr_eq(b),
+    _ => false,
+  }
+}
+
+/// Encode `nodes`, and update the jump tables in `constants` to the offsets
+/// of their cases.
+fn encode(nodes: &[Node], constants: &mut [Constant]) -> (Vec<u8>, Vec<Span>) {
+  let mut buf = Vec::new();
+  let sizes = nodes
+    .iter()
+    .map(|node| {
+      buf.clear();
+      node.instruction.encode(&mut buf);
+      buf.len()
+    })
+    .collect::<Vec<_>>();
+
+  // the size of a jump depends on the distance to its target, which in turn
+  // depends on the size of the jumps in between, so keep widening jumps until
+  // all of their offsets fit
+  let mut widths = vec![Width::Normal; nodes.len()];
+  let offsets = loop {
+    let offsets = layout(nodes, &sizes, &widths);
+    let mut changed = false;
+    for (i, node) in nodes.iter().enumerate() {
+      if node.target.is_some() {
+        let width = jump_offset(nodes, &offsets, i).width();
+        if width > widths[i] {
+          widths[i] = width;
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      break offsets;
+    }
+  };
+
+  let mut bytecode = Vec::with_capacity(*offsets.last().unwrap());
+  for (i, node) in nodes.iter().enumerate() {
+    let offset = jump_offset(nodes, &offsets, i);
+    with_offset(node.instruction, offset).encode(&mut bytecode);
+    debug_assert_eq!(bytecode.len(), offsets[i + 1]);
+
+    if let AnyInstruction::Switch(Switch { table, .. }) = node.instruction {
+      let mut cases = JumpTable::new();
+      for (key, target) in node.cases.iter() {
+        cases.insert(
+          key.as_str(),
+          op::Offset((offsets[*target] - offsets[i]) as u32),
+        );
+      }
+      constants[table.index()] = Constant::JumpTable(Rc::new(cases));
+    }
+  }
+  let spans = nodes.iter().map(|node| node.span).collect();
+
+  (bytecode, spans)
+}
+
+/// Returns the offset of each node, followed by the total size.
+///
+/// The offset of each jump is assumed to need exactly `widths[i]`.
+fn layout(nodes: &[Node], sizes: &[usize], widths: &[Width]) -> Vec<usize> {
+  let mut offsets = Vec::with_capacity(nodes.len() + 1);
+  let mut offset = 0;
+  let mut buf = Vec::new();
+  for (i, node) in nodes.iter().enumerate() {
+    offsets.push(offset);
+    offset += match node.target {
+      Some(_) => {
+        // the smallest offset which needs this width, so that any other
+        // operands still widen the instruction if they need more
+        let smallest = match widths[i] {
+          Width::Normal => 0,
+          Width::Wide16 => u8::MAX as u32 + 1,
+          Width::Wide32 => u16::MAX as u32 + 1,
+        };
+        buf.clear();
+        with_offset(node.instruction, op::Offset(smallest)).encode(&mut buf);
+        buf.len()
+      }
+      None => sizes[i],
+    };
+  }
+  offsets.push(offset);
+  offsets
+}
+
+/// Returns `instruction` with its jump offset replaced by `offset`.
+fn with_offset(instruction: AnyInstruction, offset: op::Offset) -> AnyInstruction {
+  match instruction {
+    AnyInstruction::Jump(_) => AnyInstruction::Jump(Jump { offset }),
+    AnyInstruction::JumpIfFalse(JumpIfFalse { cond, .. }) => {
+      AnyInstruction::JumpIfFalse(JumpIfFalse { offset, cond })
+    }
+    AnyInstruction::JumpLoop(_) => AnyInstruction::JumpLoop(JumpLoop { offset }),
+    AnyInstruction::CmpLtJumpIfFalse(CmpLtJumpIfFalse { dst, lhs, rhs, .. }) => {
+      AnyInstruction::CmpLtJumpIfFalse(CmpLtJumpIfFalse {
+        dst,
+        lhs,
+        rhs,
+        offset,
+      })
+    }
+    instruction => instruction,
+  }
+}
+
+fn jump_offset(nodes: &[Node], offsets: &[usize], i: usize) -> op::Offset {
+  match nodes[i].target {
+    Some(target) => op::Offset(offsets[i].abs_diff(offsets[target]) as u32),
+    None => op::Offset(0),
+  }
+}
+
+#[cfg(all(test, not(feature = "__miri")))]
+mod tests;
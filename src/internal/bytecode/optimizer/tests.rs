@@ -0,0 +1,338 @@
+use super::*;
+use crate::internal::bytecode::builder::BytecodeBuilder;
+use crate::internal::bytecode::opcode::Opcode;
+use crate::internal::value::constant::NonNaNFloat;
+
+fn run(builder: BytecodeBuilder) -> (Vec<u8>, Vec<Constant>) {
+  let (bytecode, constants, spans) = builder.finish_with_spans();
+  let (bytecode, constants, spans) = optimize(bytecode, constants, spans);
+  assert_eq!(
+    spans.len(),
+    decode(&bytecode, &constants, &spans).unwrap().len()
+  );
+  (bytecode, constants)
+}
+
+fn r(index: u32) -> op::Register {
+  op::Register(index)
+}
+
+#[rustfmt::skip]
+#[test]
+fn fold_binary_op() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadSmi { dst: r(1), value: op::Smi(10) }, 0..0);
+  builder.emit(LoadSmi { dst: r(2), value: op::Smi(5) }, 0..0);
+  builder.emit(Add { dst: r(0), lhs: r(1), rhs: r(2) }, 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.emit(LoadNone { dst: r(0) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadSmi as u8, /*dst*/ 0, 15i8.to_le_bytes()[0],
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::LoadNone as u8, /*dst*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn fold_binary_op_live_register() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadSmi { dst: r(0), value: op::Smi(10) }, 0..0);
+  builder.emit(LoadSmi { dst: r(1), value: op::Smi(5) }, 0..0);
+  builder.emit(CmpLt { dst: r(2), lhs: r(0), rhs: r(1) }, 0..0);
+  builder.emit(Print { src: r(2) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  // `r0` is read later, so it must still be written
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadSmi as u8, /*dst*/ 0, 10i8.to_le_bytes()[0],
+      Opcode::LoadFalse as u8, /*dst*/ 2,
+      Opcode::Print as u8, /*src*/ 2,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn fold_unary_op() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadTrue { dst: r(0) }, 0..0);
+  builder.emit(Not { dst: r(0), src: r(0) }, 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.emit(LoadSmi { dst: r(1), value: op::Smi(1) }, 0..0);
+  builder.emit(Inv { dst: r(1), src: r(1) }, 0..0);
+  builder.emit(Return { src: r(1) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadFalse as u8, /*dst*/ 0,
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::LoadSmi as u8, /*dst*/ 1, (-1i8).to_le_bytes()[0],
+      Opcode::Return as u8, /*src*/ 1,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn reencode_jumps() {
+  let mut builder = BytecodeBuilder::new();
+
+  let end = builder.label("end");
+  builder.emit(LoadTrue { dst: r(0) }, 0..0);
+  builder.emit_jump_if_false(&end, r(0), 0..0);
+  builder.emit(Nop, 0..0);
+  builder.emit(Nop, 0..0);
+  builder.emit(LoadSmi { dst: r(1), value: op::Smi(1) }, 0..0);
+  builder.emit(Print { src: r(1) }, 0..0);
+  builder.bind_label(end);
+  builder.emit(LoadNone { dst: r(0) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadTrue as u8, /*dst*/ 0,
+      Opcode::JumpIfFalse as u8, /*offset*/ 8, /*cond*/ 0,
+      Opcode::LoadSmi as u8, /*dst*/ 1, 1i8.to_le_bytes()[0],
+      Opcode::Print as u8, /*src*/ 1,
+      Opcode::LoadNone as u8, /*dst*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn reencode_switch() {
+  let mut builder = BytecodeBuilder::new();
+
+  let end = builder.label("end");
+  let mut table = builder.emit_switch(r(0), 0..0);
+  builder.emit_jump(&end, 0..0);
+  builder.bind_switch_case(&mut table, "a");
+  builder.emit(Nop, 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.bind_switch_case(&mut table, "b");
+  builder.emit(Nop, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+  builder.finish_switch(table);
+  builder.bind_label(end);
+  builder.emit(LoadNone { dst: r(0) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, constants) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::Switch as u8, /*src*/ 0, /*table*/ 0,
+      Opcode::Jump as u8, /*offset*/ 6,
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+      Opcode::LoadNone as u8, /*dst*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+  // the cases no longer jump to the removed `nop`s
+  let table = constants[0].as_jump_table().unwrap();
+  assert_eq!(table.get("a"), Some(op::Offset(5)));
+  assert_eq!(table.get("b"), Some(op::Offset(7)));
+}
+
+#[test]
+fn remove_jump_to_next() {
+  let mut builder = BytecodeBuilder::new();
+
+  let end = builder.label("end");
+  builder.emit_jump(&end, 0..0);
+  for _ in 0..(256 - 2) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(end);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, constants) = run(builder);
+
+  assert_eq!(bytecode, [Opcode::Return as u8, 0]);
+  // the jump offset no longer needs a constant
+  assert!(constants.is_empty());
+}
+
+#[rustfmt::skip]
+#[test]
+fn remove_unused_constants() {
+  let mut builder = BytecodeBuilder::new();
+
+  let a = builder.constant_pool_builder().insert(NonNaNFloat::from(10.0));
+  let b = builder.constant_pool_builder().insert(NonNaNFloat::from(5.0));
+  builder.emit(LoadConst { dst: r(0), idx: a }, 0..0);
+  builder.emit(LoadConst { dst: r(0), idx: b }, 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.emit(LoadNone { dst: r(0) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, constants) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadConst as u8, /*dst*/ 0, /*index*/ 0,
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::LoadNone as u8, /*dst*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+  assert_eq!(constants.len(), 1);
+  assert_eq!(constants[0].as_float().unwrap().value(), 5.0);
+}
+
+#[rustfmt::skip]
+#[test]
+fn coalesce_mov() {
+  let mut builder = BytecodeBuilder::new();
+
+  let a = builder.constant_pool_builder().insert(NonNaNFloat::from(10.0));
+  builder.emit(LoadConst { dst: r(1), idx: a }, 0..0);
+  builder.emit(Mov { dst: r(0), src: r(1) }, 0..0);
+  builder.emit(Add { dst: r(1), lhs: r(0), rhs: r(0) }, 0..0);
+  builder.emit(Mov { dst: r(0), src: r(1) }, 0..0);
+  builder.emit(Mov { dst: r(0), src: r(0) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadConst as u8, /*dst*/ 0, /*index*/ 0,
+      Opcode::Add as u8, /*dst*/ 0, /*lhs*/ 0, /*rhs*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn no_coalesce_mov_live_register() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadTrue { dst: r(1) }, 0..0);
+  builder.emit(Mov { dst: r(0), src: r(1) }, 0..0);
+  builder.emit(Print { src: r(1) }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  // `r1` is printed after the move
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadTrue as u8, /*dst*/ 1,
+      Opcode::Mov as u8, /*dst*/ 0, /*src*/ 1,
+      Opcode::Print as u8, /*src*/ 1,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn fuse_cmp_lt_jump_if_false() {
+  let mut builder = BytecodeBuilder::new();
+
+  let end = builder.label("end");
+  builder.emit(LoadSmi { dst: r(0), value: op::Smi(1) }, 0..0);
+  builder.emit(CmpLt { dst: r(2), lhs: r(1), rhs: r(0) }, 0..0);
+  builder.emit_jump_if_false(&end, r(2), 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.bind_label(end);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::LoadSmi as u8, /*dst*/ 0, 1i8.to_le_bytes()[0],
+      Opcode::CmpLtJumpIfFalse as u8, /*dst*/ 2, /*lhs*/ 1, /*rhs*/ 0, /*offset*/ 7,
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn no_fuse_different_register() {
+  let mut builder = BytecodeBuilder::new();
+
+  let end = builder.label("end");
+  builder.emit(CmpLt { dst: r(2), lhs: r(1), rhs: r(0) }, 0..0);
+  builder.emit_jump_if_false(&end, r(3), 0..0);
+  builder.emit(Print { src: r(2) }, 0..0);
+  builder.bind_label(end);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::CmpLt as u8, /*dst*/ 2, /*lhs*/ 1, /*rhs*/ 0,
+      Opcode::JumpIfFalse as u8, /*offset*/ 5, /*cond*/ 3,
+      Opcode::Print as u8, /*src*/ 2,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
+
+#[rustfmt::skip]
+#[test]
+fn no_fuse_into_jump_target() {
+  let mut builder = BytecodeBuilder::new();
+
+  let target = builder.label("target");
+  let end = builder.label("end");
+  builder.emit_jump(&target, 0..0);
+  builder.emit(CmpLt { dst: r(2), lhs: r(1), rhs: r(0) }, 0..0);
+  builder.bind_label(target);
+  builder.emit_jump_if_false(&end, r(2), 0..0);
+  builder.emit(Print { src: r(0) }, 0..0);
+  builder.bind_label(end);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, _) = run(builder);
+
+  assert_eq!(
+    bytecode,
+    [
+      Opcode::Jump as u8, /*offset*/ 6,
+      Opcode::CmpLt as u8, /*dst*/ 2, /*lhs*/ 1, /*rhs*/ 0,
+      Opcode::JumpIfFalse as u8, /*offset*/ 5, /*cond*/ 2,
+      Opcode::Print as u8, /*src*/ 0,
+      Opcode::Return as u8, /*src*/ 0,
+    ],
+  );
+}
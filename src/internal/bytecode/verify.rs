@@ -0,0 +1,339 @@
+//! Checks that bytecode is well-formed before it is executed.
+//!
+//! The VM trusts the bytecode it runs, and accesses registers, upvalues and
+//! constants without bounds checks, and jumps without checking where it lands.
+//! Bytecode emitted by codegen is well-formed by construction (which is
+//! checked in debug builds), but bytecode which was built by hand has to be
+//! verified before it is given to the VM.
+
+use super::opcode::symbolic::*;
+use super::opcode::{self as op, OperandVisitor};
+use crate::internal::error::Result;
+use crate::internal::object::function::{self, FunctionDescriptor};
+use crate::internal::value::constant::Constant;
+use crate::span::Span;
+
+/// Verify the bytecode of `desc`, see [`verify`].
+pub fn verify_function(desc: &FunctionDescriptor) -> Result<()> {
+  let (bytecode, spans) = unsafe { (desc.instructions.as_ref(), desc.spans.as_ref()) };
+  verify(
+    bytecode,
+    desc.constants(),
+    spans,
+    desc.frame_size,
+    desc.upvalues.borrow().len(),
+  )
+}
+
+/// Check that every instruction in `bytecode` decodes, only refers to
+/// registers within `frame_size`, upvalues within `upvalues`, and constants of
+/// the right kind within `constants`. Jumps must land at the start of an
+/// instruction, and the last instruction must not fall through.
+///
+/// Functions created by the bytecode capture registers and upvalues of this
+/// function, so those are checked as well.
+///
+/// Errors point at the span of the offending instruction, if there is one in
+/// `spans`.
+pub fn verify(
+  bytecode: &[u8],
+  constants: &[Constant],
+  spans: &[Span],
+  frame_size: usize,
+  upvalues: usize,
+) -> Result<()> {
+  let frame = Frame {
+    constants,
+    frame_size,
+    upvalues,
+  };
+  let mut boundaries = vec![false; bytecode.len()];
+  let mut jumps = vec![];
+  let mut remaining = bytecode;
+  let mut last = None;
+  for index in 0.. {
+    if remaining.is_empty() {
+      break;
+    }
+    let offset = bytecode.len() - remaining.len();
+    let span = spans.get(index).copied().unwrap_or_default();
+    boundaries[offset] = true;
+
+    let Some((instruction, next)) = AnyInstruction::decode(remaining) else {
+      fail!(@span, "invalid instruction at offset {offset}");
+    };
+    if let Err(e) = frame.verify_instruction(&instruction) {
+      fail!(@span, "invalid instruction at offset {offset}: {e}");
+    }
+    if let Some(target) = jump_target(&instruction, offset, constants) {
+      jumps.push((span, offset, target));
+    }
+    if let AnyInstruction::Switch(Switch { table, .. }) = instruction {
+      if let Some(Constant::JumpTable(table)) = constants.get(table.index()) {
+        jumps.extend(
+          table
+            .iter()
+            .map(|(_, case)| (span, offset, offset + case.value())),
+        );
+      }
+    }
+
+    remaining = next;
+    last = Some(instruction);
+  }
+
+  for (span, offset, target) in jumps {
+    if !boundaries.get(target).copied().unwrap_or(false) {
+      fail!(@span, "invalid instruction at offset {offset}: jump does not land at the start of an instruction");
+    }
+  }
+
+  match last {
+    Some(
+      AnyInstruction::Return(_)
+      | AnyInstruction::Raise(_)
+      | AnyInstruction::Jump(_)
+      | AnyInstruction::JumpConst(_)
+      | AnyInstruction::JumpLoop(_),
+    ) => Ok(()),
+    _ => fail!("bytecode must end with a `return`, `raise`, or an unconditional jump"),
+  }
+}
+
+/// The offset which `instruction` at `offset` may jump to, if it is a jump.
+///
+/// Jumps from the start of the bytecode further backwards wrap around, so
+/// they end up outside of it.
+fn jump_target(
+  instruction: &AnyInstruction,
+  offset: usize,
+  constants: &[Constant],
+) -> Option<usize> {
+  use AnyInstruction as I;
+  let forward = match *instruction {
+    I::Jump(Jump { offset, .. })
+    | I::JumpIfFalse(JumpIfFalse { offset, .. })
+    | I::CmpLtJumpIfFalse(CmpLtJumpIfFalse { offset, .. }) => offset,
+    I::JumpConst(JumpConst { offset: idx, .. })
+    | I::JumpIfFalseConst(JumpIfFalseConst { offset: idx, .. }) => {
+      match constants.get(idx.index()) {
+        Some(Constant::Offset(offset)) => *offset,
+        _ => return None,
+      }
+    }
+    I::JumpLoop(JumpLoop { offset: back }) => return Some(offset.wrapping_sub(back.value())),
+    _ => return None,
+  };
+  Some(offset + forward.value())
+}
+
+struct Frame<'a> {
+  constants: &'a [Constant],
+  frame_size: usize,
+  upvalues: usize,
+}
+
+impl<'a> Frame<'a> {
+  fn verify_instruction(&self, instruction: &AnyInstruction) -> Result<(), String> {
+    let mut operands = Operands {
+      frame_size: self.frame_size,
+      upvalues: self.upvalues,
+      error: None,
+    };
+    instruction.visit_operands(&mut operands);
+    if let Some(e) = operands.error {
+      return Err(e);
+    }
+
+    let (constants, frame_size) = (self.constants, self.frame_size);
+    use AnyInstruction as I;
+    match *instruction {
+      I::LoadConst(LoadConst { idx, .. }) => {
+        let constant = get_constant(constants, idx)?;
+        if matches!(
+          constant,
+          Constant::Reserved | Constant::Offset(_) | Constant::JumpTable(_) | Constant::KwNames(_)
+        ) {
+          return Err(format!("constant {idx} is not a value"));
+        }
+      }
+      I::LoadGlobal(LoadGlobal { name, .. })
+      | I::StoreGlobal(StoreGlobal { name, .. })
+      | I::LoadField(LoadField { name, .. })
+      | I::LoadFieldOpt(LoadFieldOpt { name, .. })
+      | I::StoreField(StoreField { name, .. })
+      | I::Import(Import { path: name, .. }) => expect_string(constants, name)?,
+      I::CheckParam(CheckParam { name, hint, .. }) => {
+        expect_string(constants, name)?;
+        expect_string(constants, hint)?;
+      }
+      I::MakeFn(MakeFn { desc, .. }) => {
+        let Constant::Function(desc) = get_constant(constants, desc)? else {
+          return Err(format!("constant {desc} is not a function descriptor"));
+        };
+        self.verify_captures(desc)?
+      }
+      I::MakeClass(MakeClass { desc, .. }) | I::MakeClassDerived(MakeClassDerived { desc, .. }) => {
+        self.verify_class(desc, None, false)?
+      }
+      I::MakeDataClass(MakeDataClass { desc, parts, .. }) => {
+        self.verify_class(desc, Some(parts), false)?
+      }
+      I::MakeDataClassDerived(MakeDataClassDerived { desc, parts, .. }) => {
+        self.verify_class(desc, Some(parts), true)?
+      }
+      I::MakeBytes(MakeBytes { data, .. }) => expect(constants, data, "a bytes object", |c| {
+        matches!(c, Constant::Bytes(_))
+      })?,
+      I::JumpConst(JumpConst { offset, .. })
+      | I::JumpIfFalseConst(JumpIfFalseConst { offset, .. }) => {
+        expect(constants, offset, "a jump offset", |c| {
+          matches!(c, Constant::Offset(_))
+        })?
+      }
+      I::Switch(Switch { table, .. }) => expect(constants, table, "a jump table", |c| {
+        matches!(c, Constant::JumpTable(_))
+      })?,
+      I::MakeList(MakeList { start, count, .. })
+      | I::MakeTuple(MakeTuple { start, count, .. })
+      | I::Unpack(Unpack { start, count, .. })
+      | I::PrintN(PrintN { start, count }) => register_range(start, count.value(), frame_size)?,
+      I::MakeTable(MakeTable { start, count, .. }) => {
+        register_range(start, count.value() * 2, frame_size)?
+      }
+      I::Call(Call { callee, args, .. }) | I::Defer(Defer { callee, args }) => {
+        register_range(callee, 1 + args.value(), frame_size)?
+      }
+      I::CallKw(CallKw {
+        callee,
+        args,
+        names,
+        ..
+      }) => {
+        register_range(callee, 1 + args.value(), frame_size)?;
+        let Constant::KwNames(kw) = get_constant(constants, names)? else {
+          return Err(format!("constant {names} is not a list of keyword names"));
+        };
+        if kw.len() > args.value() {
+          return Err(format!(
+            "{} keyword names are passed with only {args} arguments",
+            kw.len()
+          ));
+        }
+      }
+      _ => {}
+    }
+
+    Ok(())
+  }
+
+  fn verify_class(
+    &self,
+    desc: op::Constant,
+    parts: Option<op::Register>,
+    derived: bool,
+  ) -> Result<(), String> {
+    let Constant::Class(class) = get_constant(self.constants, desc)? else {
+      return Err(format!("constant {desc} is not a class descriptor"));
+    };
+    if let Some(parts) = parts {
+      let count = derived as usize + class.fields.len() + class.mixins;
+      register_range(parts, count, self.frame_size)?;
+    }
+    for method in class
+      .init
+      .iter()
+      .chain(class.methods.values())
+      .chain(class.static_methods.values())
+    {
+      self.verify_captures(method)?;
+    }
+    Ok(())
+  }
+
+  /// Creating a function copies the registers and upvalues it captures out of
+  /// the current frame.
+  fn verify_captures(&self, desc: &FunctionDescriptor) -> Result<(), String> {
+    for upvalue in desc.upvalues.borrow().iter() {
+      let mut operands = Operands {
+        frame_size: self.frame_size,
+        upvalues: self.upvalues,
+        error: None,
+      };
+      match *upvalue {
+        function::Upvalue::Register(register) => operands.visit_register(register),
+        function::Upvalue::Upvalue(upvalue) => operands.visit_upvalue(upvalue),
+      }
+      if let Some(e) = operands.error {
+        return Err(format!("invalid capture in function `{}`: {e}", desc.name));
+      }
+    }
+    Ok(())
+  }
+}
+
+struct Operands {
+  frame_size: usize,
+  upvalues: usize,
+  error: Option<String>,
+}
+
+impl OperandVisitor for Operands {
+  fn visit_register(&mut self, register: op::Register) {
+    if self.error.is_none() && register.index() >= self.frame_size {
+      self.error = Some(format!(
+        "register {register} is outside of the frame, which has {} registers",
+        self.frame_size
+      ));
+    }
+  }
+
+  fn visit_upvalue(&mut self, upvalue: op::Upvalue) {
+    if self.error.is_none() && upvalue.index() >= self.upvalues {
+      self.error = Some(format!(
+        "upvalue {upvalue} does not exist, the function has {} upvalues",
+        self.upvalues
+      ));
+    }
+  }
+}
+
+fn register_range(start: op::Register, count: usize, frame_size: usize) -> Result<(), String> {
+  if start.index() + count > frame_size {
+    return Err(format!(
+      "registers {start} to r{} are outside of the frame, which has {frame_size} registers",
+      start.index() + count - 1,
+    ));
+  }
+  Ok(())
+}
+
+fn get_constant(constants: &[Constant], index: op::Constant) -> Result<&Constant, String> {
+  constants.get(index.index()).ok_or_else(|| {
+    format!(
+      "constant {index} is outside of the constant pool, which has {} constants",
+      constants.len()
+    )
+  })
+}
+
+fn expect(
+  constants: &[Constant],
+  index: op::Constant,
+  kind: &str,
+  f: impl FnOnce(&Constant) -> bool,
+) -> Result<(), String> {
+  if !f(get_constant(constants, index)?) {
+    return Err(format!("constant {index} is not {kind}"));
+  }
+  Ok(())
+}
+
+fn expect_string(constants: &[Constant], index: op::Constant) -> Result<(), String> {
+  expect(constants, index, "a string", |c| {
+    matches!(c, Constant::String(_))
+  })
+}
+
+#[cfg(all(test, not(feature = "__miri")))]
+mod tests;
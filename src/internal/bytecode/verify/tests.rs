@@ -0,0 +1,307 @@
+use std::rc::Rc;
+
+use super::*;
+use crate::internal::bytecode::builder::BytecodeBuilder;
+use crate::internal::object::function::Params;
+use crate::internal::value::constant::{JumpTable, KwNames, NonNaNFloat};
+use crate::internal::vm::global::Global;
+
+fn r(index: u32) -> op::Register {
+  op::Register(index)
+}
+
+fn check(builder: BytecodeBuilder, frame_size: usize) -> Result<(), String> {
+  let (bytecode, constants, spans) = builder.finish_with_spans();
+  verify(&bytecode, &constants, &spans, frame_size, 0).map_err(|e| e.to_string())
+}
+
+#[test]
+fn well_formed() {
+  let global = Global::default();
+  let mut builder = BytecodeBuilder::new();
+
+  let name = builder.constant_pool_builder().insert(global.intern("x"));
+  let end = builder.label("end");
+  builder.emit(LoadGlobal { dst: r(1), name }, 0..0);
+  builder.emit_jump_if_false(&end, r(1), 0..0);
+  builder.emit(
+    MakeList {
+      dst: r(1),
+      start: r(2),
+      count: op::Count(2),
+    },
+    0..0,
+  );
+  builder.bind_label(end);
+  builder.emit(Return { src: r(1) }, 0..0);
+
+  check(builder, 4).unwrap();
+}
+
+#[test]
+fn register_out_of_bounds() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(
+    Mov {
+      dst: r(1),
+      src: r(4),
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(1) }, 0..0);
+
+  let e = check(builder, 4).unwrap_err();
+  assert!(e.contains("register r4 is outside of the frame"), "{e}");
+}
+
+#[test]
+fn register_range_out_of_bounds() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(
+    Call {
+      dst: r(1),
+      callee: r(1),
+      args: op::Count(3),
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(1) }, 0..0);
+
+  let e = check(builder, 4).unwrap_err();
+  assert!(e.contains("registers r1 to r4"), "{e}");
+}
+
+#[test]
+fn constant_out_of_bounds() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(
+    LoadConst {
+      dst: r(0),
+      idx: op::Constant(0),
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let e = check(builder, 1).unwrap_err();
+  assert!(
+    e.contains("constant [0] is outside of the constant pool"),
+    "{e}"
+  );
+}
+
+#[test]
+fn constant_of_wrong_kind() {
+  let mut builder = BytecodeBuilder::new();
+
+  let float = builder
+    .constant_pool_builder()
+    .insert(NonNaNFloat::from(1.0));
+  builder.emit(
+    LoadConst {
+      dst: r(0),
+      idx: float,
+    },
+    0..0,
+  );
+  builder.emit(
+    MakeFn {
+      dst: r(0),
+      desc: float,
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let e = check(builder, 1).unwrap_err();
+  assert!(e.contains("offset 3"), "{e}");
+  assert!(e.contains("is not a function descriptor"), "{e}");
+}
+
+#[test]
+fn falls_through_end() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadNone { dst: r(0) }, 0..0);
+
+  let e = check(builder, 1).unwrap_err();
+  assert!(e.contains("must end with"), "{e}");
+}
+
+#[test]
+fn truncated_instruction() {
+  let (bytecode, constants, spans) = {
+    let mut builder = BytecodeBuilder::new();
+    builder.emit(Return { src: r(0) }, 0..0);
+    builder.finish_with_spans()
+  };
+
+  let e = verify(&bytecode[..1], &constants, &spans, 1, 0).unwrap_err();
+  assert!(
+    e.to_string().contains("invalid instruction at offset 0"),
+    "{e}"
+  );
+}
+
+/// Jumps can't be emitted with arbitrary offsets using the builder.
+fn encode(instructions: &[AnyInstruction]) -> Vec<u8> {
+  let mut bytecode = vec![];
+  for instruction in instructions {
+    instruction.encode(&mut bytecode);
+  }
+  bytecode
+}
+
+#[test]
+fn jump_into_instruction() {
+  // `load_smi` is 3 bytes long, so this jumps to its operand
+  let bytecode = encode(&[
+    AnyInstruction::Jump(Jump {
+      offset: op::Offset(3),
+    }),
+    AnyInstruction::LoadSmi(LoadSmi {
+      dst: r(0),
+      value: op::Smi(1),
+    }),
+    AnyInstruction::Return(Return { src: r(0) }),
+  ]);
+
+  let e = verify(&bytecode, &[], &[], 1, 0).unwrap_err().to_string();
+  assert!(e.contains("offset 0"), "{e}");
+  assert!(e.contains("jump does not land"), "{e}");
+}
+
+#[test]
+fn jump_outside_of_bytecode() {
+  let bytecode = encode(&[
+    AnyInstruction::JumpIfFalse(JumpIfFalse {
+      offset: op::Offset(5),
+      cond: r(0),
+    }),
+    AnyInstruction::Return(Return { src: r(0) }),
+  ]);
+
+  let e = verify(&bytecode, &[], &[], 1, 0).unwrap_err().to_string();
+  assert!(e.contains("jump does not land"), "{e}");
+
+  let bytecode = encode(&[
+    AnyInstruction::LoadNone(LoadNone { dst: r(0) }),
+    AnyInstruction::JumpLoop(JumpLoop {
+      offset: op::Offset(3),
+    }),
+  ]);
+
+  let e = verify(&bytecode, &[], &[], 1, 0).unwrap_err().to_string();
+  assert!(e.contains("offset 2"), "{e}");
+  assert!(e.contains("jump does not land"), "{e}");
+}
+
+#[test]
+fn switch_into_instruction() {
+  let mut table = JumpTable::new();
+  table.insert("a", op::Offset(3));
+  // `load_smi` is 3 bytes long, so this jumps to its operand
+  table.insert("b", op::Offset(4));
+  let bytecode = encode(&[
+    AnyInstruction::Switch(Switch {
+      src: r(0),
+      table: op::Constant(0),
+    }),
+    AnyInstruction::LoadSmi(LoadSmi {
+      dst: r(0),
+      value: op::Smi(1),
+    }),
+    AnyInstruction::Return(Return { src: r(0) }),
+  ]);
+
+  let constants = [Constant::JumpTable(Rc::new(table))];
+  let e = verify(&bytecode, &constants, &[], 1, 0)
+    .unwrap_err()
+    .to_string();
+  assert!(e.contains("offset 0"), "{e}");
+  assert!(e.contains("jump does not land"), "{e}");
+
+  let constants = [Constant::Offset(op::Offset(3))];
+  let e = verify(&bytecode, &constants, &[], 1, 0)
+    .unwrap_err()
+    .to_string();
+  assert!(e.contains("is not a jump table"), "{e}");
+}
+
+#[test]
+fn upvalue_out_of_bounds() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(
+    LoadUpvalue {
+      dst: r(0),
+      idx: op::Upvalue(1),
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let (bytecode, constants, spans) = builder.finish_with_spans();
+  verify(&bytecode, &constants, &spans, 1, 2).unwrap();
+  let e = verify(&bytecode, &constants, &spans, 1, 1).unwrap_err();
+  assert!(e.to_string().contains("upvalue ^1 does not exist"), "{e}");
+}
+
+#[test]
+fn capture_out_of_bounds() {
+  let global = Global::default();
+  let mut builder = BytecodeBuilder::new();
+
+  let inner = global.alloc(FunctionDescriptor::new(
+    global.intern("inner"),
+    false,
+    Params {
+      has_self: false,
+      min: 0,
+      max: 0,
+      hints: Box::new([]),
+      kw: Box::new([]),
+    },
+    vec![function::Upvalue::Register(r(1))],
+    1,
+    vec![],
+    vec![],
+  ));
+  let desc = builder.constant_pool_builder().insert(inner);
+  builder.emit(MakeFn { dst: r(0), desc }, 0..0);
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let e = check(builder, 1).unwrap_err();
+  assert!(
+    e.contains("invalid capture in function `inner`: register r1"),
+    "{e}"
+  );
+}
+
+#[test]
+fn call_kw_names() {
+  let mut builder = BytecodeBuilder::new();
+
+  let names = builder
+    .constant_pool_builder()
+    .insert(KwNames::new(vec!["a".into(), "b".into()]));
+  builder.emit(
+    CallKw {
+      dst: r(0),
+      callee: r(0),
+      args: op::Count(1),
+      names,
+    },
+    0..0,
+  );
+  builder.emit(Return { src: r(0) }, 0..0);
+
+  let e = check(builder, 2).unwrap_err();
+  assert!(
+    e.contains("2 keyword names are passed with only 1 arguments"),
+    "{e}"
+  );
+}
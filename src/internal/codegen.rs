@@ -1,16 +1,20 @@
 // TEMP
 #![allow(dead_code)]
 
+mod append;
 mod expr;
 mod regalloc;
 mod stmt;
 
+use std::ops::Deref;
+
 use indexmap::{IndexMap, IndexSet};
 
 use self::regalloc::{RegAlloc, Register, Slice};
 use super::bytecode::builder::{BytecodeBuilder, InsertConstant, LoopHeader, MultiLabel};
 use super::bytecode::opcode::symbolic::*;
 use super::bytecode::opcode::{self as op};
+use super::bytecode::optimizer;
 use super::object;
 use super::object::function;
 use super::object::ptr::Ptr;
@@ -29,15 +33,17 @@ pub fn emit<'src>(
 
   let mut module = State::new(global.clone(), ast, name.clone(), is_root).emit_module();
 
-  let name = global.alloc(object::Str::owned(name));
+  let name = global.intern(name.to_string());
   // NOTE: no need to handle `.upvalues` here,
   // because the module root never has any upvalues
   let root = module.functions.pop().unwrap().finish().ptr;
+  let pool = object::ConstantPool::share(&global, &root);
   let module_vars = module.vars;
 
   global.alloc(object::ModuleDescriptor {
     name,
     root,
+    pool,
     module_vars,
   })
 }
@@ -101,20 +107,80 @@ impl<'src> State<'src> {
     self.current_function().regalloc.alloc_slice(n)
   }
 
-  fn emit_var(&mut self, name: impl Into<Cow<'src, str>>, span: Span) {
+  /// Returns the register which a new local variable `name` should be
+  /// emitted into, or `None` if the value must be emitted elsewhere first.
+  ///
+  /// Globals and module variables are stored from a temporary register. A
+  /// variable which shadows another one in the same scope may share its
+  /// register, so it is only written once the value no longer needs the
+  /// shadowed variable.
+  fn var_register(&mut self, name: &str) -> Option<Register> {
+    if self.is_global_scope() || self.is_declared_in_scope(name) {
+      None
+    } else {
+      Some(self.alloc_register())
+    }
+  }
+
+  fn is_declared_in_scope(&mut self, name: &str) -> bool {
+    let function = self.current_function();
+    function
+      .locals
+      .keys()
+      .any(|(scope, var)| *scope == function.scope && var == name)
+  }
+
+  /// Declare a variable `name` which holds `value`.
+  ///
+  /// In a function scope, `value` becomes the variable's register unless the
+  /// variable shadows another one in the same scope, so it must not be used
+  /// for anything else.
+  fn emit_var(&mut self, name: impl Into<Cow<'src, str>>, value: Register, span: Span) {
     let name = name.into();
     if self.is_global_scope() {
+      let src = value.access();
       if self.module.is_root {
         let name = self.constant_name(name);
-        self.builder().emit(StoreGlobal { name }, span);
+        self.builder().emit(StoreGlobal { name, src }, span);
       } else {
         let idx = self.declare_module_var(name);
-        self.builder().emit(StoreModuleVar { idx }, span);
+        self.builder().emit(StoreModuleVar { idx, src }, span);
       }
-    } else {
+    } else if self.is_declared_in_scope(&name) {
+      let src = value.access();
       let register = self.alloc_register();
-      self.emit_store(register.clone(), span);
+      self.builder().emit(
+        Mov {
+          dst: register.access(),
+          src,
+        },
+        span,
+      );
       self.declare_local(name, register);
+    } else {
+      self.declare_local(name, value);
+    }
+  }
+
+  /// Assign `value` to the variable `name`, which must already exist.
+  fn emit_assign_var(&mut self, name: &'src ast::Ident<'src>, value: Register, span: Span) {
+    match self.resolve_var(name.lexeme()) {
+      Get::Local(reg) => {
+        self.emit_move(value, Some(reg), span);
+      }
+      Get::Upvalue(idx) => {
+        let src = value.access();
+        self.builder().emit(StoreUpvalue { idx, src }, span);
+      }
+      Get::ModuleVar(idx) => {
+        let src = value.access();
+        self.builder().emit(StoreModuleVar { idx, src }, span);
+      }
+      Get::Global => {
+        let name = self.constant_name(name);
+        let src = value.access();
+        self.builder().emit(StoreGlobal { name, src }, span);
+      }
     }
   }
 
@@ -205,52 +271,103 @@ impl<'src> State<'src> {
       .map(|v| op::ModuleVar(v as u32))
   }
 
-  fn emit_get(&mut self, name: impl Into<Cow<'src, str>>, span: Span) {
+  /// Emit a read of the variable `name` into `dst`.
+  ///
+  /// Without a `dst`, a local variable is not copied, and its own register is
+  /// returned instead.
+  fn emit_get(
+    &mut self,
+    name: impl Into<Cow<'src, str>>,
+    dst: Option<Register>,
+    span: Span,
+  ) -> Register {
     let name = name.into();
     match self.resolve_var(name.clone()) {
-      Get::Local(reg) => self.builder().emit(Load { reg: reg.access() }, span),
-      Get::Upvalue(idx) => self.builder().emit(LoadUpvalue { idx }, span),
-      Get::ModuleVar(idx) => self.builder().emit(LoadModuleVar { idx }, span),
+      Get::Local(reg) => self.emit_move(reg, dst, span),
+      Get::Upvalue(idx) => {
+        let dst = self.dst(dst);
+        self.builder().emit(
+          LoadUpvalue {
+            dst: dst.access(),
+            idx,
+          },
+          span,
+        );
+        dst
+      }
+      Get::ModuleVar(idx) => {
+        let dst = self.dst(dst);
+        self.builder().emit(
+          LoadModuleVar {
+            dst: dst.access(),
+            idx,
+          },
+          span,
+        );
+        dst
+      }
       Get::Global => {
         let name = self.constant_name(name);
-        self.builder().emit(LoadGlobal { name }, span)
+        let dst = self.dst(dst);
+        self.builder().emit(
+          LoadGlobal {
+            dst: dst.access(),
+            name,
+          },
+          span,
+        );
+        dst
       }
     }
   }
 
+  /// Returns `dst`, or a new register if there is none.
+  ///
+  /// Operands should be accessed before this is called, so that the new
+  /// register may reuse one of theirs.
   #[inline]
-  fn emit_store(&mut self, register: Register, span: Span) {
-    self.builder().emit(
-      Store {
-        reg: register.access(),
-      },
-      span,
-    )
+  fn dst(&mut self, dst: Option<Register>) -> Register {
+    dst.unwrap_or_else(|| self.alloc_register())
   }
 
-  #[inline]
-  fn emit_load(&mut self, register: Register, span: Span) {
-    self.builder().emit(
-      Load {
-        reg: register.access(),
-      },
-      span,
-    );
+  /// Copy `src` into `dst`, or just return `src` if there is no `dst`.
+  fn emit_move(&mut self, src: Register, dst: Option<Register>, span: Span) -> Register {
+    match dst {
+      Some(dst) => {
+        self.builder().emit(
+          Mov {
+            dst: dst.access(),
+            src: src.access(),
+          },
+          span,
+        );
+        dst
+      }
+      None => src,
+    }
   }
 
   fn emit_function(&mut self, func: &'src ast::Func<'src>, is_init: bool) -> EmittedFunction<'src> {
+    // a decorated global function refers to itself through the global, so
+    // that recursive calls go through the decorator as well
+    let declare_callee = func.decorators.is_empty() || !self.is_global_scope();
+
     self.module.functions.push(Function::new(
       self.global.clone(),
       func.name.lexeme(),
-      function::Params::from_ast_func(func),
+      function::Params::from_ast_func(&self.global, func),
       func.has_yield,
       is_init,
     ));
+    self.current_function().doc = func
+      .doc
+      .as_ref()
+      .map(|doc| self.global.alloc(object::Str::owned(doc)));
 
     self.current_function().enter_scope();
 
     // allocate registers
-    let param_slice = self.alloc_register_slice(1 + func.params.pos.len());
+    let param_slice = self.alloc_register_slice(1 + func.params.pos.len() + func.params.kw.len());
     let (callee, receiver, positional) = match func.params.has_self {
       true => (None, Some(param_slice.get(0)), param_slice.offset(1)),
       false => (Some(param_slice.get(0)), None, param_slice.offset(1)),
@@ -263,7 +380,7 @@ impl<'src> State<'src> {
     // the point of this is to give access to:
     // - `self` in methods.
     // - the function being called in recursive functions.
-    if let Some(callee) = &callee {
+    if let Some(callee) = callee.as_ref().filter(|_| declare_callee) {
       self.declare_local(func.name.lexeme(), callee.clone());
     }
     if let Some(receiver) = &receiver {
@@ -271,43 +388,119 @@ impl<'src> State<'src> {
     }
 
     // emit default values
-    for (i, param) in func.params.pos.iter().enumerate() {
+    for (i, param) in func.params.iter().enumerate() {
       if let Some(default) = &param.default {
         let next = self.builder().label("next");
-        self.emit_load(positional.get(i), param.span());
-        self.builder().emit(IsNone, param.span());
-        self.builder().emit_jump_if_false(&next, param.span());
-        self.emit_expr(default);
-        self.emit_store(positional.get(i), param.span());
+        let src = positional.access(i);
+        let is_none = self.alloc_register();
+        self.builder().emit(
+          IsNone {
+            dst: is_none.access(),
+            src,
+          },
+          param.span(),
+        );
+        self
+          .builder()
+          .emit_jump_if_false(&next, is_none.access(), param.span());
+        self.emit_expr_into(default, positional.get(i));
         self.builder().bind_label(next);
       }
     }
 
+    // check type hints, after the defaults are applied
+    if self.global.type_checks() {
+      for (i, param) in func.params.iter().enumerate() {
+        if let Some(hint) = &param.hint {
+          let name = self.constant_name(param.name.lexeme());
+          let hint = self.constant_name(hint.lexeme());
+          self.builder().emit(
+            CheckParam {
+              param: positional.access(i),
+              name,
+              hint,
+            },
+            param.span(),
+          );
+        }
+      }
+    }
+
     // declare parameters
     // this happens *after* emitting the defaults, because the
     // defaults should not be able to access the parameters
-    for (i, param) in func.params.pos.iter().enumerate() {
+    for (i, param) in func.params.iter().enumerate() {
       self.declare_local(param.name.lexeme(), positional.get(i));
     }
 
+    if func.has_defer() {
+      let value = self.alloc_register();
+      let label = self.builder().multi_label("exit");
+      self.current_function().exit = Some(Exit { value, label });
+    }
+
+    // functions evaluate to their last statement, if that is an expression,
+    // like the root module. `init` always returns `self`, and generators only
+    // produce values through `yield`.
+    let (tail, body) = match func.body.split_last() {
+      Some((last, body)) if !is_init && !func.has_yield => match last.deref() {
+        ast::StmtKind::Expr(expr) => (Some(expr), body),
+        _ => (None, &func.body[..]),
+      },
+      _ => (None, &func.body[..]),
+    };
+
     // emit body
-    for stmt in func.body.iter() {
+    for stmt in body.iter() {
       self.emit_stmt(stmt);
     }
 
-    // all functions return `none` by default
+    // all other functions return `none` by default
     let end_span = func
       .body
       .last()
       .map(|stmt| stmt.span)
       .unwrap_or((0..0).into());
 
-    if self.current_function().is_init {
-      self.builder().emit(LoadSelf, end_span);
-    } else {
-      self.builder().emit(LoadNone, end_span);
+    let exit = self.current_function().exit.take();
+    let value = match (tail, &exit) {
+      (Some(tail), Some(exit)) => {
+        self.emit_expr_into(tail, exit.value.clone());
+        exit.value.clone()
+      }
+      (Some(tail), None) => self.emit_expr(tail),
+      (None, _) => {
+        let value = match &exit {
+          Some(exit) => exit.value.clone(),
+          None => self.alloc_register(),
+        };
+        if self.current_function().is_init {
+          self.builder().emit(
+            LoadSelf {
+              dst: value.access(),
+            },
+            end_span,
+          );
+        } else {
+          self.builder().emit(
+            LoadNone {
+              dst: value.access(),
+            },
+            end_span,
+          );
+        }
+        value
+      }
+    };
+    match exit {
+      Some(exit) => self.emit_exit(exit, end_span),
+      None => self.builder().emit(
+        Return {
+          src: value.access(),
+        },
+        end_span,
+      ),
     }
-    self.builder().emit(Return, end_span);
 
     self.current_function().leave_scope();
 
@@ -321,17 +514,108 @@ impl<'src> State<'src> {
     function
   }
 
+  /// Return `value` from the current function.
+  fn emit_return(&mut self, value: Register, span: Span) {
+    let Some(dst) = self
+      .current_function()
+      .exit
+      .as_ref()
+      .map(|exit| exit.value.clone())
+    else {
+      self.builder().emit(
+        Return {
+          src: value.access(),
+        },
+        span,
+      );
+      return;
+    };
+    self.emit_move(value, Some(dst), span);
+    let function = self.current_function();
+    let exit = function.exit.as_ref().unwrap();
+    function.builder.emit_jump(&exit.label, span);
+  }
+
+  /// Emit the block which calls the deferred functions, most recently
+  /// deferred first, and then returns the value in `exit.value`.
+  fn emit_exit(&mut self, exit: Exit, span: Span) {
+    self.builder().bind_label(exit.label);
+
+    let next = self.builder().loop_header();
+    self.builder().bind_loop_header(&next);
+    let done = self.builder().label("done");
+    let has_deferred = self.alloc_register();
+    self.builder().emit(
+      HasDeferred {
+        dst: has_deferred.access(),
+      },
+      span,
+    );
+    self
+      .builder()
+      .emit_jump_if_false(&done, has_deferred.access(), span);
+    let result = self.alloc_register();
+    self.builder().emit(
+      CallDeferred {
+        dst: result.access(),
+      },
+      span,
+    );
+    self.builder().emit_jump_loop(&next, span);
+    self.builder().bind_label(done);
+
+    self.builder().emit(
+      Return {
+        src: exit.value.access(),
+      },
+      span,
+    );
+  }
+
   fn emit_module(mut self) -> Module<'src> {
     let callee = self.alloc_register();
     self.current_function().enter_scope();
+    // the root module evaluates to its last statement, if that is an
+    // expression
+    let mut result = None;
     for stmt in self.ast.body.iter() {
-      self.emit_stmt(stmt);
+      result = match stmt.deref() {
+        ast::StmtKind::Expr(expr) => Some(self.emit_expr(expr)),
+        _ => {
+          self.emit_stmt(stmt);
+          None
+        }
+      };
     }
     self.current_function().leave_scope();
-    if !self.module.is_root {
-      self.builder().emit(FinalizeModule, 0..0);
-    }
-    self.builder().emit(Return, 0..0);
+    let value = match result {
+      Some(value) if self.module.is_root => value,
+      _ => {
+        let value = self.alloc_register();
+        if self.module.is_root {
+          self.builder().emit(
+            LoadNone {
+              dst: value.access(),
+            },
+            0..0,
+          );
+        } else {
+          self.builder().emit(
+            FinalizeModule {
+              dst: value.access(),
+            },
+            0..0,
+          );
+        }
+        value
+      }
+    };
+    self.builder().emit(
+      Return {
+        src: value.access(),
+      },
+      0..0,
+    );
     let _ = callee.access();
 
     self.module
@@ -339,20 +623,37 @@ impl<'src> State<'src> {
 }
 
 impl function::Params {
-  pub fn from_ast_func(func: &ast::Func) -> Self {
+  pub fn from_ast_func(global: &Global, func: &ast::Func) -> Self {
     let mut min = 0;
-    let mut max = 0;
-    for param in func.params.pos.iter() {
-      if param.default.is_none() {
+    let mut hints = vec![];
+    for (index, param) in func.params.iter().enumerate() {
+      if index < func.params.pos.len() && param.default.is_none() {
         min += 1;
       }
-      max += 1;
+      if let Some(hint) = &param.hint {
+        hints.push(function::ParamHint {
+          index: index as u16,
+          name: global.intern(param.name.to_string()),
+          hint: global.intern(hint.to_string()),
+        });
+      }
     }
+    let kw = func
+      .params
+      .kw
+      .iter()
+      .map(|param| function::KwParam {
+        name: global.intern(param.name.to_string()),
+        required: param.default.is_none(),
+      })
+      .collect();
 
     Self {
       has_self: func.params.has_self,
       min,
-      max,
+      max: func.params.pos.len() as u16,
+      hints: hints.into_boxed_slice(),
+      kw,
     }
   }
 }
@@ -386,8 +687,14 @@ struct Function<'src> {
 
   is_in_opt_expr: bool,
   current_loop: Option<Loop>,
+  /// Locals which are held in a string builder by an enclosing loop, see
+  /// the `append` module.
+  appended_vars: Vec<&'src str>,
+  /// Set in functions which contain a `defer` statement.
+  exit: Option<Exit>,
 
   inner_functions: Vec<Ptr<object::FunctionDescriptor>>,
+  doc: Option<Ptr<object::Str>>,
 }
 
 impl<'src> Function<'src> {
@@ -416,8 +723,11 @@ impl<'src> Function<'src> {
 
       is_in_opt_expr: false,
       current_loop: None,
+      appended_vars: Vec::new(),
+      exit: None,
 
       inner_functions: Vec::new(),
+      doc: None,
     }
   }
 
@@ -460,11 +770,17 @@ impl<'src> Function<'src> {
 
   fn finish(self) -> EmittedFunction<'src> {
     let (frame_size, register_map) = self.regalloc.finish();
-    let (mut bytecode, constants) = self.builder.finish();
+    let (mut bytecode, constants, spans) = self.builder.finish_with_spans();
 
     // patch registers in bytecode
     op::patch_registers(&mut bytecode, &register_map);
 
+    let (bytecode, constants, spans) = if self.global.optimize() {
+      optimizer::optimize(bytecode, constants, spans)
+    } else {
+      (bytecode, constants, spans)
+    };
+
     // patch registers in inner functions
     for function in self.inner_functions.iter() {
       for upvalue in function.upvalues.borrow_mut().iter_mut() {
@@ -477,22 +793,30 @@ impl<'src> Function<'src> {
       }
     }
 
-    let ptr = self.global.alloc(object::FunctionDescriptor::new(
-      self.global.intern(self.name.to_string()),
-      self.is_generator,
-      self.params,
-      self
-        .upvalues
-        .values()
-        .map(|v| match &v.src {
-          UpvalueSource::Register(register) => function::Upvalue::Register(register.access()),
-          UpvalueSource::Upvalue(index) => function::Upvalue::Upvalue(*index),
-        })
-        .collect(),
-      frame_size,
-      bytecode,
-      constants,
-    ));
+    let ptr = self.global.alloc(
+      object::FunctionDescriptor::new(
+        self.global.intern(self.name.to_string()),
+        self.is_generator,
+        self.params,
+        self
+          .upvalues
+          .values()
+          .map(|v| match &v.src {
+            UpvalueSource::Register(register) => function::Upvalue::Register(register.access()),
+            UpvalueSource::Upvalue(index) => function::Upvalue::Upvalue(*index),
+          })
+          .collect(),
+        frame_size,
+        bytecode,
+        constants,
+      )
+      .with_spans(spans)
+      .with_doc(self.doc),
+    );
+    #[cfg(debug_assertions)]
+    if let Err(e) = super::bytecode::verify::verify_function(&ptr) {
+      panic!("emitted invalid bytecode for `{}`: {e}", self.name);
+    }
     let upvalues = Upvalues(self.upvalues);
 
     EmittedFunction { ptr, upvalues }
@@ -533,6 +857,14 @@ struct Loop {
   end: MultiLabel,
 }
 
+/// The block at the end of a function which calls its deferred functions
+/// before returning. Every `return` jumps here instead of returning directly.
+struct Exit {
+  /// Holds the return value while the deferred functions are called.
+  value: Register,
+  label: MultiLabel,
+}
+
 #[repr(transparent)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Scope(usize);
@@ -0,0 +1,252 @@
+//! Finds the string variables which a loop builds up with `+=`.
+//!
+//! Strings are immutable, so `s += x` in a loop copies all of `s` on every
+//! iteration. If the loop does nothing else with `s`, the emitter keeps it in
+//! a string builder for the duration of the loop instead, using `AppendStr`
+//! for each `s += x`, and `BuildStr` to turn it back into a string after the
+//! loop. Nothing can observe the builder, because nothing reads `s` until
+//! the loop ends.
+
+use std::ops::Deref;
+
+use indexmap::IndexSet;
+
+use super::ast;
+
+/// Returns the variables which are only used by statements of the form
+/// `name += value` in `stmt`, where `value` does not use `name` either.
+///
+/// The variables are not resolved, so the caller must check that each of
+/// them refers to a local of the function which contains the loop.
+pub(super) fn appended_vars<'a>(stmt: &'a ast::Loop<'_>) -> Vec<&'a str> {
+  let mut uses = Uses::default();
+  uses.loop_(stmt);
+  uses
+    .appended
+    .into_iter()
+    .filter(|name| !uses.other.contains(name))
+    .collect()
+}
+
+/// Returns `value` if `set` is `name += value`, which is parsed as
+/// `name = name + value`.
+pub(super) fn appended_value<'a, 'src>(set: &'a ast::SetVar<'src>) -> Option<&'a ast::Expr<'src>> {
+  let ast::ExprKind::Binary(binary) = set.value.deref() else {
+    return None;
+  };
+  let ast::ExprKind::GetVar(get) = binary.left.deref() else {
+    return None;
+  };
+  if !matches!(binary.op, ast::BinaryOp::Add) || get.name.as_str() != set.target.name.as_str() {
+    return None;
+  }
+  Some(&binary.right)
+}
+
+#[derive(Default)]
+struct Uses<'a> {
+  appended: IndexSet<&'a str>,
+  other: IndexSet<&'a str>,
+  /// Inside a nested function, appends are uses like any other.
+  depth: usize,
+}
+
+impl<'a> Uses<'a> {
+  fn name(&mut self, name: &'a ast::Ident<'_>) {
+    self.other.insert(name.as_str());
+  }
+
+  fn stmt_list(&mut self, list: &'a [ast::Stmt<'_>]) {
+    for stmt in list {
+      self.stmt(stmt);
+    }
+  }
+
+  fn stmt(&mut self, stmt: &'a ast::Stmt<'_>) {
+    match stmt.deref() {
+      ast::StmtKind::Var(v) => {
+        self.expr(&v.value);
+        self.name(&v.name);
+      }
+      ast::StmtKind::Unpack(v) => {
+        self.expr(&v.value);
+        for target in v.targets.iter() {
+          self.name(target);
+        }
+      }
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.stmt_list(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.stmt_list(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => self.loop_(v),
+      ast::StmtKind::Ctrl(v) => match v.deref() {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Raise(v) => self.expr(&v.value),
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => {
+        self.name(&v.name);
+        self.function(v);
+      }
+      ast::StmtKind::Class(v) => self.class(v),
+      ast::StmtKind::Expr(v) => self.expr_stmt(v),
+      ast::StmtKind::Pass => {}
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Import(v) => match v.deref() {
+        ast::Import::Module { path, alias } => {
+          self.name(alias.as_ref().unwrap_or(path.segments.last().unwrap()));
+        }
+        ast::Import::Symbols { symbols, .. } => {
+          for symbol in symbols {
+            self.name(symbol.alias.as_ref().unwrap_or(&symbol.name));
+          }
+        }
+      },
+      ast::StmtKind::Defer(v) => self.call(&v.call),
+    }
+  }
+
+  fn expr_stmt(&mut self, expr: &'a ast::Expr<'_>) {
+    if let ast::ExprKind::SetVar(set) = &**expr {
+      if let Some(value) = appended_value(set).filter(|_| self.depth == 0) {
+        self.appended.insert(set.target.name.as_str());
+        self.expr(value);
+        return;
+      }
+    }
+    self.expr(expr);
+  }
+
+  fn loop_(&mut self, stmt: &'a ast::Loop<'_>) {
+    match stmt {
+      ast::Loop::For(v) => {
+        for item in v.items.iter() {
+          self.name(item);
+        }
+        match &v.iter {
+          ast::ForIter::Range(range) => {
+            self.expr(&range.start);
+            self.expr(&range.end);
+          }
+          ast::ForIter::Expr(iter) => self.expr(iter),
+        }
+        self.stmt_list(&v.body);
+      }
+      ast::Loop::While(v) => {
+        self.expr(&v.cond);
+        self.stmt_list(&v.body);
+      }
+      ast::Loop::Infinite(v) => self.stmt_list(&v.body),
+    }
+  }
+
+  fn function(&mut self, func: &'a ast::Func<'_>) {
+    for decorator in func.decorators.iter() {
+      self.expr(decorator);
+    }
+    self.depth += 1;
+    for param in func.params.iter() {
+      self.name(&param.name);
+      if let Some(default) = &param.default {
+        self.expr(default);
+      }
+    }
+    self.stmt_list(&func.body);
+    self.depth -= 1;
+  }
+
+  fn class(&mut self, class: &'a ast::Class<'_>) {
+    for decorator in class.decorators.iter() {
+      self.expr(decorator);
+    }
+    self.name(&class.name);
+    for name in class.parent.iter().chain(class.mixins.iter()) {
+      self.name(name);
+    }
+    let members = &class.members;
+    for field in members.fields.iter().chain(members.static_fields.iter()) {
+      self.expr(&field.default);
+    }
+    let methods = members
+      .init
+      .iter()
+      .chain(members.methods.iter())
+      .chain(members.static_methods.iter());
+    for method in methods {
+      self.function(method);
+    }
+  }
+
+  fn call(&mut self, call: &'a ast::Call<'_>) {
+    self.expr(&call.target);
+    for arg in call.args.iter() {
+      self.expr(arg);
+    }
+    for (_, value) in call.kwargs.iter() {
+      self.expr(value);
+    }
+  }
+
+  fn expr(&mut self, expr: &'a ast::Expr<'_>) {
+    match expr.deref() {
+      ast::ExprKind::Literal(v) => match v.deref() {
+        ast::Literal::List(items) | ast::Literal::Tuple(items) => {
+          for item in items {
+            self.expr(item);
+          }
+        }
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries {
+            self.expr(key);
+            self.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        self.expr(&v.left);
+        self.expr(&v.right);
+      }
+      ast::ExprKind::Unary(v) => self.expr(&v.right),
+      ast::ExprKind::Conditional(v) => {
+        self.expr(&v.cond);
+        self.expr(&v.then);
+        self.expr(&v.otherwise);
+      }
+      ast::ExprKind::GetVar(v) => self.name(&v.name),
+      ast::ExprKind::SetVar(v) => {
+        self.expr(&v.value);
+        self.name(&v.target.name);
+      }
+      ast::ExprKind::GetField(v) => self.expr(&v.target),
+      ast::ExprKind::SetField(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        self.expr(&v.target);
+        self.expr(&v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.target.key);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::Call(v) => self.call(v),
+      ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    }
+  }
+}
@@ -1,133 +1,298 @@
 use super::*;
-use crate::internal::value::constant::NonNaNFloat;
+#[cfg(feature = "bigint")]
+use crate::internal::object::bigint::BigInt;
+use crate::internal::object::int::BoxedInt;
+use crate::internal::object::Bytes;
+use crate::internal::value::constant::{KwNames, NonNaNFloat};
 
 impl<'src> State<'src> {
-  pub fn emit_expr(&mut self, expr: &'src ast::Expr<'src>) {
+  /// Emit `expr` and return the register which holds its result.
+  ///
+  /// The register may belong to a local variable, so it must not be written
+  /// to.
+  pub fn emit_expr(&mut self, expr: &'src ast::Expr<'src>) -> Register {
+    self.emit_expr_to(expr, None)
+  }
+
+  /// Emit `expr` and store its result in `dst`.
+  ///
+  /// `dst` must not be readable by `expr`, e.g. because it is a slot in an
+  /// argument window which has not been written yet. This allows expressions
+  /// to write `dst` before they are done reading their operands.
+  pub fn emit_expr_into(&mut self, expr: &'src ast::Expr<'src>, dst: Register) {
+    let _ = self.emit_expr_to(expr, Some(dst));
+  }
+
+  /// Emit `expr` and store its result in `dst`, or in a new register if there
+  /// is no `dst`. Returns the register which holds the result.
+  fn emit_expr_to(&mut self, expr: &'src ast::Expr<'src>, dst: Option<Register>) -> Register {
     match &**expr {
-      ast::ExprKind::Literal(v) => self.emit_literal_expr(v, expr.span),
-      ast::ExprKind::Binary(v) => self.emit_binary_expr(v, expr.span),
-      ast::ExprKind::Unary(v) => self.emit_unary_expr(v, expr.span),
-      ast::ExprKind::GetVar(v) => self.emit_get_var_expr(v, expr.span),
-      ast::ExprKind::SetVar(v) => self.emit_set_var_expr(v, expr.span),
-      ast::ExprKind::GetField(v) => self.emit_get_field_expr(v, expr.span),
-      ast::ExprKind::SetField(v) => self.emit_set_field_expr(v, expr.span),
-      ast::ExprKind::GetIndex(v) => self.emit_get_index_expr(v, expr.span),
-      ast::ExprKind::SetIndex(v) => self.emit_set_index_expr(v, expr.span),
-      ast::ExprKind::Call(v) => self.emit_call_expr(v, expr.span),
-      ast::ExprKind::GetSelf => self.emit_get_self_expr(expr.span),
-      ast::ExprKind::GetSuper => self.emit_get_super_expr(expr.span),
+      ast::ExprKind::Literal(v) => self.emit_literal_expr(v, expr.span, dst),
+      ast::ExprKind::Binary(v) => self.emit_binary_expr(v, expr.span, dst),
+      ast::ExprKind::Unary(v) => self.emit_unary_expr(v, expr.span, dst),
+      ast::ExprKind::Conditional(v) => self.emit_conditional_expr(v, expr.span, dst),
+      ast::ExprKind::GetVar(v) => self.emit_get_var_expr(v, expr.span, dst),
+      ast::ExprKind::SetVar(v) => self.emit_set_var_expr(v, expr.span, dst),
+      ast::ExprKind::GetField(v) => self.emit_get_field_expr(v, expr.span, dst),
+      ast::ExprKind::SetField(v) => self.emit_set_field_expr(v, expr.span, dst),
+      ast::ExprKind::GetIndex(v) => self.emit_get_index_expr(v, expr.span, dst),
+      ast::ExprKind::SetIndex(v) => self.emit_set_index_expr(v, expr.span, dst),
+      ast::ExprKind::Call(v) => self.emit_call_expr(v, expr.span, dst),
+      ast::ExprKind::GetSelf => self.emit_get_self_expr(expr.span, dst),
+      ast::ExprKind::GetSuper => self.emit_get_super_expr(expr.span, dst),
     }
   }
 
-  fn emit_literal_expr(&mut self, expr: &'src ast::Literal<'src>, span: Span) {
+  fn emit_literal_expr(
+    &mut self,
+    expr: &'src ast::Literal<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
     match expr {
-      ast::Literal::None => self.builder().emit(LoadNone, span),
-      ast::Literal::Int(v) => self.builder().emit(LoadSmi { value: op::Smi(*v) }, span),
+      ast::Literal::None => {
+        let dst = self.dst(dst);
+        self.builder().emit(LoadNone { dst: dst.access() }, span);
+        dst
+      }
+      ast::Literal::Int(v) => match i32::try_from(*v) {
+        Ok(v) => {
+          let dst = self.dst(dst);
+          self.builder().emit(
+            LoadSmi {
+              dst: dst.access(),
+              value: op::Smi(v),
+            },
+            span,
+          );
+          dst
+        }
+        Err(_) => {
+          // ints which don't fit in a smi are boxed
+          let int = self.global.alloc(BoxedInt::new(*v));
+          let num = self.constant_value(int);
+          self.emit_load_const(num, span, dst)
+        }
+      },
+      #[cfg(feature = "bigint")]
+      ast::Literal::BigInt(v) => {
+        // the parser only produces decimal digits
+        let value = v.parse().unwrap();
+        let int = self.global.alloc(BigInt::new(value));
+        let num = self.constant_value(int);
+        self.emit_load_const(num, span, dst)
+      }
+      #[cfg(not(feature = "bigint"))]
+      ast::Literal::BigInt(_) => unreachable!("bigint literals are rejected by the parser"),
       ast::Literal::Float(v) => {
         // float is 4 bits so cannot be stored inline,
         // but it is interned
         let num = self.constant_value(NonNaNFloat::try_from(*v).unwrap());
-        self.builder().emit(LoadConst { idx: num }, span);
+        self.emit_load_const(num, span, dst)
+      }
+      ast::Literal::Bool(v) => {
+        let dst = self.dst(dst);
+        match v {
+          true => self.builder().emit(LoadTrue { dst: dst.access() }, span),
+          false => self.builder().emit(LoadFalse { dst: dst.access() }, span),
+        }
+        dst
       }
-      ast::Literal::Bool(v) => match v {
-        true => self.builder().emit(LoadTrue, span),
-        false => self.builder().emit(LoadFalse, span),
-      },
       ast::Literal::String(v) => {
         // `const_` interns the string
         let str = self.constant_name(v);
-        self.builder().emit(LoadConst { idx: str }, span);
+        self.emit_load_const(str, span, dst)
+      }
+      ast::Literal::Bytes(v) => {
+        // bytes are mutable, so the constant is copied on each evaluation
+        let data = self.constant_value(self.global.alloc(Bytes::from(v.clone())));
+        let dst = self.dst(dst);
+        self.builder().emit(
+          MakeBytes {
+            dst: dst.access(),
+            data,
+          },
+          span,
+        );
+        dst
       }
       ast::Literal::List(list) => {
         if list.is_empty() {
-          self.builder().emit(MakeListEmpty, span);
-          return;
+          let dst = self.dst(dst);
+          self
+            .builder()
+            .emit(MakeListEmpty { dst: dst.access() }, span);
+          return dst;
         }
 
         let items = self.alloc_register_slice(list.len());
 
         for (i, value) in list.iter().enumerate() {
-          self.emit_expr(value);
-          self.emit_store(items.get(i), value.span);
+          self.emit_expr_into(value, items.get(i));
         }
+        let start = items.access(0);
+        let dst = self.dst(dst);
         self.builder().emit(
           MakeList {
-            start: items.access(0),
+            dst: dst.access(),
+            start,
             count: op::Count(list.len() as u32),
           },
           span,
         );
-        /* for register in registers.iter().rev() {
-          register.access();
-        } */
+        dst
+      }
+      ast::Literal::Tuple(tuple) => {
+        if tuple.is_empty() {
+          let dst = self.dst(dst);
+          self
+            .builder()
+            .emit(MakeTupleEmpty { dst: dst.access() }, span);
+          return dst;
+        }
+
+        let items = self.alloc_register_slice(tuple.len());
+
+        for (i, value) in tuple.iter().enumerate() {
+          self.emit_expr_into(value, items.get(i));
+        }
+        let start = items.access(0);
+        let dst = self.dst(dst);
+        self.builder().emit(
+          MakeTuple {
+            dst: dst.access(),
+            start,
+            count: op::Count(tuple.len() as u32),
+          },
+          span,
+        );
+        dst
       }
       ast::Literal::Table(table) => {
         if table.is_empty() {
-          self.builder().emit(MakeTableEmpty, span);
-          return;
+          let dst = self.dst(dst);
+          self
+            .builder()
+            .emit(MakeTableEmpty { dst: dst.access() }, span);
+          return dst;
         }
 
         // TODO: from descriptor
         let pairs = self.alloc_register_slice(table.len() * 2);
 
         for (i, (key, value)) in table.iter().enumerate() {
-          self.emit_expr(key);
-          self.emit_store(pairs.get(i * 2), key.span);
-          self.emit_expr(value);
-          self.emit_store(pairs.get(i * 2 + 1), value.span);
+          self.emit_expr_into(key, pairs.get(i * 2));
+          self.emit_expr_into(value, pairs.get(i * 2 + 1));
         }
+        let start = pairs.access(0);
+        let dst = self.dst(dst);
         self.builder().emit(
           MakeTable {
-            start: pairs.access(0),
+            dst: dst.access(),
+            start,
             count: op::Count(table.len() as u32),
           },
           span,
         );
-        /* for (key, value) in registers.iter().rev() {
-          value.access();
-          key.access();
-        } */
+        dst
       }
     }
   }
 
-  fn emit_binary_expr(&mut self, expr: &'src ast::Binary<'src>, span: Span) {
-    // binary expressions store lhs in a register,
-    // and rhs in the accumulator
+  fn emit_load_const(&mut self, idx: op::Constant, span: Span, dst: Option<Register>) -> Register {
+    let dst = self.dst(dst);
+    self.builder().emit(
+      LoadConst {
+        dst: dst.access(),
+        idx,
+      },
+      span,
+    );
+    dst
+  }
 
+  fn emit_binary_expr(
+    &mut self,
+    expr: &'src ast::Binary<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
     match expr.op {
       ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe => {
-        return self.emit_logical_expr(expr, span)
+        return self.emit_logical_expr(expr, span, dst)
       }
       _ => {}
     }
 
-    let lhs = self.alloc_register();
-    self.emit_expr(&expr.left);
-    self.emit_store(lhs.clone(), expr.left.span);
-    self.emit_expr(&expr.right);
+    // locals are used as operands directly instead of being copied into a
+    // temporary register, because assignments are statements, so evaluating
+    // the rhs can never change the value of the local
+    let lhs = self.emit_expr(&expr.left);
+    let rhs = self.emit_expr(&expr.right);
 
-    let lhs = lhs.access();
-    match expr.op {
-      ast::BinaryOp::Add => self.builder().emit(Add { lhs }, span),
-      ast::BinaryOp::Sub => self.builder().emit(Sub { lhs }, span),
-      ast::BinaryOp::Div => self.builder().emit(Div { lhs }, span),
-      ast::BinaryOp::Mul => self.builder().emit(Mul { lhs }, span),
-      ast::BinaryOp::Rem => self.builder().emit(Rem { lhs }, span),
-      ast::BinaryOp::Pow => self.builder().emit(Pow { lhs }, span),
-      ast::BinaryOp::Eq => self.builder().emit(CmpEq { lhs }, span),
-      ast::BinaryOp::Neq => self.builder().emit(CmpNe { lhs }, span),
-      ast::BinaryOp::More => self.builder().emit(CmpGt { lhs }, span),
-      ast::BinaryOp::MoreEq => self.builder().emit(CmpGe { lhs }, span),
-      ast::BinaryOp::Less => self.builder().emit(CmpLt { lhs }, span),
-      ast::BinaryOp::LessEq => self.builder().emit(CmpLe { lhs }, span),
-      ast::BinaryOp::Is => self.builder().emit(CmpType { lhs }, span),
-      ast::BinaryOp::In => self.builder().emit(Contains { lhs }, span),
+    let (lhs, rhs) = (lhs.access(), rhs.access());
+    let dst = self.dst(dst);
+    self.emit_binary_op(expr.op, dst.access(), lhs, rhs, span);
+    dst
+  }
+
+  fn emit_binary_op(
+    &mut self,
+    op: ast::BinaryOp,
+    d: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+    span: Span,
+  ) {
+    match op {
+      ast::BinaryOp::Add => self.builder().emit(Add { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Sub => self.builder().emit(Sub { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Div => self.builder().emit(Div { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Mul => self.builder().emit(Mul { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::FloorDiv => self.builder().emit(FloorDiv { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Rem => self.builder().emit(Rem { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Pow => self.builder().emit(Pow { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Eq => self.builder().emit(CmpEq { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Neq => self.builder().emit(CmpNe { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::More => self.builder().emit(CmpGt { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::MoreEq => self.builder().emit(CmpGe { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Less => self.builder().emit(CmpLt { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::LessEq => self.builder().emit(CmpLe { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::Is => self.builder().emit(CmpType { dst: d, lhs, rhs }, span),
+      ast::BinaryOp::In => self.builder().emit(Contains { dst: d, lhs, rhs }, span),
       ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe => unreachable!(),
     }
   }
 
-  fn emit_logical_expr(&mut self, expr: &'src ast::Binary<'src>, span: Span) {
+  /// Logical expressions write their result before they are done reading
+  /// their operands, so they may only be emitted into a register which is
+  /// not readable by `expr`.
+  pub(super) fn writes_dst_early(expr: &ast::Expr<'src>) -> bool {
+    match &**expr {
+      ast::ExprKind::Binary(v) => matches!(
+        v.op,
+        ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe
+      ),
+      ast::ExprKind::Unary(v) => {
+        matches!(v.op, ast::UnaryOp::Plus | ast::UnaryOp::Opt) && Self::writes_dst_early(&v.right)
+      }
+      ast::ExprKind::Conditional(v) => {
+        Self::writes_dst_early(&v.then) || Self::writes_dst_early(&v.otherwise)
+      }
+      _ => false,
+    }
+  }
+
+  /// `&&`, `||` and `??` short-circuit, and evaluate to the operand which
+  /// decided the result instead of converting it to a `bool`, so
+  /// `0 || "x"` is `"x"`, and `none && f()` is `none` without calling `f`.
+  fn emit_logical_expr(
+    &mut self,
+    expr: &'src ast::Binary<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    let dst = self.dst(dst);
     match expr.op {
       ast::BinaryOp::And => {
         /*
@@ -136,17 +301,10 @@ impl<'src> State<'src> {
           if v:
             v = <right>
         */
-        let lhs = self.alloc_register();
-        let use_lhs = self.builder().label("lhs");
         let end = self.builder().label("end");
-        self.emit_expr(&expr.left);
-        self.emit_store(lhs.clone(), expr.left.span);
-        self.emit_load(lhs.clone(), expr.left.span);
-        self.builder().emit_jump_if_false(&use_lhs, span);
-        self.emit_expr(&expr.right);
-        self.builder().emit_jump(&end, span);
-        self.builder().bind_label(use_lhs);
-        self.emit_load(lhs, expr.left.span);
+        self.emit_expr_into(&expr.left, dst.clone());
+        self.builder().emit_jump_if_false(&end, dst.access(), span);
+        self.emit_expr_into(&expr.right, dst.clone());
         self.builder().bind_label(end);
       }
       ast::BinaryOp::Or => {
@@ -156,17 +314,13 @@ impl<'src> State<'src> {
           if !v:
             v = <right>
         */
-        let lhs = self.alloc_register();
         let rhs = self.builder().label("rhs");
         let end = self.builder().label("end");
-        self.emit_expr(&expr.left);
-        self.emit_store(lhs.clone(), expr.left.span);
-        self.emit_load(lhs.clone(), expr.left.span);
-        self.builder().emit_jump_if_false(&rhs, span);
-        self.emit_load(lhs, expr.left.span);
+        self.emit_expr_into(&expr.left, dst.clone());
+        self.builder().emit_jump_if_false(&rhs, dst.access(), span);
         self.builder().emit_jump(&end, span);
         self.builder().bind_label(rhs);
-        self.emit_expr(&expr.right);
+        self.emit_expr_into(&expr.right, dst.clone());
         self.builder().bind_label(end);
       }
       ast::BinaryOp::Maybe => {
@@ -176,154 +330,430 @@ impl<'src> State<'src> {
           if v is none:
             v = <right>
         */
-        let use_lhs = self.builder().label("lhs");
         let end = self.builder().label("end");
-        let lhs = self.alloc_register();
-        self.emit_expr(&expr.left);
-        self.emit_store(lhs.clone(), expr.left.span);
-        self.emit_load(lhs.clone(), expr.left.span);
-        self.builder().emit(IsNone, span);
-        self.builder().emit_jump_if_false(&use_lhs, span);
-        self.emit_expr(&expr.right);
-        self.builder().emit_jump(&end, span);
-        self.builder().bind_label(use_lhs);
-        self.emit_load(lhs, span);
+        self.emit_expr_into(&expr.left, dst.clone());
+        let src = dst.access();
+        let is_none = self.alloc_register();
+        self.builder().emit(
+          IsNone {
+            dst: is_none.access(),
+            src,
+          },
+          span,
+        );
+        self
+          .builder()
+          .emit_jump_if_false(&end, is_none.access(), span);
+        self.emit_expr_into(&expr.right, dst.clone());
         self.builder().bind_label(end);
       }
       _ => unreachable!("not a logical expr: {:?}", expr.op),
     }
+    dst
   }
 
-  fn emit_unary_expr(&mut self, expr: &'src ast::Unary<'src>, span: Span) {
-    // unary expressions only use the accumulator
+  fn emit_conditional_expr(
+    &mut self,
+    expr: &'src ast::Conditional<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    /*
+      <then> if <cond> else <otherwise>
+      if <cond>:
+        v = <then>
+      else:
+        v = <otherwise>
+    */
+    let dst = self.dst(dst);
+    let otherwise = self.builder().label("otherwise");
+    let end = self.builder().label("end");
+    let cond = self.emit_expr(&expr.cond);
+    self
+      .builder()
+      .emit_jump_if_false(&otherwise, cond.access(), span);
+    self.emit_expr_into(&expr.then, dst.clone());
+    self.builder().emit_jump(&end, span);
+    self.builder().bind_label(otherwise);
+    self.emit_expr_into(&expr.otherwise, dst.clone());
+    self.builder().bind_label(end);
+    dst
+  }
 
-    if matches!(expr.op, ast::UnaryOp::Opt) {
-      return self.emit_opt_expr(expr);
+  fn emit_unary_expr(
+    &mut self,
+    expr: &'src ast::Unary<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    match expr.op {
+      ast::UnaryOp::Plus => return self.emit_expr_to(&expr.right, dst),
+      ast::UnaryOp::Opt => return self.emit_opt_expr(expr, dst),
+      _ => {}
     }
 
-    self.emit_expr(&expr.right);
+    let src = self.emit_expr(&expr.right).access();
+    let dst = self.dst(dst);
 
     match expr.op {
-      ast::UnaryOp::Plus => {}
-      ast::UnaryOp::Minus => self.builder().emit(Inv, span),
-      ast::UnaryOp::Not => self.builder().emit(Not, span),
-      ast::UnaryOp::Opt => unreachable!(),
+      ast::UnaryOp::Minus => self.builder().emit(
+        Inv {
+          dst: dst.access(),
+          src,
+        },
+        span,
+      ),
+      ast::UnaryOp::Not => self.builder().emit(
+        Not {
+          dst: dst.access(),
+          src,
+        },
+        span,
+      ),
+      ast::UnaryOp::Plus | ast::UnaryOp::Opt => unreachable!(),
     }
+    dst
   }
 
-  fn emit_opt_expr(&mut self, expr: &'src ast::Unary<'src>) {
+  fn emit_opt_expr(&mut self, expr: &'src ast::Unary<'src>, dst: Option<Register>) -> Register {
     assert!(matches!(expr.op, ast::UnaryOp::Opt));
 
     // - emit_call_expr <- with receiver, `CallMethodOpt` or similar
 
     let prev = std::mem::replace(&mut self.current_function().is_in_opt_expr, true);
-    self.emit_expr(&expr.right);
+    let dst = self.emit_expr_to(&expr.right, dst);
     let _ = std::mem::replace(&mut self.current_function().is_in_opt_expr, prev);
+    dst
   }
 
-  fn emit_get_var_expr(&mut self, expr: &'src ast::GetVar<'src>, span: Span) {
-    self.emit_get(expr.name.lexeme(), span);
+  fn emit_get_var_expr(
+    &mut self,
+    expr: &'src ast::GetVar<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    self.emit_get(expr.name.lexeme(), dst, span)
   }
 
-  fn emit_set_var_expr(&mut self, expr: &'src ast::SetVar<'src>, span: Span) {
-    self.emit_expr(&expr.value);
-    match self.resolve_var(expr.target.name.lexeme()) {
-      Get::Local(reg) => self.builder().emit(Store { reg: reg.access() }, span),
-      Get::Upvalue(idx) => self.builder().emit(StoreUpvalue { idx }, span),
-      Get::ModuleVar(idx) => self.builder().emit(StoreModuleVar { idx }, span),
+  /// Returns `value` if `expr` is `name += value`, and `name` is held in a
+  /// string builder by an enclosing loop.
+  fn appended_value(&mut self, expr: &'src ast::SetVar<'src>) -> Option<&'src ast::Expr<'src>> {
+    let name = expr.target.name.as_str();
+    if !self.current_function().appended_vars.contains(&name) {
+      return None;
+    }
+    append::appended_value(expr)
+  }
+
+  fn emit_set_var_expr(
+    &mut self,
+    expr: &'src ast::SetVar<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    let value = match self.resolve_var(expr.target.name.lexeme()) {
+      Get::Local(reg) => {
+        if let Some(value) = self.appended_value(expr) {
+          let value = self.emit_expr(value);
+          let (lhs, rhs) = (reg.access(), value.access());
+          self.builder().emit(
+            AppendStr {
+              dst: reg.access(),
+              lhs,
+              rhs,
+            },
+            span,
+          );
+          reg
+        } else if Self::writes_dst_early(&expr.value) {
+          let value = self.emit_expr(&expr.value);
+          self.emit_move(value, Some(reg), span)
+        } else {
+          self.emit_expr_to(&expr.value, Some(reg))
+        }
+      }
+      Get::Upvalue(idx) => {
+        let value = self.emit_expr(&expr.value);
+        let src = value.access();
+        self.builder().emit(StoreUpvalue { idx, src }, span);
+        value
+      }
+      Get::ModuleVar(idx) => {
+        let value = self.emit_expr(&expr.value);
+        let src = value.access();
+        self.builder().emit(StoreModuleVar { idx, src }, span);
+        value
+      }
       Get::Global => {
         let name = self.constant_name(&expr.target.name);
-        self.builder().emit(StoreGlobal { name }, span);
+        let value = self.emit_expr(&expr.value);
+        let src = value.access();
+        self.builder().emit(StoreGlobal { name, src }, span);
+        value
       }
-    }
+    };
+    self.emit_move(value, dst, span)
   }
 
-  fn emit_get_field_expr(&mut self, expr: &'src ast::GetField<'src>, span: Span) {
+  fn emit_get_field_expr(
+    &mut self,
+    expr: &'src ast::GetField<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
     let name = self.constant_name(&expr.name);
-    self.emit_expr(&expr.target);
+    let obj = self.emit_expr(&expr.target).access();
+    let dst = self.dst(dst);
     if self.current_function().is_in_opt_expr {
-      self.builder().emit(LoadFieldOpt { name }, span);
+      self.builder().emit(
+        LoadFieldOpt {
+          dst: dst.access(),
+          obj,
+          name,
+        },
+        span,
+      );
     } else {
-      self.builder().emit(LoadField { name }, span);
+      self.builder().emit(
+        LoadField {
+          dst: dst.access(),
+          obj,
+          name,
+        },
+        span,
+      );
     }
+    dst
   }
 
-  fn emit_set_field_expr(&mut self, expr: &'src ast::SetField<'src>, span: Span) {
-    let obj = self.alloc_register();
+  fn emit_set_field_expr(
+    &mut self,
+    expr: &'src ast::SetField<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
     let get = &expr.target;
     let name = self.constant_name(&get.name);
-    self.emit_expr(&get.target);
-    self.emit_store(obj.clone(), get.target.span);
-    self.emit_expr(&expr.value);
+    let obj = self.emit_expr(&get.target);
+    let value = match expr.op {
+      Some(op) => {
+        let current = self.alloc_register();
+        self.builder().emit(
+          LoadField {
+            dst: current.access(),
+            obj: obj.access(),
+            name,
+          },
+          span,
+        );
+        self.emit_compound_value(op, current, &expr.value, span)
+      }
+      None => self.emit_expr(&expr.value),
+    };
     self.builder().emit(
       StoreField {
         obj: obj.access(),
         name,
+        src: value.access(),
       },
       span,
     );
+    self.emit_move(value, dst, span)
   }
 
-  fn emit_get_index_expr(&mut self, expr: &'src ast::GetIndex<'src>, span: Span) {
-    let obj = self.alloc_register();
-    self.emit_expr(&expr.target);
-    self.emit_store(obj.clone(), expr.target.span);
-    self.emit_expr(&expr.key);
+  fn emit_get_index_expr(
+    &mut self,
+    expr: &'src ast::GetIndex<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    let obj = self.emit_expr(&expr.target);
+    let key = self.emit_expr(&expr.key);
+    let (obj, key) = (obj.access(), key.access());
+    let dst = self.dst(dst);
     if self.current_function().is_in_opt_expr {
-      self
-        .builder()
-        .emit(LoadIndexOpt { obj: obj.access() }, span);
+      self.builder().emit(
+        LoadIndexOpt {
+          dst: dst.access(),
+          obj,
+          key,
+        },
+        span,
+      );
     } else {
-      self.builder().emit(LoadIndex { obj: obj.access() }, span);
+      self.builder().emit(
+        LoadIndex {
+          dst: dst.access(),
+          obj,
+          key,
+        },
+        span,
+      );
     }
+    dst
   }
 
-  fn emit_set_index_expr(&mut self, expr: &'src ast::SetIndex<'src>, span: Span) {
+  fn emit_set_index_expr(
+    &mut self,
+    expr: &'src ast::SetIndex<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
     let get = &expr.target;
-    let obj = self.alloc_register();
-    let key = self.alloc_register();
-    self.emit_expr(&get.target);
-    self.emit_store(obj.clone(), get.target.span);
-    self.emit_expr(&get.key);
-    self.emit_store(key.clone(), get.key.span);
-    self.emit_expr(&expr.value);
+    let obj = self.emit_expr(&get.target);
+    let key = self.emit_expr(&get.key);
+    let value = match expr.op {
+      Some(op) => {
+        let current = self.alloc_register();
+        self.builder().emit(
+          LoadIndex {
+            dst: current.access(),
+            obj: obj.access(),
+            key: key.access(),
+          },
+          span,
+        );
+        self.emit_compound_value(op, current, &expr.value, span)
+      }
+      None => self.emit_expr(&expr.value),
+    };
     self.builder().emit(
       StoreIndex {
         obj: obj.access(),
         key: key.access(),
+        src: value.access(),
       },
       span,
     );
+    self.emit_move(value, dst, span)
   }
 
-  fn emit_call_expr(&mut self, expr: &'src ast::Call<'src>, span: Span) {
-    self.emit_expr(&expr.target);
-    if expr.args.is_empty() {
-      self.builder().emit(Call0, span);
+  /// Emit `<current> <op> <value>` for a compound assignment, where `current`
+  /// holds the value of the target, and is overwritten with the result.
+  fn emit_compound_value(
+    &mut self,
+    op: ast::AssignOp,
+    current: Register,
+    value: &'src ast::Expr<'src>,
+    span: Span,
+  ) -> Register {
+    match op {
+      ast::AssignOp::Maybe => {
+        /*
+          <target> ??= <value>
+          v = <target>
+          if v is none:
+            v = <value>
+          <target> = v
+        */
+        let end = self.builder().label("end");
+        let is_none = self.alloc_register();
+        self.builder().emit(
+          IsNone {
+            dst: is_none.access(),
+            src: current.access(),
+          },
+          span,
+        );
+        self
+          .builder()
+          .emit_jump_if_false(&end, is_none.access(), span);
+        self.emit_expr_into(value, current.clone());
+        self.builder().bind_label(end);
+      }
+      _ => {
+        let rhs = self.emit_expr(value).access();
+        let lhs = current.access();
+        self.emit_binary_op(op.into(), current.access(), lhs, rhs, span);
+      }
+    }
+    current
+  }
+
+  fn emit_call_expr(
+    &mut self,
+    expr: &'src ast::Call<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    if !expr.kwargs.is_empty() {
+      return self.emit_call_kw_expr(expr, span, dst);
+    }
+
+    // the callee is followed by the arguments, so it only has to be copied
+    // into a new register if there are any
+    let callee = if expr.args.is_empty() {
+      self.emit_expr(&expr.target).access()
     } else {
       let args = self.alloc_register_slice(1 + expr.args.len());
-      let callee = args.get(0);
-      self.emit_store(callee.clone(), expr.target.span);
+      self.emit_expr_into(&expr.target, args.get(0));
       for (i, value) in expr.args.iter().enumerate() {
-        self.emit_expr(value);
-        self.emit_store(args.get(1 + i), value.span);
+        self.emit_expr_into(value, args.get(1 + i));
       }
+      args.access(0)
+    };
 
-      self.builder().emit(
-        Call {
-          callee: callee.access(),
-          args: op::Count(expr.args.len() as u32),
-        },
-        span,
-      );
+    let dst = self.dst(dst);
+    self.builder().emit(
+      Call {
+        dst: dst.access(),
+        callee,
+        args: op::Count(expr.args.len() as u32),
+      },
+      span,
+    );
+    dst
+  }
+
+  /// The values of keyword arguments follow the positional ones, and are
+  /// matched to the params by name when the call happens.
+  fn emit_call_kw_expr(
+    &mut self,
+    expr: &'src ast::Call<'src>,
+    span: Span,
+    dst: Option<Register>,
+  ) -> Register {
+    let num_args = expr.args.len() + expr.kwargs.len();
+    let args = self.alloc_register_slice(1 + num_args);
+    self.emit_expr_into(&expr.target, args.get(0));
+    let values = expr
+      .args
+      .iter()
+      .chain(expr.kwargs.iter().map(|(_, value)| value));
+    for (i, value) in values.enumerate() {
+      self.emit_expr_into(value, args.get(1 + i));
     }
+    let names = KwNames::new(
+      expr
+        .kwargs
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect(),
+    );
+    let names = self.builder().constant_pool_builder().insert(names);
+
+    let dst = self.dst(dst);
+    self.builder().emit(
+      CallKw {
+        dst: dst.access(),
+        callee: args.access(0),
+        args: op::Count(num_args as u32),
+        names,
+      },
+      span,
+    );
+    dst
   }
 
-  fn emit_get_self_expr(&mut self, span: Span) {
-    self.builder().emit(LoadSelf, span);
+  fn emit_get_self_expr(&mut self, span: Span, dst: Option<Register>) -> Register {
+    let dst = self.dst(dst);
+    self.builder().emit(LoadSelf { dst: dst.access() }, span);
+    dst
   }
 
-  fn emit_get_super_expr(&mut self, span: Span) {
-    self.builder().emit(LoadSuper, span);
+  fn emit_get_super_expr(&mut self, span: Span, dst: Option<Register>) -> Register {
+    let dst = self.dst(dst);
+    self.builder().emit(LoadSuper { dst: dst.access() }, span);
+    dst
   }
 }
@@ -3,12 +3,12 @@ use std::ops::Deref;
 use super::*;
 use crate::internal::object::Table;
 use crate::internal::value::Value;
-use crate::util::JoinIter;
 
 impl<'src> State<'src> {
   pub(super) fn emit_stmt(&mut self, stmt: &'src ast::Stmt<'src>) {
     match stmt.deref() {
       ast::StmtKind::Var(v) => self.emit_var_stmt(v, stmt.span),
+      ast::StmtKind::Unpack(v) => self.emit_unpack_stmt(v, stmt.span),
       ast::StmtKind::If(v) => self.emit_if_stmt(v, stmt.span),
       ast::StmtKind::Loop(v) => self.emit_loop_stmt(v, stmt.span),
       ast::StmtKind::Ctrl(v) => self.emit_ctrl_stmt(v, stmt.span),
@@ -17,6 +17,7 @@ impl<'src> State<'src> {
       ast::StmtKind::Expr(v) => self.emit_expr_stmt(v),
       ast::StmtKind::Pass => self.emit_pass_stmt(),
       ast::StmtKind::Print(v) => self.emit_print_stmt(v, stmt.span),
+      ast::StmtKind::Defer(v) => self.emit_defer_stmt(v, stmt.span),
       ast::StmtKind::Import(v) => self.emit_import_stmt(v, stmt.span),
     }
   }
@@ -28,18 +29,61 @@ impl<'src> State<'src> {
   }
 
   fn emit_var_stmt(&mut self, stmt: &'src ast::Var<'src>, span: Span) {
-    self.emit_expr(&stmt.value);
-    self.emit_var(stmt.name.lexeme(), span)
+    let dst = self.var_register(&stmt.name.lexeme());
+    let value = match dst {
+      Some(dst) => {
+        self.emit_expr_into(&stmt.value, dst.clone());
+        dst
+      }
+      None => self.emit_expr(&stmt.value),
+    };
+    self.emit_var(stmt.name.lexeme(), value, span)
+  }
+
+  fn emit_unpack_stmt(&mut self, stmt: &'src ast::Unpack<'src>, span: Span) {
+    let value = self.emit_expr(&stmt.value);
+    let items = self.emit_unpack(value, stmt.targets.len(), span);
+    for (i, target) in stmt.targets.iter().enumerate() {
+      if stmt.decl {
+        let value = match self.var_register(&target.lexeme()) {
+          Some(dst) => self.emit_move(items.get(i), Some(dst), span),
+          None => items.get(i),
+        };
+        self.emit_var(target.lexeme(), value, span);
+      } else {
+        self.emit_assign_var(target, items.get(i), span);
+      }
+    }
+  }
+
+  /// Emit `Unpack`, which writes the `n` items of `value` to a new slice of
+  /// registers.
+  fn emit_unpack(&mut self, value: Register, n: usize, span: Span) -> Slice {
+    let items = self.alloc_register_slice(n);
+    let src = value.access();
+    self.builder().emit(
+      Unpack {
+        start: items.access(0),
+        count: op::Count(n as u32),
+        src,
+      },
+      span,
+    );
+    items
   }
 
   fn emit_if_stmt(&mut self, stmt: &'src ast::If<'src>, span: Span) {
+    if let Some((subject, keys)) = Self::switch_cases(stmt) {
+      return self.emit_switch_stmt(stmt, subject, &keys, span);
+    }
+
     // exit label for all branches
     let end = self.builder().multi_label("end");
 
     for branch in stmt.branches.iter() {
       let next = self.builder().label("next");
-      self.emit_expr(&branch.cond);
-      self.builder().emit_jump_if_false(&next, span);
+      let cond = self.emit_expr(&branch.cond).access();
+      self.builder().emit_jump_if_false(&next, cond, span);
       self.current_function().enter_scope();
       for stmt in branch.body.iter() {
         self.emit_stmt(stmt);
@@ -60,7 +104,99 @@ impl<'src> State<'src> {
     self.builder().bind_label(end);
   }
 
+  /// The fewest branches for which an `if` statement is emitted as a jump
+  /// table. Below that, comparing the strings one by one is about as fast as
+  /// hashing the subject.
+  const MIN_SWITCH_CASES: usize = 4;
+
+  /// If every branch of `stmt` compares the same variable against a different
+  /// string literal, returns that variable and the strings.
+  fn switch_cases(stmt: &'src ast::If<'src>) -> Option<(&'src ast::Expr<'src>, Vec<&'src str>)> {
+    if stmt.branches.len() < Self::MIN_SWITCH_CASES {
+      return None;
+    }
+
+    let mut subject: Option<(&ast::Expr<'src>, &str)> = None;
+    let mut keys = Vec::with_capacity(stmt.branches.len());
+    for branch in stmt.branches.iter() {
+      let ast::ExprKind::Binary(cond) = &*branch.cond else {
+        return None;
+      };
+      if !matches!(cond.op, ast::BinaryOp::Eq) {
+        return None;
+      }
+      let (var, name, key) = match (&*cond.left, &*cond.right) {
+        (ast::ExprKind::GetVar(get), ast::ExprKind::Literal(key)) => {
+          (&cond.left, get.name.as_str(), key)
+        }
+        (ast::ExprKind::Literal(key), ast::ExprKind::GetVar(get)) => {
+          (&cond.right, get.name.as_str(), key)
+        }
+        _ => return None,
+      };
+      let ast::Literal::String(key) = &**key else {
+        return None;
+      };
+      match subject {
+        Some((_, subject)) if subject != name => return None,
+        _ => subject = Some((var, name)),
+      }
+      if keys.contains(&key.as_ref()) {
+        return None;
+      }
+      keys.push(key.as_ref());
+    }
+
+    subject.map(|(subject, _)| (subject, keys))
+  }
+
+  fn emit_switch_stmt(
+    &mut self,
+    stmt: &'src ast::If<'src>,
+    subject: &'src ast::Expr<'src>,
+    keys: &[&'src str],
+    span: Span,
+  ) {
+    /*
+      switch <subject>
+      jump default
+      <case 0>: <body 0>; jump end
+      ...
+      default: <default body>
+      end:
+    */
+    let end = self.builder().multi_label("end");
+    let default = self.builder().label("default");
+
+    let value = self.emit_expr(subject).access();
+    let mut table = self.builder().emit_switch(value, span);
+    self.builder().emit_jump(&default, span);
+
+    for (branch, key) in stmt.branches.iter().zip(keys) {
+      self.builder().bind_switch_case(&mut table, *key);
+      self.current_function().enter_scope();
+      for stmt in branch.body.iter() {
+        self.emit_stmt(stmt);
+      }
+      self.builder().emit_jump(&end, span);
+      self.current_function().leave_scope();
+    }
+    self.builder().finish_switch(table);
+    self.builder().bind_label(default);
+
+    if let Some(default) = stmt.default.as_ref() {
+      self.current_function().enter_scope();
+      for stmt in default.iter() {
+        self.emit_stmt(stmt);
+      }
+      self.current_function().leave_scope();
+    }
+
+    self.builder().bind_label(end);
+  }
+
   fn emit_loop_stmt(&mut self, stmt: &'src ast::Loop<'src>, span: Span) {
+    let appended = self.enter_appended_vars(stmt);
     match stmt {
       ast::Loop::For(v) => match &v.iter {
         ast::ForIter::Range(range) => self.emit_for_range_loop(v, range),
@@ -69,6 +205,39 @@ impl<'src> State<'src> {
       ast::Loop::While(v) => self.emit_while_loop(v, span),
       ast::Loop::Infinite(v) => self.emit_inf_loop(v, span),
     }
+    self.leave_appended_vars(appended, span);
+  }
+
+  /// Hold the locals which `stmt` only appends to in string builders, and
+  /// return the ones which were not already held by an enclosing loop.
+  fn enter_appended_vars(&mut self, stmt: &'src ast::Loop<'src>) -> Vec<&'src str> {
+    let function = self.current_function();
+    let appended = append::appended_vars(stmt)
+      .into_iter()
+      .filter(|name| {
+        !function.appended_vars.contains(name)
+          && function.resolve_local(&Cow::from(*name)).is_some()
+      })
+      .collect::<Vec<_>>();
+    function.appended_vars.extend(appended.iter().copied());
+    appended
+  }
+
+  /// Turn the builders of the `appended` locals back into strings, once the
+  /// loop which appends to them has ended.
+  fn leave_appended_vars(&mut self, appended: Vec<&'src str>, span: Span) {
+    for name in appended {
+      let function = self.current_function();
+      function.appended_vars.retain(|var| *var != name);
+      let register = function.resolve_local(&Cow::from(name)).unwrap();
+      self.builder().emit(
+        BuildStr {
+          dst: register.access(),
+          src: register.access(),
+        },
+        span,
+      );
+    }
   }
 
   fn emit_for_range_loop(&mut self, stmt: &'src ast::For<'src>, range: &'src ast::IterRange<'src>) {
@@ -82,44 +251,55 @@ impl<'src> State<'src> {
     let item_register = self.alloc_register();
     let end_register = self.alloc_register();
 
-    self.declare_local(stmt.item.lexeme(), item_register.clone());
-    self.emit_expr(&range.start);
-    self.emit_store(item_register.clone(), stmt.item.span);
-
-    self.emit_expr(&range.end);
-    self.emit_store(end_register.clone(), range.span());
+    // the parser only allows one variable for ranges
+    self.declare_local(stmt.items[0].lexeme(), item_register.clone());
+    self.emit_expr_into(&range.start, item_register.clone());
+    self.emit_expr_into(&range.end, end_register.clone());
 
     self.builder().bind_loop_header(&cond);
-    self.emit_load(end_register.clone(), range.span());
+    let (lhs, rhs) = (item_register.access(), end_register.access());
+    let in_range = self.alloc_register();
     if range.inclusive {
       self.builder().emit(
         CmpLe {
-          lhs: item_register.access(),
+          dst: in_range.access(),
+          lhs,
+          rhs,
         },
         range.span(),
       );
     } else {
       self.builder().emit(
         CmpLt {
-          lhs: item_register.access(),
+          dst: in_range.access(),
+          lhs,
+          rhs,
         },
         range.span(),
       );
     }
-    self.builder().emit_jump_if_false(&end, range.span());
+    self
+      .builder()
+      .emit_jump_if_false(&end, in_range.access(), range.span());
     self.builder().emit_jump(&body, range.span());
 
     self.builder().bind_loop_header(&latch);
-    self
-      .builder()
-      .emit(LoadSmi { value: op::Smi(1) }, range.span());
+    let one = self.alloc_register();
+    self.builder().emit(
+      LoadSmi {
+        dst: one.access(),
+        value: op::Smi(1),
+      },
+      range.span(),
+    );
     self.builder().emit(
       Add {
+        dst: item_register.access(),
         lhs: item_register.access(),
+        rhs: one.access(),
       },
       range.span(),
     );
-    self.emit_store(item_register.clone(), range.span());
     self.builder().emit_jump_loop(&cond, range.span());
 
     self.builder().bind_label(body);
@@ -147,48 +327,111 @@ impl<'src> State<'src> {
     self.current_function().enter_scope();
 
     // iterator
-    self.emit_expr(iter);
-    self
-      .builder()
-      .emit(LoadField { name: iter_const }, iter.span);
-    self.builder().emit(Call0, iter.span);
-    self.emit_store(iter_register.clone(), iter.span);
+    let iterable = self.emit_expr(iter);
+    self.emit_method_call0(iterable, iter_const, iter_register.clone(), iter.span);
 
     // `item = none`
-    self.builder().emit(LoadNone, iter.span);
-    self.emit_store(item_register.clone(), iter.span);
-    self.declare_local(stmt.item.lexeme(), item_register.clone());
+    self.builder().emit(
+      LoadNone {
+        dst: item_register.access(),
+      },
+      iter.span,
+    );
+    // `for a, b in iter` declares `a` and `b` instead, and unpacks each item
+    // into them
+    let unpacked = match &stmt.items[..] {
+      [item] => {
+        self.declare_local(item.lexeme(), item_register.clone());
+        vec![]
+      }
+      items => items
+        .iter()
+        .map(|item| {
+          let register = self.alloc_register();
+          self.builder().emit(
+            LoadNone {
+              dst: register.access(),
+            },
+            item.span,
+          );
+          self.declare_local(item.lexeme(), register.clone());
+          register
+        })
+        .collect::<Vec<_>>(),
+    };
 
     // condition
     self.builder().bind_loop_header(&cond);
-    self.emit_load(iter_register.clone(), iter.span);
-    self
-      .builder()
-      .emit(LoadField { name: done_const }, iter.span);
-    self.builder().emit(Call0, iter.span);
-    self.builder().emit(Not, iter.span);
+    let not_done = self.alloc_register();
+    self.emit_method_call0(
+      iter_register.clone(),
+      done_const,
+      not_done.clone(),
+      iter.span,
+    );
+    self.builder().emit(
+      Not {
+        dst: not_done.access(),
+        src: not_done.access(),
+      },
+      iter.span,
+    );
     // if `iter.done()` jump .end
     // else jump .body
-    self.builder().emit_jump_if_false(&end, iter.span);
-
-    // `item = iter.next()`
-    self.emit_load(iter_register.clone(), iter.span);
     self
       .builder()
-      .emit(LoadField { name: next_const }, iter.span);
-    self.builder().emit(Call0, iter.span);
-    self.emit_store(item_register.clone(), iter.span);
+      .emit_jump_if_false(&end, not_done.access(), iter.span);
+
+    // `item = iter.next()`
+    self.emit_method_call0(
+      iter_register.clone(),
+      next_const,
+      item_register.clone(),
+      iter.span,
+    );
+    if let [first, .., last] = &stmt.items[..] {
+      let span = Span::from(first.span.start..last.span.end);
+      let items = self.emit_unpack(item_register.clone(), unpacked.len(), span);
+      for (i, register) in unpacked.iter().enumerate() {
+        self.emit_move(items.get(i), Some(register.clone()), span);
+      }
+    }
 
     let (cond, end) = self.emit_loop_body((cond, end), &stmt.body);
     self.builder().emit_jump_loop(&cond, iter.span);
 
     let _ = item_register.access();
     let _ = iter_register.access();
+    for register in unpacked {
+      let _ = register.access();
+    }
 
     self.builder().bind_label(end);
     self.current_function().leave_scope();
   }
 
+  /// Emit `dst = obj.name()`.
+  fn emit_method_call0(&mut self, obj: Register, name: op::Constant, dst: Register, span: Span) {
+    let obj = obj.access();
+    let method = self.alloc_register();
+    self.builder().emit(
+      LoadField {
+        dst: method.access(),
+        obj,
+        name,
+      },
+      span,
+    );
+    self.builder().emit(
+      Call {
+        dst: dst.access(),
+        callee: method.access(),
+        args: op::Count(0),
+      },
+      span,
+    );
+  }
+
   fn emit_while_loop(&mut self, stmt: &'src ast::While<'src>, span: Span) {
     let start = self.builder().loop_header();
     let end = self.builder().multi_label("end");
@@ -196,8 +439,10 @@ impl<'src> State<'src> {
     self.current_function().enter_scope();
     self.builder().bind_loop_header(&start);
 
-    self.emit_expr(&stmt.cond);
-    self.builder().emit_jump_if_false(&end, stmt.cond.span);
+    let cond = self.emit_expr(&stmt.cond).access();
+    self
+      .builder()
+      .emit_jump_if_false(&end, cond, stmt.cond.span);
 
     let (start, end) = self.emit_loop_body((start, end), &stmt.body);
     self.builder().emit_jump_loop(&start, span);
@@ -234,22 +479,57 @@ impl<'src> State<'src> {
   fn emit_ctrl_stmt(&mut self, stmt: &'src ast::Ctrl<'src>, span: Span) {
     match stmt {
       ast::Ctrl::Return(stmt) => {
-        if let Some(value) = stmt.value.as_ref() {
-          self.emit_expr(value);
-        } else if self.current_function().is_init {
-          self.builder().emit(LoadSelf, span);
+        let value = if let Some(value) = stmt.value.as_ref() {
+          self.emit_expr(value)
         } else {
-          self.builder().emit(LoadNone, span);
-        }
-        self.builder().emit(Return, span);
+          let value = self.alloc_register();
+          if self.current_function().is_init {
+            self.builder().emit(
+              LoadSelf {
+                dst: value.access(),
+              },
+              span,
+            );
+          } else {
+            self.builder().emit(
+              LoadNone {
+                dst: value.access(),
+              },
+              span,
+            );
+          }
+          value
+        };
+        self.emit_return(value, span);
       }
       ast::Ctrl::Yield(stmt) => {
-        if let Some(value) = stmt.value.as_ref() {
-          self.emit_expr(value);
+        let value = if let Some(value) = stmt.value.as_ref() {
+          self.emit_expr(value)
         } else {
-          self.builder().emit(LoadNone, span);
-        }
-        self.builder().emit(Yield, span);
+          let value = self.alloc_register();
+          self.builder().emit(
+            LoadNone {
+              dst: value.access(),
+            },
+            span,
+          );
+          value
+        };
+        self.builder().emit(
+          Yield {
+            src: value.access(),
+          },
+          span,
+        );
+      }
+      ast::Ctrl::Raise(stmt) => {
+        let value = self.emit_expr(&stmt.value);
+        self.builder().emit(
+          Raise {
+            src: value.access(),
+          },
+          span,
+        );
       }
       ast::Ctrl::Continue => {
         let function = self.current_function();
@@ -271,14 +551,66 @@ impl<'src> State<'src> {
   }
 
   fn emit_func_stmt(&mut self, stmt: &'src ast::Func<'src>) {
+    let decorators = self.emit_decorators(&stmt.decorators);
     let function = self.emit_function(stmt, false);
     let desc = self.constant_value(function.ptr);
-    self.builder().emit(MakeFn { desc }, stmt.name.span);
+    let dst = self.alloc_register();
+    self.builder().emit(
+      MakeFn {
+        dst: dst.access(),
+        desc,
+      },
+      stmt.name.span,
+    );
     function.upvalues.finish();
-    self.emit_var(stmt.name.lexeme(), stmt.name.span);
+    let dst = self.apply_decorators(decorators, dst, stmt.name.span);
+    self.emit_var(stmt.name.lexeme(), dst, stmt.name.span);
+  }
+
+  /// Decorators are evaluated top to bottom before the function or class is
+  /// created, each into a register which is followed by its argument.
+  fn emit_decorators(&mut self, decorators: &'src [ast::Expr<'src>]) -> Vec<(Slice, Span)> {
+    decorators
+      .iter()
+      .map(|decorator| {
+        let call = self.alloc_register_slice(2);
+        self.emit_expr_into(decorator, call.get(0));
+        (call, decorator.span)
+      })
+      .collect()
+  }
+
+  /// Call the decorators bottom to top, so that `value` is wrapped by the one
+  /// nearest to it first.
+  fn apply_decorators(
+    &mut self,
+    decorators: Vec<(Slice, Span)>,
+    mut value: Register,
+    span: Span,
+  ) -> Register {
+    for (call, decorator_span) in decorators.into_iter().rev() {
+      self.builder().emit(
+        Mov {
+          dst: call.access(1),
+          src: value.access(),
+        },
+        span,
+      );
+      self.builder().emit(
+        Call {
+          dst: call.access(0),
+          callee: call.access(0),
+          args: op::Count(1),
+        },
+        decorator_span,
+      );
+      value = call.get(0);
+    }
+    value
   }
 
   fn emit_class_stmt(&mut self, stmt: &'src ast::Class<'src>) {
+    let decorators = self.emit_decorators(&stmt.decorators);
     let mut preserve = Vec::new();
 
     let init = match stmt.members.init.as_ref() {
@@ -298,87 +630,181 @@ impl<'src> State<'src> {
       methods.insert(function.name.clone(), function.clone());
     }
 
+    let mut static_methods = IndexMap::with_capacity(stmt.members.static_methods.len());
+    for function in stmt.members.static_methods.iter() {
+      let function = self.emit_function(function, false);
+      preserve.push(function.upvalues);
+      let function = function.ptr;
+      static_methods.insert(function.name.clone(), function.clone());
+    }
+
+    let static_fields = Table::with_capacity(stmt.members.static_fields.len());
+    for field in stmt.members.static_fields.iter() {
+      static_fields.insert(self.global.intern(field.name.to_string()), Value::none());
+    }
+    let static_fields = self.global.alloc(static_fields);
+
     let fields = Table::with_capacity(stmt.members.fields.len());
     for field in stmt.members.fields.iter() {
       fields.insert(self.global.intern(field.name.to_string()), Value::none());
     }
     let fields = self.global.alloc(fields);
 
+    let hints = Table::new();
+    for field in stmt.members.fields.iter() {
+      if let Some(hint) = &field.hint {
+        hints.insert(
+          self.global.intern(field.name.to_string()),
+          Value::object(self.global.intern(hint.to_string())),
+        );
+      }
+    }
+    let hints = self.global.alloc(hints);
+
     let class = self.global.alloc(object::ClassDescriptor {
       name: self.global.intern(stmt.name.to_string()),
       init,
       methods,
       fields,
+      hints,
+      static_methods,
+      static_fields,
+      mixins: stmt.mixins.len(),
+      doc: stmt
+        .doc
+        .as_ref()
+        .map(|doc| self.global.alloc(object::Str::owned(doc))),
     });
     let desc = self.constant_value(class);
 
-    if stmt.members.fields.is_empty() {
+    let class = if stmt.members.fields.is_empty() && stmt.mixins.is_empty() {
       if let Some(parent) = stmt.parent.as_ref() {
-        self.emit_get(parent.lexeme(), parent.span);
-        self
-          .builder()
-          .emit(MakeClassDerived { desc }, stmt.name.span);
+        let parent = self.emit_get(parent.lexeme(), None, parent.span).access();
+        let dst = self.alloc_register();
+        self.builder().emit(
+          MakeClassDerived {
+            dst: dst.access(),
+            desc,
+            parent,
+          },
+          stmt.name.span,
+        );
+        dst
       } else {
-        self.builder().emit(MakeClass { desc }, stmt.name.span);
+        let dst = self.alloc_register();
+        self.builder().emit(
+          MakeClass {
+            dst: dst.access(),
+            desc,
+          },
+          stmt.name.span,
+        );
+        dst
       }
     } else {
+      // parts are the parent, then field defaults, then mixins
+      let num_parts = stmt.members.fields.len() + stmt.mixins.len();
       let (parts, offset) = match stmt.parent.as_ref() {
         Some(parent) => {
-          let parts = self.alloc_register_slice(1 + stmt.members.fields.len());
-          self.emit_get(parent.lexeme(), parent.span);
-          self.emit_store(parts.get(0), parent.span);
+          let parts = self.alloc_register_slice(1 + num_parts);
+          self.emit_get(parent.lexeme(), Some(parts.get(0)), parent.span);
           (parts, 1)
         }
-        None => (self.alloc_register_slice(stmt.members.fields.len()), 0),
+        None => (self.alloc_register_slice(num_parts), 0),
       };
+      let mixins = offset + stmt.members.fields.len();
+      for (i, mixin) in stmt.mixins.iter().enumerate() {
+        self.emit_get(mixin.lexeme(), Some(parts.get(mixins + i)), mixin.span);
+      }
       for (i, field) in stmt.members.fields.iter().enumerate() {
-        self.emit_expr(&field.default);
-        self.emit_store(parts.get(offset + i), field.span());
+        self.emit_expr_into(&field.default, parts.get(offset + i));
       }
+      let parts = parts.access(0);
+      let dst = self.alloc_register();
       match stmt.parent.as_ref() {
         Some(_) => self.builder().emit(
           MakeDataClassDerived {
+            dst: dst.access(),
             desc,
-            parts: parts.access(0),
+            parts,
           },
           stmt.name.span,
         ),
         None => self.builder().emit(
           MakeDataClass {
+            dst: dst.access(),
             desc,
-            parts: parts.access(0),
+            parts,
           },
           stmt.name.span,
         ),
       }
+      dst
+    };
+
+    // static fields are initialized in order, after the class is created
+    for field in stmt.members.static_fields.iter() {
+      let name = self.constant_name(field.name.lexeme());
+      let value = self.emit_expr(&field.default);
+      self.builder().emit(
+        StoreField {
+          obj: class.access(),
+          name,
+          src: value.access(),
+        },
+        field.span(),
+      );
     }
 
     for upvalues in preserve.iter().rev() {
       upvalues.finish();
     }
 
-    self.emit_var(stmt.name.lexeme(), stmt.name.span);
+    let class = self.apply_decorators(decorators, class, stmt.name.span);
+    self.emit_var(stmt.name.lexeme(), class, stmt.name.span);
   }
 
   fn emit_expr_stmt(&mut self, expr: &'src ast::Expr<'src>) {
-    self.emit_expr(expr)
+    let _ = self.emit_expr(expr);
   }
 
   fn emit_pass_stmt(&mut self) {}
 
+  fn emit_defer_stmt(&mut self, stmt: &'src ast::Defer<'src>, span: Span) {
+    // the callee and arguments are evaluated now, and only the call is deferred
+    let call = &stmt.call;
+    let args = self.alloc_register_slice(1 + call.args.len());
+    let callee = args.get(0);
+    self.emit_expr_into(&call.target, callee.clone());
+    for (i, value) in call.args.iter().enumerate() {
+      self.emit_expr_into(value, args.get(1 + i));
+    }
+    self.builder().emit(
+      Defer {
+        callee: callee.access(),
+        args: op::Count(call.args.len() as u32),
+      },
+      span,
+    );
+  }
+
   fn emit_print_stmt(&mut self, stmt: &'src ast::Print<'src>, span: Span) {
     match &stmt.values[..] {
       [] => {}
       [value] => {
-        self.emit_expr(value);
-        self.builder().emit(Print, span);
+        let value = self.emit_expr(value);
+        self.builder().emit(
+          Print {
+            src: value.access(),
+          },
+          span,
+        );
       }
       values => {
         let args = self.alloc_register_slice(values.len());
 
         for (i, value) in values.iter().enumerate() {
-          self.emit_expr(value);
-          self.emit_store(args.get(i), span);
+          self.emit_expr_into(value, args.get(i));
         }
 
         self.builder().emit(
@@ -395,31 +821,44 @@ impl<'src> State<'src> {
   fn emit_import_stmt(&mut self, stmt: &'src ast::Import<'src>, span: Span) {
     match stmt {
       ast::Import::Module { path, alias } => {
-        let name = alias.as_ref().unwrap_or(path.last().unwrap());
-        let path = path.iter().map(|p| p.as_ref()).join(".");
-        let path = self.constant_name(path);
+        let name = alias.as_ref().unwrap_or(path.segments.last().unwrap());
+        let path = self.constant_name(path.to_path_string());
         let dst = self.alloc_register();
         self.declare_local(name.lexeme(), dst.clone());
-        self.builder().emit(Import { path }, span);
-        self.builder().emit(Store { reg: dst.access() }, span);
+        self.builder().emit(
+          Import {
+            dst: dst.access(),
+            path,
+          },
+          span,
+        );
       }
       ast::Import::Symbols { path, symbols } => {
-        let path = path.iter().map(|p| p.as_ref()).join(".");
-        let path = self.constant_name(path);
-        let temp = self.alloc_register();
-        self.builder().emit(Import { path }, span);
-        self.builder().emit(Store { reg: temp.access() }, span);
+        let path = self.constant_name(path.to_path_string());
+        let module = self.alloc_register();
+        self.builder().emit(
+          Import {
+            dst: module.access(),
+            path,
+          },
+          span,
+        );
 
         for symbol in symbols {
           let name = symbol.alias.as_ref().unwrap_or(&symbol.name);
-          let name_idx = self.constant_name(name);
-
-          self.emit_load(temp.clone(), span);
-          self.builder().emit(LoadField { name: name_idx }, span);
+          let name_idx = self.constant_name(&symbol.name);
 
+          let obj = module.access();
           let dst = self.alloc_register();
-          self.declare_local(name.lexeme(), dst.clone());
-          self.emit_store(dst.clone(), span);
+          self.builder().emit(
+            LoadField {
+              dst: dst.access(),
+              obj,
+              name: name_idx,
+            },
+            span,
+          );
+          self.declare_local(name.lexeme(), dst);
         }
       }
     }
@@ -84,6 +84,16 @@ check! {
 
 check!(call_arg_subexpr, r#"f(a+b)"#);
 
+check!(list_subexpr, r#"print [a + 1, b * c]"#);
+
+check! {
+  binary_local_operand,
+  r#"
+    fn test(a, b):
+      return a * b - a
+  "#
+}
+
 check! {
   function_no_params,
   r#"
@@ -199,6 +209,18 @@ check! {
   "#
 }
 
+check! {
+  conditional_expr,
+  r#"
+    fn test0(a, b, c):
+      return a if b else c
+    fn test1(a, b, c):
+      return a if b else c && a
+    fn test2(a, b, c):
+      f(a if b else c, c)
+  "#
+}
+
 check! {
   opt_chaining,
   r#"
@@ -230,6 +252,67 @@ check! {
   "#
 }
 
+check! {
+  if_stmt_switch,
+  r#"
+    fn route(cmd):
+      if cmd == "get":
+        print 0
+      elif cmd == "set":
+        print 1
+      elif "del" == cmd:
+        print 2
+      elif cmd == "list":
+        print 3
+      else:
+        print 4
+    fn mixed(cmd, other):
+      if cmd == "get":
+        print 0
+      elif cmd == "set":
+        print 1
+      elif other == "del":
+        print 2
+      elif cmd == "list":
+        print 3
+  "#
+}
+
+check! {
+  implicit_return,
+  r#"
+    fn double(v): v * 2
+    fn deferred(v):
+      defer f()
+      v + 1
+    fn no_tail(v):
+      print v
+  "#
+}
+
+check! {
+  decorators,
+  r#"
+    @a
+    @b(0)
+    fn f(n): f(n - 1)
+    fn g():
+      @a
+      fn h(): h()
+      return h
+    @a
+    class T: pass
+  "#
+}
+
+check! {
+  kw_params,
+  r#"
+    fn f(a, *, b, c=1): a + b + c
+    f(0, c=2, b=1)
+  "#
+}
+
 check! {
   loop_print,
   r#"
@@ -287,6 +370,18 @@ check! {
   "#
 }
 
+check! {
+  compound_assign_field_and_index,
+  r#"
+    fn test0(a, b):
+      f().x += a
+    fn test1(a, b):
+      a[f()] -= b
+    fn test2(a, b):
+      a.x ??= b
+  "#
+}
+
 check! {
   while_nested_while_break,
   r#"
@@ -595,3 +690,52 @@ check! {
         print b
   "#
 }
+
+check! {
+  string_append_in_loop,
+  r#"
+    fn test(n):
+      s := ""
+      t := ""
+      for i in 0..n:
+        s += "a"
+        t += "b"
+        print t
+      return s + t
+  "#
+}
+
+check! {
+  unpack,
+  r#"
+    a, b := (0, 1)
+    a, b = b, a
+    fn test(pairs):
+      x, y := pairs[0]
+      for k, v in pairs:
+        print k, v
+  "#
+}
+
+#[test]
+fn disassemble_with_source() {
+  let global = crate::internal::vm::global::Global::default();
+  let input = indoc::indoc! {
+    r#"
+      fn add(a, b):
+        return a + b
+
+      v := 0
+      for i in 0..10:
+        v = add(v, i)
+      print v
+    "#
+  };
+  let module = syntax::parse(global.clone(), input).unwrap();
+  let module = emit(global, &module, "main", true);
+  let snapshot = format!(
+    "# Input:\n{input}\n\n# Func:\n{}\n\n",
+    module.root.disassemble_with_source(input),
+  );
+  assert_snapshot!(snapshot);
+}
@@ -1,7 +1,11 @@
 use std::error::Error as StdError;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 
+use super::object::class::ClassInstance;
+use super::object::table::Key;
 use super::syntax::SyntaxError;
+use super::value::Value;
+use super::vm::frozen::{self, Frozen};
 use crate::span::SpannedError;
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -13,11 +17,95 @@ pub enum Error {
   User(Box<dyn StdError + Send + Sync + 'static>),
 }
 
+/// What kind of runtime error occurred.
+///
+/// Errors raised by the VM use one of the specific kinds where it applies,
+/// and [`ErrorKind::Other`] otherwise. Native functions may raise errors of
+/// any kind using the `error!` and `fail!` macros:
+///
+/// ```rust
+/// use hebi::prelude::*;
+/// use hebi::ErrorKind;
+///
+/// let e = hebi::error!(Index: "no such thing");
+/// assert!(matches!(e.kind, ErrorKind::Index));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub enum ErrorKind {
+  #[default]
+  Other,
+  /// A value doesn't support an operation, such as calling something which
+  /// isn't a function.
+  Type,
+  /// A variable, field or export doesn't exist.
+  Name,
+  /// An index is out of bounds, or a key is missing.
+  Index,
+  /// A function was called with the wrong number of arguments.
+  Arity,
+  /// The evaluation was stopped through a
+  /// [`CancellationToken`][crate::CancellationToken].
+  Cancelled,
+  /// The script called `os.exit` with this exit code.
+  Exit(i32),
+  /// An error which carries a value, see [`Error::from_value`].
+  User(ErrorValue),
+}
+
+/// The value carried by an [`ErrorKind::User`] error.
+///
+/// The value is frozen when the error is created, so that the error isn't
+/// tied to the VM it was raised in, see [`Frozen`]. Values which can't be
+/// frozen, such as class instances, are kept as their string form. The
+/// fields of a class instance are frozen one by one, see
+/// [`ErrorValue::field`].
+#[derive(Clone, Debug)]
+pub struct ErrorValue(pub(crate) Box<ErrorPayload>);
+
+#[derive(Clone, Debug)]
+pub(crate) struct ErrorPayload {
+  pub(crate) value: Frozen,
+  pub(crate) fields: Vec<(String, Frozen)>,
+}
+
+impl ErrorValue {
+  pub(crate) fn new(value: &Value) -> Self {
+    let fields = match value.clone().to_object::<ClassInstance>() {
+      Some(instance) => instance
+        .fields
+        .entries()
+        .filter_map(|(key, value)| match key {
+          Key::Str(name) => Some((name.as_str().to_string(), freeze_or_render(&value))),
+          _ => None,
+        })
+        .collect(),
+      None => vec![],
+    };
+    Self(Box::new(ErrorPayload {
+      value: freeze_or_render(value),
+      fields,
+    }))
+  }
+}
+
+fn freeze_or_render(value: &Value) -> Frozen {
+  frozen::freeze(value).unwrap_or_else(|_| Frozen::from(value.to_string()))
+}
+
 impl Error {
   pub fn user(e: impl StdError + Send + Sync + 'static) -> Self {
     Self::User(Box::new(e))
   }
 
+  /// The kind of a runtime error. Syntax errors and errors created with
+  /// [`Error::user`] are [`ErrorKind::Other`].
+  pub fn kind(&self) -> &ErrorKind {
+    match self {
+      Error::Vm(e) => &e.kind,
+      Error::Syntax(_) | Error::User(_) => &ErrorKind::Other,
+    }
+  }
+
   pub fn report(&self, src: &str, use_color: bool) -> String {
     match self {
       Error::Vm(e) => format!("runtime error: {}", e.report(src, use_color)),
@@ -158,14 +158,14 @@ declare_object_trait! {
       let _ = scope;
       let _ = name;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support field access")
+      fail!(Type: "`{this}` does not support field access")
     }
 
     fn named_field_opt(scope, this, name: StrPtr) -> Result<Option<Value>> {
       let _ = scope;
       let _ = name;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support field access")
+      fail!(Type: "`{this}` does not support field access")
     }
 
     fn set_named_field(scope, this, name: StrPtr, value: Value) -> Result<()> {
@@ -173,21 +173,21 @@ declare_object_trait! {
       let _ = value;
       let _ = name;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support field access")
+      fail!(Type: "`{this}` does not support field access")
     }
 
     fn keyed_field(scope, this, key: Value) -> Result<Value> {
       let _ = scope;
       let _ = key;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `[]`")
+      fail!(Type: "`{this}` does not support `[]`")
     }
 
     fn keyed_field_opt(scope, this, key: Value) -> Result<Option<Value>> {
       let _ = scope;
       let _ = key;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `[]`")
+      fail!(Type: "`{this}` does not support `[]`")
     }
 
     fn set_keyed_field(scope, this, key: Value, value: Value) -> Result<()> {
@@ -195,82 +195,89 @@ declare_object_trait! {
       let _ = key;
       let this = Self::type_name(this);
       let _ = value;
-      fail!("`{this}` does not support `[]=`")
+      fail!(Type: "`{this}` does not support `[]=`")
     }
 
     fn call(scope, this, return_addr: ReturnAddr) -> Result<CallResult> {
       let _ = scope;
       let _ = return_addr;
       let this = Self::type_name(this);
-      fail!("`{this}` is not callable")
+      fail!(Type: "`{this}` is not callable")
     }
 
     fn contains(scope, this, item: Value) -> Result<bool> {
       let _ = scope;
       let _ = item;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `in`")
+      fail!(Type: "`{this}` does not support `in`")
     }
 
     fn add(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `+`")
+      fail!(Type: "`{this}` does not support `+`")
     }
 
     fn subtract(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `-`")
+      fail!(Type: "`{this}` does not support `-`")
     }
 
     fn multiply(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `*`")
+      fail!(Type: "`{this}` does not support `*`")
     }
 
     fn divide(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `/`")
+      fail!(Type: "`{this}` does not support `/`")
+    }
+
+    fn floor_divide(scope, this, other: Self) -> Result<Value> {
+      let _ = scope;
+      let _ = other;
+      let this = Self::type_name(this);
+      fail!(Type: "`{this}` does not support `//`")
     }
 
     fn remainder(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `%`")
+      fail!(Type: "`{this}` does not support `%`")
     }
 
     fn pow(scope, this, other: Self) -> Result<Value> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `**`")
+      fail!(Type: "`{this}` does not support `**`")
     }
 
     fn invert(scope, this) -> Result<Value> {
       let _ = scope;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support unary `-`")
+      fail!(Type: "`{this}` does not support unary `-`")
     }
 
     fn not(scope, this) -> Result<Value> {
       let _ = scope;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support `!`")
+      fail!(Type: "`{this}` does not support `!`")
     }
 
     fn cmp(scope, this, other: Self) -> Result<Ordering> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
-      fail!("`{this}` does not support comparison")
+      fail!(Type: "`{this}` does not support comparison")
     }
 
     fn eq(scope, this, other: Self) -> Result<bool> {
@@ -298,6 +305,17 @@ pub fn is_callable(v: &Ptr<Any>) -> bool {
     || v.is::<NativeAsyncFunction>()
 }
 
+/// Whether `v` is something that a method lookup may return: a script
+/// function, a bound method, or a builtin or native function or method.
+pub fn is_method(v: &Ptr<Any>) -> bool {
+  is_callable(v)
+    || v.is::<NativeBoundFunction>()
+    || v.is::<builtin::BuiltinFunction>()
+    || v.is::<builtin::BuiltinAsyncFunction>()
+    || v.is::<builtin::BuiltinMethod>()
+    || v.is::<builtin::BuiltinAsyncMethod>()
+}
+
 pub fn is_class(v: &Ptr<Any>) -> bool {
   v.is::<ClassInstance>() || v.is::<ClassProxy>() || v.is::<NativeClassInstance>()
 }
@@ -306,30 +324,47 @@ pub type ReturnAddr = Option<usize>;
 
 #[macro_use]
 pub mod builtin;
+#[cfg(feature = "bigint")]
+pub mod bigint;
+pub mod bytes;
+pub mod channel;
 
 pub mod class;
+pub mod coroutine;
+pub mod duration;
 pub mod function;
+pub mod host;
+pub mod int;
+pub mod iter;
 pub mod list;
 pub mod module;
 pub mod native;
+pub mod protocol;
+pub mod set;
+pub mod shape;
 pub mod string;
+pub mod string_builder;
 pub mod table;
+pub mod tuple;
 
 pub(crate) mod ptr;
 
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 
+pub use bytes::Bytes;
 pub use class::{ClassDescriptor, ClassType};
-pub use function::{BoundFunction, Function, FunctionDescriptor};
+pub use function::{BoundFunction, ConstantPool, Function, FunctionDescriptor};
 pub use list::List;
 pub use module::{Module, ModuleDescriptor};
 pub use ptr::{Any, Ptr};
 pub use string::Str;
+pub use string_builder::StringBuilder;
 pub use table::Table;
+pub use tuple::Tuple;
 
 use self::class::{ClassInstance, ClassProxy};
-use self::native::{NativeAsyncFunction, NativeClassInstance, NativeFunction};
+use self::native::{NativeAsyncFunction, NativeBoundFunction, NativeClassInstance, NativeFunction};
 use super::error::Result;
 use super::value::Value;
 use super::vm::thread::CallResult;
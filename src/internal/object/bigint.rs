@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+
+use num_bigint::Sign;
+use num_traits::FromPrimitive;
+
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public::{self, Scope, Unbind};
+
+/// An arbitrary precision integer.
+///
+/// Unlike [`BoxedInt`][super::int::BoxedInt], bigints are a distinct type
+/// from `int`, and arithmetic between the two is an error. Use the `bigint`
+/// builtin to convert between them.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BigInt {
+  value: num_bigint::BigInt,
+}
+
+impl BigInt {
+  pub fn new(value: num_bigint::BigInt) -> Self {
+    Self { value }
+  }
+
+  pub fn value(&self) -> &num_bigint::BigInt {
+    &self.value
+  }
+}
+
+impl Object for BigInt {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "bigint"
+  }
+
+  default_instance_of!();
+
+  fn add(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    Ok(new_bigint(scope, &this.value + &other.value))
+  }
+
+  fn subtract(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    Ok(new_bigint(scope, &this.value - &other.value))
+  }
+
+  fn multiply(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    Ok(new_bigint(scope, &this.value * &other.value))
+  }
+
+  /// Bigint division truncates towards zero.
+  fn divide(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    if other.value.sign() == Sign::NoSign {
+      fail!("cannot divide bigint by zero");
+    }
+    Ok(new_bigint(scope, &this.value / &other.value))
+  }
+
+  /// Floor division rounds towards negative infinity, like for ints.
+  fn floor_divide(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    if other.value.sign() == Sign::NoSign {
+      fail!("cannot divide bigint by zero");
+    }
+    let quotient = &this.value / &other.value;
+    let remainder = &this.value % &other.value;
+    if remainder.sign() != Sign::NoSign && remainder.sign() != other.value.sign() {
+      return Ok(new_bigint(scope, quotient - 1));
+    }
+    Ok(new_bigint(scope, quotient))
+  }
+
+  /// The remainder has the sign of the divisor, so that it is consistent with
+  /// `//`: `a == (a // b) * b + a % b`.
+  fn remainder(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    if other.value.sign() == Sign::NoSign {
+      fail!("cannot divide bigint by zero");
+    }
+    let remainder = &this.value % &other.value;
+    if remainder.sign() != Sign::NoSign && remainder.sign() != other.value.sign() {
+      return Ok(new_bigint(scope, remainder + &other.value));
+    }
+    Ok(new_bigint(scope, remainder))
+  }
+
+  fn pow(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let Ok(exp) = u32::try_from(&other.value) else {
+      fail!(
+        "bigint exponent must be between 0 and {}, got `{other}`",
+        u32::MAX
+      );
+    };
+    Ok(new_bigint(scope, this.value.pow(exp)))
+  }
+
+  fn invert(scope: Scope<'_>, this: Ptr<Self>) -> Result<Value> {
+    Ok(new_bigint(scope, -&this.value))
+  }
+
+  fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    Ok(this.value.cmp(&other.value))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.value == other.value)
+  }
+}
+
+declare_object_type!(BigInt);
+
+fn new_bigint(scope: Scope<'_>, value: num_bigint::BigInt) -> Value {
+  Value::object(scope.alloc(BigInt::new(value)))
+}
+
+fn bigint(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if let Some(int) = value.to_i64() {
+    Ok(new_bigint(scope, int.into()))
+  } else if value.is_float() {
+    let float = unsafe { value.clone().to_float_unchecked() };
+    match num_bigint::BigInt::from_f64(float) {
+      Some(int) if float.fract() == 0.0 => Ok(new_bigint(scope, int)),
+      _ => fail!(Type: "cannot convert `{value}` to a bigint"),
+    }
+  } else if value.is_object() {
+    if value.clone().to_object::<BigInt>().is_some() {
+      return Ok(value);
+    }
+    if let Some(str) = value.clone().to_object::<Str>() {
+      return match str.as_str().parse() {
+        Ok(int) => Ok(new_bigint(scope, int)),
+        Err(e) => fail!("failed to parse `{str}` as bigint: {e}"),
+      };
+    }
+    fail!(Type: "cannot convert `{value}` to a bigint")
+  } else {
+    fail!(Type: "cannot convert `{value}` to a bigint")
+  }
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, bigint);
+}
+
+impl Display for BigInt {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(&self.value, f)
+  }
+}
+
+impl Debug for BigInt {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}n", self.value)
+  }
+}
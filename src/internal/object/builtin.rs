@@ -1,34 +1,57 @@
 #![allow(dead_code)] // TEMP
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt::{Debug, Display};
 
 use indexmap::IndexMap;
 
-use super::{List, Object, Ptr, ReturnAddr, Str};
+use super::class::ClassInstance;
+use super::table::Key as TableKey;
+use super::{List, Object, Ptr, ReturnAddr, Str, Table, Tuple};
 use crate::internal::error::Result;
+use crate::internal::object::coroutine::coroutine;
+use crate::internal::object::int::int;
+use crate::internal::object::iter::Source;
 use crate::internal::object::native::LocalBoxFuture;
-use crate::internal::object::{list, string};
+use crate::internal::object::{bytes, list, string};
 use crate::internal::value::Value;
-use crate::internal::vm::global::Global;
-use crate::internal::vm::thread::util::is_truthy;
+use crate::internal::vm::global::{Global, Stream};
+use crate::internal::vm::thread::util::{compare, is_truthy, truthiness};
 use crate::internal::vm::thread::{AsyncFrame, CallResult};
+use crate::internal::vm::transfer::{Key, Transfer};
+use crate::internal::vm::{format, frozen};
 use crate::public;
 use crate::public::{Bind, Scope, Unbind};
 
 pub type Callback = fn(Scope<'_>) -> Result<Value>;
 pub type AsyncCallback = fn(Scope<'_>) -> LocalBoxFuture<'_, Result<Value>>;
 pub type MethodCallback = fn(Value, Scope<'_>) -> Result<Value>;
+pub type AsyncMethodCallback = fn(Value, Scope<'_>) -> LocalBoxFuture<'_, Result<Value>>;
 pub type TypedMethodCallback<T> = fn(Ptr<T>, Scope<'_>) -> Result<Value>;
 
 #[derive(Clone)]
 pub struct BuiltinFunction {
   pub name: &'static str,
+  /// The names of the params, for functions which may be called with
+  /// keyword arguments.
+  pub params: &'static [&'static str],
   function: Callback,
 }
 
 impl BuiltinFunction {
   pub fn new(name: &'static str, function: Callback) -> Self {
-    Self { name, function }
+    Self {
+      name,
+      params: &[],
+      function,
+    }
+  }
+
+  pub fn with_params(mut self, params: &'static [&'static str]) -> Self {
+    self.params = params;
+    self
   }
 
   pub fn call(&self, scope: Scope<'_>) -> Result<Value> {
@@ -60,8 +83,9 @@ impl Object for BuiltinFunction {
   }
 
   fn eq(_scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
-    // This is a pointer comparison.
-    Ok(this.function.eq(&other.function))
+    // Static methods of different builtin types may share a name, so the
+    // callbacks are compared as well.
+    Ok(this.name == other.name && std::ptr::fn_addr_eq(this.function, other.function))
   }
 
   fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
@@ -71,14 +95,26 @@ impl Object for BuiltinFunction {
 
 declare_object_type!(BuiltinFunction);
 
+#[derive(Clone)]
 pub struct BuiltinAsyncFunction {
   pub name: &'static str,
+  /// See [`BuiltinFunction::params`].
+  pub params: &'static [&'static str],
   function: AsyncCallback,
 }
 
 impl BuiltinAsyncFunction {
   pub fn new(name: &'static str, function: AsyncCallback) -> Self {
-    Self { name, function }
+    Self {
+      name,
+      params: &[],
+      function,
+    }
+  }
+
+  pub fn with_params(mut self, params: &'static [&'static str]) -> Self {
+    self.params = params;
+    self
   }
 
   pub fn call(&self, scope: Scope) -> LocalBoxFuture<'static, Result<Value>> {
@@ -114,12 +150,13 @@ impl Object for BuiltinAsyncFunction {
     Ok(CallResult::Poll(AsyncFrame {
       stack_base: scope.stack_base,
       fut: BuiltinAsyncFunction::call(this.as_ref(), scope),
+      on_ready: None,
     }))
   }
 
   fn eq(_scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
-    // This is a pointer comparison.
-    Ok(this.function.eq(&other.function))
+    // See `BuiltinFunction::eq`.
+    Ok(this.name == other.name && std::ptr::fn_addr_eq(this.function, other.function))
   }
 }
 
@@ -133,6 +170,7 @@ declare_object_type!(BuiltinAsyncFunction);
 pub struct BuiltinType {
   pub name: &'static str,
   methods: IndexMap<&'static str, BuiltinFunction>,
+  async_methods: IndexMap<&'static str, BuiltinAsyncFunction>,
 }
 
 impl BuiltinType {
@@ -140,6 +178,7 @@ impl BuiltinType {
     BuiltinTypeBuilder {
       name,
       methods: IndexMap::new(),
+      async_methods: IndexMap::new(),
     }
   }
 }
@@ -147,6 +186,7 @@ impl BuiltinType {
 pub struct BuiltinTypeBuilder {
   name: &'static str,
   methods: IndexMap<&'static str, BuiltinFunction>,
+  async_methods: IndexMap<&'static str, BuiltinAsyncFunction>,
 }
 
 impl BuiltinTypeBuilder {
@@ -155,18 +195,30 @@ impl BuiltinTypeBuilder {
     self
   }
 
+  pub fn async_method(mut self, name: &'static str, f: AsyncCallback) -> Self {
+    self
+      .async_methods
+      .insert(name, BuiltinAsyncFunction::new(name, f));
+    self
+  }
+
   pub fn finish(self) -> BuiltinType {
     BuiltinType {
       name: self.name,
       methods: self.methods,
+      async_methods: self.async_methods,
     }
   }
 }
 
 macro_rules! builtin_type {
-  ($name:ident { $($method_name:ident : $method_cb:expr),* }) => {
+  (
+    $name:ident { $($method_name:ident : $method_cb:expr),* }
+    $(async { $($async_method_name:ident : $async_method_cb:expr),* })?
+  ) => {
     $crate::internal::object::builtin::BuiltinType::builder(stringify!($name))
       $(.method(stringify!($method_name), $method_cb))*
+      $($(.async_method(stringify!($async_method_name), $async_method_cb))*)?
       .finish()
   }
 }
@@ -186,11 +238,14 @@ impl Object for BuiltinType {
     Ok(
       this
         .named_field_opt(scope, name.clone())?
-        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
     )
   }
 
   fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    if let Some(method) = this.async_methods.get(name.as_str()) {
+      return Ok(Some(Value::object(scope.alloc(method.clone()))));
+    }
     Ok(
       this
         .methods
@@ -209,6 +264,9 @@ declare_object_type!(BuiltinType);
 #[derive(Clone)]
 pub struct BuiltinMethod {
   this: Value,
+  /// The name of the method on the type of `this`, which identifies it in
+  /// comparisons.
+  name: Ptr<Str>,
   function: MethodCallback,
 }
 
@@ -218,8 +276,12 @@ impl BuiltinMethod {
   ///
   /// Easiest way to ensure the safety invariant is to use the
   /// `builtin_callback` macro to create the callback.
-  pub unsafe fn new(this: Value, function: MethodCallback) -> Self {
-    Self { this, function }
+  pub unsafe fn new(this: Value, name: Ptr<Str>, function: MethodCallback) -> Self {
+    Self {
+      this,
+      name,
+      function,
+    }
   }
 
   pub fn call(&self, scope: Scope<'_>) -> Result<Value> {
@@ -257,7 +319,7 @@ impl Object for BuiltinMethod {
     // unaliased pointer. Therefore, their values must have the exact same
     // representation as values, which means that we don't need to unbox them in
     // order to compare.
-    Ok(this.this.bitwise_eq(&other.this) && this.function.eq(&other.function))
+    Ok(this.this.bitwise_eq(&other.this) && this.name.as_str() == other.name.as_str())
   }
 }
 
@@ -275,6 +337,82 @@ macro_rules! builtin_method {
   }};
 }
 
+#[derive(Clone)]
+pub struct BuiltinAsyncMethod {
+  this: Value,
+  /// See [`BuiltinMethod::name`].
+  name: Ptr<Str>,
+  function: AsyncMethodCallback,
+}
+
+impl BuiltinAsyncMethod {
+  /// # Safety
+  /// - type of `this` must match expected type of `function` first param
+  ///
+  /// Easiest way to ensure the safety invariant is to use the
+  /// `builtin_async_method` macro to create the callback.
+  pub unsafe fn new(this: Value, name: Ptr<Str>, function: AsyncMethodCallback) -> Self {
+    Self {
+      this,
+      name,
+      function,
+    }
+  }
+
+  pub fn call(&self, scope: Scope) -> LocalBoxFuture<'static, Result<Value>> {
+    let scope = unsafe { ::core::mem::transmute::<Scope<'_>, Scope<'static>>(scope) };
+    (self.function)(self.this.clone(), scope)
+  }
+}
+
+impl Debug for BuiltinAsyncMethod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("BuiltinAsyncMethod").finish()
+  }
+}
+
+impl Display for BuiltinAsyncMethod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<builtin method>")
+  }
+}
+
+impl Object for BuiltinAsyncMethod {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "BuiltinAsyncMethod"
+  }
+
+  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
+    Ok(false)
+  }
+
+  fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    Ok(CallResult::Poll(AsyncFrame {
+      stack_base: scope.stack_base,
+      fut: BuiltinAsyncMethod::call(this.as_ref(), scope),
+      on_ready: None,
+    }))
+  }
+
+  fn eq(_scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // See `BuiltinMethod::eq`.
+    Ok(this.this.bitwise_eq(&other.this) && this.name.as_str() == other.name.as_str())
+  }
+}
+
+declare_object_type!(BuiltinAsyncMethod);
+
+macro_rules! builtin_async_method {
+  ($function:ident) => {{
+    let cb: $crate::internal::object::builtin::AsyncMethodCallback =
+      |this: $crate::internal::value::Value, scope: $crate::public::Scope<'_>| {
+        let this = unsafe { this.to_object_unchecked::<Self>() };
+        Box::pin($function(this, scope))
+      };
+    cb
+  }};
+}
+
 macro_rules! builtin_method_static {
   ($T:ident, $function:expr) => {{
     let cb: $crate::internal::object::builtin::Callback = |mut scope: $crate::public::Scope<'_>| {
@@ -295,33 +433,67 @@ macro_rules! builtin_method_static {
   }};
 }
 
+macro_rules! builtin_async_method_static {
+  ($T:ident, $function:ident) => {{
+    let cb: $crate::internal::object::builtin::AsyncCallback =
+      |mut scope: $crate::public::Scope<'_>| {
+        Box::pin(async move {
+          use $crate::public::Unbind;
+          let this = scope.param::<$crate::public::Value>(0)?;
+          scope.consume_args(1);
+          let this = match this.clone().unbind().to_object::<$T>() {
+            Some(value) => value,
+            None => fail!(
+              "`{this}` is not an instance of {}",
+              std::any::type_name::<$T>()
+            ),
+          };
+          $function(this, scope).await
+        })
+      };
+    cb
+  }};
+}
+
 fn to_int(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
-  if value.is_int() {
+  if value.to_i64().is_some() {
     Ok(value)
   } else if value.is_float() {
     let value = unsafe { value.to_float_unchecked() };
-    Ok(Value::int(value as i32))
+    if !value.is_finite() {
+      fail!("cannot convert `{value}` to an int");
+    }
+    Ok(int(&scope.thread.global, value as i64))
   } else {
-    fail!("cannot convert `{value}` to an int")
+    #[cfg(feature = "bigint")]
+    if let Some(value) = value.clone().to_object::<super::bigint::BigInt>() {
+      return match i64::try_from(value.value()) {
+        Ok(value) => Ok(int(&scope.thread.global, value)),
+        Err(_) => fail!("bigint `{value}` is too large to convert to an int"),
+      };
+    }
+    fail!(Type: "cannot convert `{value}` to an int")
   }
 }
 
 fn to_float(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
-  if value.is_int() {
-    let value = unsafe { value.to_int_unchecked() };
-    Ok(Value::float(value as f64))
-  } else if value.is_float() {
-    Ok(value)
-  } else {
-    fail!("cannot convert `{value}` to a float")
+  if let Some(value) = value.to_number() {
+    return Ok(Value::float(value));
   }
+  #[cfg(feature = "bigint")]
+  if let Some(value) = value.clone().to_object::<super::bigint::BigInt>() {
+    use num_traits::ToPrimitive;
+    return Ok(Value::float(value.value().to_f64().unwrap_or(f64::NAN)));
+  }
+  fail!(Type: "cannot convert `{value}` to a float")
 }
 
-fn to_bool(scope: Scope<'_>) -> Result<Value> {
+/// `to_bool(value)` calls the `__bool__` method of `value` if it has one.
+async fn to_bool(mut scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
-  let bool = is_truthy(value);
+  let bool = truthiness(&mut scope, value).await?;
   Ok(Value::bool(bool))
 }
 
@@ -335,32 +507,96 @@ fn to_str(scope: Scope<'_>) -> Result<Value> {
   }
 }
 
+/// `parse_int(value, radix=10)`
+///
+/// Strings are parsed the same way regardless of the locale: an optional
+/// sign followed by digits in `radix`, which may be separated by `_` like in
+/// literals. Anything else is an error.
 fn parse_int(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
-  if value.is_int() {
-    return Ok(value);
-  } else if value.is_float() {
-    return Ok(Value::int(unsafe { value.to_float_unchecked() } as i32));
-  } else if value.is_object() {
-    if let Some(value) = value.clone().to_object::<Str>() {
-      return Ok(Value::int(
-        value
-          .as_str()
-          .parse()
-          .map_err(|e| error!("failed to parse `{value}` as int: {e}"))?,
-      ));
+  let radix = match scope.num_args() {
+    0 | 1 => None,
+    _ => {
+      let radix = scope.param::<public::Value>(1)?.unbind();
+      match radix.to_i64() {
+        Some(radix @ 2..=36) => Some(radix as u32),
+        _ => fail!("radix must be an int between 2 and 36, got `{radix}`"),
+      }
+    }
+  };
+
+  if let Some(str) = value.clone().to_object::<Str>() {
+    let radix = radix.unwrap_or(10);
+    let Some(digits) = number_digits(str.as_str()) else {
+      fail!("failed to parse `{str}` as int: misplaced `_`");
+    };
+    return match i64::from_str_radix(&digits, radix) {
+      Ok(value) => Ok(int(&scope.thread.global, value)),
+      Err(e) if radix == 10 => fail!("failed to parse `{str}` as int: {e}"),
+      Err(e) => fail!("failed to parse `{str}` as int in base {radix}: {e}"),
     };
   }
+  if radix.is_some() {
+    fail!("cannot parse `{value}` with a radix, because it is not a string");
+  }
+  if value.to_i64().is_some() {
+    return Ok(value);
+  } else if let Some(float) = value.clone().to_float() {
+    if float.is_finite() {
+      return Ok(int(&scope.thread.global, float as i64));
+    }
+  }
 
   fail!("could not parse `{value}` as int");
 }
 
+/// `parse_float(value)`
+///
+/// Strings are parsed the same way regardless of the locale, with `.` as the
+/// decimal separator and an optional exponent. Digits may be separated by `_`
+/// like in literals.
+fn parse_float(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if let Some(str) = value.clone().to_object::<Str>() {
+    let Some(digits) = number_digits(str.as_str()) else {
+      fail!("failed to parse `{str}` as float: misplaced `_`");
+    };
+    return match digits.parse::<f64>() {
+      Ok(value) => Ok(Value::float(value)),
+      Err(e) => fail!("failed to parse `{str}` as float: {e}"),
+    };
+  }
+  if let Some(value) = value.to_number() {
+    return Ok(Value::float(value));
+  }
+
+  fail!("could not parse `{value}` as float");
+}
+
+/// Remove the `_` which separate the digits of a number, or return `None`
+/// if one of them is not between two digits.
+fn number_digits(s: &str) -> Option<Cow<'_, str>> {
+  if !s.contains('_') {
+    return Some(Cow::from(s));
+  }
+  let bytes = s.as_bytes();
+  for (i, _) in s.match_indices('_') {
+    let before = s[..i].trim_end_matches('_').bytes().next_back();
+    let after = bytes[i + 1..].iter().copied().find(|&c| c != b'_');
+    let is_digit = |c: Option<u8>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    if !is_digit(before) || !is_digit(after) {
+      return None;
+    }
+  }
+  Some(Cow::from(s.replace('_', "")))
+}
+
 fn type_of(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
 
   if value.is_float() {
     Ok(Value::object(scope.intern("float")))
-  } else if value.is_int() {
+  } else if value.to_i64().is_some() {
     Ok(Value::object(scope.intern("int")))
   } else if value.is_bool() {
     Ok(Value::object(scope.intern("bool")))
@@ -372,38 +608,250 @@ fn type_of(scope: Scope<'_>) -> Result<Value> {
   }
 }
 
-async fn collect(mut scope: Scope<'_>) -> Result<Value> {
-  let iterable = scope.param::<public::Value>(0)?.unbind();
+fn format(scope: Scope<'_>) -> Result<Value> {
+  let template = scope.param::<public::Str>(0)?.unbind();
+  let args = (1..scope.num_args())
+    .map(|n| Ok(scope.param::<public::Value>(n)?.unbind()))
+    .collect::<Result<Vec<_>>>()?;
+  let str = format::format(template.as_str(), &args)?;
+  Ok(Value::object(scope.alloc(Str::owned(str))))
+}
+
+fn eprint(scope: Scope<'_>) -> Result<Value> {
+  let values = (0..scope.num_args())
+    .map(|n| Ok(scope.param::<public::Value>(n)?.to_string()))
+    .collect::<Result<Vec<_>>>()?;
+  let span = scope.thread.call_span();
+  let io = scope.thread.global.io();
+  io.print(Stream::Stderr, &values.join(" "), span)?;
+  Ok(Value::none())
+}
 
-  let Some(iterable) = iterable.clone().to_any() else {
-    fail!("`{iterable}` is not iterable");
+fn env(scope: Scope<'_>) -> Result<Value> {
+  let name = scope.param::<public::Str>(0)?.unbind();
+  if let Some(policy) = scope.thread.global.security_policy() {
+    policy.check_env(name.as_str())?;
+  }
+  match std::env::var(name.as_str()) {
+    Ok(value) => Ok(Value::object(scope.alloc(Str::owned(value)))),
+    Err(_) => Ok(Value::none()),
+  }
+}
+
+fn help(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  match super::function::doc_of(&value) {
+    Some(doc) => Ok(Value::object(doc)),
+    None => Ok(Value::none()),
+  }
+}
+
+/// `clone(value, deep=false)`
+///
+/// Lists, tables and class instances are copied, other values are returned
+/// as they are. A deep clone also copies everything they contain, and keeps
+/// shared references and cycles between the copies.
+fn clone(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let deep = scope.num_args() > 1 && is_truthy(scope.param::<public::Value>(1)?.unbind());
+  if deep {
+    return Transfer::new(&scope.thread.global, true).value(&value);
+  }
+
+  let Some(object) = value.clone().to_any() else {
+    return Ok(value);
   };
+  if let Some(list) = object.clone_cast::<List>() {
+    let copy = List::with_capacity(list.len());
+    for item in list.iter() {
+      copy.push(item);
+    }
+    return Ok(Value::object(scope.alloc(copy)));
+  }
+  if let Some(table) = object.clone_cast::<Table>() {
+    return Ok(Value::object(scope.alloc(table.copy())));
+  }
+  if let Some(instance) = object.clone_cast::<ClassInstance>() {
+    let global = &scope.thread.global;
+    let copy = global.alloc(ClassInstance::new(global.clone(), instance.class.clone()));
+    if instance.class.methods.contains_key("__del__") {
+      global.register_finalizer(copy.clone().into_any());
+    }
+    for (key, value) in instance.fields.entries() {
+      if let Some(key) = key.as_str() {
+        copy.insert_field(key.clone(), value);
+      }
+    }
+    return Ok(Value::object(copy));
+  }
+  Ok(value)
+}
+
+/// `eq_deep(a, b)`
+///
+/// Lists, tuples, tables and class instances of the same class are equal if
+/// their contents are equal, which is checked recursively. Other values are
+/// compared using `==`.
+fn eq_deep(scope: Scope<'_>) -> Result<Value> {
+  let (lhs, rhs) = scope.params::<(public::Value, public::Value)>()?;
+  let eq = DeepEq::default().eq(&scope, &lhs.unbind(), &rhs.unbind())?;
+  Ok(Value::bool(eq))
+}
+
+/// Pairs of objects which are reached again while they are being compared
+/// are assumed to be equal. This makes cyclic values equal if they have the
+/// same structure, instead of recursing forever.
+#[derive(Default)]
+struct DeepEq {
+  seen: HashSet<(Key, Key)>,
+}
+
+impl DeepEq {
+  fn eq(&mut self, scope: &Scope<'_>, lhs: &Value, rhs: &Value) -> Result<bool> {
+    let (Some(a), Some(b)) = (lhs.clone().to_any(), rhs.clone().to_any()) else {
+      return scope.are_equal(lhs.clone(), rhs.clone());
+    };
+    if a.ptr_eq(&b) || !self.seen.insert((Key(a.clone()), Key(b.clone()))) {
+      return Ok(true);
+    }
+
+    if let (Some(a), Some(b)) = (a.clone_cast::<List>(), b.clone_cast::<List>()) {
+      let (a, b) = (a.iter().collect::<Vec<_>>(), b.iter().collect::<Vec<_>>());
+      return self.all(scope, &a, &b);
+    }
+    if let (Some(a), Some(b)) = (a.clone_cast::<Tuple>(), b.clone_cast::<Tuple>()) {
+      return self.all(scope, a.as_slice(), b.as_slice());
+    }
+    if let (Some(a), Some(b)) = (a.clone_cast::<Table>(), b.clone_cast::<Table>()) {
+      return self.tables(scope, &a, &b);
+    }
+    if let (Some(a), Some(b)) = (
+      a.clone_cast::<ClassInstance>(),
+      b.clone_cast::<ClassInstance>(),
+    ) {
+      return Ok(a.class.ptr_eq(&b.class) && self.tables(scope, &a.fields, &b.fields)?);
+    }
+    scope.are_equal(lhs.clone(), rhs.clone())
+  }
 
-  let iter = iterable
-    .named_field(scope.clone(), scope.intern("iter"))?
-    .to_any()
-    .ok_or_else(|| error!("`iter` is not callable"))?
-    .bind(scope.global());
+  fn all(&mut self, scope: &Scope<'_>, a: &[Value], b: &[Value]) -> Result<bool> {
+    if a.len() != b.len() {
+      return Ok(false);
+    }
+    for (a, b) in a.iter().zip(b) {
+      if !self.eq(scope, a, b)? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
 
-  let iterator = scope.call(iter, &[]).await?.unbind();
-  let Some(iterator) = iterator.clone().to_any() else {
-    fail!("`{iterable}` is not an iterator");
+  fn tables(&mut self, scope: &Scope<'_>, a: &Table, b: &Table) -> Result<bool> {
+    if a.len() != b.len() {
+      return Ok(false);
+    }
+    for (key, a) in a.entries() {
+      let Some(b) = b.get(&key) else {
+        return Ok(false);
+      };
+      if !self.eq(scope, &a, &b)? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+}
+
+/// `hash(value)`
+///
+/// Values which may be used as table keys are hashed the same way as keys,
+/// so `1` and `1.0` have the same hash. Other objects must have a `__hash__`
+/// method, which returns an int.
+async fn hash(mut scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if let Some(key) = TableKey::new(&value) {
+    return Ok(int(&scope.thread.global, key.stable_hash() as i64));
+  }
+
+  let method = value.clone().to_any().and_then(|object| {
+    // some objects fail instead of returning `None` for unknown fields
+    object
+      .named_field_opt(scope.clone(), scope.intern("__hash__"))
+      .ok()
+      .flatten()
+  });
+  let Some(method) = method.and_then(|method| method.to_any()) else {
+    fail!(Type: "`{value}` is not hashable");
+  };
+  let method = method.bind(scope.global());
+  let hash = scope.call(method, &[]).await?.unbind();
+  if hash.to_i64().is_none() {
+    fail!(Type: "`__hash__` must return an int, got `{hash}`");
+  }
+  Ok(hash)
+}
+
+/// `cmp(a, b)` returns `-1`, `0` or `1` if `a` is ordered before, the same
+/// as, or after `b`. Class instances may define the order with a `__cmp__`
+/// method, which is called with `b`.
+async fn cmp(mut scope: Scope<'_>) -> Result<Value> {
+  let lhs = scope.param::<public::Value>(0)?.unbind();
+  let rhs = scope.param::<public::Value>(1)?.unbind();
+  let ordering = compare(&mut scope, lhs, rhs).await?;
+  Ok(Value::int(ordering as i32))
+}
+
+/// `min(a, b, ...)` returns the first argument which no other one is ordered
+/// before, as in `cmp`. A single list argument is the same as passing each
+/// of its items.
+async fn min(scope: Scope<'_>) -> Result<Value> {
+  extremum(scope, "min", Ordering::Less).await
+}
+
+/// `max(a, b, ...)`, the counterpart to `min`.
+async fn max(scope: Scope<'_>) -> Result<Value> {
+  extremum(scope, "max", Ordering::Greater).await
+}
+
+async fn extremum(mut scope: Scope<'_>, name: &str, wanted: Ordering) -> Result<Value> {
+  let mut values = Vec::with_capacity(scope.num_args());
+  for n in 0..scope.num_args() {
+    values.push(scope.param::<public::Value>(n)?.unbind());
+  }
+  if let [value] = &values[..] {
+    if let Some(list) = value.clone().to_object::<List>() {
+      values = list.iter().collect();
+    }
+  }
+
+  let mut values = values.into_iter();
+  let Some(mut result) = values.next() else {
+    fail!("`{name}` expects at least one value");
   };
+  for value in values {
+    if compare(&mut scope, value.clone(), result.clone()).await? == wanted {
+      result = value;
+    }
+  }
+  Ok(result)
+}
+
+/// `freeze(value)` returns an immutable copy of `value`, in which lists are
+/// replaced by tuples. Fails if `value` contains anything other than `none`,
+/// bools, numbers, strings, tuples and lists.
+fn freeze(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let frozen = frozen::freeze(&value)?;
+  Ok(frozen::thaw(&frozen, &scope.thread.global))
+}
 
-  let next = iterator
-    .named_field(scope.clone(), scope.intern("next"))?
-    .to_any()
-    .ok_or_else(|| error!("`next` is not callable"))?
-    .bind(scope.global());
-  let done = iterator
-    .named_field(scope.clone(), scope.intern("done"))?
-    .to_any()
-    .ok_or_else(|| error!("`done` is not callable"))?
-    .bind(scope.global());
+async fn collect(mut scope: Scope<'_>) -> Result<Value> {
+  let iterable = scope.param::<public::Value>(0)?.unbind();
+  let source = Source::new(&mut scope, iterable).await?;
 
   let list = List::new();
-  while !is_truthy(scope.call(done.clone(), &[]).await?.unbind()) {
-    list.push(scope.call(next.clone(), &[]).await?.unbind());
+  while !source.done(&mut scope).await? {
+    list.push(source.next(&mut scope).await?);
   }
   let list = scope.alloc(list);
 
@@ -411,6 +859,16 @@ async fn collect(mut scope: Scope<'_>) -> Result<Value> {
 }
 
 macro_rules! bind_builtin_fn {
+  ($global:ident, $builtin:ident($($param:ident),*)) => {{
+    let name = stringify!($builtin);
+    $global.set(
+      $global.intern(name),
+      $crate::internal::value::Value::object($global.alloc(
+        $crate::internal::object::builtin::BuiltinFunction::new(name, $builtin)
+          .with_params(&[$(stringify!($param)),*]),
+      )),
+    )
+  }};
   ($global:ident, $builtin:ident) => {{
     let name = stringify!($builtin);
     $global.set(
@@ -420,6 +878,18 @@ macro_rules! bind_builtin_fn {
       )),
     )
   }};
+  ($global:ident, async $builtin:ident($($param:ident),*)) => {{
+    let name = stringify!($builtin);
+    $global.set(
+      $global.intern(name),
+      $crate::internal::value::Value::object($global.alloc(
+        $crate::internal::object::builtin::BuiltinAsyncFunction::new(name, |scope| {
+          Box::pin(($builtin)(scope))
+        })
+        .with_params(&[$(stringify!($param)),*]),
+      )),
+    )
+  }};
   ($global:ident, async $builtin:ident) => {{
     let name = stringify!($builtin);
     $global.set(
@@ -443,15 +913,49 @@ macro_rules! bind_builtin_type {
   }};
 }
 
+/// Register the `env` builtin, which is only available to scripts if the
+/// host allows access to the OS with `HebiBuilder::allow_os`.
+pub fn register_env(global: &Global) {
+  bind_builtin_fn!(global, env);
+}
+
 pub fn register_builtin_functions(global: &Global) {
   bind_builtin_fn!(global, to_int);
   bind_builtin_fn!(global, to_float);
-  bind_builtin_fn!(global, to_bool);
+  bind_builtin_fn!(global, async to_bool);
   bind_builtin_fn!(global, to_str);
   bind_builtin_fn!(global, type_of);
-  bind_builtin_fn!(global, parse_int);
+  bind_builtin_fn!(global, parse_int(value, radix));
+  bind_builtin_fn!(global, parse_float(value));
+  bind_builtin_fn!(global, format);
+  bind_builtin_fn!(global, eprint);
+  bind_builtin_fn!(global, help);
+  bind_builtin_fn!(global, clone(value, deep));
+  bind_builtin_fn!(global, eq_deep);
+  bind_builtin_fn!(global, async hash);
+  bind_builtin_fn!(global, async cmp);
+  bind_builtin_fn!(global, async min);
+  bind_builtin_fn!(global, async max);
+  bind_builtin_fn!(global, freeze);
   bind_builtin_fn!(global, async collect);
+  bind_builtin_fn!(global, coroutine);
+
+  let argv = List::new();
+  for arg in global.args() {
+    argv.push(Value::object(global.alloc(Str::owned(arg))));
+  }
+  global.set(global.intern("argv"), Value::object(global.alloc(argv)));
 
+  super::class::register_error_class(global);
+  super::iter::register_builtin_functions(global);
   list::register_builtin_functions(global);
+  super::set::register_builtin_functions(global);
+  super::channel::register_builtin_functions(global);
+  super::host::register_builtin_functions(global);
+  super::protocol::register_builtin_protocols(global);
   string::register_builtin_functions(global);
+  bytes::register_builtin_functions(global);
+  super::string_builder::register_builtin_functions(global);
+  #[cfg(feature = "bigint")]
+  super::bigint::register_builtin_functions(global);
 }
@@ -0,0 +1,360 @@
+use std::cell::{Cell, Ref, RefCell};
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::list::to_index;
+use super::{List, Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A mutable buffer of bytes.
+#[derive(Default)]
+pub struct Bytes {
+  data: RefCell<Vec<u8>>,
+}
+
+impl Bytes {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.data.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.borrow().is_empty()
+  }
+
+  pub fn get(&self, index: usize) -> Option<u8> {
+    self.data.borrow().get(index).copied()
+  }
+
+  #[must_use = "`set` returns false if index is out of bounds"]
+  pub fn set(&self, index: usize, value: u8) -> bool {
+    if let Some(slot) = self.data.borrow_mut().get_mut(index) {
+      *slot = value;
+      true
+    } else {
+      false
+    }
+  }
+
+  pub fn push(&self, value: u8) {
+    self.data.borrow_mut().push(value);
+  }
+
+  pub fn pop(&self) -> Option<u8> {
+    self.data.borrow_mut().pop()
+  }
+
+  pub fn extend_from_slice(&self, other: &[u8]) {
+    self.data.borrow_mut().extend_from_slice(other);
+  }
+
+  pub fn as_slice(&self) -> Ref<'_, [u8]> {
+    Ref::map(self.data.borrow(), |v| &v[..])
+  }
+
+  pub fn to_vec(&self) -> Vec<u8> {
+    self.data.borrow().clone()
+  }
+}
+
+impl From<Vec<u8>> for Bytes {
+  fn from(data: Vec<u8>) -> Self {
+    Self {
+      data: RefCell::new(data),
+    }
+  }
+}
+
+impl Display for Bytes {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "b\"{}\"", self.data.borrow().escape_ascii())
+  }
+}
+
+impl Debug for Bytes {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(self, f)
+  }
+}
+
+fn to_byte(value: Value) -> Result<u8> {
+  match value.to_i64().map(u8::try_from) {
+    Some(Ok(byte)) => Ok(byte),
+    _ => fail!("`{value}` is not a valid byte, expected an int between 0 and 255"),
+  }
+}
+
+/// Collect the bytes of a `Bytes`, `List` of ints, or `Str` (as utf-8).
+fn to_byte_vec(value: Value) -> Result<Vec<u8>> {
+  if let Some(bytes) = value.clone().to_object::<Bytes>() {
+    return Ok(bytes.to_vec());
+  }
+  if let Some(list) = value.clone().to_object::<List>() {
+    return list.iter().map(to_byte).collect();
+  }
+  if let Some(str) = value.clone().to_object::<Str>() {
+    return Ok(str.as_bytes().to_vec());
+  }
+  fail!(Type: "cannot convert `{value}` to bytes")
+}
+
+fn bytes_len(this: Ptr<Bytes>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.len() as i32))
+}
+
+fn bytes_is_empty(this: Ptr<Bytes>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_empty()))
+}
+
+fn bytes_get(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let index = scope.param::<public::Value>(0)?.unbind();
+  let index = to_index(index, this.len())?;
+  Ok(
+    this
+      .get(index)
+      .map(|v| Value::int(v as i32))
+      .unwrap_or_else(Value::none),
+  )
+}
+
+fn bytes_set(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let (index, value) = scope.params::<(public::Value, public::Value)>()?;
+  let (index, value) = (index.unbind(), value.unbind());
+  let len = this.len();
+  let index = to_index(index, len)?;
+  if !this.set(index, to_byte(value)?) {
+    fail!(Index: "index `{index}` out of bounds, len was `{len}`")
+  }
+
+  Ok(Value::none())
+}
+
+fn bytes_push(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  this.push(to_byte(value)?);
+  Ok(Value::none())
+}
+
+fn bytes_pop(this: Ptr<Bytes>, _: Scope<'_>) -> Result<Value> {
+  Ok(
+    this
+      .pop()
+      .map(|v| Value::int(v as i32))
+      .unwrap_or_else(Value::none),
+  )
+}
+
+fn bytes_extend(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let other = scope.param::<public::Value>(0)?.unbind();
+  let other = to_byte_vec(other)?;
+  this.extend_from_slice(&other);
+  Ok(Value::none())
+}
+
+fn bytes_slice(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let len = this.len();
+  let start = to_index(scope.param::<public::Value>(0)?.unbind(), len)?;
+  let end = if scope.num_args() > 1 {
+    to_index(scope.param::<public::Value>(1)?.unbind(), len)?
+  } else {
+    len
+  };
+  if start > end || end > len {
+    fail!(Index: "slice `{start}..{end}` out of bounds, len was `{len}`");
+  }
+  let data = this.as_slice()[start..end].to_vec();
+  Ok(Value::object(scope.alloc(Bytes::from(data))))
+}
+
+fn bytes_to_str(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let str = match std::str::from_utf8(&this.as_slice()) {
+    Ok(str) => Str::owned(str),
+    Err(e) => fail!("bytes are not valid utf-8: {e}"),
+  };
+  Ok(Value::object(scope.alloc(str)))
+}
+
+fn bytes_to_list(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  let list = List::with_capacity(this.len());
+  for byte in this.as_slice().iter() {
+    list.push(Value::int(*byte as i32));
+  }
+  Ok(Value::object(scope.alloc(list)))
+}
+
+#[derive(Debug)]
+pub struct BytesIter {
+  bytes: Ptr<Bytes>,
+  index: Cell<usize>,
+}
+
+impl Display for BytesIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<bytes iter>")
+  }
+}
+
+fn bytes_iter_iter(this: Ptr<BytesIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+fn bytes_iter_next(this: Ptr<BytesIter>, _: Scope<'_>) -> Result<Value> {
+  if let Some(byte) = this.bytes.get(this.index.get()) {
+    this.index.set(this.index.get() + 1);
+    Ok(Value::int(byte as i32))
+  } else {
+    Ok(Value::none())
+  }
+}
+
+fn bytes_iter_done(this: Ptr<BytesIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.bytes.get(this.index.get()).is_none()))
+}
+
+impl Object for BytesIter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "BytesIter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_method!(bytes_iter_iter),
+      "next" => builtin_method!(bytes_iter_next),
+      "done" => builtin_method!(bytes_iter_done),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(BytesIter);
+
+fn bytes_iter(this: Ptr<Bytes>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(BytesIter {
+    bytes: this,
+    index: Cell::new(0),
+  })))
+}
+
+impl Object for Bytes {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Bytes"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "len" => builtin_method!(bytes_len),
+      "is_empty" => builtin_method!(bytes_is_empty),
+      "get" => builtin_method!(bytes_get),
+      "set" => builtin_method!(bytes_set),
+      "push" => builtin_method!(bytes_push),
+      "pop" => builtin_method!(bytes_pop),
+      "extend" => builtin_method!(bytes_extend),
+      "slice" => builtin_method!(bytes_slice),
+      "to_str" => builtin_method!(bytes_to_str),
+      "to_list" => builtin_method!(bytes_to_list),
+      "iter" => builtin_method!(bytes_iter),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+
+  fn keyed_field(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Value> {
+    let len = this.len();
+    let index = to_index(key.clone(), len)?;
+    let value = this
+      .get(index)
+      .ok_or_else(|| error!(Index: "index `{key}` out of bounds, len was `{len}`"))?;
+    Ok(Value::int(value as i32))
+  }
+
+  fn keyed_field_opt(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Option<Value>> {
+    let len = this.len();
+    let index = to_index(key, len)?;
+    Ok(this.get(index).map(|v| Value::int(v as i32)))
+  }
+
+  fn set_keyed_field(_: Scope<'_>, this: Ptr<Self>, key: Value, value: Value) -> Result<()> {
+    let len = this.len();
+    let index = to_index(key.clone(), len)?;
+    if !this.set(index, to_byte(value)?) {
+      fail!(Index: "index `{key}` out of bounds, len was `{len}`");
+    };
+    Ok(())
+  }
+
+  fn contains(_: Scope<'_>, this: Ptr<Self>, item: Value) -> Result<bool> {
+    Ok(this.as_slice().contains(&to_byte(item)?))
+  }
+
+  fn add(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let mut data = this.to_vec();
+    data.extend_from_slice(&other.as_slice());
+    Ok(Value::object(scope.alloc(Bytes::from(data))))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(*this.as_slice() == *other.as_slice())
+  }
+}
+
+declare_object_type!(Bytes);
+
+fn bytes(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let data = to_byte_vec(value)?;
+  Ok(Value::object(scope.alloc(Bytes::from(data))))
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, bytes);
+  bind_builtin_type!(
+    global,
+    builtin_type!(Bytes {
+      len: builtin_method_static!(Bytes, bytes_len),
+      is_empty: builtin_method_static!(Bytes, bytes_is_empty),
+      get: builtin_method_static!(Bytes, bytes_get),
+      set: builtin_method_static!(Bytes, bytes_set),
+      push: builtin_method_static!(Bytes, bytes_push),
+      pop: builtin_method_static!(Bytes, bytes_pop),
+      extend: builtin_method_static!(Bytes, bytes_extend),
+      slice: builtin_method_static!(Bytes, bytes_slice),
+      to_str: builtin_method_static!(Bytes, bytes_to_str),
+      to_list: builtin_method_static!(Bytes, bytes_to_list),
+      iter: builtin_method_static!(Bytes, bytes_iter)
+    })
+  );
+}
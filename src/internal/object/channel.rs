@@ -0,0 +1,344 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use super::builtin::{BuiltinAsyncMethod, BuiltinMethod};
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::frozen::{self, Frozen};
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A queue of values, which may be sent to by one side and received from by
+/// another.
+///
+/// Scripts create channels with `channel()`, or `channel(capacity)` for a
+/// bounded one, and use them through `send`, `recv` and `close`. `recv` on an
+/// empty channel, and `send` on a full one, suspend the script until another
+/// side makes progress.
+///
+/// A channel is also a handle which the host may hold on to, to push events
+/// into a script, or receive values from it. Values are frozen when they are
+/// sent (see [`Frozen`]), so a channel may be used from any thread, and even
+/// connect scripts running in different VMs.
+///
+/// ```rust
+/// use hebi::{Channel, Hebi};
+///
+/// let mut hebi = Hebi::new();
+/// let events = Channel::unbounded();
+/// let global = hebi.global();
+/// global.set(global.new_string("events"), global.new_channel(&events));
+///
+/// let host = std::thread::spawn(move || {
+///   for n in 1..=3 {
+///     events.try_send(n).unwrap();
+///   }
+///   events.close();
+/// });
+///
+/// let total = hebi
+///   .eval(
+///     r#"
+/// total := 0
+/// loop:
+///   event := events.recv()
+///   if event == none: break
+///   total += event
+/// total
+/// "#,
+///   )
+///   .unwrap();
+/// host.join().unwrap();
+/// assert_eq!(total.as_int(), Some(6));
+/// ```
+#[derive(Clone)]
+pub struct Channel {
+  shared: Arc<Shared>,
+}
+
+struct Shared {
+  capacity: Option<usize>,
+  state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+  queue: VecDeque<Frozen>,
+  closed: bool,
+  receivers: Vec<Waker>,
+  senders: Vec<Waker>,
+}
+
+impl State {
+  fn wake(wakers: &mut Vec<Waker>) {
+    for waker in wakers.drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+impl Channel {
+  /// Create a channel which may hold any number of values.
+  pub fn unbounded() -> Self {
+    Self::new(None)
+  }
+
+  /// Create a channel which holds at most `capacity` values. Sending to a
+  /// full channel waits until a value is received from it.
+  ///
+  /// Panics if `capacity` is zero.
+  pub fn bounded(capacity: usize) -> Self {
+    assert!(capacity > 0, "channel capacity must be greater than zero");
+    Self::new(Some(capacity))
+  }
+
+  fn new(capacity: Option<usize>) -> Self {
+    Self {
+      shared: Arc::new(Shared {
+        capacity,
+        state: Mutex::new(State::default()),
+      }),
+    }
+  }
+
+  fn state(&self) -> MutexGuard<'_, State> {
+    // a panic while the lock is held can't leave the queue in an invalid state
+    self
+      .shared
+      .state
+      .lock()
+      .unwrap_or_else(|poison| poison.into_inner())
+  }
+
+  /// Send `value`, waiting until there is space for it.
+  ///
+  /// Fails if the channel is closed.
+  pub async fn send(&self, value: impl Into<Frozen>) -> Result<()> {
+    let mut value = Some(value.into());
+    std::future::poll_fn(|cx| self.poll_send(cx, &mut value)).await
+  }
+
+  /// Send `value` without waiting.
+  ///
+  /// Fails if the channel is closed or full.
+  pub fn try_send(&self, value: impl Into<Frozen>) -> Result<()> {
+    let mut state = self.state();
+    if state.closed {
+      fail!("cannot send to a closed channel");
+    }
+    if self.is_full(&state) {
+      fail!("cannot send to a full channel");
+    }
+    state.queue.push_back(value.into());
+    State::wake(&mut state.receivers);
+    Ok(())
+  }
+
+  /// Receive a value, waiting until there is one.
+  ///
+  /// Returns `None` once the channel is closed and empty.
+  pub async fn recv(&self) -> Option<Frozen> {
+    std::future::poll_fn(|cx| self.poll_recv(cx)).await
+  }
+
+  /// Receive a value without waiting.
+  pub fn try_recv(&self) -> Option<Frozen> {
+    let mut state = self.state();
+    let value = state.queue.pop_front()?;
+    State::wake(&mut state.senders);
+    Some(value)
+  }
+
+  /// Close the channel. Values which were already sent may still be
+  /// received, but no more may be sent.
+  pub fn close(&self) {
+    let mut state = self.state();
+    state.closed = true;
+    State::wake(&mut state.receivers);
+    State::wake(&mut state.senders);
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.state().closed
+  }
+
+  /// The number of values which were sent, but not received yet.
+  pub fn len(&self) -> usize {
+    self.state().queue.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn is_full(&self, state: &State) -> bool {
+    self
+      .shared
+      .capacity
+      .is_some_and(|capacity| state.queue.len() >= capacity)
+  }
+
+  fn poll_send(&self, cx: &mut Context<'_>, value: &mut Option<Frozen>) -> Poll<Result<()>> {
+    let mut state = self.state();
+    if state.closed {
+      return Poll::Ready(Err(error!("cannot send to a closed channel").into()));
+    }
+    if self.is_full(&state) {
+      state.senders.push(cx.waker().clone());
+      return Poll::Pending;
+    }
+    if let Some(value) = value.take() {
+      state.queue.push_back(value);
+    }
+    State::wake(&mut state.receivers);
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Frozen>> {
+    let mut state = self.state();
+    if let Some(value) = state.queue.pop_front() {
+      State::wake(&mut state.senders);
+      return Poll::Ready(Some(value));
+    }
+    if state.closed {
+      return Poll::Ready(None);
+    }
+    state.receivers.push(cx.waker().clone());
+    Poll::Pending
+  }
+
+  /// Whether this is the only handle to the channel, in which case nothing
+  /// else can send to or receive from it while the script waits.
+  fn is_unique(&self) -> bool {
+    Arc::strong_count(&self.shared) == 1
+  }
+}
+
+/// `channel(capacity)` creates a channel, which is unbounded if `capacity` is
+/// not given.
+fn channel(scope: Scope<'_>) -> Result<Value> {
+  let channel = match scope.num_args() {
+    0 => Channel::unbounded(),
+    _ => {
+      let capacity = scope.param::<public::Value>(0)?.unbind();
+      match capacity.to_i64() {
+        Some(capacity) if capacity > 0 => Channel::bounded(capacity as usize),
+        _ => fail!("channel capacity must be a positive int, got `{capacity}`"),
+      }
+    }
+  };
+  Ok(Value::object(scope.alloc(channel)))
+}
+
+async fn channel_send(this: Ptr<Channel>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let value = frozen::freeze(&value)?;
+  let blocked = {
+    let state = this.state();
+    !state.closed && this.is_full(&state)
+  };
+  if blocked && this.is_unique() {
+    fail!("`send` on a full channel would wait forever, because nothing else can receive from it");
+  }
+  this.send(value).await?;
+  Ok(Value::none())
+}
+
+async fn channel_recv(this: Ptr<Channel>, scope: Scope<'_>) -> Result<Value> {
+  let blocked = {
+    let state = this.state();
+    !state.closed && state.queue.is_empty()
+  };
+  if blocked && this.is_unique() {
+    fail!("`recv` on an empty channel would wait forever, because nothing else can send to it");
+  }
+  match this.recv().await {
+    Some(value) => Ok(frozen::thaw(&value, &scope.thread.global)),
+    None => Ok(Value::none()),
+  }
+}
+
+fn channel_try_recv(this: Ptr<Channel>, scope: Scope<'_>) -> Result<Value> {
+  match this.try_recv() {
+    Some(value) => Ok(frozen::thaw(&value, &scope.thread.global)),
+    None => Ok(Value::none()),
+  }
+}
+
+fn channel_close(this: Ptr<Channel>, _: Scope<'_>) -> Result<Value> {
+  this.close();
+  Ok(Value::none())
+}
+
+fn channel_is_closed(this: Ptr<Channel>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_closed()))
+}
+
+fn channel_len(this: Ptr<Channel>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.len() as i32))
+}
+
+impl Object for Channel {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Channel"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "send" | "recv" => {
+        let method = match name.as_str() {
+          "send" => builtin_async_method!(channel_send),
+          _ => builtin_async_method!(channel_recv),
+        };
+        return Ok(Some(Value::object(unsafe {
+          scope.alloc(BuiltinAsyncMethod::new(Value::object(this), name, method))
+        })));
+      }
+      "try_recv" => builtin_method!(channel_try_recv),
+      "close" => builtin_method!(channel_close),
+      "is_closed" => builtin_method!(channel_is_closed),
+      "len" => builtin_method!(channel_len),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(Channel);
+
+impl Display for Channel {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<channel>")
+  }
+}
+
+impl Debug for Channel {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Channel")
+      .field("capacity", &self.shared.capacity)
+      .field("len", &self.len())
+      .field("closed", &self.is_closed())
+      .finish()
+  }
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, channel);
+}
@@ -1,36 +1,184 @@
-use std::fmt::Display;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::fmt::{Debug, Display};
 
 use indexmap::IndexMap;
 
+use super::module::ModuleId;
+use super::native::{NativeClass, NativeClassInstance, NativeFunction};
 use super::ptr::Ptr;
-use super::{BoundFunction, Function, FunctionDescriptor, Object, ReturnAddr, Str, Table};
+use super::shape::{Shape, ShapeId};
+use super::{BoundFunction, Function, FunctionDescriptor, List, Object, ReturnAddr, Str, Table};
 use crate::internal::error::Result;
+use crate::internal::value::constant::Constant;
 use crate::internal::value::Value;
 use crate::internal::vm::global::Global;
+use crate::internal::vm::thread::cache::FieldCache;
+use crate::internal::vm::thread::util::check_hint;
 use crate::internal::vm::thread::CallResult;
+use crate::internal::{codegen, syntax};
 use crate::public::Scope;
 
-#[derive(Debug)]
 pub struct ClassInstance {
   pub name: Ptr<Str>,
+  /// New fields must be added with `insert_field`, so that the shape is kept
+  /// up to date.
   pub fields: Ptr<Table>,
+  /// The shape of the class, or `None` if the instance has gained fields
+  /// which the class doesn't have.
+  shape: Cell<Option<ShapeId>>,
+  pub class: Ptr<ClassType>,
   pub parent: Option<Ptr<ClassType>>,
+  /// The type hints of the class' fields, if they are checked.
+  pub hints: Option<Ptr<Table>>,
+  /// The instance of the native class this class is derived from, created by
+  /// its initializer.
+  pub native: RefCell<Option<Ptr<NativeClassInstance>>>,
 }
 
 impl ClassInstance {
-  pub fn new(global: Global, type_: &ClassType) -> Self {
+  pub fn new(global: Global, type_: Ptr<ClassType>) -> Self {
     let name = type_.name.clone();
     let fields = global.alloc(type_.fields.copy());
     for (key, method) in type_.methods.iter() {
       fields.insert(key.clone(), Value::object(method.clone()));
     }
+    // every instance of the class starts out with the same fields
+    let shape = type_
+      .shape
+      .get_or_init(|| Shape::new(fields.keys().filter_map(|key| key.as_str().cloned())))
+      .id();
     let parent = type_.parent.clone();
+    let hints = (global.type_checks() && !type_.hints.is_empty()).then(|| type_.hints.clone());
     Self {
       name,
       fields,
+      shape: Cell::new(Some(shape)),
+      class: type_,
       parent,
+      hints,
+      native: RefCell::new(None),
+    }
+  }
+
+  pub fn shape(&self) -> Option<ShapeId> {
+    self.shape.get()
+  }
+
+  /// Set the field `key`, adding it if it doesn't exist yet. The instance
+  /// loses its shape if the field is new.
+  pub fn insert_field(&self, key: Ptr<Str>, value: Value) {
+    if self.fields.insert(key, value).is_none() {
+      self.shape.set(None);
     }
   }
+
+  /// Fail if `value` does not match the type hint of the field `name`.
+  fn check_field(&self, name: &Ptr<Str>, value: &Value) -> Result<()> {
+    let hint = self.hints.as_ref().and_then(|hints| hints.get(name));
+    let Some(hint) = hint.and_then(|hint| hint.to_object::<Str>()) else {
+      return Ok(());
+    };
+    check_hint(value, hint.as_str(), format_args!("field `{name}`"))
+  }
+
+  /// Run the initializer of `class`, and store the native instance it
+  /// returns in `this`.
+  fn init_native(scope: Scope<'_>, this: &Ptr<Self>, class: &Ptr<NativeClass>) -> Result<()> {
+    let Some(init) = class.init.as_ref() else {
+      fail!("native class `{}` has no initializer", class.name);
+    };
+    let value = NativeFunction::call(init.as_ref(), scope)?;
+    let Some(native) = value.clone().to_object::<NativeClassInstance>() else {
+      fail!(Type: "initializer of `{}` returned `{value}`", class.name);
+    };
+    *this.native.borrow_mut() = Some(native);
+    Ok(())
+  }
+
+  fn native_field(scope: Scope<'_>, this: &Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let native = this.native.borrow().clone();
+    match native {
+      Some(native) => native.named_field_opt(scope, name),
+      None => Ok(None),
+    }
+  }
+}
+
+impl ClassInstance {
+  /// Like `named_field_opt`, but uses the slot stored in `cache` if the
+  /// instance has the shape it was stored for, and updates `cache` on a miss.
+  pub fn named_field_cached(
+    scope: Scope<'_>,
+    this: Ptr<Self>,
+    name: &Ptr<Str>,
+    cache: &FieldCache,
+  ) -> Option<Value> {
+    let shape = this.shape();
+    let value = match shape
+      .and_then(|shape| cache.get(shape))
+      .and_then(|slot| this.fields.get_index(slot))
+    {
+      Some(value) => value,
+      None => {
+        let (slot, value) = this.fields.get_full(name)?;
+        if let Some(shape) = shape {
+          cache.set(shape, slot);
+        }
+        value
+      }
+    };
+    Some(bind(scope, this, value))
+  }
+
+  /// Like `set_named_field`, but uses the slot stored in `cache` if the
+  /// instance has the shape it was stored for, and updates `cache` on a miss.
+  ///
+  /// Returns `false` if the instance has no field called `name`.
+  pub fn set_named_field_cached(
+    this: Ptr<Self>,
+    name: &Ptr<Str>,
+    value: Value,
+    cache: &FieldCache,
+  ) -> Result<bool> {
+    this.check_field(name, &value)?;
+    let shape = this.shape();
+    if let Some(slot) = shape.and_then(|shape| cache.get(shape)) {
+      if this.fields.set_index(slot, value.clone()) {
+        return Ok(true);
+      }
+    }
+    match this.fields.set_full(name, value) {
+      Some(slot) => {
+        if let Some(shape) = shape {
+          cache.set(shape, slot);
+        }
+        Ok(true)
+      }
+      None => Ok(false),
+    }
+  }
+}
+
+/// Bind `value` to `this` if it is a method.
+fn bind(scope: Scope<'_>, this: Ptr<ClassInstance>, value: Value) -> Value {
+  match value.clone().to_object::<Function>() {
+    Some(function) => Value::object(scope.alloc(BoundFunction::new(this.into_any(), function))),
+    None => value,
+  }
+}
+
+// The shape is left out, because its id differs between runs.
+impl Debug for ClassInstance {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ClassInstance")
+      .field("name", &self.name)
+      .field("fields", &self.fields)
+      .field("class", &self.class)
+      .field("parent", &self.parent)
+      .field("hints", &self.hints)
+      .field("native", &self.native)
+      .finish()
+  }
 }
 
 impl Display for ClassInstance {
@@ -44,47 +192,44 @@ impl Object for ClassInstance {
     "Instance"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    todo!()
+  fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool> {
+    if let Some(ty) = ty.clone().to_object::<NativeClass>() {
+      return Ok(this.class.native.as_ref().is_some_and(|n| n.ptr_eq(&ty)));
+    }
+    Ok(match ty.to_object::<ClassType>() {
+      Some(ty) => this.class.is_subclass_of(&ty),
+      None => false,
+    })
   }
 
   fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
-    let value = this
-      .fields
-      .get(&name)
-      .ok_or_else(|| error!("`{this}` has no field `{name}`"))?;
-
-    // bind functions
-    if let Some(function) = value.clone().to_object::<Function>() {
-      return Ok(Value::object(
-        scope.alloc(BoundFunction::new(this.into_any(), function)),
-      ));
-    }
-
-    Ok(value)
+    this
+      .named_field_opt(scope, name.clone())?
+      .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`").into())
   }
 
   fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
-    let value = this.fields.get(&name);
-
-    // bind functions
-    if let Some(value) = value.clone() {
-      if let Some(function) = value.to_object::<Function>() {
-        return Ok(Some(Value::object(
-          scope.alloc(BoundFunction::new(this.into_any(), function)),
-        )));
-      }
+    match this.fields.get(&name) {
+      Some(value) => Ok(Some(bind(scope, this, value))),
+      None => ClassInstance::native_field(scope, &this, name),
     }
-
-    Ok(value)
   }
 
-  fn set_named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>, value: Value) -> Result<()> {
-    if !this.fields.set(&name, value) {
-      fail!("`{this}` has no field `{name}`");
+  fn set_named_field(
+    scope: Scope<'_>,
+    this: Ptr<Self>,
+    name: Ptr<Str>,
+    value: Value,
+  ) -> Result<()> {
+    this.check_field(&name, &value)?;
+    if this.fields.set(&name, value.clone()) {
+      return Ok(());
+    }
+    let native = this.native.borrow().clone();
+    match native {
+      Some(native) if native.class.has_setter(&name) => native.set_named_field(scope, name, value),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
     }
-
-    Ok(())
   }
 }
 declare_object_type!(ClassInstance);
@@ -106,39 +251,27 @@ impl Object for ClassProxy {
     "Instance"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    /*
-    class Foo(Bar):
-      fn test(self):
-        super is Foo ?
-        super is Bar ?
-    */
-    todo!()
+  fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool> {
+    // `super` is still the same instance, so `super is Foo` is true
+    // inside of `Foo`'s methods
+    ClassInstance::instance_of(this.this.clone(), ty)
   }
 
   fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
-    let method = this
-      .class
-      .methods
-      .get(name.as_str())
-      .cloned()
-      .ok_or_else(|| error!("failed to get field `{name}`"))?;
-
-    Ok(Value::object(
-      scope.alloc(BoundFunction::new(this.into_any(), method)),
-    ))
+    this
+      .named_field_opt(scope, name.clone())?
+      .ok_or_else(|| error!("failed to get field `{name}`").into())
   }
 
   fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
-    let method = this
-      .class
-      .methods
-      .get(name.as_str())
-      .cloned()
-      .map(|method| scope.alloc(BoundFunction::new(this.into_any(), method)))
-      .map(Value::object);
-
-    Ok(method)
+    let method = this.class.methods.get(name.as_str()).cloned();
+    if let Some(method) = method {
+      return Ok(Some(Value::object(
+        scope.alloc(BoundFunction::new(this.into_any(), method)),
+      )));
+    }
+    // methods of a native ancestor are only found on the native part
+    ClassInstance::native_field(scope, &this.this, name)
   }
 
   fn call(scope: Scope<'_>, this: Ptr<Self>, return_addr: ReturnAddr) -> Result<CallResult> {
@@ -155,13 +288,71 @@ impl Object for ClassProxy {
 
 declare_object_type!(ClassProxy);
 
+/// `super` inside the methods of a class derived from a native class.
 #[derive(Debug)]
+pub struct NativeClassProxy {
+  pub this: Ptr<ClassInstance>,
+  pub class: Ptr<NativeClass>,
+}
+
+impl Display for NativeClassProxy {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<class `{}` instance>", self.this.name)
+  }
+}
+
+impl Object for NativeClassProxy {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Instance"
+  }
+
+  fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool> {
+    ClassInstance::instance_of(this.this.clone(), ty)
+  }
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    this.named_field_opt(scope, name.clone())?.ok_or_else(|| {
+      error!(Name: "native class `{}` has no field `{name}`", this.class.name).into()
+    })
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    if this.this.native.borrow().is_none() {
+      fail!(
+        "`{}` is not initialized, call `super` in `init` first",
+        this.class.name
+      );
+    }
+    ClassInstance::native_field(scope, &this.this, name)
+  }
+
+  fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    ClassInstance::init_native(scope, &this.this, &this.class)?;
+    Ok(CallResult::Return(Value::none()))
+  }
+}
+
+declare_object_type!(NativeClassProxy);
+
 pub struct ClassType {
   pub name: Ptr<Str>,
   pub init: Option<Ptr<Function>>,
   pub fields: Ptr<Table>,
   pub methods: IndexMap<Ptr<Str>, Ptr<Function>>,
   pub parent: Option<Ptr<ClassType>>,
+  /// Type hints of the fields of this class and its parents, by field name.
+  pub hints: Ptr<Table>,
+  /// Static fields and methods of this class and its parents.
+  pub statics: Ptr<Table>,
+  /// Classes whose members were merged into this one by `with`.
+  pub mixins: Box<[Ptr<ClassType>]>,
+  /// The native class this class is derived from, either directly or
+  /// through its parent.
+  pub native: Option<Ptr<NativeClass>>,
+  /// The shape of its instances, which is created along with the first one.
+  pub shape: OnceCell<Shape>,
+  /// The `##` doc comment above the class.
+  pub doc: Option<Ptr<Str>>,
 }
 
 impl ClassType {
@@ -171,6 +362,8 @@ impl ClassType {
     fields: Ptr<Table>,
     methods: IndexMap<Ptr<Str>, Ptr<Function>>,
     parent: Option<Ptr<ClassType>>,
+    hints: Ptr<Table>,
+    statics: Ptr<Table>,
   ) -> Self {
     Self {
       name,
@@ -178,8 +371,59 @@ impl ClassType {
       fields,
       methods,
       parent,
+      hints,
+      statics,
+      mixins: Box::new([]),
+      native: None,
+      shape: OnceCell::new(),
+      doc: None,
     }
   }
+
+  pub fn with_mixins(mut self, mixins: impl Into<Box<[Ptr<ClassType>]>>) -> Self {
+    self.mixins = mixins.into();
+    self
+  }
+
+  pub fn with_native(mut self, native: Option<Ptr<NativeClass>>) -> Self {
+    self.native = native;
+    self
+  }
+
+  pub fn with_doc(mut self, doc: Option<Ptr<Str>>) -> Self {
+    self.doc = doc;
+    self
+  }
+
+  /// Whether `self` is `other`, or is derived from it, either directly or
+  /// through a mixin.
+  pub fn is_subclass_of(&self, other: &Ptr<ClassType>) -> bool {
+    if std::ptr::eq(self, other.as_ref()) {
+      return true;
+    }
+    self
+      .parent
+      .iter()
+      .chain(self.mixins.iter())
+      .any(|class| class.is_subclass_of(other))
+  }
+}
+
+// The shape is left out, because its id differs between runs.
+impl Debug for ClassType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ClassType")
+      .field("name", &self.name)
+      .field("init", &self.init)
+      .field("fields", &self.fields)
+      .field("methods", &self.methods)
+      .field("parent", &self.parent)
+      .field("hints", &self.hints)
+      .field("statics", &self.statics)
+      .field("mixins", &self.mixins)
+      .field("native", &self.native)
+      .finish()
+  }
 }
 
 impl Display for ClassType {
@@ -193,39 +437,73 @@ impl Object for ClassType {
     "Class"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    // A is Type == true
-    // otherwise == false
-    todo!()
-  }
+  default_instance_of!();
 
-  fn named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
     let value = this
-      .methods
-      .get(&name)
-      .cloned()
-      .map(Value::object)
+      .named_field_opt(scope, name.clone())?
       .ok_or_else(|| error!("failed to get field `{name}`"))?;
     Ok(value)
   }
 
   fn named_field_opt(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    if let Some(value) = this.statics.get(&name) {
+      return Ok(Some(value));
+    }
     let value = this.methods.get(&name).cloned().map(Value::object);
     Ok(value)
   }
 
+  fn set_named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>, value: Value) -> Result<()> {
+    if !this.statics.set(&name, value) {
+      fail!(Name: "class `{}` has no static field `{name}`", this.name);
+    }
+    Ok(())
+  }
+
   fn call(scope: Scope<'_>, this: Ptr<Self>, return_addr: ReturnAddr) -> Result<CallResult> {
     let instance = scope.alloc(ClassInstance::new(
       scope.thread.global.clone(),
-      this.as_ref(),
+      this.clone(),
     ));
+    if this.methods.contains_key("__del__") {
+      scope
+        .thread
+        .global
+        .register_finalizer(instance.clone().into_any());
+    }
 
-    match this.init.as_ref() {
-      Some(init) => {
-        let init = scope.alloc(BoundFunction::new(instance.into_any(), init.clone()));
+    match (this.init.as_ref(), this.native.as_ref()) {
+      (Some(init), _) => {
+        // an inherited `init` is called through the class which declares it,
+        // so that `super` inside of it refers to that class' parent
+        let mut owner = this.clone();
+        while let Some(parent) = owner
+          .parent
+          .clone()
+          .filter(|parent| parent.init.as_ref().is_some_and(|i| i.ptr_eq(init)))
+        {
+          owner = parent;
+        }
+        let receiver = if owner.ptr_eq(&this) {
+          instance.into_any()
+        } else {
+          scope
+            .alloc(ClassProxy {
+              this: instance,
+              class: owner,
+            })
+            .into_any()
+        };
+        let init = scope.alloc(BoundFunction::new(receiver, init.clone()));
         <BoundFunction as Object>::call(scope, init, return_addr)
       }
-      None => Ok(CallResult::Return(Value::object(instance))),
+      // without an `init`, the arguments are passed to the native initializer
+      (None, Some(native)) => {
+        ClassInstance::init_native(scope, &instance, native)?;
+        Ok(CallResult::Return(Value::object(instance)))
+      }
+      (None, None) => Ok(CallResult::Return(Value::object(instance))),
     }
   }
 }
@@ -238,6 +516,17 @@ pub struct ClassDescriptor {
   pub init: Option<Ptr<FunctionDescriptor>>,
   pub methods: IndexMap<Ptr<Str>, Ptr<FunctionDescriptor>>,
   pub fields: Ptr<Table>,
+  /// Type hints of the fields which have one, by field name.
+  pub hints: Ptr<Table>,
+  /// Methods which are called on the class itself, without `self`.
+  pub static_methods: IndexMap<Ptr<Str>, Ptr<FunctionDescriptor>>,
+  /// Names of the static fields, which are initialized after the class is
+  /// created.
+  pub static_fields: Ptr<Table>,
+  /// The number of classes listed after `with`.
+  pub mixins: usize,
+  /// The `##` doc comment above the class.
+  pub doc: Option<Ptr<Str>>,
 }
 
 impl Display for ClassDescriptor {
@@ -255,3 +544,61 @@ impl Object for ClassDescriptor {
 }
 
 declare_object_type!(ClassDescriptor);
+
+/// The builtin `Error` class. Only instances of it or of classes derived from
+/// it may be raised.
+const ERROR_CLASS: &str = "
+## The base class of every raised value.
+class Error:
+  message = none
+  init(self, message = none):
+    self.message = message
+";
+
+pub fn register_error_class(global: &Global) {
+  let ast = syntax::parse(global.clone(), ERROR_CLASS).expect("failed to parse `Error`");
+  let module = codegen::emit(global.clone(), &ast, "__builtin__", false);
+  let desc = module
+    .root
+    .constants()
+    .iter()
+    .find_map(|constant| match constant {
+      Constant::Class(desc) => Some(desc.clone()),
+      _ => None,
+    })
+    .expect("failed to emit `Error`");
+
+  // the class is created here instead of by running the module, so that it
+  // exists before any script runs and doesn't show up in hooks or policies
+  let init = desc.init.clone().map(|init| {
+    global.alloc(Function::new(
+      init,
+      module.pool.clone(),
+      global.alloc(List::new()),
+      ModuleId::global(),
+    ))
+  });
+  let class = global.alloc(
+    ClassType::new(
+      desc.name.clone(),
+      init,
+      global.alloc(desc.fields.copy()),
+      IndexMap::new(),
+      None,
+      desc.hints.clone(),
+      global.alloc(Table::new()),
+    )
+    .with_doc(desc.doc.clone()),
+  );
+  global.set(desc.name.clone(), Value::object(class.clone()));
+  global.set_error_class(class);
+}
+
+/// Whether `instance` is an instance of the builtin `Error` class, or of a
+/// class derived from it.
+pub fn is_error(global: &Global, instance: &ClassInstance) -> bool {
+  let Some(error) = global.error_class() else {
+    return false;
+  };
+  instance.class.is_subclass_of(&error)
+}
@@ -0,0 +1,158 @@
+use std::cell::Cell;
+use std::fmt::{Debug, Display};
+use std::ptr::NonNull;
+
+use super::builtin::{BuiltinAsyncMethod, BuiltinMethod};
+use super::{Any, Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::thread::{Resumed, Stack, Thread};
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A function which runs on its own stack, and may be suspended with `yield`
+/// and later continued with `resume`.
+///
+/// Each `resume` runs the coroutine on a new `Thread` until it yields or
+/// returns. The value it yields or returns is the result of `resume`.
+pub struct Coroutine {
+  function: Ptr<Any>,
+  stack: NonNull<Stack>,
+  state: Cell<State>,
+  /// Where to continue from after a `yield`.
+  pc: Cell<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+  /// Created, but not resumed yet.
+  Initial,
+  Suspended,
+  Running,
+  Dead,
+}
+
+impl Coroutine {
+  pub fn new(function: Ptr<Any>) -> Self {
+    Self {
+      function,
+      stack: unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(Stack::new()))) },
+      state: Cell::new(State::Initial),
+      pc: Cell::new(0),
+    }
+  }
+
+  pub fn status(&self) -> &'static str {
+    match self.state.get() {
+      State::Initial | State::Suspended => "suspended",
+      State::Running => "running",
+      State::Dead => "dead",
+    }
+  }
+}
+
+impl Drop for Coroutine {
+  fn drop(&mut self) {
+    let _ = unsafe { Box::from_raw(self.stack.as_ptr()) };
+  }
+}
+
+pub fn coroutine(scope: Scope<'_>) -> Result<Value> {
+  let function = scope.param::<public::Value>(0)?.unbind();
+  let Some(function) = function.clone().to_any() else {
+    fail!(Type: "`{function}` is not callable");
+  };
+  Ok(Value::object(scope.alloc(Coroutine::new(function))))
+}
+
+async fn coroutine_resume(this: Ptr<Coroutine>, scope: Scope<'_>) -> Result<Value> {
+  let state = this.state.get();
+  match state {
+    State::Running => fail!("cannot resume a running coroutine"),
+    State::Dead => fail!("cannot resume a dead coroutine"),
+    State::Initial | State::Suspended => {}
+  }
+
+  // arguments are only passed to the function on the first `resume`,
+  // because `yield` is a statement and has no value to receive them
+  let regs = unsafe { &scope.thread.stack.as_ref().regs };
+  let args = regs[scope.args.start..scope.args.start + scope.args.count].to_vec();
+
+  this.state.set(State::Running);
+  let mut thread = Thread::coroutine(scope.thread.global.clone(), this.stack, this.pc.get());
+  let result = match state {
+    State::Initial => thread.start_coroutine(this.function.clone(), &args).await,
+    _ => thread.resume_coroutine().await,
+  };
+
+  match result {
+    Ok(Resumed::Yield(value)) => {
+      this.pc.set(thread.pc);
+      this.state.set(State::Suspended);
+      Ok(value)
+    }
+    Ok(Resumed::Return(value)) => {
+      this.state.set(State::Dead);
+      Ok(value)
+    }
+    Err(e) => {
+      this.state.set(State::Dead);
+      Err(e)
+    }
+  }
+}
+
+fn coroutine_status(this: Ptr<Coroutine>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.intern(this.status())))
+}
+
+impl Object for Coroutine {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Coroutine"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "resume" => {
+        let method = builtin_async_method!(coroutine_resume);
+        return Ok(Some(Value::object(unsafe {
+          scope.alloc(BuiltinAsyncMethod::new(Value::object(this), name, method))
+        })));
+      }
+      "status" => builtin_method!(coroutine_status),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(Coroutine);
+
+impl Display for Coroutine {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<coroutine>")
+  }
+}
+
+impl Debug for Coroutine {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Coroutine")
+      .field("function", &self.function)
+      .field("state", &self.state.get())
+      .field("pc", &self.pc.get())
+      .finish()
+  }
+}
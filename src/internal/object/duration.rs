@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A span of time, such as the time between two calls to `time.now()`.
+///
+/// Durations can be added to and subtracted from each other, multiplied and
+/// divided by numbers, and compared, but they can never be negative.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration(pub std::time::Duration);
+
+impl Duration {
+  pub fn from_secs(secs: f64) -> Result<Self> {
+    match std::time::Duration::try_from_secs_f64(secs) {
+      Ok(v) => Ok(Self(v)),
+      Err(_) => fail!("`{secs}` is not a valid duration"),
+    }
+  }
+
+  pub fn mul_f64(self, factor: f64) -> Result<Self> {
+    match std::time::Duration::try_from_secs_f64(self.0.as_secs_f64() * factor) {
+      Ok(v) => Ok(Self(v)),
+      Err(_) => fail!("`{self} * {factor}` is not a valid duration"),
+    }
+  }
+
+  pub fn div_f64(self, divisor: f64) -> Result<Self> {
+    if divisor == 0.0 {
+      fail!("cannot divide duration by zero");
+    }
+    match std::time::Duration::try_from_secs_f64(self.0.as_secs_f64() / divisor) {
+      Ok(v) => Ok(Self(v)),
+      Err(_) => fail!("`{self} / {divisor}` is not a valid duration"),
+    }
+  }
+}
+
+impl Display for Duration {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?}", self.0)
+  }
+}
+
+impl Debug for Duration {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(self, f)
+  }
+}
+
+fn duration_secs(this: Ptr<Duration>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::float(this.0.as_secs_f64()))
+}
+
+fn duration_millis(this: Ptr<Duration>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::float(this.0.as_secs_f64() * 1000.0))
+}
+
+fn duration_mul(this: Ptr<Duration>, scope: Scope<'_>) -> Result<Value> {
+  let factor = scope.param::<public::Value>(0)?.unbind();
+  let Some(factor) = factor.to_number() else {
+    fail!("`{factor}` is not a number");
+  };
+  let result = this.mul_f64(factor)?;
+  Ok(Value::object(scope.alloc(result)))
+}
+
+impl Object for Duration {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Duration"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "secs" => builtin_method!(duration_secs),
+      "millis" => builtin_method!(duration_millis),
+      "mul" => builtin_method!(duration_mul),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+
+  fn add(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let Some(result) = this.0.checked_add(other.0) else {
+      fail!("`{this} + {other}` overflowed");
+    };
+    Ok(Value::object(scope.alloc(Duration(result))))
+  }
+
+  fn subtract(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let Some(result) = this.0.checked_sub(other.0) else {
+      fail!("`{this} - {other}` would be a negative duration");
+    };
+    Ok(Value::object(scope.alloc(Duration(result))))
+  }
+
+  fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    Ok(this.0.cmp(&other.0))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.0 == other.0)
+  }
+}
+
+declare_object_type!(Duration);
@@ -1,7 +1,8 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt::{Debug, Display};
-use std::ptr::NonNull;
+use std::ptr::{self, NonNull};
 
+use super::class::{ClassInstance, ClassProxy, ClassType};
 use super::module::ModuleId;
 use super::ptr::Ptr;
 use super::{Any, List, Object, ReturnAddr, Str};
@@ -11,25 +12,41 @@ use crate::internal::object;
 use crate::internal::value::constant::Constant;
 use crate::internal::value::Value;
 use crate::internal::vm::dispatch::LoadFrame;
+use crate::internal::vm::global::Global;
+use crate::internal::vm::thread::cache::FieldCaches;
 use crate::internal::vm::thread::util::check_args;
-use crate::internal::vm::thread::{Args, CallResult, Frame, Slot0, Thread};
+use crate::internal::vm::thread::{Args, CallResult, Slot0, Thread};
 use crate::public::Scope;
+use crate::span::Span;
 
-#[derive(Debug)]
 pub struct Function {
   pub descriptor: Ptr<FunctionDescriptor>,
+  /// Holds the constants of the descriptor.
+  pub pool: Ptr<ConstantPool>,
   pub upvalues: Ptr<List>,
   pub module_id: ModuleId,
 }
 
+impl Debug for Function {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Function")
+      .field("descriptor", &self.descriptor)
+      .field("upvalues", &self.upvalues)
+      .field("module_id", &self.module_id)
+      .finish()
+  }
+}
+
 impl Function {
   pub fn new(
     descriptor: Ptr<FunctionDescriptor>,
+    pool: Ptr<ConstantPool>,
     upvalues: Ptr<List>,
     module_id: ModuleId,
   ) -> Self {
     Self {
       descriptor,
+      pool,
       upvalues,
       module_id,
     }
@@ -48,9 +65,7 @@ impl Function {
     let stack = unsafe { thread.stack.as_mut() };
 
     thread.pc = 0;
-    stack
-      .frames
-      .push(Frame::new(function, stack.regs.len(), return_addr));
+    stack.push_frame(function, return_addr);
 
     stack.regs.reserve(frame_size);
     if !descriptor.params.has_self {
@@ -72,15 +87,16 @@ impl Function {
     let function = this.as_ref();
     let descriptor = function.descriptor.as_ref();
     let bytecode = descriptor.instructions;
-    check_args(&descriptor.params, false, args.count)?;
+    // args passed by name were already checked by `call_kw`
+    if !args.kw {
+      check_args(&descriptor.params, false, args.count)?;
+    }
 
     let frame_size = descriptor.frame_size;
     let stack = unsafe { thread.stack.as_mut() };
 
     thread.pc = 0;
-    stack
-      .frames
-      .push(Frame::new(function, stack.regs.len(), return_addr));
+    stack.push_frame(function, return_addr);
 
     stack.regs.reserve(frame_size);
 
@@ -105,6 +121,46 @@ impl Function {
   }
 }
 
+/// The descriptor of the function which runs when `callee` is called, and
+/// whether that function receives `self` implicitly.
+///
+/// This is `None` for callables which are not implemented in bytecode.
+pub fn descriptor_of(callee: &Ptr<Any>) -> Option<(Ptr<FunctionDescriptor>, bool)> {
+  if let Some(function) = callee.clone_cast::<Function>() {
+    return Some((function.descriptor.clone(), false));
+  }
+  if let Some(bound) = callee.clone_cast::<BoundFunction>() {
+    return Some((bound.function.descriptor.clone(), true));
+  }
+  let init = if let Some(class) = callee.clone_cast::<ClassType>() {
+    class.init.clone()
+  } else if let Some(proxy) = callee.clone_cast::<ClassProxy>() {
+    proxy.class.init.clone()
+  } else {
+    None
+  };
+  init.map(|init| (init.descriptor.clone(), true))
+}
+
+/// The `##` doc comment of a function, method or class. Instances have the
+/// doc comment of their class.
+pub fn doc_of(value: &Value) -> Option<Ptr<Str>> {
+  let object = value.clone().to_any()?;
+  if let Some(function) = object.clone_cast::<Function>() {
+    function.descriptor.doc.clone()
+  } else if let Some(bound) = object.clone_cast::<BoundFunction>() {
+    bound.function.descriptor.doc.clone()
+  } else if let Some(class) = object.clone_cast::<ClassType>() {
+    class.doc.clone()
+  } else if let Some(instance) = object.clone_cast::<ClassInstance>() {
+    instance.class.doc.clone()
+  } else if let Some(proxy) = object.clone_cast::<ClassProxy>() {
+    proxy.class.doc.clone()
+  } else {
+    None
+  }
+}
+
 impl Object for Function {
   fn type_name(_: Ptr<Self>) -> &'static str {
     "Function"
@@ -160,11 +216,19 @@ pub struct FunctionDescriptor {
   pub upvalues: RefCell<Vec<Upvalue>>,
   pub frame_size: usize,
   pub instructions: NonNull<[u8]>,
-  pub constants: NonNull<[Constant]>,
-  // TODO: spans
+  /// Points into the `ConstantPool` of the module once it has been emitted.
+  ///
+  /// Until then, the constants are owned by the descriptor.
+  pub constants: Cell<NonNull<[Constant]>>,
+  owns_constants: Cell<bool>,
+  /// Source span of each instruction, in bytecode order.
+  pub spans: NonNull<[Span]>,
+  pub field_caches: FieldCaches,
+  /// The `##` doc comment above the function.
+  pub doc: Option<Ptr<Str>>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum Upvalue {
   Register(op::Register),
   Upvalue(op::Upvalue),
@@ -184,8 +248,10 @@ impl FunctionDescriptor {
     instructions: Vec<u8>,
     constants: Vec<Constant>,
   ) -> Self {
+    let field_caches = FieldCaches::new(&instructions);
     let instructions = vec_to_nonnull_ptr(instructions);
     let constants = vec_to_nonnull_ptr(constants);
+    let spans = vec_to_nonnull_ptr(Vec::new());
     Self {
       name,
       is_generator,
@@ -193,24 +259,160 @@ impl FunctionDescriptor {
       upvalues: RefCell::new(upvalues),
       frame_size,
       instructions,
-      constants,
+      constants: Cell::new(constants),
+      owns_constants: Cell::new(true),
+      spans,
+      field_caches,
+      doc: None,
+    }
+  }
+
+  /// Set the source span of each instruction, in bytecode order. Errors
+  /// raised by a function without them have no location.
+  pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+    let _ = unsafe { Box::from_raw(self.spans.as_ptr()) };
+    self.spans = vec_to_nonnull_ptr(spans);
+    self
+  }
+
+  pub fn with_doc(mut self, doc: Option<Ptr<Str>>) -> Self {
+    self.doc = doc;
+    self
+  }
+
+  pub fn constants(&self) -> &[Constant] {
+    unsafe { self.constants.get().as_ref() }
+  }
+
+  fn take_constants(&self) -> Box<[Constant]> {
+    assert!(
+      self.owns_constants.replace(false),
+      "constants of `{}` are already in a pool",
+      self.name
+    );
+    let empty = ptr::slice_from_raw_parts_mut(NonNull::dangling().as_ptr(), 0);
+    let constants = self
+      .constants
+      .replace(unsafe { NonNull::new_unchecked(empty) });
+    unsafe { Box::from_raw(constants.as_ptr()) }
+  }
+}
+
+/// The constants of every function in a module, in a single allocation.
+///
+/// Each function's constants occupy a contiguous window of the pool, so the
+/// constant operands in its bytecode stay the same.
+///
+/// The pool owns the descriptors of nested functions and classes, which means
+/// descriptors can't own the pool without creating a cycle. Instead, each
+/// `Function` holds on to the pool of the module it was defined in.
+pub struct ConstantPool {
+  constants: Box<[Constant]>,
+}
+
+impl ConstantPool {
+  /// Move the constants of `root` and all of the functions nested in it into
+  /// a new pool.
+  pub fn share(global: &Global, root: &Ptr<FunctionDescriptor>) -> Ptr<ConstantPool> {
+    let mut functions = Vec::new();
+    collect_functions(root, &mut functions);
+
+    let mut constants = Vec::new();
+    let mut windows = Vec::with_capacity(functions.len());
+    for function in functions.iter() {
+      let own = function.take_constants();
+      windows.push((constants.len(), own.len()));
+      constants.extend(own.into_vec());
+    }
+
+    let pool = global.alloc(ConstantPool {
+      constants: constants.into_boxed_slice(),
+    });
+    let base = pool.constants.as_ptr() as *mut Constant;
+    for (function, (start, len)) in functions.iter().zip(windows) {
+      let window = ptr::slice_from_raw_parts_mut(unsafe { base.add(start) }, len);
+      function
+        .constants
+        .set(unsafe { NonNull::new_unchecked(window) });
+    }
+    pool
+  }
+
+  pub fn len(&self) -> usize {
+    self.constants.len()
+  }
+}
+
+/// Collect `function` and every function nested in it, in pre-order.
+pub fn collect_functions(
+  function: &Ptr<FunctionDescriptor>,
+  out: &mut Vec<Ptr<FunctionDescriptor>>,
+) {
+  if out.iter().any(|other| other.ptr_eq(function)) {
+    return;
+  }
+  out.push(function.clone());
+  for constant in function.constants() {
+    match constant {
+      Constant::Function(inner) => collect_functions(inner, out),
+      Constant::Class(class) => {
+        for method in class.init.iter().chain(class.methods.values()) {
+          collect_functions(method, out);
+        }
+      }
+      _ => {}
     }
   }
 }
 
+impl Object for ConstantPool {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "ConstantPool"
+  }
+
+  default_instance_of!();
+}
+
+declare_object_type!(ConstantPool);
+
+impl Display for ConstantPool {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<constant pool>")
+  }
+}
+
+impl Debug for ConstantPool {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ConstantPool")
+      .field("constants", &self.constants.len())
+      .finish()
+  }
+}
+
 impl FunctionDescriptor {
   pub fn disassemble(&self) -> Disassembly {
-    self.disassemble_inner(None)
+    self.disassemble_inner(None, None)
+  }
+
+  /// Disassemble the function, interleaving the bytecode with the lines of
+  /// `source` it was emitted from.
+  pub fn disassemble_with_source<'a>(&'a self, source: &'a str) -> Disassembly<'a> {
+    self.disassemble_inner(None, Some(source))
   }
 
   pub fn disassemble_as_method(&self, class_name: Ptr<Str>) -> Disassembly {
-    self.disassemble_inner(Some(class_name))
+    self.disassemble_inner(Some(class_name), None)
   }
 
-  fn disassemble_inner(&self, class_name: Option<Ptr<Str>>) -> Disassembly {
+  fn disassemble_inner<'a>(
+    &'a self,
+    class_name: Option<Ptr<Str>>,
+    source: Option<&'a str>,
+  ) -> Disassembly<'a> {
     Disassembly {
       function: self,
       class_name,
+      source,
     }
   }
 }
@@ -218,23 +420,33 @@ impl FunctionDescriptor {
 pub struct Disassembly<'a> {
   function: &'a FunctionDescriptor,
   class_name: Option<Ptr<Str>>,
+  source: Option<&'a str>,
 }
 
 impl<'a> Display for Disassembly<'a> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let function = self.function;
 
-    let (bytecode, constants) =
-      unsafe { (function.instructions.as_ref(), function.constants.as_ref()) };
+    let (bytecode, constants, spans) = unsafe {
+      (
+        function.instructions.as_ref(),
+        function.constants.get().as_ref(),
+        function.spans.as_ref(),
+      )
+    };
 
     for constant in constants {
       match constant {
         Constant::Function(function) => {
-          writeln!(f, "{}\n", function.disassemble())?;
+          writeln!(f, "{}\n", function.disassemble_inner(None, self.source))?;
         }
         Constant::Class(class) => {
           for method in class.methods.values() {
-            writeln!(f, "{}\n", method.disassemble_as_method(class.name.clone()))?;
+            writeln!(
+              f,
+              "{}\n",
+              method.disassemble_inner(Some(class.name.clone()), self.source)
+            )?;
           }
         }
         _ => {}
@@ -263,18 +475,21 @@ impl<'a> Display for Disassembly<'a> {
       }
     }
     writeln!(f, ".code")?;
-    writeln!(
-      f,
-      "{}",
-      disasm::Disassembly::new(bytecode, constants, 2, true)
-    )
+    let disassembly = disasm::Disassembly::new(bytecode, constants, 2, true);
+    match self.source {
+      Some(source) => writeln!(f, "{}", disassembly.with_source(spans, source)),
+      None => writeln!(f, "{disassembly}"),
+    }
   }
 }
 
 impl Drop for FunctionDescriptor {
   fn drop(&mut self) {
     let _ = unsafe { Box::from_raw(self.instructions.as_ptr()) };
-    let _ = unsafe { Box::from_raw(self.constants.as_ptr()) };
+    if self.owns_constants.get() {
+      let _ = unsafe { Box::from_raw(self.constants.get().as_ptr()) };
+    }
+    let _ = unsafe { Box::from_raw(self.spans.as_ptr()) };
   }
 }
 
@@ -302,16 +517,36 @@ impl Debug for FunctionDescriptor {
       .field("upvalues", &self.upvalues)
       .field("frame_size", &self.frame_size)
       .field("instructions", &unsafe { self.instructions.as_ref() }.len())
-      .field("constants", &unsafe { self.constants.as_ref() }.len())
+      .field("constants", &self.constants().len())
       .finish()
   }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone)]
 pub struct Params {
   pub has_self: bool,
   pub min: u16,
   pub max: u16,
+  /// Type hints of the params which have one.
+  pub hints: Box<[ParamHint]>,
+  /// Params which may only be passed by name, their registers follow the
+  /// positional params.
+  pub kw: Box<[KwParam]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct KwParam {
+  pub name: Ptr<Str>,
+  /// Whether the param has no default value.
+  pub required: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParamHint {
+  /// Index of the param, not counting `self`.
+  pub index: u16,
+  pub name: Ptr<Str>,
+  pub hint: Ptr<Str>,
 }
 
 impl Params {
@@ -320,11 +555,13 @@ impl Params {
       has_self: false,
       min: 0,
       max: 0,
+      hints: Box::new([]),
+      kw: Box::new([]),
     }
   }
 
   pub fn is_empty(&self) -> bool {
-    self.min == 0 && self.max == 0
+    self.min == 0 && self.max == 0 && self.kw.is_empty()
   }
 }
 
@@ -334,8 +571,29 @@ impl Default for Params {
   }
 }
 
+impl Debug for Params {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut s = f.debug_struct("Params");
+    s.field("has_self", &self.has_self)
+      .field("min", &self.min)
+      .field("max", &self.max);
+    if !self.hints.is_empty() {
+      s.field("hints", &self.hints);
+    }
+    if !self.kw.is_empty() {
+      s.field("kw", &self.kw);
+    }
+    s.finish()
+  }
+}
+
 // TODO: store name and type_name
 #[derive(Debug)]
+/// A method bound to its receiver.
+///
+/// A new one is created each time a method is accessed, so bound methods are
+/// compared by their receiver and function instead of by identity. This means
+/// that `obj.method == obj.method` holds.
 pub struct BoundFunction {
   this: Ptr<Any>, // ClassInstance or ClassProxy
   function: Ptr<Function>,
@@ -347,6 +605,20 @@ impl BoundFunction {
 
     Self { this, function }
   }
+
+  fn same_receiver(&self, other: &Self) -> bool {
+    if self.this.ptr_eq(&other.this) {
+      return true;
+    }
+    // `super` creates a new proxy each time it is used
+    match (
+      self.this.clone_cast::<ClassProxy>(),
+      other.this.clone_cast::<ClassProxy>(),
+    ) {
+      (Some(a), Some(b)) => a.this.ptr_eq(&b.this) && a.class.ptr_eq(&b.class),
+      _ => false,
+    }
+  }
 }
 
 impl Display for BoundFunction {
@@ -368,13 +640,13 @@ impl Object for BoundFunction {
     let bound_function = this.as_ref();
     let function = bound_function.function.as_ref();
     let descriptor = function.descriptor.as_ref();
-    check_args(&descriptor.params, true, scope.num_args())?;
+    if !scope.args.kw {
+      check_args(&descriptor.params, true, scope.num_args())?;
+    }
 
     scope.thread.pc = 0;
     let stack = unsafe { scope.thread.stack.as_mut() };
-    stack
-      .frames
-      .push(Frame::new(function, stack.regs.len(), return_addr));
+    stack.push_frame(function, return_addr);
 
     let _ = scope.enter_nested(
       Slot0::Receiver(Value::object(this.this.clone())),
@@ -384,6 +656,10 @@ impl Object for BoundFunction {
 
     Ok(CallResult::Dispatch)
   }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.same_receiver(&other) && this.function.ptr_eq(&other.function))
+  }
 }
 
 declare_object_type!(BoundFunction);
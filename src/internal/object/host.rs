@@ -0,0 +1,77 @@
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// The `host` builtin, through which scripts call the commands of the host
+/// application by name, see [`HostDispatcher`].
+///
+/// [`HostDispatcher`]: crate::public::HostDispatcher
+pub struct Host;
+
+/// `host.call(name, ...args)`
+fn host_call(_: Ptr<Host>, mut scope: Scope<'_>) -> Result<Value> {
+  let name = scope.param::<public::Value>(0)?.unbind();
+  let Some(name) = name.clone().to_object::<Str>() else {
+    fail!(Type: "command name must be a string, got `{name}`");
+  };
+  let global = scope.thread.global.clone();
+  let Some(dispatcher) = global.host_dispatcher() else {
+    fail!("cannot call `{name}`, because the host has no dispatcher");
+  };
+  if let Some(policy) = global.security_policy() {
+    policy.check_native_call(name.as_str())?;
+  }
+  scope.consume_args(1);
+  Ok(dispatcher.call(name.as_str(), scope)?.unbind())
+}
+
+impl Object for Host {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Host"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "call" => builtin_method!(host_call),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(Host);
+
+impl Display for Host {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<host>")
+  }
+}
+
+impl Debug for Host {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Host").finish()
+  }
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  global.set(global.intern("host"), Value::object(global.alloc(Host)));
+}
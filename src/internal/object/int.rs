@@ -0,0 +1,58 @@
+use std::fmt::{Debug, Display};
+
+use super::{Object, Ptr};
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+
+/// An integer which does not fit in the 32 bits available in a [`Value`].
+///
+/// Scripts never observe this type directly: every operation on ints accepts
+/// both representations, and results are stored inline whenever they fit.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BoxedInt {
+  value: i64,
+}
+
+impl BoxedInt {
+  pub fn new(value: i64) -> Self {
+    debug_assert!(
+      i32::try_from(value).is_err(),
+      "{value} should be stored inline"
+    );
+    Self { value }
+  }
+
+  pub fn value(&self) -> i64 {
+    self.value
+  }
+}
+
+/// Create an int value, boxing it only if it does not fit in an `i32`.
+pub fn int(global: &Global, value: i64) -> Value {
+  match i32::try_from(value) {
+    Ok(value) => Value::int(value),
+    Err(_) => Value::object(global.alloc(BoxedInt::new(value))),
+  }
+}
+
+impl Object for BoxedInt {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "int"
+  }
+
+  default_instance_of!();
+}
+
+declare_object_type!(BoxedInt);
+
+impl Display for BoxedInt {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(&self.value, f)
+  }
+}
+
+impl Debug for BoxedInt {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Debug::fmt(&self.value, f)
+  }
+}
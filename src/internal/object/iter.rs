@@ -0,0 +1,538 @@
+//! Lazy iterator adapters.
+//!
+//! The builtins in this module work with anything which implements the
+//! iterator protocol: an `iter` method which returns an iterator, which has
+//! a `done` method and a `next` method. The adapters implement the same
+//! protocol, so they can be passed to each other, to `collect`, or used in a
+//! `for` loop.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{Debug, Display};
+use std::iter::Peekable;
+
+use super::builtin::{BuiltinAsyncMethod, BuiltinMethod};
+use super::{Any, List, Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::internal::vm::thread::util::{is_truthy, truthiness};
+use crate::public;
+use crate::public::{Bind, IntoValue, Scope, Unbind};
+
+/// The `next` and `done` methods of an iterator.
+pub struct Source {
+  next: Ptr<Any>,
+  done: Ptr<Any>,
+}
+
+impl Source {
+  /// Call `iterable.iter()`, and get the methods of the iterator it returns.
+  pub async fn new(scope: &mut Scope<'_>, iterable: Value) -> Result<Self> {
+    let Some(iterable) = iterable.clone().to_any() else {
+      fail!(Type: "`{iterable}` is not iterable");
+    };
+
+    let iter = method(scope, &iterable, "iter")?;
+    let iterator = scope.call(iter, &[]).await?.unbind();
+    let Some(iterator) = iterator.clone().to_any() else {
+      fail!(Type: "`{iterable}` is not an iterator");
+    };
+
+    Ok(Self {
+      next: method(scope, &iterator, "next")?.unbind(),
+      done: method(scope, &iterator, "done")?.unbind(),
+    })
+  }
+
+  pub async fn done(&self, scope: &mut Scope<'_>) -> Result<bool> {
+    let done = self.done.clone().bind(scope.global());
+    Ok(is_truthy(scope.call(done, &[]).await?.unbind()))
+  }
+
+  pub async fn next(&self, scope: &mut Scope<'_>) -> Result<Value> {
+    let next = self.next.clone().bind(scope.global());
+    Ok(scope.call(next, &[]).await?.unbind())
+  }
+}
+
+fn method<'cx>(
+  scope: &Scope<'cx>,
+  object: &Ptr<Any>,
+  name: &'static str,
+) -> Result<public::Any<'cx>> {
+  Ok(
+    object
+      .named_field(scope.clone(), scope.intern(name))?
+      .to_any()
+      .ok_or_else(|| error!(Type: "`{name}` is not callable"))?
+      .bind(scope.global()),
+  )
+}
+
+async fn call(scope: &mut Scope<'_>, function: &Ptr<Any>, args: &[Value]) -> Result<Value> {
+  let function = function.clone().bind(scope.global());
+  let args = args
+    .iter()
+    .map(|arg| arg.clone().bind(scope.global()))
+    .collect::<Vec<_>>();
+  Ok(scope.call(function, &args).await?.unbind())
+}
+
+fn callable(scope: &Scope<'_>, n: usize) -> Result<Ptr<Any>> {
+  let function = scope.param::<public::Value>(n)?.unbind();
+  let Some(function) = function.clone().to_any() else {
+    fail!(Type: "`{function}` is not callable");
+  };
+  Ok(function)
+}
+
+/// An iterator returned by `map`, `filter`, `zip`, or `enumerate`.
+pub struct Iter {
+  kind: Kind,
+}
+
+enum Kind {
+  Map {
+    source: Source,
+    function: Ptr<Any>,
+  },
+  Filter {
+    source: Source,
+    function: Ptr<Any>,
+    /// The next item which passed the filter, found by `done`.
+    peeked: RefCell<Option<Value>>,
+  },
+  Zip {
+    left: Source,
+    right: Source,
+  },
+  Enumerate {
+    source: Source,
+    index: Cell<i32>,
+  },
+}
+
+impl Iter {
+  fn name(&self) -> &'static str {
+    match &self.kind {
+      Kind::Map { .. } => "map",
+      Kind::Filter { .. } => "filter",
+      Kind::Zip { .. } => "zip",
+      Kind::Enumerate { .. } => "enumerate",
+    }
+  }
+}
+
+async fn iter_iter(this: Ptr<Iter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+async fn iter_done(this: Ptr<Iter>, mut scope: Scope<'_>) -> Result<Value> {
+  let done = match &this.kind {
+    Kind::Map { source, .. } | Kind::Enumerate { source, .. } => source.done(&mut scope).await?,
+    Kind::Filter {
+      source,
+      function,
+      peeked,
+    } => {
+      // `done` can only be answered by finding the next item which passes
+      // the filter, so it is kept around for `next`
+      loop {
+        if peeked.borrow().is_some() {
+          break false;
+        }
+        if source.done(&mut scope).await? {
+          break true;
+        }
+        let item = source.next(&mut scope).await?;
+        let keep = call(&mut scope, function, std::slice::from_ref(&item)).await?;
+        if truthiness(&mut scope, keep).await? {
+          *peeked.borrow_mut() = Some(item);
+        }
+      }
+    }
+    Kind::Zip { left, right } => left.done(&mut scope).await? || right.done(&mut scope).await?,
+  };
+  Ok(Value::bool(done))
+}
+
+async fn iter_next(this: Ptr<Iter>, mut scope: Scope<'_>) -> Result<Value> {
+  match &this.kind {
+    Kind::Map { source, function } => {
+      let item = source.next(&mut scope).await?;
+      call(&mut scope, function, &[item]).await
+    }
+    Kind::Filter { peeked, .. } => {
+      if peeked.borrow().is_none() {
+        iter_done(this.clone(), scope).await?;
+      }
+      Ok(peeked.borrow_mut().take().unwrap_or_else(Value::none))
+    }
+    Kind::Zip { left, right } => {
+      let left = left.next(&mut scope).await?;
+      let right = right.next(&mut scope).await?;
+      Ok(Value::object(scope.alloc(List::from(vec![left, right]))))
+    }
+    Kind::Enumerate { source, index } => {
+      let item = source.next(&mut scope).await?;
+      let i = index.get();
+      index.set(i + 1);
+      Ok(Value::object(
+        scope.alloc(List::from(vec![Value::int(i), item])),
+      ))
+    }
+  }
+}
+
+impl Object for Iter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Iter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_async_method!(iter_iter),
+      "next" => builtin_async_method!(iter_next),
+      "done" => builtin_async_method!(iter_done),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinAsyncMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(Iter);
+
+impl Display for Iter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<{} iter>", self.name())
+  }
+}
+
+impl Debug for Iter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Iter").field("kind", &self.name()).finish()
+  }
+}
+
+/// The iterator protocol, implemented in Rust.
+///
+/// Implementing it for the type of a native class, and calling
+/// [`NativeClassBuilder::iterator`] when registering the class, gives the
+/// class the `iter`, `next` and `done` methods, so its instances may be used
+/// in a `for` loop, or passed to builtins such as `map`. A value which
+/// implements this trait may also be turned into an iterator object directly
+/// with [`Global::new_iterator`].
+///
+/// Like any other native class, the iterator is only given a shared
+/// reference to itself, so it has to keep its state in a [`Cell`] or
+/// [`RefCell`]. To turn a Rust [`Iterator`] into a script iterator, use
+/// [`FromIter`] instead.
+///
+/// ```rust
+/// use std::cell::Cell;
+///
+/// use hebi::prelude::*;
+/// use hebi::NativeIterator;
+///
+/// struct Countdown(Cell<i32>);
+///
+/// impl NativeIterator for Countdown {
+///   type Item = i32;
+///
+///   fn next(&self) -> Option<i32> {
+///     let n = self.0.get();
+///     self.0.set(n - 1);
+///     (n > 0).then_some(n)
+///   }
+///
+///   fn done(&self) -> bool {
+///     self.0.get() <= 0
+///   }
+/// }
+///
+/// let module = NativeModule::builder("countdown")
+///   .class::<Countdown>("Countdown", |class| {
+///     class
+///       .init(|scope| Ok(Countdown(Cell::new(scope.param(0)?))))
+///       .iterator()
+///       .finish()
+///   })
+///   .finish();
+///
+/// let mut hebi = Hebi::new();
+/// hebi.register(&module);
+/// let value = hebi
+///   .eval(
+///     r#"
+/// import countdown
+/// total := 0
+/// for n in countdown.Countdown(3):
+///   total = total * 10 + n
+/// total
+/// "#,
+///   )
+///   .unwrap();
+/// assert_eq!(value.as_int(), Some(321));
+/// ```
+///
+/// [`NativeClassBuilder::iterator`]: crate::public::module::NativeClassBuilder::iterator
+/// [`Global::new_iterator`]: crate::public::Global::new_iterator
+pub trait NativeIterator: Send + 'static {
+  type Item: for<'cx> IntoValue<'cx>;
+
+  /// The next item, or `None` if there are no more items.
+  fn next(&self) -> Option<Self::Item>;
+
+  /// Whether there are no more items. Scripts call this before each call to
+  /// `next`, so it must not skip an item.
+  fn done(&self) -> bool;
+}
+
+/// An adapter which implements [`NativeIterator`] for any Rust
+/// [`Iterator`].
+///
+/// It is also converted into a script iterator when returned from a native
+/// function:
+///
+/// ```rust
+/// use hebi::prelude::*;
+/// use hebi::FromIter;
+///
+/// let module = NativeModule::builder("text")
+///   .function("words", |scope| {
+///     let text = scope.param::<String>(0)?;
+///     let words = text
+///       .split_whitespace()
+///       .map(str::to_string)
+///       .collect::<Vec<_>>();
+///     hebi::Result::Ok(FromIter::new(words))
+///   })
+///   .finish();
+///
+/// let mut hebi = Hebi::new();
+/// hebi.register(&module);
+/// let value = hebi
+///   .eval(
+///     r#"
+/// import text
+/// count := 0
+/// for word in text.words("a quick  brown fox"):
+///   count += 1
+/// count
+/// "#,
+///   )
+///   .unwrap();
+/// assert_eq!(value.as_int(), Some(4));
+/// ```
+pub struct FromIter<I: Iterator> {
+  iter: RefCell<Peekable<I>>,
+}
+
+impl<I: Iterator> FromIter<I> {
+  pub fn new(iter: impl IntoIterator<IntoIter = I>) -> Self {
+    Self {
+      iter: RefCell::new(iter.into_iter().peekable()),
+    }
+  }
+}
+
+impl<I> NativeIterator for FromIter<I>
+where
+  I: Iterator + Send + 'static,
+  I::Item: for<'cx> IntoValue<'cx> + Send,
+{
+  type Item = I::Item;
+
+  fn next(&self) -> Option<I::Item> {
+    self.iter.borrow_mut().next()
+  }
+
+  fn done(&self) -> bool {
+    // `done` can only be answered by getting the next item, which `Peekable`
+    // keeps around for `next`
+    self.iter.borrow_mut().peek().is_none()
+  }
+}
+
+impl<'cx, I> IntoValue<'cx> for FromIter<I>
+where
+  I: Iterator + Send + 'static,
+  I::Item: for<'a> IntoValue<'a> + Send,
+{
+  fn into_value(self, global: public::Global<'cx>) -> Result<public::Value<'cx>> {
+    Ok(global.new_iterator(self))
+  }
+}
+
+/// A [`NativeIterator`] with the type of its items erased.
+trait AnyNativeIterator: Send {
+  fn next<'cx>(&self, global: public::Global<'cx>) -> Result<public::Value<'cx>>;
+  fn done(&self) -> bool;
+}
+
+impl<T: NativeIterator> AnyNativeIterator for T {
+  fn next<'cx>(&self, global: public::Global<'cx>) -> Result<public::Value<'cx>> {
+    NativeIterator::next(self).into_value(global)
+  }
+
+  fn done(&self) -> bool {
+    NativeIterator::done(self)
+  }
+}
+
+/// An iterator created from a [`NativeIterator`].
+pub struct NativeIter {
+  inner: Box<dyn AnyNativeIterator>,
+}
+
+impl NativeIter {
+  pub fn new(iter: impl NativeIterator) -> Self {
+    Self {
+      inner: Box::new(iter),
+    }
+  }
+}
+
+fn native_iter_iter(this: Ptr<NativeIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+fn native_iter_next(this: Ptr<NativeIter>, scope: Scope<'_>) -> Result<Value> {
+  Ok(this.inner.next(scope.global())?.unbind())
+}
+
+fn native_iter_done(this: Ptr<NativeIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.inner.done()))
+}
+
+impl Object for NativeIter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "NativeIter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_method!(native_iter_iter),
+      "next" => builtin_method!(native_iter_next),
+      "done" => builtin_method!(native_iter_done),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(NativeIter);
+
+impl Display for NativeIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<native iter>")
+  }
+}
+
+impl Debug for NativeIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("NativeIter").finish_non_exhaustive()
+  }
+}
+
+async fn map(mut scope: Scope<'_>) -> Result<Value> {
+  let function = callable(&scope, 0)?;
+  let iterable = scope.param::<public::Value>(1)?.unbind();
+  let source = Source::new(&mut scope, iterable).await?;
+  Ok(Value::object(scope.alloc(Iter {
+    kind: Kind::Map { source, function },
+  })))
+}
+
+async fn filter(mut scope: Scope<'_>) -> Result<Value> {
+  let function = callable(&scope, 0)?;
+  let iterable = scope.param::<public::Value>(1)?.unbind();
+  let source = Source::new(&mut scope, iterable).await?;
+  Ok(Value::object(scope.alloc(Iter {
+    kind: Kind::Filter {
+      source,
+      function,
+      peeked: RefCell::new(None),
+    },
+  })))
+}
+
+async fn zip(mut scope: Scope<'_>) -> Result<Value> {
+  let left = scope.param::<public::Value>(0)?.unbind();
+  let right = scope.param::<public::Value>(1)?.unbind();
+  let left = Source::new(&mut scope, left).await?;
+  let right = Source::new(&mut scope, right).await?;
+  Ok(Value::object(scope.alloc(Iter {
+    kind: Kind::Zip { left, right },
+  })))
+}
+
+async fn enumerate(mut scope: Scope<'_>) -> Result<Value> {
+  let iterable = scope.param::<public::Value>(0)?.unbind();
+  let source = Source::new(&mut scope, iterable).await?;
+  Ok(Value::object(scope.alloc(Iter {
+    kind: Kind::Enumerate {
+      source,
+      index: Cell::new(0),
+    },
+  })))
+}
+
+/// `reduce(f, iterable, initial)` calls `f(acc, item)` for each item, and
+/// returns the final `acc`. Without `initial`, the first item is used instead.
+async fn reduce(mut scope: Scope<'_>) -> Result<Value> {
+  let function = callable(&scope, 0)?;
+  let iterable = scope.param::<public::Value>(1)?.unbind();
+  let initial = match scope.num_args() {
+    3 => Some(scope.param::<public::Value>(2)?.unbind()),
+    _ => None,
+  };
+  let source = Source::new(&mut scope, iterable).await?;
+
+  let mut acc = match initial {
+    Some(initial) => initial,
+    None if source.done(&mut scope).await? => {
+      fail!("cannot reduce an empty iterator without an initial value")
+    }
+    None => source.next(&mut scope).await?,
+  };
+  while !source.done(&mut scope).await? {
+    let item = source.next(&mut scope).await?;
+    acc = call(&mut scope, &function, &[acc, item]).await?;
+  }
+  Ok(acc)
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, async map);
+  bind_builtin_fn!(global, async filter);
+  bind_builtin_fn!(global, async zip);
+  bind_builtin_fn!(global, async enumerate);
+  bind_builtin_fn!(global, async reduce);
+}
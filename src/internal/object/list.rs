@@ -1,14 +1,18 @@
 use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
+use std::mem::swap;
 use std::vec::Vec;
 
-use super::builtin::BuiltinMethod;
-use super::{Object, Ptr, Str};
+use super::builtin::{BuiltinAsyncMethod, BuiltinMethod};
+use super::native::bind_builtin_method;
+use super::{Any, Object, Ptr, Str};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
-use crate::internal::vm::global::Global;
+use crate::internal::vm::global::{BuiltinKind, Global};
+use crate::internal::vm::thread::util::compare;
 use crate::public;
-use crate::public::{Scope, Unbind};
+use crate::public::{Bind, Scope, Unbind};
 use crate::util::{JoinIter, MAX_SAFE_INT, MIN_SAFE_INT};
 
 #[derive(Default)]
@@ -78,6 +82,14 @@ impl List {
     *self.data.borrow_mut().get_mut(index).unwrap_unchecked() = value;
   }
 
+  pub fn insert(&self, index: usize, value: Value) {
+    self.data.borrow_mut().insert(index, value);
+  }
+
+  pub fn remove(&self, index: usize) -> Value {
+    self.data.borrow_mut().remove(index)
+  }
+
   pub fn iter(&self) -> Iter {
     Iter {
       list: self,
@@ -146,7 +158,7 @@ fn list_set(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
   let len = this.len();
   let index = to_index(index, len)?;
   if !this.set(index, value) {
-    fail!("index `{index}` out of bounds, len was `{len}`")
+    fail!(Index: "index `{index}` out of bounds, len was `{len}`")
   }
 
   Ok(Value::none())
@@ -178,6 +190,199 @@ fn list_join(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
   ))
 }
 
+fn list_insert(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let (index, value) = scope.params::<(public::Value, public::Value)>()?;
+  let (index, value) = (index.unbind(), value.unbind());
+  let len = this.len();
+  let index = to_index(index, len)?;
+  // inserting at `len` is the same as `push`
+  if index > len {
+    fail!(Index: "index `{index}` out of bounds, len was `{len}`")
+  }
+  this.insert(index, value);
+  Ok(Value::none())
+}
+
+fn list_remove(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let index = scope.param::<public::Value>(0)?.unbind();
+  let len = this.len();
+  let index = to_index(index, len)?;
+  if index >= len {
+    fail!(Index: "index `{index}` out of bounds, len was `{len}`")
+  }
+  Ok(this.remove(index))
+}
+
+fn list_index_of(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  for (i, item) in this.iter().enumerate() {
+    if scope.are_equal(item, value.clone())? {
+      return Ok(Value::int(i as i32));
+    }
+  }
+  Ok(Value::none())
+}
+
+fn list_contains(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  for item in this.iter() {
+    if scope.are_equal(item, value.clone())? {
+      return Ok(Value::bool(true));
+    }
+  }
+  Ok(Value::bool(false))
+}
+
+fn list_reverse(this: Ptr<List>, _: Scope<'_>) -> Result<Value> {
+  this.data.borrow_mut().reverse();
+  Ok(Value::none())
+}
+
+/// `slice(start, end)` returns a new list with the items from `start` up to,
+/// but not including, `end`. Without `end`, the slice goes until the end of
+/// the list.
+fn list_slice(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let len = this.len();
+  let start = to_index(scope.param::<public::Value>(0)?.unbind(), len)?;
+  let end = match scope.num_args() {
+    2 => to_index(scope.param::<public::Value>(1)?.unbind(), len)?,
+    _ => len,
+  };
+  if start > end || end > len {
+    fail!(Index: "slice `{start}..{end}` out of bounds, len was `{len}`")
+  }
+  let items = this.data.borrow()[start..end].to_vec();
+  Ok(Value::object(scope.alloc(List::from(items))))
+}
+
+/// `sort(key)` sorts the list in place. Items are compared like in `cmp`,
+/// and if `key` is given, they are compared by `key(item)` instead. The sort
+/// is stable.
+///
+/// Items which can't be compared are a type error, and the list is left as
+/// it was.
+async fn list_sort(this: Ptr<List>, mut scope: Scope<'_>) -> Result<Value> {
+  let key = match scope.num_args() {
+    1 => Some(scope.param::<public::Value>(0)?.unbind()).filter(|key| !key.is_none()),
+    _ => None,
+  };
+
+  // the list is copied, because `key` may modify it while it is called
+  let items = this.iter().collect::<Vec<_>>();
+  let mut keyed = Vec::with_capacity(items.len());
+  for item in items {
+    let key = match &key {
+      Some(key) => {
+        let Some(key) = key.clone().to_any() else {
+          fail!(Type: "`{key}` is not callable");
+        };
+        let key = key.bind(scope.global());
+        let item = item.clone().bind(scope.global());
+        scope.call(key, &[item]).await?.unbind()
+      }
+      None => item.clone(),
+    };
+    keyed.push((key, item));
+  }
+
+  *this.data.borrow_mut() = merge_sort(&mut scope, keyed, None).await?;
+  Ok(Value::none())
+}
+
+/// `sort(list, by)` sorts `list` in place. If `by` is given, it is called
+/// with two items, and returns a number which is less than, equal to, or
+/// greater than zero if the first item is ordered before, the same as, or
+/// after the second one. Otherwise items are compared like in `cmp`. The sort
+/// is stable.
+///
+/// If `by` raises an error, it is propagated, and `list` is left as it was.
+async fn sort(mut scope: Scope<'_>) -> Result<Value> {
+  let list = scope.param::<public::Value>(0)?.unbind();
+  let Some(list) = list
+    .clone()
+    .to_any()
+    .and_then(|list| list.clone_cast::<List>())
+  else {
+    fail!(Type: "`{list}` is not a list");
+  };
+  let by = match scope.num_args() {
+    1 => None,
+    _ => match scope.param::<public::Value>(1)?.unbind() {
+      by if by.is_none() => None,
+      by => match by.clone().to_any() {
+        Some(by) => Some(by),
+        None => fail!(Type: "`{by}` is not callable"),
+      },
+    },
+  };
+
+  let items = list.iter().map(|item| (item.clone(), item)).collect();
+  *list.data.borrow_mut() = merge_sort(&mut scope, items, by.as_ref()).await?;
+  Ok(Value::none())
+}
+
+/// Sort `(key, item)` pairs by their keys, and return the items.
+///
+/// Comparisons may call back into the VM, so this can't use
+/// `slice::sort_by`, which may also panic if the comparison is not a total
+/// order. This is a bottom-up merge sort over a copy of the list, which is
+/// only written back once every comparison has succeeded. That also means the
+/// comparison may modify the list while it is called.
+async fn merge_sort(
+  scope: &mut Scope<'_>,
+  mut items: Vec<(Value, Value)>,
+  by: Option<&Ptr<Any>>,
+) -> Result<Vec<Value>> {
+  let mut merged = Vec::with_capacity(items.len());
+  let len = items.len();
+  let mut width = 1;
+  while width < len {
+    merged.clear();
+    for start in (0..len).step_by(2 * width) {
+      let mid = usize::min(start + width, len);
+      let end = usize::min(start + 2 * width, len);
+      let (mut i, mut j) = (start, mid);
+      while i < mid && j < end {
+        // the right item only goes first if it is strictly less than the left
+        // one, which keeps the sort stable
+        if compare_by(scope, by, &items[j].0, &items[i].0).await? == Ordering::Less {
+          merged.push(items[j].clone());
+          j += 1;
+        } else {
+          merged.push(items[i].clone());
+          i += 1;
+        }
+      }
+      merged.extend_from_slice(&items[i..mid]);
+      merged.extend_from_slice(&items[j..end]);
+    }
+    swap(&mut items, &mut merged);
+    width *= 2;
+  }
+  Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
+async fn compare_by(
+  scope: &mut Scope<'_>,
+  by: Option<&Ptr<Any>>,
+  a: &Value,
+  b: &Value,
+) -> Result<Ordering> {
+  let Some(by) = by else {
+    return compare(scope, a.clone(), b.clone()).await;
+  };
+  let by = by.clone().bind(scope.global());
+  let args = [
+    a.clone().bind(scope.global()),
+    b.clone().bind(scope.global()),
+  ];
+  let result = scope.call(by, &args).await?.unbind();
+  match result.clone().to_number().and_then(|n| n.partial_cmp(&0.0)) {
+    Some(ordering) => Ok(ordering),
+    None => fail!(Type: "`by` must return a number, got `{result}`"),
+  }
+}
+
 #[derive(Debug)]
 pub struct ListIter {
   list: Ptr<List>,
@@ -218,7 +423,7 @@ impl Object for ListIter {
     Ok(
       this
         .named_field_opt(scope, name.clone())?
-        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
     )
   }
 
@@ -231,11 +436,11 @@ impl Object for ListIter {
       "iter" => builtin_method!(list_iter_iter),
       "next" => builtin_method!(list_iter_next),
       "done" => builtin_method!(list_iter_done),
-      _ => fail!("`{this}` has no field `{name}`"),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
     };
 
     Ok(Some(Value::object(unsafe {
-      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
     })))
   }
 }
@@ -262,7 +467,7 @@ impl Object for List {
     Ok(
       this
         .named_field_opt(scope, name.clone())?
-        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
     )
   }
 
@@ -281,11 +486,32 @@ impl Object for List {
       "extend" => builtin_method!(list_extend),
       "join" => builtin_method!(list_join),
       "iter" => builtin_method!(list_iter),
-      _ => fail!("`{this}` has no field `{name}`"),
+      "insert" => builtin_method!(list_insert),
+      "remove" => builtin_method!(list_remove),
+      "index_of" => builtin_method!(list_index_of),
+      "contains" => builtin_method!(list_contains),
+      "reverse" => builtin_method!(list_reverse),
+      "slice" => builtin_method!(list_slice),
+      "sort" => {
+        let method = builtin_async_method!(list_sort);
+        return Ok(Some(Value::object(unsafe {
+          scope.alloc(BuiltinAsyncMethod::new(Value::object(this), name, method))
+        })));
+      }
+      _ => {
+        let method = bind_builtin_method(
+          &scope,
+          BuiltinKind::List,
+          Value::object(this.clone()),
+          &name,
+        )
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?;
+        return Ok(Some(method));
+      }
     };
 
     Ok(Some(Value::object(unsafe {
-      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
     })))
   }
 
@@ -294,7 +520,7 @@ impl Object for List {
     let index = to_index(key.clone(), len)?;
     let value = this
       .get(index)
-      .ok_or_else(|| error!("index `{key}` out of bounds, len was `{len}`"))?;
+      .ok_or_else(|| error!(Index: "index `{key}` out of bounds, len was `{len}`"))?;
     Ok(value)
   }
 
@@ -308,7 +534,7 @@ impl Object for List {
     let len = this.len();
     let index = to_index(key.clone(), len)?;
     if !this.set(index, value) {
-      fail!("index `{key}` out of bounds, len was `{len}`");
+      fail!(Index: "index `{key}` out of bounds, len was `{len}`");
     };
     Ok(())
   }
@@ -345,12 +571,21 @@ pub fn register_builtin_functions(global: &Global) {
       pop: builtin_method_static!(List, list_pop),
       extend: builtin_method_static!(List, list_extend),
       join: builtin_method_static!(List, list_join),
-      iter: builtin_method_static!(List, list_iter)
+      iter: builtin_method_static!(List, list_iter),
+      insert: builtin_method_static!(List, list_insert),
+      remove: builtin_method_static!(List, list_remove),
+      index_of: builtin_method_static!(List, list_index_of),
+      contains: builtin_method_static!(List, list_contains),
+      reverse: builtin_method_static!(List, list_reverse),
+      slice: builtin_method_static!(List, list_slice)
+    } async {
+      sort: builtin_async_method_static!(List, list_sort)
     })
   );
+  bind_builtin_fn!(global, async sort(list, by));
 }
 
-fn to_index(index: Value, len: usize) -> Result<usize> {
+pub(super) fn to_index(index: Value, len: usize) -> Result<usize> {
   if index.is_int() {
     let index = unsafe { index.to_int().unwrap_unchecked() };
     let index = if index.is_negative() {
@@ -366,7 +601,7 @@ fn to_index(index: Value, len: usize) -> Result<usize> {
     }
   };
 
-  fail!("`{index}` is not a valid index")
+  fail!(Type: "`{index}` is not a valid index")
 }
 
 declare_object_type!(List);
@@ -1,11 +1,14 @@
+use std::cell::RefCell;
 use std::fmt::{Debug, Display};
 use std::num::NonZeroU64;
 
 use indexmap::{IndexMap, IndexSet};
 
-use super::native::{NativeAsyncFunction, NativeClass, NativeFunction};
+use super::native::{
+  InitCallback, LocalBoxFuture, NativeAsyncFunction, NativeClass, NativeFunction,
+};
 use super::ptr::Ptr;
-use super::{Function, FunctionDescriptor, Object, Str, Table};
+use super::{ConstantPool, Function, FunctionDescriptor, Object, Str, Table};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
 use crate::internal::vm::global::Global;
@@ -14,7 +17,88 @@ use crate::public::Scope;
 use crate::Cow;
 
 pub trait ModuleLoader: Send {
-  fn load(&self, path: &str) -> Result<Cow<'static, str>>;
+  fn load(&self, path: &ModulePath) -> Result<Cow<'static, str>>;
+}
+
+/// A module loader which may suspend the VM while loading a module, e.g. to
+/// fetch the source code over the network.
+pub trait AsyncModuleLoader: Send {
+  fn load(&self, path: &ModulePath) -> LocalBoxFuture<'static, Result<Cow<'static, str>>>;
+}
+
+pub enum AnyModuleLoader {
+  Sync(Box<dyn ModuleLoader>),
+  Async(Box<dyn AsyncModuleLoader>),
+}
+
+pub enum LoadResult {
+  Ready(Cow<'static, str>),
+  Pending(LocalBoxFuture<'static, Result<Cow<'static, str>>>),
+}
+
+impl AnyModuleLoader {
+  pub fn load(&self, path: &ModulePath) -> Result<LoadResult> {
+    match self {
+      AnyModuleLoader::Sync(loader) => loader.load(path).map(LoadResult::Ready),
+      AnyModuleLoader::Async(loader) => Ok(LoadResult::Pending(loader.load(path))),
+    }
+  }
+}
+
+/// An absolute module path, such as `foo.bar.baz`.
+///
+/// Relative imports (`import .sibling`) are resolved against the importing
+/// module before they reach a [`ModuleLoader`], so loaders only ever see
+/// absolute paths.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModulePath {
+  path: String,
+}
+
+impl ModulePath {
+  /// Resolve `path` relative to the module named `base`.
+  ///
+  /// Each leading `.` in `path` refers to one level above `base`: a single
+  /// `.` is the package containing `base`, `..` is its parent, and so on.
+  /// If `base` is `None`, the path is resolved relative to the root package.
+  pub fn resolve(base: Option<&str>, path: &str) -> Result<Self> {
+    let rest = path.trim_start_matches('.');
+    let level = path.len() - rest.len();
+    if level == 0 {
+      return Ok(Self { path: path.into() });
+    }
+
+    // code which is not in any module behaves like a top-level module
+    let mut package = match base {
+      Some(base) => base.split('.').collect::<Vec<_>>(),
+      None => vec![""],
+    };
+    for _ in 0..level {
+      if package.pop().is_none() {
+        fail!("relative import `{path}` goes beyond the root package");
+      }
+    }
+    package.push(rest);
+    Ok(Self {
+      path: package.join("."),
+    })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.path
+  }
+
+  /// The individual segments of this path, e.g. `["foo", "bar", "baz"]` for
+  /// `foo.bar.baz`.
+  pub fn segments(&self) -> impl Iterator<Item = &str> {
+    self.path.split('.')
+  }
+}
+
+impl Display for ModulePath {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.path)
+  }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -102,7 +186,34 @@ pub struct Module {
 #[derive(Debug)]
 pub enum ModuleKind {
   Script { root: Ptr<Function> },
-  Native,
+  Native { init: PendingInit },
+}
+
+/// The `init` callback of a native module, which has not run yet.
+pub struct PendingInit(RefCell<Option<InitCallback>>);
+
+impl PendingInit {
+  /// Run the callback if it has not yet run successfully.
+  ///
+  /// If the callback fails, it will be retried on the next import.
+  pub fn run(&self, scope: Scope<'_>, module_vars: Ptr<Table>) -> Result<()> {
+    let Some(init) = self.0.borrow_mut().take() else {
+      return Ok(());
+    };
+    if let Err(e) = init(scope, module_vars) {
+      *self.0.borrow_mut() = Some(init);
+      return Err(e);
+    }
+    Ok(())
+  }
+}
+
+impl Debug for PendingInit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("PendingInit")
+      .field(&self.0.borrow().is_some())
+      .finish()
+  }
 }
 
 impl Module {
@@ -138,10 +249,12 @@ impl Module {
     let module_vars = global.alloc(Table::with_capacity(module.data.fns.len()));
 
     for (name, f) in module.data.fns.iter() {
+      let params = module.data.fn_params.get(name).copied().unwrap_or(&[]);
       let name = global.alloc(Str::owned(name.clone()));
       let f = Value::object(global.alloc(NativeFunction {
         name: name.clone(),
         cb: f.clone(),
+        params,
       }));
       module_vars.insert(name, f);
     }
@@ -166,7 +279,9 @@ impl Module {
       module_id,
       name,
       module_vars,
-      kind: ModuleKind::Native,
+      kind: ModuleKind::Native {
+        init: PendingInit(RefCell::new(module.data.init.clone())),
+      },
     }
   }
 }
@@ -179,18 +294,30 @@ impl Object for Module {
   default_instance_of!();
 
   fn named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    if is_private(name.as_str()) {
+      fail!("`{}` is private to module `{}`", name, this.name);
+    }
     let value = this
       .module_vars
       .get(&name)
-      .ok_or_else(|| error!("module `{}` has no export `{}`", this.name, name))?;
+      .ok_or_else(|| error!(Name: "module `{}` has no export `{}`", this.name, name))?;
     Ok(value)
   }
 
   fn named_field_opt(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    if is_private(name.as_str()) {
+      return Ok(None);
+    }
     Ok(this.module_vars.get(&name))
   }
 }
 
+/// Module variables whose names begin with an underscore are private, and may
+/// only be used from within the module which declares them.
+fn is_private(name: &str) -> bool {
+  name.starts_with('_')
+}
+
 declare_object_type!(Module);
 
 impl Display for Module {
@@ -203,6 +330,7 @@ impl Display for Module {
 pub struct ModuleDescriptor {
   pub name: Ptr<Str>,
   pub root: Ptr<FunctionDescriptor>,
+  pub pool: Ptr<ConstantPool>,
   pub module_vars: IndexSet<Ptr<Str>>,
 }
 
@@ -1,4 +1,5 @@
 use std::any::{Any as StdAny, TypeId};
+use std::cell::OnceCell;
 use std::fmt::{Debug, Display};
 use std::pin::Pin;
 use std::string::String as StdString;
@@ -6,10 +7,11 @@ use std::sync::Arc;
 
 use indexmap::IndexMap;
 
-use super::{Any, Object, Ptr, ReturnAddr, Str};
+use super::class::{ClassInstance, ClassType};
+use super::{Any, BoundFunction, Function, Object, Ptr, ReturnAddr, Str, Table};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
-use crate::internal::vm::global::Global;
+use crate::internal::vm::global::{BuiltinKind, Global};
 use crate::internal::vm::thread::{AsyncFrame, CallResult, Slot0};
 use crate::public::Scope;
 
@@ -18,10 +20,23 @@ pub type LocalBoxFuture<'a, T> = Pin<Box<dyn core::future::Future<Output = T> +
 pub type Callback<R> = Arc<dyn Fn(Scope<'_>) -> R + Send + Sync + 'static>;
 pub type SyncCallback = Callback<Result<Value>>;
 pub type AsyncCallback = Callback<LocalBoxFuture<'static, Result<Value>>>;
+pub type InitCallback = Arc<dyn Fn(Scope<'_>, Ptr<Table>) -> Result<()> + Send + Sync + 'static>;
+
+fn check_native_call(scope: &Scope<'_>, name: &Str) -> Result<()> {
+  match scope.thread.global.security_policy() {
+    Some(policy) => policy.check_native_call(name.as_str()),
+    None => Ok(()),
+  }
+}
 
 pub struct NativeFunction {
   pub name: Ptr<Str>,
   pub cb: SyncCallback,
+  /// The params which may be passed by name, see
+  /// [`NativeModuleBuilder::function_with_params`].
+  ///
+  /// [`NativeModuleBuilder::function_with_params`]: crate::public::module::NativeModuleBuilder::function_with_params
+  pub params: &'static [&'static str],
 }
 
 impl NativeFunction {
@@ -54,6 +69,7 @@ impl Object for NativeFunction {
   }
 
   fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    check_native_call(&scope, &this.name)?;
     NativeFunction::call(this.as_ref(), scope).map(CallResult::Return)
   }
 }
@@ -95,9 +111,11 @@ impl Object for NativeAsyncFunction {
   }
 
   fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    check_native_call(&scope, &this.name)?;
     Ok(CallResult::Poll(AsyncFrame {
       stack_base: scope.stack_base,
       fut: NativeAsyncFunction::call(this.as_ref(), scope),
+      on_ready: None,
     }))
   }
 }
@@ -108,6 +126,39 @@ declare_object_type!(NativeAsyncFunction);
 pub struct NativeClassInstance {
   pub instance: Box<dyn StdAny + Send>,
   pub class: Ptr<NativeClass>,
+  /// Holds the fields and methods inherited from the script parent of
+  /// `class`, if it has one.
+  pub base: Option<Ptr<ClassInstance>>,
+}
+
+impl NativeClassInstance {
+  pub fn new(
+    global: &Global,
+    class: Ptr<NativeClass>,
+    instance: Box<dyn StdAny + Send>,
+  ) -> Result<Self> {
+    let base = class
+      .parent(global)?
+      .map(|parent| global.alloc(ClassInstance::new(global.clone(), parent)));
+    Ok(Self {
+      instance,
+      class,
+      base,
+    })
+  }
+
+  /// Look up `name` in the fields and methods inherited from the script
+  /// parent. Methods are bound to `this`, so that they can call its native
+  /// methods through `self`.
+  fn base_field(scope: &Scope<'_>, this: &Ptr<Self>, name: &Ptr<Str>) -> Option<Value> {
+    let value = this.base.as_ref()?.fields.get(name)?;
+    Some(match value.clone().to_object::<Function>() {
+      Some(method) => {
+        Value::object(scope.alloc(BoundFunction::new(this.clone().into_any(), method)))
+      }
+      None => value,
+    })
+  }
 }
 
 impl Display for NativeClassInstance {
@@ -121,8 +172,19 @@ impl Object for NativeClassInstance {
     "NativeClassInstance"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    todo!()
+  fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool> {
+    if let Some(ty) = ty.clone().to_object::<ClassType>() {
+      return Ok(
+        this
+          .base
+          .as_ref()
+          .is_some_and(|base| base.class.is_subclass_of(&ty)),
+      );
+    }
+    Ok(match ty.to_object::<NativeClass>() {
+      Some(ty) => this.class.ptr_eq(&ty),
+      None => false,
+    })
   }
 
   fn named_field(mut scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
@@ -137,11 +199,13 @@ impl Object for NativeClassInstance {
       result
     } else if let Some(method) = this.class.methods.get(name.as_str()) {
       Ok(Value::object(scope.alloc(NativeBoundFunction::new(
-        this.clone(),
+        Value::object(this.clone()),
         method.clone(),
       ))))
+    } else if let Some(value) = NativeClassInstance::base_field(&scope, &this, &name) {
+      Ok(value)
     } else {
-      fail!("`{this}` has no field `{name}`")
+      fail!(Name: "`{this}` has no field `{name}`")
     }
   }
 
@@ -161,11 +225,11 @@ impl Object for NativeClassInstance {
       result
     } else if let Some(method) = this.class.methods.get(name.as_str()) {
       Ok(Some(Value::object(scope.alloc(NativeBoundFunction::new(
-        this.clone(),
+        Value::object(this.clone()),
         method.clone(),
       )))))
     } else {
-      Ok(None)
+      Ok(NativeClassInstance::base_field(&scope, &this, &name))
     }
   }
 
@@ -186,8 +250,10 @@ impl Object for NativeClassInstance {
       let result = NativeFunction::call(setter.as_ref(), scope.clone()).map(|_| ());
       scope.leave();
       result
+    } else if let Some(base) = this.base.clone() {
+      base.set_named_field(scope, name, value)
     } else {
-      fail!("`{this}` has no field `{name}`")
+      fail!(Name: "`{this}` has no field `{name}`")
     }
   }
 }
@@ -202,6 +268,10 @@ pub struct NativeClass {
   pub fields: IndexMap<Ptr<Str>, NativeField>,
   pub methods: IndexMap<Ptr<Str>, Ptr<Any>>,
   pub static_methods: IndexMap<Ptr<Str>, Ptr<Any>>,
+  pub finalizer: Option<Ptr<NativeFunction>>,
+  /// The name of the global script class this class is derived from.
+  pub parent_name: Option<Ptr<Str>>,
+  pub parent: OnceCell<Ptr<ClassType>>,
 }
 
 impl NativeClass {
@@ -214,6 +284,7 @@ impl NativeClass {
       global.alloc(NativeFunction {
         name: global.intern("__init__"),
         cb: init,
+        params: &[],
       })
     });
 
@@ -224,11 +295,13 @@ impl NativeClass {
         get: global.alloc(NativeFunction {
           name: global.intern("__get__"),
           cb: desc.get.clone(),
+          params: &[],
         }),
         set: desc.set.as_ref().map(|set| {
           global.alloc(NativeFunction {
             name: global.intern("__set__"),
             cb: set.clone(),
+            params: &[],
           })
         }),
       };
@@ -249,6 +322,19 @@ impl NativeClass {
       static_methods.insert(name, method);
     }
 
+    let finalizer = desc.finalizer.clone().map(|finalizer| {
+      global.alloc(NativeFunction {
+        name: global.intern("__del__"),
+        cb: finalizer,
+        params: &[],
+      })
+    });
+
+    let parent_name = desc
+      .parent
+      .as_ref()
+      .map(|parent| global.alloc(Str::owned(parent.clone())));
+
     Self {
       name,
       type_id,
@@ -256,7 +342,36 @@ impl NativeClass {
       fields,
       methods,
       static_methods,
+      finalizer,
+      parent_name,
+      parent: OnceCell::new(),
+    }
+  }
+
+  /// The script parent of this class. It is looked up by name in the globals
+  /// the first time it is needed, because native classes are created before
+  /// any script runs.
+  pub fn parent(&self, global: &Global) -> Result<Option<Ptr<ClassType>>> {
+    let Some(name) = self.parent_name.as_ref() else {
+      return Ok(None);
+    };
+    if let Some(parent) = self.parent.get() {
+      return Ok(Some(parent.clone()));
     }
+    let Some(parent) = global.get(name.as_str()) else {
+      fail!(Name: "parent `{name}` of native class `{}` is not defined", self.name);
+    };
+    let Some(parent) = parent.clone().to_object::<ClassType>() else {
+      fail!(Type: "parent `{parent}` of native class `{}` is not a class", self.name);
+    };
+    Ok(Some(self.parent.get_or_init(|| parent).clone()))
+  }
+
+  pub fn has_setter(&self, name: &str) -> bool {
+    self
+      .fields
+      .get(name)
+      .is_some_and(|field| field.set.is_some())
   }
 }
 
@@ -271,15 +386,19 @@ impl Object for NativeClass {
     "NativeClass"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    todo!()
-  }
+  default_instance_of!();
 
   fn named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
     if let Some(method) = this.static_methods.get(name.as_str()) {
       Ok(Value::object(method.clone()))
     } else if let Some(method) = this.methods.get(name.as_str()) {
       Ok(Value::object(method.clone()))
+    } else if let Some(method) = this
+      .parent
+      .get()
+      .and_then(|parent| parent.methods.get(&name).cloned())
+    {
+      Ok(Value::object(method))
     } else {
       fail!("failed to get field `{name}`")
     }
@@ -291,7 +410,12 @@ impl Object for NativeClass {
     } else if let Some(method) = this.methods.get(name.as_str()) {
       Ok(Some(Value::object(method.clone())))
     } else {
-      Ok(None)
+      let parent = this.parent.get();
+      Ok(
+        parent
+          .and_then(|parent| parent.methods.get(&name).cloned())
+          .map(Value::object),
+      )
     }
   }
 
@@ -308,12 +432,12 @@ declare_object_type!(NativeClass);
 
 #[derive(Debug)]
 pub struct NativeBoundFunction {
-  pub this: Ptr<NativeClassInstance>,
+  pub this: Value,        // NativeClassInstance, or a value of a builtin type
   pub function: Ptr<Any>, // NativeFunction or NativeAsyncFunction
 }
 
 impl NativeBoundFunction {
-  fn new(this: Ptr<NativeClassInstance>, function: Ptr<Any>) -> Self {
+  pub(crate) fn new(this: Value, function: Ptr<Any>) -> Self {
     debug_assert!(function.is::<NativeFunction>() || function.is::<NativeAsyncFunction>());
     Self { this, function }
   }
@@ -336,11 +460,7 @@ impl Object for NativeBoundFunction {
   }
 
   fn call(mut scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
-    let scope = scope.enter_nested(
-      Slot0::Receiver(Value::object(this.this.clone())),
-      scope.args,
-      None,
-    );
+    let scope = scope.enter_nested(Slot0::Receiver(this.this.clone()), scope.args, None);
     if this.function.is::<NativeFunction>() {
       let function = unsafe { this.function.clone().cast_unchecked::<NativeFunction>() };
       let result = NativeFunction::call(function.as_ref(), scope.clone()).map(CallResult::Return);
@@ -357,13 +477,33 @@ impl Object for NativeBoundFunction {
       Ok(CallResult::Poll(AsyncFrame {
         stack_base: scope.stack_base,
         fut: NativeAsyncFunction::call(function.as_ref(), scope),
+        on_ready: None,
       }))
     }
   }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // like `BuiltinMethod`, a receiver which is an object is the same object
+    Ok(this.this.bitwise_eq(&other.this) && this.function.ptr_eq(&other.function))
+  }
 }
 
 declare_object_type!(NativeBoundFunction);
 
+/// Bind the method called `name` which was registered by the host on the
+/// builtin type `kind` to `this`, if there is one.
+pub fn bind_builtin_method(
+  scope: &Scope<'_>,
+  kind: BuiltinKind,
+  this: Value,
+  name: &str,
+) -> Option<Value> {
+  let method = scope.thread.global.builtin_method(kind, name)?;
+  Some(Value::object(
+    scope.alloc(NativeBoundFunction::new(this, method.into_any())),
+  ))
+}
+
 #[derive(Debug)]
 pub struct NativeField {
   // TODO: these probably don't need to be function objects
@@ -378,6 +518,8 @@ pub struct NativeClassDescriptor {
   pub(crate) fields: IndexMap<StdString, NativeFieldDescriptor>,
   pub(crate) methods: IndexMap<StdString, NativeMethodDescriptor>,
   pub(crate) static_methods: IndexMap<StdString, NativeMethodDescriptor>,
+  pub(crate) finalizer: Option<SyncCallback>,
+  pub(crate) parent: Option<StdString>,
 }
 
 #[derive(Clone)]
@@ -399,6 +541,7 @@ impl NativeMethodDescriptor {
         .alloc(NativeFunction {
           name,
           cb: cb.clone(),
+          params: &[],
         })
         .into_any(),
       NativeMethodDescriptor::Async(cb) => global
@@ -0,0 +1,62 @@
+use std::fmt::{Debug, Display};
+
+use super::{is_method, Object, Ptr, Str};
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public::Scope;
+
+/// A structural type, which is matched by any value that has all of its
+/// methods.
+///
+/// `value is Iterable` is true for any value with an `iter` method, whether
+/// it is a builtin, a script class instance, or a native class instance.
+#[derive(Debug)]
+pub struct Protocol {
+  pub name: Ptr<Str>,
+  pub methods: Box<[Ptr<Str>]>,
+}
+
+impl Protocol {
+  pub fn new(name: Ptr<Str>, methods: impl Into<Box<[Ptr<Str>]>>) -> Self {
+    Self {
+      name,
+      methods: methods.into(),
+    }
+  }
+
+  pub fn is_implemented_by(&self, scope: Scope<'_>, value: &Value) -> bool {
+    let Some(value) = value.clone().to_any() else {
+      return false;
+    };
+    // some objects fail instead of returning `None` for unknown fields,
+    // which is treated the same as a missing method. a field which holds
+    // anything other than a function does not count either.
+    self.methods.iter().all(
+      |name| match value.named_field_opt(scope.clone(), name.clone()) {
+        Ok(Some(field)) => field.to_any().is_some_and(|field| is_method(&field)),
+        _ => false,
+      },
+    )
+  }
+}
+
+impl Display for Protocol {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<protocol `{}`>", self.name)
+  }
+}
+
+impl Object for Protocol {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Protocol"
+  }
+
+  default_instance_of!();
+}
+
+declare_object_type!(Protocol);
+
+pub fn register_builtin_protocols(global: &Global) {
+  global.register_protocol("Iterable", &["iter"]);
+  global.register_protocol("Iterator", &["iter", "next", "done"]);
+}
@@ -5,10 +5,12 @@ use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::ptr::{self, NonNull};
+use std::rc::Rc;
 use std::{alloc, mem};
 
 use super::{Type, VTable};
 use crate::internal::error::Result;
+use crate::internal::vm::allocator::Allocator;
 use crate::internal::vm::global::Global;
 
 // TODO: identity eq specialization similar to `std::rc::Rc`
@@ -19,6 +21,9 @@ struct Repr<T: Sized + 'static> {
   type_id: TypeId,
   refs: Cell<u64>,
   vtable: &'static super::VTable<T>,
+  /// The allocator which the object is freed through, or `None` for the
+  /// global allocator.
+  allocator: Option<Rc<dyn Allocator>>,
   data: T,
 }
 
@@ -96,8 +101,12 @@ impl<T: Sized + 'static> Drop for Ptr<T> {
 
       let ptr = self.repr.as_ptr() as *mut u8;
       let layout = self.repr().layout;
-      // TODO: replace with `alloc::Global.deallocate` when `alloc::Global` is stable
-      unsafe { alloc::dealloc(ptr, layout) }
+      // only `data` was dropped above, so the allocator is moved out of the object
+      match unsafe { ptr::read(&self.repr().allocator) } {
+        Some(allocator) => unsafe { allocator.deallocate(NonNull::new_unchecked(ptr), layout) },
+        // TODO: replace with `alloc::Global.deallocate` when `alloc::Global` is stable
+        None => unsafe { alloc::dealloc(ptr, layout) },
+      }
     }
   }
 }
@@ -166,17 +175,40 @@ impl<T: Type + Sized + 'static> Ptr<T> {
       type_id: TypeId::of::<T>(),
       refs: Cell::new(1),
       vtable: <T as Type>::vtable(),
+      allocator: None,
       data: v,
     });
     Ptr {
       repr: NonNull::new_unchecked(Box::into_raw(object)),
     }
   }
+
+  /// Like `alloc_raw`, but the object is allocated by `allocator`, and freed
+  /// through it once it is dropped.
+  pub(crate) unsafe fn alloc_in(v: T, allocator: Rc<dyn Allocator>) -> Self {
+    let layout = Layout::new::<Repr<T>>();
+    let Some(repr) = allocator.allocate(layout) else {
+      alloc::handle_alloc_error(layout)
+    };
+    let repr = repr.cast::<Repr<T>>();
+    repr.as_ptr().write(Repr {
+      layout,
+      type_id: TypeId::of::<T>(),
+      refs: Cell::new(1),
+      vtable: <T as Type>::vtable(),
+      allocator: Some(allocator),
+      data: v,
+    });
+    Ptr { repr }
+  }
 }
 
 impl Global {
   pub fn alloc<T: Type + 'static>(&self, v: T) -> Ptr<T> {
-    unsafe { Ptr::alloc_raw(v) }
+    match self.allocator() {
+      Some(allocator) => unsafe { Ptr::alloc_in(v, allocator.clone()) },
+      None => unsafe { Ptr::alloc_raw(v) },
+    }
   }
 }
 
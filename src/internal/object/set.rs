@@ -0,0 +1,291 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::{Debug, Display};
+
+use indexmap::IndexMap;
+
+use super::builtin::BuiltinMethod;
+use super::iter::Source;
+use super::table::Key;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// An unordered collection of unique values.
+///
+/// Only values which are compared by value may be stored in a set: `none`,
+/// bools, numbers, strings, and tuples of those. Like `Table`, entries are
+/// kept in insertion order, so iteration and printing is deterministic.
+#[derive(Default)]
+pub struct Set {
+  data: RefCell<IndexMap<Key, Value>>,
+}
+
+fn key(value: &Value) -> Result<Key> {
+  Ok(Key::new(value).ok_or_else(|| error!(Type: "`{value}` may not be stored in a set"))?)
+}
+
+impl Set {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.data.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.borrow().is_empty()
+  }
+
+  /// Returns `false` if the set already contained `value`.
+  pub fn insert(&self, value: Value) -> Result<bool> {
+    let key = key(&value)?;
+    let mut data = self.data.borrow_mut();
+    if data.contains_key(&key) {
+      return Ok(false);
+    }
+    data.insert(key, value);
+    Ok(true)
+  }
+
+  /// Returns `false` if the set did not contain `value`.
+  pub fn remove(&self, value: &Value) -> Result<bool> {
+    let key = key(value)?;
+    Ok(self.data.borrow_mut().shift_remove(&key).is_some())
+  }
+
+  pub fn contains(&self, value: &Value) -> Result<bool> {
+    let key = key(value)?;
+    Ok(self.data.borrow().contains_key(&key))
+  }
+
+  pub fn get_index(&self, index: usize) -> Option<Value> {
+    self
+      .data
+      .borrow()
+      .get_index(index)
+      .map(|(_, value)| value.clone())
+  }
+
+  /// Returns a new set with the entries of `self` for which `f` returns true.
+  fn filter(&self, f: impl Fn(&Key) -> bool) -> Self {
+    let data = self
+      .data
+      .borrow()
+      .iter()
+      .filter(|(key, _)| f(key))
+      .map(|(key, value)| (key.clone(), value.clone()))
+      .collect();
+    Self {
+      data: RefCell::new(data),
+    }
+  }
+}
+
+impl Display for Set {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<set>")
+  }
+}
+
+impl Debug for Set {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_set().entries(self.data.borrow().values()).finish()
+  }
+}
+
+fn other_set(scope: &Scope<'_>) -> Result<Ptr<Set>> {
+  let other = scope.param::<public::Value>(0)?.unbind();
+  let Some(other) = other.clone().to_object::<Set>() else {
+    fail!(Type: "`{other}` is not a set");
+  };
+  Ok(other)
+}
+
+fn set_len(this: Ptr<Set>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.len() as i32))
+}
+
+fn set_is_empty(this: Ptr<Set>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_empty()))
+}
+
+fn set_add(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  this.insert(value)?;
+  Ok(Value::none())
+}
+
+fn set_remove(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  Ok(Value::bool(this.remove(&value)?))
+}
+
+fn set_contains(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  Ok(Value::bool(Set::contains(&this, &value)?))
+}
+
+fn set_union(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let other = other_set(&scope)?;
+  let result = this.filter(|_| true);
+  {
+    let mut data = result.data.borrow_mut();
+    for (key, value) in other.data.borrow().iter() {
+      data.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+  }
+  Ok(Value::object(scope.alloc(result)))
+}
+
+fn set_intersection(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let other = other_set(&scope)?;
+  let other_data = other.data.borrow();
+  let result = this.filter(|key| other_data.contains_key(key));
+  Ok(Value::object(scope.alloc(result)))
+}
+
+fn set_difference(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  let other = other_set(&scope)?;
+  let other_data = other.data.borrow();
+  let result = this.filter(|key| !other_data.contains_key(key));
+  Ok(Value::object(scope.alloc(result)))
+}
+
+fn set_iter(this: Ptr<Set>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(SetIter {
+    set: this,
+    index: Cell::new(0),
+  })))
+}
+
+impl Object for Set {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Set"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "len" => builtin_method!(set_len),
+      "is_empty" => builtin_method!(set_is_empty),
+      "add" => builtin_method!(set_add),
+      "remove" => builtin_method!(set_remove),
+      "contains" => builtin_method!(set_contains),
+      "union" => builtin_method!(set_union),
+      "intersection" => builtin_method!(set_intersection),
+      "difference" => builtin_method!(set_difference),
+      "iter" => builtin_method!(set_iter),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+
+  fn contains(_: Scope<'_>, this: Ptr<Self>, item: Value) -> Result<bool> {
+    Set::contains(&this, &item)
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    let this_data = this.data.borrow();
+    let other_data = other.data.borrow();
+    Ok(
+      this_data.len() == other_data.len()
+        && this_data.keys().all(|key| other_data.contains_key(key)),
+    )
+  }
+}
+
+declare_object_type!(Set);
+
+#[derive(Debug)]
+pub struct SetIter {
+  set: Ptr<Set>,
+  index: Cell<usize>,
+}
+
+impl Display for SetIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<set iter>")
+  }
+}
+
+fn set_iter_iter(this: Ptr<SetIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+fn set_iter_next(this: Ptr<SetIter>, _: Scope<'_>) -> Result<Value> {
+  if let Some(item) = this.set.get_index(this.index.get()) {
+    this.index.set(this.index.get() + 1);
+    Ok(item)
+  } else {
+    Ok(Value::none())
+  }
+}
+
+fn set_iter_done(this: Ptr<SetIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.index.get() >= this.set.len()))
+}
+
+impl Object for SetIter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "SetIter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_method!(set_iter_iter),
+      "next" => builtin_method!(set_iter_next),
+      "done" => builtin_method!(set_iter_done),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(SetIter);
+
+/// `set(iterable)` creates a set with the items of `iterable`, or an empty set
+/// if it is called without arguments.
+async fn set(mut scope: Scope<'_>) -> Result<Value> {
+  let set = Set::new();
+  if scope.num_args() > 0 {
+    let iterable = scope.param::<public::Value>(0)?.unbind();
+    let source = Source::new(&mut scope, iterable).await?;
+    while !source.done(&mut scope).await? {
+      set.insert(source.next(&mut scope).await?)?;
+    }
+  }
+  Ok(Value::object(scope.alloc(set)))
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, async set);
+}
@@ -0,0 +1,62 @@
+//! Shapes, also known as hidden classes.
+//!
+//! Every instance of a class starts out with the same fields, in the same
+//! order, so they are stored in the same slots of each instance's field
+//! table. The shape of the class describes that layout. Once the slot of a
+//! field has been found in one instance, it can be used to access the field
+//! in any other instance with the same shape, without looking it up by name.
+//!
+//! An instance which gains a field its class doesn't have no longer has a
+//! shape. It is in "dictionary mode", and its fields are always looked up by
+//! name.
+
+use std::fmt::Debug;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Ptr, Str};
+
+/// Identifies a shape. Ids are never reused, even by different VMs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShapeId(NonZeroU64);
+
+impl ShapeId {
+  fn next() -> Self {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    Self(NonZeroU64::new(id).expect("shape id overflowed"))
+  }
+}
+
+pub struct Shape {
+  id: ShapeId,
+  /// The name of the field in each slot.
+  keys: Box<[Ptr<Str>]>,
+}
+
+impl Shape {
+  pub fn new(keys: impl IntoIterator<Item = Ptr<Str>>) -> Self {
+    Self {
+      id: ShapeId::next(),
+      keys: keys.into_iter().collect(),
+    }
+  }
+
+  pub fn id(&self) -> ShapeId {
+    self.id
+  }
+
+  /// The names of the fields, in slot order.
+  pub fn keys(&self) -> &[Ptr<Str>] {
+    &self.keys
+  }
+}
+
+impl Debug for Shape {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Shape")
+      .field("id", &self.id.0)
+      .field("keys", &self.keys)
+      .finish()
+  }
+}
@@ -2,37 +2,91 @@ use std::borrow::Borrow;
 use std::cell::Cell;
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
 use super::builtin::BuiltinMethod;
+use super::native::bind_builtin_method;
 use super::{Object, Ptr};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
-use crate::internal::vm::global::Global;
+use crate::internal::vm::global::{BuiltinKind, Global};
 use crate::public::Scope;
 use crate::Cow;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Str {
-  data: Cow<'static, str>,
+  repr: Repr,
+  /// Set for strings which were allocated by `Global::intern`. There is only
+  /// ever one interned string with given contents.
+  interned: bool,
+}
+
+/// Strings of up to this many bytes are stored inline.
+const INLINE_CAPACITY: usize = 23;
+
+enum Repr {
+  /// Most keys and identifiers are short, so they are stored in the object
+  /// itself instead of behind another pointer.
+  Inline {
+    len: u8,
+    buf: [u8; INLINE_CAPACITY],
+  },
+  Heap(Cow<'static, str>),
+}
+
+impl Repr {
+  fn new(data: Cow<'static, str>) -> Self {
+    if data.len() > INLINE_CAPACITY {
+      return Repr::Heap(data);
+    }
+    let mut buf = [0u8; INLINE_CAPACITY];
+    buf[..data.len()].copy_from_slice(data.as_bytes());
+    Repr::Inline {
+      len: data.len() as u8,
+      buf,
+    }
+  }
 }
 
 impl Str {
   #[allow(dead_code)] // symmetry with `owned`
   pub fn borrowed(data: &'static str) -> Self {
     Self {
-      data: Cow::borrowed(data),
+      repr: Repr::new(Cow::borrowed(data)),
+      interned: false,
     }
   }
 
   pub fn owned(data: impl ToString) -> Self {
     Self {
-      data: Cow::owned(data.to_string()),
+      repr: Repr::new(Cow::owned(data.to_string())),
+      interned: false,
+    }
+  }
+
+  /// Only `Global::intern` should use this.
+  pub(crate) fn interned(data: Cow<'static, str>) -> Self {
+    Self {
+      repr: Repr::new(data),
+      interned: true,
     }
   }
 
+  pub fn is_interned(&self) -> bool {
+    self.interned
+  }
+
+  /// Returns `true` if the string is stored inline.
+  pub fn is_inline(&self) -> bool {
+    matches!(self.repr, Repr::Inline { .. })
+  }
+
   pub fn as_str(&self) -> &str {
-    self.data.as_ref()
+    match &self.repr {
+      // SAFETY: `buf[..len]` was copied from a `str`
+      Repr::Inline { len, buf } => unsafe { std::str::from_utf8_unchecked(&buf[..*len as usize]) },
+      Repr::Heap(data) => data.as_ref(),
+    }
   }
 
   pub fn concat(&self, other: &str) -> Self {
@@ -83,7 +137,7 @@ impl Object for LinesIter {
     Ok(
       this
         .named_field_opt(scope, name.clone())?
-        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
     )
   }
 
@@ -96,11 +150,11 @@ impl Object for LinesIter {
       "iter" => builtin_method!(str_lines_iter),
       "next" => builtin_method!(str_lines_next),
       "done" => builtin_method!(str_lines_done),
-      _ => fail!("`{this}` has no field `{name}`"),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
     };
 
     Ok(Some(Value::object(unsafe {
-      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
     })))
   }
 }
@@ -168,7 +222,7 @@ impl Object for Str {
     Ok(
       this
         .named_field_opt(scope, name.clone())?
-        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
     )
   }
 
@@ -181,11 +235,16 @@ impl Object for Str {
       "len" => builtin_method!(str_len),
       "is_empty" => builtin_method!(str_is_empty),
       "lines" => builtin_method!(str_lines),
-      _ => fail!("`{this}` has no field `{name}`"),
+      _ => {
+        let method =
+          bind_builtin_method(&scope, BuiltinKind::Str, Value::object(this.clone()), &name)
+            .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?;
+        return Ok(Some(method));
+      }
     };
 
     Ok(Some(Value::object(unsafe {
-      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
     })))
   }
 
@@ -198,7 +257,7 @@ impl Object for Str {
   }
 
   fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
-    Ok(this.as_str() == other.as_str())
+    Ok(this == other)
   }
 }
 
@@ -217,13 +276,47 @@ declare_object_type!(Str);
 
 impl Display for Str {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    Display::fmt(&self.data, f)
+    Display::fmt(self.as_str(), f)
   }
 }
 
 impl Debug for Str {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    Debug::fmt(&self.data, f)
+    Debug::fmt(self.as_str(), f)
+  }
+}
+
+impl PartialEq for Str {
+  fn eq(&self, other: &Self) -> bool {
+    if std::ptr::eq(self, other) {
+      return true;
+    }
+    // two different interned strings never have the same contents
+    if self.interned && other.interned {
+      return false;
+    }
+    self.as_str() == other.as_str()
+  }
+}
+
+impl Eq for Str {}
+
+impl PartialOrd for Str {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Str {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+// Hashing is by contents, so that tables may still be queried with a `&str`.
+impl Hash for Str {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_str().hash(state)
   }
 }
 
@@ -231,19 +324,19 @@ impl Deref for Str {
   type Target = str;
 
   fn deref(&self) -> &Self::Target {
-    self.data.as_ref()
+    self.as_str()
   }
 }
 
 impl std::borrow::Borrow<str> for Str {
   fn borrow(&self) -> &str {
-    self.data.borrow()
+    self.as_str()
   }
 }
 
 impl AsRef<str> for Str {
   fn as_ref(&self) -> &str {
-    self.data.as_ref()
+    self.as_str()
   }
 }
 
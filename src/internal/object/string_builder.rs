@@ -0,0 +1,189 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A mutable buffer of text, which grows in place instead of copying the
+/// whole string on every append like `+` does.
+///
+/// Scripts create one with `string_builder()`, or `string_builder(str)` to
+/// start with some contents, and turn it into a string with `build`.
+///
+/// The emitter also uses it for loops which only ever append to a string
+/// variable with `+=`, see [`StringBuilder::implicit`].
+#[derive(Default)]
+pub struct StringBuilder {
+  data: RefCell<String>,
+  implicit: bool,
+}
+
+impl StringBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A builder which holds the value of a string variable while a loop
+  /// appends to it. These are created by `AppendStr`, and turned back into
+  /// strings by `BuildStr` once the loop ends, so scripts never see them.
+  ///
+  /// Builders created by scripts are left alone by both, so that `+` keeps
+  /// failing on them.
+  pub fn implicit(data: String) -> Self {
+    Self {
+      data: RefCell::new(data),
+      implicit: true,
+    }
+  }
+
+  pub fn is_implicit(&self) -> bool {
+    self.implicit
+  }
+
+  pub fn len(&self) -> usize {
+    self.data.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.borrow().is_empty()
+  }
+
+  pub fn push_str(&self, str: &str) {
+    self.data.borrow_mut().push_str(str);
+  }
+
+  pub fn clear(&self) {
+    self.data.borrow_mut().clear();
+  }
+
+  pub fn build(&self) -> Str {
+    Str::owned(self.data.borrow().as_str())
+  }
+}
+
+impl From<String> for StringBuilder {
+  fn from(data: String) -> Self {
+    Self {
+      data: RefCell::new(data),
+      implicit: false,
+    }
+  }
+}
+
+impl Display for StringBuilder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.data.borrow())
+  }
+}
+
+impl Debug for StringBuilder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("StringBuilder")
+      .field(&self.data.borrow())
+      .finish()
+  }
+}
+
+/// `push(value)` appends `value` the same way `to_str` would format it.
+fn string_builder_push(this: Ptr<StringBuilder>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  match value.clone().to_object::<Str>() {
+    Some(str) => this.push_str(str.as_str()),
+    None => this.push_str(&value.to_string()),
+  }
+  Ok(Value::object(this))
+}
+
+/// `push_str(str)` appends `str`, which must be a string.
+fn string_builder_push_str(this: Ptr<StringBuilder>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let Some(str) = value.clone().to_object::<Str>() else {
+    fail!(Type: "`push_str` expects a string, got `{value}`");
+  };
+  this.push_str(str.as_str());
+  Ok(Value::object(this))
+}
+
+fn string_builder_len(this: Ptr<StringBuilder>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.len() as i32))
+}
+
+fn string_builder_is_empty(this: Ptr<StringBuilder>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_empty()))
+}
+
+fn string_builder_clear(this: Ptr<StringBuilder>, _: Scope<'_>) -> Result<Value> {
+  this.clear();
+  Ok(Value::none())
+}
+
+fn string_builder_build(this: Ptr<StringBuilder>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(this.build())))
+}
+
+impl Object for StringBuilder {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "StringBuilder"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this:?}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "push" => builtin_method!(string_builder_push),
+      "push_str" => builtin_method!(string_builder_push_str),
+      "len" => builtin_method!(string_builder_len),
+      "is_empty" => builtin_method!(string_builder_is_empty),
+      "clear" => builtin_method!(string_builder_clear),
+      "build" | "to_str" => builtin_method!(string_builder_build),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(StringBuilder);
+
+/// `string_builder(initial="")`
+fn string_builder(scope: Scope<'_>) -> Result<Value> {
+  let builder = StringBuilder::new();
+  if scope.num_args() > 0 {
+    let value = scope.param::<public::Value>(0)?.unbind();
+    let Some(str) = value.clone().to_object::<Str>() else {
+      fail!(Type: "`string_builder` expects a string, got `{value}`");
+    };
+    builder.push_str(str.as_str());
+  }
+  Ok(Value::object(scope.alloc(builder)))
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, string_builder);
+  bind_builtin_type!(
+    global,
+    builtin_type!(StringBuilder {
+      push: builtin_method_static!(StringBuilder, string_builder_push),
+      push_str: builtin_method_static!(StringBuilder, string_builder_push_str),
+      len: builtin_method_static!(StringBuilder, string_builder_len),
+      is_empty: builtin_method_static!(StringBuilder, string_builder_is_empty),
+      clear: builtin_method_static!(StringBuilder, string_builder_clear),
+      build: builtin_method_static!(StringBuilder, string_builder_build)
+    })
+  );
+}
@@ -1,18 +1,214 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Debug, Display};
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 
 use indexmap::{Equivalent, IndexMap};
 
+use super::native::bind_builtin_method;
 use super::ptr::Ptr;
-use super::{Object, Str};
+use super::{Object, Str, Tuple};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
+use crate::internal::vm::global::BuiltinKind;
 use crate::public::Scope;
+use crate::util::{MAX_SAFE_INT, MIN_SAFE_INT};
 
+/// A map from hashable values to values.
+///
+/// Entries are kept in insertion order, so iteration and printing is
+/// deterministic. Overwriting an existing key does not change its position.
 #[derive(Default)]
 pub struct Table {
-  data: RefCell<IndexMap<Ptr<Str>, Value>>,
+  data: RefCell<IndexMap<Key, Value>>,
+}
+
+/// The hashable representation of a value, used as a `Table` key or stored
+/// in a `Set`.
+///
+/// Only values which are compared by value may be keys: `none`, bools,
+/// numbers, strings, and tuples of those. Floats with no fractional part are
+/// equal to the int with the same value, because `1 == 1.0`.
+#[derive(Clone)]
+pub enum Key {
+  None,
+  Bool(bool),
+  /// The number, and the value it was created from.
+  Int(i64, Value),
+  Float(u64),
+  Str(Ptr<Str>),
+  /// The tuple, and the keys of its items.
+  Tuple(Ptr<Tuple>, Box<[Key]>),
+}
+
+impl Key {
+  /// Returns `None` if `value` is not hashable.
+  pub fn new(value: &Value) -> Option<Self> {
+    if value.is_none() {
+      return Some(Key::None);
+    }
+    if value.is_bool() {
+      return Some(Key::Bool(unsafe { value.clone().to_bool_unchecked() }));
+    }
+    if let Some(v) = value.to_i64() {
+      return Some(Key::Int(v, value.clone()));
+    }
+    if value.is_float() {
+      let v = unsafe { value.clone().to_float_unchecked() };
+      if v.is_nan() {
+        return None;
+      }
+      if v.fract() == 0.0 && (MIN_SAFE_INT..=MAX_SAFE_INT).contains(&v) {
+        return Some(Key::Int(v as i64, value.clone()));
+      }
+      return Some(Key::Float(v.to_bits()));
+    }
+    if let Some(v) = value.clone().to_object::<Str>() {
+      return Some(Key::Str(v));
+    }
+    if let Some(v) = value.clone().to_object::<Tuple>() {
+      let items = v.as_slice().iter().map(Key::new).collect::<Option<_>>()?;
+      return Some(Key::Tuple(v, items));
+    }
+    None
+  }
+
+  pub fn to_value(&self) -> Value {
+    match self {
+      Key::None => Value::none(),
+      Key::Bool(v) => Value::bool(*v),
+      Key::Int(_, v) => v.clone(),
+      Key::Float(v) => Value::float(f64::from_bits(*v)),
+      Key::Str(v) => Value::object(v.clone()),
+      Key::Tuple(v, _) => Value::object(v.clone()),
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&Ptr<Str>> {
+    match self {
+      Key::Str(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  /// A hash which stays the same for the lifetime of the process, unlike the
+  /// hashes used by tables, which are seeded randomly. Equal keys have equal
+  /// hashes.
+  pub fn stable_hash(&self) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    self.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// Used to order keys of different types.
+  fn rank(&self) -> u8 {
+    match self {
+      Key::None => 0,
+      Key::Bool(_) => 1,
+      Key::Int(..) | Key::Float(_) => 2,
+      Key::Str(_) => 3,
+      Key::Tuple(..) => 4,
+    }
+  }
+}
+
+impl From<Ptr<Str>> for Key {
+  fn from(value: Ptr<Str>) -> Self {
+    Key::Str(value)
+  }
+}
+
+impl PartialEq for Key {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Key::None, Key::None) => true,
+      (Key::Bool(a), Key::Bool(b)) => a == b,
+      (Key::Int(a, _), Key::Int(b, _)) => a == b,
+      (Key::Float(a), Key::Float(b)) => a == b,
+      (Key::Str(a), Key::Str(b)) => a == b,
+      (Key::Tuple(_, a), Key::Tuple(_, b)) => a == b,
+      _ => false,
+    }
+  }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    match self {
+      // hashed exactly like `str`, so that tables may be indexed by `&str`
+      Key::Str(v) => v.as_str().hash(state),
+      Key::None => state.write_u8(0),
+      Key::Bool(v) => {
+        state.write_u8(1);
+        v.hash(state);
+      }
+      Key::Int(v, _) => {
+        state.write_u8(2);
+        v.hash(state);
+      }
+      Key::Float(v) => {
+        state.write_u8(3);
+        v.hash(state);
+      }
+      Key::Tuple(_, v) => {
+        state.write_u8(4);
+        v.hash(state);
+      }
+    }
+  }
+}
+
+impl PartialOrd for Key {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// Keys of different types are ordered by type, and numbers by value.
+impl Ord for Key {
+  fn cmp(&self, other: &Self) -> Ordering {
+    let number = |key: &Key| match key {
+      Key::Int(v, _) => *v as f64,
+      Key::Float(v) => f64::from_bits(*v),
+      _ => unreachable!(),
+    };
+    match (self, other) {
+      (Key::Bool(a), Key::Bool(b)) => a.cmp(b),
+      (Key::Int(a, _), Key::Int(b, _)) => a.cmp(b),
+      (Key::Str(a), Key::Str(b)) => a.as_str().cmp(b.as_str()),
+      (Key::Tuple(_, a), Key::Tuple(_, b)) => a.cmp(b),
+      _ if self.rank() == 2 && other.rank() == 2 => number(self).total_cmp(&number(other)),
+      _ => self.rank().cmp(&other.rank()),
+    }
+  }
+}
+
+impl Equivalent<Key> for str {
+  fn equivalent(&self, key: &Key) -> bool {
+    key.as_str().is_some_and(|key| key.as_str() == self)
+  }
+}
+
+impl Equivalent<Key> for Ptr<Str> {
+  fn equivalent(&self, key: &Key) -> bool {
+    key.as_str().is_some_and(|key| key == self)
+  }
+}
+
+impl Debug for Key {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Key::None => write!(f, "none"),
+      Key::Bool(v) => write!(f, "{v}"),
+      Key::Int(_, v) => write!(f, "{v}"),
+      Key::Float(v) => write!(f, "{}", f64::from_bits(*v)),
+      Key::Str(v) => Debug::fmt(v, f),
+      Key::Tuple(v, _) => Debug::fmt(v, f),
+    }
+  }
 }
 
 impl Table {
@@ -35,15 +231,15 @@ impl Table {
     self.data.borrow().is_empty()
   }
 
-  pub fn insert(&self, key: Ptr<Str>, value: Value) -> Option<Value> {
-    self.data.borrow_mut().insert(key, value)
+  pub fn insert(&self, key: impl Into<Key>, value: Value) -> Option<Value> {
+    self.data.borrow_mut().insert(key.into(), value)
   }
 
-  pub fn get<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K) -> Option<Value> {
+  pub fn get<K: Equivalent<Key> + ?Sized + Hash>(&self, key: &K) -> Option<Value> {
     self.data.borrow().get(key).cloned()
   }
 
-  pub fn set<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K, value: Value) -> bool {
+  pub fn set<K: Equivalent<Key> + ?Sized + Hash>(&self, key: &K, value: Value) -> bool {
     if let Some(slot) = self.data.borrow_mut().get_mut(key) {
       *slot = value;
       true
@@ -52,6 +248,45 @@ impl Table {
     }
   }
 
+  /// Like `get`, but also returns the index of the entry.
+  pub fn get_full<K: Equivalent<Key> + ?Sized + Hash>(&self, key: &K) -> Option<(usize, Value)> {
+    self
+      .data
+      .borrow()
+      .get_full(key)
+      .map(|(index, _, value)| (index, value.clone()))
+  }
+
+  /// Like `set`, but returns the index of the entry.
+  pub fn set_full<K: Equivalent<Key> + ?Sized + Hash>(
+    &self,
+    key: &K,
+    value: Value,
+  ) -> Option<usize> {
+    let mut data = self.data.borrow_mut();
+    let (index, _, slot) = data.get_full_mut(key)?;
+    *slot = value;
+    Some(index)
+  }
+
+  /// Returns the value at `index`, but only if its key is `key`.
+  pub fn get_index_if(&self, index: usize, key: &Ptr<Str>) -> Option<Value> {
+    let data = self.data.borrow();
+    let (k, value) = data.get_index(index)?;
+    (k.as_str() == Some(key)).then(|| value.clone())
+  }
+
+  /// Sets the value at `index`, but only if its key is `key`.
+  pub fn set_index_if(&self, index: usize, key: &Ptr<Str>, value: Value) -> bool {
+    match self.data.borrow_mut().get_index_mut(index) {
+      Some((k, slot)) if k.as_str() == Some(key) => {
+        *slot = value;
+        true
+      }
+      _ => false,
+    }
+  }
+
   pub fn get_index(&self, index: usize) -> Option<Value> {
     self
       .data
@@ -92,6 +327,21 @@ impl Table {
     }
   }
 
+  /// Sort the entries by key.
+  ///
+  /// Keys of different types are grouped by type: `none`, bools, numbers,
+  /// strings, then tuples.
+  ///
+  /// Keys inserted after this call are still appended to the end.
+  pub fn sort_keys(&self) {
+    self.data.borrow_mut().sort_keys()
+  }
+
+  /// Remove the entries for which `f` returns false.
+  pub fn retain(&self, mut f: impl FnMut(&Key) -> bool) {
+    self.data.borrow_mut().retain(|key, _| f(key))
+  }
+
   pub fn copy(&self) -> Self {
     Self {
       data: self.data.clone(),
@@ -105,7 +355,7 @@ pub struct Keys<'a> {
 }
 
 impl<'a> Iterator for Keys<'a> {
-  type Item = Ptr<Str>;
+  type Item = Key;
 
   fn next(&mut self) -> Option<Self::Item> {
     match self.table.data.borrow().get_index(self.index) {
@@ -143,7 +393,7 @@ pub struct Entries<'a> {
 }
 
 impl<'a> Iterator for Entries<'a> {
-  type Item = (Ptr<Str>, Value);
+  type Item = (Key, Value);
 
   fn next(&mut self) -> Option<Self::Item> {
     match self.table.data.borrow().get_index(self.index) {
@@ -172,6 +422,10 @@ impl Debug for Table {
   }
 }
 
+pub fn table_key(value: &Value) -> Result<Key> {
+  Ok(Key::new(value).ok_or_else(|| error!(Type: "`{value}` may not be used as a table key"))?)
+}
+
 impl Object for Table {
   fn type_name(_: Ptr<Self>) -> &'static str {
     "Table"
@@ -179,29 +433,38 @@ impl Object for Table {
 
   default_instance_of!();
 
+  // entries are only accessible through `[]`, so the only named fields are
+  // the methods registered by the host
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    match this.named_field_opt(scope, name)? {
+      Some(method) => Ok(method),
+      None => fail!(Type: "`Table` does not support field access"),
+    }
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    Ok(bind_builtin_method(
+      &scope,
+      BuiltinKind::Table,
+      Value::object(this),
+      &name,
+    ))
+  }
+
   fn keyed_field(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Value> {
-    let Some(key) = key.clone().to_object::<Str>() else {
-      fail!("`{key}` is not a string");
-    };
     let value = this
-      .get(key.as_str())
-      .ok_or_else(|| error!("`{this}` has no index `{key}`"))?;
+      .get(&table_key(&key)?)
+      .ok_or_else(|| error!(Index: "`{this}` has no index `{key}`"))?;
     Ok(value)
   }
 
   fn keyed_field_opt(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Option<Value>> {
-    let Some(key) = key.clone().to_object::<Str>() else {
-      fail!("`{key}` is not a string");
-    };
-    let value = this.get(key.as_str());
+    let value = this.get(&table_key(&key)?);
     Ok(value)
   }
 
   fn set_keyed_field(_: Scope<'_>, this: Ptr<Self>, key: Value, value: Value) -> Result<()> {
-    let Some(key) = key.clone().to_object::<Str>() else {
-      fail!("`{key}` is not a string");
-    };
-    this.insert(key, value);
+    this.insert(table_key(&key)?, value);
     Ok(())
   }
 
@@ -0,0 +1,232 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::list::to_index;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// An immutable sequence, created with `(a, b, c)`.
+///
+/// Because it cannot change, a tuple which only holds values that may be
+/// stored in a `Set` may itself be stored in a `Set`.
+pub struct Tuple {
+  items: Box<[Value]>,
+}
+
+impl Tuple {
+  pub fn len(&self) -> usize {
+    self.items.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+
+  pub fn get(&self, index: usize) -> Option<Value> {
+    self.items.get(index).cloned()
+  }
+
+  pub fn as_slice(&self) -> &[Value] {
+    &self.items
+  }
+}
+
+impl From<Vec<Value>> for Tuple {
+  fn from(items: Vec<Value>) -> Self {
+    Self {
+      items: items.into_boxed_slice(),
+    }
+  }
+}
+
+impl Display for Tuple {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<tuple>")
+  }
+}
+
+impl Debug for Tuple {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // `debug_tuple` leaves out the parentheses when there are no fields
+    if self.items.is_empty() {
+      return f.write_str("()");
+    }
+    let mut s = f.debug_tuple("");
+    for item in self.items.iter() {
+      s.field(item);
+    }
+    s.finish()
+  }
+}
+
+fn tuple_len(this: Ptr<Tuple>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.len() as i32))
+}
+
+fn tuple_is_empty(this: Ptr<Tuple>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_empty()))
+}
+
+fn tuple_get(this: Ptr<Tuple>, scope: Scope<'_>) -> Result<Value> {
+  let index = scope.param::<public::Value>(0)?.unbind();
+  let index = to_index(index, this.len())?;
+  Ok(this.get(index).unwrap_or_else(Value::none))
+}
+
+fn tuple_contains(this: Ptr<Tuple>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  Ok(Value::bool(this.contains(scope, value)?))
+}
+
+fn tuple_iter(this: Ptr<Tuple>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(TupleIter {
+    tuple: this,
+    index: Cell::new(0),
+  })))
+}
+
+impl Object for Tuple {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Tuple"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "len" => builtin_method!(tuple_len),
+      "is_empty" => builtin_method!(tuple_is_empty),
+      "get" => builtin_method!(tuple_get),
+      "contains" => builtin_method!(tuple_contains),
+      "iter" => builtin_method!(tuple_iter),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+
+  fn keyed_field(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Value> {
+    let len = this.len();
+    let index = to_index(key.clone(), len)?;
+    let value = this
+      .get(index)
+      .ok_or_else(|| error!(Index: "index `{key}` out of bounds, len was `{len}`"))?;
+    Ok(value)
+  }
+
+  fn keyed_field_opt(_: Scope<'_>, this: Ptr<Self>, key: Value) -> Result<Option<Value>> {
+    let len = this.len();
+    let index = to_index(key, len)?;
+    Ok(this.get(index))
+  }
+
+  fn contains(scope: Scope<'_>, this: Ptr<Self>, item: Value) -> Result<bool> {
+    for value in this.items.iter() {
+      if scope.are_equal(value.clone(), item.clone())? {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+
+  fn eq(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    if this.len() != other.len() {
+      return Ok(false);
+    }
+    for (lhs, rhs) in this.items.iter().zip(other.items.iter()) {
+      if !scope.are_equal(lhs.clone(), rhs.clone())? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  /// Tuples are compared item by item, and a shorter tuple is less than a
+  /// longer one which starts with the same items.
+  fn cmp(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    for (lhs, rhs) in this.items.iter().zip(other.items.iter()) {
+      match scope.compare(lhs.clone(), rhs.clone())? {
+        Ordering::Equal => continue,
+        ordering => return Ok(ordering),
+      }
+    }
+    Ok(this.len().cmp(&other.len()))
+  }
+}
+
+declare_object_type!(Tuple);
+
+#[derive(Debug)]
+pub struct TupleIter {
+  tuple: Ptr<Tuple>,
+  index: Cell<usize>,
+}
+
+impl Display for TupleIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<tuple iter>")
+  }
+}
+
+fn tuple_iter_iter(this: Ptr<TupleIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+fn tuple_iter_next(this: Ptr<TupleIter>, _: Scope<'_>) -> Result<Value> {
+  if let Some(item) = this.tuple.get(this.index.get()) {
+    this.index.set(this.index.get() + 1);
+    Ok(item)
+  } else {
+    Ok(Value::none())
+  }
+}
+
+fn tuple_iter_done(this: Ptr<TupleIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.index.get() >= this.tuple.len()))
+}
+
+impl Object for TupleIter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "TupleIter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!(Name: "`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_method!(tuple_iter_iter),
+      "next" => builtin_method!(tuple_iter_next),
+      "done" => builtin_method!(tuple_iter_done),
+      _ => fail!(Name: "`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), name, method))
+    })))
+  }
+}
+
+declare_object_type!(TupleIter);
@@ -1,9 +1,12 @@
+use std::cell::RefCell;
 use std::string::String as StdString;
 
 use serde::de::{DeserializeSeed, Visitor};
 use serde::ser::{Serialize, SerializeMap, SerializeSeq};
 
-use super::object::{List, Ptr, Str, Table};
+use super::object::class::ClassInstance;
+use super::object::int::BoxedInt;
+use super::object::{Bytes, Function, List, Ptr, Str, Table, Tuple};
 use super::value::Value;
 use super::vm::global::Global;
 use crate::util::{MAX_SAFE_INT, MIN_SAFE_INT};
@@ -36,7 +39,31 @@ impl Serialize for Value {
       } else if value.is::<List>() {
         let value = unsafe { value.cast_unchecked::<List>() };
         value.serialize(serializer)
+      } else if value.is::<Tuple>() {
+        let value = unsafe { value.cast_unchecked::<Tuple>() };
+        value.serialize(serializer)
+      } else if value.is::<BoxedInt>() {
+        let value = unsafe { value.cast_unchecked::<BoxedInt>() };
+        serializer.serialize_i64(value.value())
+      } else if value.is::<Bytes>() {
+        let value = unsafe { value.cast_unchecked::<Bytes>() };
+        let bytes = value.as_slice();
+        serializer.serialize_bytes(&bytes)
+      } else if value.is::<ClassInstance>() {
+        let value = unsafe { value.cast_unchecked::<ClassInstance>() };
+        value.serialize(serializer)
       } else {
+        #[cfg(feature = "bigint")]
+        if let Some(value) = value.clone_cast::<super::object::bigint::BigInt>() {
+          use num_traits::ToPrimitive;
+          return match value.value().to_i128() {
+            Some(value) => serializer.serialize_i128(value),
+            None => Err(serde::ser::Error::custom(format!(
+              "bigint `{value}` is too large to serialize"
+            ))),
+          };
+        }
+
         Err(serde::ser::Error::custom(format!(
           "cannot serialize `{value}`"
         )))
@@ -49,14 +76,64 @@ impl Serialize for Value {
   }
 }
 
+thread_local! {
+  /// The addresses of the containers which are being serialized, from the
+  /// outermost one in.
+  static SERIALIZING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a container as being serialized until it is dropped, so that
+/// serializing a container which contains itself fails instead of recursing
+/// until the stack overflows.
+struct Serializing;
+
+impl Serializing {
+  fn enter<T, E: serde::ser::Error>(object: &T) -> Result<Self, E> {
+    let addr = object as *const T as usize;
+    SERIALIZING.with(|stack| {
+      let mut stack = stack.borrow_mut();
+      if stack.contains(&addr) {
+        return Err(E::custom("cannot serialize a value that contains itself"));
+      }
+      stack.push(addr);
+      Ok(Serializing)
+    })
+  }
+}
+
+impl Drop for Serializing {
+  fn drop(&mut self) {
+    SERIALIZING.with(|stack| stack.borrow_mut().pop());
+  }
+}
+
 impl Serialize for Table {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
     S: serde::Serializer,
   {
+    let _guard = Serializing::enter(self)?;
     let mut map = serializer.serialize_map(Some(self.len()))?;
     for (key, value) in self.entries() {
-      map.serialize_entry(key.as_ref(), &value)?;
+      map.serialize_entry(&key.to_value(), &value)?;
+    }
+    map.end()
+  }
+}
+
+/// Serialized as a map of its fields. Methods are skipped.
+impl Serialize for ClassInstance {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    let _guard = Serializing::enter(self)?;
+    let mut map = serializer.serialize_map(None)?;
+    for (key, value) in self.fields.entries() {
+      if value.clone().to_object::<Function>().is_some() {
+        continue;
+      }
+      map.serialize_entry(&key.to_value(), &value)?;
     }
     map.end()
   }
@@ -67,6 +144,7 @@ impl Serialize for List {
   where
     S: serde::Serializer,
   {
+    let _guard = Serializing::enter(self)?;
     let mut list = serializer.serialize_seq(Some(self.len()))?;
     for value in self.iter() {
       list.serialize_element(&value)?;
@@ -75,6 +153,20 @@ impl Serialize for List {
   }
 }
 
+impl Serialize for Tuple {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    let _guard = Serializing::enter(self)?;
+    let mut tuple = serializer.serialize_seq(Some(self.len()))?;
+    for value in self.as_slice() {
+      tuple.serialize_element(value)?;
+    }
+    tuple.end()
+  }
+}
+
 impl Serialize for Str {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
@@ -459,4 +551,36 @@ mod tests {
 
     assert_eq!(value.to_float(), Some(5360574452_f64));
   }
+
+  #[test]
+  fn serialize_value() {
+    let mut hebi = crate::public::Hebi::new();
+    let value = hebi
+      .eval(
+        r#"
+class Point:
+  x = 1
+  y = 2
+  fn sum(self):
+    return self.x + self.y
+
+{ int: 5360574452, float: 0.5, str: "a", list: [none, true], point: Point() }
+"#,
+      )
+      .unwrap();
+
+    assert_eq!(
+      serde_json::to_string(&value).unwrap(),
+      r#"{"int":5360574452,"float":0.5,"str":"a","list":[null,true],"point":{"x":1,"y":2}}"#
+    );
+  }
+
+  #[test]
+  fn serialize_function_fails() {
+    let mut hebi = crate::public::Hebi::new();
+    let value = hebi.eval("fn f():\n  pass\nf").unwrap();
+
+    let e = serde_json::to_string(&value).unwrap_err();
+    assert!(e.to_string().contains("cannot serialize"), "{e}");
+  }
 }
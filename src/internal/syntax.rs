@@ -1,5 +1,10 @@
+#[cfg(feature = "analysis")]
+pub mod analysis;
 pub mod ast;
+pub mod fmt;
 pub mod lexer;
+#[cfg(feature = "analysis")]
+pub mod lint;
 pub mod parser;
 
 use std::error::Error as StdError;
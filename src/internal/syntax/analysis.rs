@@ -0,0 +1,461 @@
+//! Analysis of scripts without compiling or running them, for tools such as
+//! language servers.
+//!
+//! Names are resolved the same way as the emitter resolves them, so a
+//! reference points to the definition which the compiled script would use.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use super::{ast, parser};
+use crate::internal::vm::global::Global;
+use crate::span::{Span, SpannedError};
+
+/// The result of [`analyze`].
+#[derive(Debug)]
+pub struct Analysis<'src> {
+  /// The syntax tree of the script. Statements which failed to parse are
+  /// missing from it.
+  pub module: ast::Module<'src>,
+  pub symbols: SymbolTable,
+  /// Every syntax error in the script.
+  pub diagnostics: Vec<SpannedError>,
+}
+
+/// Parse `src` and resolve the names in it.
+pub fn analyze(src: &str) -> Analysis<'_> {
+  let (module, diagnostics) = parser::parse_recovering(Global::default(), src);
+  let symbols = Resolver::new().module(&module);
+  Analysis {
+    module,
+    symbols,
+    diagnostics,
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SymbolId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+  /// Declared with `name := value`.
+  Variable,
+  Function,
+  Class,
+  Parameter,
+  /// The item of a `for` loop.
+  LoopItem,
+  /// A module, or a symbol imported from one.
+  Import,
+  Field,
+  Method,
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+  pub name: String,
+  pub kind: SymbolKind,
+  /// The span of the name in the definition.
+  pub span: Span,
+  /// The function or class in which the symbol is defined.
+  pub container: Option<SymbolId>,
+}
+
+/// A use of a variable.
+#[derive(Clone, Debug)]
+pub struct Reference {
+  pub span: Span,
+  /// `None` if the name is not defined in the script, in which case it is
+  /// looked up in the globals when the script runs.
+  pub symbol: Option<SymbolId>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+  symbols: Vec<Symbol>,
+  references: Vec<Reference>,
+}
+
+impl SymbolTable {
+  pub fn get(&self, id: SymbolId) -> &Symbol {
+    &self.symbols[id.0]
+  }
+
+  /// Every symbol defined in the script.
+  pub fn symbols(&self) -> impl Iterator<Item = (SymbolId, &Symbol)> + '_ {
+    self
+      .symbols
+      .iter()
+      .enumerate()
+      .map(|(i, symbol)| (SymbolId(i), symbol))
+  }
+
+  pub fn references(&self) -> &[Reference] {
+    &self.references
+  }
+
+  pub fn references_to(&self, id: SymbolId) -> impl Iterator<Item = &Reference> + '_ {
+    self
+      .references
+      .iter()
+      .filter(move |reference| reference.symbol == Some(id))
+  }
+
+  /// The symbol which is defined or referenced at byte `offset`.
+  pub fn symbol_at(&self, offset: usize) -> Option<SymbolId> {
+    let contains = |span: Span| span.start <= offset && offset <= span.end;
+    self
+      .references
+      .iter()
+      .find(|reference| contains(reference.span))
+      .and_then(|reference| reference.symbol)
+      .or_else(|| {
+        self
+          .symbols()
+          .find(|(_, symbol)| contains(symbol.span))
+          .map(|(id, _)| id)
+      })
+  }
+}
+
+struct Resolver {
+  table: SymbolTable,
+  module_vars: HashMap<String, SymbolId>,
+  /// The module root, and the functions which are being resolved.
+  functions: Vec<Function>,
+  container: Option<SymbolId>,
+}
+
+#[derive(Default)]
+struct Function {
+  scope: usize,
+  locals: Vec<(usize, String, SymbolId)>,
+}
+
+impl Resolver {
+  fn new() -> Self {
+    Self {
+      table: SymbolTable::default(),
+      module_vars: HashMap::new(),
+      functions: vec![Function::default()],
+      container: None,
+    }
+  }
+
+  fn module(mut self, module: &ast::Module) -> SymbolTable {
+    self.enter_scope();
+    self.stmt_list(&module.body);
+    self.leave_scope();
+    self.table
+  }
+
+  fn current_function(&mut self) -> &mut Function {
+    self.functions.last_mut().unwrap()
+  }
+
+  fn is_global_scope(&self) -> bool {
+    self.functions.len() <= 1
+  }
+
+  fn enter_scope(&mut self) {
+    self.current_function().scope += 1;
+  }
+
+  fn leave_scope(&mut self) {
+    let function = self.current_function();
+    let scope = function.scope;
+    function.locals.retain(|(s, _, _)| *s != scope);
+    function.scope -= 1;
+  }
+
+  fn symbol(&mut self, name: &ast::Ident, kind: SymbolKind) -> SymbolId {
+    let id = SymbolId(self.table.symbols.len());
+    self.table.symbols.push(Symbol {
+      name: name.to_string(),
+      kind,
+      span: name.span,
+      container: self.container,
+    });
+    id
+  }
+
+  fn declare_local(&mut self, name: &ast::Ident, id: SymbolId) {
+    let function = self.current_function();
+    let scope = function.scope;
+    function.locals.push((scope, name.to_string(), id));
+  }
+
+  /// Declare a module variable at the top level, and a local elsewhere.
+  fn declare_var(&mut self, name: &ast::Ident, id: SymbolId) {
+    if self.is_global_scope() {
+      self.module_vars.insert(name.to_string(), id);
+    } else {
+      self.declare_local(name, id);
+    }
+  }
+
+  fn resolve(&self, name: &str) -> Option<SymbolId> {
+    // locals of the current function, then upvalues, then module variables
+    self
+      .functions
+      .iter()
+      .rev()
+      .find_map(|function| {
+        function
+          .locals
+          .iter()
+          .rev()
+          .find(|(_, local, _)| local == name)
+          .map(|(_, _, id)| *id)
+      })
+      .or_else(|| self.module_vars.get(name).copied())
+  }
+
+  fn reference(&mut self, name: &ast::Ident) {
+    let symbol = self.resolve(name.as_str());
+    self.table.references.push(Reference {
+      span: name.span,
+      symbol,
+    });
+  }
+
+  fn stmt_list(&mut self, list: &[ast::Stmt]) {
+    for stmt in list {
+      self.stmt(stmt);
+    }
+  }
+
+  fn block(&mut self, list: &[ast::Stmt]) {
+    self.enter_scope();
+    self.stmt_list(list);
+    self.leave_scope();
+  }
+
+  fn stmt(&mut self, stmt: &ast::Stmt) {
+    match stmt.deref() {
+      ast::StmtKind::Var(v) => {
+        self.expr(&v.value);
+        let id = self.symbol(&v.name, SymbolKind::Variable);
+        self.declare_var(&v.name, id);
+      }
+      ast::StmtKind::Unpack(v) => {
+        self.expr(&v.value);
+        for target in v.targets.iter() {
+          if v.decl {
+            let id = self.symbol(target, SymbolKind::Variable);
+            self.declare_var(target, id);
+          } else {
+            self.reference(target);
+          }
+        }
+      }
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.block(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.block(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => self.loop_(v),
+      ast::StmtKind::Ctrl(v) => match v.deref() {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Raise(v) => self.expr(&v.value),
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => {
+        for decorator in v.decorators.iter() {
+          self.expr(decorator);
+        }
+        let id = self.symbol(&v.name, SymbolKind::Function);
+        self.function(v, id);
+        self.declare_var(&v.name, id);
+      }
+      ast::StmtKind::Class(v) => self.class(v),
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Pass => {}
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Import(v) => match v.deref() {
+        ast::Import::Module { path, alias } => {
+          let name = alias.as_ref().unwrap_or(path.segments.last().unwrap());
+          let id = self.symbol(name, SymbolKind::Import);
+          self.declare_local(name, id);
+        }
+        ast::Import::Symbols { symbols, .. } => {
+          for symbol in symbols {
+            let name = symbol.alias.as_ref().unwrap_or(&symbol.name);
+            let id = self.symbol(name, SymbolKind::Import);
+            self.declare_local(name, id);
+          }
+        }
+      },
+      ast::StmtKind::Defer(v) => self.call(&v.call),
+    }
+  }
+
+  fn loop_(&mut self, stmt: &ast::Loop) {
+    self.enter_scope();
+    match stmt {
+      ast::Loop::For(v) => {
+        let items = v
+          .items
+          .iter()
+          .map(|item| (item, self.symbol(item, SymbolKind::LoopItem)))
+          .collect::<Vec<_>>();
+        match &v.iter {
+          // the item is declared before the range is evaluated
+          ast::ForIter::Range(range) => {
+            for (name, item) in items {
+              self.declare_local(name, item);
+            }
+            self.expr(&range.start);
+            self.expr(&range.end);
+          }
+          ast::ForIter::Expr(iter) => {
+            self.expr(iter);
+            for (name, item) in items {
+              self.declare_local(name, item);
+            }
+          }
+        }
+        self.block(&v.body);
+      }
+      ast::Loop::While(v) => {
+        self.expr(&v.cond);
+        self.block(&v.body);
+      }
+      ast::Loop::Infinite(v) => self.block(&v.body),
+    }
+    self.leave_scope();
+  }
+
+  fn function(&mut self, func: &ast::Func, id: SymbolId) {
+    let container = self.container.replace(id);
+    self.functions.push(Function::default());
+    self.enter_scope();
+
+    // plain functions may refer to themselves
+    if !func.params.has_self {
+      self.declare_local(&func.name, id);
+    }
+    // defaults are evaluated before the parameters are declared
+    for param in func.params.iter() {
+      if let Some(default) = &param.default {
+        self.expr(default);
+      }
+    }
+    for param in func.params.iter() {
+      let param_id = self.symbol(&param.name, SymbolKind::Parameter);
+      self.declare_local(&param.name, param_id);
+    }
+    self.stmt_list(&func.body);
+
+    self.leave_scope();
+    self.functions.pop();
+    self.container = container;
+  }
+
+  fn class(&mut self, class: &ast::Class) {
+    for decorator in class.decorators.iter() {
+      self.expr(decorator);
+    }
+    let id = self.symbol(&class.name, SymbolKind::Class);
+    let members = &class.members;
+
+    let container = self.container.replace(id);
+    for field in members.fields.iter().chain(members.static_fields.iter()) {
+      self.symbol(&field.name, SymbolKind::Field);
+    }
+    let methods = members
+      .init
+      .iter()
+      .chain(members.methods.iter())
+      .chain(members.static_methods.iter());
+    for method in methods {
+      let method_id = self.symbol(&method.name, SymbolKind::Method);
+      self.function(method, method_id);
+    }
+    self.container = container;
+
+    for name in class.parent.iter().chain(class.mixins.iter()) {
+      self.reference(name);
+    }
+    for field in members.fields.iter().chain(members.static_fields.iter()) {
+      self.expr(&field.default);
+    }
+
+    self.declare_var(&class.name, id);
+  }
+
+  fn call(&mut self, call: &ast::Call) {
+    self.expr(&call.target);
+    for arg in call.args.iter() {
+      self.expr(arg);
+    }
+    for (_, value) in call.kwargs.iter() {
+      self.expr(value);
+    }
+  }
+
+  fn expr(&mut self, expr: &ast::Expr) {
+    match expr.deref() {
+      ast::ExprKind::Literal(v) => match v.deref() {
+        ast::Literal::List(items) | ast::Literal::Tuple(items) => {
+          for item in items {
+            self.expr(item);
+          }
+        }
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries {
+            self.expr(key);
+            self.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        self.expr(&v.left);
+        self.expr(&v.right);
+      }
+      ast::ExprKind::Unary(v) => self.expr(&v.right),
+      ast::ExprKind::Conditional(v) => {
+        self.expr(&v.cond);
+        self.expr(&v.then);
+        self.expr(&v.otherwise);
+      }
+      ast::ExprKind::GetVar(v) => self.reference(&v.name),
+      ast::ExprKind::SetVar(v) => {
+        self.expr(&v.value);
+        self.reference(&v.target.name);
+      }
+      ast::ExprKind::GetField(v) => self.expr(&v.target),
+      ast::ExprKind::SetField(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        self.expr(&v.target);
+        self.expr(&v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.target.key);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::Call(v) => self.call(v),
+      ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    }
+  }
+}
+
+#[cfg(all(test, not(feature = "__miri")))]
+mod tests;
@@ -0,0 +1,126 @@
+use indoc::indoc;
+
+use super::*;
+
+/// The name and kind of the symbol which `offset` of `needle` refers to.
+fn symbol_at<'a>(analysis: &'a Analysis, src: &str, needle: &str) -> Option<&'a Symbol> {
+  let offset = src.find(needle).unwrap();
+  let id = analysis.symbols.symbol_at(offset)?;
+  Some(analysis.symbols.get(id))
+}
+
+#[test]
+fn resolve_locals_and_upvalues() {
+  let src = indoc! {r#"
+    v := 0
+    fn outer(a, b = v):
+      c := a
+      fn inner():
+        return c + b
+      return inner
+    print outer(undefined)
+  "#};
+  let analysis = analyze(src);
+  assert!(analysis.diagnostics.is_empty());
+
+  let c = symbol_at(&analysis, src, "c + b").unwrap();
+  assert_eq!((c.name.as_str(), c.kind), ("c", SymbolKind::Variable));
+  assert_eq!(c.span.start, src.find("c :=").unwrap());
+
+  let b = symbol_at(&analysis, src, "b\n").unwrap();
+  assert_eq!((b.name.as_str(), b.kind), ("b", SymbolKind::Parameter));
+  let outer = analysis.symbols.get(b.container.unwrap());
+  assert_eq!(outer.name, "outer");
+
+  let v = symbol_at(&analysis, src, "v):").unwrap();
+  assert_eq!(v.kind, SymbolKind::Variable);
+  assert_eq!(v.container, None);
+
+  let inner = symbol_at(&analysis, src, "inner\n").unwrap();
+  assert_eq!(inner.kind, SymbolKind::Function);
+
+  // not defined in the script
+  assert!(symbol_at(&analysis, src, "undefined").is_none());
+}
+
+#[test]
+fn resolve_shadowing_and_scopes() {
+  let src = indoc! {r#"
+    fn f():
+      x := 0
+      if true:
+        x := 1
+        print x
+      print x
+      for i in 0..10:
+        print i
+  "#};
+  let analysis = analyze(src);
+  let symbols = &analysis.symbols;
+  let defs = symbols
+    .symbols()
+    .filter(|(_, symbol)| symbol.name == "x")
+    .map(|(id, _)| id)
+    .collect::<Vec<_>>();
+  assert_eq!(defs.len(), 2);
+  assert_eq!(symbols.references_to(defs[0]).count(), 1);
+  assert_eq!(symbols.references_to(defs[1]).count(), 1);
+
+  let i = symbol_at(&analysis, src, "i\n").unwrap();
+  assert_eq!(i.kind, SymbolKind::LoopItem);
+}
+
+#[test]
+fn classes_and_imports() {
+  let src = indoc! {r#"
+    import m as n
+    from m import a, b as c
+    class T(n):
+      x = c
+      init(self):
+        self.x = a
+      fn get(self):
+        return self.x
+  "#};
+  let analysis = analyze(src);
+  assert!(analysis.diagnostics.is_empty());
+  let symbols = &analysis.symbols;
+  let kinds = symbols
+    .symbols()
+    .map(|(_, symbol)| (symbol.name.as_str(), symbol.kind))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    kinds,
+    [
+      ("n", SymbolKind::Import),
+      ("a", SymbolKind::Import),
+      ("c", SymbolKind::Import),
+      ("T", SymbolKind::Class),
+      ("x", SymbolKind::Field),
+      ("init", SymbolKind::Method),
+      ("get", SymbolKind::Method),
+    ]
+  );
+  assert_eq!(
+    symbol_at(&analysis, src, "c\n").unwrap().kind,
+    SymbolKind::Import
+  );
+  assert_eq!(symbol_at(&analysis, src, "a\n").unwrap().name, "a");
+  assert_eq!(symbol_at(&analysis, src, "n):").unwrap().name, "n");
+}
+
+#[test]
+fn syntax_errors() {
+  let src = indoc! {r#"
+    x := )
+    y := 1
+    fn f():
+      z := ]
+      return y
+  "#};
+  let analysis = analyze(src);
+  assert_eq!(analysis.diagnostics.len(), 2);
+  // the statements which parsed are still resolved
+  let y = symbol_at(&analysis, src, "y\n").unwrap();
+  assert_eq!(y.span.start, src.find("y :=").unwrap());
+}
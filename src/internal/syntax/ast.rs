@@ -4,6 +4,7 @@ use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
 
 use crate::span::{Span, Spanned};
+use crate::util::JoinIter;
 use crate::Cow;
 
 #[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -43,7 +44,7 @@ impl<'src> DerefMut for Ident<'src> {
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Module<'src> {
   pub body: Vec<Stmt<'src>>,
 }
@@ -62,9 +63,10 @@ impl<'src> Default for Module<'src> {
 
 pub type Stmt<'src> = Spanned<StmtKind<'src>>;
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub enum StmtKind<'src> {
   Var(Box<Var<'src>>),
+  Unpack(Box<Unpack<'src>>),
   If(Box<If<'src>>),
   Loop(Box<Loop<'src>>),
   Ctrl(Box<Ctrl<'src>>),
@@ -74,50 +76,107 @@ pub enum StmtKind<'src> {
   Pass,
   Print(Box<Print<'src>>),
   Import(Box<Import<'src>>),
+  Defer(Box<Defer<'src>>),
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub enum Import<'src> {
   Module {
-    path: Vec<Ident<'src>>,
+    path: ImportPath<'src>,
     alias: Option<Ident<'src>>,
   },
   Symbols {
-    path: Vec<Ident<'src>>,
+    path: ImportPath<'src>,
     symbols: Vec<ImportSymbol<'src>>,
   },
 }
 
-#[cfg_attr(test, derive(Debug))]
+/// A module path such as `a.b.c`, optionally prefixed by dots, which make it
+/// relative to the importing module.
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
+pub struct ImportPath<'src> {
+  /// The number of leading dots.
+  pub level: usize,
+  pub segments: Vec<Ident<'src>>,
+}
+
+impl<'src> ImportPath<'src> {
+  /// The path in the form expected by the `Import` instruction, e.g. `..a.b`.
+  pub fn to_path_string(&self) -> String {
+    format!(
+      "{}{}",
+      ".".repeat(self.level),
+      self.segments.iter().map(|s| s.as_ref()).join(".")
+    )
+  }
+}
+
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct ImportSymbol<'src> {
   pub name: Ident<'src>,
   pub alias: Option<Ident<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Func<'src> {
   pub name: Ident<'src>,
   pub params: Params<'src>,
   pub body: Vec<Stmt<'src>>,
   pub has_yield: bool,
+  /// The `@decorator` expressions above a `fn` statement, top to bottom.
+  pub decorators: Vec<Expr<'src>>,
+  /// The `##` doc comment above the function.
+  pub doc: Option<String>,
+}
+
+impl<'src> Func<'src> {
+  /// Whether the body of this function contains a `defer` statement,
+  /// not counting the ones in nested functions.
+  pub fn has_defer(&self) -> bool {
+    fn any_defer(body: &[Stmt]) -> bool {
+      body.iter().any(|stmt| match &**stmt {
+        StmtKind::Defer(_) => true,
+        StmtKind::If(v) => {
+          v.branches.iter().any(|branch| any_defer(&branch.body))
+            || v.default.as_deref().is_some_and(any_defer)
+        }
+        StmtKind::Loop(v) => match &**v {
+          Loop::For(v) => any_defer(&v.body),
+          Loop::While(v) => any_defer(&v.body),
+          Loop::Infinite(v) => any_defer(&v.body),
+        },
+        _ => false,
+      })
+    }
+    any_defer(&self.body)
+  }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Default)]
 pub struct Params<'src> {
   pub has_self: bool,
   pub pos: Vec<Param<'src>>,
+  /// The params after `*`, which may only be passed by name.
+  pub kw: Vec<Param<'src>>,
 }
 
 impl<'src> Params<'src> {
   pub fn contains(&self, param: &Ident<'src>) -> bool {
-    self.pos.iter().any(|v| v.name.as_ref() == param.as_ref())
+    self.iter().any(|v| v.name.as_ref() == param.as_ref())
+  }
+
+  /// The positional params, followed by the keyword-only ones.
+  pub fn iter(&self) -> impl Iterator<Item = &Param<'src>> + '_ {
+    self.pos.iter().chain(self.kw.iter())
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Param<'src> {
   pub name: Ident<'src>,
+  /// The type name in `name: type`.
+  pub hint: Option<Ident<'src>>,
   pub default: Option<Expr<'src>>,
 }
 
@@ -130,18 +189,27 @@ impl<'src> Param<'src> {
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Class<'src> {
   pub name: Ident<'src>,
   pub parent: Option<Ident<'src>>,
+  pub mixins: Vec<Ident<'src>>,
   pub members: ClassMembers<'src>,
+  /// The `@decorator` expressions above the `class` statement, top to bottom.
+  pub decorators: Vec<Expr<'src>>,
+  /// The `##` doc comment above the class.
+  pub doc: Option<String>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct ClassMembers<'src> {
   pub init: Option<Func<'src>>,
   pub fields: Vec<Field<'src>>,
   pub methods: Vec<Func<'src>>,
+  /// Fields declared with `static`, which are stored on the class.
+  pub static_fields: Vec<Field<'src>>,
+  /// Methods declared with `static fn`, which have no `self`.
+  pub static_methods: Vec<Func<'src>>,
 }
 
 impl<'src> ClassMembers<'src> {
@@ -151,13 +219,17 @@ impl<'src> ClassMembers<'src> {
       init: None,
       fields: vec![],
       methods: vec![],
+      static_fields: vec![],
+      static_methods: vec![],
     }
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Field<'src> {
   pub name: Ident<'src>,
+  /// The type name in `name: type`.
+  pub hint: Option<Ident<'src>>,
   pub default: Expr<'src>,
 }
 
@@ -167,27 +239,29 @@ impl<'src> Field<'src> {
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub enum Loop<'src> {
   For(For<'src>),
   While(While<'src>),
   Infinite(Infinite<'src>),
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct For<'src> {
-  pub item: Ident<'src>,
+  /// The loop variable, or the variables each item is unpacked into, as in
+  /// `for key, value in pairs`.
+  pub items: Vec<Ident<'src>>,
   pub iter: ForIter<'src>,
   pub body: Vec<Stmt<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub enum ForIter<'src> {
   Range(IterRange<'src>),
   Expr(Expr<'src>),
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct IterRange<'src> {
   pub start: Expr<'src>,
   pub end: Expr<'src>,
@@ -200,30 +274,38 @@ impl<'src> IterRange<'src> {
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct While<'src> {
   pub cond: Expr<'src>,
   pub body: Vec<Stmt<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Infinite<'src> {
   pub body: Vec<Stmt<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Print<'src> {
   pub values: Vec<Expr<'src>>,
 }
 
+/// A call which is made when the function containing it returns, or when an
+/// error unwinds through it.
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
+pub struct Defer<'src> {
+  pub call: Call<'src>,
+}
+
 pub type Expr<'src> = Spanned<ExprKind<'src>>;
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub enum ExprKind<'src> {
   Literal(Box<Literal<'src>>),
   Binary(Box<Binary<'src>>),
   Unary(Box<Unary<'src>>),
+  Conditional(Box<Conditional<'src>>),
   GetVar(Box<GetVar<'src>>),
   SetVar(Box<SetVar<'src>>),
   GetField(Box<GetField<'src>>),
@@ -235,19 +317,23 @@ pub enum ExprKind<'src> {
   GetSuper,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub enum Literal<'src> {
   None,
-  Int(i32),
+  Int(i64),
+  /// Decimal digits of a `123n` literal, without the suffix.
+  BigInt(Cow<'src, str>),
   Float(f64),
   Bool(bool),
   String(Cow<'src, str>),
+  Bytes(Vec<u8>),
   List(Vec<Expr<'src>>),
+  Tuple(Vec<Expr<'src>>),
   Table(Vec<(Expr<'src>, Expr<'src>)>),
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct Binary<'src> {
   pub op: BinaryOp,
@@ -260,6 +346,7 @@ pub enum BinaryOp {
   Add,
   Sub,
   Div,
+  FloorDiv,
   Mul,
   Rem,
   Pow,
@@ -276,14 +363,23 @@ pub enum BinaryOp {
   In,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct Unary<'src> {
   pub op: UnaryOp,
   pub right: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+/// `<then> if <cond> else <otherwise>`
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
+#[derive(Clone)]
+pub struct Conditional<'src> {
+  pub cond: Expr<'src>,
+  pub then: Expr<'src>,
+  pub otherwise: Expr<'src>,
+}
+
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone, Copy)]
 pub enum UnaryOp {
   Plus,
@@ -292,53 +388,62 @@ pub enum UnaryOp {
   Opt,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct GetVar<'src> {
   pub name: Ident<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct SetVar<'src> {
   pub target: GetVar<'src>,
   pub value: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct GetField<'src> {
   pub target: Expr<'src>,
   pub name: Ident<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct SetField<'src> {
   pub target: GetField<'src>,
+  /// The operator of a compound assignment such as `a.b += c`.
+  ///
+  /// Unlike compound assignments to variables, these are not desugared,
+  /// because the object must only be evaluated once.
+  pub op: Option<AssignOp>,
   pub value: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct GetIndex<'src> {
   pub target: Expr<'src>,
   pub key: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct SetIndex<'src> {
   pub target: GetIndex<'src>,
+  /// The operator of a compound assignment such as `a[b] += c`, see
+  /// [`SetField::op`].
+  pub op: Option<AssignOp>,
   pub value: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone, Copy)]
 pub enum AssignOp {
   Add,
   Sub,
   Div,
+  FloorDiv,
   Mul,
   Rem,
   Pow,
@@ -351,6 +456,7 @@ impl From<AssignOp> for BinaryOp {
       AssignOp::Add => BinaryOp::Add,
       AssignOp::Sub => BinaryOp::Sub,
       AssignOp::Div => BinaryOp::Div,
+      AssignOp::FloorDiv => BinaryOp::FloorDiv,
       AssignOp::Mul => BinaryOp::Mul,
       AssignOp::Rem => BinaryOp::Rem,
       AssignOp::Pow => BinaryOp::Pow,
@@ -365,54 +471,73 @@ pub enum AssignKind {
   Decl,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct Yield<'src> {
   pub value: Option<Expr<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct Return<'src> {
   pub value: Option<Expr<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
+#[derive(Clone)]
+pub struct Raise<'src> {
+  pub value: Expr<'src>,
+}
+
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 #[derive(Clone)]
 pub struct Call<'src> {
   pub target: Expr<'src>,
   pub args: Vec<Expr<'src>>,
+  /// The `name=value` arguments, which always come after `args`.
+  pub kwargs: Vec<(Ident<'src>, Expr<'src>)>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Var<'src> {
   pub name: Ident<'src>,
   pub value: Expr<'src>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+/// `a, b := value` or `a, b = value`, which assign the items of a tuple or
+/// list to the variables, in order.
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
+pub struct Unpack<'src> {
+  pub targets: Vec<Ident<'src>>,
+  /// `true` for `:=`, which declares the variables.
+  pub decl: bool,
+  pub value: Expr<'src>,
+}
+
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct If<'src> {
   pub branches: Vec<Branch<'src>>,
   pub default: Option<Vec<Stmt<'src>>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub struct Branch<'src> {
   pub cond: Expr<'src>,
   pub body: Vec<Stmt<'src>>,
 }
 
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "analysis"), derive(Debug))]
 pub enum Ctrl<'src> {
   Return(Return<'src>),
   Yield(Yield<'src>),
+  Raise(Raise<'src>),
   Continue,
   Break,
 }
 
 pub fn import_module_stmt<'src>(
   s: impl Into<Span>,
-  path: Vec<Ident<'src>>,
+  path: ImportPath<'src>,
   alias: Option<Ident<'src>>,
 ) -> Stmt<'src> {
   Stmt::new(
@@ -423,7 +548,7 @@ pub fn import_module_stmt<'src>(
 
 pub fn import_symbols_stmt<'src>(
   s: impl Into<Span>,
-  path: Vec<Ident<'src>>,
+  path: ImportPath<'src>,
   symbols: Vec<ImportSymbol<'src>>,
 ) -> Stmt<'src> {
   Stmt::new(
@@ -455,6 +580,10 @@ pub fn yield_stmt(inner: Spanned<Yield>) -> Stmt {
   )
 }
 
+pub fn raise_stmt(s: impl Into<Span>, value: Expr) -> Stmt {
+  Stmt::new(s, StmtKind::Ctrl(Box::new(Ctrl::Raise(Raise { value }))))
+}
+
 pub fn continue_stmt<'src>(s: impl Into<Span>) -> Stmt<'src> {
   Stmt::new(s, StmtKind::Ctrl(Box::new(Ctrl::Continue)))
 }
@@ -467,6 +596,10 @@ pub fn pass_stmt<'src>(s: impl Into<Span>) -> Stmt<'src> {
   Stmt::new(s, StmtKind::Pass)
 }
 
+pub fn defer_stmt(s: impl Into<Span>, call: Call) -> Stmt {
+  Stmt::new(s, StmtKind::Defer(Box::new(Defer { call })))
+}
+
 pub fn print_stmt(s: impl Into<Span>, values: Vec<Expr>) -> Stmt {
   Stmt::new(s, StmtKind::Print(Box::new(Print { values })))
 }
@@ -484,12 +617,36 @@ pub fn expr_unary(s: impl Into<Span>, op: UnaryOp, right: Expr) -> Expr {
   Expr::new(s, ExprKind::Unary(Box::new(Unary { op, right })))
 }
 
+pub fn expr_conditional<'src>(
+  s: impl Into<Span>,
+  cond: Expr<'src>,
+  then: Expr<'src>,
+  otherwise: Expr<'src>,
+) -> Expr<'src> {
+  Expr::new(
+    s,
+    ExprKind::Conditional(Box::new(Conditional {
+      cond,
+      then,
+      otherwise,
+    })),
+  )
+}
+
 pub fn expr_call<'src>(
   s: impl Into<Span>,
   target: Expr<'src>,
   args: Vec<Expr<'src>>,
+  kwargs: Vec<(Ident<'src>, Expr<'src>)>,
 ) -> Expr<'src> {
-  Expr::new(s, ExprKind::Call(Box::new(Call { target, args })))
+  Expr::new(
+    s,
+    ExprKind::Call(Box::new(Call {
+      target,
+      args,
+      kwargs,
+    })),
+  )
 }
 
 pub fn expr_get_field<'src>(
@@ -508,6 +665,10 @@ pub fn expr_list(s: impl Into<Span>, items: Vec<Expr>) -> Expr {
   Expr::new(s, ExprKind::Literal(Box::new(Literal::List(items))))
 }
 
+pub fn expr_tuple(s: impl Into<Span>, items: Vec<Expr>) -> Expr {
+  Expr::new(s, ExprKind::Literal(Box::new(Literal::Tuple(items))))
+}
+
 pub fn ident_key(v: Ident) -> Expr {
   Expr::new(
     v.span,
@@ -557,6 +718,8 @@ pub fn func<'src>(
     params,
     body,
     has_yield,
+    decorators: vec![],
+    doc: None,
   }
 }
 
@@ -564,6 +727,7 @@ pub fn class_stmt<'src>(
   s: impl Into<Span>,
   name: Ident<'src>,
   parent: Option<Ident<'src>>,
+  mixins: Vec<Ident<'src>>,
   members: ClassMembers<'src>,
 ) -> Stmt<'src> {
   Stmt::new(
@@ -571,7 +735,10 @@ pub fn class_stmt<'src>(
     StmtKind::Class(Box::new(Class {
       name,
       parent,
+      mixins,
       members,
+      decorators: vec![],
+      doc: None,
     })),
   )
 }
@@ -589,16 +756,18 @@ pub fn assign<'src>(target: Expr<'src>, kind: AssignKind, value: Expr<'src>) ->
     AssignKind::Op(op) => {
       let assign = match target.into_inner() {
         ExprKind::GetVar(target) => ExprKind::SetVar(Box::new(SetVar {
-          value: desugar_assign(span, &*target, op, value),
+          value: desugar_assign(span, &target, op, value),
           target: *target,
         })),
         ExprKind::GetField(target) => ExprKind::SetField(Box::new(SetField {
-          value: desugar_assign(span, &*target, op, value),
           target: *target,
+          op,
+          value,
         })),
         ExprKind::GetIndex(target) => ExprKind::SetIndex(Box::new(SetIndex {
-          value: desugar_assign(span, &*target, op, value),
           target: *target,
+          op,
+          value,
         })),
         _ => return None,
       };
@@ -607,46 +776,55 @@ pub fn assign<'src>(target: Expr<'src>, kind: AssignKind, value: Expr<'src>) ->
   }
 }
 
-fn desugar_assign<'src, T>(
+/// Returns `None` if any of the targets is not a variable, or if `kind` is a
+/// compound assignment.
+pub fn unpack<'src>(
+  targets: Vec<Expr<'src>>,
+  kind: AssignKind,
+  value: Expr<'src>,
+) -> Option<Stmt<'src>> {
+  let span = Span::from(targets[0].span.start..value.span.end);
+  let decl = match kind {
+    AssignKind::Decl => true,
+    AssignKind::Op(None) => false,
+    AssignKind::Op(Some(_)) => return None,
+  };
+  let targets = targets
+    .into_iter()
+    .map(|target| match target.into_inner() {
+      ExprKind::GetVar(target) => Some(target.name),
+      _ => None,
+    })
+    .collect::<Option<Vec<_>>>()?;
+  Some(Stmt::new(
+    span,
+    StmtKind::Unpack(Box::new(Unpack {
+      targets,
+      decl,
+      value,
+    })),
+  ))
+}
+
+/// `a += b` is desugared to `a = a + b`.
+fn desugar_assign<'src>(
   span: impl Into<Span>,
-  target: &T,
+  target: &GetVar<'src>,
   op: Option<AssignOp>,
   value: Expr<'src>,
-) -> Expr<'src>
-where
-  T: Clone,
-  ExprKind<'src>: From<T>,
-{
+) -> Expr<'src> {
   let span = span.into();
   match op {
     Some(op) => expr_binary(
       span,
       op.into(),
-      Expr::new(span, ExprKind::from(target.clone())),
+      Expr::new(span, ExprKind::GetVar(Box::new(target.clone()))),
       value,
     ),
     None => value,
   }
 }
 
-impl<'src> From<GetVar<'src>> for ExprKind<'src> {
-  fn from(value: GetVar<'src>) -> Self {
-    ExprKind::GetVar(Box::new(value))
-  }
-}
-
-impl<'src> From<GetField<'src>> for ExprKind<'src> {
-  fn from(value: GetField<'src>) -> Self {
-    ExprKind::GetField(Box::new(value))
-  }
-}
-
-impl<'src> From<GetIndex<'src>> for ExprKind<'src> {
-  fn from(value: GetIndex<'src>) -> Self {
-    ExprKind::GetIndex(Box::new(value))
-  }
-}
-
 pub fn loop_stmt(s: impl Into<Span>, body: Vec<Stmt>) -> Stmt {
   Stmt::new(
     s,
@@ -667,13 +845,13 @@ pub fn while_loop_stmt<'src>(
 
 pub fn for_loop_stmt<'src>(
   s: impl Into<Span>,
-  item: Ident<'src>,
+  items: Vec<Ident<'src>>,
   iter: ForIter<'src>,
   body: Vec<Stmt<'src>>,
 ) -> Stmt<'src> {
   Stmt::new(
     s,
-    StmtKind::Loop(Box::new(Loop::For(For { item, iter, body }))),
+    StmtKind::Loop(Box::new(Loop::For(For { items, iter, body }))),
   )
 }
 
@@ -698,21 +876,33 @@ pub mod lit {
 
   pub fn int<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
     let s = s.into();
-    let value = lexeme
+    let value = without_underscores(lexeme)
       .parse::<i64>()
       .map_err(|e| SpannedError::new(format!("invalid number {e}"), s))?;
-    let lit = if value < (i32::MIN as i64) || (i32::MAX as i64) < value {
-      // TODO: bigint?
-      Literal::Float(value as f64)
-    } else {
-      Literal::Int(value as i32)
-    };
-    Ok(Expr::new(s, ExprKind::Literal(Box::new(lit))))
+    Ok(Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::Int(value))),
+    ))
+  }
+
+  pub fn bigint<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
+    let s = s.into();
+    if cfg!(not(feature = "bigint")) {
+      return Err(SpannedError::new(
+        "bigint literals require the `bigint` feature",
+        s,
+      ));
+    }
+    let digits = lexeme.strip_suffix('n').unwrap_or(lexeme);
+    Ok(Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::BigInt(without_underscores(digits)))),
+    ))
   }
 
   pub fn float<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
     let s = s.into();
-    let value = lexeme
+    let value = without_underscores(lexeme)
       .parse()
       .map_err(|e| SpannedError::new(format!("invalid number {e}"), s))?;
     Ok(Expr::new(
@@ -721,91 +911,205 @@ pub mod lit {
     ))
   }
 
-  pub fn str<'src>(s: impl Into<Span>, lexeme: &'src str) -> Option<Expr<'src>> {
+  pub fn str<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
     let s = s.into();
-    let lexeme = lexeme.strip_prefix('"').unwrap_or(lexeme);
-    let lexeme = lexeme.strip_suffix('"').unwrap_or(lexeme);
-    let mut lexeme = lexeme.to_string();
-    unescape_in_place(&mut lexeme)?;
-    Some(Expr::new(
+    let contents = &lexeme[1..lexeme.len() - 1];
+    let value = match contents.contains('\\') {
+      // `\xNN` only produces a raw byte in bytes literals, so this is utf-8
+      true => Cow::owned(String::from_utf8(unescape(s.start + 1, contents, false)?).unwrap()),
+      false => Cow::borrowed(contents),
+    };
+    Ok(Expr::new(
       s,
-      ExprKind::Literal(Box::new(Literal::String(Cow::from(lexeme)))),
+      ExprKind::Literal(Box::new(Literal::String(value))),
     ))
   }
 
-  // Adapted from https://docs.rs/snailquote/0.3.0/x86_64-pc-windows-msvc/src/snailquote/lib.rs.html.
-  /// Unescapes the given string in-place. Returns `None` if the string contains
-  /// an invalid escape sequence.
-  fn unescape_in_place(s: &mut String) -> Option<()> {
-    let mut out = String::with_capacity(s.len());
-    let mut chars = s.chars();
-    while let Some(ch) = chars.next() {
-      if ch == '\\' {
-        if let Some(next) = chars.next() {
-          let escape = match next {
-            'a' => Some('\u{07}'),
-            'b' => Some('\u{08}'),
-            'v' => Some('\u{0B}'),
-            'f' => Some('\u{0C}'),
-            'n' => Some('\n'),
-            'r' => Some('\r'),
-            't' => Some('\t'),
-            '\'' => Some('\''),
-            '"' => Some('"'),
-            '\\' => Some('\\'),
-            'e' | 'E' => Some('\u{1B}'),
-            'x' => Some(parse_hex_code(&mut chars)?),
-            'u' => Some(parse_unicode(&mut chars)?),
-            _ => None,
-          };
-          match escape {
-            Some(esc) => {
-              out.push(esc);
-            }
-            None => {
-              out.push(ch);
-              out.push(next);
-            }
-          }
-        }
-      } else {
-        out.push(ch);
+  /// `"""..."""`, which may span multiple lines.
+  ///
+  /// A line break right after the opening quotes is not a part of the
+  /// string, and neither is the indentation which all of the following lines
+  /// have in common, so the string may be indented along with the code around
+  /// it. If the closing quotes are on their own line, their indentation counts
+  /// too, and the string ends with a line break.
+  pub fn multiline_str<'src>(
+    s: impl Into<Span>,
+    lexeme: &'src str,
+  ) -> Result<Expr<'src>, SpannedError> {
+    let s = s.into();
+    let mut offset = s.start + 3;
+    let mut contents = &lexeme[3..lexeme.len() - 3];
+    let mut first_line = true;
+    if let Some(end) = contents.find('\n') {
+      if contents[..end].trim().is_empty() {
+        offset += end + 1;
+        contents = &contents[end + 1..];
+        first_line = false;
+      }
+    }
+
+    let mut lines = vec![];
+    let mut start = 0;
+    for line in contents.split('\n') {
+      lines.push((offset + start, line.strip_suffix('\r').unwrap_or(line)));
+      start += line.len() + 1;
+    }
+    let indent_of = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let last = lines.len() - 1;
+    let indent = lines
+      .iter()
+      .enumerate()
+      .skip(usize::from(first_line))
+      .filter(|(i, (_, line))| *i == last || !line.trim().is_empty())
+      .map(|(_, (_, line))| indent_of(line))
+      .min()
+      .unwrap_or(0);
+
+    let mut value = Vec::with_capacity(contents.len());
+    for (i, (start, line)) in lines.into_iter().enumerate() {
+      if i > 0 {
+        value.push(b'\n');
+      }
+      if i == last && i > 0 && line.trim().is_empty() {
+        break;
       }
+      let strip = match first_line && i == 0 {
+        true => 0,
+        false => indent.min(indent_of(line)),
+      };
+      value.extend(unescape(start + strip, &line[strip..], false)?);
+    }
+    Ok(Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::String(Cow::owned(
+        String::from_utf8(value).unwrap(),
+      )))),
+    ))
+  }
+
+  /// `r"..."`, in which a `\` is just a `\`.
+  pub fn raw_str<'src>(s: impl Into<Span>, lexeme: &'src str) -> Expr<'src> {
+    let s = s.into();
+    let contents = &lexeme[2..lexeme.len() - 1];
+    Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::String(Cow::borrowed(contents)))),
+    )
+  }
+
+  pub fn bytes<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
+    let s = s.into();
+    let contents = &lexeme[2..lexeme.len() - 1];
+    let bytes = unescape(s.start + 2, contents, true)?;
+    Ok(Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::Bytes(bytes))),
+    ))
+  }
+
+  /// Number literals may separate their digits with `_`, which the lexer
+  /// already checked are only between digits.
+  fn without_underscores(lexeme: &str) -> Cow<'_, str> {
+    if lexeme.contains('_') {
+      Cow::from(lexeme.replace('_', ""))
+    } else {
+      Cow::from(lexeme)
     }
-    *s = out;
-    Some(())
-  }
-
-  fn parse_hex_code<I>(chars: &mut I) -> Option<char>
-  where
-    I: Iterator<Item = char>,
-  {
-    let digits = [
-      u8::try_from(chars.next()?).ok()?,
-      u8::try_from(chars.next()?).ok()?,
-    ];
-    let digits = std::str::from_utf8(&digits[..]).ok()?;
-    let c = u32::from_str_radix(digits, 16).ok()?;
-    char::from_u32(c)
-  }
-
-  // Adapted from https://docs.rs/snailquote/0.3.0/x86_64-pc-windows-msvc/src/snailquote/lib.rs.html.
-  fn parse_unicode<I>(chars: &mut I) -> Option<char>
-  where
-    I: Iterator<Item = char>,
-  {
-    match chars.next() {
-      Some('{') => {}
-      _ => {
-        return None;
+  }
+
+  /// Replace the escape sequences in the contents of a string literal, which
+  /// start at `offset` in the source.
+  ///
+  /// In a bytes literal, `\xNN` produces a raw byte instead of a `char`, so it
+  /// may be used to write non-utf-8 data.
+  fn unescape(offset: usize, s: &str, bytes: bool) -> Result<Vec<u8>, SpannedError> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('\\') {
+      out.extend_from_slice(&rest.as_bytes()[..i]);
+      let start = offset + (s.len() - rest.len()) + i;
+      let sequence = &rest[i + 1..];
+      let (escape, len) = escape(sequence, bytes)
+        .map_err(|(message, len)| SpannedError::new(message, start..start + 1 + len))?;
+      match escape {
+        Escape::Char(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        Escape::Byte(b) => out.push(b),
       }
+      rest = &sequence[len..];
     }
+    out.extend_from_slice(rest.as_bytes());
+    Ok(out)
+  }
 
-    let unicode_seq: String = chars.take_while(|&c| c != '}').collect();
+  enum Escape {
+    Char(char),
+    Byte(u8),
+  }
 
-    u32::from_str_radix(&unicode_seq, 16)
-      .ok()
-      .and_then(char::from_u32)
+  /// Parse the escape sequence which follows a `\` at the start of `s`, and
+  /// return it along with its length. An error holds the message, and the
+  /// length of the invalid part of the sequence.
+  fn escape(s: &str, bytes: bool) -> Result<(Escape, usize), (String, usize)> {
+    // the lexer only produces string literals in which a `\` is followed by
+    // another character
+    let c = s.chars().next().unwrap_or_default();
+    let escape = match c {
+      'n' => '\n',
+      'r' => '\r',
+      't' => '\t',
+      '0' => '\0',
+      'a' => '\u{07}',
+      'b' => '\u{08}',
+      'v' => '\u{0B}',
+      'f' => '\u{0C}',
+      'e' | 'E' => '\u{1B}',
+      '\'' => '\'',
+      '"' => '"',
+      '\\' => '\\',
+      'x' => {
+        let digits = s[1..]
+          .get(..2)
+          .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()));
+        let Some(digits) = digits else {
+          let len = 1 + s[1..].chars().take(2).map(char::len_utf8).sum::<usize>();
+          return Err((
+            "invalid hex escape, expected two hex digits, as in `\\x7f`".into(),
+            len,
+          ));
+        };
+        let value = u8::from_str_radix(digits, 16).unwrap();
+        return match bytes {
+          true => Ok((Escape::Byte(value), 3)),
+          false => Ok((Escape::Char(char::from(value)), 3)),
+        };
+      }
+      'u' => {
+        let invalid = |len| {
+          Err((
+            "invalid unicode escape, expected 1 to 6 hex digits in braces, as in `\\u{1F600}`"
+              .into(),
+            len,
+          ))
+        };
+        let Some(rest) = s[1..].strip_prefix('{') else {
+          return invalid(1);
+        };
+        let Some(end) = rest.find('}') else {
+          return invalid(2);
+        };
+        let digits = &rest[..end];
+        let len = end + 3;
+        if digits.is_empty() || digits.len() > 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+          return invalid(len);
+        }
+        return match char::from_u32(u32::from_str_radix(digits, 16).unwrap()) {
+          Some(c) => Ok((Escape::Char(c), len)),
+          None => Err((format!("`\\u{{{digits}}}` is not a unicode character"), len)),
+        };
+      }
+      c => return Err((format!("unknown escape sequence `\\{c}`"), c.len_utf8())),
+    };
+    Ok((Escape::Char(escape), 1))
   }
 }
 
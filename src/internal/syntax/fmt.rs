@@ -0,0 +1,627 @@
+//! Printing of the syntax tree back to source code in a canonical style.
+//!
+//! Comments are not a part of the syntax tree, so they are collected from the
+//! source by a separate pass over it. A comment is printed on its own line
+//! before the statement which follows it, unless it is at the end of the line
+//! of a simple statement, where it stays.
+
+use std::fmt::Write;
+use std::ops::Deref;
+
+use logos::Logos;
+
+use super::ast;
+use super::lexer::TokenKind;
+use super::parser;
+use super::SyntaxError;
+use crate::internal::vm::global::Global;
+use crate::span::Span;
+
+const INDENT: &str = "  ";
+
+/// Format `src`, or fail if it contains syntax errors.
+pub fn format(src: &str) -> Result<String, SyntaxError> {
+  let module = parser::parse(Global::default(), src)?;
+  let mut printer = Printer::new(src);
+  printer.stmt_list(&module.body);
+  Ok(printer.finish())
+}
+
+struct Printer<'src> {
+  src: &'src str,
+  comments: Vec<Span>,
+  next_comment: usize,
+  out: String,
+  indent: usize,
+  /// The end of the last comment or statement which was printed.
+  last_end: usize,
+  /// Set at the start of a block, where blank lines are not preserved.
+  block_start: bool,
+}
+
+impl<'src> Printer<'src> {
+  fn new(src: &'src str) -> Self {
+    let comments = TokenKind::lexer(src)
+      .spanned()
+      .filter(|(token, _)| matches!(token, Ok(TokenKind::_Tok_Comment)))
+      .map(|(_, span)| Span::from(span))
+      .collect();
+    Self {
+      src,
+      comments,
+      next_comment: 0,
+      out: String::new(),
+      indent: 0,
+      last_end: 0,
+      block_start: true,
+    }
+  }
+
+  fn finish(mut self) -> String {
+    self.comments_before(self.src.len());
+    self.out
+  }
+
+  fn comment(&self, span: Span) -> &'src str {
+    self.src[span.range()].trim_end()
+  }
+
+  /// Print the comments which start before `offset` on their own lines.
+  fn comments_before(&mut self, offset: usize) {
+    while let Some(&span) = self.comments.get(self.next_comment) {
+      if span.start >= offset {
+        break;
+      }
+      self.next_comment += 1;
+      self.blank_line_before(span.start);
+      self.line(self.comment(span));
+      self.last_end = span.end;
+    }
+  }
+
+  /// Print a single blank line if there is at least one between the last
+  /// printed item and `offset` in the source.
+  fn blank_line_before(&mut self, offset: usize) {
+    if !self.block_start
+      && offset > self.last_end
+      && has_blank_line(&self.src[self.last_end..offset])
+    {
+      self.out.push('\n');
+    }
+    self.block_start = false;
+  }
+
+  fn line(&mut self, text: &str) {
+    self.indented(text);
+    self.out.push('\n');
+  }
+
+  /// Write `text` at the current indentation. Only multi-line strings span
+  /// more than one line, and each of their lines is indented as well, which
+  /// the parser strips again.
+  fn indented(&mut self, text: &str) {
+    for (i, line) in text.split('\n').enumerate() {
+      if i > 0 {
+        self.out.push('\n');
+        if line.is_empty() {
+          continue;
+        }
+      }
+      for _ in 0..self.indent {
+        self.out.push_str(INDENT);
+      }
+      self.out.push_str(line);
+    }
+  }
+
+  /// Print a statement which fits on one line, along with any comments inside
+  /// of it or at the end of its line.
+  fn simple(&mut self, span: Span, text: &str) {
+    self.comments_before(span.end);
+    self.blank_line_before(span.start);
+    self.indented(text);
+    self.last_end = span.end;
+    if let Some(&comment) = self.comments.get(self.next_comment) {
+      if !self.src[span.end..comment.start].contains('\n') {
+        self.next_comment += 1;
+        self.out.push(' ');
+        self.out.push_str(self.comment(comment));
+        self.last_end = comment.end;
+      }
+    }
+    self.out.push('\n');
+  }
+
+  /// Print a header such as `if cond:`, followed by an indented `body`.
+  fn block(&mut self, start: usize, header: &str, body: &[ast::Stmt]) {
+    self.comments_before(start);
+    self.blank_line_before(start);
+    self.line(header);
+    self.indent += 1;
+    self.stmt_list(body);
+    self.indent -= 1;
+  }
+
+  fn stmt_list(&mut self, body: &[ast::Stmt]) {
+    self.block_start = true;
+    for stmt in body {
+      self.stmt(stmt);
+    }
+  }
+
+  fn stmt(&mut self, stmt: &ast::Stmt) {
+    let start = stmt.span.start;
+    match stmt.deref() {
+      ast::StmtKind::Var(v) => {
+        let text = format!("{} := {}", v.name, expr(&v.value));
+        self.simple(stmt.span, &text);
+      }
+      ast::StmtKind::Unpack(v) => {
+        let targets = v.targets.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+        let op = if v.decl { ":=" } else { "=" };
+        let text = format!("{} {op} {}", targets.join(", "), expr(&v.value));
+        self.simple(stmt.span, &text);
+      }
+      ast::StmtKind::If(v) => {
+        for (i, branch) in v.branches.iter().enumerate() {
+          let keyword = if i == 0 { "if" } else { "elif" };
+          let start = if i == 0 {
+            start
+          } else {
+            branch.cond.span.start
+          };
+          let header = format!("{keyword} {}:", expr(&branch.cond));
+          self.block(start, &header, &branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.line("else:");
+          self.indent += 1;
+          self.stmt_list(default);
+          self.indent -= 1;
+        }
+      }
+      ast::StmtKind::Loop(v) => match v.deref() {
+        ast::Loop::For(v) => {
+          let iter = match &v.iter {
+            ast::ForIter::Range(range) => format!(
+              "{}{}{}",
+              expr(&range.start),
+              if range.inclusive { "..=" } else { ".." },
+              expr(&range.end)
+            ),
+            ast::ForIter::Expr(iter) => expr(iter),
+          };
+          let items = v.items.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+          let header = format!("for {} in {iter}:", items.join(", "));
+          self.block(start, &header, &v.body);
+        }
+        ast::Loop::While(v) => {
+          self.block(start, &format!("while {}:", expr(&v.cond)), &v.body);
+        }
+        ast::Loop::Infinite(v) => self.block(start, "loop:", &v.body),
+      },
+      ast::StmtKind::Ctrl(v) => {
+        let text = match v.deref() {
+          ast::Ctrl::Return(ast::Return { value }) => keyword_with("return", value.as_ref()),
+          ast::Ctrl::Yield(ast::Yield { value }) => keyword_with("yield", value.as_ref()),
+          ast::Ctrl::Raise(v) => keyword_with("raise", Some(&v.value)),
+          ast::Ctrl::Continue => "continue".into(),
+          ast::Ctrl::Break => "break".into(),
+        };
+        self.simple(stmt.span, &text);
+      }
+      ast::StmtKind::Func(v) => {
+        self.decorators(&v.decorators);
+        self.func(start, "fn ", v)
+      }
+      ast::StmtKind::Class(v) => {
+        self.decorators(&v.decorators);
+        self.class(start, v)
+      }
+      ast::StmtKind::Expr(v) => self.simple(stmt.span, &expr_stmt(v)),
+      ast::StmtKind::Pass => self.simple(stmt.span, "pass"),
+      ast::StmtKind::Print(v) => {
+        let values = list(&v.values);
+        // `print (a, b)` would print `a` and `b` instead of a tuple
+        let text = if values.starts_with('(') {
+          format!("print({values})")
+        } else {
+          format!("print {values}")
+        };
+        self.simple(stmt.span, &text);
+      }
+      ast::StmtKind::Import(v) => {
+        let text = match v.deref() {
+          ast::Import::Module { path, alias } => {
+            let mut text = format!("import {}", path.to_path_string());
+            if let Some(alias) = alias {
+              write!(text, " as {alias}").unwrap();
+            }
+            text
+          }
+          ast::Import::Symbols { path, symbols } => {
+            let symbols = symbols
+              .iter()
+              .map(|symbol| match &symbol.alias {
+                Some(alias) => format!("{} as {alias}", symbol.name),
+                None => symbol.name.to_string(),
+              })
+              .collect::<Vec<_>>()
+              .join(", ");
+            format!("from {} import {symbols}", path.to_path_string())
+          }
+        };
+        self.simple(stmt.span, &text);
+      }
+      ast::StmtKind::Defer(v) => {
+        let text = format!("defer {}", call(&v.call));
+        self.simple(stmt.span, &text);
+      }
+    }
+  }
+
+  fn decorators(&mut self, decorators: &[ast::Expr]) {
+    for decorator in decorators {
+      self.simple(decorator.span, &format!("@{}", expr(decorator)));
+    }
+  }
+
+  fn func(&mut self, start: usize, keyword: &str, func: &ast::Func) {
+    let mut params = vec![];
+    if func.params.has_self {
+      params.push("self".to_string());
+    }
+    for (i, param) in func.params.iter().enumerate() {
+      if i == func.params.pos.len() {
+        params.push("*".to_string());
+      }
+      let mut text = param.name.to_string();
+      if let Some(hint) = &param.hint {
+        write!(text, ": {hint}").unwrap();
+      }
+      if let Some(default) = &param.default {
+        write!(text, " = {}", expr(default)).unwrap();
+      }
+      params.push(text);
+    }
+    let header = format!("{keyword}{}({}):", func.name, params.join(", "));
+    self.block(start, &header, &func.body);
+  }
+
+  fn class(&mut self, start: usize, class: &ast::Class) {
+    let mut header = format!("class {}", class.name);
+    if let Some(parent) = &class.parent {
+      write!(header, "({parent})").unwrap();
+    }
+    if !class.mixins.is_empty() {
+      let mixins = class.mixins.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+      write!(header, " with {}", mixins.join(", ")).unwrap();
+    }
+    header.push(':');
+
+    self.comments_before(start);
+    self.blank_line_before(start);
+    self.line(&header);
+    self.indent += 1;
+    self.block_start = true;
+
+    let members = &class.members;
+    let is_empty = members.init.is_none()
+      && members.fields.is_empty()
+      && members.methods.is_empty()
+      && members.static_fields.is_empty()
+      && members.static_methods.is_empty();
+    if is_empty {
+      self.line("pass");
+    }
+    for field in members.fields.iter() {
+      let mut text = field.name.to_string();
+      if let Some(hint) = &field.hint {
+        write!(text, ": {hint}").unwrap();
+      }
+      write!(text, " = {}", expr(&field.default)).unwrap();
+      self.simple(field.span(), &text);
+    }
+    for field in members.static_fields.iter() {
+      let text = format!("static {} = {}", field.name, expr(&field.default));
+      self.simple(field.span(), &text);
+    }
+    if let Some(init) = &members.init {
+      self.func(init.name.span.start, "", init);
+    }
+    for method in members.methods.iter() {
+      self.func(method.name.span.start, "fn ", method);
+    }
+    for method in members.static_methods.iter() {
+      self.func(method.name.span.start, "static fn ", method);
+    }
+
+    self.indent -= 1;
+  }
+}
+
+/// Whether `text` contains a line with only whitespace on it. The first and
+/// last lines are not complete, so they are not counted.
+fn has_blank_line(text: &str) -> bool {
+  let lines = text.split('\n').collect::<Vec<_>>();
+  lines.len() > 2
+    && lines[1..lines.len() - 1]
+      .iter()
+      .any(|v| v.trim().is_empty())
+}
+
+fn keyword_with(keyword: &str, value: Option<&ast::Expr>) -> String {
+  match value {
+    Some(value) => format!("{keyword} {}", expr(value)),
+    None => keyword.into(),
+  }
+}
+
+/// Print an expression statement, turning the desugared form of compound
+/// assignments such as `a += b` back into the original.
+fn expr_stmt(e: &ast::Expr) -> String {
+  match e.deref() {
+    ast::ExprKind::SetVar(v) => {
+      let target = &v.target.name;
+      match v.value.deref() {
+        // the desugared binary expression spans the entire assignment
+        ast::ExprKind::Binary(binary) if v.value.span == e.span => {
+          let op = assign_op(binary.op);
+          format!("{target} {op} {}", expr(&binary.right))
+        }
+        _ => format!("{target} = {}", expr(&v.value)),
+      }
+    }
+    ast::ExprKind::SetField(v) => assignment(get_field(&v.target), v.op, &v.value),
+    ast::ExprKind::SetIndex(v) => assignment(get_index(&v.target), v.op, &v.value),
+    _ => expr(e),
+  }
+}
+
+fn assignment(target: String, op: Option<ast::AssignOp>, value: &ast::Expr) -> String {
+  let op = op.map(|op| assign_op(op.into())).unwrap_or("=");
+  format!("{target} {op} {}", expr(value))
+}
+
+fn assign_op(op: ast::BinaryOp) -> &'static str {
+  use ast::BinaryOp::*;
+  match op {
+    Add => "+=",
+    Sub => "-=",
+    Div => "/=",
+    FloorDiv => "//=",
+    Mul => "*=",
+    Rem => "%=",
+    Pow => "**=",
+    Maybe => "??=",
+    _ => unreachable!("`{op:?}` is not a compound assignment operator"),
+  }
+}
+
+fn binary_op(op: ast::BinaryOp) -> (&'static str, u8) {
+  use ast::BinaryOp::*;
+  match op {
+    Maybe => ("??", 1),
+    Or => ("||", 2),
+    And => ("&&", 3),
+    Eq => ("==", 4),
+    Neq => ("!=", 4),
+    Less => ("<", 5),
+    LessEq => ("<=", 5),
+    More => (">", 5),
+    MoreEq => (">=", 5),
+    Is => ("is", 5),
+    In => ("in", 5),
+    Add => ("+", 6),
+    Sub => ("-", 6),
+    Mul => ("*", 7),
+    Div => ("/", 7),
+    FloorDiv => ("//", 7),
+    Rem => ("%", 7),
+    Pow => ("**", PREC_POW),
+  }
+}
+
+const PREC_CONDITIONAL: u8 = 0;
+const PREC_UNARY: u8 = 8;
+const PREC_POW: u8 = 9;
+const PREC_POSTFIX: u8 = 10;
+
+fn expr(e: &ast::Expr) -> String {
+  expr_prec(e, 0)
+}
+
+/// Print `e`, wrapped in parentheses if it binds less tightly than `min`.
+fn expr_prec(e: &ast::Expr, min: u8) -> String {
+  let (text, prec) = expr_inner(e);
+  if prec < min {
+    format!("({text})")
+  } else {
+    text
+  }
+}
+
+fn expr_inner(e: &ast::Expr) -> (String, u8) {
+  let text = match e.deref() {
+    ast::ExprKind::Literal(v) => literal(v),
+    ast::ExprKind::Binary(v) => {
+      let (op, prec) = binary_op(v.op);
+      // `**` is right-associative, and its exponent may be a unary
+      // expression. Every other binary operator is left-associative.
+      let (left, right) = match v.op {
+        ast::BinaryOp::Pow => (
+          expr_prec(&v.left, prec + 1),
+          expr_prec(&v.right, PREC_UNARY),
+        ),
+        _ => (expr_prec(&v.left, prec), expr_prec(&v.right, prec + 1)),
+      };
+      return (format!("{left} {op} {right}"), prec);
+    }
+    ast::ExprKind::Unary(v) => {
+      let op = match v.op {
+        ast::UnaryOp::Plus => "+",
+        ast::UnaryOp::Minus => "-",
+        ast::UnaryOp::Not => "!",
+        ast::UnaryOp::Opt => "?",
+      };
+      let right = expr_prec(&v.right, PREC_UNARY);
+      // `??` is a different operator
+      let sep = if op == "?" && right.starts_with('?') {
+        " "
+      } else {
+        ""
+      };
+      return (format!("{op}{sep}{right}"), PREC_UNARY);
+    }
+    ast::ExprKind::Conditional(v) => {
+      // right-associative, `a if b else c if d else e` is parsed as
+      // `a if b else (c if d else e)`
+      let then = expr_prec(&v.then, PREC_CONDITIONAL + 1);
+      let cond = expr_prec(&v.cond, PREC_CONDITIONAL + 1);
+      let otherwise = expr_prec(&v.otherwise, PREC_CONDITIONAL);
+      return (
+        format!("{then} if {cond} else {otherwise}"),
+        PREC_CONDITIONAL,
+      );
+    }
+    ast::ExprKind::GetVar(v) => v.name.to_string(),
+    ast::ExprKind::SetVar(_) | ast::ExprKind::SetField(_) | ast::ExprKind::SetIndex(_) => {
+      return (expr_stmt(e), 0)
+    }
+    ast::ExprKind::GetField(v) => get_field(v),
+    ast::ExprKind::GetIndex(v) => get_index(v),
+    ast::ExprKind::Call(v) => call(v),
+    ast::ExprKind::GetSelf => "self".into(),
+    ast::ExprKind::GetSuper => "super".into(),
+  };
+  (text, PREC_POSTFIX)
+}
+
+fn get_field(v: &ast::GetField) -> String {
+  format!("{}.{}", expr_prec(&v.target, PREC_POSTFIX), v.name)
+}
+
+fn get_index(v: &ast::GetIndex) -> String {
+  format!("{}[{}]", expr_prec(&v.target, PREC_POSTFIX), expr(&v.key))
+}
+
+fn call(v: &ast::Call) -> String {
+  let mut args = list(&v.args);
+  for (name, value) in v.kwargs.iter() {
+    if !args.is_empty() {
+      args.push_str(", ");
+    }
+    write!(args, "{name} = {}", expr(value)).unwrap();
+  }
+  format!("{}({args})", expr_prec(&v.target, PREC_POSTFIX))
+}
+
+fn list(items: &[ast::Expr]) -> String {
+  items.iter().map(expr).collect::<Vec<_>>().join(", ")
+}
+
+fn literal(v: &ast::Literal) -> String {
+  match v {
+    ast::Literal::None => "none".into(),
+    ast::Literal::Int(v) => v.to_string(),
+    ast::Literal::BigInt(v) => format!("{v}n"),
+    // `Debug` always includes the fractional part, so it is read back as
+    // a float
+    ast::Literal::Float(v) => format!("{v:?}"),
+    ast::Literal::Bool(v) => v.to_string(),
+    ast::Literal::String(v) => string(v),
+    ast::Literal::Bytes(v) => bytes(v),
+    ast::Literal::List(items) => format!("[{}]", list(items)),
+    ast::Literal::Tuple(items) if items.len() == 1 => format!("({},)", expr(&items[0])),
+    ast::Literal::Tuple(items) => format!("({})", list(items)),
+    ast::Literal::Table(entries) => {
+      let entries = entries
+        .iter()
+        .map(|(key, value)| format!("{}: {}", table_key(key), expr(value)))
+        .collect::<Vec<_>>();
+      format!("{{{}}}", entries.join(", "))
+    }
+  }
+}
+
+fn table_key(key: &ast::Expr) -> String {
+  if let ast::ExprKind::Literal(v) = key.deref() {
+    if let ast::Literal::String(name) = v.deref() {
+      if is_ident(name) {
+        return name.to_string();
+      }
+    }
+  }
+  format!("[{}]", expr(key))
+}
+
+/// Whether `s` is lexed as a single identifier, which excludes keywords.
+fn is_ident(s: &str) -> bool {
+  let mut lexer = TokenKind::lexer(s);
+  matches!(lexer.next(), Some(Ok(TokenKind::Lit_Ident))) && lexer.next().is_none()
+}
+
+/// Strings which contain a backslash are printed as raw strings if they can
+/// be, because they are easier to read without every backslash escaped.
+///
+/// Strings which span multiple lines are printed as `"""..."""` if they end
+/// with a line break, so that the closing quotes are on their own line.
+fn string(s: &str) -> String {
+  if s.contains('\\') && !s.contains('"') && !s.chars().any(char::is_control) {
+    return format!("r\"{s}\"");
+  }
+
+  if s.contains('\n') && s.ends_with('\n') && !s.contains("\"\"\"") {
+    let mut out = String::with_capacity(s.len() + 7);
+    out.push_str("\"\"\"\n");
+    for c in s.chars() {
+      match c {
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push('\n'),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+        c => out.push(c),
+      }
+    }
+    out.push_str("\"\"\"");
+    return out;
+  }
+
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+fn bytes(v: &[u8]) -> String {
+  let mut out = String::with_capacity(v.len() + 3);
+  out.push_str("b\"");
+  for &b in v {
+    match b {
+      b'"' => out.push_str("\\\""),
+      b'\\' => out.push_str("\\\\"),
+      b'\n' => out.push_str("\\n"),
+      b'\r' => out.push_str("\\r"),
+      b'\t' => out.push_str("\\t"),
+      b' '..=b'~' => out.push(b as char),
+      b => write!(out, "\\x{b:02x}").unwrap(),
+    }
+  }
+  out.push('"');
+  out
+}
+
+#[cfg(all(test, not(feature = "__miri")))]
+mod tests;
@@ -0,0 +1,344 @@
+use indoc::indoc;
+
+use super::*;
+
+/// Format `input`, check that the output is `expected`, and that formatting
+/// it again doesn't change it.
+macro_rules! check {
+  ($input:expr, $expected:expr) => {{
+    let input = indoc!($input);
+    let expected = indoc!($expected);
+    let output = match format(input) {
+      Ok(output) => output,
+      Err(e) => {
+        for err in e.errors() {
+          eprintln!("{}", err.report(input, true));
+        }
+        panic!("Failed to parse source, see errors above.")
+      }
+    };
+    assert_eq!(output, expected);
+    assert_eq!(
+      format(&output).unwrap(),
+      output,
+      "formatting is not idempotent"
+    );
+  }};
+}
+
+#[test]
+fn raw_strings() {
+  check!(
+    r#"
+      a := r"\d+\.\d+"
+      b := "C:\\temp"
+      c := "\\\"q\""
+      d := r""
+      e := "tab\t\\"
+    "#,
+    r#"
+      a := r"\d+\.\d+"
+      b := r"C:\temp"
+      c := "\\\"q\""
+      d := ""
+      e := "tab\t\\"
+    "#
+  );
+}
+
+#[test]
+fn multiline_strings() {
+  check!(
+    r#"
+      fn query():
+        q := """
+          SELECT *
+            FROM "t"\t\\
+
+          """
+        return q
+      s := "a\nb\n"
+      t := "no line break\nat the end"
+      u := """first
+        second"""
+    "#,
+    r#"
+      fn query():
+        q := """
+        SELECT *
+          FROM "t"\t\\
+
+        """
+        return q
+      s := """
+      a
+      b
+      """
+      t := "no line break\nat the end"
+      u := "first\nsecond"
+    "#
+  );
+}
+
+#[test]
+fn simple_statements() {
+  check!(
+    r#"
+      x:=1;y  :=  [1,2,  3]
+      t := {a:1, ["b c"]: 2, [0]: (1,), ["if"]: ()}
+      print(x,y)
+      print((1, 2), 3)
+      import a.b as c
+      from ..m import x as y,z
+      s := "a\"b\\c\nd"
+      b := b"\x00\xff ok"
+      f := 1.5e3 + 10 + 2.0
+    "#,
+    r#"
+      x := 1
+      y := [1, 2, 3]
+      t := {a: 1, ["b c"]: 2, [0]: (1,), ["if"]: ()}
+      print x, y
+      print((1, 2), 3)
+      import a.b as c
+      from ..m import x as y, z
+      s := "a\"b\\c\nd"
+      b := b"\x00\xff ok"
+      f := 1500.0 + 10 + 2.0
+    "#
+  );
+}
+
+#[test]
+fn parentheses() {
+  check!(
+    r#"
+      a := ((1 + 2)) * 3
+      b := 1 + (2 * 3)
+      c := 1 - (2 - 3)
+      d := (1 - 2) - 3
+      e := -(a + b) ** 2
+      f := (a ?? b).c(d)[0]
+      g := !(a && b) || c
+      h := ?(?a)
+      i := (a || b) && c
+      j := (a if b else c) + 1
+      k := a if (b if c else d) else (e if f else g)
+    "#,
+    r#"
+      a := (1 + 2) * 3
+      b := 1 + 2 * 3
+      c := 1 - (2 - 3)
+      d := 1 - 2 - 3
+      e := -(a + b) ** 2
+      f := (a ?? b).c(d)[0]
+      g := !(a && b) || c
+      h := ? ?a
+      i := (a || b) && c
+      j := (a if b else c) + 1
+      k := a if (b if c else d) else e if f else g
+    "#
+  );
+}
+
+#[test]
+fn pow_precedence() {
+  check!(
+    r#"
+      a := -2 ** 2
+      b := (-2) ** 2
+      c := 2 ** (3 ** 2)
+      d := (2 ** 3) ** 2
+      e := 2 ** -(1)
+      f := -(a ** b) * c
+    "#,
+    r#"
+      a := -2 ** 2
+      b := (-2) ** 2
+      c := 2 ** 3 ** 2
+      d := (2 ** 3) ** 2
+      e := 2 ** -1
+      f := -a ** b * c
+    "#
+  );
+}
+
+#[test]
+fn compound_assignment() {
+  check!(
+    r#"
+      a = b
+      a += 1
+      a.b   -=   2 * 3
+      a[0] ??= b
+      a = a + 1
+    "#,
+    r#"
+      a = b
+      a += 1
+      a.b -= 2 * 3
+      a[0] ??= b
+      a = a + 1
+    "#
+  );
+}
+
+#[test]
+fn blocks() {
+  check!(
+    r#"
+      if a: print a
+      elif b:
+          print b
+      else: pass
+      for i in 0..=10: print i
+      for v in items:
+          if v: break
+          else: continue
+      while true: pass
+      loop:
+          pass
+      fn f(a, b: int = 1):
+          defer g()
+          if a: return
+          yield a
+    "#,
+    r#"
+      if a:
+        print a
+      elif b:
+        print b
+      else:
+        pass
+      for i in 0..=10:
+        print i
+      for v in items:
+        if v:
+          break
+        else:
+          continue
+      while true:
+        pass
+      loop:
+        pass
+      fn f(a, b: int = 1):
+        defer g()
+        if a:
+          return
+        yield a
+    "#
+  );
+}
+
+#[test]
+fn classes() {
+  check!(
+    r#"
+      class A: pass
+      class B(A) with M, N:
+          static count = 0
+          init(self):
+              self.v = 1
+          v: int = 0
+          fn m(self, x):
+              return super.m(x)
+          static fn s(): return 0
+    "#,
+    r#"
+      class A:
+        pass
+      class B(A) with M, N:
+        v: int = 0
+        static count = 0
+        init(self):
+          self.v = 1
+        fn m(self, x):
+          return super.m(x)
+        static fn s():
+          return 0
+    "#
+  );
+}
+
+#[test]
+fn decorators() {
+  check!(
+    r#"
+      @memo
+      fn f(n):   pass
+      @ a.b( c )
+      @d
+      class T:
+        pass
+    "#,
+    r#"
+      @memo
+      fn f(n):
+        pass
+      @a.b(c)
+      @d
+      class T:
+        pass
+    "#
+  );
+}
+
+#[test]
+fn kw_params() {
+  check!(
+    r#"
+      fn f(a,*,b=1,c):   pass
+      f(a,b=c,c=d(e=f))
+    "#,
+    r#"
+      fn f(a, *, b = 1, c):
+        pass
+      f(a, b = c, c = d(e = f))
+    "#
+  );
+}
+
+#[test]
+fn comments_and_blank_lines() {
+  check!(
+    r#"
+      # leading
+      a := 1 # trailing
+
+
+
+      # before f
+      fn f():
+
+          # inside
+          return a  # also trailing
+      # end of f
+
+      b := f(
+        # in call
+        a,
+      )
+      # at the end
+    "#,
+    r#"
+      # leading
+      a := 1 # trailing
+
+      # before f
+      fn f():
+        # inside
+        return a # also trailing
+      # end of f
+
+      # in call
+      b := f(a)
+      # at the end
+    "#
+  );
+}
+
+#[test]
+fn syntax_error() {
+  let e = format("a := (").unwrap_err();
+  assert!(!e.errors().is_empty());
+}
@@ -14,6 +14,8 @@ pub struct Token {
   pub ws: Option<u64>,
   pub kind: TokenKind,
   pub span: Span,
+  /// The `##` doc comments on the lines before this token.
+  pub doc: Option<Span>,
 }
 
 impl Token {
@@ -29,6 +31,7 @@ pub struct Lexer<'src> {
   previous: Token,
   current: Token,
   ws: Option<u64>,
+  doc: Option<Span>,
   eof: Token,
 }
 
@@ -39,6 +42,7 @@ impl<'src> Lexer<'src> {
       ws: None,
       span: (end..end).into(),
       kind: TokenKind::Tok_Eof,
+      doc: None,
     };
 
     let mut lex = Self {
@@ -47,6 +51,7 @@ impl<'src> Lexer<'src> {
       previous: eof.clone(),
       current: eof.clone(),
       ws: Some(0),
+      doc: None,
       eof,
     };
     lex.bump();
@@ -54,6 +59,11 @@ impl<'src> Lexer<'src> {
     lex
   }
 
+  #[inline]
+  pub fn src(&self) -> &'src str {
+    self.src
+  }
+
   #[inline]
   pub fn previous(&self) -> &Token {
     &self.previous
@@ -80,11 +90,21 @@ impl<'src> Lexer<'src> {
     let lexer = &mut self.inner;
     while let Some(kind) = lexer.next() {
       let lexeme = lexer.slice();
-      let span = lexer.span().into();
+      let span: Span = lexer.span().into();
 
       match kind {
         // Filter
-        Ok(TokenKind::_Tok_Whitespace | TokenKind::_Tok_Comment) => continue,
+        Ok(TokenKind::_Tok_Whitespace) => continue,
+        // Collect doc comments, which must be on their own line
+        Ok(TokenKind::_Tok_Comment) => {
+          if lexeme.starts_with("##") && self.ws.is_some() {
+            self.doc = Some(match self.doc {
+              Some(doc) => (doc.start..span.end).into(),
+              None => span,
+            });
+          }
+          continue;
+        }
         // Measure indentation
         Ok(TokenKind::_Tok_Indent) => {
           self.ws = Some(measure_indent(lexeme));
@@ -96,6 +116,7 @@ impl<'src> Lexer<'src> {
             ws: take(&mut self.ws),
             kind,
             span,
+            doc: take(&mut self.doc),
           };
           return Some(token);
         }
@@ -104,6 +125,7 @@ impl<'src> Lexer<'src> {
             ws: take(&mut self.ws),
             kind: TokenKind::Tok_Error,
             span,
+            doc: take(&mut self.doc),
           };
           return Some(token);
         }
@@ -153,6 +175,10 @@ pub enum TokenKind {
   Kw_Break,
   #[token("continue")]
   Kw_Continue,
+  #[token("raise")]
+  Kw_Raise,
+  #[token("defer")]
+  Kw_Defer,
   #[token("print")]
   Kw_Print,
   #[token("if")]
@@ -163,6 +189,10 @@ pub enum TokenKind {
   Kw_Else,
   #[token("pass")]
   Kw_Pass,
+  #[token("static")]
+  Kw_Static,
+  #[token("with")]
+  Kw_With,
 
   // Brackets
   #[token("{")]
@@ -191,6 +221,8 @@ pub enum TokenKind {
   Tok_Colon,
   #[token("?")]
   Tok_Question,
+  #[token("@")]
+  Tok_At,
 
   // Equals operators
   #[token("=")]
@@ -203,6 +235,8 @@ pub enum TokenKind {
   Op_MinusEqual,
   #[token("/=")]
   Op_SlashEqual,
+  #[token("//=")]
+  Op_SlashSlashEqual,
   #[token("*=")]
   Op_StarEqual,
   #[token("%=")]
@@ -223,6 +257,8 @@ pub enum TokenKind {
   Op_Minus,
   #[token("/")]
   Op_Slash,
+  #[token("//")]
+  Op_SlashSlash,
   #[token("*")]
   Op_Star,
   #[token("%")]
@@ -256,8 +292,11 @@ pub enum TokenKind {
   Lit_None,
   #[regex("[0-9]([0-9_]*[0-9])?", priority = 10)]
   Lit_Int,
-  /// `0`, `1.0`, `5e10`, etc.
-  #[regex(r"[0-9]+(\.[0-9]+)?([Ee][+-]?[0-9]+)?")]
+  /// `0n`, `123n`, etc.
+  #[regex("[0-9]([0-9_]*[0-9])?n")]
+  Lit_BigInt,
+  /// `0`, `1.0`, `5e10`, `1_000.5`, etc.
+  #[regex(r"[0-9]([0-9_]*[0-9])?(\.[0-9]([0-9_]*[0-9])?)?([Ee][+-]?[0-9]([0-9_]*[0-9])?)?")]
   Lit_Float,
   /// `true` or `false`
   #[token("true")]
@@ -265,6 +304,15 @@ pub enum TokenKind {
   Lit_Bool,
   #[regex(r#""([^"\\]|\\.)*""#)] // fix highlighting -> "
   Lit_String,
+  /// `"""..."""`, which may not contain a `"""`
+  #[regex(r#""""([^"\\]|\\.|"([^"\\]|\\.)|""([^"\\]|\\.))*""""#)] // fix highlighting -> "
+  Lit_MultilineString,
+  /// `r"..."`, which may not contain a `"`
+  #[regex(r#"r"[^"]*""#)] // fix highlighting -> "
+  Lit_RawString,
+  /// `b"..."`
+  #[regex(r#"b"([^"\\]|\\.)*""#)] // fix highlighting -> "
+  Lit_Bytes,
   /// `a`, `b_c`, `__x0`, etc.
   #[regex("[a-zA-Z_][a-zA-Z0-9_]*")]
   Lit_Ident,
@@ -303,11 +351,15 @@ impl TokenKind {
       TokenKind::Kw_Return => "return",
       TokenKind::Kw_Break => "break",
       TokenKind::Kw_Continue => "continue",
+      TokenKind::Kw_Raise => "raise",
+      TokenKind::Kw_Defer => "defer",
       TokenKind::Kw_Print => "print",
       TokenKind::Kw_If => "if",
       TokenKind::Kw_Elif => "elif",
       TokenKind::Kw_Else => "else",
       TokenKind::Kw_Pass => "pass",
+      TokenKind::Kw_Static => "static",
+      TokenKind::Kw_With => "with",
       TokenKind::Brk_CurlyL => "{",
       TokenKind::Brk_CurlyR => "}",
       TokenKind::Brk_ParenL => "(",
@@ -320,11 +372,13 @@ impl TokenKind {
       TokenKind::Tok_SemicolonSemicolon => ";;",
       TokenKind::Tok_Colon => ":",
       TokenKind::Tok_Question => "?",
+      TokenKind::Tok_At => "@",
       TokenKind::Op_Equal => "=",
       TokenKind::Op_EqualEqual => "==",
       TokenKind::Op_PlusEqual => "+=",
       TokenKind::Op_MinusEqual => "-=",
       TokenKind::Op_SlashEqual => "/=",
+      TokenKind::Op_SlashSlashEqual => "//=",
       TokenKind::Op_StarEqual => "*=",
       TokenKind::Op_PercentEqual => "%=",
       TokenKind::Op_StarStarEqual => "**=",
@@ -334,6 +388,7 @@ impl TokenKind {
       TokenKind::Op_Plus => "+",
       TokenKind::Op_Minus => "-",
       TokenKind::Op_Slash => "/",
+      TokenKind::Op_SlashSlash => "//",
       TokenKind::Op_Star => "*",
       TokenKind::Op_Percent => "%",
       TokenKind::Op_StarStar => "**",
@@ -349,9 +404,13 @@ impl TokenKind {
       TokenKind::Op_RangeInc => "..=",
       TokenKind::Lit_None => "none",
       TokenKind::Lit_Int => "int",
+      TokenKind::Lit_BigInt => "bigint",
       TokenKind::Lit_Float => "float",
       TokenKind::Lit_Bool => "bool",
       TokenKind::Lit_String => "string",
+      TokenKind::Lit_MultilineString => "string",
+      TokenKind::Lit_RawString => "raw string",
+      TokenKind::Lit_Bytes => "bytes",
       TokenKind::Lit_Ident => "identifier",
       TokenKind::_Tok_Indent => "<indentation>",
       TokenKind::_Tok_Whitespace => "<whitespace>",
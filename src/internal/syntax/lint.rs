@@ -0,0 +1,371 @@
+//! Checks for code which is valid, but likely to be a mistake.
+//!
+//! Lints are computed from an [`Analysis`], so they are available even for
+//! scripts with syntax errors. Statements which failed to parse are not
+//! checked.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use super::analysis::{Analysis, SymbolKind};
+use super::ast;
+use crate::span::SpannedError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+  /// A variable declared in a function which is never used.
+  UnusedVariable,
+  /// A statement after `return`, `raise`, `break`, or `continue`.
+  UnreachableCode,
+  /// A variable which has the same name as a parameter of its function.
+  ShadowedParam,
+  /// `==` or `!=` between values of different types, which is always
+  /// `false` or `true`.
+  SuspiciousComparison,
+}
+
+impl Rule {
+  pub const ALL: [Rule; 4] = [
+    Rule::UnusedVariable,
+    Rule::UnreachableCode,
+    Rule::ShadowedParam,
+    Rule::SuspiciousComparison,
+  ];
+
+  /// The name of the rule, such as `unused-variable`.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Rule::UnusedVariable => "unused-variable",
+      Rule::UnreachableCode => "unreachable-code",
+      Rule::ShadowedParam => "shadowed-param",
+      Rule::SuspiciousComparison => "suspicious-comparison",
+    }
+  }
+}
+
+impl Display for Rule {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+impl FromStr for Rule {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Rule::ALL
+      .into_iter()
+      .find(|rule| rule.name() == s)
+      .ok_or_else(|| format!("unknown lint rule `{s}`"))
+  }
+}
+
+/// Which rules are checked. Every rule is enabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct LintConfig {
+  disabled: HashSet<Rule>,
+}
+
+impl LintConfig {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Disable `rule`.
+  pub fn allow(mut self, rule: Rule) -> Self {
+    self.disabled.insert(rule);
+    self
+  }
+
+  pub fn is_enabled(&self, rule: Rule) -> bool {
+    !self.disabled.contains(&rule)
+  }
+}
+
+#[derive(Debug)]
+pub struct Lint {
+  pub rule: Rule,
+  pub diagnostic: SpannedError,
+}
+
+/// Check the script in `analysis` using the rules enabled in `config`. The
+/// lints are ordered by their position in the script.
+pub fn lint(analysis: &Analysis, config: &LintConfig) -> Vec<Lint> {
+  let mut linter = Linter {
+    config,
+    lints: vec![],
+  };
+  linter.symbols(analysis);
+  linter.stmt_list(&analysis.module.body);
+  linter
+    .lints
+    .sort_by_key(|lint| (lint.diagnostic.span.start, lint.diagnostic.span.end));
+  linter.lints
+}
+
+struct Linter<'a> {
+  config: &'a LintConfig,
+  lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+  fn emit(&mut self, rule: Rule, diagnostic: SpannedError) {
+    if self.config.is_enabled(rule) {
+      self.lints.push(Lint { rule, diagnostic });
+    }
+  }
+
+  fn symbols(&mut self, analysis: &Analysis) {
+    let symbols = &analysis.symbols;
+    for (id, symbol) in symbols.symbols() {
+      if !matches!(symbol.kind, SymbolKind::Variable | SymbolKind::LoopItem) {
+        continue;
+      }
+      // module variables may be used by other modules
+      let Some(container) = symbol.container else {
+        continue;
+      };
+
+      if !symbol.name.starts_with('_') && symbols.references_to(id).next().is_none() {
+        self.emit(
+          Rule::UnusedVariable,
+          SpannedError::new(format!("unused variable `{}`", symbol.name), symbol.span)
+            .with_help("if this is intentional, prefix the name with an underscore"),
+        );
+      }
+
+      let param = symbols.symbols().find(|(_, other)| {
+        other.kind == SymbolKind::Parameter
+          && other.container == Some(container)
+          && other.name == symbol.name
+      });
+      if let Some((_, param)) = param {
+        self.emit(
+          Rule::ShadowedParam,
+          SpannedError::new(
+            format!("variable `{}` shadows a parameter", symbol.name),
+            symbol.span,
+          )
+          .with_label(param.span, "the parameter is defined here"),
+        );
+      }
+    }
+  }
+
+  fn stmt_list(&mut self, list: &[ast::Stmt]) {
+    let mut terminator = None;
+    for (i, stmt) in list.iter().enumerate() {
+      if let ast::StmtKind::Ctrl(ctrl) = stmt.deref() {
+        let keyword = match ctrl.deref() {
+          ast::Ctrl::Return(_) => "return",
+          ast::Ctrl::Raise(_) => "raise",
+          ast::Ctrl::Break => "break",
+          ast::Ctrl::Continue => "continue",
+          ast::Ctrl::Yield(_) => "",
+        };
+        if !keyword.is_empty() && terminator.is_none() && i + 1 < list.len() {
+          terminator = Some((i, keyword));
+        }
+      }
+      self.stmt(stmt);
+    }
+
+    if let Some((i, keyword)) = terminator {
+      let span = list[i + 1].span.join(list.last().unwrap().span);
+      self.emit(
+        Rule::UnreachableCode,
+        SpannedError::new("unreachable code", span).with_label(
+          list[i].span,
+          format!("any code after this `{keyword}` is unreachable"),
+        ),
+      );
+    }
+  }
+
+  fn func(&mut self, func: &ast::Func) {
+    for param in func.params.iter() {
+      if let Some(default) = &param.default {
+        self.expr(default);
+      }
+    }
+    self.stmt_list(&func.body);
+  }
+
+  fn stmt(&mut self, stmt: &ast::Stmt) {
+    match stmt.deref() {
+      ast::StmtKind::Var(v) => self.expr(&v.value),
+      ast::StmtKind::Unpack(v) => self.expr(&v.value),
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.stmt_list(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.stmt_list(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => match v.deref() {
+        ast::Loop::For(v) => {
+          match &v.iter {
+            ast::ForIter::Range(range) => {
+              self.expr(&range.start);
+              self.expr(&range.end);
+            }
+            ast::ForIter::Expr(iter) => self.expr(iter),
+          }
+          self.stmt_list(&v.body);
+        }
+        ast::Loop::While(v) => {
+          self.expr(&v.cond);
+          self.stmt_list(&v.body);
+        }
+        ast::Loop::Infinite(v) => self.stmt_list(&v.body),
+      },
+      ast::StmtKind::Ctrl(v) => match v.deref() {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Raise(v) => self.expr(&v.value),
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => {
+        for decorator in v.decorators.iter() {
+          self.expr(decorator);
+        }
+        self.func(v)
+      }
+      ast::StmtKind::Class(v) => {
+        for decorator in v.decorators.iter() {
+          self.expr(decorator);
+        }
+        let members = &v.members;
+        for field in members.fields.iter().chain(members.static_fields.iter()) {
+          self.expr(&field.default);
+        }
+        let methods = members
+          .init
+          .iter()
+          .chain(members.methods.iter())
+          .chain(members.static_methods.iter());
+        for method in methods {
+          self.func(method);
+        }
+      }
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Pass | ast::StmtKind::Import(_) => {}
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Defer(v) => self.call(&v.call),
+    }
+  }
+
+  fn call(&mut self, call: &ast::Call) {
+    self.expr(&call.target);
+    for arg in call.args.iter() {
+      self.expr(arg);
+    }
+    for (_, value) in call.kwargs.iter() {
+      self.expr(value);
+    }
+  }
+
+  fn expr(&mut self, expr: &ast::Expr) {
+    match expr.deref() {
+      ast::ExprKind::Literal(v) => match v.deref() {
+        ast::Literal::List(items) | ast::Literal::Tuple(items) => {
+          for item in items {
+            self.expr(item);
+          }
+        }
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries {
+            self.expr(key);
+            self.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        if matches!(v.op, ast::BinaryOp::Eq | ast::BinaryOp::Neq) {
+          self.comparison(expr, v);
+        }
+        self.expr(&v.left);
+        self.expr(&v.right);
+      }
+      ast::ExprKind::Unary(v) => self.expr(&v.right),
+      ast::ExprKind::Conditional(v) => {
+        self.expr(&v.cond);
+        self.expr(&v.then);
+        self.expr(&v.otherwise);
+      }
+      ast::ExprKind::GetVar(_) | ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+      ast::ExprKind::SetVar(v) => self.expr(&v.value),
+      ast::ExprKind::GetField(v) => self.expr(&v.target),
+      ast::ExprKind::SetField(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        self.expr(&v.target);
+        self.expr(&v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.target.key);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::Call(v) => self.call(v),
+    }
+  }
+
+  fn comparison(&mut self, expr: &ast::Expr, binary: &ast::Binary) {
+    let (Some(left), Some(right)) = (type_of(&binary.left), type_of(&binary.right)) else {
+      return;
+    };
+    // numbers of different types may still be equal
+    if left == right || (is_number(left) && is_number(right)) {
+      return;
+    }
+    let result = matches!(binary.op, ast::BinaryOp::Neq);
+    self.emit(
+      Rule::SuspiciousComparison,
+      SpannedError::new(
+        format!("comparison between `{left}` and `{right}` is always `{result}`"),
+        expr.span,
+      ),
+    );
+  }
+}
+
+/// The type of `expr`, if it is known without running the script.
+fn type_of(expr: &ast::Expr) -> Option<&'static str> {
+  let ast::ExprKind::Literal(v) = expr.deref() else {
+    return None;
+  };
+  Some(match v.deref() {
+    ast::Literal::None => "none",
+    ast::Literal::Int(_) => "int",
+    ast::Literal::BigInt(_) => "bigint",
+    ast::Literal::Float(_) => "float",
+    ast::Literal::Bool(_) => "bool",
+    ast::Literal::String(_) => "str",
+    ast::Literal::Bytes(_) => "bytes",
+    ast::Literal::List(_) => "list",
+    ast::Literal::Tuple(_) => "tuple",
+    ast::Literal::Table(_) => "table",
+  })
+}
+
+fn is_number(ty: &str) -> bool {
+  matches!(ty, "int" | "bigint" | "float")
+}
+
+#[cfg(all(test, not(feature = "__miri")))]
+mod tests;
@@ -0,0 +1,127 @@
+use indoc::indoc;
+
+use super::*;
+use crate::internal::syntax::analysis::analyze;
+
+/// The rule and message of every lint in `src`.
+fn check(src: &str, config: &LintConfig) -> Vec<(Rule, String)> {
+  let analysis = analyze(src);
+  assert!(analysis.diagnostics.is_empty());
+  lint(&analysis, config)
+    .into_iter()
+    .map(|lint| (lint.rule, lint.diagnostic.message))
+    .collect()
+}
+
+#[test]
+fn unused_variable() {
+  let src = indoc! {r#"
+    unused_global := 0
+    fn f(a):
+      used := a
+      unused := 1
+      _ignored := 2
+      for i in 0..10:
+        pass
+      fn g():
+        return used
+      return g
+  "#};
+  assert_eq!(
+    check(src, &LintConfig::new()),
+    [
+      (Rule::UnusedVariable, "unused variable `unused`".into()),
+      (Rule::UnusedVariable, "unused variable `i`".into()),
+    ]
+  );
+}
+
+#[test]
+fn unreachable_code() {
+  let src = indoc! {r#"
+    fn f(a):
+      loop:
+        break
+        print a
+      if a:
+        raise "error"
+      return a
+      print a
+      print a
+  "#};
+  let analysis = analyze(src);
+  let lints = lint(&analysis, &LintConfig::new());
+  let lints = lints
+    .iter()
+    .map(|lint| {
+      let span = lint.diagnostic.span;
+      (lint.rule, &src[span.range()])
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(
+    lints,
+    [
+      (Rule::UnreachableCode, "print a"),
+      (Rule::UnreachableCode, "print a\n  print a"),
+    ]
+  );
+}
+
+#[test]
+fn shadowed_param() {
+  let src = indoc! {r#"
+    class T:
+      fn m(self, a, b):
+        a := b
+        return a
+  "#};
+  let analysis = analyze(src);
+  let lints = lint(&analysis, &LintConfig::new());
+  assert_eq!(lints.len(), 1);
+  assert_eq!(lints[0].rule, Rule::ShadowedParam);
+  assert_eq!(lints[0].diagnostic.span.start, src.find("a :=").unwrap());
+  assert_eq!(
+    lints[0].diagnostic.labels[0].span.start,
+    src.find("a, b").unwrap()
+  );
+}
+
+#[test]
+fn suspicious_comparison() {
+  let src = indoc! {r#"
+    print 1 == "1", 1 == 1.0, "a" != none, a == "a", [] == []
+  "#};
+  assert_eq!(
+    check(src, &LintConfig::new()),
+    [
+      (
+        Rule::SuspiciousComparison,
+        "comparison between `int` and `str` is always `false`".into()
+      ),
+      (
+        Rule::SuspiciousComparison,
+        "comparison between `str` and `none` is always `true`".into()
+      ),
+    ]
+  );
+}
+
+#[test]
+fn allowed_rules() {
+  let src = indoc! {r#"
+    fn f(a):
+      a := 1 == "1"
+  "#};
+  let config = LintConfig::new()
+    .allow(Rule::UnusedVariable)
+    .allow(Rule::SuspiciousComparison);
+  assert_eq!(
+    check(src, &config),
+    [(
+      Rule::ShadowedParam,
+      "variable `a` shadows a parameter".into()
+    )]
+  );
+  assert_eq!("shadowed-param".parse::<Rule>(), Ok(Rule::ShadowedParam));
+  assert!("unknown".parse::<Rule>().is_err());
+}
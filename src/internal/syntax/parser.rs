@@ -1,6 +1,8 @@
 #![deny(unused_must_use)]
 #![allow(dead_code, clippy::needless_update)]
 
+use std::ops::Range;
+
 use self::indent::IndentStack;
 use super::lexer::TokenKind::*;
 use super::lexer::{Lexer, Token, TokenKind};
@@ -18,6 +20,17 @@ pub fn parse(global: Global, src: &str) -> Result<ast::Module, SyntaxError> {
   parser.module().map_err(SyntaxError::new)
 }
 
+/// Like `parse`, but also returns the module if there are syntax errors.
+/// Statements which failed to parse are missing from it.
+pub fn parse_recovering<'src>(
+  global: Global,
+  src: &'src str,
+) -> (ast::Module<'src>, Vec<SpannedError>) {
+  let lexer = Lexer::new(src);
+  let parser = Parser::new(global, lexer);
+  parser.module_recovering()
+}
+
 #[derive(Clone)]
 struct State<'src> {
   ignore_indent: bool,
@@ -45,14 +58,16 @@ impl<'src> State<'src> {
     }
   }
 
-  pub fn with_func(&self, name: Cow<'src, str>, has_self: bool) -> Self {
+  pub fn with_func(&self, name: &ast::Ident<'src>, has_self: bool) -> Self {
     Self {
       ignore_indent: false,
       current_loop: None,
       current_func: Some(Func {
-        name,
+        name: name.lexeme(),
+        span: name.span,
         has_yield: false,
         has_self,
+        in_loop: self.current_loop.is_some(),
       }),
       current_class: self.current_class,
     }
@@ -76,8 +91,11 @@ struct Class {
 #[derive(Clone)]
 struct Func<'src> {
   name: Cow<'src, str>,
+  span: Span,
   has_yield: bool,
   has_self: bool,
+  /// Set if the function is declared inside of a loop.
+  in_loop: bool,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -97,8 +115,10 @@ impl<'src> Default for Func<'src> {
   fn default() -> Self {
     Self {
       name: Cow::borrowed("__main__"),
+      span: Span::default(),
       has_yield: false,
       has_self: false,
+      in_loop: false,
     }
   }
 }
@@ -109,6 +129,8 @@ struct Parser<'src> {
   lex: Lexer<'src>,
   errors: Vec<SpannedError>,
   indent: IndentStack,
+  /// The number of brackets which are currently open.
+  brackets: usize,
   state: State<'src>,
 }
 
@@ -120,6 +142,7 @@ impl<'src> Parser<'src> {
       lex,
       errors: Vec::new(),
       indent: IndentStack::new(),
+      brackets: 0,
       state: State::default(),
     }
   }
@@ -209,6 +232,11 @@ impl<'src> Parser<'src> {
   /// Move forward by one token, returning the previous one.
   #[inline]
   fn bump(&mut self) -> &Token {
+    match self.current().kind {
+      Brk_CurlyL | Brk_ParenL | Brk_SquareL => self.brackets += 1,
+      Brk_CurlyR | Brk_ParenR | Brk_SquareR => self.brackets = self.brackets.saturating_sub(1),
+      _ => {}
+    }
     self.lex.bump();
     while self.current().is(Tok_Error) {
       self.errors.push(SpannedError::new(
@@ -246,29 +274,63 @@ impl<'src> Parser<'src> {
     Ok((state, res?))
   }
 
-  fn sync(&mut self) {
-    self.bump();
-    while !self.current().is(Tok_Eof) {
-      // break when exiting a block (dedent)
-      // but not in an if statement, because it is composed of multiple blocks
-      if self.dedent().is_ok() && ![Kw_Else, Kw_Elif].contains(&self.current().kind) {
-        break;
-      }
+  /// The text of the `##` doc comments before `token`, without the `##`.
+  /// Plain comments between them are skipped.
+  fn doc_comment(&self, token: &Token) -> Option<String> {
+    let span = token.doc?;
+    let lines = self.lex.src()[Range::from(span)]
+      .lines()
+      .filter_map(|line| line.trim().strip_prefix("##"))
+      .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+      .collect::<Vec<_>>();
+    Some(lines.join("\n"))
+  }
 
-      match self.current().kind {
-        // break on keywords that begin statements
-        Kw_Import | Kw_From | Kw_Fn | Kw_Class | Kw_For | Kw_While | Kw_Loop | Kw_If => break,
-        // handle any errors
-        Tok_Error => self.errors.push(SpannedError::new(
-          format!("invalid token `{}`", self.lex.lexeme(self.current())),
-          self.current().span,
-        )),
-        _ => {}
-      }
+  /// Record `error`, and skip to the start of the next statement in the
+  /// current block, or the end of the block.
+  ///
+  /// `point` is the state of the parser at the first token of the statement
+  /// which failed to parse. The indentation stack is restored to that state,
+  /// because the statement may have failed inside of a nested block, and
+  /// lines inside of brackets which were opened by the statement are not
+  /// the start of a new one.
+  fn recover(&mut self, error: SpannedError, point: RecoveryPoint) {
+    self.errors.push(error);
+    self.indent.truncate(point.indent);
 
+    while !self.current().is(Tok_Eof) {
+      let token = self.current();
+      if self.brackets <= point.brackets && token.span.start > point.start {
+        match token.ws {
+          // a dedent ends the block
+          Some(n) if self.indent.is_lt(n) => break,
+          // `elif` and `else` belong to an `if` statement which failed to
+          // parse, so they are skipped along with it
+          Some(n) if self.indent.is_eq(n) && ![Kw_Elif, Kw_Else].contains(&token.kind) => break,
+          _ => {}
+        }
+      }
       self.bump();
     }
+    self.brackets = point.brackets;
   }
+
+  /// The state passed to `recover` if the statement which starts at the
+  /// current token fails to parse.
+  fn recovery_point(&self) -> RecoveryPoint {
+    RecoveryPoint {
+      indent: self.indent.depth(),
+      brackets: self.brackets,
+      start: self.current().span.start,
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+struct RecoveryPoint {
+  indent: usize,
+  brackets: usize,
+  start: usize,
 }
 
 mod common;
@@ -2,7 +2,26 @@ use super::*;
 
 impl<'src> Parser<'src> {
   pub(super) fn expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
-    self.maybe_expr()
+    self.conditional_expr()
+  }
+
+  fn conditional_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
+    let then = self.maybe_expr()?;
+    if self.no_indent().is_ok() && self.bump_if(Kw_If) {
+      self.no_indent()?;
+      let cond = self.maybe_expr()?;
+      self.no_indent()?;
+      self.expect(Kw_Else)?;
+      self.no_indent()?;
+      let otherwise = self.conditional_expr()?;
+      return Ok(ast::expr_conditional(
+        then.span.start..otherwise.span.end,
+        cond,
+        then,
+        otherwise,
+      ));
+    }
+    Ok(then)
   }
 
   fn maybe_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
@@ -103,44 +122,32 @@ impl<'src> Parser<'src> {
   }
 
   fn mul_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
-    let mut left = self.pow_expr()?;
+    let mut left = self.unary_expr()?;
     while self.no_indent().is_ok() {
       let op = match self.current().kind {
         Op_Star => ast::BinaryOp::Mul,
         Op_Slash => ast::BinaryOp::Div,
+        Op_SlashSlash => ast::BinaryOp::FloorDiv,
         Op_Percent => ast::BinaryOp::Rem,
         _ => break,
       };
       self.bump(); // bump operator
-      self.no_indent()?;
-      let right = self.pow_expr()?;
-      left = ast::expr_binary(left.span.start..right.span.end, op, left, right);
-    }
-    Ok(left)
-  }
-
-  fn pow_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
-    let mut left = self.unary_expr()?;
-    while self.no_indent().is_ok() && self.bump_if(Op_StarStar) {
       self.no_indent()?;
       let right = self.unary_expr()?;
-      left = ast::expr_binary(
-        left.span.start..right.span.end,
-        ast::BinaryOp::Pow,
-        left,
-        right,
-      );
+      left = ast::expr_binary(left.span.start..right.span.end, op, left, right);
     }
     Ok(left)
   }
 
+  /// Prefix operators bind less tightly than `**` on their right, so
+  /// `-2 ** 2` is `-(2 ** 2)`.
   fn unary_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
     let op = match self.current().kind {
       Op_Minus => ast::UnaryOp::Minus,
       Op_Plus => ast::UnaryOp::Plus,
       Op_Bang => ast::UnaryOp::Not,
       Tok_Question => ast::UnaryOp::Opt,
-      _ => return self.postfix_expr(),
+      _ => return self.pow_expr(),
     };
     self.bump(); // bump operator
     let start = self.previous().span.start;
@@ -149,13 +156,35 @@ impl<'src> Parser<'src> {
     Ok(ast::expr_unary(start..right.span.end, op, right))
   }
 
+  /// `**` is right-associative, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`. Its
+  /// exponent may have a prefix operator, as in `2 ** -1`.
+  fn pow_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
+    let left = self.postfix_expr()?;
+    if self.no_indent().is_ok() && self.bump_if(Op_StarStar) {
+      self.no_indent()?;
+      let right = self.unary_expr()?;
+      return Ok(ast::expr_binary(
+        left.span.start..right.span.end,
+        ast::BinaryOp::Pow,
+        left,
+        right,
+      ));
+    }
+    Ok(left)
+  }
+
   fn postfix_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
     let mut expr = self.primary_expr()?;
     while self.no_indent().is_ok() {
       match self.current().kind {
         Brk_ParenL => {
-          let args = self.call_args()?; // bumps `(`
-          expr = ast::expr_call(expr.span.start..self.previous().span.end, expr, args);
+          let (args, kwargs) = self.call_args()?; // bumps `(`
+          expr = ast::expr_call(
+            expr.span.start..self.previous().span.end,
+            expr,
+            args,
+            kwargs,
+          );
         }
         Brk_SquareL => {
           self.bump(); // bump `[`
@@ -191,6 +220,11 @@ impl<'src> Parser<'src> {
       return ast::lit::int(token.span, self.lex.lexeme(token));
     }
 
+    if self.bump_if(Lit_BigInt) {
+      let token = self.previous();
+      return ast::lit::bigint(token.span, self.lex.lexeme(token));
+    }
+
     if self.bump_if(Lit_Float) {
       let token = self.previous();
       return ast::lit::float(token.span, self.lex.lexeme(token));
@@ -198,10 +232,22 @@ impl<'src> Parser<'src> {
 
     if self.bump_if(Lit_String) {
       let token = self.previous();
-      match ast::lit::str(token.span, self.lex.lexeme(token)) {
-        Some(str) => return Ok(str),
-        None => fail!(@token.span, "invalid escape sequence"),
-      }
+      return ast::lit::str(token.span, self.lex.lexeme(token));
+    }
+
+    if self.bump_if(Lit_MultilineString) {
+      let token = self.previous();
+      return ast::lit::multiline_str(token.span, self.lex.lexeme(token));
+    }
+
+    if self.bump_if(Lit_RawString) {
+      let token = self.previous();
+      return Ok(ast::lit::raw_str(token.span, self.lex.lexeme(token)));
+    }
+
+    if self.bump_if(Lit_Bytes) {
+      let token = self.previous();
+      return ast::lit::bytes(token.span, self.lex.lexeme(token));
     }
 
     if self.bump_if(Brk_SquareL) {
@@ -287,10 +333,26 @@ impl<'src> Parser<'src> {
     }
 
     if self.bump_if(Brk_ParenL) {
+      let start = self.previous().span.start;
       let state = self.state.with_ignore_indent();
-      let expr = self.with_state(state, |p| p.expr())?;
-      self.expect(Brk_ParenR)?;
-      return Ok(expr);
+      return self.with_state(state, |p| {
+        // `()` is an empty tuple, `(a)` is a group, and `(a,)` is a tuple
+        let mut items = vec![];
+        if !p.current().is(Brk_ParenR) {
+          let expr = p.expr()?;
+          if !p.current().is(Tok_Comma) {
+            p.expect(Brk_ParenR)?;
+            return Ok(expr);
+          }
+          items.push(expr);
+          while p.bump_if(Tok_Comma) && !p.current().is(Brk_ParenR) {
+            items.push(p.expr()?);
+          }
+        }
+        p.expect(Brk_ParenR)?;
+        let end = p.previous().span.end;
+        Ok(ast::expr_tuple(start..end, items))
+      });
     }
 
     Err(SpannedError::new("unexpected token", self.current().span))
@@ -314,20 +376,61 @@ impl<'src> Parser<'src> {
     }
   }
 
-  fn call_args(&mut self) -> Result<Vec<ast::Expr<'src>>, SpannedError> {
+  #[allow(clippy::type_complexity)]
+  fn call_args(
+    &mut self,
+  ) -> Result<
+    (
+      Vec<ast::Expr<'src>>,
+      Vec<(ast::Ident<'src>, ast::Expr<'src>)>,
+    ),
+    SpannedError,
+  > {
     let mut args = Vec::new();
+    let mut kwargs = Vec::new();
     self.expect(Brk_ParenL)?;
     if !self.current().is(Brk_ParenR) {
       let state = self.state.with_ignore_indent();
       self.with_state(state, |p| {
-        args.push(p.expr()?);
+        p.call_arg(&mut args, &mut kwargs)?;
         while p.bump_if(Tok_Comma) && !p.current().is(Brk_ParenR) {
-          args.push(p.expr()?);
+          p.call_arg(&mut args, &mut kwargs)?;
         }
         Ok(())
       })?;
     }
     self.expect(Brk_ParenR)?;
-    Ok(args)
+    Ok((args, kwargs))
+  }
+
+  /// Parses `value` or `name=value`.
+  fn call_arg(
+    &mut self,
+    args: &mut Vec<ast::Expr<'src>>,
+    kwargs: &mut Vec<(ast::Ident<'src>, ast::Expr<'src>)>,
+  ) -> Result<(), SpannedError> {
+    let value = self.expr()?;
+    if !self.current().is(Op_Equal) {
+      if !kwargs.is_empty() {
+        fail!(@value.span, "positional argument follows keyword argument");
+      }
+      args.push(value);
+      return Ok(());
+    }
+
+    let span = value.span;
+    let ast::ExprKind::GetVar(var) = value.into_inner() else {
+      fail!(@span, "expected a name before `=`");
+    };
+    let name = var.name;
+    if kwargs
+      .iter()
+      .any(|(other, _)| other.as_ref() == name.as_ref())
+    {
+      fail!(@name.span, "duplicate keyword argument `{name}`");
+    }
+    self.bump(); // bump `=`
+    kwargs.push((name, self.expr()?));
+    Ok(())
   }
 }
@@ -37,6 +37,17 @@ impl IndentStack {
       .expect("pop_indent should not empty the indent stack");
   }
 
+  pub fn depth(&self) -> usize {
+    self.stack.len()
+  }
+
+  /// Pop levels until there are only `depth` left.
+  pub fn truncate(&mut self, depth: usize) {
+    while self.stack.len() > depth {
+      self.pop();
+    }
+  }
+
   pub fn reset(&mut self) {
     self.stack.clear();
     self.stack.push(0);
@@ -1,18 +1,23 @@
 use super::*;
 
 impl<'src> Parser<'src> {
-  pub(super) fn module(mut self) -> Result<ast::Module<'src>, Vec<SpannedError>> {
+  pub(super) fn module(self) -> Result<ast::Module<'src>, Vec<SpannedError>> {
+    let (module, errors) = self.module_recovering();
+    if !errors.is_empty() {
+      return Err(errors);
+    }
+
+    Ok(module)
+  }
+
+  pub(super) fn module_recovering(mut self) -> (ast::Module<'src>, Vec<SpannedError>) {
     while !self.current().is(Tok_Eof) {
+      let recovery_point = self.recovery_point();
       if let Err(e) = self.top_level_stmt() {
-        self.errors.push(e);
-        self.sync();
+        self.recover(e, recovery_point);
       }
     }
 
-    if !self.errors.is_empty() {
-      return Err(self.errors);
-    }
-
-    Ok(self.module)
+    (self.module, self.errors)
   }
 }
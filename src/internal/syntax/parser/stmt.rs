@@ -29,6 +29,7 @@ impl<'src> Parser<'src> {
       Kw_Loop => Some(self.loop_stmt()?),
       Kw_Fn => Some(self.func_stmt()?),
       Kw_Class => Some(self.class_stmt()?),
+      Tok_At => Some(self.decorated_stmt()?),
       Kw_Import | Kw_From => Some(self.import_stmt()?),
       _ => None,
     })
@@ -64,13 +65,25 @@ impl<'src> Parser<'src> {
     }
   }
 
-  fn import_module_path(&mut self) -> Result<Vec<ast::Ident<'src>>, SpannedError> {
+  fn import_module_path(&mut self) -> Result<ast::ImportPath<'src>, SpannedError> {
     self.no_indent()?;
-    let mut path = vec![self.ident()?];
+    // `..` is lexed as a single token
+    let mut level = 0;
+    loop {
+      if self.bump_if(Op_Dot) {
+        level += 1;
+      } else if self.bump_if(Op_Range) {
+        level += 2;
+      } else {
+        break;
+      }
+      self.no_indent()?;
+    }
+    let mut segments = vec![self.ident()?];
     while self.no_indent().is_ok() && self.bump_if(Op_Dot) {
-      path.push(self.ident()?);
+      segments.push(self.ident()?);
     }
-    Ok(path)
+    Ok(ast::ImportPath { level, segments })
   }
 
   fn import_symbol_list(&mut self) -> Result<Vec<ast::ImportSymbol<'src>>, SpannedError> {
@@ -130,16 +143,24 @@ impl<'src> Parser<'src> {
     self.expect(Kw_For)?;
     let start = self.previous().span.start;
     self.no_indent()?;
-    let item = self.ident()?;
+    let mut items = vec![self.ident()?];
     self.no_indent()?;
+    while self.bump_if(Tok_Comma) {
+      self.no_indent()?;
+      items.push(self.ident()?);
+      self.no_indent()?;
+    }
     self.expect(Kw_In)?;
     self.no_indent()?;
     let iter = self.for_iter()?;
+    if let (ast::ForIter::Range(_), [first, .., last]) = (&iter, &items[..]) {
+      fail!(@first.span.start..last.span.end, "cannot unpack the numbers of a range");
+    }
     self.no_indent()?;
     self.expect(Tok_Colon)?;
     let body = self.loop_body()?;
     let end = self.previous().span.end;
-    Ok(ast::for_loop_stmt(start..end, item, iter, body))
+    Ok(ast::for_loop_stmt(start..end, items, iter, body))
   }
 
   fn for_iter(&mut self) -> Result<ast::ForIter<'src>, SpannedError> {
@@ -190,13 +211,42 @@ impl<'src> Parser<'src> {
     Ok(body)
   }
 
+  fn decorated_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    let doc = self.doc_comment(self.current());
+    let mut decorators = vec![];
+    while self.bump_if(Tok_At) {
+      self.no_indent()?;
+      decorators.push(self.expr()?);
+      self.indent_eq()?;
+    }
+    let mut stmt = match self.current().kind {
+      Kw_Fn => self.func_stmt()?,
+      Kw_Class => self.class_stmt()?,
+      _ => fail!(@self.current().span, "expected `fn` or `class` after decorator"),
+    };
+    match &mut *stmt {
+      ast::StmtKind::Func(func) => {
+        func.decorators = decorators;
+        func.doc = doc.or(func.doc.take());
+      }
+      ast::StmtKind::Class(class) => {
+        class.decorators = decorators;
+        class.doc = doc.or(class.doc.take());
+      }
+      _ => unreachable!(),
+    }
+    Ok(stmt)
+  }
+
   fn func_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    let doc = self.doc_comment(self.current());
     self.expect(Kw_Fn)?;
     let start = self.previous().span.start;
     self.no_indent()?;
     let name = self.ident()?;
     self.no_indent()?; // func's opening paren must be unindented
-    let func = self.func(name)?;
+    let mut func = self.func(name)?;
+    func.doc = doc;
     let end = self.previous().span.end;
     Ok(ast::func_stmt(start..end, func))
   }
@@ -205,7 +255,7 @@ impl<'src> Parser<'src> {
     let params = self.func_params()?;
     self.no_indent()?;
     self.expect(Tok_Colon)?;
-    let state = self.state.with_func(name.lexeme(), params.has_self);
+    let state = self.state.with_func(&name, params.has_self);
     let (state, body) = self.with_state2(state, Self::body)?;
     let has_yield = state
       .current_func
@@ -237,6 +287,9 @@ impl<'src> Parser<'src> {
       while self.bump_if(Tok_Comma) && !self.current().is(Brk_ParenR) {
         self.param(&mut params, &mut state)?;
       }
+      if let ParamState::KeywordMarker(span) = state {
+        fail!(@span, "`*` must be followed by at least one param");
+      }
     }
     self.expect(Brk_ParenR)?;
 
@@ -248,10 +301,38 @@ impl<'src> Parser<'src> {
     params: &mut ast::Params<'src>,
     state: &mut ParamState,
   ) -> Result<(), SpannedError> {
+    if self.bump_if(Op_Star) {
+      let span = self.previous().span;
+      if matches!(state, ParamState::KeywordMarker(_) | ParamState::Keyword) {
+        fail!(@span, "duplicate `*`");
+      }
+      if !self.current().is(Tok_Comma) && !self.current().is(Brk_ParenR) {
+        fail!(@self.current().span, "expected `,` after `*`");
+      }
+      *state = ParamState::KeywordMarker(span);
+      return Ok(());
+    }
+
     let name = self.ident()?;
     if params.contains(&name) {
       fail!(@name.span, "duplicate argument `{name}`");
     }
+    let hint = self.type_hint()?;
+    if let ParamState::KeywordMarker(_) | ParamState::Keyword = state {
+      // keyword-only params are passed by name, so their order doesn't matter
+      *state = ParamState::Keyword;
+      let default = if self.bump_if(Op_Equal) {
+        Some(self.expr()?)
+      } else {
+        None
+      };
+      params.kw.push(ast::Param {
+        name,
+        hint,
+        default,
+      });
+      return Ok(());
+    }
     let default = if self.bump_if(Op_Equal) {
       *state = ParamState::Default;
       Some(self.expr()?)
@@ -266,12 +347,29 @@ impl<'src> Parser<'src> {
       None
     };
 
-    params.pos.push(ast::Param { name, default });
+    params.pos.push(ast::Param {
+      name,
+      hint,
+      default,
+    });
 
     Ok(())
   }
 
+  /// Parses the `: type` after a param or field name, if there is one.
+  fn type_hint(&mut self) -> Result<Option<ast::Ident<'src>>, SpannedError> {
+    if self.current().is(Tok_Colon) {
+      self.no_indent()?;
+      self.bump(); // bump colon
+      self.no_indent()?;
+      Ok(Some(self.ident()?))
+    } else {
+      Ok(None)
+    }
+  }
+
   fn class_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    let doc = self.doc_comment(self.current());
     self.expect(Kw_Class)?;
     let start = self.previous().span.start;
     self.no_indent()?;
@@ -285,12 +383,27 @@ impl<'src> Parser<'src> {
     } else {
       None
     };
+    let mut mixins = vec![];
+    if self.current().is(Kw_With) {
+      self.no_indent()?;
+      self.bump(); // bump `with`
+      self.no_indent()?;
+      mixins.push(self.ident()?);
+      while self.no_indent().is_ok() && self.bump_if(Tok_Comma) {
+        self.no_indent()?;
+        mixins.push(self.ident()?);
+      }
+    }
     self.no_indent()?;
     self.expect(Tok_Colon)?;
     let state = State::with_class(parent.is_some());
     let members = self.with_state(state, Self::class_members)?;
     let end = self.previous().span.end;
-    Ok(ast::class_stmt(start..end, name, parent, members))
+    let mut stmt = ast::class_stmt(start..end, name, parent, mixins, members);
+    if let ast::StmtKind::Class(class) = &mut *stmt {
+      class.doc = doc;
+    }
+    Ok(stmt)
   }
 
   fn class_members(&mut self) -> Result<ast::ClassMembers<'src>, SpannedError> {
@@ -322,47 +435,92 @@ impl<'src> Parser<'src> {
     }
 
     let mut names = HashSet::new();
+    // set when `static` is followed by `fn`, which ends the list of fields
+    let mut static_method = false;
+
+    while (self.current().is(Lit_Ident) || self.current().is(Kw_Static))
+      && indent_check!(inline, self, first = names.is_empty())
+    {
+      if self.bump_if(Kw_Static) {
+        if self.current().is(Kw_Fn) {
+          static_method = true;
+          break;
+        }
+        let name = self.ident()?;
+        if let Some(first) = names.get(&name) {
+          self.errors.push(duplicate_field(&name, first));
+        } else {
+          names.insert(name.clone());
+        }
+        self.no_indent()?; // op_equal must be unindented
+        self.expect(Op_Equal)?;
+        self.no_indent()?;
+        let default = self.expr()?;
+        members.static_fields.push(ast::Field {
+          name,
+          hint: None,
+          default,
+        });
+        self.bump_if(Tok_Semicolon);
+        continue;
+      }
 
-    while self.current().is(Lit_Ident) && indent_check!(inline, self, first = names.is_empty()) {
+      let doc = self.doc_comment(self.current());
       let name = self.ident()?;
 
       if name == "init" {
-        if members.init.is_some() {
-          self
-            .errors
-            .push(SpannedError::new("duplicate initializer", name.span));
+        if let Some(first) = &members.init {
+          self.errors.push(
+            SpannedError::new("duplicate initializer", name.span)
+              .with_label(first.name.span, "first defined here"),
+          );
         }
 
         self.no_indent()?; // func's opening paren must be unindented
-        members.init = Some(self.func(name)?);
+        let mut init = self.func(name)?;
+        init.doc = doc;
+        members.init = Some(init);
       } else {
-        if names.contains(&name) {
-          self.errors.push(SpannedError::new(
-            format!("duplicate field {name}"),
-            name.span,
-          ));
+        if let Some(first) = names.get(&name) {
+          self.errors.push(duplicate_field(&name, first));
         } else {
           names.insert(name.clone());
         }
 
+        let hint = self.type_hint()?;
         self.no_indent()?; // op_equal must be unindented
         self.expect(Op_Equal)?;
         self.no_indent()?;
         let default = self.expr()?;
-        members.fields.push(ast::Field { name, default });
+        members.fields.push(ast::Field {
+          name,
+          hint,
+          default,
+        });
         self.bump_if(Tok_Semicolon);
       }
     }
 
-    while self.current().is(Kw_Fn) && indent_check!(inline, self, first = names.is_empty()) {
+    while static_method
+      || ((self.current().is(Kw_Fn) || self.current().is(Kw_Static))
+        && indent_check!(inline, self, first = names.is_empty()))
+    {
+      // after `static fn`, the `static` was already consumed by the field loop
+      let doc = self.doc_comment(if static_method {
+        self.previous()
+      } else {
+        self.current()
+      });
+      let is_static = static_method || self.bump_if(Kw_Static);
+      static_method = false;
+      if is_static && !self.current().is(Kw_Fn) {
+        fail!(@self.current().span, "fields may not appear after methods");
+      }
       self.expect(Kw_Fn)?;
 
       let name = self.ident()?;
-      if names.contains(&name) {
-        self.errors.push(SpannedError::new(
-          format!("duplicate field {name}"),
-          name.span,
-        ));
+      if let Some(first) = names.get(&name) {
+        self.errors.push(duplicate_field(&name, first));
       } else if name == "init" {
         self.errors.push(SpannedError::new(
           "\
@@ -376,8 +534,19 @@ impl<'src> Parser<'src> {
         names.insert(name.clone());
       }
       self.no_indent()?; // func's opening paren must be unindented
-      let f = self.func(name)?;
-      members.methods.push(f);
+      let mut f = self.func(name)?;
+      f.doc = doc;
+      if is_static {
+        if f.params.has_self {
+          self.errors.push(SpannedError::new(
+            "static methods may not take `self`",
+            f.name.span,
+          ));
+        }
+        members.static_methods.push(f);
+      } else {
+        members.methods.push(f);
+      }
       self.bump_if(Tok_Semicolon);
     }
 
@@ -410,9 +579,18 @@ impl<'src> Parser<'src> {
     } else {
       self.indent_gt()?;
 
-      let mut body = vec![self.stmt()?];
-      while self.indent_eq().is_ok() && !self.current().is(Tok_Eof) {
-        body.push(self.stmt()?);
+      // errors are recovered from at every statement, so that all of them
+      // are reported, not only the first one in the block
+      let mut body = vec![];
+      loop {
+        let recovery_point = self.recovery_point();
+        match self.stmt() {
+          Ok(stmt) => body.push(stmt),
+          Err(e) => self.recover(e, recovery_point),
+        }
+        if self.indent_eq().is_err() || self.current().is(Tok_Eof) {
+          break;
+        }
       }
 
       self.dedent()?;
@@ -431,6 +609,8 @@ impl<'src> Parser<'src> {
       Kw_Return => self.return_stmt(),
       Kw_Continue => self.continue_stmt(),
       Kw_Break => self.break_stmt(),
+      Kw_Raise => self.raise_stmt(),
+      Kw_Defer => self.defer_stmt(),
       Kw_Yield => self.yield_().map(ast::yield_stmt),
       Kw_Print => self.print_stmt(),
       _ => self.expr_stmt(),
@@ -468,7 +648,7 @@ impl<'src> Parser<'src> {
 
   fn continue_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
     if self.state.current_loop.is_none() {
-      fail!(@self.current().span, "continue outside of loop");
+      return Err(self.outside_of_loop("continue"));
     }
 
     self.expect(Kw_Continue)?;
@@ -477,13 +657,56 @@ impl<'src> Parser<'src> {
 
   fn break_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
     if self.state.current_loop.is_none() {
-      fail!(@self.current().span, "break outside of loop");
+      return Err(self.outside_of_loop("break"));
     }
 
     self.expect(Kw_Break)?;
     Ok(ast::break_stmt(self.previous().span))
   }
 
+  fn outside_of_loop(&self, keyword: &str) -> SpannedError {
+    let error = SpannedError::new(format!("{keyword} outside of loop"), self.current().span);
+    match &self.state.current_func {
+      Some(func) if func.in_loop => error
+        .with_label(
+          func.span,
+          format!("the loop is outside of function `{}`", func.name),
+        )
+        .with_note(format!("`{keyword}` may not cross function boundaries")),
+      _ => error.with_help(format!(
+        "`{keyword}` may only appear inside of `for`, `while`, or `loop`"
+      )),
+    }
+  }
+
+  fn raise_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    self.expect(Kw_Raise)?;
+    let start = self.previous().span.start;
+    self.no_indent()?;
+    let value = self.expr()?;
+    let end = self.previous().span.end;
+    Ok(ast::raise_stmt(start..end, value))
+  }
+
+  fn defer_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    if self.state.current_func.is_none() {
+      fail!(@self.current().span, "defer outside of function");
+    }
+
+    self.expect(Kw_Defer)?;
+    let start = self.previous().span.start;
+    self.no_indent()?;
+    let value = self.expr()?;
+    let span = value.span;
+    let ast::ExprKind::Call(call) = value.into_inner() else {
+      fail!(@span, "expected a call after `defer`");
+    };
+    if let Some((name, _)) = call.kwargs.first() {
+      fail!(@name.span, "`defer` does not support keyword arguments");
+    }
+    Ok(ast::defer_stmt(start..span.end, *call))
+  }
+
   fn print_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
     self.expect(Kw_Print)?;
     let start = self.previous().span;
@@ -509,6 +732,9 @@ impl<'src> Parser<'src> {
 
   fn assign_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
     let target = self.expr()?;
+    if self.current().is(Tok_Comma) && self.no_indent().is_ok() {
+      return self.unpack_stmt(target);
+    }
 
     'assign: {
       if self.no_indent().is_ok() {
@@ -532,6 +758,41 @@ impl<'src> Parser<'src> {
     Ok(ast::expr_stmt(target))
   }
 
+  fn unpack_stmt(&mut self, first: ast::Expr<'src>) -> Result<ast::Stmt<'src>, SpannedError> {
+    let mut targets = vec![first];
+    while self.bump_if(Tok_Comma) {
+      self.no_indent()?;
+      targets.push(self.expr()?);
+      self.no_indent()?;
+    }
+    let error_span = targets[0].span.start..self.current().span.end;
+    let Some(kind) = self.assign_kind() else {
+      fail!(@self.current().span, "expected `:=` or `=`");
+    };
+    self.no_indent()?;
+    let value = self.expr()?;
+    // `a, b = b, a` is the same as `a, b = (b, a)`
+    let value = if self.current().is(Tok_Comma) && self.no_indent().is_ok() {
+      let start = value.span.start;
+      let mut items = vec![value];
+      while self.bump_if(Tok_Comma) {
+        self.no_indent()?;
+        items.push(self.expr()?);
+      }
+      ast::expr_tuple(start..self.previous().span.end, items)
+    } else {
+      value
+    };
+    let Some(stmt) = ast::unpack(targets, kind, value) else {
+      let msg = match kind {
+        ast::AssignKind::Op(Some(_)) => "cannot unpack into a compound assignment",
+        _ => "only variables may be unpacked into",
+      };
+      fail!(@error_span, "{msg}");
+    };
+    Ok(stmt)
+  }
+
   fn assign_kind(&mut self) -> Option<ast::AssignKind> {
     let kind = match self.current().kind {
       Op_ColonEqual => ast::AssignKind::Decl,
@@ -539,6 +800,7 @@ impl<'src> Parser<'src> {
       Op_PlusEqual => ast::AssignKind::Op(Some(ast::AssignOp::Add)),
       Op_MinusEqual => ast::AssignKind::Op(Some(ast::AssignOp::Sub)),
       Op_SlashEqual => ast::AssignKind::Op(Some(ast::AssignOp::Div)),
+      Op_SlashSlashEqual => ast::AssignKind::Op(Some(ast::AssignOp::FloorDiv)),
       Op_StarEqual => ast::AssignKind::Op(Some(ast::AssignOp::Mul)),
       Op_PercentEqual => ast::AssignKind::Op(Some(ast::AssignOp::Rem)),
       Op_StarStarEqual => ast::AssignKind::Op(Some(ast::AssignOp::Pow)),
@@ -561,4 +823,12 @@ fn extend_path<'src>(p: &Vec<ast::Ident<'src>>, v: ast::Ident<'src>) -> Vec<ast:
 enum ParamState {
   Positional,
   Default,
+  /// Just after the `*`, at the given span.
+  KeywordMarker(Span),
+  Keyword,
+}
+
+fn duplicate_field(name: &ast::Ident, first: &ast::Ident) -> SpannedError {
+  SpannedError::new(format!("duplicate field {name}"), name.span)
+    .with_label(first.span, "first defined here")
 }
@@ -136,6 +136,85 @@ fn import_stmt() {
   };
 }
 
+check_module! {
+  relative_import_stmt,
+  r#"#!hebi
+    import .a
+    import ..a.b as c
+    from .a import x
+    from ...a.b import y
+  "#
+}
+
+/// Write `e` with every operator and its operands in parentheses.
+fn sexpr(e: &ast::Expr) -> String {
+  match &**e {
+    ast::ExprKind::Binary(v) => format!("({:?} {} {})", v.op, sexpr(&v.left), sexpr(&v.right)),
+    ast::ExprKind::Unary(v) => format!("({:?} {})", v.op, sexpr(&v.right)),
+    ast::ExprKind::Literal(v) => match v.as_ref() {
+      ast::Literal::Int(v) => v.to_string(),
+      v => format!("{v:?}"),
+    },
+    ast::ExprKind::GetVar(v) => v.name.to_string(),
+    ast::ExprKind::GetField(v) => format!("{}.{}", sexpr(&v.target), v.name),
+    ast::ExprKind::Call(v) => {
+      let args = v.args.iter().map(sexpr).collect::<Vec<_>>();
+      format!("{}({})", sexpr(&v.target), args.join(", "))
+    }
+    e => format!("{e:?}"),
+  }
+}
+
+#[test]
+fn precedence() {
+  let inputs = [
+    "-2 ** 2",
+    "(-2) ** 2",
+    "2 ** -1",
+    "2 ** 3 ** 2",
+    "(2 ** 3) ** 2",
+    "-a.b ** 2",
+    "-f(x) ** 2",
+    "!a ** b",
+    "--a ** b",
+    "a ** -b ** c",
+    "a * b ** c",
+    "a ** b * c",
+    "-a * b",
+    "a + b * c - d",
+    "a - b - c",
+    "a / b // c % d",
+    "a + -b ** 2",
+    "a == b < c",
+    "a && b || c ?? d",
+    "a in b && !c",
+  ];
+  let mut out = String::new();
+  for input in inputs {
+    let global = Global::default();
+    let e = match Parser::new(global, Lexer::new(input)).expr() {
+      Ok(e) => e,
+      Err(e) => panic!("{}", e.report(input, true)),
+    };
+    out.push_str(&format!("{input:<18}{}\n", sexpr(&e)));
+  }
+  insta::assert_snapshot!(out);
+}
+
+#[test]
+fn floor_div_expr() {
+  check_expr!(r#"a // b"#);
+  check_expr!(r#"a + b // c % d"#);
+
+  check_module! {
+    r#"#!hebi
+      a //= b
+      a.b //= b
+      a // b # comment
+    "#
+  };
+}
+
 #[test]
 fn binary_expr() {
   check_expr!(r#"a + b"#);
@@ -179,6 +258,19 @@ fn unary_expr() {
   check_expr!(r#"?a.b[c].d()"#);
 }
 
+#[test]
+fn conditional_expr() {
+  check_expr!(r#"a if b else c"#);
+  check_expr!(r#"a + 1 if b || c else d ?? e"#);
+  check_expr!(r#"a if b else c if d else e"#);
+
+  check_error! {
+    r#"#!hebi
+      v := a if b
+    "#
+  }
+}
+
 #[test]
 fn postfix_expr() {
   check_expr!(r#"a.b[c].d"#);
@@ -237,6 +329,82 @@ fn simple_literal_expr() {
   }
 }
 
+#[test]
+fn string_escapes() {
+  check_module! {
+    r#"#!hebi
+      "\u{1F600}\x41\0\e\"\\"
+      r"\d+\n"
+      r""
+      b"\xff\u{e9}"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      "a\qb"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      "\x4"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      b"\xzz"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      "ok \u{110000}"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      "\u41"
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      "\u{12"
+    "#
+  }
+}
+
+#[test]
+fn multiline_string() {
+  check_module! {
+    r#"#!hebi
+      fn f():
+        return """
+          a "quoted" \u{41}
+            indented
+
+          """
+      """single line"""
+      """first
+        second
+          third"""
+      """"""
+    "#
+  }
+
+  check_error! {
+    r#"#!hebi
+      """
+        ok
+        \q
+      """
+    "#
+  }
+}
+
 #[test]
 fn array_literal_expr() {
   check_module! {
@@ -254,6 +422,69 @@ fn array_literal_expr() {
   }
 }
 
+#[test]
+fn type_hints() {
+  check_module! {
+    r#"#!hebi
+      fn f(a: int, b: String = "", c = 0):
+        pass
+      class T:
+        v: float = 0.0
+        w = none
+        init(self, v: float):
+          self.v = v
+    "#
+  }
+}
+
+#[test]
+fn class_statics() {
+  check_module! {
+    r#"#!hebi
+      class T:
+        v = 0
+        static count = 0
+        static fn make():
+          T.count += 1
+          return T()
+        fn get(self):
+          return self.v
+    "#
+  }
+  check_error! {
+    r#"#!hebi
+      class T:
+        static fn f(self): pass
+    "#
+  }
+}
+
+#[test]
+fn class_mixins() {
+  check_module! {
+    r#"#!hebi
+      class T with M: pass
+      class U(T) with M, N:
+        v = 0
+    "#
+  }
+}
+
+#[test]
+fn tuple_literal_expr() {
+  check_module! {
+    r#"#!hebi
+      ()
+      (0,)
+      (0, 1, 2)
+      (0,
+       1,
+       2,)
+      (0)
+    "#
+  }
+}
+
 #[test]
 fn table_literal_expr() {
   check_module! {
@@ -537,6 +768,23 @@ fn loop_stmts() {
   }
 }
 
+#[test]
+fn unpack_stmts() {
+  check_module! {
+    r#"#!hebi
+      a, b := c
+      a, b = (b, a)
+      a, b = b, a
+      for k, v in pairs(): pass
+    "#
+  }
+
+  check_error!(r#"a, b += c"#);
+  check_error!(r#"a.b, c := d"#);
+  check_error!(r#"a, b"#);
+  check_error!(r#"for a, b in 0..10: pass"#);
+}
+
 #[test]
 fn func_stmt() {
   check_module! {
@@ -570,6 +818,45 @@ fn func_stmt() {
   }
 }
 
+#[test]
+fn kw_params() {
+  check_module! {
+    r#"#!hebi
+      fn f(a, *, b): pass
+      fn f(a=0, *, b=1, c,): pass
+      fn f(*, a: int, b): pass
+    "#
+  }
+
+  check_error!(r#"fn f(a, *): pass"#);
+  check_error!(r#"fn f(a, *, *, b): pass"#);
+  check_error!(r#"fn f(a, *, b, a): pass"#);
+}
+
+#[test]
+fn kw_args() {
+  check_module! {
+    r#"#!hebi
+      f(a, b=c)
+      f(b=c, d=e(f=g),)
+      f(
+        a,
+        b = c,
+      )
+    "#
+  }
+
+  check_error!(r#"f(a=b, c)"#);
+  check_error!(r#"f(a=b, a=c)"#);
+  check_error!(r#"f(a.b=c)"#);
+  check_error! {
+    r#"#!hebi
+      fn f():
+        defer g(a=b)
+    "#
+  }
+}
+
 #[test]
 fn ctrl_stmt() {
   check_module! {
@@ -673,6 +960,14 @@ fn ctrl_stmt() {
       break
     "#
   }
+
+  check_error! {
+    r#"#!hebi
+      loop:
+        fn f():
+          break
+    "#
+  }
 }
 
 #[test]
@@ -759,6 +1054,57 @@ check_error! {
   "#
 }
 
+check_module! {
+  decorated_stmts,
+  r#"#!hebi
+    @memo
+    fn f(n): pass
+    @a.b(c)
+    @d
+    class T: pass
+  "#
+}
+
+check_module! {
+  doc_comments,
+  r#"#!hebi
+    ## Computes `f`.
+    ##
+    # not part of the doc
+    ##   indented
+    fn f(n): pass
+
+    ## memoized
+    @memo
+    fn g(): pass
+
+    v := 0 ## not a doc comment
+    class T:
+      ## creates a `T`
+      init(self): pass
+      ## a method
+      fn m(self): pass
+      ## a static method
+      static fn s(): pass
+  "#
+}
+
+check_error! {
+  bad_decorated_stmt,
+  r#"#!hebi
+    @memo
+    v := 0
+  "#
+}
+
+check_error! {
+  bad_decorated_stmt_indented,
+  r#"#!hebi
+    @memo
+      fn f(n): pass
+  "#
+}
+
 #[test]
 fn class_self_and_super() {
   check_module! {
@@ -880,6 +1226,13 @@ fn duplicate_fields() {
         fn a(): pass
     "#
   }
+  check_error! {
+    r#"#!hebi
+      class Test:
+        init(self): pass
+        init(self): pass
+    "#
+  }
 }
 
 #[test]
@@ -1339,3 +1692,36 @@ check_error! {
       print y
   "#
 }
+
+check_error! {
+  recover_at_statements,
+  r#"#!hebi
+    a = )
+    b := 1
+    fn f():
+      c = ]
+      d := 2
+      if e:
+        f = }
+      elif g
+        pass
+      h := [
+        1 2,
+      ]
+    i := 3 +
+    j := 4
+  "#
+}
+
+check_error! {
+  recover_skips_failed_if,
+  r#"#!hebi
+    if a b:
+      pass
+    elif c:
+      pass
+    else:
+      pass
+    d = )
+  "#
+}
@@ -12,6 +12,47 @@ pub mod constant;
 
 use std::fmt::{Debug, Display};
 
+use super::object::int::BoxedInt;
+
+impl Value {
+  /// Returns the value as an `i64` if it is an int, whether it is stored
+  /// inline or boxed.
+  pub fn to_i64(&self) -> Option<i64> {
+    if self.is_int() {
+      return Some(unsafe { self.clone().to_int_unchecked() } as i64);
+    }
+    self.clone().to_object::<BoxedInt>().map(|v| v.value())
+  }
+
+  /// Returns the value as an `f64` if it is a float or an int.
+  pub fn to_number(&self) -> Option<f64> {
+    if self.is_float() {
+      return Some(unsafe { self.clone().to_float_unchecked() });
+    }
+    self.to_i64().map(|v| v as f64)
+  }
+}
+
+/// Write a float the way scripts see it.
+///
+/// A float is written as the shortest string which parses back to the same
+/// number, and always looks like a float: `1.0` rather than `1`. Numbers with
+/// a magnitude of at least `1e16` or less than `1e-4` are written in
+/// scientific notation, such as `1e16` or `1.5e-7`. The special values are
+/// written as `inf`, `-inf` and `NaN`.
+///
+/// If the formatter has a precision, as in `{:.2}`, exactly that many decimal
+/// places are written instead.
+///
+/// This is used everywhere a value is turned into a string, so `print`,
+/// `str`, `join` and string interpolation all agree.
+pub fn fmt_float(v: f64, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+  match f.precision() {
+    Some(precision) => write!(f, "{v:.precision$}"),
+    None => write!(f, "{v:?}"),
+  }
+}
+
 impl Default for Value {
   fn default() -> Self {
     Self::none()
@@ -22,7 +63,7 @@ impl Display for Value {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let v = self.clone();
     if let Some(v) = v.clone().to_float() {
-      write!(f, "{v}")?;
+      fmt_float(v, f)?;
     } else if let Some(v) = v.clone().to_int() {
       write!(f, "{v}")?;
     } else if let Some(v) = v.clone().to_bool() {
@@ -44,7 +85,7 @@ impl Debug for Value {
     let v = self.clone();
     if let Some(v) = v.clone().to_float() {
       f.debug_tuple("Float").field(&v).finish()
-    } else if let Some(v) = v.clone().to_int() {
+    } else if let Some(v) = v.to_i64() {
       f.debug_tuple("Int").field(&v).finish()
     } else if let Some(v) = v.clone().to_bool() {
       f.debug_tuple("Bool").field(&v).finish()
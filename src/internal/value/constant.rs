@@ -1,13 +1,21 @@
 #![allow(clippy::wrong_self_convention)]
 
+#[cfg(feature = "bigint")]
+use std::fmt::Debug;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
 
 use super::Value;
 use crate::internal::bytecode::opcode as op;
+#[cfg(feature = "bigint")]
+use crate::internal::object::bigint::BigInt;
+use crate::internal::object::int::BoxedInt;
 use crate::internal::object::ptr::Ptr;
-use crate::internal::object::{ClassDescriptor, FunctionDescriptor, Str};
+use crate::internal::object::{Bytes, ClassDescriptor, FunctionDescriptor, Str};
 
 #[derive(Debug, Clone)]
 pub enum Constant {
@@ -16,7 +24,13 @@ pub enum Constant {
   Function(Ptr<FunctionDescriptor>),
   Class(Ptr<ClassDescriptor>),
   Offset(op::Offset),
+  JumpTable(Rc<JumpTable>),
+  KwNames(Rc<KwNames>),
   Float(NonNaNFloat),
+  Int(Ptr<BoxedInt>),
+  Bytes(Ptr<Bytes>),
+  #[cfg(feature = "bigint")]
+  BigInt(Ptr<BigInt>),
 }
 
 impl Constant {
@@ -29,7 +43,13 @@ impl Constant {
       Constant::Function(v) => Value::object(v),
       Constant::Class(v) => Value::object(v),
       Constant::Offset(_) => panic!("cannot convert constant jump offset to value"),
+      Constant::JumpTable(_) => panic!("cannot convert constant jump table to value"),
+      Constant::KwNames(_) => panic!("cannot convert constant keyword names to value"),
       Constant::Float(v) => Value::float(v.value()),
+      Constant::Int(v) => Value::object(v),
+      Constant::Bytes(v) => Value::object(v),
+      #[cfg(feature = "bigint")]
+      Constant::BigInt(v) => Value::object(v),
     }
   }
 }
@@ -43,6 +63,22 @@ impl Constant {
     }
   }
 
+  pub fn as_jump_table(&self) -> Option<&JumpTable> {
+    if let Self::JumpTable(v) = self {
+      Some(v)
+    } else {
+      None
+    }
+  }
+
+  pub fn as_kw_names(&self) -> Option<&KwNames> {
+    if let Self::KwNames(v) = self {
+      Some(v)
+    } else {
+      None
+    }
+  }
+
   #[allow(dead_code)] // used in tests
   pub fn as_float(&self) -> Option<&NonNaNFloat> {
     if let Self::Float(v) = self {
@@ -61,11 +97,90 @@ impl Display for Constant {
       Constant::Function(v) => Display::fmt(v, f),
       Constant::Class(v) => Display::fmt(v, f),
       Constant::Offset(v) => Display::fmt(&v.0, f),
+      Constant::JumpTable(v) => Display::fmt(v, f),
+      Constant::KwNames(v) => Display::fmt(v, f),
       Constant::Float(v) => Display::fmt(&v.0, f),
+      Constant::Int(v) => Display::fmt(v, f),
+      Constant::Bytes(v) => Display::fmt(v, f),
+      #[cfg(feature = "bigint")]
+      Constant::BigInt(v) => Debug::fmt(v, f),
     }
   }
 }
 
+/// The targets of a `switch` instruction, keyed by the string which selects
+/// them. Offsets are relative to the start of the instruction.
+#[derive(Debug, Clone, Default)]
+pub struct JumpTable {
+  cases: IndexMap<String, op::Offset>,
+}
+
+impl JumpTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a case, returns `false` if there already is one for `key`.
+  pub fn insert(&mut self, key: impl Into<String>, offset: op::Offset) -> bool {
+    self.cases.insert(key.into(), offset).is_none()
+  }
+
+  pub fn get(&self, key: &str) -> Option<op::Offset> {
+    self.cases.get(key).copied()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, op::Offset)> + '_ {
+    self
+      .cases
+      .iter()
+      .map(|(key, offset)| (key.as_str(), *offset))
+  }
+}
+
+impl Display for JumpTable {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{{")?;
+    for (i, (key, offset)) in self.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{key:?}: {offset}")?;
+    }
+    write!(f, "}}")
+  }
+}
+
+/// The names of the keyword arguments of a `call_kw` instruction, in the
+/// order in which their values follow the positional arguments.
+#[derive(Debug, Clone, Default)]
+pub struct KwNames {
+  names: Vec<String>,
+}
+
+impl KwNames {
+  pub fn new(names: Vec<String>) -> Self {
+    Self { names }
+  }
+
+  pub fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+    self.names.iter().map(|name| name.as_str())
+  }
+}
+
+impl Display for KwNames {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "({})", self.names.join(", "))
+  }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct NonNaNFloat(f64);
 
@@ -108,3 +108,22 @@ fn create_value_from_qnan() {
     0b01111111_11111100_00000000_00000000_00000000_00000000_00000000_00000000,
   ));
 }
+
+#[test]
+fn float_display() {
+  let cases = [
+    (1.0, "1.0"),
+    (-0.0, "-0.0"),
+    (0.1 + 0.2, "0.30000000000000004"),
+    (1500000000.0, "1500000000.0"),
+    (1e16, "1e16"),
+    (1.5e-7, "1.5e-7"),
+    (f64::INFINITY, "inf"),
+    (f64::NEG_INFINITY, "-inf"),
+  ];
+  for (v, expected) in cases {
+    assert_eq!(Value::float(v).to_string(), expected);
+    assert_eq!(expected.parse::<f64>().unwrap(), v);
+  }
+  assert_eq!(format!("{:.2}", Value::float(2.0 / 3.0)), "0.67");
+}
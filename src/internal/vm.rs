@@ -1,32 +1,54 @@
 #![allow(clippy::new_without_default)]
 
+pub mod allocator;
+pub mod cancel;
+#[cfg(feature = "coverage")]
+pub mod coverage;
 pub mod dispatch;
+pub mod format;
+pub mod frozen;
 pub mod global;
+pub mod host;
+pub mod policy;
+#[cfg(feature = "profiler")]
+pub mod profiler;
+pub mod program;
+pub mod safepoint;
+pub mod snapshot;
 pub mod thread;
+pub mod transfer;
 
 use std::fmt::Debug;
-use std::future::Future;
 use std::ptr::NonNull;
+use std::rc::Rc;
+use std::task::{Context, Poll};
 
 use global::Global;
 use module::Module;
 
-use self::global::{Input, Output};
-use self::thread::{Stack, Thread};
+use self::allocator::Allocator;
+use self::global::{Input, Output, PrintHook};
+use self::host::HostDispatcher;
+use self::policy::SecurityPolicy;
+use self::safepoint::SafepointHook;
+use self::thread::{Stack, Thread, DEFAULT_FRAME_RESERVE, DEFAULT_STACK_RESERVE};
 use super::error::{Error, Result};
 use super::object::function::Disassembly;
-use super::object::module::{ModuleId, ModuleLoader};
+use super::object::module::{AnyModuleLoader, ModuleId};
+use super::object::native::LocalBoxFuture;
 use super::object::{builtin, module, Any, Function, List, Ptr, Str};
 use super::value::Value;
 use super::{codegen, syntax};
 use crate::public::NativeModule;
-use crate::span::SpannedError;
+use crate::span::{Span, SpannedError};
 use crate::Cow;
 
 pub struct Vm {
   pub(crate) global: Global,
   pub(crate) root: Thread,
   pub(crate) stack: NonNull<Stack>,
+  /// The evaluation started by `start`, which is driven by `poll`.
+  pending: Option<LocalBoxFuture<'static, Result<Value>>>,
 }
 
 impl Debug for Vm {
@@ -42,7 +64,7 @@ struct DefaultModuleLoader {}
 
 impl module::ModuleLoader for DefaultModuleLoader {
   // TODO: return user error
-  fn load(&self, path: &str) -> Result<Cow<'static, str>> {
+  fn load(&self, path: &module::ModulePath) -> Result<Cow<'static, str>> {
     Err(Error::Vm(SpannedError::new(
       format!("failed to load module {path}"),
       0..0,
@@ -50,30 +72,81 @@ impl module::ModuleLoader for DefaultModuleLoader {
   }
 }
 
+/// What happens when integer arithmetic overflows the 64-bit range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntOverflow {
+  /// The result is computed as a float instead.
+  #[default]
+  Promote,
+  /// The result is clamped to `i64::MIN..=i64::MAX`.
+  Saturate,
+  /// A runtime error is raised.
+  Error,
+}
+
 pub struct Config {
-  pub module_loader: Option<Box<dyn ModuleLoader>>,
+  pub module_loader: Option<AnyModuleLoader>,
   pub input: Option<Box<dyn Input>>,
   pub output: Option<Box<dyn Output>>,
+  pub error_output: Option<Box<dyn Output>>,
+  pub print_hook: Option<Box<dyn PrintHook>>,
+  pub safepoint_hook: Option<Box<dyn SafepointHook>>,
+  pub security_policy: Option<Box<dyn SecurityPolicy>>,
+  pub host_dispatcher: Option<Box<dyn HostDispatcher>>,
+  pub allocator: Option<Box<dyn Allocator>>,
+  pub int_overflow: IntOverflow,
+  pub optimize: bool,
+  pub type_checks: bool,
+  /// The initial capacity of the register stack.
+  pub stack_reserve: usize,
+  /// The initial capacity of the call stack.
+  pub frame_reserve: usize,
+  /// The contents of the `argv` builtin.
+  pub args: Vec<String>,
+}
+
+/// The module loader and streams of a [`Config`], with the defaults filled
+/// in for the ones which weren't set.
+struct ResolvedConfig {
+  module_loader: AnyModuleLoader,
+  input: Box<dyn Input>,
+  output: Box<dyn Output>,
+  error_output: Box<dyn Output>,
 }
 
 impl Config {
-  fn resolve(self) -> (Box<dyn ModuleLoader>, Box<dyn Input>, Box<dyn Output>) {
-    (
-      self
+  fn resolve(self) -> ResolvedConfig {
+    ResolvedConfig {
+      module_loader: self
         .module_loader
-        .unwrap_or_else(|| Box::new(DefaultModuleLoader {})),
-      self.input.unwrap_or_else(|| Box::new(std::io::stdin())),
-      self.output.unwrap_or_else(|| Box::new(std::io::stdout())),
-    )
+        .unwrap_or_else(|| AnyModuleLoader::Sync(Box::new(DefaultModuleLoader {}))),
+      input: self.input.unwrap_or_else(|| Box::new(std::io::stdin())),
+      output: self.output.unwrap_or_else(|| Box::new(std::io::stdout())),
+      error_output: self
+        .error_output
+        .unwrap_or_else(|| Box::new(std::io::stderr())),
+    }
   }
 }
 
 impl Default for Config {
   fn default() -> Self {
     Self {
-      module_loader: Some(Box::new(DefaultModuleLoader {})),
+      module_loader: Some(AnyModuleLoader::Sync(Box::new(DefaultModuleLoader {}))),
       input: Some(Box::new(std::io::stdin())),
       output: Some(Box::new(std::io::stdout())),
+      error_output: Some(Box::new(std::io::stderr())),
+      print_hook: None,
+      safepoint_hook: None,
+      security_policy: None,
+      host_dispatcher: None,
+      allocator: None,
+      int_overflow: IntOverflow::default(),
+      optimize: false,
+      type_checks: false,
+      stack_reserve: DEFAULT_STACK_RESERVE,
+      frame_reserve: DEFAULT_FRAME_RESERVE,
+      args: Vec::new(),
     }
   }
 }
@@ -86,14 +159,16 @@ impl Default for Vm {
 
 impl Vm {
   pub fn with_config(config: Config) -> Self {
+    let stack = Stack::with_capacity(config.stack_reserve, config.frame_reserve);
     let global = Global::new(config);
     builtin::register_builtin_functions(&global);
-    let stack = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(Stack::new()))) };
+    let stack = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(stack))) };
     let root = Thread::new(global.clone(), stack);
     Self {
       global,
       root,
       stack,
+      pending: None,
     }
   }
 
@@ -105,24 +180,71 @@ impl Vm {
   pub fn compile(&self, code: &str) -> Result<Chunk> {
     let ast = syntax::parse(self.global.clone(), code).map_err(Error::Syntax)?;
     let module = codegen::emit(self.global.clone(), &ast, "__main__", true);
+    #[cfg(feature = "coverage")]
+    self
+      .global
+      .coverage()
+      .add_module("__main__", code, &module.root);
     let module_id = ModuleId::global();
     let upvalues = self.global.alloc(List::new());
     let main = module.root.clone();
-    let main = self.global.alloc(Function::new(main, upvalues, module_id));
+    let main = self.global.alloc(Function::new(
+      main,
+      module.pool.clone(),
+      upvalues,
+      module_id,
+    ));
 
-    Ok(Chunk { main })
+    Ok(Chunk {
+      main,
+      source: Rc::from(code),
+    })
   }
 
   pub async fn entry(&mut self, chunk: Chunk) -> Result<Value> {
-    self.root.entry(chunk.main).await
+    self.check_not_pending()?;
+    let result = self.root.entry(chunk.main).await;
+    self.root.run_finalizers(false).await;
+    result
+  }
+
+  pub async fn call(&mut self, callable: Ptr<Any>, args: &[Value]) -> Result<Value> {
+    self.check_not_pending()?;
+    self.root.call(callable, args).await
+  }
+
+  /// Begin running `chunk`, without driving it. The evaluation only makes
+  /// progress when `poll` is called.
+  pub fn start(&mut self, chunk: Chunk) -> Result<()> {
+    self.check_not_pending()?;
+    // the thread shares the stack with `root`, which is not used while the
+    // evaluation is pending
+    let mut thread = Thread::new(self.global.clone(), self.stack);
+    self.pending = Some(Box::pin(async move {
+      let result = thread.entry(chunk.main).await;
+      thread.run_finalizers(false).await;
+      result
+    }));
+    Ok(())
   }
 
-  pub fn call<'a>(
-    &'a mut self,
-    callable: Ptr<Any>,
-    args: &'a [Value],
-  ) -> impl Future<Output = Result<Value>> + 'a {
-    self.root.call(callable, args)
+  /// Drive the evaluation started by `start`. If it is waiting on a native
+  /// future, that future is polled with `cx`, so it wakes the task when the
+  /// evaluation can make progress again.
+  pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<Value>> {
+    let Some(pending) = self.pending.as_mut() else {
+      return Poll::Ready(Err(error!("there is no evaluation in progress").into()));
+    };
+    let result = std::task::ready!(pending.as_mut().poll(cx));
+    self.pending = None;
+    Poll::Ready(result)
+  }
+
+  fn check_not_pending(&self) -> Result<()> {
+    if self.pending.is_some() {
+      fail!("cannot run code while another evaluation is in progress");
+    }
+    Ok(())
   }
 
   pub fn register(&mut self, module: &NativeModule) {
@@ -134,7 +256,10 @@ impl Vm {
       module,
       module_id,
     ));
-    self.root.global.define_module(module_id, name, module);
+    self
+      .root
+      .global
+      .define_module(module_id, name, module, Span::default());
     self.root.global.finish_module(module_id, true);
   }
 }
@@ -142,16 +267,41 @@ impl Vm {
 #[derive(Clone)]
 pub struct Chunk {
   main: Ptr<Function>,
+  source: Rc<str>,
 }
 
 impl Chunk {
+  /// A chunk which runs `main`, which was not compiled from source code.
+  pub fn from_function(main: Ptr<Function>) -> Self {
+    Self {
+      main,
+      source: Rc::from(""),
+    }
+  }
+
   pub fn disassemble(&self) -> Disassembly {
     self.main.descriptor.disassemble()
   }
+
+  pub fn disassemble_with_source(&self) -> Disassembly<'_> {
+    self.main.descriptor.disassemble_with_source(&self.source)
+  }
 }
 
 impl Drop for Vm {
   fn drop(&mut self) {
+    // an unfinished evaluation still holds values on the stack
+    if self.pending.take().is_some() {
+      let stack = unsafe { self.stack.as_mut() };
+      stack.frames.clear();
+      stack.regs.clear();
+    }
+    // `drop` can't be async, so finalizers which await anything that needs a
+    // runtime (such as a tokio timer) never finish here, see
+    // `NativeClassBuilder::finalizer`
+    if !std::thread::panicking() {
+      pollster::block_on(self.root.run_finalizers(true));
+    }
     let _ = unsafe { Box::from_raw(self.stack.as_ptr()) };
   }
 }
@@ -0,0 +1,64 @@
+//! Custom allocators for script objects.
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+
+/// Allocates the memory of the objects created by a VM, such as strings,
+/// lists, tables, functions and class instances.
+///
+/// Each object is freed through the allocator it was allocated with, which
+/// is kept alive until all of its objects are freed. Objects which are part
+/// of a reference cycle are never freed.
+///
+/// ```rust
+/// use std::alloc::{GlobalAlloc, Layout, System};
+/// use std::ptr::NonNull;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// use hebi::prelude::*;
+/// use hebi::Allocator;
+///
+/// struct Counting(Arc<AtomicUsize>);
+///
+/// unsafe impl Allocator for Counting {
+///   fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+///     self.0.fetch_add(layout.size(), Ordering::Relaxed);
+///     NonNull::new(unsafe { System.alloc(layout) })
+///   }
+///
+///   unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+///     self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+///     System.dealloc(ptr.as_ptr(), layout)
+///   }
+/// }
+///
+/// let bytes = Arc::new(AtomicUsize::new(0));
+/// let mut hebi = Hebi::builder()
+///   .allocator(Counting(bytes.clone()))
+///   .finish();
+/// let before = bytes.load(Ordering::Relaxed);
+/// hebi.eval("v := [1, 2, 3]").unwrap();
+/// assert!(bytes.load(Ordering::Relaxed) > before);
+/// ```
+///
+/// # Safety
+///
+/// `allocate` must return memory which fits `layout`, or `None` if it is
+/// exhausted. The memory must stay valid until it is passed to `deallocate`,
+/// even if the VM which allocated it has been dropped.
+///
+/// This means that an arena may only be reset once nothing can access the
+/// objects in it anymore: the VM has been dropped, and so have all values and
+/// errors returned from it.
+pub unsafe trait Allocator: Send + Sync + 'static {
+  fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+  /// Free `ptr`, which was returned by `allocate` with the same `layout`.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must have been returned by a call to `allocate` with `layout`,
+  /// and must not have been freed already.
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
@@ -0,0 +1,79 @@
+//! Stopping scripts from outside of the VM.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Stops an evaluation started by [`Hebi::eval_with_cancellation`] or
+/// [`TypedFunction::call_with_cancellation`].
+///
+/// The token may be cloned and sent to another thread, which cancels the
+/// evaluation by calling [`CancellationToken::cancel`]. A token created with
+/// [`CancellationToken::with_timeout`], or converted from a [`Duration`], is
+/// also cancelled once the timeout elapses.
+///
+/// The VM checks the token at the start of every loop iteration and function
+/// call, and fails with an error of kind [`ErrorKind::Cancelled`]. A native
+/// function which is running at that time is not interrupted, and the
+/// cancellation is only noticed once it returns.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use hebi::{ErrorKind, Hebi};
+///
+/// let mut hebi = Hebi::new();
+/// let e = hebi
+///   .eval_with_cancellation("while true: pass", Duration::from_millis(10))
+///   .unwrap_err();
+/// assert!(matches!(e.kind(), ErrorKind::Cancelled));
+/// ```
+///
+/// [`Hebi::eval_with_cancellation`]: crate::Hebi::eval_with_cancellation
+/// [`TypedFunction::call_with_cancellation`]: crate::TypedFunction::call_with_cancellation
+/// [`ErrorKind::Cancelled`]: crate::ErrorKind::Cancelled
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+  deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A token which is cancelled once `timeout` elapses, starting now.
+  pub fn with_timeout(timeout: Duration) -> Self {
+    Self {
+      cancelled: Arc::default(),
+      deadline: Instant::now().checked_add(timeout),
+    }
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed) || self.is_timed_out()
+  }
+
+  /// Like `is_cancelled`, but doesn't check the deadline, which is more
+  /// expensive than loading the flag.
+  pub(crate) fn is_cancelled_fast(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn is_timed_out(&self) -> bool {
+    self
+      .deadline
+      .is_some_and(|deadline| Instant::now() >= deadline)
+  }
+}
+
+impl From<Duration> for CancellationToken {
+  fn from(timeout: Duration) -> Self {
+    Self::with_timeout(timeout)
+  }
+}
@@ -0,0 +1,179 @@
+//! Recording which lines of each module were executed.
+//!
+//! Only compiled with the `coverage` feature, because it adds work to every
+//! dispatched instruction.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::internal::bytecode::opcode as op;
+use crate::internal::object::function::{collect_functions, FunctionDescriptor};
+use crate::internal::object::Ptr;
+
+#[derive(Default)]
+pub struct Coverage {
+  modules: RefCell<IndexMap<String, ModuleCoverage>>,
+  functions: RefCell<HashMap<Key, FunctionLines>>,
+}
+
+/// Identifies a function descriptor by its address.
+struct Key(Ptr<FunctionDescriptor>);
+
+impl Hash for Key {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.0.ptr_hash(state)
+  }
+}
+
+impl PartialEq for Key {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.ptr_eq(&other.0)
+  }
+}
+
+impl Eq for Key {}
+
+struct FunctionLines {
+  /// Index of the function's module in `Coverage::modules`.
+  module: usize,
+  /// The 1-based line of the instruction which each byte of the function's
+  /// bytecode belongs to, or `0` if it isn't known.
+  lines: Box<[usize]>,
+}
+
+impl Coverage {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Start tracking the module `name`, which was compiled from `source` and
+  /// has the root function `root`.
+  ///
+  /// If a module with the same name was added before, its coverage is reset.
+  /// This happens to `__main__` on every call to `eval`.
+  pub fn add_module(&self, name: &str, source: &str, root: &Ptr<FunctionDescriptor>) {
+    let line_starts = std::iter::once(0)
+      .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+      .collect::<Vec<_>>();
+
+    let mut instrumented = BTreeSet::new();
+    let mut functions = vec![];
+    collect_functions(root, &mut functions);
+    let functions = functions
+      .into_iter()
+      .map(|function| {
+        let lines = line_per_byte(&function, source.len(), &line_starts);
+        instrumented.extend(lines.iter().copied().filter(|&line| line != 0));
+        (function, lines)
+      })
+      .collect::<Vec<_>>();
+
+    let mut modules = self.modules.borrow_mut();
+    let (module, _) = modules.insert_full(
+      name.to_string(),
+      ModuleCoverage {
+        instrumented,
+        executed: BTreeSet::new(),
+      },
+    );
+    self.functions.borrow_mut().extend(
+      functions
+        .into_iter()
+        .map(|(function, lines)| (Key(function), FunctionLines { module, lines })),
+    );
+  }
+
+  /// Record that the instruction at `pc` in `function` was executed.
+  #[inline]
+  pub fn record(&self, function: &Ptr<FunctionDescriptor>, pc: usize) {
+    let functions = self.functions.borrow();
+    // functions which were not compiled from source, such as the ones in a
+    // program compiled by a different VM, are not tracked
+    let Some(function) = functions.get(&Key(function.clone())) else {
+      return;
+    };
+    let line = function.lines.get(pc).copied().unwrap_or(0);
+    if line != 0 {
+      let mut modules = self.modules.borrow_mut();
+      if let Some((_, module)) = modules.get_index_mut(function.module) {
+        module.executed.insert(line);
+      }
+    }
+  }
+
+  pub fn report(&self) -> CoverageReport {
+    CoverageReport {
+      modules: self
+        .modules
+        .borrow()
+        .iter()
+        .map(|(name, module)| (name.clone(), module.clone()))
+        .collect(),
+    }
+  }
+}
+
+fn line_per_byte(
+  function: &FunctionDescriptor,
+  source_len: usize,
+  line_starts: &[usize],
+) -> Box<[usize]> {
+  let (mut bytecode, spans) = unsafe { (function.instructions.as_ref(), function.spans.as_ref()) };
+  let mut lines = Vec::with_capacity(bytecode.len());
+  for span in spans {
+    let Some((_, remainder)) = op::symbolic::decode(bytecode) else {
+      break;
+    };
+    let line = if span.is_empty() || span.start > source_len {
+      0
+    } else {
+      match line_starts.binary_search(&span.start) {
+        Ok(line) => line + 1,
+        Err(next_line) => next_line,
+      }
+    };
+    lines.extend(std::iter::repeat_n(line, bytecode.len() - remainder.len()));
+    bytecode = remainder;
+  }
+  lines.into_boxed_slice()
+}
+
+/// The lines of each module which were executed, as returned by
+/// [`Hebi::coverage`][`crate::Hebi::coverage`].
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+  /// Coverage of each module, by module name. The main script is called
+  /// `__main__`.
+  pub modules: BTreeMap<String, ModuleCoverage>,
+}
+
+/// Line numbers are 1-based.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleCoverage {
+  /// Lines which have at least one instruction.
+  pub instrumented: BTreeSet<usize>,
+  /// Lines where at least one instruction was executed.
+  pub executed: BTreeSet<usize>,
+}
+
+impl CoverageReport {
+  /// Format the report as an lcov tracefile, with one record per module.
+  pub fn to_lcov(&self) -> String {
+    let mut out = String::new();
+    for (name, module) in self.modules.iter() {
+      let _ = writeln!(out, "SF:{name}");
+      for line in module.instrumented.iter() {
+        let hits = module.executed.contains(line) as usize;
+        let _ = writeln!(out, "DA:{line},{hits}");
+      }
+      let _ = writeln!(out, "LH:{}", module.executed.len());
+      let _ = writeln!(out, "LF:{}", module.instrumented.len());
+      let _ = writeln!(out, "end_of_record");
+    }
+    out
+  }
+}
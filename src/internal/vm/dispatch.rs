@@ -1,15 +1,25 @@
 #[macro_use]
 mod macros;
+#[cfg(all(feature = "threaded-dispatch", not(target_family = "wasm")))]
+mod threaded;
 
 use std::error::Error as StdError;
 use std::ptr::NonNull;
 
 use super::thread::AsyncFrame;
 use crate::internal::bytecode::opcode as op;
+#[cfg(not(all(feature = "threaded-dispatch", not(target_family = "wasm"))))]
 use crate::internal::bytecode::opcode::Opcode;
+#[cfg(not(all(feature = "threaded-dispatch", not(target_family = "wasm"))))]
 use crate::internal::bytecode::operands::Width;
 use crate::internal::error::Result;
 
+// On wasm, indirect calls are checked against the callee's signature and a
+// `match` lowers to a `br_table`, so the portable loop is used instead.
+#[cfg(all(feature = "threaded-dispatch", not(target_family = "wasm")))]
+pub use self::threaded::dispatch;
+
+#[cfg(not(all(feature = "threaded-dispatch", not(target_family = "wasm"))))]
 #[inline(never)]
 pub fn dispatch<T: Handler>(
   handler: &mut T,
@@ -29,7 +39,12 @@ pub fn dispatch<T: Handler>(
 
     loop {
       let start = ip;
-      match read_opcode!(ip, end) {
+      let opcode = read_opcode!(ip, end);
+      #[cfg(feature = "profiler")]
+      handler.profile_op(opcode);
+      #[cfg(feature = "coverage")]
+      handler.cover(get_pc!(start, bytecode));
+      match opcode {
         Opcode::Nop => {
           continue;
         }
@@ -41,154 +56,164 @@ pub fn dispatch<T: Handler>(
           width = Width::Wide32;
           continue;
         }
-        Opcode::Load => {
-          let (reg,) = read_operands!(Load, ip, end, width);
-          handler.op_load(reg)?;
-          continue;
-        }
-        Opcode::Store => {
-          let (reg,) = read_operands!(Store, ip, end, width);
-          handler.op_store(reg)?;
+        Opcode::Mov => {
+          let (dst, src) = read_operands!(Mov, ip, end, width);
+          handler.op_mov(dst, src)?;
           continue;
         }
         Opcode::LoadConst => {
-          let (idx,) = read_operands!(LoadConst, ip, end, width);
-          handler.op_load_const(idx)?;
+          let (dst, idx) = read_operands!(LoadConst, ip, end, width);
+          handler.op_load_const(dst, idx)?;
           continue;
         }
         Opcode::LoadUpvalue => {
-          let (idx,) = read_operands!(LoadUpvalue, ip, end, width);
-          handler.op_load_upvalue(idx)?;
+          let (dst, idx) = read_operands!(LoadUpvalue, ip, end, width);
+          handler.op_load_upvalue(dst, idx)?;
           continue;
         }
         Opcode::StoreUpvalue => {
-          let (idx,) = read_operands!(StoreUpvalue, ip, end, width);
-          handler.op_store_upvalue(idx)?;
+          let (idx, src) = read_operands!(StoreUpvalue, ip, end, width);
+          handler.op_store_upvalue(idx, src)?;
           continue;
         }
         Opcode::LoadModuleVar => {
-          let (idx,) = read_operands!(LoadModuleVar, ip, end, width);
-          handler.op_load_module_var(idx)?;
+          let (dst, idx) = read_operands!(LoadModuleVar, ip, end, width);
+          handler.op_load_module_var(dst, idx)?;
           continue;
         }
         Opcode::StoreModuleVar => {
-          let (idx,) = read_operands!(StoreModuleVar, ip, end, width);
-          handler.op_store_module_var(idx)?;
+          let (idx, src) = read_operands!(StoreModuleVar, ip, end, width);
+          handler.op_store_module_var(idx, src)?;
           continue;
         }
         Opcode::LoadGlobal => {
-          let (name,) = read_operands!(LoadGlobal, ip, end, width);
-          handler.op_load_global(name)?;
+          let (dst, name) = read_operands!(LoadGlobal, ip, end, width);
+          handler.op_load_global(dst, name)?;
           continue;
         }
         Opcode::StoreGlobal => {
-          let (name,) = read_operands!(StoreGlobal, ip, end, width);
-          handler.op_store_global(name)?;
+          let (name, src) = read_operands!(StoreGlobal, ip, end, width);
+          handler.op_store_global(name, src)?;
           continue;
         }
         Opcode::LoadField => {
-          let (name,) = read_operands!(LoadField, ip, end, width);
-          handler.op_load_field(name)?;
+          let (dst, obj, name) = read_operands!(LoadField, ip, end, width);
+          handler.op_load_field(get_pc!(start, bytecode), dst, obj, name)?;
           continue;
         }
         Opcode::LoadFieldOpt => {
-          let (name,) = read_operands!(LoadFieldOpt, ip, end, width);
-          handler.op_load_field_opt(name)?;
+          let (dst, obj, name) = read_operands!(LoadFieldOpt, ip, end, width);
+          handler.op_load_field_opt(get_pc!(start, bytecode), dst, obj, name)?;
           continue;
         }
         Opcode::StoreField => {
-          let (obj, name) = read_operands!(StoreField, ip, end, width);
-          handler.op_store_field(obj, name)?;
+          let (obj, name, src) = read_operands!(StoreField, ip, end, width);
+          handler.op_store_field(get_pc!(start, bytecode), obj, name, src)?;
           continue;
         }
         Opcode::LoadIndex => {
-          let (name,) = read_operands!(LoadIndex, ip, end, width);
-          handler.op_load_index(name)?;
+          let (dst, obj, key) = read_operands!(LoadIndex, ip, end, width);
+          handler.op_load_index(dst, obj, key)?;
           continue;
         }
         Opcode::LoadIndexOpt => {
-          let (name,) = read_operands!(LoadIndexOpt, ip, end, width);
-          handler.op_load_index_opt(name)?;
+          let (dst, obj, key) = read_operands!(LoadIndexOpt, ip, end, width);
+          handler.op_load_index_opt(dst, obj, key)?;
           continue;
         }
         Opcode::StoreIndex => {
-          let (obj, key) = read_operands!(StoreIndex, ip, end, width);
-          handler.op_store_index(obj, key)?;
+          let (obj, key, src) = read_operands!(StoreIndex, ip, end, width);
+          handler.op_store_index(obj, key, src)?;
           continue;
         }
         Opcode::LoadSelf => {
-          let () = read_operands!(LoadSelf, ip, end, width);
-          handler.op_load_self()?;
+          let (dst,) = read_operands!(LoadSelf, ip, end, width);
+          handler.op_load_self(dst)?;
           continue;
         }
         Opcode::LoadSuper => {
-          let () = read_operands!(LoadSuper, ip, end, width);
-          handler.op_load_super()?;
+          let (dst,) = read_operands!(LoadSuper, ip, end, width);
+          handler.op_load_super(dst)?;
           continue;
         }
         Opcode::LoadNone => {
-          let () = read_operands!(LoadNone, ip, end, width);
-          handler.op_load_none()?;
+          let (dst,) = read_operands!(LoadNone, ip, end, width);
+          handler.op_load_none(dst)?;
           continue;
         }
         Opcode::LoadTrue => {
-          let () = read_operands!(LoadTrue, ip, end, width);
-          handler.op_load_true()?;
+          let (dst,) = read_operands!(LoadTrue, ip, end, width);
+          handler.op_load_true(dst)?;
           continue;
         }
         Opcode::LoadFalse => {
-          let () = read_operands!(LoadFalse, ip, end, width);
-          handler.op_load_false()?;
+          let (dst,) = read_operands!(LoadFalse, ip, end, width);
+          handler.op_load_false(dst)?;
           continue;
         }
         Opcode::LoadSmi => {
-          let (smi,) = read_operands!(LoadSmi, ip, end, width);
-          handler.op_load_smi(smi)?;
+          let (dst, value) = read_operands!(LoadSmi, ip, end, width);
+          handler.op_load_smi(dst, value)?;
           continue;
         }
         Opcode::MakeFn => {
-          let (desc,) = read_operands!(MakeFn, ip, end, width);
-          handler.op_make_fn(desc)?;
+          let (dst, desc) = read_operands!(MakeFn, ip, end, width);
+          handler.op_make_fn(dst, desc)?;
           continue;
         }
         Opcode::MakeClass => {
-          let (desc,) = read_operands!(MakeClass, ip, end, width);
-          handler.op_make_class(desc)?;
+          let (dst, desc) = read_operands!(MakeClass, ip, end, width);
+          handler.op_make_class(dst, desc)?;
           continue;
         }
         Opcode::MakeClassDerived => {
-          let (desc,) = read_operands!(MakeClassDerived, ip, end, width);
-          handler.op_make_class_derived(desc)?;
+          let (dst, desc, parent) = read_operands!(MakeClassDerived, ip, end, width);
+          handler.op_make_class_derived(dst, desc, parent)?;
           continue;
         }
         Opcode::MakeDataClass => {
-          let (desc, parts) = read_operands!(MakeDataClass, ip, end, width);
-          handler.op_make_data_class(desc, parts)?;
+          let (dst, desc, parts) = read_operands!(MakeDataClass, ip, end, width);
+          handler.op_make_data_class(dst, desc, parts)?;
           continue;
         }
         Opcode::MakeDataClassDerived => {
-          let (desc, parts) = read_operands!(MakeDataClassDerived, ip, end, width);
-          handler.op_make_data_class_derived(desc, parts)?;
+          let (dst, desc, parts) = read_operands!(MakeDataClassDerived, ip, end, width);
+          handler.op_make_data_class_derived(dst, desc, parts)?;
           continue;
         }
         Opcode::MakeList => {
-          let (start, count) = read_operands!(MakeList, ip, end, width);
-          handler.op_make_list(start, count)?;
+          let (dst, start, count) = read_operands!(MakeList, ip, end, width);
+          handler.op_make_list(dst, start, count)?;
           continue;
         }
         Opcode::MakeListEmpty => {
-          let () = read_operands!(MakeListEmpty, ip, end, width);
-          handler.op_make_list_empty()?;
+          let (dst,) = read_operands!(MakeListEmpty, ip, end, width);
+          handler.op_make_list_empty(dst)?;
+          continue;
+        }
+        Opcode::MakeTuple => {
+          let (dst, start, count) = read_operands!(MakeTuple, ip, end, width);
+          handler.op_make_tuple(dst, start, count)?;
+          continue;
+        }
+        Opcode::MakeTupleEmpty => {
+          let (dst,) = read_operands!(MakeTupleEmpty, ip, end, width);
+          handler.op_make_tuple_empty(dst)?;
           continue;
         }
         Opcode::MakeTable => {
-          let (start, count) = read_operands!(MakeTable, ip, end, width);
-          handler.op_make_table(start, count)?;
+          let (dst, start, count) = read_operands!(MakeTable, ip, end, width);
+          handler.op_make_table(dst, start, count)?;
           continue;
         }
         Opcode::MakeTableEmpty => {
-          let () = read_operands!(MakeTableEmpty, ip, end, width);
-          handler.op_make_table_empty()?;
+          let (dst,) = read_operands!(MakeTableEmpty, ip, end, width);
+          handler.op_make_table_empty(dst)?;
+          continue;
+        }
+        Opcode::MakeBytes => {
+          let (dst, data) = read_operands!(MakeBytes, ip, end, width);
+          handler.op_make_bytes(dst, data)?;
           continue;
         }
         Opcode::Jump => {
@@ -211,131 +236,190 @@ pub fn dispatch<T: Handler>(
           let width_adjustment = if width.size() > 1 { 1 } else { 0 };
           #[allow(unused_assignments)] // ip is overwritten by start-offset
           let (offset,) = read_operands!(JumpLoop, ip, end, width);
-          let offset = handler.op_jump_loop(offset)?;
-          unsafe { ip = start.sub(offset.value() + width_adjustment) }
+          match handler.op_jump_loop(offset)? {
+            Jump::Move(offset) => unsafe { ip = start.sub(offset.value() + width_adjustment) },
+            Jump::Skip => {}
+            Jump::Yield => {
+              return Ok(ControlFlow::Yield(
+                get_pc!(start, bytecode) - width_adjustment,
+              ))
+            }
+          }
           continue;
         }
         Opcode::JumpIfFalse => {
           let width_adjustment = if width.size() > 1 { 1 } else { 0 };
-          let (offset,) = read_operands!(JumpIfFalse, ip, end, width);
-          let offset = handler.op_jump_if_false(offset)?;
+          let (offset, cond) = read_operands!(JumpIfFalse, ip, end, width);
+          let offset = handler.op_jump_if_false(offset, cond)?;
           match offset {
             Jump::Move(offset) => unsafe { ip = start.add(offset.value() - width_adjustment) },
             Jump::Skip => {}
+            Jump::Yield => {
+              return Ok(ControlFlow::Yield(
+                get_pc!(start, bytecode) - width_adjustment,
+              ))
+            }
           }
           continue;
         }
         Opcode::JumpIfFalseConst => {
           let width_adjustment = if width.size() > 1 { 1 } else { 0 };
-          let (idx,) = read_operands!(JumpIfFalseConst, ip, end, width);
-          let offset = handler.op_jump_if_false_const(idx)?;
+          let (idx, cond) = read_operands!(JumpIfFalseConst, ip, end, width);
+          let offset = handler.op_jump_if_false_const(idx, cond)?;
           match offset {
             Jump::Move(offset) => unsafe { ip = start.add(offset.value() - width_adjustment) },
             Jump::Skip => {}
+            Jump::Yield => {
+              return Ok(ControlFlow::Yield(
+                get_pc!(start, bytecode) - width_adjustment,
+              ))
+            }
+          }
+          continue;
+        }
+        Opcode::Switch => {
+          let width_adjustment = if width.size() > 1 { 1 } else { 0 };
+          let (src, table) = read_operands!(Switch, ip, end, width);
+          let offset = handler.op_switch(src, table)?;
+          match offset {
+            Jump::Move(offset) => unsafe { ip = start.add(offset.value() - width_adjustment) },
+            Jump::Skip => {}
+            Jump::Yield => {
+              return Ok(ControlFlow::Yield(
+                get_pc!(start, bytecode) - width_adjustment,
+              ))
+            }
           }
           continue;
         }
         Opcode::Add => {
-          let (lhs,) = read_operands!(Add, ip, end, width);
-          handler.op_add(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Add, ip, end, width);
+          handler.op_add(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Sub => {
-          let (lhs,) = read_operands!(Sub, ip, end, width);
-          handler.op_sub(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Sub, ip, end, width);
+          handler.op_sub(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Mul => {
-          let (lhs,) = read_operands!(Mul, ip, end, width);
-          handler.op_mul(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Mul, ip, end, width);
+          handler.op_mul(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Div => {
-          let (lhs,) = read_operands!(Div, ip, end, width);
-          handler.op_div(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Div, ip, end, width);
+          handler.op_div(dst, lhs, rhs)?;
+          continue;
+        }
+        Opcode::FloorDiv => {
+          let (dst, lhs, rhs) = read_operands!(FloorDiv, ip, end, width);
+          handler.op_floor_div(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Rem => {
-          let (lhs,) = read_operands!(Rem, ip, end, width);
-          handler.op_rem(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Rem, ip, end, width);
+          handler.op_rem(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Pow => {
-          let (lhs,) = read_operands!(Pow, ip, end, width);
-          handler.op_pow(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Pow, ip, end, width);
+          handler.op_pow(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Inv => {
-          let () = read_operands!(Inv, ip, end, width);
-          handler.op_inv()?;
+          let (dst, src) = read_operands!(Inv, ip, end, width);
+          handler.op_inv(dst, src)?;
           continue;
         }
         Opcode::Not => {
-          let () = read_operands!(Not, ip, end, width);
-          handler.op_not()?;
-          continue;
+          let (dst, src) = read_operands!(Not, ip, end, width);
+          match handler.op_not(dst, src)? {
+            Call::LoadFrame(new_frame) => {
+              bytecode = new_frame.bytecode;
+              pc = new_frame.pc;
+              continue 'load_frame;
+            }
+            Call::Continue => continue,
+            Call::Yield => return Ok(ControlFlow::Yield(get_pc!(ip, bytecode))),
+          }
         }
         Opcode::CmpEq => {
-          let (lhs,) = read_operands!(CmpEq, ip, end, width);
-          handler.op_cmp_eq(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpEq, ip, end, width);
+          handler.op_cmp_eq(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpNe => {
-          let (lhs,) = read_operands!(CmpNe, ip, end, width);
-          handler.op_cmp_ne(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpNe, ip, end, width);
+          handler.op_cmp_ne(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpGt => {
-          let (lhs,) = read_operands!(CmpGt, ip, end, width);
-          handler.op_cmp_gt(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpGt, ip, end, width);
+          handler.op_cmp_gt(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpGe => {
-          let (lhs,) = read_operands!(CmpGe, ip, end, width);
-          handler.op_cmp_ge(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpGe, ip, end, width);
+          handler.op_cmp_ge(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpLt => {
-          let (lhs,) = read_operands!(CmpLt, ip, end, width);
-          handler.op_cmp_lt(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpLt, ip, end, width);
+          handler.op_cmp_lt(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpLe => {
-          let (lhs,) = read_operands!(CmpLe, ip, end, width);
-          handler.op_cmp_le(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpLe, ip, end, width);
+          handler.op_cmp_le(dst, lhs, rhs)?;
           continue;
         }
         Opcode::CmpType => {
-          let (lhs,) = read_operands!(CmpType, ip, end, width);
-          handler.op_cmp_type(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(CmpType, ip, end, width);
+          handler.op_cmp_type(dst, lhs, rhs)?;
           continue;
         }
         Opcode::Contains => {
-          let (lhs,) = read_operands!(Contains, ip, end, width);
-          handler.op_contains(lhs)?;
+          let (dst, lhs, rhs) = read_operands!(Contains, ip, end, width);
+          handler.op_contains(dst, lhs, rhs)?;
           continue;
         }
         Opcode::IsNone => {
-          let () = read_operands!(IsNone, ip, end, width);
-          handler.op_is_none()?;
+          let (dst, src) = read_operands!(IsNone, ip, end, width);
+          handler.op_is_none(dst, src)?;
+          continue;
+        }
+        Opcode::Unpack => {
+          let (start, count, src) = read_operands!(Unpack, ip, end, width);
+          handler.op_unpack(start, count, src)?;
+          continue;
+        }
+        Opcode::AppendStr => {
+          let (dst, lhs, rhs) = read_operands!(AppendStr, ip, end, width);
+          handler.op_append_str(dst, lhs, rhs)?;
+          continue;
+        }
+        Opcode::BuildStr => {
+          let (dst, src) = read_operands!(BuildStr, ip, end, width);
+          handler.op_build_str(dst, src)?;
           continue;
         }
         Opcode::Print => {
-          let () = read_operands!(Print, ip, end, width);
-          handler.op_print()?;
+          let (src,) = read_operands!(Print, ip, end, width);
+          handler.op_print(get_pc!(start, bytecode), src)?;
           continue;
         }
         Opcode::PrintN => {
-          let (start, count) = read_operands!(PrintN, ip, end, width);
-          handler.op_print_n(start, count)?;
+          let (reg, count) = read_operands!(PrintN, ip, end, width);
+          handler.op_print_n(get_pc!(start, bytecode), reg, count)?;
           continue;
         }
         Opcode::Call => {
           // frame is reloaded so neither `ip` nor `width` are read
           #[allow(unused_assignments)]
-          let (callee, args) = read_operands!(Call, ip, end, width);
+          let (dst, callee, args) = read_operands!(Call, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_call(return_addr, callee, args)? {
+          match handler.op_call(return_addr, dst, callee, args)? {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -345,12 +429,12 @@ pub fn dispatch<T: Handler>(
             Call::Yield => return Ok(ControlFlow::Yield(get_pc!(ip, bytecode))),
           }
         }
-        Opcode::Call0 => {
+        Opcode::CallKw => {
           // frame is reloaded so neither `ip` nor `width` are read
           #[allow(unused_assignments)]
-          let () = read_operands!(Call0, ip, end, width);
+          let (dst, callee, args, names) = read_operands!(CallKw, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_call0(return_addr)? {
+          match handler.op_call_kw(return_addr, dst, callee, args, names)? {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -361,9 +445,9 @@ pub fn dispatch<T: Handler>(
           }
         }
         Opcode::Import => {
-          let (path,) = read_operands!(Import, ip, end, width);
+          let (dst, path) = read_operands!(Import, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_import(path, return_addr)? {
+          match handler.op_import(dst, path, return_addr)? {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -374,14 +458,14 @@ pub fn dispatch<T: Handler>(
           }
         }
         Opcode::FinalizeModule => {
-          let () = read_operands!(FinalizeModule, ip, end, width);
-          handler.op_finalize_module()?;
+          let (dst,) = read_operands!(FinalizeModule, ip, end, width);
+          handler.op_finalize_module(dst)?;
           continue;
         }
         Opcode::Return => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
-          let () = read_operands!(Return, ip, end, width);
-          match handler.op_return()? {
+          let (src,) = read_operands!(Return, ip, end, width);
+          match handler.op_return(src)? {
             Return::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -392,10 +476,60 @@ pub fn dispatch<T: Handler>(
         }
         Opcode::Yield => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
-          let () = read_operands!(Yield, ip, end, width);
-          handler.op_yield()?;
+          let (src,) = read_operands!(Yield, ip, end, width);
+          handler.op_yield(src)?;
           return Ok(ControlFlow::Yield(get_pc!(ip, bytecode)));
         }
+        Opcode::Raise => {
+          let (src,) = read_operands!(Raise, ip, end, width);
+          handler.op_raise(src)?;
+          continue;
+        }
+        Opcode::Defer => {
+          let (callee, args) = read_operands!(Defer, ip, end, width);
+          handler.op_defer(callee, args)?;
+          continue;
+        }
+        Opcode::HasDeferred => {
+          let (dst,) = read_operands!(HasDeferred, ip, end, width);
+          handler.op_has_deferred(dst)?;
+          continue;
+        }
+        Opcode::CallDeferred => {
+          // frame is reloaded so neither `ip` nor `width` are read
+          #[allow(unused_assignments)]
+          let (dst,) = read_operands!(CallDeferred, ip, end, width);
+          let return_addr = get_pc!(ip, bytecode);
+          match handler.op_call_deferred(return_addr, dst)? {
+            Call::LoadFrame(new_frame) => {
+              bytecode = new_frame.bytecode;
+              pc = new_frame.pc;
+              continue 'load_frame;
+            }
+            Call::Continue => continue,
+            Call::Yield => return Ok(ControlFlow::Yield(get_pc!(ip, bytecode))),
+          }
+        }
+        Opcode::CheckParam => {
+          let (param, name, hint) = read_operands!(CheckParam, ip, end, width);
+          handler.op_check_param(param, name, hint)?;
+          continue;
+        }
+        Opcode::CmpLtJumpIfFalse => {
+          let width_adjustment = if width.size() > 1 { 1 } else { 0 };
+          let (dst, lhs, rhs, offset) = read_operands!(CmpLtJumpIfFalse, ip, end, width);
+          let offset = handler.op_cmp_lt_jump_if_false(dst, lhs, rhs, offset)?;
+          match offset {
+            Jump::Move(offset) => unsafe { ip = start.add(offset.value() - width_adjustment) },
+            Jump::Skip => {}
+            Jump::Yield => {
+              return Ok(ControlFlow::Yield(
+                get_pc!(start, bytecode) - width_adjustment,
+              ))
+            }
+          }
+          continue;
+        }
       }
     }
   }
@@ -416,6 +550,8 @@ pub enum ControlFlow {
 pub enum Jump {
   Skip,
   Move(op::Offset),
+  /// Suspend the thread, and run the instruction again once it is resumed.
+  Yield,
 }
 
 pub struct LoadFrame {
@@ -445,77 +581,288 @@ impl From<LoadFrame> for Return {
 pub trait Handler {
   type Error: StdError;
 
-  fn op_load(&mut self, reg: op::Register) -> Result<(), Self::Error>;
-  fn op_store(&mut self, reg: op::Register) -> Result<(), Self::Error>;
-  fn op_load_const(&mut self, idx: op::Constant) -> Result<(), Self::Error>;
-  fn op_load_upvalue(&mut self, idx: op::Upvalue) -> Result<(), Self::Error>;
-  fn op_store_upvalue(&mut self, idx: op::Upvalue) -> Result<(), Self::Error>;
-  fn op_load_module_var(&mut self, idx: op::ModuleVar) -> Result<(), Self::Error>;
-  fn op_store_module_var(&mut self, idx: op::ModuleVar) -> Result<(), Self::Error>;
-  fn op_load_global(&mut self, name: op::Constant) -> Result<(), Self::Error>;
-  fn op_store_global(&mut self, name: op::Constant) -> Result<(), Self::Error>;
-  fn op_load_field(&mut self, name: op::Constant) -> Result<(), Self::Error>;
-  fn op_load_field_opt(&mut self, name: op::Constant) -> Result<(), Self::Error>;
-  fn op_store_field(&mut self, obj: op::Register, name: op::Constant) -> Result<(), Self::Error>;
-  fn op_load_index(&mut self, obj: op::Register) -> Result<(), Self::Error>;
-  fn op_load_index_opt(&mut self, obj: op::Register) -> Result<(), Self::Error>;
-  fn op_store_index(&mut self, obj: op::Register, key: op::Register) -> Result<(), Self::Error>;
-  fn op_load_self(&mut self) -> Result<(), Self::Error>;
-  fn op_load_super(&mut self) -> Result<(), Self::Error>;
-  fn op_load_none(&mut self) -> Result<(), Self::Error>;
-  fn op_load_true(&mut self) -> Result<(), Self::Error>;
-  fn op_load_false(&mut self) -> Result<(), Self::Error>;
-  fn op_load_smi(&mut self, smi: op::Smi) -> Result<(), Self::Error>;
-  fn op_make_fn(&mut self, desc: op::Constant) -> Result<(), Self::Error>;
-  fn op_make_class(&mut self, desc: op::Constant) -> Result<(), Self::Error>;
-  fn op_make_class_derived(&mut self, desc: op::Constant) -> Result<(), Self::Error>;
+  /// Called before each instruction is dispatched.
+  #[cfg(feature = "profiler")]
+  fn profile_op(&mut self, opcode: op::Opcode);
+
+  /// Called before the instruction at `pc` in the current frame is
+  /// dispatched.
+  #[cfg(feature = "coverage")]
+  fn cover(&mut self, pc: usize);
+
+  fn op_mov(&mut self, dst: op::Register, src: op::Register) -> Result<(), Self::Error>;
+  fn op_load_const(&mut self, dst: op::Register, idx: op::Constant) -> Result<(), Self::Error>;
+  fn op_load_upvalue(&mut self, dst: op::Register, idx: op::Upvalue) -> Result<(), Self::Error>;
+  fn op_store_upvalue(&mut self, idx: op::Upvalue, src: op::Register) -> Result<(), Self::Error>;
+  fn op_load_module_var(
+    &mut self,
+    dst: op::Register,
+    idx: op::ModuleVar,
+  ) -> Result<(), Self::Error>;
+  fn op_store_module_var(
+    &mut self,
+    idx: op::ModuleVar,
+    src: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_load_global(&mut self, dst: op::Register, name: op::Constant) -> Result<(), Self::Error>;
+  fn op_store_global(&mut self, name: op::Constant, src: op::Register) -> Result<(), Self::Error>;
+  fn op_load_field(
+    &mut self,
+    pc: usize,
+    dst: op::Register,
+    obj: op::Register,
+    name: op::Constant,
+  ) -> Result<(), Self::Error>;
+  fn op_load_field_opt(
+    &mut self,
+    pc: usize,
+    dst: op::Register,
+    obj: op::Register,
+    name: op::Constant,
+  ) -> Result<(), Self::Error>;
+  fn op_store_field(
+    &mut self,
+    pc: usize,
+    obj: op::Register,
+    name: op::Constant,
+    src: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_load_index(
+    &mut self,
+    dst: op::Register,
+    obj: op::Register,
+    key: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_load_index_opt(
+    &mut self,
+    dst: op::Register,
+    obj: op::Register,
+    key: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_store_index(
+    &mut self,
+    obj: op::Register,
+    key: op::Register,
+    src: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_load_self(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_load_super(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_load_none(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_load_true(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_load_false(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_load_smi(&mut self, dst: op::Register, value: op::Smi) -> Result<(), Self::Error>;
+  fn op_make_fn(&mut self, dst: op::Register, desc: op::Constant) -> Result<(), Self::Error>;
+  fn op_make_class(&mut self, dst: op::Register, desc: op::Constant) -> Result<(), Self::Error>;
+  fn op_make_class_derived(
+    &mut self,
+    dst: op::Register,
+    desc: op::Constant,
+    parent: op::Register,
+  ) -> Result<(), Self::Error>;
   fn op_make_data_class(
     &mut self,
+    dst: op::Register,
     desc: op::Constant,
     parts: op::Register,
   ) -> Result<(), Self::Error>;
   fn op_make_data_class_derived(
     &mut self,
+    dst: op::Register,
     desc: op::Constant,
     parts: op::Register,
   ) -> Result<(), Self::Error>;
-  fn op_make_list(&mut self, start: op::Register, count: op::Count) -> Result<(), Self::Error>;
-  fn op_make_list_empty(&mut self) -> Result<(), Self::Error>;
-  fn op_make_table(&mut self, start: op::Register, count: op::Count) -> Result<(), Self::Error>;
-  fn op_make_table_empty(&mut self) -> Result<(), Self::Error>;
+  fn op_make_list(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<(), Self::Error>;
+  fn op_make_list_empty(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_make_tuple(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<(), Self::Error>;
+  fn op_make_tuple_empty(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_unpack(
+    &mut self,
+    start: op::Register,
+    count: op::Count,
+    src: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_make_table(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<(), Self::Error>;
+  fn op_make_table_empty(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_make_bytes(&mut self, dst: op::Register, data: op::Constant) -> Result<(), Self::Error>;
   fn op_jump(&mut self, offset: op::Offset) -> Result<op::Offset, Self::Error>;
   fn op_jump_const(&mut self, idx: op::Constant) -> Result<op::Offset, Self::Error>;
-  fn op_jump_loop(&mut self, offset: op::Offset) -> Result<op::Offset, Self::Error>;
-  fn op_jump_if_false(&mut self, offset: op::Offset) -> Result<Jump, Self::Error>;
-  fn op_jump_if_false_const(&mut self, idx: op::Constant) -> Result<Jump, Self::Error>;
-  fn op_add(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_sub(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_mul(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_div(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_rem(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_pow(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_inv(&mut self) -> Result<(), Self::Error>;
-  fn op_not(&mut self) -> Result<(), Self::Error>;
-  fn op_cmp_eq(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_ne(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_gt(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_ge(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_lt(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_le(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_cmp_type(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_contains(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
-  fn op_is_none(&mut self) -> Result<(), Self::Error>;
-  fn op_print(&mut self) -> Result<(), Self::Error>;
-  fn op_print_n(&mut self, start: op::Register, count: op::Count) -> Result<(), Self::Error>;
+  fn op_jump_loop(&mut self, offset: op::Offset) -> Result<Jump, Self::Error>;
+  fn op_jump_if_false(
+    &mut self,
+    offset: op::Offset,
+    cond: op::Register,
+  ) -> Result<Jump, Self::Error>;
+  fn op_jump_if_false_const(
+    &mut self,
+    idx: op::Constant,
+    cond: op::Register,
+  ) -> Result<Jump, Self::Error>;
+  fn op_switch(&mut self, src: op::Register, table: op::Constant) -> Result<Jump, Self::Error>;
+  fn op_add(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_sub(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_mul(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_div(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_floor_div(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_rem(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_pow(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_inv(&mut self, dst: op::Register, src: op::Register) -> Result<(), Self::Error>;
+  fn op_not(&mut self, dst: op::Register, src: op::Register) -> Result<Call, Self::Error>;
+  fn op_cmp_eq(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_ne(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_gt(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_ge(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_lt(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_le(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_type(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_contains(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_is_none(&mut self, dst: op::Register, src: op::Register) -> Result<(), Self::Error>;
+  fn op_append_str(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<(), Self::Error>;
+  fn op_build_str(&mut self, dst: op::Register, src: op::Register) -> Result<(), Self::Error>;
+  fn op_print(&mut self, pc: usize, src: op::Register) -> Result<(), Self::Error>;
+  fn op_print_n(
+    &mut self,
+    pc: usize,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<(), Self::Error>;
   fn op_call(
     &mut self,
     return_addr: usize,
+    dst: op::Register,
+    callee: op::Register,
+    args: op::Count,
+  ) -> Result<Call, Self::Error>;
+  fn op_call_kw(
+    &mut self,
+    return_addr: usize,
+    dst: op::Register,
     callee: op::Register,
     args: op::Count,
+    names: op::Constant,
   ) -> Result<Call, Self::Error>;
-  fn op_call0(&mut self, return_addr: usize) -> Result<Call, Self::Error>;
-  fn op_import(&mut self, path: op::Constant, return_addr: usize) -> Result<Call, Self::Error>;
-  fn op_finalize_module(&mut self) -> Result<(), Self::Error>;
-  fn op_return(&mut self) -> Result<Return, Self::Error>;
-  fn op_yield(&mut self) -> Result<(), Self::Error>;
+  fn op_import(
+    &mut self,
+    dst: op::Register,
+    path: op::Constant,
+    return_addr: usize,
+  ) -> Result<Call, Self::Error>;
+  fn op_finalize_module(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_return(&mut self, src: op::Register) -> Result<Return, Self::Error>;
+  fn op_yield(&mut self, src: op::Register) -> Result<(), Self::Error>;
+  fn op_raise(&mut self, src: op::Register) -> Result<(), Self::Error>;
+  fn op_defer(&mut self, callee: op::Register, args: op::Count) -> Result<(), Self::Error>;
+  fn op_has_deferred(&mut self, dst: op::Register) -> Result<(), Self::Error>;
+  fn op_call_deferred(
+    &mut self,
+    return_addr: usize,
+    dst: op::Register,
+  ) -> Result<Call, Self::Error>;
+  fn op_check_param(
+    &mut self,
+    param: op::Register,
+    name: op::Constant,
+    hint: op::Constant,
+  ) -> Result<(), Self::Error>;
+  fn op_cmp_lt_jump_if_false(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+    offset: op::Offset,
+  ) -> Result<Jump, Self::Error>;
 }
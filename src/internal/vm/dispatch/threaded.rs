@@ -0,0 +1,300 @@
+//! Threaded dispatch.
+//!
+//! Every instruction is implemented by its own function, and the dispatch
+//! loop calls through a table of function pointers indexed by opcode instead
+//! of going through a single `match`. Each handler ends in its own indirect
+//! branch, which the branch predictor can track separately, rather than all
+//! of them sharing the one at the top of the `match`.
+//!
+//! Stable Rust has neither computed goto nor guaranteed tail calls, so the
+//! handlers return to the loop instead of jumping directly to the next one.
+//!
+//! The handlers are generated from [`for_each_instruction`]. Instructions
+//! which only read their operands and call into the [`Handler`] use the
+//! generic implementation, the rest are implemented in `threaded_op`.
+
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+
+use super::{Call, ControlFlow, Handler, Jump, LoadFrame, Return};
+use crate::internal::bytecode::opcode::{for_each_instruction, Opcode};
+use crate::internal::bytecode::operands::Width;
+
+struct Context<'a, T: Handler> {
+  handler: &'a mut T,
+  bytecode: NonNull<[u8]>,
+  /// Points at the opcode of the current instruction.
+  start: *mut u8,
+  ip: *mut u8,
+  end: *mut u8,
+  width: Width,
+}
+
+impl<'a, T: Handler> Context<'a, T> {
+  fn load_frame(&mut self, frame: LoadFrame) {
+    let LoadFrame { bytecode, pc } = frame;
+    let ip = bytecode.as_ptr() as *mut u8;
+    debug_assert!(pc < bytecode.len(), "unexpected end of bytecode stream");
+
+    self.bytecode = bytecode;
+    self.end = unsafe { ip.add(bytecode.len()) };
+    self.ip = unsafe { ip.add(pc) };
+    self.width = Width::Normal;
+  }
+}
+
+enum Next {
+  Continue,
+  LoadFrame(LoadFrame),
+  Exit(ControlFlow),
+}
+
+type Op<T> = for<'a, 'b> fn(&'a mut Context<'b, T>) -> Result<Next, <T as Handler>::Error>;
+
+struct Ops<T>(PhantomData<T>);
+
+#[inline(never)]
+pub fn dispatch<T: Handler + 'static>(
+  handler: &mut T,
+  bytecode: NonNull<[u8]>,
+  pc: usize,
+) -> Result<ControlFlow, T::Error> {
+  let ops = Ops::<T>::TABLE;
+  let mut cx = Context {
+    handler,
+    bytecode,
+    start: ptr::null_mut(),
+    ip: ptr::null_mut(),
+    end: ptr::null_mut(),
+    width: Width::Normal,
+  };
+  cx.load_frame(LoadFrame { bytecode, pc });
+
+  loop {
+    cx.start = cx.ip;
+    let opcode = {
+      let (mut ip, end) = (cx.ip, cx.end);
+      let opcode = read_opcode!(ip, end);
+      cx.ip = ip;
+      opcode
+    };
+    #[cfg(feature = "profiler")]
+    cx.handler.profile_op(opcode);
+    #[cfg(feature = "coverage")]
+    {
+      let (start, bytecode) = (cx.start, cx.bytecode);
+      cx.handler.cover(get_pc!(start, bytecode));
+    }
+    match ops[opcode as usize](&mut cx)? {
+      Next::Continue => {}
+      Next::LoadFrame(frame) => cx.load_frame(frame),
+      Next::Exit(flow) => return Ok(flow),
+    }
+  }
+}
+
+/// Reads the operands of instruction `$name` and advances `$cx.ip` past them.
+macro_rules! read {
+  ($cx:ident, $name:ident) => {{
+    let (mut ip, end, mut width) = ($cx.ip, $cx.end, $cx.width);
+    let operands = read_operands!($name, ip, end, width);
+    $cx.ip = ip;
+    $cx.width = width;
+    operands
+  }};
+}
+
+macro_rules! pc {
+  ($cx:ident, $ptr:ident) => {{
+    let ($ptr, bytecode) = ($cx.$ptr, $cx.bytecode);
+    get_pc!($ptr, bytecode)
+  }};
+}
+
+/// The width prefix comes before `start`, so jump offsets which are relative
+/// to the start of the whole instruction have to be adjusted by it.
+macro_rules! width_adjustment {
+  ($cx:ident) => {
+    if $cx.width.size() > 1 {
+      1
+    } else {
+      0
+    }
+  };
+}
+
+macro_rules! call {
+  ($cx:ident, $call:expr) => {{
+    match $call {
+      Call::LoadFrame(new_frame) => Ok(Next::LoadFrame(new_frame)),
+      Call::Continue => Ok(Next::Continue),
+      Call::Yield => Ok(Next::Exit(ControlFlow::Yield(pc!($cx, ip)))),
+    }
+  }};
+}
+
+macro_rules! jump {
+  ($cx:ident, $width_adjustment:ident, $jump:expr) => {{
+    match $jump {
+      Jump::Move(offset) => {
+        $cx.ip = unsafe { $cx.start.add(offset.value() - $width_adjustment) };
+        Ok(Next::Continue)
+      }
+      Jump::Skip => Ok(Next::Continue),
+      Jump::Yield => Ok(Next::Exit(ControlFlow::Yield(
+        pc!($cx, start) - $width_adjustment,
+      ))),
+    }
+  }};
+}
+
+macro_rules! threaded_op {
+  ($cx:ident, Nop) => {{
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, Wide16) => {{
+    $cx.width = Width::Wide16;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, Wide32) => {{
+    $cx.width = Width::Wide32;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, LoadField $($operands:tt)*) => {{
+    let (dst, obj, name) = read!($cx, LoadField);
+    $cx.handler.op_load_field(pc!($cx, start), dst, obj, name)?;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, LoadFieldOpt $($operands:tt)*) => {{
+    let (dst, obj, name) = read!($cx, LoadFieldOpt);
+    $cx.handler.op_load_field_opt(pc!($cx, start), dst, obj, name)?;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, StoreField $($operands:tt)*) => {{
+    let (obj, name, src) = read!($cx, StoreField);
+    $cx.handler.op_store_field(pc!($cx, start), obj, name, src)?;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, Jump $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (offset,) = read!($cx, Jump);
+    let offset = $cx.handler.op_jump(offset)?;
+    $cx.ip = unsafe { $cx.start.add(offset.value() - width_adjustment) };
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, JumpConst $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (idx,) = read!($cx, JumpConst);
+    let offset = $cx.handler.op_jump_const(idx)?;
+    $cx.ip = unsafe { $cx.start.add(offset.value() - width_adjustment) };
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, JumpLoop $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (offset,) = read!($cx, JumpLoop);
+    match $cx.handler.op_jump_loop(offset)? {
+      Jump::Move(offset) => {
+        $cx.ip = unsafe { $cx.start.sub(offset.value() + width_adjustment) };
+        Ok(Next::Continue)
+      }
+      Jump::Skip => Ok(Next::Continue),
+      Jump::Yield => Ok(Next::Exit(ControlFlow::Yield(
+        pc!($cx, start) - width_adjustment,
+      ))),
+    }
+  }};
+  ($cx:ident, JumpIfFalse $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (offset, cond) = read!($cx, JumpIfFalse);
+    jump!($cx, width_adjustment, $cx.handler.op_jump_if_false(offset, cond)?)
+  }};
+  ($cx:ident, JumpIfFalseConst $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (idx, cond) = read!($cx, JumpIfFalseConst);
+    jump!($cx, width_adjustment, $cx.handler.op_jump_if_false_const(idx, cond)?)
+  }};
+  ($cx:ident, Switch $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (src, table) = read!($cx, Switch);
+    jump!($cx, width_adjustment, $cx.handler.op_switch(src, table)?)
+  }};
+  ($cx:ident, CmpLtJumpIfFalse $($operands:tt)*) => {{
+    let width_adjustment = width_adjustment!($cx);
+    let (dst, lhs, rhs, offset) = read!($cx, CmpLtJumpIfFalse);
+    jump!($cx, width_adjustment, $cx.handler.op_cmp_lt_jump_if_false(dst, lhs, rhs, offset)?)
+  }};
+  ($cx:ident, Not $($operands:tt)*) => {{
+    let (dst, src) = read!($cx, Not);
+    call!($cx, $cx.handler.op_not(dst, src)?)
+  }};
+  ($cx:ident, Print $($operands:tt)*) => {{
+    let (src,) = read!($cx, Print);
+    $cx.handler.op_print(pc!($cx, start), src)?;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, PrintN $($operands:tt)*) => {{
+    let (reg, count) = read!($cx, PrintN);
+    $cx.handler.op_print_n(pc!($cx, start), reg, count)?;
+    Ok(Next::Continue)
+  }};
+  ($cx:ident, Call $($operands:tt)*) => {{
+    let (dst, callee, args) = read!($cx, Call);
+    let return_addr = pc!($cx, ip);
+    call!($cx, $cx.handler.op_call(return_addr, dst, callee, args)?)
+  }};
+  ($cx:ident, CallKw $($operands:tt)*) => {{
+    let (dst, callee, args, names) = read!($cx, CallKw);
+    let return_addr = pc!($cx, ip);
+    call!($cx, $cx.handler.op_call_kw(return_addr, dst, callee, args, names)?)
+  }};
+  ($cx:ident, CallDeferred $($operands:tt)*) => {{
+    let (dst,) = read!($cx, CallDeferred);
+    let return_addr = pc!($cx, ip);
+    call!($cx, $cx.handler.op_call_deferred(return_addr, dst)?)
+  }};
+  ($cx:ident, Import $($operands:tt)*) => {{
+    let (dst, path) = read!($cx, Import);
+    let return_addr = pc!($cx, ip);
+    call!($cx, $cx.handler.op_import(dst, path, return_addr)?)
+  }};
+  ($cx:ident, Return $($operands:tt)*) => {{
+    let (src,) = read!($cx, Return);
+    match $cx.handler.op_return(src)? {
+      Return::LoadFrame(new_frame) => Ok(Next::LoadFrame(new_frame)),
+      Return::Yield => Ok(Next::Exit(ControlFlow::Return)),
+    }
+  }};
+  ($cx:ident, Yield $($operands:tt)*) => {{
+    let (src,) = read!($cx, Yield);
+    $cx.handler.op_yield(src)?;
+    Ok(Next::Exit(ControlFlow::Yield(pc!($cx, ip))))
+  }};
+  ($cx:ident, $name:ident $(($($operand:ident : $ty:ident),+))?) => {{
+    let ($($($operand,)+)?) = read!($cx, $name);
+    paste::paste! {
+      $cx.handler.[<op_ $name:snake>]($($($operand),+)?)?;
+    }
+    Ok(Next::Continue)
+  }};
+}
+
+macro_rules! threaded_ops {
+  ($($name:ident $(($($operand:ident : $ty:ident),+))? $(= $fused:tt $(if $lhs:ident == $rhs:ident)?)?),* $(,)?) => {
+    paste::paste! {
+      $(
+        fn [<op_ $name:snake>]<T: Handler>(cx: &mut Context<'_, T>) -> Result<Next, T::Error> {
+          // `Nop` has no operands and does not touch the context.
+          let _ = &cx;
+          threaded_op!(cx, $name $(($($operand : $ty),+))?)
+        }
+      )*
+
+      impl<T: Handler + 'static> Ops<T> {
+        /// Indexed by [`Opcode`], which is declared in the same order.
+        const TABLE: &'static [Op<T>] = &[$([<op_ $name:snake>]::<T>),*];
+      }
+    }
+  };
+}
+
+for_each_instruction!(threaded_ops);
@@ -0,0 +1,326 @@
+//! The formatter behind the `format` builtin.
+//!
+//! A format string contains replacement fields surrounded by `{}`. Each field
+//! may select an argument by index and include a spec after a `:`:
+//!
+//! ```text
+//! {[index][:[[fill]align][+][#][0][width][.precision][type]]}
+//! ```
+//!
+//! - `align` is one of `<` (left), `>` (right) or `^` (center). Numbers are
+//!   aligned right and everything else left by default.
+//! - `+` prints the sign of positive numbers, too.
+//! - `#` adds a `0x`, `0b` or `0o` prefix to ints formatted as `x`, `b` or `o`.
+//! - `0` pads numbers with zeros after the sign.
+//! - `precision` is the number of decimal places of a number, or the maximum
+//!   number of characters of any other value.
+//! - `type` is one of `x`/`X` (hex), `b` (binary), `o` (octal) or `e`
+//!   (scientific notation). If omitted, values are displayed as-is.
+//!
+//! Use `{{` and `}}` to write literal braces.
+
+use std::fmt::Write;
+
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+
+/// Format `args` according to `template`.
+pub fn format(template: &str, args: &[Value]) -> Result<String> {
+  let mut out = String::with_capacity(template.len());
+  let mut next_arg = 0;
+  let mut chars = template.char_indices().peekable();
+  while let Some((start, c)) = chars.next() {
+    match c {
+      '{' if chars.next_if(|&(_, c)| c == '{').is_some() => out.push('{'),
+      '}' if chars.next_if(|&(_, c)| c == '}').is_some() => out.push('}'),
+      '{' => {
+        let Some(len) = template[start..].find('}') else {
+          fail!("unterminated `{{` in format string");
+        };
+        let field = &template[start + 1..start + len];
+        // skip past the closing `}`
+        for _ in 0..field.chars().count() + 1 {
+          chars.next();
+        }
+
+        let (index, spec) = field.split_once(':').unwrap_or((field, ""));
+        let index = if index.is_empty() {
+          next_arg += 1;
+          next_arg - 1
+        } else {
+          match index.parse::<usize>() {
+            Ok(index) => index,
+            Err(_) => fail!("invalid argument index `{index}` in format string"),
+          }
+        };
+        let Some(arg) = args.get(index) else {
+          fail!(
+            "format string refers to argument {index}, but there are only {} arguments",
+            args.len()
+          );
+        };
+        format_value(&mut out, arg, &Spec::parse(spec)?)?;
+      }
+      '}' => fail!("unmatched `}}` in format string"),
+      c => out.push(c),
+    }
+  }
+  Ok(out)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+  Left,
+  Right,
+  Center,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+  Display,
+  LowerHex,
+  UpperHex,
+  Binary,
+  Octal,
+  Exp,
+}
+
+/// A parsed format spec, which is the part of a replacement field after `:`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spec {
+  pub fill: char,
+  pub align: Option<Align>,
+  pub sign: bool,
+  pub alternate: bool,
+  pub zero: bool,
+  pub width: usize,
+  pub precision: Option<usize>,
+  pub ty: Type,
+}
+
+impl Default for Spec {
+  fn default() -> Self {
+    Self {
+      fill: ' ',
+      align: None,
+      sign: false,
+      alternate: false,
+      zero: false,
+      width: 0,
+      precision: None,
+      ty: Type::Display,
+    }
+  }
+}
+
+fn to_align(c: char) -> Option<Align> {
+  match c {
+    '<' => Some(Align::Left),
+    '>' => Some(Align::Right),
+    '^' => Some(Align::Center),
+    _ => None,
+  }
+}
+
+impl Spec {
+  pub fn parse(spec: &str) -> Result<Self> {
+    let mut out = Spec::default();
+    let mut rest = spec.chars().peekable();
+
+    let mut lookahead = spec.chars();
+    match (lookahead.next(), lookahead.next().and_then(to_align)) {
+      (Some(fill), Some(align)) => {
+        out.fill = fill;
+        out.align = Some(align);
+        rest.nth(1);
+      }
+      (Some(c), _) if to_align(c).is_some() => {
+        out.align = to_align(c);
+        rest.next();
+      }
+      _ => {}
+    }
+
+    out.sign = rest.next_if_eq(&'+').is_some();
+    out.alternate = rest.next_if_eq(&'#').is_some();
+    out.zero = rest.next_if_eq(&'0').is_some();
+    let mut width = String::new();
+    while let Some(c) = rest.next_if(char::is_ascii_digit) {
+      width.push(c);
+    }
+    if !width.is_empty() {
+      out.width = parse_count(spec, &width)?;
+    }
+    if rest.next_if_eq(&'.').is_some() {
+      let mut precision = String::new();
+      while let Some(c) = rest.next_if(char::is_ascii_digit) {
+        precision.push(c);
+      }
+      out.precision = Some(parse_count(spec, &precision)?);
+    }
+    out.ty = match rest.next() {
+      None => Type::Display,
+      Some('x') => Type::LowerHex,
+      Some('X') => Type::UpperHex,
+      Some('b') => Type::Binary,
+      Some('o') => Type::Octal,
+      Some('e') => Type::Exp,
+      Some(c) => fail!("unknown format type `{c}` in `{spec}`"),
+    };
+    if rest.next().is_some() {
+      fail!("invalid format spec `{spec}`");
+    }
+
+    Ok(out)
+  }
+}
+
+fn parse_count(spec: &str, count: &str) -> Result<usize> {
+  match count.parse() {
+    Ok(count) => Ok(count),
+    Err(_) => fail!("invalid format spec `{spec}`"),
+  }
+}
+
+/// Write `value` to `out` according to `spec`.
+pub fn format_value(out: &mut String, value: &Value, spec: &Spec) -> Result<()> {
+  let number = match value.clone().to_float() {
+    Some(v) => Some(format_float(value, v, spec)?),
+    None => value.to_i64().map(|v| format_int(v, spec)),
+  };
+
+  let Some((negative, prefix, digits)) = number else {
+    if spec.ty != Type::Display {
+      fail!("cannot format `{value}` as a number");
+    }
+    let mut text = value.to_string();
+    if let Some(precision) = spec.precision {
+      if let Some((end, _)) = text.char_indices().nth(precision) {
+        text.truncate(end);
+      }
+    }
+    pad(out, &text, spec, Align::Left);
+    return Ok(());
+  };
+
+  let sign = match (negative, spec.sign) {
+    (true, _) => "-",
+    (false, true) => "+",
+    (false, false) => "",
+  };
+  if spec.zero && spec.align.is_none() {
+    let len = sign.len() + prefix.len() + digits.chars().count();
+    out.push_str(sign);
+    out.push_str(prefix);
+    out.extend(std::iter::repeat_n('0', spec.width.saturating_sub(len)));
+    out.push_str(&digits);
+  } else {
+    pad(out, &format!("{sign}{prefix}{digits}"), spec, Align::Right);
+  }
+  Ok(())
+}
+
+/// Returns whether `v` is negative, the prefix, and the absolute value of `v`.
+fn format_int(v: i64, spec: &Spec) -> (bool, &'static str, String) {
+  let abs = v.unsigned_abs();
+  let (prefix, digits) = match spec.ty {
+    Type::Display => match spec.precision {
+      Some(precision) => ("", format!("{:.precision$}", abs as f64)),
+      None => ("", abs.to_string()),
+    },
+    Type::LowerHex => ("0x", format!("{abs:x}")),
+    Type::UpperHex => ("0x", format!("{abs:X}")),
+    Type::Binary => ("0b", format!("{abs:b}")),
+    Type::Octal => ("0o", format!("{abs:o}")),
+    Type::Exp => ("", exp(abs as f64, spec.precision)),
+  };
+  let prefix = if spec.alternate { prefix } else { "" };
+  (v < 0, prefix, digits)
+}
+
+fn format_float(value: &Value, v: f64, spec: &Spec) -> Result<(bool, &'static str, String)> {
+  let abs = v.abs();
+  let digits = match (spec.ty, spec.precision) {
+    (Type::Display, Some(precision)) => format!("{abs:.precision$}"),
+    (Type::Display, None) => Value::float(abs).to_string(),
+    (Type::Exp, precision) => exp(abs, precision),
+    _ => fail!("cannot format float `{value}` as an int"),
+  };
+  Ok((v.is_sign_negative() && !v.is_nan(), "", digits))
+}
+
+fn exp(v: f64, precision: Option<usize>) -> String {
+  let mut out = String::new();
+  let _ = match precision {
+    Some(precision) => write!(out, "{v:.precision$e}"),
+    None => write!(out, "{v:e}"),
+  };
+  out
+}
+
+fn pad(out: &mut String, text: &str, spec: &Spec, default: Align) {
+  let padding = spec.width.saturating_sub(text.chars().count());
+  let (before, after) = match spec.align.unwrap_or(default) {
+    Align::Left => (0, padding),
+    Align::Right => (padding, 0),
+    Align::Center => (padding / 2, padding - padding / 2),
+  };
+  out.extend(std::iter::repeat_n(spec.fill, before));
+  out.push_str(text);
+  out.extend(std::iter::repeat_n(spec.fill, after));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fmt(template: &str, args: &[Value]) -> String {
+    format(template, args).unwrap()
+  }
+
+  #[test]
+  fn format_spec() {
+    let int = Value::int(42);
+    let neg = Value::int(-7);
+    let float = Value::float(1.23456);
+
+    assert_eq!(fmt("{} and {}", &[int.clone(), neg.clone()]), "42 and -7");
+    assert_eq!(fmt("{1} {0} {1}", &[int.clone(), neg.clone()]), "-7 42 -7");
+    assert_eq!(fmt("{{}} {}", &[int.clone()]), "{} 42");
+    assert_eq!(fmt("[{:>6}]", &[int.clone()]), "[    42]");
+    assert_eq!(fmt("[{:<6}]", &[int.clone()]), "[42    ]");
+    assert_eq!(fmt("[{:*^7}]", &[int.clone()]), "[**42***]");
+    assert_eq!(fmt("[{:+}]", &[int.clone()]), "[+42]");
+    assert_eq!(fmt("[{:05}]", &[neg.clone()]), "[-0007]");
+    assert_eq!(
+      fmt("[{:#x}] [{:X}]", &[int.clone(), int.clone()]),
+      "[0x2a] [2A]"
+    );
+    assert_eq!(fmt("[{:#010b}]", &[int.clone()]), "[0b00101010]");
+    assert_eq!(fmt("[{:o}]", &[int.clone()]), "[52]");
+    assert_eq!(fmt("[{:.2}]", &[float.clone()]), "[1.23]");
+    assert_eq!(fmt("[{:>8.2}]", &[float.clone()]), "[    1.23]");
+    assert_eq!(fmt("[{:.1}]", &[int.clone()]), "[42.0]");
+    assert_eq!(fmt("[{:.2e}]", &[float.clone()]), "[1.23e0]");
+    assert_eq!(fmt("[{:.1}]", &[Value::float(-1.26)]), "[-1.3]");
+    assert_eq!(
+      fmt("[{:>6}] [{:.2}]", &[Value::bool(true), Value::none()]),
+      "[  true] [no]"
+    );
+  }
+
+  #[test]
+  fn format_errors() {
+    let int = Value::int(42);
+    let err = |template: &str, args: &[Value]| format(template, args).unwrap_err().to_string();
+
+    assert!(err("{", &[]).contains("unterminated"));
+    assert!(err("}", &[]).contains("unmatched"));
+    assert!(err("{} {}", &[int.clone()]).contains("only 1 arguments"));
+    assert!(err("{a}", &[int.clone()]).contains("invalid argument index"));
+    assert!(err("{:q}", &[int.clone()]).contains("unknown format type"));
+    assert!(err("{:5.}", &[int.clone()]).contains("invalid format spec"));
+    assert!(err("{:x}", &[Value::float(1.0)]).contains("cannot format float"));
+    assert!(err("{:x}", &[Value::none()]).contains("as a number"));
+  }
+}
@@ -0,0 +1,224 @@
+//! Immutable values which may be shared between VMs.
+
+use std::sync::Arc;
+
+use super::global::Global;
+use crate::internal::error::Result;
+use crate::internal::object::int;
+use crate::internal::object::{Any, List, Ptr, Str, Tuple};
+use crate::internal::value::Value;
+
+/// An immutable copy of a value, which is not tied to any VM.
+///
+/// Unlike the values of a VM, a frozen value is reference counted atomically,
+/// so it implements `Send` and `Sync`, and cloning it is cheap. This makes it
+/// a good fit for data such as configuration, which is loaded once and then
+/// used by many VMs running on different threads.
+///
+/// Only `none`, bools, numbers, strings and tuples may be frozen. Lists are
+/// frozen as tuples, anything else can't be frozen.
+///
+/// ```rust
+/// use hebi::{Frozen, Hebi};
+///
+/// let mut hebi = Hebi::new();
+/// let config = hebi.eval(r#"("localhost", [8080, 8081])"#).unwrap();
+/// let config: Frozen = config.freeze().unwrap();
+///
+/// std::thread::spawn(move || {
+///   let mut worker = Hebi::new();
+///   let global = worker.global();
+///   global.set(global.new_string("config"), config.thaw(global.clone()));
+///   let port = worker.eval("config[1][0]").unwrap();
+///   assert_eq!(port.as_int(), Some(8080));
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frozen(Repr);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Repr {
+  None,
+  Bool(bool),
+  Int(i64),
+  Float(f64),
+  #[cfg(feature = "bigint")]
+  BigInt(Arc<num_bigint::BigInt>),
+  Str(Arc<str>),
+  Tuple(Arc<[Frozen]>),
+}
+
+impl Frozen {
+  pub fn is_none(&self) -> bool {
+    matches!(self.0, Repr::None)
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self.0 {
+      Repr::Bool(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  pub fn as_int(&self) -> Option<i64> {
+    match self.0 {
+      Repr::Int(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  pub fn as_float(&self) -> Option<f64> {
+    match self.0 {
+      Repr::Float(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  #[cfg(feature = "bigint")]
+  pub fn as_bigint(&self) -> Option<&num_bigint::BigInt> {
+    match &self.0 {
+      Repr::BigInt(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match &self.0 {
+      Repr::Str(v) => Some(v),
+      _ => None,
+    }
+  }
+
+  /// The items of a frozen tuple or list.
+  pub fn as_tuple(&self) -> Option<&[Frozen]> {
+    match &self.0 {
+      Repr::Tuple(v) => Some(v),
+      _ => None,
+    }
+  }
+}
+
+impl From<()> for Frozen {
+  fn from(_: ()) -> Self {
+    Self(Repr::None)
+  }
+}
+
+impl From<bool> for Frozen {
+  fn from(value: bool) -> Self {
+    Self(Repr::Bool(value))
+  }
+}
+
+impl From<i32> for Frozen {
+  fn from(value: i32) -> Self {
+    Self(Repr::Int(value as i64))
+  }
+}
+
+impl From<i64> for Frozen {
+  fn from(value: i64) -> Self {
+    Self(Repr::Int(value))
+  }
+}
+
+impl From<f64> for Frozen {
+  fn from(value: f64) -> Self {
+    Self(Repr::Float(value))
+  }
+}
+
+impl From<&str> for Frozen {
+  fn from(value: &str) -> Self {
+    Self(Repr::Str(value.into()))
+  }
+}
+
+impl From<String> for Frozen {
+  fn from(value: String) -> Self {
+    Self(Repr::Str(value.into()))
+  }
+}
+
+/// A tuple of `items`.
+impl From<Vec<Frozen>> for Frozen {
+  fn from(items: Vec<Frozen>) -> Self {
+    Self(Repr::Tuple(items.into()))
+  }
+}
+
+/// Freeze `value`, see [`Frozen`].
+///
+/// A list which contains itself can't be frozen, because the copy would be
+/// infinitely large.
+pub fn freeze(value: &Value) -> Result<Frozen> {
+  freeze_inner(value, &mut vec![])
+}
+
+fn freeze_inner(value: &Value, parents: &mut Vec<Ptr<Any>>) -> Result<Frozen> {
+  if value.is_none() {
+    return Ok(Frozen(Repr::None));
+  }
+  if let Some(v) = value.clone().to_bool() {
+    return Ok(Frozen(Repr::Bool(v)));
+  }
+  if let Some(v) = value.to_i64() {
+    return Ok(Frozen(Repr::Int(v)));
+  }
+  if let Some(v) = value.clone().to_float() {
+    return Ok(Frozen(Repr::Float(v)));
+  }
+  #[cfg(feature = "bigint")]
+  if let Some(v) = value
+    .clone()
+    .to_object::<crate::internal::object::bigint::BigInt>()
+  {
+    return Ok(Frozen(Repr::BigInt(Arc::new(v.value().clone()))));
+  }
+  let Some(object) = value.clone().to_any() else {
+    fail!("cannot freeze `{value}`");
+  };
+  if let Some(v) = object.clone_cast::<Str>() {
+    return Ok(Frozen(Repr::Str(v.as_str().into())));
+  }
+
+  let items = if let Some(v) = object.clone_cast::<Tuple>() {
+    v.as_slice().to_vec()
+  } else if let Some(v) = object.clone_cast::<List>() {
+    v.iter().collect()
+  } else {
+    fail!("cannot freeze `{value}`");
+  };
+  if parents.iter().any(|parent| parent.ptr_eq(&object)) {
+    fail!("cannot freeze `{value}`, because it contains itself");
+  }
+  parents.push(object);
+  let items = items
+    .iter()
+    .map(|item| freeze_inner(item, parents))
+    .collect::<Result<Vec<_>>>();
+  parents.pop();
+  Ok(Frozen(Repr::Tuple(items?.into())))
+}
+
+/// Recreate `frozen` in the VM which owns `global`. Tuples, strings, and
+/// numbers which don't fit inline are allocated there.
+pub fn thaw(frozen: &Frozen, global: &Global) -> Value {
+  match &frozen.0 {
+    Repr::None => Value::none(),
+    Repr::Bool(v) => Value::bool(*v),
+    Repr::Int(v) => int::int(global, *v),
+    Repr::Float(v) => Value::float(*v),
+    #[cfg(feature = "bigint")]
+    Repr::BigInt(v) => Value::object(global.alloc(crate::internal::object::bigint::BigInt::new(
+      v.as_ref().clone(),
+    ))),
+    Repr::Str(v) => Value::object(global.alloc(Str::owned(v.to_string()))),
+    Repr::Tuple(v) => {
+      let items = v.iter().map(|item| thaw(item, global)).collect::<Vec<_>>();
+      Value::object(global.alloc(Tuple::from(items)))
+    }
+  }
+}
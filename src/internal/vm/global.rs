@@ -1,17 +1,30 @@
-use std::any::TypeId;
-use std::cell::RefCell;
+use std::any::{Any as StdAny, TypeId};
+use std::cell::{Cell, OnceCell, Ref, RefCell, RefMut};
 use std::fmt::Debug;
+use std::io::Write;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use indexmap::{IndexMap, IndexSet};
 
-use super::Config;
-use crate::internal::error::Result;
+use super::allocator::Allocator;
+use super::cancel::CancellationToken;
+#[cfg(feature = "coverage")]
+use super::coverage::Coverage;
+use super::host::HostDispatcher;
+use super::policy::SecurityPolicy;
+#[cfg(feature = "profiler")]
+use super::profiler::Profiler;
+use super::safepoint::Safepoints;
+use super::{snapshot, Config, IntOverflow, ResolvedConfig};
+use crate::internal::error::{Error, Result};
+use crate::internal::object::class::ClassType;
 use crate::internal::object::module::{Module, ModuleId};
-use crate::internal::object::native::NativeClass;
-use crate::internal::object::{module, table, Ptr, Str, Table};
+use crate::internal::object::native::{NativeClass, NativeFunction};
+use crate::internal::object::protocol::Protocol;
+use crate::internal::object::{module, table, Any, Ptr, Str, Table};
 use crate::internal::value::Value;
+use crate::span::Span;
 use crate::Cow;
 
 #[derive(Debug, Clone)]
@@ -40,14 +53,72 @@ impl<T: std::io::Write + IoBase> Output for T {}
 pub trait Input: std::io::Read + IoBase {}
 impl<T: std::io::Read + IoBase> Input for T {}
 
+/// The stream which a script printed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+  /// Written by the `print` statement.
+  Stdout,
+  /// Written by the `eprint` builtin.
+  Stderr,
+}
+
+/// A builtin type which may be extended with methods defined by the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BuiltinKind {
+  Str,
+  List,
+  Table,
+  Int,
+  Float,
+}
+
+/// Receives everything a script prints, instead of the VM's outputs.
+///
+/// This is useful for hosts which display output somewhere other than a
+/// terminal, such as a game console or a web page.
+pub trait PrintHook: IoBase {
+  /// Called once for every `print` statement or call to `eprint`.
+  ///
+  /// `text` does not include the trailing newline. `span` is the span of the
+  /// statement or call, relative to the source of the module it is in.
+  fn print(&mut self, stream: Stream, text: &str, span: Span);
+}
+
 pub struct State {
   globals: Ptr<Table>,
   io: Io,
   module_registry: RefCell<module::Registry>,
-  module_loader: Box<dyn module::ModuleLoader>,
-  module_visited_set: RefCell<IndexSet<ModuleId>>,
-  string_table: RefCell<IndexMap<Cow<'static, str>, Ptr<Str>>>,
+  module_loader: module::AnyModuleLoader,
+  /// Modules which are currently being initialized, in the order in which
+  /// they were imported, along with the span of the import statement which
+  /// started loading each one.
+  import_chain: RefCell<IndexMap<ModuleId, Span>>,
+  string_table: RefCell<IndexSet<Ptr<Str>>>,
   type_map: RefCell<IndexMap<TypeId, Ptr<NativeClass>>>,
+  /// Values of any type set by the host, at most one per type.
+  user_data: RefCell<IndexMap<TypeId, Box<dyn StdAny + Send>>>,
+  /// Methods registered by the host on builtin types. These are only used
+  /// if the type doesn't already have a method with the same name.
+  builtin_methods: RefCell<IndexMap<BuiltinKind, IndexMap<String, Ptr<NativeFunction>>>>,
+  /// The builtin `Error` class, which every raised value must derive from.
+  error_class: OnceCell<Ptr<ClassType>>,
+  /// Objects which have a finalizer that hasn't run yet.
+  finalizers: RefCell<Vec<Ptr<Any>>>,
+  /// The number of safepoints left until `finalizers` is scanned again.
+  finalizer_countdown: Cell<usize>,
+  snapshots: RefCell<snapshot::Store>,
+  safepoints: Safepoints,
+  int_overflow: IntOverflow,
+  optimize: bool,
+  type_checks: bool,
+  security_policy: Option<Box<dyn SecurityPolicy>>,
+  host_dispatcher: Option<Box<dyn HostDispatcher>>,
+  allocator: Option<Rc<dyn Allocator>>,
+  args: Vec<String>,
+  #[cfg(feature = "profiler")]
+  profiler: Profiler,
+  #[cfg(feature = "coverage")]
+  coverage: Coverage,
 }
 
 impl Debug for State {
@@ -57,9 +128,14 @@ impl Debug for State {
       .field("io", &"<...>")
       .field("module_registry", &self.module_registry)
       .field("module_loader", &"<...>")
-      .field("module_visited_set", &self.module_visited_set)
+      .field("import_chain", &self.import_chain)
       .field("string_table", &self.string_table)
       .field("type_map", &self.type_map)
+      .field("int_overflow", &self.int_overflow)
+      .field("optimize", &self.optimize)
+      .field("type_checks", &self.type_checks)
+      .field("security_policy", &"<...>")
+      .field("host_dispatcher", &"<...>")
       .finish()
   }
 }
@@ -67,20 +143,46 @@ impl Debug for State {
 pub struct Io {
   pub(crate) input: RefCell<Box<dyn Input>>,
   pub(crate) output: RefCell<Box<dyn Output>>,
+  pub(crate) error_output: RefCell<Box<dyn Output>>,
+  pub(crate) print_hook: RefCell<Option<Box<dyn PrintHook>>>,
 }
 
 impl Io {
-  pub fn new(input: Box<dyn Input>, output: Box<dyn Output>) -> Self {
+  pub fn new(
+    input: Box<dyn Input>,
+    output: Box<dyn Output>,
+    error_output: Box<dyn Output>,
+  ) -> Self {
     Self {
       input: RefCell::new(Box::new(input)),
       output: RefCell::new(Box::new(output)),
+      error_output: RefCell::new(Box::new(error_output)),
+      print_hook: RefCell::new(None),
     }
   }
+
+  /// Write a line of `text` printed by a script to `stream`, or pass it to
+  /// the print hook if there is one.
+  pub fn print(&self, stream: Stream, text: &str, span: Span) -> Result<()> {
+    if let Some(hook) = self.print_hook.borrow_mut().as_mut() {
+      hook.print(stream, text, span);
+      return Ok(());
+    }
+    let mut output = match stream {
+      Stream::Stdout => self.output.borrow_mut(),
+      Stream::Stderr => self.error_output.borrow_mut(),
+    };
+    writeln!(&mut output, "{text}").map_err(Error::user)
+  }
 }
 
 impl Default for Io {
   fn default() -> Self {
-    Self::new(Box::new(std::io::stdin()), Box::new(std::io::stdout()))
+    Self::new(
+      Box::new(std::io::stdin()),
+      Box::new(std::io::stdout()),
+      Box::new(std::io::stderr()),
+    )
   }
 }
 
@@ -91,22 +193,61 @@ impl Default for Global {
 }
 
 impl Global {
-  pub fn new(config: Config) -> Self {
-    let (module_loader, input, output) = config.resolve();
+  pub fn new(mut config: Config) -> Self {
+    let int_overflow = config.int_overflow;
+    let optimize = config.optimize;
+    let type_checks = config.type_checks;
+    let print_hook = config.print_hook.take();
+    let safepoint_hook = config.safepoint_hook.take();
+    let security_policy = config.security_policy.take();
+    let host_dispatcher = config.host_dispatcher.take();
+    let allocator = config.allocator.take().map(Rc::<dyn Allocator>::from);
+    let args = std::mem::take(&mut config.args);
+    let ResolvedConfig {
+      module_loader,
+      input,
+      output,
+      error_output,
+    } = config.resolve();
     let io = Io {
       input: RefCell::new(input),
       output: RefCell::new(output),
+      error_output: RefCell::new(error_output),
+      print_hook: RefCell::new(print_hook),
     };
 
     Self {
       inner: Rc::new(State {
-        globals: unsafe { Ptr::alloc_raw(Table::with_capacity(0)) },
+        globals: unsafe {
+          match &allocator {
+            Some(allocator) => Ptr::alloc_in(Table::with_capacity(0), allocator.clone()),
+            None => Ptr::alloc_raw(Table::with_capacity(0)),
+          }
+        },
         io,
         module_registry: RefCell::new(module::Registry::new()),
         module_loader,
-        module_visited_set: RefCell::new(IndexSet::new()),
-        string_table: RefCell::new(IndexMap::new()),
+        import_chain: RefCell::new(IndexMap::new()),
+        string_table: RefCell::new(IndexSet::new()),
         type_map: RefCell::new(IndexMap::new()),
+        user_data: RefCell::new(IndexMap::new()),
+        builtin_methods: RefCell::new(IndexMap::new()),
+        error_class: OnceCell::new(),
+        finalizers: RefCell::new(Vec::new()),
+        finalizer_countdown: Cell::new(0),
+        snapshots: RefCell::new(snapshot::Store::default()),
+        safepoints: Safepoints::new(safepoint_hook),
+        int_overflow,
+        optimize,
+        type_checks,
+        security_policy,
+        host_dispatcher,
+        allocator,
+        args,
+        #[cfg(feature = "profiler")]
+        profiler: Profiler::new(),
+        #[cfg(feature = "coverage")]
+        coverage: Coverage::new(),
       }),
     }
   }
@@ -120,19 +261,46 @@ impl Global {
   }
 
   pub fn is_module_visited(&self, module_id: ModuleId) -> bool {
-    self.module_visited_set.borrow().contains(&module_id)
+    self.import_chain.borrow().contains_key(&module_id)
+  }
+
+  /// Returns the part of the import chain which begins at `module_id`, as
+  /// a list of module names and the spans of the statements which imported
+  /// them.
+  pub fn import_chain_from(&self, module_id: ModuleId) -> Vec<(Ptr<Str>, Span)> {
+    let import_chain = self.import_chain.borrow();
+    let Some(start) = import_chain.get_index_of(&module_id) else {
+      return vec![];
+    };
+    let registry = self.module_registry.borrow();
+    import_chain
+      .iter()
+      .skip(start)
+      .filter_map(|(id, span)| Some((registry.get_by_id(*id)?.name.clone(), *span)))
+      .collect()
   }
 
   pub fn get_module_by_id(&self, module_id: ModuleId) -> Option<Ptr<Module>> {
     self.module_registry.borrow().get_by_id(module_id)
   }
 
+  /// The names of all loaded modules, including registered native modules.
+  pub fn module_names(&self) -> Vec<Ptr<Str>> {
+    self
+      .module_registry
+      .borrow()
+      .index
+      .keys()
+      .cloned()
+      .collect()
+  }
+
   pub fn get_module_by_name(&self, name: &str) -> Option<(ModuleId, Ptr<Module>)> {
     self.module_registry.borrow().get_by_name(name)
   }
 
   pub fn finish_module(&self, module_id: ModuleId, success: bool) {
-    self.module_visited_set.borrow_mut().remove(&module_id);
+    self.import_chain.borrow_mut().shift_remove(&module_id);
     if !success {
       self.module_registry.borrow_mut().remove(module_id);
     }
@@ -142,27 +310,40 @@ impl Global {
     self.module_registry.borrow_mut().next_module_id()
   }
 
-  pub fn load_module(&self, path: &str) -> Result<Cow<'static, str>> {
+  pub fn load_module(&self, path: &module::ModulePath) -> Result<module::LoadResult> {
     self.module_loader.load(path)
   }
 
-  pub fn define_module(&self, module_id: ModuleId, name: Ptr<Str>, module: Ptr<Module>) {
+  pub fn define_module(
+    &self,
+    module_id: ModuleId,
+    name: Ptr<Str>,
+    module: Ptr<Module>,
+    import_span: Span,
+  ) {
     self
       .module_registry
       .borrow_mut()
       .insert(module_id, name, module);
-    self.module_visited_set.borrow_mut().insert(module_id);
+    self
+      .import_chain
+      .borrow_mut()
+      .insert(module_id, import_span);
   }
 
+  /// Returns the one string with the contents `s`.
+  ///
+  /// Interned strings are compared by pointer, so field names and other
+  /// strings which are frequently used as keys should be interned.
   pub fn intern(&self, s: impl Into<Cow<'static, str>>) -> Ptr<Str> {
     let s = s.into();
 
-    if let Some(s) = self.inner.string_table.borrow().get(&s) {
-      return s.clone();
+    if let Some(v) = self.inner.string_table.borrow().get(s.as_ref()) {
+      return v.clone();
     }
 
-    let v = self.alloc(Str::owned(s.clone()));
-    self.inner.string_table.borrow_mut().insert(s, v.clone());
+    let v = self.alloc(Str::interned(s));
+    self.inner.string_table.borrow_mut().insert(v.clone());
     v
   }
 
@@ -183,6 +364,179 @@ impl Global {
       .cloned()
   }
 
+  /// Returns the value of type `T` which was set before, if any.
+  ///
+  /// Panics if user data is currently borrowed.
+  pub fn set_user_data<T: Send + 'static>(&self, value: T) -> Option<T> {
+    let previous = self
+      .inner
+      .user_data
+      .borrow_mut()
+      .insert(TypeId::of::<T>(), Box::new(value));
+    previous
+      .and_then(|previous| previous.downcast::<T>().ok())
+      .map(|previous| *previous)
+  }
+
+  /// Panics if user data is currently borrowed.
+  pub fn remove_user_data<T: Send + 'static>(&self) -> Option<T> {
+    let value = self
+      .inner
+      .user_data
+      .borrow_mut()
+      .shift_remove(&TypeId::of::<T>());
+    value
+      .and_then(|value| value.downcast::<T>().ok())
+      .map(|value| *value)
+  }
+
+  pub fn user_data<T: Send + 'static>(&self) -> Option<Ref<'_, T>> {
+    Ref::filter_map(self.inner.user_data.borrow(), |user_data| {
+      user_data
+        .get(&TypeId::of::<T>())
+        .and_then(|value| value.downcast_ref::<T>())
+    })
+    .ok()
+  }
+
+  pub fn register_builtin_method(&self, kind: BuiltinKind, function: Ptr<NativeFunction>) {
+    self
+      .inner
+      .builtin_methods
+      .borrow_mut()
+      .entry(kind)
+      .or_default()
+      .insert(function.name.as_str().to_string(), function);
+  }
+
+  pub fn builtin_method(&self, kind: BuiltinKind, name: &str) -> Option<Ptr<NativeFunction>> {
+    self
+      .inner
+      .builtin_methods
+      .borrow()
+      .get(&kind)?
+      .get(name)
+      .cloned()
+  }
+
+  /// Define a global protocol called `name`, which is implemented by any value
+  /// that has all of `methods`.
+  pub fn register_protocol(&self, name: &str, methods: &[&str]) -> Ptr<Protocol> {
+    let name = self.intern(name.to_string());
+    let methods = methods
+      .iter()
+      .map(|method| self.intern(method.to_string()))
+      .collect::<Vec<_>>();
+    let protocol = self.alloc(Protocol::new(name.clone(), methods));
+    self.set(name, Value::object(protocol.clone()));
+    protocol
+  }
+
+  pub fn set_error_class(&self, class: Ptr<ClassType>) {
+    let _ = self.inner.error_class.set(class);
+  }
+
+  pub fn error_class(&self) -> Option<Ptr<ClassType>> {
+    self.inner.error_class.get().cloned()
+  }
+
+  /// Run the finalizer of `object` once it is no longer referenced by
+  /// anything else, or when the VM is dropped.
+  pub fn register_finalizer(&self, object: Ptr<Any>) {
+    self.inner.finalizers.borrow_mut().push(object);
+  }
+
+  /// Remove the objects whose finalizers should run now. These are the
+  /// objects which are only referenced by the list of finalizers, or all of
+  /// them if `all` is set.
+  pub fn take_finalizable(&self, all: bool) -> Vec<Ptr<Any>> {
+    let mut finalizers = self.inner.finalizers.borrow_mut();
+    if all {
+      return std::mem::take(&mut *finalizers);
+    }
+    let mut finalizable = vec![];
+    finalizers.retain(|object| {
+      if object.refs() == 1 {
+        finalizable.push(object.clone());
+        false
+      } else {
+        true
+      }
+    });
+    finalizable
+  }
+
+  /// Like [`Global::take_finalizable`], but called at every safepoint.
+  ///
+  /// The list is only scanned once per as many safepoints as it has entries,
+  /// so a script which keeps many objects with finalizers alive doesn't pay
+  /// for scanning all of them on every call and loop iteration.
+  pub fn take_finalizable_at_safepoint(&self) -> Vec<Ptr<Any>> {
+    let countdown = self.inner.finalizer_countdown.get();
+    if countdown > 0 {
+      self.inner.finalizer_countdown.set(countdown - 1);
+      return vec![];
+    }
+    let finalizable = self.take_finalizable(false);
+    self
+      .inner
+      .finalizer_countdown
+      .set(self.inner.finalizers.borrow().len());
+    finalizable
+  }
+
+  /// Replace the cancellation token, and return the previous one.
+  pub fn set_cancellation(&self, token: Option<CancellationToken>) -> Option<CancellationToken> {
+    self.inner.safepoints.set_cancellation(token)
+  }
+
+  /// Called at every safepoint. Fails if the current evaluation was
+  /// cancelled, or if the safepoint hook fails.
+  #[inline(always)]
+  pub fn safepoint(&self) -> Result<()> {
+    self.inner.safepoints.check()
+  }
+
+  pub fn int_overflow(&self) -> IntOverflow {
+    self.inner.int_overflow
+  }
+
+  pub fn optimize(&self) -> bool {
+    self.inner.optimize
+  }
+
+  pub fn type_checks(&self) -> bool {
+    self.inner.type_checks
+  }
+
+  pub fn security_policy(&self) -> Option<&dyn SecurityPolicy> {
+    self.inner.security_policy.as_deref()
+  }
+
+  pub fn host_dispatcher(&self) -> Option<&dyn HostDispatcher> {
+    self.inner.host_dispatcher.as_deref()
+  }
+
+  pub fn allocator(&self) -> Option<&Rc<dyn Allocator>> {
+    self.inner.allocator.as_ref()
+  }
+
+  /// The arguments passed to the script, which are available to it as
+  /// `argv`.
+  pub fn args(&self) -> &[String] {
+    &self.inner.args
+  }
+
+  #[cfg(feature = "profiler")]
+  pub fn profiler(&self) -> &Profiler {
+    &self.inner.profiler
+  }
+
+  #[cfg(feature = "coverage")]
+  pub fn coverage(&self) -> &Coverage {
+    &self.inner.coverage
+  }
+
   pub fn io(&self) -> &Io {
     &self.inner.io
   }
@@ -190,6 +544,26 @@ impl Global {
   pub fn entries(&self) -> table::Entries<'_> {
     self.inner.globals.entries()
   }
+
+  pub fn globals(&self) -> &Ptr<Table> {
+    &self.inner.globals
+  }
+
+  /// Returns every module which was loaded so far.
+  pub fn modules(&self) -> Vec<Ptr<Module>> {
+    self
+      .inner
+      .module_registry
+      .borrow()
+      .modules
+      .values()
+      .cloned()
+      .collect()
+  }
+
+  pub fn snapshots(&self) -> RefMut<'_, snapshot::Store> {
+    self.inner.snapshots.borrow_mut()
+  }
 }
 
 impl Deref for Global {
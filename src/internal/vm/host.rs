@@ -0,0 +1,44 @@
+//! Calling into the host by name.
+
+use crate::internal::error::Result;
+use crate::public::{Scope, Value};
+
+/// Handles `host.call(name, ...args)` in scripts.
+///
+/// Registering a native function for each command of an application is
+/// tedious when there are many of them, or when they are only known at
+/// runtime. A dispatcher receives every call instead, along with the name of
+/// the command, and may route it to an existing command registry.
+///
+/// The arguments after the name are the params of `scope`.
+///
+/// ```rust
+/// use hebi::prelude::*;
+/// use hebi::HostDispatcher;
+///
+/// struct Commands;
+///
+/// impl HostDispatcher for Commands {
+///   fn call<'cx>(&self, name: &str, scope: Scope<'cx>) -> hebi::Result<Value<'cx>> {
+///     match name {
+///       "add" => {
+///         let (a, b) = scope.params::<(i32, i32)>()?;
+///         (a + b).into_value(scope.global())
+///       }
+///       "count" => (scope.num_args() as i32).into_value(scope.global()),
+///       _ => fail!("unknown command `{name}`"),
+///     }
+///   }
+/// }
+///
+/// let mut hebi = Hebi::builder().host_dispatcher(Commands).finish();
+/// let value = hebi.eval(r#"host.call("add", 1, 2)"#).unwrap();
+/// assert_eq!(value.as_int(), Some(3));
+/// let value = hebi.eval(r#"host.call("count", "a", "b")"#).unwrap();
+/// assert_eq!(value.as_int(), Some(2));
+/// ```
+pub trait HostDispatcher: Send + 'static {
+  /// Call the command called `name`. Returning an error raises it in the
+  /// script.
+  fn call<'cx>(&self, name: &str, scope: Scope<'cx>) -> Result<Value<'cx>>;
+}
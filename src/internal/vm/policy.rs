@@ -0,0 +1,95 @@
+//! Restricting what scripts are allowed to do.
+
+use crate::internal::error::Result;
+use crate::internal::object::module::ModulePath;
+
+/// Decides whether a script may perform certain operations.
+///
+/// The VM consults the policy before each of the operations below. Returning
+/// an error denies the operation, and the error is raised in the script like
+/// any other runtime error. The default implementation of every check allows
+/// the operation, so a policy only has to implement the ones it cares about.
+///
+/// ```rust
+/// use hebi::prelude::*;
+/// use hebi::SecurityPolicy;
+///
+/// struct NoFs;
+///
+/// impl SecurityPolicy for NoFs {
+///   fn check_import(&self, path: &ModulePath) -> hebi::Result<()> {
+///     if path.as_str() == "fs" {
+///       fail!("module `fs` is not available");
+///     }
+///     Ok(())
+///   }
+/// }
+///
+/// let mut hebi = Hebi::builder().security_policy(NoFs).finish();
+/// let e = hebi.eval("import fs").unwrap_err();
+/// assert!(e.to_string().contains("not available"));
+/// ```
+pub trait SecurityPolicy: Send + Sync + 'static {
+  /// Called whenever an `import` runs, even if the module is already loaded.
+  fn check_import(&self, path: &ModulePath) -> Result<()> {
+    let _ = path;
+    Ok(())
+  }
+
+  /// Called before a script calls a native function, where `name` is the
+  /// name the function was registered with, and before `host.call`, where
+  /// `name` is the name of the command.
+  ///
+  /// This is also a good place to record an audit log of native calls.
+  fn check_native_call(&self, name: &str) -> Result<()> {
+    let _ = name;
+    Ok(())
+  }
+
+  /// Called before a script writes to the global variable `name`.
+  ///
+  /// Variables declared at the top level of the main script are globals, so
+  /// declaring them is also a write.
+  fn check_global_write(&self, name: &str) -> Result<()> {
+    let _ = name;
+    Ok(())
+  }
+
+  /// Called before a script reads the environment variable `name` using the
+  /// `env` builtin or `os.env`, which are only available with
+  /// [`HebiBuilder::allow_os`].
+  ///
+  /// [`HebiBuilder::allow_os`]: crate::HebiBuilder::allow_os
+  fn check_env(&self, name: &str) -> Result<()> {
+    let _ = name;
+    Ok(())
+  }
+
+  /// Called before a script accesses `path` using the `fs` module, which
+  /// must be enabled with [`HebiBuilder::allow_fs`].
+  ///
+  /// `path` is the path as it was passed by the script, so it may be
+  /// relative to the working directory.
+  ///
+  /// [`HebiBuilder::allow_fs`]: crate::HebiBuilder::allow_fs
+  fn check_fs(&self, path: &str, access: FsAccess) -> Result<()> {
+    let _ = (path, access);
+    Ok(())
+  }
+
+  /// Called before a script runs `program` with `args` using `os.run`, which
+  /// is only available with the `process` feature.
+  fn check_process(&self, program: &str, args: &[String]) -> Result<()> {
+    let _ = (program, args);
+    Ok(())
+  }
+}
+
+/// How a script accesses the file system, see [`SecurityPolicy::check_fs`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsAccess {
+  /// Reading a file, listing a directory, or checking if a path exists.
+  Read,
+  /// Creating or writing to a file.
+  Write,
+}
@@ -0,0 +1,151 @@
+//! Counting instructions and timing script functions.
+//!
+//! Only compiled with the `profiler` feature, because it adds work to every
+//! dispatched instruction and every call.
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use indexmap::IndexMap;
+
+use crate::internal::bytecode::opcode::Opcode;
+use crate::internal::object::function::FunctionDescriptor;
+use crate::internal::object::Ptr;
+
+pub struct Profiler {
+  opcodes: [Cell<u64>; 256],
+  functions: RefCell<IndexMap<Key, FunctionStats>>,
+}
+
+/// Identifies a function descriptor by its address.
+struct Key(Ptr<FunctionDescriptor>);
+
+impl Hash for Key {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.0.ptr_hash(state)
+  }
+}
+
+impl PartialEq for Key {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.ptr_eq(&other.0)
+  }
+}
+
+impl Eq for Key {}
+
+#[derive(Default)]
+struct FunctionStats {
+  calls: u64,
+  total: Duration,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Self {
+      opcodes: std::array::from_fn(|_| Cell::new(0)),
+      functions: RefCell::new(IndexMap::new()),
+    }
+  }
+
+  #[inline]
+  pub fn record_op(&self, opcode: Opcode) {
+    let count = &self.opcodes[opcode as usize];
+    count.set(count.get() + 1);
+  }
+
+  /// Record a call to `function` which returned or unwound after `elapsed`.
+  pub fn record_call(&self, function: &Ptr<FunctionDescriptor>, elapsed: Duration) {
+    let mut functions = self.functions.borrow_mut();
+    let stats = functions.entry(Key(function.clone())).or_default();
+    stats.calls += 1;
+    stats.total += elapsed;
+  }
+
+  pub fn report(&self) -> ProfileReport {
+    let mut opcodes = self
+      .opcodes
+      .iter()
+      .enumerate()
+      .filter(|(_, count)| count.get() > 0)
+      .filter_map(|(opcode, count)| {
+        Some(OpcodeProfile {
+          name: format!("{:?}", Opcode::try_from(opcode as u8).ok()?),
+          count: count.get(),
+        })
+      })
+      .collect::<Vec<_>>();
+    opcodes.sort_by_key(|op| Reverse(op.count));
+
+    let mut functions = self
+      .functions
+      .borrow()
+      .iter()
+      .map(|(key, stats)| FunctionProfile {
+        name: key.0.name.to_string(),
+        calls: stats.calls,
+        total: stats.total,
+      })
+      .collect::<Vec<_>>();
+    functions.sort_by_key(|function| Reverse(function.total));
+
+    ProfileReport { opcodes, functions }
+  }
+
+  pub fn reset(&self) {
+    for count in self.opcodes.iter() {
+      count.set(0);
+    }
+    self.functions.borrow_mut().clear();
+  }
+}
+
+/// What a VM spent its time on, as returned by
+/// [`Hebi::profile_report`][`crate::Hebi::profile_report`].
+#[derive(Clone, Debug, Default)]
+pub struct ProfileReport {
+  /// How many times each instruction was dispatched, most frequent first.
+  /// Instructions which never ran are left out.
+  pub opcodes: Vec<OpcodeProfile>,
+  /// Every script function which has returned at least once, slowest first.
+  pub functions: Vec<FunctionProfile>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OpcodeProfile {
+  pub name: String,
+  pub count: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionProfile {
+  pub name: String,
+  /// The number of calls which have returned, including ones which ended
+  /// with an error.
+  pub calls: u64,
+  /// The wall time spent in all calls. This includes the time spent in
+  /// functions called from this one, and the time spent awaiting native
+  /// async functions.
+  pub total: Duration,
+}
+
+impl Display for ProfileReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "opcodes:")?;
+    for op in self.opcodes.iter() {
+      writeln!(f, "  {:<24} {:>12}", op.name, op.count)?;
+    }
+    writeln!(f, "functions:")?;
+    for function in self.functions.iter() {
+      writeln!(
+        f,
+        "  {:<24} {:>12} calls {:>12?}",
+        function.name, function.calls, function.total
+      )?;
+    }
+    Ok(())
+  }
+}
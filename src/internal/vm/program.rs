@@ -0,0 +1,320 @@
+//! Compiled programs which are not tied to a specific VM.
+//!
+//! Objects allocated by a VM use non-atomic reference counts, so they can't be
+//! shared between VMs running on different threads. A [`CompiledProgram`]
+//! instead stores the output of codegen as plain data, which is `Send` and
+//! `Sync`. Linking it into a VM only allocates the descriptors, it does not
+//! parse or emit any code.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::global::Global;
+use super::Chunk;
+use crate::internal::bytecode::opcode as op;
+use crate::internal::object::class::ClassDescriptor;
+use crate::internal::object::function::{KwParam, ParamHint, Params, Upvalue};
+use crate::internal::object::int::BoxedInt;
+use crate::internal::object::module::ModuleId;
+use crate::internal::object::{
+  Bytes, ConstantPool, Function, FunctionDescriptor, List, Ptr, Str, Table,
+};
+use crate::internal::value::constant::{Constant, JumpTable, KwNames, NonNaNFloat};
+use crate::internal::value::Value;
+use crate::span::Span;
+
+/// The main function of a script, along with everything it references.
+#[derive(Debug)]
+pub struct CompiledProgram {
+  source: Arc<str>,
+  main: CompiledFunction,
+}
+
+#[derive(Debug)]
+struct CompiledFunction {
+  name: String,
+  is_generator: bool,
+  params: CompiledParams,
+  upvalues: Vec<Upvalue>,
+  frame_size: usize,
+  instructions: Box<[u8]>,
+  constants: Vec<CompiledConstant>,
+  spans: Box<[Span]>,
+  doc: Option<String>,
+}
+
+#[derive(Debug)]
+struct CompiledParams {
+  has_self: bool,
+  min: u16,
+  max: u16,
+  /// The index, name and type hint of each param which has one.
+  hints: Vec<(u16, String, String)>,
+  /// The name of each keyword-only param, and whether it is required.
+  kw: Vec<(String, bool)>,
+}
+
+#[derive(Debug)]
+struct CompiledClass {
+  name: String,
+  init: Option<CompiledFunction>,
+  methods: Vec<(String, CompiledFunction)>,
+  fields: Vec<String>,
+  /// The name and type hint of each field which has one.
+  hints: Vec<(String, String)>,
+  static_methods: Vec<(String, CompiledFunction)>,
+  static_fields: Vec<String>,
+  mixins: usize,
+  doc: Option<String>,
+}
+
+#[derive(Debug)]
+enum CompiledConstant {
+  Reserved,
+  String(String),
+  Function(CompiledFunction),
+  Class(CompiledClass),
+  Offset(op::Offset),
+  JumpTable(JumpTable),
+  KwNames(KwNames),
+  Float(NonNaNFloat),
+  Int(i64),
+  Bytes(Vec<u8>),
+  #[cfg(feature = "bigint")]
+  BigInt(num_bigint::BigInt),
+}
+
+impl CompiledProgram {
+  pub(crate) fn new(chunk: &Chunk) -> Self {
+    Self {
+      source: Arc::from(&*chunk.source),
+      main: CompiledFunction::new(&chunk.main.descriptor),
+    }
+  }
+
+  /// Allocate the descriptors of this program in `global`.
+  pub(crate) fn link(&self, global: &Global) -> Chunk {
+    let main = self.main.link(global);
+    #[cfg(feature = "coverage")]
+    global
+      .coverage()
+      .add_module("__main__", &self.source, &main);
+    let pool = ConstantPool::share(global, &main);
+    let upvalues = global.alloc(List::new());
+    let main = global.alloc(Function::new(main, pool, upvalues, ModuleId::global()));
+
+    Chunk {
+      main,
+      source: self.source.as_ref().into(),
+    }
+  }
+}
+
+impl CompiledFunction {
+  fn new(descriptor: &FunctionDescriptor) -> Self {
+    Self {
+      name: descriptor.name.as_str().to_string(),
+      is_generator: descriptor.is_generator,
+      params: CompiledParams::new(&descriptor.params),
+      upvalues: descriptor.upvalues.borrow().clone(),
+      frame_size: descriptor.frame_size,
+      instructions: unsafe { descriptor.instructions.as_ref() }.into(),
+      constants: descriptor
+        .constants()
+        .iter()
+        .map(CompiledConstant::new)
+        .collect(),
+      spans: unsafe { descriptor.spans.as_ref() }.into(),
+      doc: descriptor.doc.as_ref().map(|doc| doc.as_str().to_string()),
+    }
+  }
+
+  fn link(&self, global: &Global) -> Ptr<FunctionDescriptor> {
+    global.alloc(
+      FunctionDescriptor::new(
+        global.intern(self.name.clone()),
+        self.is_generator,
+        self.params.link(global),
+        self.upvalues.clone(),
+        self.frame_size,
+        self.instructions.to_vec(),
+        self.constants.iter().map(|c| c.link(global)).collect(),
+      )
+      .with_spans(self.spans.to_vec())
+      .with_doc(link_doc(global, &self.doc)),
+    )
+  }
+}
+
+impl CompiledParams {
+  fn new(params: &Params) -> Self {
+    Self {
+      has_self: params.has_self,
+      min: params.min,
+      max: params.max,
+      hints: params
+        .hints
+        .iter()
+        .map(|hint| {
+          (
+            hint.index,
+            hint.name.as_str().to_string(),
+            hint.hint.as_str().to_string(),
+          )
+        })
+        .collect(),
+      kw: params
+        .kw
+        .iter()
+        .map(|param| (param.name.as_str().to_string(), param.required))
+        .collect(),
+    }
+  }
+
+  fn link(&self, global: &Global) -> Params {
+    Params {
+      has_self: self.has_self,
+      min: self.min,
+      max: self.max,
+      hints: self
+        .hints
+        .iter()
+        .map(|(index, name, hint)| ParamHint {
+          index: *index,
+          name: global.intern(name.clone()),
+          hint: global.intern(hint.clone()),
+        })
+        .collect(),
+      kw: self
+        .kw
+        .iter()
+        .map(|(name, required)| KwParam {
+          name: global.intern(name.clone()),
+          required: *required,
+        })
+        .collect(),
+    }
+  }
+}
+
+impl CompiledClass {
+  fn new(descriptor: &ClassDescriptor) -> Self {
+    Self {
+      name: descriptor.name.as_str().to_string(),
+      init: descriptor.init.as_deref().map(CompiledFunction::new),
+      methods: descriptor
+        .methods
+        .iter()
+        .map(|(name, method)| (name.as_str().to_string(), CompiledFunction::new(method)))
+        .collect(),
+      fields: descriptor
+        .fields
+        .keys()
+        .filter_map(|name| Some(name.as_str()?.as_str().to_string()))
+        .collect(),
+      hints: descriptor
+        .hints
+        .entries()
+        .filter_map(|(name, hint)| {
+          let hint = hint.to_object::<Str>()?;
+          Some((
+            name.as_str()?.as_str().to_string(),
+            hint.as_str().to_string(),
+          ))
+        })
+        .collect(),
+      static_methods: descriptor
+        .static_methods
+        .iter()
+        .map(|(name, method)| (name.as_str().to_string(), CompiledFunction::new(method)))
+        .collect(),
+      static_fields: descriptor
+        .static_fields
+        .keys()
+        .filter_map(|name| Some(name.as_str()?.as_str().to_string()))
+        .collect(),
+      mixins: descriptor.mixins,
+      doc: descriptor.doc.as_ref().map(|doc| doc.as_str().to_string()),
+    }
+  }
+
+  fn link(&self, global: &Global) -> Ptr<ClassDescriptor> {
+    let fields = Table::with_capacity(self.fields.len());
+    for name in self.fields.iter() {
+      fields.insert(global.intern(name.clone()), Value::none());
+    }
+    let hints = Table::with_capacity(self.hints.len());
+    for (name, hint) in self.hints.iter() {
+      hints.insert(
+        global.intern(name.clone()),
+        Value::object(global.intern(hint.clone())),
+      );
+    }
+    let static_fields = Table::with_capacity(self.static_fields.len());
+    for name in self.static_fields.iter() {
+      static_fields.insert(global.intern(name.clone()), Value::none());
+    }
+
+    global.alloc(ClassDescriptor {
+      name: global.intern(self.name.clone()),
+      init: self.init.as_ref().map(|init| init.link(global)),
+      methods: self
+        .methods
+        .iter()
+        .map(|(name, method)| (global.intern(name.clone()), method.link(global)))
+        .collect(),
+      fields: global.alloc(fields),
+      hints: global.alloc(hints),
+      static_methods: self
+        .static_methods
+        .iter()
+        .map(|(name, method)| (global.intern(name.clone()), method.link(global)))
+        .collect(),
+      static_fields: global.alloc(static_fields),
+      mixins: self.mixins,
+      doc: link_doc(global, &self.doc),
+    })
+  }
+}
+
+fn link_doc(global: &Global, doc: &Option<String>) -> Option<Ptr<Str>> {
+  doc.as_ref().map(|doc| global.alloc(Str::owned(doc)))
+}
+
+impl CompiledConstant {
+  fn new(constant: &Constant) -> Self {
+    match constant {
+      Constant::Reserved => Self::Reserved,
+      Constant::String(v) => Self::String(v.as_str().to_string()),
+      Constant::Function(v) => Self::Function(CompiledFunction::new(v)),
+      Constant::Class(v) => Self::Class(CompiledClass::new(v)),
+      Constant::Offset(v) => Self::Offset(*v),
+      Constant::JumpTable(v) => Self::JumpTable(JumpTable::clone(v)),
+      Constant::KwNames(v) => Self::KwNames(KwNames::clone(v)),
+      Constant::Float(v) => Self::Float(*v),
+      Constant::Int(v) => Self::Int(v.value()),
+      Constant::Bytes(v) => Self::Bytes(v.to_vec()),
+      #[cfg(feature = "bigint")]
+      Constant::BigInt(v) => Self::BigInt(v.value().clone()),
+    }
+  }
+
+  fn link(&self, global: &Global) -> Constant {
+    match self {
+      Self::Reserved => Constant::Reserved,
+      Self::String(v) => Constant::String(global.intern(v.clone())),
+      Self::Function(v) => Constant::Function(v.link(global)),
+      Self::Class(v) => Constant::Class(v.link(global)),
+      Self::Offset(v) => Constant::Offset(*v),
+      Self::JumpTable(v) => Constant::JumpTable(Rc::new(v.clone())),
+      Self::KwNames(v) => Constant::KwNames(Rc::new(v.clone())),
+      Self::Float(v) => Constant::Float(*v),
+      Self::Int(v) => Constant::Int(global.alloc(BoxedInt::new(*v))),
+      Self::Bytes(v) => Constant::Bytes(global.alloc(Bytes::from(v.clone()))),
+      #[cfg(feature = "bigint")]
+      Self::BigInt(v) => {
+        Constant::BigInt(global.alloc(crate::internal::object::bigint::BigInt::new(v.clone())))
+      }
+    }
+  }
+}
@@ -0,0 +1,107 @@
+//! Points at which a running script checks in with the host.
+//!
+//! The VM reaches a safepoint at the back-edge of every loop and before every
+//! call made by a script, so a script can't run for long without reaching
+//! one. Safepoints are where cancellation is noticed, and where the host's
+//! [`SafepointHook`] runs.
+
+use std::cell::{Cell, RefCell};
+
+use super::cancel::CancellationToken;
+use crate::internal::error::Result;
+
+/// Reading the clock is much slower than the rest of a safepoint check.
+/// Must be a power of two.
+const SAFEPOINTS_PER_DEADLINE_CHECK: u32 = 1024;
+
+/// Called by the VM at every safepoint, which is the back-edge of every loop
+/// and every call made by a script.
+///
+/// This may be used to implement a limit on the amount of work a script may
+/// do, or to do periodic work on the host while a script is running:
+///
+/// ```rust
+/// use hebi::{Hebi, SafepointHook};
+///
+/// struct Fuel(usize);
+///
+/// impl SafepointHook for Fuel {
+///   fn safepoint(&mut self) -> hebi::Result<()> {
+///     if self.0 == 0 {
+///       hebi::fail!("out of fuel");
+///     }
+///     self.0 -= 1;
+///     Ok(())
+///   }
+/// }
+///
+/// let mut hebi = Hebi::builder().safepoint_hook(Fuel(100)).finish();
+/// let e = hebi.eval("while true: pass").unwrap_err();
+/// assert!(e.to_string().contains("out of fuel"));
+/// ```
+pub trait SafepointHook: Send + 'static {
+  /// Returning an error stops the script, as if it was raised at the
+  /// safepoint.
+  fn safepoint(&mut self) -> Result<()>;
+}
+
+pub struct Safepoints {
+  /// Set if there is anything to do at a safepoint, so that reaching one
+  /// costs a single branch otherwise.
+  armed: Cell<bool>,
+  /// The number of safepoints reached while armed.
+  count: Cell<u32>,
+  cancellation: RefCell<Option<CancellationToken>>,
+  hook: RefCell<Option<Box<dyn SafepointHook>>>,
+}
+
+impl Safepoints {
+  pub fn new(hook: Option<Box<dyn SafepointHook>>) -> Self {
+    Self {
+      armed: Cell::new(hook.is_some()),
+      count: Cell::new(0),
+      cancellation: RefCell::new(None),
+      hook: RefCell::new(hook),
+    }
+  }
+
+  #[inline(always)]
+  pub fn check(&self) -> Result<()> {
+    if self.armed.get() {
+      self.run()
+    } else {
+      Ok(())
+    }
+  }
+
+  #[cold]
+  #[inline(never)]
+  fn run(&self) -> Result<()> {
+    let count = self.count.get().wrapping_add(1);
+    self.count.set(count);
+
+    if let Some(token) = self.cancellation.borrow().as_ref() {
+      if token.is_cancelled_fast() {
+        fail!(Cancelled: "evaluation was cancelled");
+      }
+      if count & (SAFEPOINTS_PER_DEADLINE_CHECK - 1) == 0 && token.is_timed_out() {
+        fail!(Cancelled: "evaluation timed out");
+      }
+    }
+
+    if let Some(hook) = self.hook.borrow_mut().as_mut() {
+      hook.safepoint()?;
+    }
+
+    Ok(())
+  }
+
+  /// Replace the cancellation token, and return the previous one.
+  pub fn set_cancellation(&self, token: Option<CancellationToken>) -> Option<CancellationToken> {
+    let previous = self.cancellation.replace(token);
+    self
+      .armed
+      .set(self.cancellation.borrow().is_some() || self.hook.borrow().is_some());
+    previous
+  }
+}
@@ -0,0 +1,111 @@
+//! Checkpoints of the global state of a VM.
+//!
+//! A snapshot holds a deep copy of the globals and of the variables of every
+//! loaded module. Snapshots are kept in the VM which they were taken from, and
+//! handed out as an opaque id, because the values in them may not leave the
+//! VM.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+
+use super::global::Global;
+use super::transfer::Transfer;
+use crate::internal::object::module::Module;
+use crate::internal::object::{table, Ptr};
+use crate::internal::value::Value;
+
+pub struct Snapshot {
+  globals: IndexMap<table::Key, Value>,
+  modules: Vec<(Ptr<Module>, Vec<Value>)>,
+}
+
+impl Snapshot {
+  pub fn take(global: &Global) -> Self {
+    let mut transfer = Transfer::new(global, true);
+    let globals = global
+      .entries()
+      .map(|(key, value)| (key, copy(&mut transfer, &value)))
+      .collect();
+    let modules = global
+      .modules()
+      .into_iter()
+      .map(|module| {
+        let vars = module
+          .module_vars
+          .values()
+          .map(|value| copy(&mut transfer, &value))
+          .collect();
+        (module, vars)
+      })
+      .collect();
+    Self { globals, modules }
+  }
+
+  /// Replace the current globals and module variables with copies of the
+  /// ones in this snapshot. Globals which were defined after the snapshot was
+  /// taken are removed.
+  pub fn restore(&self, global: &Global) {
+    let mut transfer = Transfer::new(global, true);
+    global
+      .globals()
+      .retain(|key| self.globals.contains_key(key));
+    for (key, value) in self.globals.iter() {
+      global
+        .globals()
+        .insert(key.clone(), copy(&mut transfer, value));
+    }
+    for (module, vars) in self.modules.iter() {
+      for (index, value) in vars.iter().enumerate() {
+        module
+          .module_vars
+          .set_index(index, copy(&mut transfer, value));
+      }
+    }
+  }
+}
+
+fn copy(transfer: &mut Transfer<'_>, value: &Value) -> Value {
+  // objects which can't be copied are shared instead, so this can't fail
+  transfer
+    .value(value)
+    .unwrap_or_else(|_| unreachable!("failed to copy `{value}`"))
+}
+
+/// The snapshots taken from one VM.
+///
+/// Handles to a snapshot may be dropped on any thread, so they only record
+/// that the snapshot is no longer needed, and it is removed the next time the
+/// store is accessed.
+#[derive(Default)]
+pub struct Store {
+  next_id: u64,
+  snapshots: HashMap<u64, Snapshot>,
+  released: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Store {
+  pub fn insert(&mut self, snapshot: Snapshot) -> (u64, Arc<Mutex<Vec<u64>>>) {
+    self.collect();
+    let id = self.next_id;
+    self.next_id += 1;
+    self.snapshots.insert(id, snapshot);
+    (id, self.released.clone())
+  }
+
+  /// Returns `None` if the handle `released` was not created by this store.
+  pub fn get(&mut self, id: u64, released: &Arc<Mutex<Vec<u64>>>) -> Option<&Snapshot> {
+    self.collect();
+    if !Arc::ptr_eq(&self.released, released) {
+      return None;
+    }
+    self.snapshots.get(&id)
+  }
+
+  fn collect(&mut self) {
+    for id in self.released.lock().unwrap().drain(..) {
+      self.snapshots.remove(&id);
+    }
+  }
+}
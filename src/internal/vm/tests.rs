@@ -112,8 +112,8 @@ impl TestModuleLoader {
 }
 
 impl module::ModuleLoader for TestModuleLoader {
-  fn load(&self, path: &str) -> Result<Cow<'static, str>> {
-    match self.modules.get(path).copied() {
+  fn load(&self, path: &module::ModulePath) -> Result<Cow<'static, str>> {
+    match self.modules.get(path.as_str()).copied() {
       Some(module) => Ok(Cow::borrowed(module)),
       None => Err(Error::Vm(SpannedError::new(
         format!("module `{path}` not found"),
@@ -247,6 +247,45 @@ check! {
   "#
 }
 
+check! {
+  module
+  import_private_symbol,
+  {
+    test: "_value := 100"
+  },
+  r#"#!hebi
+    from test import _value
+  "#
+}
+
+check! {
+  module
+  access_private_symbol,
+  {
+    test: "_value := 100"
+  },
+  r#"#!hebi
+    import test
+    test._value
+  "#
+}
+
+check! {
+  module
+  private_symbol_used_by_module,
+  {
+    test: r#"#!hebi
+      _value := 100
+      fn get():
+        return _value
+    "#
+  },
+  r#"#!hebi
+    from test import get
+    get()
+  "#
+}
+
 check! {
   module
   module_fail_to_parse,
@@ -270,6 +309,96 @@ check! {
   "#
 }
 
+check! {
+  module
+  import_nested_module,
+  {
+    foo.bar: "value := 100"
+  },
+  r#"#!hebi
+    import foo.bar
+    bar.value
+  "#
+}
+
+check! {
+  module
+  import_nested_module_named,
+  {
+    foo.bar: "value := 100"
+  },
+  r#"#!hebi
+    from foo.bar import value
+    value
+  "#
+}
+
+check! {
+  module
+  import_relative_sibling,
+  {
+    pkg.a: r#"#!hebi
+      from .b import value as v
+      value := v
+    "#,
+    pkg.b: "value := 100"
+  },
+  r#"#!hebi
+    from pkg.a import value
+    value
+  "#
+}
+
+check! {
+  module
+  import_relative_parent,
+  {
+    pkg.sub.a: r#"#!hebi
+      from ..b import value as v
+      value := v
+    "#,
+    pkg.b: "value := 100"
+  },
+  r#"#!hebi
+    from pkg.sub.a import value
+    value
+  "#
+}
+
+check! {
+  module
+  circular_import,
+  {
+    a: "import b",
+    b: "import a"
+  },
+  r#"#!hebi
+    import a
+  "#
+}
+
+check! {
+  module
+  circular_import_self,
+  {
+    a: "value := 100\nimport a"
+  },
+  r#"#!hebi
+    import a
+  "#
+}
+
+check! {
+  module
+  import_relative_beyond_root,
+  {
+    test: "value := 100"
+  },
+  r#"#!hebi
+    import ..test
+  "#
+}
+
 check! {
   simple_class,
   r#"#!hebi
@@ -1023,6 +1152,29 @@ check! {
   "#
 }
 
+check! {
+  compound_assign_evaluates_target_once,
+  r#"#!hebi
+    class T:
+      a = 1
+      b = none
+    t := T()
+    l := [1, 2]
+    calls := 0
+    fn get_t():
+      calls += 1
+      return t
+    fn key():
+      calls += 1
+      return 1
+    get_t().a += 10
+    l[key()] *= 3
+    get_t().b ??= 5
+    get_t().b ??= 6
+    print t.a, l[1], t.b, calls
+  "#
+}
+
 check! {
   unary_invert,
   r#"#!hebi
@@ -1098,1073 +1250,4815 @@ check! {
 }
 
 check! {
-  more_optional_access,
+  conditional_expr,
   r#"#!hebi
-    "a" ?? "b"
+    fn sign(v):
+      return "negative" if v < 0 else "zero" if v == 0 else "positive"
+    print sign(-1), sign(0), sign(1)
+    v := 10
+    v = v + 1 if v < 10 else v - 1
+    print v
+    v = none if v else 0
+    print v ?? "none"
   "#
 }
 
 check! {
-  logical_or_expr_return_lhs,
+  if_stmt_switch,
   r#"#!hebi
-    "a" || "b"
+    fn route(cmd):
+      if cmd == "get":
+        print "get"
+      elif cmd == "set":
+        print "set"
+      elif "del" == cmd:
+        print "del"
+      elif cmd == "list":
+        print "list"
+      else:
+        print "unknown", cmd
+    route("get")
+    route("s" + "et")
+    route("del")
+    route("list")
+    route("nope")
+    route(1)
+    route(none)
+    cmd := "d"
+    if cmd == "a":
+      print "a"
+    elif cmd == "b":
+      print "b"
+    elif cmd == "c":
+      print "c"
+    elif cmd == "d":
+      print "d"
+    print "end"
   "#
 }
 
 check! {
-  logical_or_expr_return_rhs,
+  implicit_return,
   r#"#!hebi
-    false || "b"
+    fn double(v): v * 2
+    fn pick(a, b):
+      if a:
+        return "a"
+      b ?? "none"
+    fn ends_with_stmt(v):
+      v + 1
+      pass
+    log := []
+    fn deferred(v):
+      defer log.push(v)
+      v + 1
+    class T:
+      v = 0
+      init(self):
+        self.v = 1
+      fn get(self): self.v
+    print double(2), pick(true, 0), pick(false, 0), pick(false, none)
+    print ends_with_stmt(1), deferred(1), log[0], T().get()
+  "#
+}
+
+check! {
+  decorators,
+  r#"#!hebi
+    calls := 0
+    fn memo(f):
+      cache := {}
+      fn wrapper(n):
+        if ?cache[n] == none:
+          cache[n] = f(n)
+        return cache[n]
+      return wrapper
+    @memo
+    fn fib(n):
+      calls += 1
+      if n < 2: return n
+      return fib(n - 1) + fib(n - 2)
+    print fib(30), calls
+
+    registry := []
+    fn register(v):
+      registry.push(v)
+      return v
+    fn tag(name):
+      fn decorate(f):
+        fn wrapper(): name + ":" + f()
+        return wrapper
+      return decorate
+    @register
+    @tag("outer")
+    @tag("inner")
+    fn hello(): "hello"
+    @register
+    class T:
+      fn get(self): "T"
+    print hello(), registry.len(), registry[1]().get()
   "#
 }
 
 check! {
-  logical_and_expr_return_rhs,
+  doc_comments,
   r#"#!hebi
-    "a" && "b"
+    ## Adds two numbers.
+    ##
+    ## Both must be ints.
+    fn add(a, b): a + b
+
+    ## A point in 2D space.
+    class Point:
+      x = 0
+      ## Moves the point.
+      fn move(self, dx): self.x += dx
+      static fn origin(): Point()
+
+    fn undocumented(): pass
+
+    print help(add)
+    print help(Point), help(Point()), help(Point().move)
+    print help(Point.origin), help(undocumented), help(1), help(Error)
   "#
 }
 
 check! {
-  logical_and_expr_return_lhs,
+  clone_and_eq_deep,
   r#"#!hebi
-    false && "b"
+    class P:
+      x = 0
+      items = none
+      init(self, x = 0, items = none):
+        self.x = x
+        self.items = items
+    a := {key: [1, 2], p: P(1, [3])}
+    shallow := clone(a)
+    deep := clone(a, deep=true)
+    a["key"].push(3)
+    print shallow["key"].len(), deep["key"].len(), eq_deep(a, deep), eq_deep(shallow, a)
+    deep["key"].push(3)
+    print eq_deep(a, deep), eq_deep(deep["p"], P(1, [3])), eq_deep(P(), P())
+    print eq_deep([1, "a", (2, 3)], [1, "a", (2, 3)]), eq_deep([1], [1, 2]), eq_deep(1, 1.0)
+
+    # cycles are preserved by a deep clone, and compared by structure
+    l := [0]
+    l.push(l)
+    c := clone(l, true)
+    c[0] = 1
+    print l[0], c[1][0], eq_deep(l, c)
+    c[0] = 0
+    print eq_deep(l, c), eq_deep(l, clone(l))
+
+    p := P()
+    q := clone(p)
+    q.x = 5
+    print p.x, q.x, eq_deep(p, q)
   "#
 }
 
 check! {
-  list_indexing_zero,
+  hash_builtin,
   r#"#!hebi
-    [0, 1, 2][0]
+    class Point:
+      x = 0
+      y = 0
+      init(self, x, y):
+        self.x = x
+        self.y = y
+      fn __hash__(self): hash((self.x, self.y))
+    print hash(1) == hash(1.0), hash("a") == hash("a"), hash("a") == hash("b")
+    print hash((1, "a")) == hash((1.0, "a")), hash(none) == hash(false)
+    print hash(Point(1, 2)) == hash((1, 2)), hash(Point(1, 2)) == hash(Point(2, 1))
+    t := {}
+    t[(1, 2)] = "v"
+    print t[(1.0, 2)]
   "#
 }
 
 check! {
-  list_indexing_positive,
+  hash_unhashable,
   r#"#!hebi
-    [0, 1, 2][1]
+    hash([1, 2])
   "#
 }
 
 check! {
-  list_indexing_negative,
+  sort_builtin,
   r#"#!hebi
-    [0, 1, 2][-1]
+    l := [3, 1, 2]
+    sort(l)
+    print l[0], l[1], l[2]
+
+    fn desc(a, b): b - a
+    sort(l, by=desc)
+    print l[0], l[1], l[2]
+    sort(l, desc)
+    sort(l, by=none)
+    print l[0], l[1], l[2]
+
+    # items which compare equal keep their order
+    fn by_first(a, b): a[0] - b[0]
+    pairs := [(1, "a"), (0, "b"), (1, "c"), (0, "d")]
+    sort(pairs, by=by_first)
+    print pairs[0][1], pairs[1][1], pairs[2][1], pairs[3][1]
+
+    # the comparator may call back into `sort`
+    calls := [0]
+    fn by_sum(a, b):
+      calls[0] += 1
+      x := [b[0], a[0]]
+      sort(x)
+      return (a[0] + a[1]) - (b[0] + b[1])
+    sums := [[5, 5], [1, 0], [2, 2]]
+    sort(sums, by=by_sum)
+    print sums[0][0], sums[1][0], sums[2][0], calls[0] > 0
   "#
 }
 
 check! {
-  list_indexing_invalid,
+  sort_by_not_a_number,
   r#"#!hebi
-    [0, 1, 2]["yo"]
+    fn cmp(a, b): "a"
+    sort([2, 1], by=cmp)
   "#
 }
 
 check! {
-  list_indexing_oob,
+  cmp_min_max,
   r#"#!hebi
-    [0, 1, 2][100]
+    print cmp(1, 2), cmp(2, 2), cmp(2.5, 1), cmp("b", "a")
+    print min(3, 1, 2), max(3, 1, 2), min(2, 1.5), max("a", "c", "b")
+    print min([4, 2, 8]), max([4])
+    print max((1, "a"), (1, "b"), (0, "c"))[1]
   "#
 }
 
 check! {
-  list_indexing_zero_opt,
+  cmp_method,
   r#"#!hebi
-    ?[0, 1, 2][0]
+    class Version:
+      major = 0
+      minor = 0
+      init(self, major, minor):
+        self.major = major
+        self.minor = minor
+      fn __cmp__(self, other):
+        if self.major != other.major: return self.major - other.major
+        return self.minor - other.minor
+      fn to_str(self):
+        return to_str(self.major) + "." + to_str(self.minor)
+
+    a := Version(1, 2)
+    b := Version(0, 9)
+    c := Version(1, 0)
+    print cmp(a, b), cmp(b, a), cmp(a, Version(1, 2))
+    print min(a, b, c).to_str(), max([a, b, c]).to_str()
+    l := [a, b, c]
+    l.sort()
+    print l[0].to_str(), l[1].to_str(), l[2].to_str()
+    fn desc(x, y): cmp(y, x)
+    sort(l, by=desc)
+    print l[0].to_str(), l[1].to_str(), l[2].to_str()
   "#
 }
 
 check! {
-  list_indexing_positive_opt,
+  cmp_method_must_return_number,
   r#"#!hebi
-    ?[0, 1, 2][1]
+    class T:
+      fn __cmp__(self, other): return "less"
+    cmp(T(), T())
   "#
 }
 
 check! {
-  list_indexing_negative_opt,
+  min_no_values,
   r#"#!hebi
-    ?[0, 1, 2][-1]
+    min([])
   "#
 }
 
 check! {
-  list_indexing_invalid_opt,
+  freeze_builtin,
   r#"#!hebi
-    ?[0, 1, 2]["yo"]
+    v := freeze([1, "a", [2, (3, none)]])
+    print type_of(v), type_of(v[2]), v[2][1][0], v[1]
+    print freeze(1.5), freeze("s"), freeze(none)
+    v[0] = 2
   "#
 }
 
 check! {
-  list_indexing_oob_opt,
+  freeze_table,
   r#"#!hebi
-    ?[0, 1, 2][100]
+    freeze((1, {}))
   "#
 }
 
 check! {
-  for_iter_list,
+  channel_between_coroutines,
   r#"#!hebi
-    for item in ["a", "b", "c"]:
-      print item
+    ch := channel(2)
+    fn producer():
+      for i in 0..4:
+        ch.send(i * 10)
+        yield i
+    co := coroutine(producer)
+    total := 0
+    for i in 0..4:
+      co.resume()
+      total += ch.recv()
+    print total, ch.len()
+
+    # values are frozen when they are sent
+    ch.send([1, "a"])
+    v := ch.recv()
+    print type_of(v), v[1]
+
+    ch.send(1)
+    ch.close()
+    print ch.is_closed(), ch.recv(), ch.recv(), ch.try_recv()
+    ch.send(2)
   "#
 }
 
 check! {
-  for_iter_iterable_class,
+  channel_recv_would_wait_forever,
   r#"#!hebi
-    class Counter:
-      n = 0
-      max = 0
-
-      init(self, max):
-        self.max = max
-
-      fn iter(self):
-        return self
-
-      fn next(self):
-        if self.n < self.max:
-          n := self.n
-          self.n += 1
-          return n
-
-      fn done(self):
-        return self.n >= self.max
-
-    for v in Counter(10):
-      print v
+    channel().recv()
   "#
 }
 
 check! {
-  builtin_list_methods,
+  channel_send_would_wait_forever,
   r#"#!hebi
-    v := [0, 1, 2]
-
-    print "len", v.len()
-    print "is_empty", v.is_empty()
-    print "get(1)", v.get(1)
-    print "pop()", v.pop()
-    print "set", v.set(0, v.get(1))
-    print "join", v.join(", ")
-    print "push(2)", v.push(2)
-    print "extend(3, 0)", v.extend(3, 0)
-    print "join", v.join(", ")
+    ch := channel(1)
+    ch.send(1)
+    ch.send(2)
   "#
 }
 
 check! {
-  builtin_list_methods_bound,
+  clone_unexpected_kw_arg,
   r#"#!hebi
-    v := [0, 1, 2]
-
-    v_len := v.len
-    v_is_empty := v.is_empty
-    v_get := v.get
-    v_pop := v.pop
-    v_set := v.set
-    v_join := v.join
-    v_push := v.push
-    v_extend := v.extend
-    
-    print "len", v_len()
-    print "is_empty", v_is_empty()
-    print "get(1)", v_get(1)
-    print "pop()", v_pop()
-    print "set", v_set(0, v_get(1))
-    print "join", v_join(", ")
-    print "push(2)", v_push(2)
-    print "extend(3, 0)", v_extend(3, 0)
-    print "join", v_join(", ")
+    clone([], shallow=true)
   "#
 }
 
 check! {
-  builtin_list_methods_static,
+  kw_params,
   r#"#!hebi
-    v := [0, 1, 2]
-    
-    print "len", List.len(v)
-    print "is_empty", List.is_empty(v)
-    print "get(1)", List.get(v, 1)
-    print "pop()", List.pop(v)
-    print "set", List.set(v, 0, List.get(v, 1))
-    print "join", List.join(v, ", ")
-    print "push(2)", List.push(v, 2)
-    print "extend(3, 0)", List.extend(v, 3, 0)
-    print "join", List.join(v, ", ")
+    fn f(a, b=2, *, c, d=4):
+      print a, b, c, d
+    f(1, c=3)
+    f(1, 5, d=6, c=7)
+    fn g(*, x: int = 1): x
+    print g(), g(x=5)
+    class T:
+      v = 0
+      init(self, *, v):
+        self.v = v
+      fn add(self, n, *, times=1): self.v + n * times
+    t := T(v=10)
+    print t.v, t.add(1), t.add(2, times=3)
   "#
 }
 
 check! {
-  builtin_str_methods,
+  kw_params_missing_kw_arg,
   r#"#!hebi
-    v := "a\nb\nc"
-
-    print "len", v.len()
-    print "is_empty", v.is_empty()
-    print "lines", v.lines()
+    fn f(a, *, b): a + b
+    f(1)
   "#
 }
 
 check! {
-  builtin_str_methods_bound,
+  kw_params_unexpected_kw_arg,
   r#"#!hebi
-    v := "a\nb\nc"
-
-    v_len := v.len
-    v_is_empty := v.is_empty
-    v_lines := v.lines
-
-    print "len", v_len()
-    print "is_empty", v_is_empty()
-    print "lines", v_lines()
+    fn f(a, *, b): a + b
+    f(1, b=2, c=3)
   "#
 }
 
 check! {
-  builtin_str_methods_static,
+  kw_params_too_many_positional_args,
   r#"#!hebi
-    v := "a\nb\nc"
-
-    print "len", Str.len(v)
-    print "is_empty", Str.is_empty(v)
-    print "lines", Str.lines(v)
+    fn f(a, *, b, c=0): a + b
+    f(1, 2, 3)
   "#
 }
 
 check! {
-  builtin_str_lines_iter,
+  kw_args_to_native_function,
   r#"#!hebi
-    strings := [
-      "a\n\nb\nc",
-      "\na\n\nb\nc",
-      "\na\n\nb\nc\n",
-    ]
-
-    for string in strings:
-      for line in string.lines():
-        print "`" + line + "`"
-      print ""
+    [].push(v=1)
   "#
 }
 
 check! {
-  builtin_collect,
+  more_optional_access,
   r#"#!hebi
-    class Counter:
-      n = 0
-      max = 0
-
-      init(self, max):
-        self.max = max
-
-      fn iter(self):
-        return self
-
-      fn next(self):
-        if self.n < self.max:
-          n := self.n
-          self.n += 1
-          return n
-
-      fn done(self):
-        return self.n >= self.max
-
-    collect(Counter(10))
+    "a" ?? "b"
   "#
 }
 
 check! {
-  builtin_collect_native,
+  logical_or_expr_return_lhs,
   r#"#!hebi
-    collect("a\nb\nc".lines())
+    "a" || "b"
   "#
 }
 
 check! {
-  builtin_parse_int,
+  logical_or_expr_return_rhs,
   r#"#!hebi
-    print parse_int(10)
-    print parse_int(10.0)
-    print parse_int("10")
+    false || "b"
   "#
 }
 
 check! {
-  add_objects,
+  logical_and_expr_return_rhs,
   r#"#!hebi
-    "a" + "b"
+    "a" && "b"
   "#
 }
 
 check! {
-  string_comparison,
+  logical_and_expr_return_lhs,
   r#"#!hebi
-    print "'a' <  'b'", "a" <  "b"
-    print "'b' >= 'a'", "b" >= "a"
-
-    print "'b' <  'a'", "b" <  "a"
-    print "'a' >= 'b'", "a" >= "b"
-
-    print "'a' == 'b'", "a" == "b"
-    print "'b' == 'a'", "a" == "b"
-    print "'a' == 'a'", "a" == "a"
+    false && "b"
   "#
 }
 
-// check! {
-//   type_comparison,
-//   r#"#!hebi
-//     fn interesting_thing(value):
-//       if value is Str:
-//         print "str: ", value
-//       else:
-//         print "I hardly know 'er"
-//
-//     interesting_thing("ppL")
-//     interesting_thing(100)
-//   "#
-// }
-
 check! {
-  global_builtin_functions__to_int__float,
+  logical_expr_short_circuit,
   r#"#!hebi
-    to_int(10.5)
+    calls := 0
+    fn side(v):
+      calls += 1
+      return v
+
+    print 0 || "x", 1 && "y", "" && 1, none || 0.0, none && 1, false || none
+    print side(0) && side(1), calls
+    print side(2) || side(3), calls
+    print side(none) ?? side(4), calls
+    fn describe(v):
+      return v && "truthy" || "falsy"
+    print describe(1), describe(0), describe(none), describe([])
+    v := 1
+    v = 5 && v + 1
+    print v, !(0 || none), !!(1 && "s")
+    i := 0
+    while i < 5 && i != 3:
+      i += 1
+    if i > 2 || side(0):
+      print i, calls
   "#
 }
 
 check! {
-  global_builtin_functions__to_int__int,
+  bool_method,
   r#"#!hebi
-    to_int(10)
+    class Stack:
+      items = none
+      init(self):
+        self.items = []
+      fn push(self, v):
+        self.items.push(v)
+      fn pop(self):
+        return self.items.pop()
+      fn __bool__(self):
+        return self.items.len() > 0
+    class Derived(Stack): pass
+
+    s := Stack()
+    print !s, to_bool(s), s || "empty", s && "full"
+    if s:
+      print "not empty"
+    else:
+      print "empty"
+    s.push(1)
+    s.push(2)
+    print !s, to_bool(s), s && "full"
+    while s:
+      print s.pop()
+    print Derived() || "derived is empty"
+    fn id(v):
+      return v
+    print collect(filter(id, [Stack(), Stack()])).len()
   "#
 }
 
 check! {
-  global_builtin_functions__to_int__bad_input,
+  bool_method_must_return_bool,
   r#"#!hebi
-    to_int({})
+    class T:
+      fn __bool__(self):
+        return 1
+    if T():
+      print "unreachable"
   "#
 }
 
 check! {
-  global_builtin_functions__to_float__float,
+  string_builder,
   r#"#!hebi
-    to_float(10.5)
+    b := string_builder("a")
+    b.push("b").push(1).push_str("c")
+    print b, b.len(), b.is_empty(), type_of(b)
+    s := b.build()
+    print s, type_of(s)
+    b.clear()
+    print b.is_empty(), s
+    b.push_str(0)
   "#
 }
 
 check! {
-  global_builtin_functions__to_float__int,
+  string_append_in_loop,
   r#"#!hebi
-    to_float(10)
+    fn digits(n):
+      s := ""
+      for i in 0..n:
+        s += to_str(i)
+        if i % 2 == 1: s += ","
+      return s
+    fn nested(n):
+      s := "<"
+      for i in 0..n:
+        j := 0
+        while j < 2:
+          s += "x"
+          j += 1
+        s += "|"
+      s += ">"
+      return s
+    fn early(n):
+      s := ""
+      for i in 0..n:
+        if i == 2: break
+        s += "a"
+      return s
+    fn mixed(n):
+      s := 0
+      for i in 0..n:
+        s += i
+      return s
+    print digits(5), nested(2), early(5), mixed(5)
+    print type_of(digits(1))
   "#
 }
 
 check! {
-  global_builtin_functions__to_float__bad_input,
+  string_append_in_loop_type_error,
   r#"#!hebi
-    to_float({})
+    fn f():
+      s := ""
+      for v in ["a", 1]:
+        s += v
+    f()
   "#
 }
 
 check! {
-  global_builtin_functions__to_bool,
+  string_builder_add,
   r#"#!hebi
-    print "true", to_bool(true)
-    print "false", to_bool(false)
-    print "10.0", to_bool(10.0)
-    print "0.0", to_bool(0.0)
-    print "100", to_bool(100)
-    print "0", to_bool(0)
-    print "none", to_bool(none)
-    print "{}", to_bool({})
-    print "[]", to_bool([])
-    print "\"test\"", to_bool("test")
+    fn f():
+      b := string_builder()
+      for i in 0..2:
+        b += "a"
+    f()
   "#
 }
 
 check! {
-  global_builtin_functions__to_str,
+  list_indexing_zero,
   r#"#!hebi
-    print to_str(true)
-    print to_str(false)
-    print to_str(100)
-    print to_str(3.14)
-    print to_str(none)
-    print to_str({})
-    print to_str([])
-    print to_str("test")
+    [0, 1, 2][0]
   "#
 }
 
 check! {
-  global_builtin_functions__type_of,
+  list_indexing_positive,
   r#"#!hebi
-    print type_of(true)
-    print type_of(false)
-    print type_of(100)
-    print type_of(3.14)
-    print type_of(none)
-    print type_of({})
-    print type_of([])
-    print type_of("test")
+    [0, 1, 2][1]
   "#
 }
 
 check! {
-  make_large_table,
+  list_indexing_negative,
   r#"#!hebi
-    {
-      version: "0.3.3",
-      update_time: 1684753441.253474,
-      data: [
-        {
-          flight_id: "306364ca",
-          flight: none,
-          callsign: "REDEYE6",
-          squawk: none,
-          clicks: 1092,
-          from_iata: "RMS",
-          from_city: "Ramstein",
-          to_iata: none,
-          to_city: none,
-          model: "B703",
-          type: "Boeing E-8C"
-        }
-      ]
-    }
+    [0, 1, 2][-1]
   "#
 }
 
 check! {
-  module
-  regression__variable_scope_ends_too_early,
-  {
-    http: r#"#!hebi
-      fn fetch(url, opts):
-        return {
-          version: "0.3.3",
-          update_time: 1684753441.253474,
-          data: [
-            {
-              flight_id: "306364ca",
-              flight: none,
-              callsign: "REDEYE6",
-              squawk: none,
-              clicks: 1092,
-              from_iata: "RMS",
-              from_city: "Ramstein",
-              to_iata: none,
-              to_city: none,
-              model: "B703",
-              type: "Boeing E-8C"
-            }
-          ]
-        }
-    "#,
-    utils: r#"#!hebi
-      fn get_element(list, index):
-        return list[index]
-      fn format(fmt, a, b):
-        return fmt
-      fn len(list):
-        return 1
-      fn join(list, sep):
-        return "joined"
-      fn push(list, item):
-        pass
-    "#
-  },
+  list_indexing_invalid,
   r#"#!hebi
-    from http import fetch
-    from utils import get_element, format, len, join, push
-
-    top_flights := fetch("https://flightradar24.com/flights/most-tracked", {format: "json"})
-    data := top_flights["data"]
-
-    i := 0
-    flights := []
-
-    while i < len(data):
-      flight = get_element(data, i)
-
-      callsign := ?flight["callsign"] ?? "Unknown"
-      from_city := ?flight["from_city"] ?? "N/A"
-      to_city := ?flight["to_city"] ?? "N/A"
-
-      __format = format
-      flight_info := format("{} {}", from_city, to_city)
-      print(flight_info)
-
-      i = i + 1
-
-    join(flights, ", ")
-    __format # should be the `format` function
+    [0, 1, 2]["yo"]
   "#
 }
 
-/*
-TODO: more tests
-
-- loops (for, while, loop)
-  - continue, break
-- native modules
-  - functions
-  - async functions
-  - classes
-  - class methods
-  - async class methods
-  - class methods (static call)
-  - async class methods (static call)
-  - class static methods
-  - class static async methods
-*/
-
 check! {
-  order_of_eval,
+  list_indexing_oob,
   r#"#!hebi
-    a := 1
-    fn f():
-      a += 1
-      return a
-    print (a + f())
+    [0, 1, 2][100]
   "#
 }
 
 check! {
-  bool_equality,
+  list_indexing_zero_opt,
   r#"#!hebi
-    a := true
-    b := false
-    if a == b:
-      print "a == b"
-    if a != b:
-      print "a != b"
+    ?[0, 1, 2][0]
   "#
 }
 
 check! {
-  none_equality,
+  list_indexing_positive_opt,
   r#"#!hebi
-    a := none
-    if a == none:
-      print "a == none"
-    if a != none:
-      print "a != none"
+    ?[0, 1, 2][1]
   "#
 }
 
 check! {
-  bool_add_error,
+  list_indexing_negative_opt,
   r#"#!hebi
-    true + false
+    ?[0, 1, 2][-1]
   "#
 }
 
 check! {
-  none_add_error,
+  list_indexing_invalid_opt,
   r#"#!hebi
-    none + none
+    ?[0, 1, 2]["yo"]
   "#
 }
 
 check! {
-  bool_sub_error,
+  list_indexing_oob_opt,
   r#"#!hebi
-    true - false
+    ?[0, 1, 2][100]
   "#
 }
 
 check! {
-  none_sub_error,
+  for_iter_list,
   r#"#!hebi
-    none - none
+    for item in ["a", "b", "c"]:
+      print item
   "#
 }
 
 check! {
-  bool_mul_error,
+  for_iter_iterable_class,
   r#"#!hebi
-    true * false
+    class Counter:
+      n = 0
+      max = 0
+
+      init(self, max):
+        self.max = max
+
+      fn iter(self):
+        return self
+
+      fn next(self):
+        if self.n < self.max:
+          n := self.n
+          self.n += 1
+          return n
+
+      fn done(self):
+        return self.n >= self.max
+
+    for v in Counter(10):
+      print v
   "#
 }
 
 check! {
-  none_mul_error,
+  builtin_list_methods,
   r#"#!hebi
-    none * none
+    v := [0, 1, 2]
+
+    print "len", v.len()
+    print "is_empty", v.is_empty()
+    print "get(1)", v.get(1)
+    print "pop()", v.pop()
+    print "set", v.set(0, v.get(1))
+    print "join", v.join(", ")
+    print "push(2)", v.push(2)
+    print "extend(3, 0)", v.extend(3, 0)
+    print "join", v.join(", ")
   "#
 }
 
 check! {
-  bool_div_error,
+  builtin_list_methods_bound,
   r#"#!hebi
-    true / false
+    v := [0, 1, 2]
+
+    v_len := v.len
+    v_is_empty := v.is_empty
+    v_get := v.get
+    v_pop := v.pop
+    v_set := v.set
+    v_join := v.join
+    v_push := v.push
+    v_extend := v.extend
+    
+    print "len", v_len()
+    print "is_empty", v_is_empty()
+    print "get(1)", v_get(1)
+    print "pop()", v_pop()
+    print "set", v_set(0, v_get(1))
+    print "join", v_join(", ")
+    print "push(2)", v_push(2)
+    print "extend(3, 0)", v_extend(3, 0)
+    print "join", v_join(", ")
   "#
 }
 
 check! {
-  none_div_error,
+  builtin_list_methods_static,
   r#"#!hebi
-    none / none
+    v := [0, 1, 2]
+    
+    print "len", List.len(v)
+    print "is_empty", List.is_empty(v)
+    print "get(1)", List.get(v, 1)
+    print "pop()", List.pop(v)
+    print "set", List.set(v, 0, List.get(v, 1))
+    print "join", List.join(v, ", ")
+    print "push(2)", List.push(v, 2)
+    print "extend(3, 0)", List.extend(v, 3, 0)
+    print "join", List.join(v, ", ")
   "#
 }
 
 check! {
-  bool_rem_error,
+  builtin_list_query_and_mutation_methods,
   r#"#!hebi
-    true % false
+    v := [3, 1, 2]
+
+    print "insert(1, 5)", v.insert(1, 5)
+    print "insert(4, 6)", v.insert(4, 6)
+    print "join", v.join(", ")
+    print "remove(0)", v.remove(0)
+    print "index_of(2)", v.index_of(2)
+    print "index_of(7)", v.index_of(7)
+    print "contains(6)", v.contains(6)
+    print "contains(7)", v.contains(7)
+    print "slice(1)", v.slice(1).join(", ")
+    print "slice(0, 2)", v.slice(0, 2).join(", ")
+    print "reverse()", v.reverse()
+    print "join", v.join(", ")
+    print "sort()", v.sort()
+    print "join", v.join(", ")
+
+    fn neg(n):
+      return -n
+    v.sort(neg)
+    print "sort(neg)", v.join(", ")
+
+    words := ["ccc", "a", "bb", "dd"]
+    fn len(s):
+      return s.len()
+    List.sort(words, len)
+    print "List.sort(words, len)", words.join(", ")
+
+    print "List.contains", List.contains(words, "a")
+    [1, "a"].sort()
   "#
 }
 
 check! {
-  none_rem_error,
+  builtin_set,
   r#"#!hebi
-    none % none
+    a := set([1, 2, 2, "x", 3.0])
+    b := set()
+    print "len", a.len(), b.len(), b.is_empty()
+    print "add", b.add(3), b.add(4), b.add(4)
+    print "contains", a.contains(1.0), a.contains("x"), a.contains("y")
+    print "in", 2 in a, 5 in a
+    print "union", collect(a.union(b)).join(", ")
+    print "intersection", collect(a.intersection(b)).join(", ")
+    print "difference", collect(a.difference(b)).join(", ")
+    print "remove", a.remove("x"), a.remove("x")
+    for v in a:
+      print v
+    print "eq", set([1, 2]) == set([2, 1]), set([1]) == set([1, 2])
+    b.add([])
   "#
 }
 
 check! {
-  bool_pow_error,
+  table_value_keys,
   r#"#!hebi
-    true ** false
+    t := {[1]: "one", [true]: "yes", [(1, 2)]: "pair", k: "str"}
+    print t[1], t[1.0], t[true], t[(1, 2)], t["k"]
+    t[2] = "two"
+    t[(1, 2)] = "tuple"
+    t[none] = "nothing"
+    print t[2], t[(1, 2)], t[none]
+    t[[]] = 1
   "#
 }
 
 check! {
-  none_pow_error,
+  tuple_unpack,
   r#"#!hebi
-    none ** none
+    a, b := (1, 2)
+    a, b = b, a
+    print a, b
+    fn test(pairs):
+      total := 0
+      for k, v in pairs:
+        print k, v
+        total += v
+      x, y := pairs[0]
+      x, y = y, x
+      return (x, y, total)
+    print test([("a", 1), ["b", 2]])[2]
+    a, b := (1, 2, 3)
   "#
 }
 
 check! {
-  bool_gt_error,
+  tuple_unpack__error_not_a_sequence,
   r#"#!hebi
-    true > false
+    for k, v in [1]:
+      pass
   "#
 }
 
-check! {
-  none_gt_error,
-  r#"#!hebi
-    none > none
-  "#
+#[test]
+fn tuple_debug() {
+  use crate::internal::object::Tuple;
+
+  assert_eq!(format!("{:?}", Tuple::from(vec![])), "()");
 }
 
 check! {
-  bool_ge_error,
+  tuple,
   r#"#!hebi
-    true >= false
+    t := (1, "a", (2, 3))
+    print t.len(), t[1], t[-1][0], t.get(5)
+    print type_of(()), type_of((1,)), type_of((1))
+    print 1 in t, "b" in t
+    print t == (1, "a", (2, 3)), t == (1, "a")
+    print "cmp", (1, 2) < (1, 3), (1, 2) < (1,), () < (0,)
+    for v in (4, 5):
+      print v
+
+    s := set([(1, 2), (1, 2), (2, 1)])
+    print s.len(), (2, 1) in s
+    t[0] = 2
   "#
 }
 
 check! {
-  none_ge_error,
+  builtin_str_methods,
   r#"#!hebi
-    none >= none
+    v := "a\nb\nc"
+
+    print "len", v.len()
+    print "is_empty", v.is_empty()
+    print "lines", v.lines()
   "#
 }
 
 check! {
-  bool_lt_error,
+  builtin_str_methods_bound,
   r#"#!hebi
-    true < false
+    v := "a\nb\nc"
+
+    v_len := v.len
+    v_is_empty := v.is_empty
+    v_lines := v.lines
+
+    print "len", v_len()
+    print "is_empty", v_is_empty()
+    print "lines", v_lines()
   "#
 }
 
 check! {
-  none_lt_error,
+  builtin_str_methods_static,
   r#"#!hebi
-    none < none
+    v := "a\nb\nc"
+
+    print "len", Str.len(v)
+    print "is_empty", Str.is_empty(v)
+    print "lines", Str.lines(v)
   "#
 }
 
 check! {
-  bool_le_error,
+  builtin_str_lines_iter,
   r#"#!hebi
-    true <= false
+    strings := [
+      "a\n\nb\nc",
+      "\na\n\nb\nc",
+      "\na\n\nb\nc\n",
+    ]
+
+    for string in strings:
+      for line in string.lines():
+        print "`" + line + "`"
+      print ""
   "#
 }
 
 check! {
-  none_le_error,
+  builtin_collect,
   r#"#!hebi
-    none <= none
+    class Counter:
+      n = 0
+      max = 0
+
+      init(self, max):
+        self.max = max
+
+      fn iter(self):
+        return self
+
+      fn next(self):
+        if self.n < self.max:
+          n := self.n
+          self.n += 1
+          return n
+
+      fn done(self):
+        return self.n >= self.max
+
+    collect(Counter(10))
   "#
 }
 
 check! {
-  heterogenous_type_equality,
+  builtin_collect_native,
   r#"#!hebi
-  print 1 == "0"
-  print 1 == false
-  print 1 == true
-  print 1 == none
-  print none == "string"
-  print none == []
-  print [] == "string"
-  class Test: pass
-  print Test() == "string"
-  print Test() == none
-  print Test() == []
-  print to_str == to_int
-  print Test() == to_int
+    collect("a\nb\nc".lines())
   "#
 }
 
 check! {
-  heterogenous_type_unequality,
+  builtin_iter_adapters,
   r#"#!hebi
-  print 1 != "0"
-  print 1 != false
-  print 1 != true
-  print 1 != none
-  print none != "string"
-  print none != []
-  print [] != "string"
-  class Test: pass
-  print Test() != "string"
-  print Test() != none
-  print Test() != []
-  print to_str != to_int
-  print Test() != to_int
+    fn is_even(n):
+      return n % 2 == 0
+
+    fn square(n):
+      return n * n
+
+    fn add(a, b):
+      return a + b
+
+    evens := filter(is_even, [1, 2, 3, 4, 5, 6])
+    print collect(map(square, evens)).join(", ")
+    print reduce(add, [1, 2, 3, 4])
+    print reduce(add, [], 10)
+
+    for pair in enumerate(zip(["a", "b", "c"], [1, 2])):
+      print pair[0], pair[1][0], pair[1][1]
+
+    print map(square, [])
+    reduce(add, [])
   "#
 }
 
 check! {
-  object_equality,
+  builtin_parse_int,
   r#"#!hebi
-  print to_int == to_int
-  print to_str == to_str
-  print to_int != to_str
-  print [].push != [].push
-  x := []
-  print x.push == x.push
-  print x.is_empty != x.push
-  print collect == collect
-  print collect != to_int
-  print "a" == "a"
-  print "a" + "b" == "ab"
-  print "a" + "b" != "ba"
+    print parse_int(10)
+    print parse_int(10.0)
+    print parse_int("10")
   "#
 }
 
 check! {
-  array_equality,
+  add_objects,
   r#"#!hebi
-    print [] == []
-    print [1] == [1]
-    print [1, 2] == [1, 2]
-    print [1] != []
-    print [] != [1]
-    print [1, 2] != [2, 1]
-    print [1, 2] != ["1", 2]
-    print [to_int, to_str] == [to_int, to_str]
+    "a" + "b"
   "#
 }
 
 check! {
-  table_equality,
+  string_comparison,
   r#"#!hebi
-    print {} == {}
-    print {x: 3} == {x: 3}
-    print {x: 3} != {x: none}
-    print {x: 3} != {y: 3}
-    print {x: 3} != {}
-    print {x: 3} != {x: 3, y: 4}
-    print {x: [1, 2]} == {x: [1, 2]}
-    print {x: [1, 2]} != {x: [2, 3]}
+    print "'a' <  'b'", "a" <  "b"
+    print "'b' >= 'a'", "b" >= "a"
+
+    print "'b' <  'a'", "b" <  "a"
+    print "'a' >= 'b'", "a" >= "b"
+
+    print "'a' == 'b'", "a" == "b"
+    print "'b' == 'a'", "a" == "b"
+    print "'a' == 'a'", "a" == "a"
   "#
 }
 
-// Reference test for forward and backward jumps <256 instructions
-check! {
-  small_jump,
-  r#"#!hebi
-    for i in 0..3:
-      x := i + i + i + i + i + i + i + i + i
-      print i
+// check! {
+//   type_comparison,
+//   r#"#!hebi
+//     fn interesting_thing(value):
+//       if value is Str:
+//         print "str: ", value
+//       else:
+//         print "I hardly know 'er"
+//
+//     interesting_thing("ppL")
+//     interesting_thing(100)
+//   "#
+// }
+
+check! {
+  global_builtin_functions__to_int__float,
+  r#"#!hebi
+    to_int(10.5)
   "#
 }
 
-// A wide jump test with forward and backwards jumps over >256 instructions
 check! {
-  wide_jump,
+  global_builtin_functions__to_int__int,
   r#"#!hebi
-    for i in 0..3:
-      x := i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i
-      print i
+    to_int(10)
   "#
 }
 
-// NOTE: the constants can be put on a single line once #34 is merged
-// Test test pre-allocates a large number of constants, causing more jumps to be
-// wide-encoded
 check! {
-  wide_const_jump,
+  global_builtin_functions__to_int__bad_input,
   r#"#!hebi
-    _ = "0"
-    _ = "1"
-    _ = "2"
-    _ = "3"
-    _ = "4"
-    _ = "5"
-    _ = "6"
-    _ = "7"
-    _ = "8"
-    _ = "9"
-    _ = "10"
-    _ = "11"
-    _ = "12"
-    _ = "13"
-    _ = "14"
-    _ = "15"
-    _ = "16"
-    _ = "17"
-    _ = "18"
-    _ = "19"
-    _ = "20"
-    _ = "21"
-    _ = "22"
-    _ = "23"
-    _ = "24"
-    _ = "25"
-    _ = "26"
-    _ = "27"
-    _ = "28"
-    _ = "29"
-    _ = "30"
-    _ = "31"
-    _ = "32"
-    _ = "33"
-    _ = "34"
-    _ = "35"
-    _ = "36"
-    _ = "37"
-    _ = "38"
-    _ = "39"
-    _ = "40"
-    _ = "41"
-    _ = "42"
-    _ = "43"
-    _ = "44"
-    _ = "45"
-    _ = "46"
-    _ = "47"
-    _ = "48"
-    _ = "49"
-    _ = "50"
-    _ = "51"
-    _ = "52"
-    _ = "53"
-    _ = "54"
-    _ = "55"
-    _ = "56"
-    _ = "57"
-    _ = "58"
-    _ = "59"
-    _ = "60"
-    _ = "61"
-    _ = "62"
-    _ = "63"
-    _ = "64"
-    _ = "65"
-    _ = "66"
-    _ = "67"
-    _ = "68"
-    _ = "69"
-    _ = "70"
-    _ = "71"
-    _ = "72"
-    _ = "73"
-    _ = "74"
-    _ = "75"
-    _ = "76"
-    _ = "77"
-    _ = "78"
-    _ = "79"
-    _ = "80"
-    _ = "81"
-    _ = "82"
-    _ = "83"
-    _ = "84"
-    _ = "85"
-    _ = "86"
-    _ = "87"
-    _ = "88"
-    _ = "89"
-    _ = "90"
-    _ = "91"
-    _ = "92"
-    _ = "93"
-    _ = "94"
-    _ = "95"
-    _ = "96"
-    _ = "97"
-    _ = "98"
-    _ = "99"
-    _ = "100"
-    _ = "101"
-    _ = "102"
-    _ = "103"
-    _ = "104"
-    _ = "105"
-    _ = "106"
-    _ = "107"
-    _ = "108"
-    _ = "109"
-    _ = "110"
-    _ = "111"
-    _ = "112"
-    _ = "113"
-    _ = "114"
-    _ = "115"
-    _ = "116"
-    _ = "117"
-    _ = "118"
-    _ = "119"
-    _ = "120"
-    _ = "121"
-    _ = "122"
-    _ = "123"
-    _ = "124"
-    _ = "125"
-    _ = "126"
-    _ = "127"
-    _ = "128"
-    _ = "129"
-    _ = "130"
-    _ = "131"
-    _ = "132"
-    _ = "133"
-    _ = "134"
-    _ = "135"
-    _ = "136"
-    _ = "137"
-    _ = "138"
-    _ = "139"
-    _ = "140"
-    _ = "141"
-    _ = "142"
-    _ = "143"
-    _ = "144"
-    _ = "145"
-    _ = "146"
-    _ = "147"
-    _ = "148"
-    _ = "149"
-    _ = "150"
-    _ = "151"
-    _ = "152"
-    _ = "153"
-    _ = "154"
-    _ = "155"
-    _ = "156"
-    _ = "157"
-    _ = "158"
-    _ = "159"
-    _ = "160"
-    _ = "161"
-    _ = "162"
-    _ = "163"
-    _ = "164"
-    _ = "165"
-    _ = "166"
-    _ = "167"
-    _ = "168"
-    _ = "169"
-    _ = "170"
-    _ = "171"
-    _ = "172"
-    _ = "173"
-    _ = "174"
-    _ = "175"
-    _ = "176"
-    _ = "177"
-    _ = "178"
-    _ = "179"
-    _ = "180"
-    _ = "181"
-    _ = "182"
-    _ = "183"
-    _ = "184"
-    _ = "185"
-    _ = "186"
-    _ = "187"
-    _ = "188"
-    _ = "189"
-    _ = "190"
-    _ = "191"
-    _ = "192"
-    _ = "193"
-    _ = "194"
-    _ = "195"
-    _ = "196"
-    _ = "197"
-    _ = "198"
-    _ = "199"
-    _ = "200"
-    _ = "201"
-    _ = "202"
-    _ = "203"
-    _ = "204"
-    _ = "205"
-    _ = "206"
-    _ = "207"
-    _ = "208"
-    _ = "209"
-    _ = "210"
-    _ = "211"
-    _ = "212"
-    _ = "213"
-    _ = "214"
-    _ = "215"
-    _ = "216"
-    _ = "217"
-    _ = "218"
-    _ = "219"
-    _ = "220"
-    _ = "221"
-    _ = "222"
-    _ = "223"
-    _ = "224"
-    _ = "225"
-    _ = "226"
-    _ = "227"
-    _ = "228"
-    _ = "229"
-    _ = "230"
-    _ = "231"
-    _ = "232"
-    _ = "233"
-    _ = "234"
-    _ = "235"
-    _ = "236"
-    _ = "237"
-    _ = "238"
-    _ = "239"
-    _ = "240"
-    _ = "241"
-    _ = "242"
-    _ = "243"
-    _ = "244"
-    _ = "245"
-    _ = "246"
-    _ = "247"
-    _ = "248"
-    _ = "249"
-    _ = "250"
-    _ = "251"
-    _ = "252"
-    _ = "253"
-    _ = "254"
-    _ = "255"
-    for i in 0..3:
-      x := i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i
-      print i
+    to_int({})
+  "#
+}
+
+check! {
+  global_builtin_functions__to_float__float,
+  r#"#!hebi
+    to_float(10.5)
+  "#
+}
+
+check! {
+  global_builtin_functions__to_float__int,
+  r#"#!hebi
+    to_float(10)
+  "#
+}
+
+check! {
+  global_builtin_functions__to_float__bad_input,
+  r#"#!hebi
+    to_float({})
+  "#
+}
+
+check! {
+  global_builtin_functions__to_bool,
+  r#"#!hebi
+    print "true", to_bool(true)
+    print "false", to_bool(false)
+    print "10.0", to_bool(10.0)
+    print "0.0", to_bool(0.0)
+    print "100", to_bool(100)
+    print "0", to_bool(0)
+    print "none", to_bool(none)
+    print "{}", to_bool({})
+    print "[]", to_bool([])
+    print "\"test\"", to_bool("test")
+  "#
+}
+
+check! {
+  global_builtin_functions__to_str,
+  r#"#!hebi
+    print to_str(true)
+    print to_str(false)
+    print to_str(100)
+    print to_str(3.14)
+    print to_str(none)
+    print to_str({})
+    print to_str([])
+    print to_str("test")
+  "#
+}
+
+check! {
+  global_builtin_functions__type_of,
+  r#"#!hebi
+    print type_of(true)
+    print type_of(false)
+    print type_of(100)
+    print type_of(3.14)
+    print type_of(none)
+    print type_of({})
+    print type_of([])
+    print type_of("test")
+  "#
+}
+
+check! {
+  make_large_table,
+  r#"#!hebi
+    {
+      version: "0.3.3",
+      update_time: 1684753441.253474,
+      data: [
+        {
+          flight_id: "306364ca",
+          flight: none,
+          callsign: "REDEYE6",
+          squawk: none,
+          clicks: 1092,
+          from_iata: "RMS",
+          from_city: "Ramstein",
+          to_iata: none,
+          to_city: none,
+          model: "B703",
+          type: "Boeing E-8C"
+        }
+      ]
+    }
+  "#
+}
+
+check! {
+  module
+  regression__variable_scope_ends_too_early,
+  {
+    http: r#"#!hebi
+      fn fetch(url, opts):
+        return {
+          version: "0.3.3",
+          update_time: 1684753441.253474,
+          data: [
+            {
+              flight_id: "306364ca",
+              flight: none,
+              callsign: "REDEYE6",
+              squawk: none,
+              clicks: 1092,
+              from_iata: "RMS",
+              from_city: "Ramstein",
+              to_iata: none,
+              to_city: none,
+              model: "B703",
+              type: "Boeing E-8C"
+            }
+          ]
+        }
+    "#,
+    utils: r#"#!hebi
+      fn get_element(list, index):
+        return list[index]
+      fn format(fmt, a, b):
+        return fmt
+      fn len(list):
+        return 1
+      fn join(list, sep):
+        return "joined"
+      fn push(list, item):
+        pass
+    "#
+  },
+  r#"#!hebi
+    from http import fetch
+    from utils import get_element, format, len, join, push
+
+    top_flights := fetch("https://flightradar24.com/flights/most-tracked", {format: "json"})
+    data := top_flights["data"]
+
+    i := 0
+    flights := []
+
+    while i < len(data):
+      flight = get_element(data, i)
+
+      callsign := ?flight["callsign"] ?? "Unknown"
+      from_city := ?flight["from_city"] ?? "N/A"
+      to_city := ?flight["to_city"] ?? "N/A"
+
+      __format = format
+      flight_info := format("{} {}", from_city, to_city)
+      print(flight_info)
+
+      i = i + 1
+
+    join(flights, ", ")
+    __format # should be the `format` function
+  "#
+}
+
+/*
+TODO: more tests
+
+- loops (for, while, loop)
+  - continue, break
+- native modules
+  - functions
+  - async functions
+  - classes
+  - class methods
+  - async class methods
+  - class methods (static call)
+  - async class methods (static call)
+  - class static methods
+  - class static async methods
+*/
+
+check! {
+  order_of_eval,
+  r#"#!hebi
+    a := 1
+    fn f():
+      a += 1
+      return a
+    print (a + f())
+  "#
+}
+
+check! {
+  bool_equality,
+  r#"#!hebi
+    a := true
+    b := false
+    if a == b:
+      print "a == b"
+    if a != b:
+      print "a != b"
+  "#
+}
+
+check! {
+  none_equality,
+  r#"#!hebi
+    a := none
+    if a == none:
+      print "a == none"
+    if a != none:
+      print "a != none"
+  "#
+}
+
+check! {
+  bool_add_error,
+  r#"#!hebi
+    true + false
   "#
 }
+
+check! {
+  none_add_error,
+  r#"#!hebi
+    none + none
+  "#
+}
+
+check! {
+  bool_sub_error,
+  r#"#!hebi
+    true - false
+  "#
+}
+
+check! {
+  none_sub_error,
+  r#"#!hebi
+    none - none
+  "#
+}
+
+check! {
+  bool_mul_error,
+  r#"#!hebi
+    true * false
+  "#
+}
+
+check! {
+  none_mul_error,
+  r#"#!hebi
+    none * none
+  "#
+}
+
+check! {
+  bool_div_error,
+  r#"#!hebi
+    true / false
+  "#
+}
+
+check! {
+  none_div_error,
+  r#"#!hebi
+    none / none
+  "#
+}
+
+check! {
+  bool_rem_error,
+  r#"#!hebi
+    true % false
+  "#
+}
+
+check! {
+  none_rem_error,
+  r#"#!hebi
+    none % none
+  "#
+}
+
+check! {
+  bool_pow_error,
+  r#"#!hebi
+    true ** false
+  "#
+}
+
+check! {
+  none_pow_error,
+  r#"#!hebi
+    none ** none
+  "#
+}
+
+check! {
+  bool_gt_error,
+  r#"#!hebi
+    true > false
+  "#
+}
+
+check! {
+  none_gt_error,
+  r#"#!hebi
+    none > none
+  "#
+}
+
+check! {
+  bool_ge_error,
+  r#"#!hebi
+    true >= false
+  "#
+}
+
+check! {
+  none_ge_error,
+  r#"#!hebi
+    none >= none
+  "#
+}
+
+check! {
+  bool_lt_error,
+  r#"#!hebi
+    true < false
+  "#
+}
+
+check! {
+  none_lt_error,
+  r#"#!hebi
+    none < none
+  "#
+}
+
+check! {
+  bool_le_error,
+  r#"#!hebi
+    true <= false
+  "#
+}
+
+check! {
+  none_le_error,
+  r#"#!hebi
+    none <= none
+  "#
+}
+
+check! {
+  heterogenous_type_equality,
+  r#"#!hebi
+  print 1 == "0"
+  print 1 == false
+  print 1 == true
+  print 1 == none
+  print none == "string"
+  print none == []
+  print [] == "string"
+  class Test: pass
+  print Test() == "string"
+  print Test() == none
+  print Test() == []
+  print to_str == to_int
+  print Test() == to_int
+  "#
+}
+
+check! {
+  heterogenous_type_unequality,
+  r#"#!hebi
+  print 1 != "0"
+  print 1 != false
+  print 1 != true
+  print 1 != none
+  print none != "string"
+  print none != []
+  print [] != "string"
+  class Test: pass
+  print Test() != "string"
+  print Test() != none
+  print Test() != []
+  print to_str != to_int
+  print Test() != to_int
+  "#
+}
+
+check! {
+  object_equality,
+  r#"#!hebi
+  print to_int == to_int
+  print to_str == to_str
+  print to_int != to_str
+  print [].push != [].push
+  x := []
+  print x.push == x.push
+  print x.is_empty != x.push
+  print collect == collect
+  print collect != to_int
+  print "a" == "a"
+  print "a" + "b" == "ab"
+  print "a" + "b" != "ba"
+  "#
+}
+
+check! {
+  array_equality,
+  r#"#!hebi
+    print [] == []
+    print [1] == [1]
+    print [1, 2] == [1, 2]
+    print [1] != []
+    print [] != [1]
+    print [1, 2] != [2, 1]
+    print [1, 2] != ["1", 2]
+    print [to_int, to_str] == [to_int, to_str]
+  "#
+}
+
+check! {
+  table_equality,
+  r#"#!hebi
+    print {} == {}
+    print {x: 3} == {x: 3}
+    print {x: 3} != {x: none}
+    print {x: 3} != {y: 3}
+    print {x: 3} != {}
+    print {x: 3} != {x: 3, y: 4}
+    print {x: [1, 2]} == {x: [1, 2]}
+    print {x: [1, 2]} != {x: [2, 3]}
+  "#
+}
+
+// Reference test for forward and backward jumps <256 instructions
+check! {
+  small_jump,
+  r#"#!hebi
+    for i in 0..3:
+      x := i + i + i + i + i + i + i + i + i
+      print i
+  "#
+}
+
+// A wide jump test with forward and backwards jumps over >256 instructions
+check! {
+  wide_jump,
+  r#"#!hebi
+    for i in 0..3:
+      x := i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i
+      print i
+  "#
+}
+
+// NOTE: the constants can be put on a single line once #34 is merged
+// Test test pre-allocates a large number of constants, causing more jumps to be
+// wide-encoded
+check! {
+  wide_const_jump,
+  r#"#!hebi
+    _ = "0"
+    _ = "1"
+    _ = "2"
+    _ = "3"
+    _ = "4"
+    _ = "5"
+    _ = "6"
+    _ = "7"
+    _ = "8"
+    _ = "9"
+    _ = "10"
+    _ = "11"
+    _ = "12"
+    _ = "13"
+    _ = "14"
+    _ = "15"
+    _ = "16"
+    _ = "17"
+    _ = "18"
+    _ = "19"
+    _ = "20"
+    _ = "21"
+    _ = "22"
+    _ = "23"
+    _ = "24"
+    _ = "25"
+    _ = "26"
+    _ = "27"
+    _ = "28"
+    _ = "29"
+    _ = "30"
+    _ = "31"
+    _ = "32"
+    _ = "33"
+    _ = "34"
+    _ = "35"
+    _ = "36"
+    _ = "37"
+    _ = "38"
+    _ = "39"
+    _ = "40"
+    _ = "41"
+    _ = "42"
+    _ = "43"
+    _ = "44"
+    _ = "45"
+    _ = "46"
+    _ = "47"
+    _ = "48"
+    _ = "49"
+    _ = "50"
+    _ = "51"
+    _ = "52"
+    _ = "53"
+    _ = "54"
+    _ = "55"
+    _ = "56"
+    _ = "57"
+    _ = "58"
+    _ = "59"
+    _ = "60"
+    _ = "61"
+    _ = "62"
+    _ = "63"
+    _ = "64"
+    _ = "65"
+    _ = "66"
+    _ = "67"
+    _ = "68"
+    _ = "69"
+    _ = "70"
+    _ = "71"
+    _ = "72"
+    _ = "73"
+    _ = "74"
+    _ = "75"
+    _ = "76"
+    _ = "77"
+    _ = "78"
+    _ = "79"
+    _ = "80"
+    _ = "81"
+    _ = "82"
+    _ = "83"
+    _ = "84"
+    _ = "85"
+    _ = "86"
+    _ = "87"
+    _ = "88"
+    _ = "89"
+    _ = "90"
+    _ = "91"
+    _ = "92"
+    _ = "93"
+    _ = "94"
+    _ = "95"
+    _ = "96"
+    _ = "97"
+    _ = "98"
+    _ = "99"
+    _ = "100"
+    _ = "101"
+    _ = "102"
+    _ = "103"
+    _ = "104"
+    _ = "105"
+    _ = "106"
+    _ = "107"
+    _ = "108"
+    _ = "109"
+    _ = "110"
+    _ = "111"
+    _ = "112"
+    _ = "113"
+    _ = "114"
+    _ = "115"
+    _ = "116"
+    _ = "117"
+    _ = "118"
+    _ = "119"
+    _ = "120"
+    _ = "121"
+    _ = "122"
+    _ = "123"
+    _ = "124"
+    _ = "125"
+    _ = "126"
+    _ = "127"
+    _ = "128"
+    _ = "129"
+    _ = "130"
+    _ = "131"
+    _ = "132"
+    _ = "133"
+    _ = "134"
+    _ = "135"
+    _ = "136"
+    _ = "137"
+    _ = "138"
+    _ = "139"
+    _ = "140"
+    _ = "141"
+    _ = "142"
+    _ = "143"
+    _ = "144"
+    _ = "145"
+    _ = "146"
+    _ = "147"
+    _ = "148"
+    _ = "149"
+    _ = "150"
+    _ = "151"
+    _ = "152"
+    _ = "153"
+    _ = "154"
+    _ = "155"
+    _ = "156"
+    _ = "157"
+    _ = "158"
+    _ = "159"
+    _ = "160"
+    _ = "161"
+    _ = "162"
+    _ = "163"
+    _ = "164"
+    _ = "165"
+    _ = "166"
+    _ = "167"
+    _ = "168"
+    _ = "169"
+    _ = "170"
+    _ = "171"
+    _ = "172"
+    _ = "173"
+    _ = "174"
+    _ = "175"
+    _ = "176"
+    _ = "177"
+    _ = "178"
+    _ = "179"
+    _ = "180"
+    _ = "181"
+    _ = "182"
+    _ = "183"
+    _ = "184"
+    _ = "185"
+    _ = "186"
+    _ = "187"
+    _ = "188"
+    _ = "189"
+    _ = "190"
+    _ = "191"
+    _ = "192"
+    _ = "193"
+    _ = "194"
+    _ = "195"
+    _ = "196"
+    _ = "197"
+    _ = "198"
+    _ = "199"
+    _ = "200"
+    _ = "201"
+    _ = "202"
+    _ = "203"
+    _ = "204"
+    _ = "205"
+    _ = "206"
+    _ = "207"
+    _ = "208"
+    _ = "209"
+    _ = "210"
+    _ = "211"
+    _ = "212"
+    _ = "213"
+    _ = "214"
+    _ = "215"
+    _ = "216"
+    _ = "217"
+    _ = "218"
+    _ = "219"
+    _ = "220"
+    _ = "221"
+    _ = "222"
+    _ = "223"
+    _ = "224"
+    _ = "225"
+    _ = "226"
+    _ = "227"
+    _ = "228"
+    _ = "229"
+    _ = "230"
+    _ = "231"
+    _ = "232"
+    _ = "233"
+    _ = "234"
+    _ = "235"
+    _ = "236"
+    _ = "237"
+    _ = "238"
+    _ = "239"
+    _ = "240"
+    _ = "241"
+    _ = "242"
+    _ = "243"
+    _ = "244"
+    _ = "245"
+    _ = "246"
+    _ = "247"
+    _ = "248"
+    _ = "249"
+    _ = "250"
+    _ = "251"
+    _ = "252"
+    _ = "253"
+    _ = "254"
+    _ = "255"
+    for i in 0..3:
+      x := i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i + i
+      print i
+  "#
+}
+
+check! {
+  table_insertion_order,
+  r#"#!hebi
+    v := {c: 0, a: 1, b: 2}
+    v["d"] = 3
+    v["a"] = 4
+    v
+  "#
+}
+
+#[test]
+fn table_sort_keys() {
+  use crate::public::IntoValue;
+
+  let hebi = crate::public::Hebi::new();
+  let global = hebi.global();
+  let table = hebi.new_table(3);
+  for (i, key) in ["c", "a", "b"].into_iter().enumerate() {
+    let value = (i as i32).into_value(global.clone()).unwrap();
+    table.insert(hebi.new_string(key), value);
+  }
+  let keys = |table: &crate::public::Table| {
+    table
+      .keys()
+      .map(|key| key.as_object::<crate::public::Str>(global.clone()).unwrap())
+      .map(|key| key.as_str().to_string())
+      .collect::<Vec<_>>()
+  };
+
+  assert_eq!(keys(&table), ["c", "a", "b"]);
+  table.sort_keys();
+  assert_eq!(keys(&table), ["a", "b", "c"]);
+  assert_eq!(table.get("c").and_then(|v| v.as_int()), Some(0));
+}
+
+#[test]
+fn interned_strings() {
+  use crate::internal::object::Str;
+
+  let hebi = crate::public::Hebi::new();
+  let global = hebi.global().inner;
+
+  let a = global.intern("key".to_string());
+  let b = global.intern("key");
+  let c = global.alloc(Str::owned("key"));
+  assert!(a.is_interned());
+  assert!(a.ptr_eq(&b));
+  assert!(!c.is_interned());
+  assert!(a == c);
+  assert!(a != global.intern("other"));
+
+  let table = global.alloc(crate::internal::object::Table::new());
+  table.insert(a, crate::internal::value::Value::int(1));
+  assert_eq!(table.get("key").and_then(|v| v.to_int()), Some(1));
+  assert_eq!(table.get(&c).and_then(|v| v.to_int()), Some(1));
+}
+
+#[test]
+fn inline_strings() {
+  use crate::internal::object::Str;
+
+  let short = Str::owned("a".repeat(23));
+  let long = Str::owned("a".repeat(24));
+  assert!(short.is_inline());
+  assert!(!long.is_inline());
+  assert_eq!(short.as_str(), "a".repeat(23));
+  assert_eq!(long.as_str(), "a".repeat(24));
+  assert!(Str::owned("").is_inline());
+
+  // concatenation crosses from the inline to the heap representation
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(
+      r#"
+s := "aaaaaaaaaaaaaaaaaaaaaa"
+t := {}
+t[s + "b"] = 1
+t[s + "bc"] = 2
+t["aaaaaaaaaaaaaaaaaaaaaab"] + t["aaaaaaaaaaaaaaaaaaaaaabc"]
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_int(), Some(3));
+}
+
+#[test]
+fn stack_reserve() {
+  use crate::internal::vm::{Config, Vm};
+
+  let mut vm = Vm::with_config(Config {
+    stack_reserve: 1024,
+    frame_reserve: 32,
+    ..Default::default()
+  });
+  let capacity = |vm: &Vm| {
+    let stack = unsafe { vm.stack.as_ref() };
+    (stack.regs.capacity(), stack.frames.capacity())
+  };
+  let (regs, frames) = capacity(&vm);
+  assert!(regs >= 1024);
+  assert!(frames >= 32);
+
+  pollster::block_on(vm.eval(
+    r#"
+fn f(n):
+  if n > 0:
+    return f(n - 1)
+  return 0
+f(100)
+"#,
+  ))
+  .unwrap();
+
+  // the stack is empty again, but keeps the memory it grew to
+  let stack = unsafe { vm.stack.as_ref() };
+  assert!(stack.regs.is_empty() && stack.frames.is_empty());
+  assert!(stack.frames.capacity() >= 100);
+  assert!(capacity(&vm).0 >= regs);
+}
+
+#[test]
+fn frame_pool() {
+  use crate::internal::vm::Vm;
+
+  let mut vm = Vm::default();
+  pollster::block_on(vm.eval(
+    r#"
+fn noop(): pass
+fn f(n):
+  defer noop()
+  if n > 0:
+    f(n - 1)
+for i in 0..10:
+  f(2)
+"#,
+  ))
+  .unwrap();
+
+  // each of the 3 nested calls gets a `defer` list from the pool, and gives
+  // it back when it returns
+  let stack = unsafe { vm.stack.as_ref() };
+  assert_eq!(stack.deferred_pool.len(), 3);
+  assert!(stack.deferred_pool.iter().all(|v| v.is_empty()));
+}
+
+fn eval_with_int_overflow(int_overflow: crate::public::IntOverflow, code: &str) -> String {
+  let mut hebi = crate::public::Hebi::builder()
+    .int_overflow(int_overflow)
+    .finish();
+  match hebi.eval(code) {
+    Ok(value) => format!("{value:?}"),
+    Err(e) => e.to_string(),
+  }
+}
+
+#[test]
+fn int_overflow_promote() {
+  use crate::public::IntOverflow::Promote;
+
+  assert_eq!(
+    eval_with_int_overflow(Promote, "9223372036854775806 + 1"),
+    "Int(9223372036854775807)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "9223372036854775807 + 1"),
+    "Float(9.223372036854776e18)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "-9223372036854775807 - 1"),
+    "Int(-9223372036854775808)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "-9223372036854775807 - 2"),
+    "Float(-9.223372036854776e18)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "4294967296 * 4294967296"),
+    "Float(1.8446744073709552e19)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "2 ** 62"),
+    "Int(4611686018427387904)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Promote, "2 ** 63"),
+    "Float(9.223372036854776e18)"
+  );
+  assert_eq!(eval_with_int_overflow(Promote, "2 ** -1"), "Float(0.5)");
+  assert_eq!(
+    eval_with_int_overflow(Promote, "v := -9223372036854775807 - 1\n-v"),
+    "Float(9.223372036854776e18)"
+  );
+}
+
+#[test]
+fn int_overflow_saturate() {
+  use crate::public::IntOverflow::Saturate;
+
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "9223372036854775807 + 1"),
+    "Int(9223372036854775807)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "-9223372036854775807 - 2"),
+    "Int(-9223372036854775808)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "4294967296 * -4294967296"),
+    "Int(-9223372036854775808)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "(-2) ** 63"),
+    "Int(-9223372036854775808)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "(-2) ** 64"),
+    "Int(9223372036854775807)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Saturate, "v := -9223372036854775807 - 1\n-v"),
+    "Int(9223372036854775807)"
+  );
+}
+
+#[test]
+fn int_overflow_error() {
+  use crate::public::IntOverflow::Error;
+
+  assert_eq!(
+    eval_with_int_overflow(Error, "9223372036854775806 + 1"),
+    "Int(9223372036854775807)"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Error, "9223372036854775807 + 1"),
+    "integer overflow: `9223372036854775807 + 1`"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Error, "-9223372036854775807 - 2"),
+    "integer overflow: `-9223372036854775807 - 2`"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Error, "4294967296 * 4294967296"),
+    "integer overflow: `4294967296 * 4294967296`"
+  );
+  assert_eq!(
+    eval_with_int_overflow(Error, "3 ** 40"),
+    "integer overflow: `3 ** 40`"
+  );
+}
+
+check! {
+  pow_precedence,
+  r#"#!hebi
+    x := 3
+    print -2 ** 2, (-2) ** 2, 2 ** 3 ** 2, 2.0 ** -1, -x ** 2
+  "#
+}
+
+check! {
+  floor_div,
+  r#"#!hebi
+    print 7 // 2, -7 // 2, 7 // -2, -7 // -2
+    print 7 % 2, -7 % 2, 7 % -2, -7 % -2
+    print 7.5 // 2.0, -7.5 // 2.0, -7.5 % 2.0, 7 // 2.0
+    for a in [7, -7, 6]:
+      for b in [2, -2, 3]:
+        print a, b, (a // b) * b + a % b == a
+    v := 17
+    v //= 5
+    v
+  "#
+}
+
+check! {
+  floor_div_by_zero,
+  r#"#!hebi
+    1 // 0
+  "#
+}
+
+check! {
+  boxed_int_arithmetic,
+  r#"
+    a := 2147483647 + 1
+    b := 3000000000
+    print a, b, a - 2147483648, b * 2, b / 2
+    print -2147483647 - 2, 65536 * 65536, 2 ** 40
+    print b == 3000000000, b != a, a < b, b > 2147483647, b >= 3000000000.0
+    print b + 0.5, 0.5 + b, b % 7
+    print type_of(b), to_float(b), to_int(4294967296.0), parse_int("8589934592")
+  "#
+}
+
+#[test]
+fn boxed_int_conversions() {
+  use crate::public::IntoValue;
+
+  let mut hebi = crate::public::Hebi::new();
+  let value = 3_000_000_000i64.into_value(hebi.global()).unwrap();
+  assert_eq!(value.as_i64(), Some(3_000_000_000));
+  assert_eq!(value.as_int(), None);
+  let value = 5i64.into_value(hebi.global()).unwrap();
+  assert_eq!(value.as_int(), Some(5));
+  let value = hebi.eval("2147483647 * 2").unwrap();
+  assert_eq!(value.as_i64(), Some(4294967294));
+}
+
+#[cfg(feature = "bigint")]
+check! {
+  bigint_arithmetic,
+  r#"
+    a := 9223372036854775807n
+    b := a * a + 1n
+    print b, b - a * a, -a, 2n ** 100n
+    print 7n / 2n, -7n / 2n, 7n % 3n
+    print b > a, a == 9_223_372_036_854_775_807n, bigint(5) == 5n
+    print type_of(a), bigint("123456789012345678901234567890"), bigint(2.0 ** 70)
+    print to_int(100n), to_float(2n ** 64n)
+  "#
+}
+
+#[cfg(feature = "bigint")]
+check! {
+  bigint_floor_div,
+  r#"
+    print 7n // 2n, -7n // 2n, 7n // -2n, -7n // -2n
+    print 7n % 2n, -7n % 2n, 7n % -2n, -7n % -2n
+  "#
+}
+
+#[cfg(feature = "bigint")]
+check! {
+  bigint_mixed_types_error,
+  r#"
+    1n + 1
+  "#
+}
+
+#[cfg(feature = "bigint")]
+check! {
+  bigint_divide_by_zero_error,
+  r#"
+    1n / 0n
+  "#
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn bigint_literal_requires_feature() {
+  let mut hebi = crate::public::Hebi::new();
+  let e = hebi.eval("1n").unwrap_err();
+  assert!(e
+    .to_string()
+    .contains("bigint literals require the `bigint` feature"));
+}
+
+check! {
+  bytes_literal_and_methods,
+  r#"
+    v := b"ab\x00\xff\n"
+    print v, v.len(), v[0], v[-2], 255 in v
+    v.push(99)
+    v[0] = 65
+    v.extend([1, 2])
+    v.extend(b"xy")
+    print v, v.pop(), v.slice(1, 3), v.slice(5)
+    print bytes("héllo"), bytes("héllo").to_str(), bytes([104, 105]).to_list()
+    print b"a" + b"b" == b"ab", type_of(v), Bytes.len(v)
+    for byte in b"hi":
+      print byte
+  "#
+}
+
+check! {
+  bytes_literal_is_copied_on_each_evaluation,
+  r#"
+    fn f():
+      v := b""
+      v.push(1)
+      return v.len()
+    print f(), f()
+  "#
+}
+
+check! {
+  bytes_invalid_byte,
+  r#"
+    v := b""
+    v.push(256)
+  "#
+}
+
+check! {
+  bytes_invalid_utf8,
+  r#"
+    b"\xff".to_str()
+  "#
+}
+
+check! {
+  class_static_fields_and_methods,
+  r#"
+    class T:
+      v = 0
+      static count = 0
+      static default_v = 10
+      static fn make():
+        T.count += 1
+        t := T()
+        t.v = T.default_v
+        return t
+    class U(T):
+      static fn twice():
+        return [U.make(), U.make()]
+    print T.count, T.default_v
+    print T.make().v
+    print U.twice()[1].v, U.count, T.count
+    T.default_v = 20
+    print T.make().v, U.default_v
+  "#
+}
+
+check! {
+  class_static_field_unknown,
+  r#"
+    class T:
+      static v = 0
+    T.w = 1
+  "#
+}
+
+check! {
+  class_finalizer,
+  r#"
+    class File:
+      name = ""
+      init(self, name):
+        self.name = name
+      fn __del__(self):
+        print "closing", self.name
+    fn use_temp():
+      f := File("temp")
+      print "using", f.name
+    use_temp()
+    kept := File("kept")
+    print "done"
+  "#
+}
+
+check! {
+  class_finalizer_in_loop,
+  r#"
+    class Temp:
+      fn __del__(self):
+        print "dropped"
+    for i in 0..3:
+      t := Temp()
+      print "made", i
+    print "loop done"
+  "#
+}
+
+check! {
+  class_mixins,
+  r#"
+    class A:
+      fn name(self): return "A"
+      fn greet(self): return "hello from " + self.name()
+    class Named:
+      label = "named"
+      fn name(self): return "Named"
+      fn describe(self): return self.label + " " + self.name()
+    class Loud:
+      fn name(self): return "Loud"
+      fn shout(self): return self.greet() + "!"
+    class B(A) with Named, Loud:
+      fn greet(self): return super.greet() + "?"
+    class C with Named:
+      label = "C"
+    b := B()
+    print b.name(), b.describe(), b.shout()
+    print C().describe()
+    print b is A, b is Named, b is Loud, C() is Loud
+  "#
+}
+
+check! {
+  class_mixin_not_a_class,
+  r#"
+    M := 0
+    class T with M: pass
+  "#
+}
+
+check! {
+  is_class_and_protocol,
+  r#"
+    class A: pass
+    class B(A):
+      fn check(self):
+        print self is A, super is B
+    class Counter:
+      n = 0
+      fn iter(self): return self
+      fn next(self):
+        self.n += 1
+        return self.n
+      fn done(self): return self.n >= 3
+    print B() is A, B() is B, A() is B
+    B().check()
+    print [] is Iterable, [] is Iterator, "" is Iterable, 1 is Iterable
+    print Counter() is Iterable, Counter() is Iterator, A() is Iterable
+    class NotIterable:
+      iter = 0
+      next = 1
+      fn done(self): return true
+    print NotIterable() is Iterable, NotIterable() is Iterator, { iter: 1 } is Iterable
+    fn id(v): return v
+    print map(id, []) is Iterator, Error("e") is Error
+  "#
+}
+
+#[test]
+fn bytes_from_host() {
+  use crate::public::{Bytes, FromValue, IntoValue};
+
+  let hebi = crate::public::Hebi::new();
+  let global = hebi.global();
+  let bytes = hebi.new_bytes(&b"\x01\x02"[..]);
+  bytes.push(3);
+  bytes.extend_from_slice(&[4, 5]);
+  assert!(bytes.set(0, 0));
+  assert_eq!(bytes.get(4), Some(5));
+
+  let value = bytes.into_value(global.clone()).unwrap();
+  let bytes = Bytes::from_value(value, global).unwrap();
+  assert_eq!(bytes.to_vec(), vec![0, 2, 3, 4, 5]);
+  assert_eq!(&*bytes.as_slice(), &[0, 2, 3, 4, 5]);
+}
+
+#[test]
+fn borrowed_params() {
+  let module = crate::public::NativeModule::builder("text")
+    .function("count", |scope| {
+      let (text, pattern) = (scope.param_str(0)?, scope.param_str(1)?);
+      Result::Ok(text.matches(pattern).count() as i32)
+    })
+    .function("checksum", |scope| {
+      let data = scope.param_bytes(0)?;
+      Result::Ok(data.iter().map(|&b| b as i32).sum::<i32>())
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  let mut eval = |src: &str| match hebi.eval(src) {
+    Ok(value) => value.to_string(),
+    Err(e) => e.to_string(),
+  };
+
+  assert_eq!(eval("import text\ntext.count(\"banana\", \"an\")"), "2");
+  assert_eq!(
+    eval("import text\ntext.checksum(b\"\\x01\\x02\\xff\")"),
+    "258"
+  );
+  assert!(eval("import text\ntext.count(1, \"a\")").contains("not a string"));
+  assert!(eval("import text\ntext.checksum(\"a\")").contains("not bytes"));
+  assert!(eval("import text\ntext.count(\"a\")").contains("missing argument 1"));
+}
+
+#[tokio::test]
+async fn native_module_lazy_init() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use crate::public::IntoValue;
+  use std::sync::Arc;
+
+  let calls = Arc::new(AtomicUsize::new(0));
+  let mut hebi = Vm::default();
+  hebi.register(
+    &NativeModule::builder("lazy")
+      .init({
+        let calls = calls.clone();
+        move |scope, module_vars| {
+          calls.fetch_add(1, Ordering::SeqCst);
+          module_vars.insert(
+            scope.new_string("answer"),
+            42i32.into_value(scope.global())?,
+          );
+          Ok(())
+        }
+      })
+      .finish(),
+  );
+
+  hebi.eval("v := 0").await.unwrap();
+  assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+  let value = hebi
+    .eval("from lazy import answer\nanswer")
+    .await
+    .unwrap()
+    .to_int();
+  assert_eq!(value, Some(42));
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+  hebi.eval("import lazy\nlazy.answer").await.unwrap();
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn native_module_lazy_init_error_is_retried() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+
+  let calls = Arc::new(AtomicUsize::new(0));
+  let mut hebi = Vm::default();
+  hebi.register(
+    &NativeModule::builder("flaky")
+      .init({
+        let calls = calls.clone();
+        move |_, _| {
+          if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            fail!("not yet");
+          }
+          Ok(())
+        }
+      })
+      .finish(),
+  );
+
+  let e = hebi.eval("import flaky").await.unwrap_err();
+  assert!(e.to_string().contains("not yet"), "{e}");
+  hebi.eval("import flaky").await.unwrap();
+  assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+struct TestAsyncModuleLoader {
+  modules: HashMap<&'static str, &'static str>,
+}
+
+impl module::AsyncModuleLoader for TestAsyncModuleLoader {
+  fn load(
+    &self,
+    path: &module::ModulePath,
+  ) -> crate::LocalBoxFuture<'static, Result<Cow<'static, str>>> {
+    let module = self.modules.get(path.as_str()).copied();
+    let path = path.to_string();
+    Box::pin(async move {
+      tokio::task::yield_now().await;
+      match module {
+        Some(module) => Ok(Cow::borrowed(module)),
+        None => fail!("module `{path}` not found"),
+      }
+    })
+  }
+}
+
+#[tokio::test]
+async fn async_module_loader() {
+  let mut hebi = crate::public::Hebi::builder()
+    .async_module_loader(TestAsyncModuleLoader {
+      modules: HashMap::from_iter([
+        ("a", "from b import value\nvalue := value + 1"),
+        ("b", "value := 100"),
+      ]),
+    })
+    .finish();
+
+  let value = hebi
+    .eval_async("from a import value\nvalue")
+    .await
+    .unwrap()
+    .as_int();
+  assert_eq!(value, Some(101));
+
+  let e = hebi.eval_async("import c").await.unwrap_err();
+  assert!(e.to_string().contains("module `c` not found"), "{e}");
+}
+
+#[test]
+fn optimized_eval() {
+  let sources = [
+    "1 + 2 * 3",
+    "v := 10\nv - 4",
+    "!(1 < 2)",
+    "-(5 - 10)",
+    "v := 0\nif 1 == 1:\n  v = 1\nelse:\n  v = 2\nv",
+    "v := 0\nfor i in 0..10:\n  v += i\nv",
+    "fn f(a, b):\n  return a * b + 1\nf(3, 4)",
+    "v := [1, 2, 3]\nv[0] + v[2]",
+    "v := 0\ni := 0\nwhile i < 10:\n  v = v + i\n  i += 1\nv",
+    "fn fib(n):\n  if n < 2:\n    return n\n  return fib(n - 2) + fib(n - 1)\nfib(10)",
+    "fn f(c):\n  if c == \"a\":\n    return 1\n  elif c == \"b\":\n    return 2\n  elif c == \"c\":\n    return 3\n  elif c == \"d\":\n    return 4\n  return 0\n[f(\"a\"), f(\"c\"), f(\"d\"), f(\"e\"), f(1)]",
+  ];
+
+  for source in sources {
+    let mut hebi = crate::public::Hebi::new();
+    let mut optimized = crate::public::Hebi::builder().optimize(true).finish();
+    let expected = format!("{:?}", hebi.eval(source).unwrap());
+    let actual = format!("{:?}", optimized.eval(source).unwrap());
+    assert_eq!(actual, expected, "{source}");
+  }
+}
+
+#[test]
+fn type_hint_checks() {
+  use indoc::indoc;
+
+  let source = indoc! {r#"
+    class A:
+      v: int = 0
+    class B(A):
+      w: A = none
+    fn f(a: int, b: A = B(), c: any = none):
+      return a
+  "#};
+  let run = |mut hebi: crate::public::Hebi, code: &str| {
+    hebi.eval(source).unwrap();
+    hebi
+      .eval(code)
+      .map(|v| format!("{v:?}"))
+      .map_err(|e| e.to_string())
+  };
+  let checked = || crate::public::Hebi::builder().type_checks(true).finish();
+
+  // hints are ignored unless checks are enabled
+  assert!(run(crate::public::Hebi::new(), r#"f("a")"#).is_ok());
+  assert!(run(crate::public::Hebi::new(), r#"A().v = "a""#).is_ok());
+
+  assert!(run(checked(), "f(1, A(), [])").is_ok());
+  assert!(run(checked(), "f(1, B())").is_ok());
+  let err = run(checked(), r#"f("a")"#).unwrap_err();
+  assert!(
+    err.contains("param `a` expected `int`, got `String`"),
+    "{err}"
+  );
+  let err = run(checked(), "f(1, 2)").unwrap_err();
+  assert!(err.contains("param `b` expected `A`, got `int`"), "{err}");
+
+  assert!(run(checked(), "B().v = 1").is_ok());
+  let err = run(checked(), "B().v = 1.5").unwrap_err();
+  assert!(
+    err.contains("field `v` expected `int`, got `float`"),
+    "{err}"
+  );
+  let err = run(checked(), "b := B()\nb.w = b\nb.w = A()\nb.w = 1").unwrap_err();
+  assert!(err.contains("field `w` expected `A`, got `int`"), "{err}");
+  let err = run(checked(), "class C:\n  v: int = \"a\"").unwrap_err();
+  assert!(
+    err.contains("field `v` expected `int`, got `String`"),
+    "{err}"
+  );
+}
+
+#[test]
+fn field_inline_cache() {
+  // the same instructions see instances of classes with different layouts,
+  // so the cached slot must be re-validated on every access
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(
+      r#"
+class A:
+  x = 1
+  y = 2
+  fn sum(self):
+    return self.x + self.y
+
+class B:
+  y = 10
+  x = 20
+  fn sum(self):
+    return self.x * self.y
+
+fn bump(o):
+  o.x += 1
+  return o.sum()
+
+total := 0
+for o in [A(), B(), A(), B()]:
+  total += bump(o)
+  total += bump(o)
+total
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_int(), Some(2 * (4 + 5 + 210 + 220)));
+}
+
+#[test]
+fn shared_constant_pool() {
+  // every nested function reads its constants through a window into the
+  // pool of the module, so their indices must not overlap
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(
+      r#"
+fn outer(a):
+  s := "outer"
+  fn inner(b):
+    if s == "outer":
+      return a + b + 1.5
+    return 0.0
+  return inner
+
+class C:
+  v = 2.5
+  fn get(self):
+    if "method" == "method":
+      return self.v * 4.0
+    return 0.0
+
+f := outer(10)
+f(20) + C().get() + 0.25
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_float(), Some(31.5 + 10.0 + 0.25));
+}
+
+#[test]
+fn coroutine_resume_and_status() {
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(
+      r#"
+fn count(n):
+  i := 0
+  while i < n:
+    yield i
+    i += 1
+  return 100
+
+co := coroutine(count)
+statuses := [co.status()]
+total := co.resume(3)
+total += co.resume()
+statuses.push(co.status())
+total += co.resume()
+total += co.resume()
+statuses.push(co.status())
+if statuses.join(",") != "suspended,suspended,dead":
+  total = -1
+total
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_int(), Some(100 + 1 + 2));
+}
+
+#[test]
+fn coroutine_errors() {
+  let mut hebi = crate::public::Hebi::new();
+
+  let e = hebi.eval("fn f():\n  yield 1\nf()").unwrap_err();
+  assert!(e.to_string().contains("outside of a coroutine"), "{e}");
+
+  let e = hebi
+    .eval("fn f():\n  return 1\nco := coroutine(f)\nco.resume()\nco.resume()")
+    .unwrap_err();
+  assert!(e.to_string().contains("dead coroutine"), "{e}");
+}
+
+#[test]
+fn compiled_program_on_many_threads() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<crate::public::CompiledProgram>();
+
+  let program = crate::public::Hebi::new()
+    .compile_program(
+      r#"
+class Counter:
+  n = 0
+  fn bump(self, by):
+    self.n += by
+    return self
+
+fn sum(n):
+  c := Counter()
+  for i in 0..n:
+    c.bump(i)
+  return c.n
+
+sum(10) + to_int(1.5) + "abc".len()
+"#,
+    )
+    .unwrap();
+  let program = std::sync::Arc::new(program);
+
+  let handles = (0..4)
+    .map(|_| {
+      let program = program.clone();
+      std::thread::spawn(move || {
+        let mut hebi = crate::public::Hebi::from_compiled(program);
+        let first = hebi.run_compiled().unwrap().as_int();
+        let second = hebi.run_compiled().unwrap().as_int();
+        (first, second)
+      })
+    })
+    .collect::<Vec<_>>();
+  for handle in handles {
+    assert_eq!(handle.join().unwrap(), (Some(49), Some(49)));
+  }
+}
+
+#[test]
+fn load_compiled_program() {
+  let program = crate::public::Hebi::new()
+    .compile_program("v := 0\nfor i in 0..4:\n  v += i\nv")
+    .unwrap();
+
+  let mut hebi = crate::public::Hebi::new();
+  let chunk = hebi.load(&program);
+  assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(6));
+
+  let e = hebi.run_compiled().unwrap_err();
+  assert!(e
+    .to_string()
+    .contains("not created from a compiled program"));
+}
+
+#[test]
+fn deep_clone_into_other_vm() {
+  let class = r#"
+class Point:
+  x = 0
+  y = 0
+  fn sum(self):
+    return self.x + self.y
+"#;
+
+  let mut a = crate::public::Hebi::new();
+  let mut b = crate::public::Hebi::new();
+  b.eval(class).unwrap();
+
+  let source = format!(
+    r#"{class}
+p := Point()
+p.x = 2
+p.y = 3
+shared := [1, 2]
+v := {{ points: [p, p], shared: shared, also: shared, s: "abc", n: 1.5 }}
+v["me"] = v
+v
+"#
+  );
+  let value = a.eval(&source).unwrap();
+  let copy = value.deep_clone_into(&b).unwrap();
+  b.global().set(b.new_string("v"), copy);
+
+  let result = b
+    .eval(
+      r#"
+v["shared"].push(3)
+v["points"][0].x = 10
+result := -1.0
+if v["also"].len() == 3 && v["me"]["s"] == "abc" && v["points"][1].x == 10:
+  result = v["points"][1].sum() + v["n"]
+result
+"#,
+    )
+    .unwrap();
+  assert_eq!(result.as_float(), Some(14.5));
+}
+
+#[test]
+fn deep_clone_into_errors() {
+  let mut a = crate::public::Hebi::new();
+  let b = crate::public::Hebi::new();
+
+  let value = a.eval("fn f():\n  pass\nf").unwrap();
+  let e = value.deep_clone_into(&b).unwrap_err();
+  assert!(e.to_string().contains("cannot copy"), "{e}");
+
+  let value = a.eval("class C:\n  x = 1\nC()").unwrap();
+  let e = value.deep_clone_into(&b).unwrap_err();
+  assert!(e.to_string().contains("no class named `C`"), "{e}");
+}
+
+#[test]
+fn frozen_shared_between_threads() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<crate::public::Frozen>();
+
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(r#"("db", [1, 2.5, none, true], ("nested", 9007199254740993))"#)
+    .unwrap();
+  let config = value.freeze().unwrap();
+  assert_eq!(config.as_tuple().unwrap()[0].as_str(), Some("db"));
+
+  let workers = (0..4)
+    .map(|n| {
+      let config = config.clone();
+      std::thread::spawn(move || {
+        let mut hebi = crate::public::Hebi::new();
+        hebi
+          .global()
+          .set(hebi.new_string("config"), config.thaw(hebi.global()));
+        hebi.global().set(
+          hebi.new_string("n"),
+          crate::public::IntoValue::into_value(n, hebi.global()).unwrap(),
+        );
+        let value = hebi
+          .eval(indoc::indoc! {r#"
+            items := config[1]
+            to_str(n) + " " + config[0] + " " + to_str(items[0] + items[1]) + " " + to_str(config[2][1])
+          "#})
+          .unwrap();
+        value.to_string()
+      })
+    })
+    .collect::<Vec<_>>();
+  for (n, worker) in workers.into_iter().enumerate() {
+    assert_eq!(
+      worker.join().unwrap(),
+      format!("{n} db 3.5 9007199254740993")
+    );
+  }
+}
+
+#[test]
+fn freeze_errors() {
+  let mut hebi = crate::public::Hebi::new();
+
+  let value = hebi.eval("[1, {}]").unwrap();
+  let e = value.freeze().unwrap_err();
+  assert!(e.to_string().contains("cannot freeze"), "{e}");
+
+  let value = hebi.eval("l := [1]\nl.push(l)\nl").unwrap();
+  let e = value.freeze().unwrap_err();
+  assert!(e.to_string().contains("contains itself"), "{e}");
+}
+
+#[test]
+fn host_dispatcher() {
+  use std::sync::{Arc, Mutex};
+
+  use crate::public::{HostDispatcher, IntoValue, SecurityPolicy, Value};
+
+  type Command = fn(Scope<'_>) -> Result<Value<'_>>;
+
+  /// An existing registry of commands, which scripts can call without each
+  /// of them being registered as a native function.
+  struct Registry(HashMap<&'static str, Command>);
+
+  impl HostDispatcher for Registry {
+    fn call<'cx>(&self, name: &str, scope: Scope<'cx>) -> Result<Value<'cx>> {
+      match self.0.get(name) {
+        Some(command) => command(scope),
+        None => fail!("unknown command `{name}`"),
+      }
+    }
+  }
+
+  #[derive(Default)]
+  struct Audit(Arc<Mutex<Vec<String>>>);
+
+  impl SecurityPolicy for Audit {
+    fn check_native_call(&self, name: &str) -> Result<()> {
+      self.0.lock().unwrap().push(name.to_string());
+      Ok(())
+    }
+  }
+
+  let mut commands = HashMap::<&'static str, Command>::new();
+  commands.insert("sum", |scope| {
+    let mut sum = 0;
+    for n in 0..scope.num_args() {
+      sum += scope.param::<i32>(n)?;
+    }
+    sum.into_value(scope.global())
+  });
+  commands.insert("greet", |scope| {
+    let name = scope.param::<String>(0)?;
+    format!("hello, {name}").into_value(scope.global())
+  });
+
+  let audit = Audit::default();
+  let calls = audit.0.clone();
+  let mut hebi = crate::public::Hebi::builder()
+    .host_dispatcher(Registry(commands))
+    .security_policy(audit)
+    .finish();
+
+  let value = hebi
+    .eval(r#"to_str(host.call("sum")) + " " + to_str(host.call("sum", 1, 2, 3))"#)
+    .unwrap();
+  assert_eq!(value.to_string(), "0 6");
+  let value = hebi
+    .eval("call := host.call\ncall(\"greet\", \"world\")")
+    .unwrap();
+  assert_eq!(value.to_string(), "hello, world");
+  assert_eq!(*calls.lock().unwrap(), ["sum", "sum", "greet"]);
+
+  let e = hebi.eval(r#"host.call("nope")"#).unwrap_err();
+  assert!(e.to_string().contains("unknown command `nope`"), "{e}");
+  let e = hebi.eval(r#"host.call("greet", 1)"#).unwrap_err();
+  assert!(e.to_string().contains("value is not a string"), "{e}");
+  let e = hebi.eval("host.call(1)").unwrap_err();
+  assert!(e.to_string().contains("must be a string"), "{e}");
+
+  let mut hebi = crate::public::Hebi::new();
+  let e = hebi.eval(r#"host.call("sum")"#).unwrap_err();
+  assert!(e.to_string().contains("the host has no dispatcher"), "{e}");
+}
+
+#[test]
+fn channel_host_handle() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use std::task::{Context, Poll, Wake, Waker};
+
+  use crate::public::{Channel, Frozen};
+
+  #[derive(Default)]
+  struct CountWakes(AtomicUsize);
+
+  impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  let wakes = Arc::new(CountWakes::default());
+  let waker = Waker::from(wakes.clone());
+  let mut cx = Context::from_waker(&waker);
+
+  let mut hebi = crate::public::Hebi::new();
+  let events = Channel::unbounded();
+  let results = Channel::bounded(1);
+  let global = hebi.global();
+  global.set(global.new_string("events"), global.new_channel(&events));
+  global.set(global.new_string("results"), global.new_channel(&results));
+
+  hebi
+    .start_eval(indoc::indoc! {r#"
+      loop:
+        event := events.recv()
+        if event == none: break
+        results.send(event[0] + event[1])
+      "done"
+    "#})
+    .unwrap();
+
+  // the script waits for events without blocking the host
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  events
+    .try_send(vec![Frozen::from(1), Frozen::from(2)])
+    .unwrap();
+  events
+    .try_send(vec![Frozen::from(3), Frozen::from(4)])
+    .unwrap();
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+
+  // `results` only has room for one value, so the script waits until the host
+  // receives it
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  assert_eq!(results.len(), 1);
+  assert_eq!(results.try_recv().unwrap().as_int(), Some(3));
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 2);
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  assert_eq!(results.try_recv().unwrap().as_int(), Some(7));
+
+  events.close();
+  match hebi.poll_eval(&mut cx) {
+    Poll::Ready(Ok(value)) => assert_eq!(value.to_string(), "done"),
+    _ => panic!("expected the evaluation to finish"),
+  }
+
+  let e = events.try_send(1).unwrap_err();
+  assert!(e.to_string().contains("closed channel"), "{e}");
+  hebi.eval("ch := channel(2)").unwrap();
+  let global = hebi.global();
+  let channel = global.get("ch").unwrap();
+  let channel: Channel = crate::public::FromValue::from_value(channel, global).unwrap();
+  channel.try_send("a").unwrap();
+  channel.try_send("b").unwrap();
+  let e = channel.try_send("c").unwrap_err();
+  assert!(e.to_string().contains("full channel"), "{e}");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_module() {
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let value = hebi
+    .eval(
+      r#"
+import json
+
+v := json.parse("{\"a\": [1, 2.5, \"x\"], \"b\": null}")
+v["a"].push(true)
+[json.stringify(v), json.stringify([1], true), json.stringify([1], pretty=true)]
+"#,
+    )
+    .unwrap();
+  assert_eq!(
+    serde_json::to_string(&value).unwrap(),
+    r#"["{\"a\":[1,2.5,\"x\",true],\"b\":null}","[\n  1\n]","[\n  1\n]"]"#
+  );
+
+  let e = hebi.eval("import json\njson.parse(\"{\")").unwrap_err();
+  assert!(e.to_string().contains("failed to parse json"), "{e}");
+
+  let e = hebi
+    .eval("import json\nl := []\nl.push(l)\njson.stringify(l)")
+    .unwrap_err();
+  assert!(e.to_string().contains("contains itself"), "{e}");
+  let e = hebi
+    .eval("import json\nt := {}\nt[\"self\"] = [t]\njson.stringify(t)")
+    .unwrap_err();
+  assert!(e.to_string().contains("contains itself"), "{e}");
+  // the same list twice is not a cycle
+  let value = hebi
+    .eval("import json\nl := [1]\njson.stringify([l, l])")
+    .unwrap();
+  assert_eq!(value.to_string(), "[[1],[1]]");
+}
+
+#[test]
+fn is_native_class_and_protocol() {
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  hebi.global().register_protocol("Random", &["float", "int"]);
+  let mut eval = |src: &str| hebi.eval(src).unwrap().to_string();
+
+  let prelude = "import math\nrng := math.Rng(1)\n";
+  assert_eq!(eval(&format!("{prelude}rng is math.Rng")), "true");
+  assert_eq!(eval(&format!("{prelude}rng is Random")), "true");
+  assert_eq!(eval(&format!("{prelude}[] is math.Rng")), "false");
+  assert_eq!(eval("{} is Random"), "false");
+}
+
+#[test]
+fn native_class_finalizer() {
+  use std::sync::{Arc, Mutex};
+
+  struct Socket(String);
+
+  let closed = Arc::new(Mutex::new(Vec::<String>::new()));
+  let module = crate::public::NativeModule::builder("net")
+    .class::<Socket>("Socket", {
+      let closed = closed.clone();
+      move |class| {
+        let closed = closed.clone();
+        class
+          .init(|scope| Ok(Socket(scope.param::<String>(0)?)))
+          .finalizer(move |_, this| closed.lock().unwrap().push(this.0.clone()))
+          .finish()
+      }
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  hebi
+    .eval(
+      "from net import Socket
+Socket(\"a\")
+kept := Socket(\"b\")",
+    )
+    .unwrap();
+  // `a` was dropped by the script, `b` is still referenced by a global
+  assert_eq!(*closed.lock().unwrap(), ["a"]);
+  drop(hebi);
+  assert_eq!(*closed.lock().unwrap(), ["a", "b"]);
+}
+
+#[test]
+fn typed_function_handles() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval(
+      r#"
+fn add(a, b):
+  return a + b
+fn greet(name):
+  return "hello, " + name
+value := 10
+"#,
+    )
+    .unwrap();
+
+  let mut add = hebi.get_function::<(i32, i32), i32>("add").unwrap();
+  for i in 0..10 {
+    assert_eq!(add.call((i, 1)).unwrap(), i + 1);
+  }
+  drop(add);
+
+  let mut greet = hebi.get_function::<(String,), String>("greet").unwrap();
+  assert_eq!(greet.call(("world".into(),)).unwrap(), "hello, world");
+  drop(greet);
+
+  let error = |result: crate::Result<()>| match result {
+    Ok(_) => panic!("expected an error"),
+    Err(e) => e.to_string(),
+  };
+  let e = error(hebi.get_function::<(i32,), i32>("add").map(|_| ()));
+  assert!(e.contains("expected 2 args, got 1"), "{e}");
+  let e = error(hebi.get_function::<(), ()>("missing").map(|_| ()));
+  assert!(e.contains("function `missing` is not defined"), "{e}");
+  let e = error(hebi.get_function::<(), ()>("value").map(|_| ()));
+  assert!(e.contains("`value` is not a function"), "{e}");
+}
+
+#[test]
+fn snapshot_and_restore_globals() {
+  let mut hebi = crate::public::Hebi::builder()
+    .module_loader(TestModuleLoader::new(&[(
+      "counter",
+      indoc::indoc! {"
+        count := 0
+        fn bump():
+          count += 1
+          return count
+      "},
+    )]))
+    .finish();
+  let eval = |hebi: &mut crate::public::Hebi, src: &str| hebi.eval(src).unwrap().to_string();
+
+  eval(
+    &mut hebi,
+    indoc::indoc! {"
+      from counter import bump
+      class Player:
+        hp = 10
+      player := Player()
+      items := [1, 2]
+      alias := items
+      bump()
+    "},
+  );
+  let snapshot = hebi.snapshot_globals();
+
+  eval(
+    &mut hebi,
+    indoc::indoc! {"
+      from counter import bump
+      player.hp = 0
+      items.push(3)
+      later := true
+      bump()
+    "},
+  );
+  hebi.restore_globals(&snapshot).unwrap();
+  assert_eq!(eval(&mut hebi, "player.hp"), "10");
+  assert_eq!(eval(&mut hebi, "items.len()"), "2");
+  assert!(hebi.global().get("later").is_none());
+  // `alias` still refers to the same list as `items`
+  eval(&mut hebi, "alias.push(4)");
+  assert_eq!(eval(&mut hebi, "items.len()"), "3");
+  assert_eq!(eval(&mut hebi, "from counter import bump\nbump()"), "2");
+
+  // restoring does not consume the snapshot
+  hebi.restore_globals(&snapshot).unwrap();
+  assert_eq!(eval(&mut hebi, "items.len()"), "2");
+  assert_eq!(eval(&mut hebi, "from counter import bump\nbump()"), "2");
+
+  let other = crate::public::Hebi::new().snapshot_globals();
+  let e = hebi.restore_globals(&other).unwrap_err().to_string();
+  assert!(e.contains("different VM"), "{e}");
+}
+
+#[test]
+fn std_value_conversions() {
+  use std::collections::{BTreeMap, HashMap};
+  use std::time::Duration;
+
+  use crate::public::{FromValue, IntoValue};
+
+  let module = crate::public::NativeModule::builder("stats")
+    .function("summary", |scope| {
+      let numbers = scope.param::<Vec<i64>>(0)?;
+      let mut summary = BTreeMap::new();
+      summary.insert("count".to_string(), numbers.len() as i64);
+      summary.insert("sum".to_string(), numbers.iter().sum());
+      Result::Ok(summary)
+    })
+    .function("min_max", |scope| {
+      let numbers = scope.param::<Vec<f64>>(0)?;
+      let min = numbers.iter().copied().fold(f64::INFINITY, f64::min);
+      let max = numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+      Result::Ok((min, max))
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  let value = hebi
+    .eval(
+      r#"
+import stats
+s := stats.summary([1, 2, 3])
+m := stats.min_max((2.0, 0.5, 1.0))
+s["count"] == 3 && s["sum"] == 6 && m[0] == 0.5 && m[1] == 2.0
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_bool(), Some(true));
+  let e = hebi
+    .eval("import stats\nstats.summary([1, \"a\"])")
+    .unwrap_err();
+  assert!(e.to_string().contains("not an int"), "{e}");
+
+  let global = hebi.global();
+  let value = vec![(1, "a".to_string()), (2, "b".to_string())]
+    .into_value(global.clone())
+    .unwrap();
+  let pairs = Vec::<(i32, String)>::from_value(value, global.clone()).unwrap();
+  assert_eq!(pairs, [(1, "a".to_string()), (2, "b".to_string())]);
+
+  let map = HashMap::from([("x".to_string(), vec![1, 2]), ("y".to_string(), vec![])]);
+  let value = map.clone().into_value(global.clone()).unwrap();
+  assert_eq!(
+    HashMap::<String, Vec<i32>>::from_value(value, global.clone()).unwrap(),
+    map
+  );
+
+  let value = (1, 2.5, true, "s".to_string(), (), None::<i32>, 3, 4)
+    .into_value(global.clone())
+    .unwrap();
+  let value =
+    <(i32, f64, bool, String, (), Option<i32>, i64, i64)>::from_value(value, global.clone())
+      .unwrap();
+  assert_eq!(value, (1, 2.5, true, "s".to_string(), (), None, 3, 4));
+  let value = (1, 2).into_value(global.clone()).unwrap();
+  let e = <(i32, i32, i32)>::from_value(value, global.clone()).unwrap_err();
+  assert!(e.to_string().contains("expected a tuple of 3 items"), "{e}");
+
+  let value = Duration::from_millis(1500)
+    .into_value(global.clone())
+    .unwrap();
+  assert_eq!(
+    Duration::from_value(value, global.clone()).unwrap(),
+    Duration::from_millis(1500)
+  );
+  let value = 0.25.into_value(global.clone()).unwrap();
+  assert_eq!(
+    Duration::from_value(value, global.clone()).unwrap(),
+    Duration::from_millis(250)
+  );
+
+  #[cfg(feature = "chrono")]
+  {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    let time = DateTime::from_timestamp(1_700_000_000, 500_000_000).unwrap();
+    let value = time.into_value(global.clone()).unwrap();
+    assert_eq!(value.as_float(), Some(1_700_000_000.5));
+    assert_eq!(
+      DateTime::<Utc>::from_value(value, global.clone()).unwrap(),
+      time
+    );
+    let value = global
+      .new_string("2023-11-14T22:13:20.5Z")
+      .into_value(global.clone())
+      .unwrap();
+    assert_eq!(
+      DateTime::<Utc>::from_value(value, global.clone()).unwrap(),
+      time
+    );
+
+    let value = TimeDelta::seconds(2).into_value(global.clone()).unwrap();
+    assert_eq!(
+      TimeDelta::from_value(value, global.clone()).unwrap(),
+      TimeDelta::seconds(2)
+    );
+    let e = TimeDelta::seconds(-2)
+      .into_value(global.clone())
+      .unwrap_err();
+    assert!(e.to_string().contains("negative"), "{e}");
+  }
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derive_value_conversions() {
+  use crate::public::{FromHebi, FromValue, IntoValue, ToHebi};
+
+  #[derive(Debug, PartialEq, ToHebi, FromHebi)]
+  struct Id(i32);
+
+  #[derive(Debug, PartialEq, ToHebi, FromHebi)]
+  enum Shape {
+    Circle { radius: f64 },
+    Square(f64),
+    Empty,
+  }
+
+  #[derive(Debug, PartialEq, ToHebi, FromHebi)]
+  struct Pair<T> {
+    first: T,
+    second: T,
+  }
+
+  #[derive(Debug, PartialEq, ToHebi, FromHebi)]
+  struct Config {
+    id: Id,
+    name: String,
+    debug: bool,
+    scale: Option<f64>,
+    shapes: Pair<Shape>,
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  {
+    let global = hebi.global();
+    let config = Config {
+      id: Id(1),
+      name: "test".into(),
+      debug: false,
+      scale: None,
+      shapes: Pair {
+        first: Shape::Circle { radius: 1.0 },
+        second: Shape::Empty,
+      },
+    };
+    let value = config.into_value(global.clone()).unwrap();
+    global.set(global.new_string("config"), value);
+  }
+  hebi
+    .eval(
+      r#"
+class Point:
+  x = 1
+  y = 2
+point := Point()
+config["id"] += 1
+config["debug"] = true
+config["scale"] = 0.5
+shapes := config["shapes"]
+shapes["second"] = { type: "Square", value: shapes["first"]["radius"] * 2.0 }
+"#,
+    )
+    .unwrap();
+
+  let global = hebi.global();
+  let config = Config::from_value(global.get("config").unwrap(), global.clone()).unwrap();
+  assert_eq!(
+    config,
+    Config {
+      id: Id(2),
+      name: "test".into(),
+      debug: true,
+      scale: Some(0.5),
+      shapes: Pair {
+        first: Shape::Circle { radius: 1.0 },
+        second: Shape::Square(2.0),
+      },
+    }
+  );
+  #[derive(Debug, PartialEq, FromHebi)]
+  struct Point {
+    x: i32,
+    y: i32,
+  }
+  let point = Point::from_value(global.get("point").unwrap(), global.clone()).unwrap();
+  assert_eq!(point, Point { x: 1, y: 2 });
+
+  let error = |value: crate::public::Value<'_>| match Shape::from_value(value, global.clone()) {
+    Ok(shape) => panic!("expected an error, got {shape:?}"),
+    Err(e) => e.to_string(),
+  };
+  let e = error(global.get("point").unwrap());
+  assert!(e.contains("invalid field `type` of `Shape`"), "{e}");
+  let e = Pair::<i32>::from_value(global.get("point").unwrap(), global.clone())
+    .unwrap_err()
+    .to_string();
+  assert!(e.contains("invalid field `first` of `Pair`"), "{e}");
+  let unknown = global.new_table(1);
+  unknown.insert(
+    global.new_string("type"),
+    global
+      .new_string("Hexagon")
+      .into_value(global.clone())
+      .unwrap(),
+  );
+  let e = error(unknown.into_value(global.clone()).unwrap());
+  assert!(e.contains("`Shape` has no variant `Hexagon`"), "{e}");
+  let e = error(1.into_value(global.clone()).unwrap());
+  assert!(e.contains("not a table or a class instance"), "{e}");
+}
+
+#[test]
+fn native_class_inheritance() {
+  use std::cell::Cell;
+
+  struct Vec2 {
+    x: Cell<f64>,
+    y: f64,
+  }
+
+  struct Square(f64);
+
+  let module = crate::public::NativeModule::builder("geometry")
+    .class::<Vec2>("Vec2", |class| {
+      class
+        .init(|scope| {
+          Ok(Vec2 {
+            x: Cell::new(scope.param(0)?),
+            y: scope.param(1)?,
+          })
+        })
+        .field_mut(
+          "x",
+          |_, this| this.x.get(),
+          |_, this, value| {
+            this.x.set(value);
+            Ok(())
+          },
+        )
+        .method("len", |_, this| this.x.get().hypot(this.y))
+        .finish()
+    })
+    .class::<Square>("Square", |class| {
+      class
+        .init(|scope| Ok(Square(scope.param(0)?)))
+        .extends("Shape")
+        .method("area", |_, this| this.0 * this.0)
+        .finish()
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  let mut eval = |src: &str| match hebi.eval(src) {
+    Ok(value) => value.to_string(),
+    Err(e) => e.to_string(),
+  };
+
+  // script classes derived from a native class
+  eval(
+    r#"
+from geometry import Vec2
+class Point(Vec2):
+  label = none
+  init(self, x, y, label):
+    super(x, y)
+    self.label = label
+  fn describe(self):
+    return self.label + " " + to_str(self.len())
+class Plain(Vec2): pass
+class Labeled(Point):
+  fn len(self):
+    return super.len() * 10.0
+point := Point(3.0, 4.0, "a")
+"#,
+  );
+  assert_eq!(eval("point.describe()"), "a 5.0");
+  assert_eq!(eval("point.x = 0.0\npoint.len()"), "4.0");
+  assert_eq!(
+    eval("from geometry import Vec2\npoint is Point && point is Vec2 && Plain(1.0, 0.0) is Vec2"),
+    "true"
+  );
+  assert_eq!(eval("Plain(0.0, 2.0).len()"), "2.0");
+  assert_eq!(eval("Labeled(3.0, 4.0, \"b\").len()"), "50.0");
+
+  // native classes derived from a script class
+  eval(
+    r#"
+from geometry import Square
+class Shape:
+  name = "shape"
+  fn describe(self):
+    return self.name + " with area " + to_str(self.area())
+square := Square(2.0)
+"#,
+  );
+  assert_eq!(eval("square.describe()"), "shape with area 4.0");
+  assert_eq!(
+    eval("square.name = \"square\"\nsquare.describe()"),
+    "square with area 4.0"
+  );
+  assert_eq!(
+    eval("from geometry import Square\nsquare is Shape && square is Square"),
+    "true"
+  );
+}
+
+#[test]
+fn native_iterator() {
+  use std::cell::Cell;
+
+  use crate::public::{FromIter, NativeIterator};
+
+  struct Countdown(Cell<i32>);
+
+  impl NativeIterator for Countdown {
+    type Item = i32;
+
+    fn next(&self) -> Option<i32> {
+      let n = self.0.get();
+      self.0.set(n - 1);
+      (n > 0).then_some(n)
+    }
+
+    fn done(&self) -> bool {
+      self.0.get() <= 0
+    }
+  }
+
+  let module = crate::public::NativeModule::builder("iters")
+    .class::<Countdown>("Countdown", |class| {
+      class
+        .init(|scope| Ok(Countdown(Cell::new(scope.param(0)?))))
+        .iterator()
+        .finish()
+    })
+    .function("chars", |scope| {
+      let text = scope.param::<String>(0)?;
+      let chars = text.chars().map(String::from).collect::<Vec<_>>();
+      Result::Ok(FromIter::new(chars))
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  let global = hebi.global();
+  let value = global.new_iterator(FromIter::new(1..=3));
+  global.set(global.new_string("numbers"), value);
+  let mut eval = |src: &str| match hebi.eval(src) {
+    Ok(value) => value.to_string(),
+    Err(e) => e.to_string(),
+  };
+
+  assert_eq!(
+    eval("import iters\nout := 0\nfor n in iters.Countdown(3):\n  out = out * 10 + n\nout"),
+    "321"
+  );
+  assert_eq!(
+    eval("import iters\nto_str(iters.Countdown(0).done())"),
+    "true"
+  );
+  assert_eq!(
+    eval("import iters\nout := \"\"\nfor c in iters.chars(\"abc\"):\n  out += c\nout"),
+    "abc"
+  );
+  // the adapters only rely on the protocol
+  assert_eq!(
+    eval(
+      "import iters\nfn add(a, b):\n  return a + b\nfn double(n):\n  return n * 2\nreduce(add, map(double, iters.Countdown(4)))"
+    ),
+    "20"
+  );
+  // `next` past the end returns `none`, and `done` doesn't skip items
+  assert_eq!(
+    eval("import iters\nit := iters.chars(\"ab\")\nit.done()\nit.done()\nit.next() + it.next() + to_str(it.next())"),
+    "abnone"
+  );
+  assert_eq!(
+    eval("total := 0\nfor n in numbers:\n  total += n\ntotal"),
+    "6"
+  );
+}
+
+#[test]
+fn builtin_type_extensions() {
+  use crate::public::{List, Str, Table};
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.extend_builtin("shout", |_, this: Str| this.as_str().to_uppercase());
+  hebi.extend_builtin("repeat", |scope, this: Str| {
+    let n = scope.param::<i32>(0)?;
+    Ok::<_, crate::Error>(this.as_str().repeat(n as usize))
+  });
+  // builtin methods are not replaced
+  hebi.extend_builtin("len", |_, _: Str| -1);
+  hebi.extend_builtin("sum", |_, this: List| {
+    this.iter().filter_map(|v| v.as_int()).sum::<i32>()
+  });
+  hebi.extend_builtin("size", |_, this: Table| this.len() as i32);
+  hebi.extend_builtin("double", |_, this: i32| this * 2);
+  hebi.extend_builtin("half", |_, this: f64| this / 2.0);
+
+  let mut eval = |code: &str| match hebi.eval(code) {
+    Ok(value) => value.to_string(),
+    Err(e) => e.to_string(),
+  };
+  assert_eq!(eval(r#""hi".shout()"#), "HI");
+  assert_eq!(eval(r#""ab".repeat(3)"#), "ababab");
+  assert_eq!(eval(r#""abc".len()"#), "3");
+  assert_eq!(eval("[1, 2, 3].sum()"), "6");
+  assert_eq!(eval("v := {a: 1, b: 2}\nv.size()"), "2");
+  assert_eq!(eval("v := 21\nv.double()"), "42");
+  assert_eq!(eval("v := 3.0\nv.half()"), "1.5");
+  assert_eq!(eval("v := 1\n?v.half"), "none");
+  assert!(eval("v := 1\nv.half()").contains("`1` has no field `half`"));
+  assert!(eval(r#""hi".missing()"#).contains("has no field `missing`"));
+}
+
+#[test]
+fn bound_method_equality() {
+  let prelude = r#"
+class Counter:
+  n = 0
+  fn inc(self):
+    self.n += 1
+  fn dec(self):
+    self.n -= 1
+class Loud(Counter):
+  fn inc(self):
+    return super.inc
+class Button:
+  handlers = none
+  init(self):
+    self.handlers = []
+  fn on(self, f):
+    self.handlers.push(f)
+  fn off(self, f):
+    i := self.handlers.index_of(f)
+    if i != none:
+      self.handlers.remove(i)
+  fn click(self):
+    for f in self.handlers:
+      f()
+a := Counter()
+b := Counter()
+"#;
+  let mut hebi = crate::public::Hebi::new();
+  let mut eval = |code: &str| hebi.eval(&format!("{prelude}{code}")).unwrap().to_string();
+
+  assert_eq!(eval("a.inc == a.inc"), "true");
+  assert_eq!(eval("a.inc != a.inc"), "false");
+  assert_eq!(eval("a.inc == b.inc"), "false");
+  assert_eq!(eval("a.inc == a.dec"), "false");
+  assert_eq!(eval("l := Loud()\nl.inc() == l.inc()"), "true");
+  assert_eq!(eval("l := Loud()\nl.inc() == l.inc"), "false");
+  assert_eq!(eval("v := [1]\nv.len == v.len"), "true");
+  assert_eq!(eval("[1].len == [1].len"), "false");
+  assert_eq!(eval("v := [1]\nv.len == v.push"), "false");
+  assert_eq!(eval("List.len == List.len"), "true");
+  assert_eq!(eval("List.len == Str.len"), "false");
+  let co = "fn gen():\n  yield 1\nco := coroutine(gen)\n";
+  assert_eq!(eval(&format!("{co}co.resume == co.resume")), "true");
+  assert_eq!(eval(&format!("{co}co.resume == co.status")), "false");
+  assert_eq!(eval(&format!("{co}co.resume is List")), "false");
+  assert_eq!(
+    eval(
+      r#"
+button := Button()
+button.on(a.inc)
+button.on(b.inc)
+button.click()
+button.off(a.inc)
+button.click()
+a.n == 1 && b.n == 2 && button.handlers.len() == 1
+"#
+    ),
+    "true"
+  );
+}
+
+#[test]
+fn poll_eval_with_waker() {
+  use std::future::Future;
+  use std::pin::Pin;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::{Arc, Mutex};
+  use std::task::{Context, Poll, Wake, Waker};
+
+  /// Ready once `open` is set, and wakes the last waker it was polled with.
+  #[derive(Clone, Default)]
+  struct Gate(Arc<Mutex<(bool, Option<Waker>)>>);
+
+  impl Gate {
+    fn open(&self) {
+      let mut state = self.0.lock().unwrap();
+      state.0 = true;
+      if let Some(waker) = state.1.take() {
+        waker.wake();
+      }
+    }
+  }
+
+  impl Future for Gate {
+    type Output = i32;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<i32> {
+      let mut state = self.0.lock().unwrap();
+      if state.0 {
+        Poll::Ready(10)
+      } else {
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+
+  #[derive(Default)]
+  struct CountWakes(AtomicUsize);
+
+  impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  let gate = Gate::default();
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("gate")
+      .async_function("wait", {
+        let gate = gate.clone();
+        move |_| gate.clone()
+      })
+      .finish(),
+  );
+
+  let wakes = Arc::new(CountWakes::default());
+  let waker = Waker::from(wakes.clone());
+  let mut cx = Context::from_waker(&waker);
+
+  let e = match hebi.poll_eval(&mut cx) {
+    Poll::Ready(Err(e)) => e.to_string(),
+    _ => panic!("expected an error"),
+  };
+  assert!(e.contains("there is no evaluation in progress"), "{e}");
+
+  hebi
+    .start_eval("from gate import wait\nv := 0\nfor i in 0..3:\n  v += wait()\nv")
+    .unwrap();
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+  let e = hebi.start_eval("1").unwrap_err().to_string();
+  assert!(e.contains("another evaluation is in progress"), "{e}");
+  let e = hebi.eval("1").unwrap_err().to_string();
+  assert!(e.contains("another evaluation is in progress"), "{e}");
+
+  gate.open();
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+  match hebi.poll_eval(&mut cx) {
+    Poll::Ready(Ok(value)) => assert_eq!(value.as_int(), Some(30)),
+    _ => panic!("expected the evaluation to finish"),
+  }
+
+  // the VM may be used normally once the evaluation is done
+  assert_eq!(hebi.eval("1 + 1").unwrap().as_int(), Some(2));
+
+  // an unfinished evaluation is dropped along with the VM
+  let gate = Gate::default();
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("gate")
+      .async_function("wait", move |_| gate.clone())
+      .finish(),
+  );
+  hebi.start_eval("from gate import wait\nwait()").unwrap();
+  assert!(hebi.poll_eval(&mut cx).is_pending());
+  drop(hebi);
+}
+
+#[test]
+fn cancel_evaluation() {
+  use std::time::Duration;
+
+  use crate::public::CancellationToken;
+  use crate::ErrorKind;
+
+  let mut hebi = crate::public::Hebi::new();
+
+  // cancelled from another thread while the script is looping
+  let token = CancellationToken::new();
+  let handle = std::thread::spawn({
+    let token = token.clone();
+    move || {
+      std::thread::sleep(Duration::from_millis(20));
+      token.cancel();
+    }
+  });
+  let e = hebi
+    .eval_with_cancellation("while true: pass", token)
+    .unwrap_err();
+  handle.join().unwrap();
+  assert!(matches!(e.kind(), ErrorKind::Cancelled), "{e}");
+  assert!(e.to_string().contains("evaluation was cancelled"), "{e}");
+
+  // calls are also safepoints, so recursion without loops is stopped
+  let token = CancellationToken::new();
+  token.cancel();
+  let e = hebi
+    .eval_with_cancellation("fn f():\n  return f()\nf()", token)
+    .unwrap_err();
+  assert!(matches!(e.kind(), ErrorKind::Cancelled), "{e}");
+
+  // a timeout
+  let e = hebi
+    .eval_with_cancellation("while true: pass", Duration::from_millis(10))
+    .unwrap_err();
+  assert!(e.to_string().contains("evaluation timed out"), "{e}");
+
+  // a script which finishes in time is not affected
+  let value = hebi
+    .eval_with_cancellation(
+      "v := 0\nfor i in 0..1000:\n  v += i\nv",
+      Duration::from_secs(60),
+    )
+    .unwrap();
+  assert_eq!(value.as_int(), Some(499500));
+
+  // the VM may still be used afterwards, without a token
+  hebi
+    .eval("fn spin(n):\n  v := 0\n  while v < n:\n    v += 1\n  return v")
+    .unwrap();
+  assert_eq!(hebi.eval("spin(10000)").unwrap().as_int(), Some(10000));
+
+  let mut spin = hebi.get_function::<(i32,), i32>("spin").unwrap();
+  assert_eq!(
+    spin
+      .call_with_cancellation((100,), CancellationToken::new())
+      .unwrap(),
+    100
+  );
+  let e = spin
+    .call_with_cancellation((i32::MAX,), Duration::from_millis(10))
+    .unwrap_err();
+  assert!(matches!(e.kind(), ErrorKind::Cancelled), "{e}");
+  assert_eq!(spin.call((5,)).unwrap(), 5);
+}
+
+#[test]
+fn safepoint_hook() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+
+  use crate::public::SafepointHook;
+
+  struct Counter(Arc<AtomicUsize>);
+
+  impl SafepointHook for Counter {
+    fn safepoint(&mut self) -> crate::Result<()> {
+      if self.0.fetch_add(1, Ordering::Relaxed) >= 100 {
+        fail!("out of fuel");
+      }
+      Ok(())
+    }
+  }
+
+  let count = Arc::new(AtomicUsize::new(0));
+  let mut hebi = crate::public::Hebi::builder()
+    .safepoint_hook(Counter(count.clone()))
+    .finish();
+
+  // one safepoint per loop iteration
+  hebi.eval("i := 0\nwhile i < 10:\n  i += 1").unwrap();
+  assert_eq!(count.load(Ordering::Relaxed), 10);
+
+  // one per call
+  count.store(0, Ordering::Relaxed);
+  hebi
+    .eval("fn f(n):\n  if n > 0:\n    f(n - 1)\nf(4)")
+    .unwrap();
+  assert_eq!(count.load(Ordering::Relaxed), 5);
+
+  // an error from the hook stops the script
+  count.store(0, Ordering::Relaxed);
+  let e = hebi.eval("while true: pass").unwrap_err();
+  assert!(e.to_string().contains("out of fuel"), "{e}");
+  let e = hebi.eval("fn f():\n  return f()\nf()").unwrap_err();
+  assert!(e.to_string().contains("out of fuel"), "{e}");
+}
+
+#[test]
+fn argv_and_env() {
+  use crate::public::SecurityPolicy;
+
+  struct Policy;
+
+  impl SecurityPolicy for Policy {
+    fn check_env(&self, name: &str) -> Result<()> {
+      if name == "SECRET" {
+        fail!("cannot read `{name}`");
+      }
+      Ok(())
+    }
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .args(["a", "b c"])
+    .security_policy(Policy)
+    .allow_os(true)
+    .finish();
+  let value = hebi.eval(r#"argv.len() == 2 && argv[1] == "b c""#).unwrap();
+  assert_eq!(value.as_bool(), Some(true));
+  // cargo sets this when running tests
+  let value = hebi.eval(r#"env("CARGO_PKG_NAME")"#).unwrap();
+  assert_eq!(value.to_string(), "hebi");
+  let value = hebi.eval(r#"env("__HEBI_UNSET_VARIABLE")"#).unwrap();
+  assert!(value.is_none());
+  let e = hebi.eval(r#"env("SECRET")"#).unwrap_err();
+  assert!(e.to_string().contains("cannot read"), "{e}");
+
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi.eval("argv.len()").unwrap();
+  assert_eq!(value.as_int(), Some(0));
+  // reading the environment must be allowed by the host
+  let e = hebi.eval(r#"env("CARGO_PKG_NAME")"#).unwrap_err();
+  assert!(e.to_string().contains("env"), "{e}");
+}
+
+#[test]
+fn bump_arena_allocator() {
+  use std::alloc::Layout;
+  use std::ptr::NonNull;
+  use std::sync::{Arc, Mutex};
+
+  use crate::public::Allocator;
+
+  /// Serves allocations from a fixed buffer, which is only reused once the
+  /// arena is reset.
+  struct Arena {
+    memory: Box<[u128]>,
+    used: Mutex<usize>,
+  }
+
+  impl Arena {
+    fn range(&self) -> std::ops::Range<usize> {
+      let start = self.memory.as_ptr() as usize;
+      start..start + std::mem::size_of_val(&*self.memory)
+    }
+
+    fn used(&self) -> usize {
+      *self.used.lock().unwrap()
+    }
+
+    fn reset(&self) {
+      *self.used.lock().unwrap() = 0;
+    }
+  }
+
+  struct ArenaAllocator(Arc<Arena>);
+
+  unsafe impl Allocator for ArenaAllocator {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+      let range = self.0.range();
+      let mut used = self.0.used.lock().unwrap();
+      let start = (range.start + *used).next_multiple_of(layout.align());
+      if start + layout.size() > range.end {
+        return None;
+      }
+      *used = start + layout.size() - range.start;
+      NonNull::new(start as *mut u8)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+      assert!(self.0.range().contains(&(ptr.as_ptr() as usize)));
+    }
+  }
+
+  let arena = Arc::new(Arena {
+    memory: vec![0u128; 1 << 16].into_boxed_slice(),
+    used: Mutex::new(0),
+  });
+  for _ in 0..2 {
+    let mut hebi = crate::public::Hebi::builder()
+      .allocator(ArenaAllocator(arena.clone()))
+      .finish();
+    let value = hebi.eval("v := [1, 2]\nv.push(3)\nv[0] + v[2]").unwrap();
+    assert_eq!(value.as_int(), Some(4));
+    drop(hebi);
+
+    assert!(arena.used() > 0);
+    // the VM and everything it returned have been dropped
+    arena.reset();
+  }
+}
+
+#[test]
+fn instance_shapes() {
+  use crate::internal::object::class::ClassInstance;
+  use crate::public::Unbind;
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval(indoc::indoc! {r#"
+      class T:
+        a = 1
+        b = 2
+      class U:
+        b = 3
+      x := T()
+      y := T()
+      fn get_b(t):
+        return t.b
+    "#})
+    .unwrap();
+  let instance = |name: &str| {
+    let value = hebi.global().get(name).unwrap().unbind();
+    value.to_object::<ClassInstance>().unwrap()
+  };
+  let (x, y) = (instance("x"), instance("y"));
+  assert!(x.shape().is_some());
+  assert_eq!(x.shape(), y.shape());
+
+  // the cache in `get_b` is filled for the shape of `T`, and then misses on `U`
+  let value = hebi.eval("get_b(x) + get_b(y) + get_b(U())").unwrap();
+  assert_eq!(value.as_int(), Some(7));
+
+  let global = hebi.global().inner;
+  y.insert_field(global.intern("a"), Value::int(10));
+  assert_eq!(x.shape(), y.shape());
+  y.insert_field(global.intern("c"), Value::int(100));
+  assert_eq!(y.shape(), None);
+
+  let value = hebi
+    .eval("y.b = 5\nget_b(x) + get_b(y) + y.a + y.c")
+    .unwrap();
+  assert_eq!(value.as_int(), Some(117));
+}
+
+#[test]
+fn math_module() {
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let eval = |hebi: &mut crate::public::Hebi, src: &str| hebi.eval(src).unwrap().to_string();
+
+  assert_eq!(eval(&mut hebi, "import math\nmath.sqrt(16)"), "4.0");
+  assert_eq!(eval(&mut hebi, "import math\nmath.floor(2.7)"), "2");
+  assert_eq!(eval(&mut hebi, "import math\nmath.ceil(-2.7)"), "-2");
+  assert_eq!(eval(&mut hebi, "import math\nmath.round(2.5)"), "3");
+  assert_eq!(eval(&mut hebi, "import math\nmath.abs(-3)"), "3");
+  assert_eq!(eval(&mut hebi, "import math\nmath.min(3, 1.5, 2)"), "1.5");
+  assert_eq!(eval(&mut hebi, "import math\nmath.max(3, 1.5, 2)"), "3");
+  assert_eq!(eval(&mut hebi, "import math\nmath.clamp(15, 0, 10)"), "10");
+  assert_eq!(eval(&mut hebi, "import math\nmath.log(1)"), "0.0");
+  assert_eq!(eval(&mut hebi, "import math\nmath.cos(0)"), "1.0");
+  assert_eq!(
+    eval(&mut hebi, "from math import pi\npi"),
+    std::f64::consts::PI.to_string()
+  );
+
+  // the same seed produces the same sequence
+  let source = r#"
+import math
+a := math.Rng(42)
+b := math.Rng(42)
+v := a.int(0, 1000)
+v == b.int(0, 1000) && v >= 0 && v < 1000 && a.float() == b.float()
+"#;
+  assert_eq!(eval(&mut hebi, source), "true");
+
+  let e = hebi.eval("import math\nmath.sqrt(\"a\")").unwrap_err();
+  assert!(e.to_string().contains("expected a number"), "{e}");
+  let e = hebi.eval("import math\nmath.Rng(1).int(5, 5)").unwrap_err();
+  assert!(e.to_string().contains("empty range"), "{e}");
+}
+
+#[tokio::test]
+async fn time_module() {
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let value = hebi
+    .eval_async(
+      r#"
+import time
+
+start := time.now()
+time.sleep(time.millis(20))
+elapsed := time.now() - start
+elapsed >= time.millis(20) && time.secs(1) + time.millis(500) == time.secs(1.5) && time.unix() > 0
+"#,
+    )
+    .await
+    .unwrap();
+  assert_eq!(value.as_bool(), Some(true));
+
+  let value = hebi
+    .eval_async("import time\n(time.secs(2) - time.millis(500)).millis()")
+    .await
+    .unwrap();
+  assert_eq!(value.as_float(), Some(1500.0));
+
+  let value = hebi
+    .eval_async(
+      r#"
+import time
+
+d := time.secs(2)
+d * 3 == time.secs(6) && 2.5 * d == time.secs(5) && d / 4 == time.millis(500) && d / 0.5 == time.secs(4) && d.mul(2) == d * 2
+"#,
+    )
+    .await
+    .unwrap();
+  assert_eq!(value.as_bool(), Some(true));
+
+  for (code, error) in [
+    ("time.secs(1) / 0", "divide duration by zero"),
+    ("time.secs(1) * -1", "not a valid duration"),
+    ("1 / time.secs(1)", "same type"),
+  ] {
+    let e = hebi
+      .eval_async(&format!("import time\n{code}"))
+      .await
+      .unwrap_err();
+    assert!(e.to_string().contains(error), "{code}: {e}");
+  }
+
+  let e = hebi
+    .eval_async("import time\ntime.millis(1) - time.secs(1)")
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("negative duration"), "{e}");
+  let e = hebi
+    .eval_async("import time\ntime.sleep(-1)")
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("not a valid duration"), "{e}");
+}
+
+#[test]
+fn fs_module() {
+  use crate::public::{FsAccess, IntoValue, SecurityPolicy};
+
+  struct ReadOnly;
+
+  impl SecurityPolicy for ReadOnly {
+    fn check_fs(&self, path: &str, access: FsAccess) -> Result<()> {
+      if access == FsAccess::Write {
+        fail!("cannot write `{path}`");
+      }
+      Ok(())
+    }
+  }
+
+  let dir = std::env::temp_dir().join(format!("hebi-fs-module-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let dir_str = dir.to_string_lossy().into_owned();
+
+  let mut hebi = crate::public::Hebi::builder().allow_fs(true).finish();
+  let global = hebi.global();
+  let value = dir_str.clone().into_value(global.clone()).unwrap();
+  global.set(global.new_string("dir"), value);
+  let value = hebi
+    .eval(
+      r#"
+import fs
+
+path := fs.join(dir, "b.txt")
+fs.write(path, "hello")
+fs.write(fs.join(dir, "a.txt"), "")
+names := fs.list_dir(dir)
+exists := fs.exists(path) && !fs.exists(fs.join(dir, "c.txt"))
+listed := names.len() == 2 && names[0] == "a.txt" && names[1] == "b.txt"
+split := fs.split(path)[0] == dir && fs.split(path)[1] == "b.txt" && fs.split("b.txt")[0] == ""
+exists && listed && split && fs.read_to_string(path) == "hello"
+"#,
+    )
+    .unwrap();
+  assert_eq!(value.as_bool(), Some(true));
+  let e = hebi
+    .eval("import fs\nfs.read_to_string(fs.join(dir, \"c.txt\"))")
+    .unwrap_err();
+  assert!(e.to_string().contains("cannot read"), "{e}");
+
+  let mut hebi = crate::public::Hebi::builder()
+    .allow_fs(true)
+    .security_policy(ReadOnly)
+    .finish();
+  let global = hebi.global();
+  let value = dir_str.clone().into_value(global.clone()).unwrap();
+  global.set(global.new_string("dir"), value);
+  let value = hebi
+    .eval("import fs\nfs.read_to_string(fs.join(dir, \"b.txt\"))")
+    .unwrap();
+  assert_eq!(value.to_string(), "hello");
+  let e = hebi
+    .eval("import fs\nfs.write(fs.join(dir, \"b.txt\"), \"bye\")")
+    .unwrap_err();
+  assert!(e.to_string().contains("cannot write"), "{e}");
+
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let e = hebi.eval("import fs").unwrap_err();
+  assert!(e.to_string().contains("fs"), "{e}");
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn os_module() {
+  use crate::public::SecurityPolicy;
+  use crate::ErrorKind;
+
+  struct NoProcesses;
+
+  impl SecurityPolicy for NoProcesses {
+    fn check_process(&self, program: &str, _: &[String]) -> Result<()> {
+      fail!("cannot run `{program}`")
+    }
+  }
+
+  let mut hebi = crate::public::Hebi::builder().allow_os(true).finish();
+  // cargo sets this when running tests
+  let value = hebi.eval("import os\nos.env(\"CARGO_PKG_NAME\")").unwrap();
+  assert_eq!(value.to_string(), "hebi");
+  let value = hebi.eval("import os\nos.cwd()").unwrap();
+  let cwd = std::env::current_dir().unwrap();
+  assert_eq!(value.to_string(), cwd.to_string_lossy());
+  let e = hebi.eval("import os\nos.exit(3)").unwrap_err();
+  assert!(matches!(e.kind(), ErrorKind::Exit(3)), "{e}");
+  let e = hebi.eval("import os\nos.exit()").unwrap_err();
+  assert!(matches!(e.kind(), ErrorKind::Exit(0)), "{e}");
+
+  #[cfg(all(feature = "process", unix))]
+  {
+    let value = hebi
+      .eval(
+        r#"
+import os
+
+out := os.run("sh", ["-c", "echo out; echo err >&2; exit 2"])
+out["status"] == 2 && out["stdout"] == "out\n" && out["stderr"] == "err\n"
+"#,
+      )
+      .unwrap();
+    assert_eq!(value.as_bool(), Some(true));
+    let e = hebi
+      .eval("import os\nos.run(\"__hebi_no_such_program\")")
+      .unwrap_err();
+    assert!(e.to_string().contains("cannot run"), "{e}");
+
+    let mut hebi = crate::public::Hebi::builder()
+      .allow_os(true)
+      .security_policy(NoProcesses)
+      .finish();
+    let e = hebi
+      .eval("import os\nos.run(\"sh\", [\"-c\", \"exit 0\"])")
+      .unwrap_err();
+    assert!(e.to_string().contains("cannot run `sh`"), "{e}");
+  }
+
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let e = hebi.eval("import os").unwrap_err();
+  assert!(e.to_string().contains("os"), "{e}");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn re_module() {
+  let mut hebi = crate::public::Hebi::builder().with_builtins().finish();
+  let mut eval = |src: &str| hebi.eval(src).unwrap().to_string();
+
+  let prelude = "import re\nr := re.compile(\"(?P<key>[a-z]+)=(?P<value>[0-9]+)?\")\n";
+  assert_eq!(eval(&format!("{prelude}r.is_match(\"a=1\")")), "true");
+  assert_eq!(eval(&format!("{prelude}r.is_match(\"1\")")), "false");
+  assert_eq!(
+    eval(&format!("{prelude}r.find(\"x a=1\")[\"text\"]")),
+    "a=1"
+  );
+  assert_eq!(eval(&format!("{prelude}r.find(\"x a=1\")[\"start\"]")), "2");
+  assert_eq!(eval(&format!("{prelude}r.find(\"1\")")), "none");
+  assert_eq!(
+    eval(&format!("{prelude}r.find_all(\"a=1 b=2\")[1][\"text\"]")),
+    "b=2"
+  );
+  assert_eq!(
+    eval(&format!("{prelude}r.captures(\"a=1\")[\"groups\"][1]")),
+    "a"
+  );
+  assert_eq!(
+    eval(&format!(
+      "{prelude}r.captures(\"a=1\")[\"named\"][\"value\"]"
+    )),
+    "1"
+  );
+  assert_eq!(
+    eval(&format!(
+      "{prelude}r.captures(\"a=\")[\"named\"][\"value\"]"
+    )),
+    "none"
+  );
+  assert_eq!(
+    eval(&format!("{prelude}r.replace(\"a=1 b=2\", \"$key\")")),
+    "a b"
+  );
+
+  let e = hebi.eval("import re\nre.compile(\"(\")").unwrap_err();
+  assert!(e.to_string().contains("invalid regex"), "{e}");
+}
+
+#[test]
+fn format_builtin() {
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(r#"format("{} and {:>8.2}|{:<4}|", "a", 3.14159, true)"#)
+    .unwrap();
+  assert_eq!(value.to_string(), "a and     3.14|true|");
+
+  let e = hebi.eval(r#"format("{} {}", 1)"#).unwrap_err();
+  assert!(e.to_string().contains("only 1 arguments"), "{e}");
+}
+
+#[test]
+fn print_hook_and_error_output() {
+  use std::sync::{Arc, Mutex};
+
+  use crate::public::{PrintHook, Stream};
+  use crate::span::Span;
+
+  type Lines = Arc<Mutex<Vec<(Stream, String, Span)>>>;
+  struct Hook(Lines);
+  impl PrintHook for Hook {
+    fn print(&mut self, stream: Stream, text: &str, span: Span) {
+      self
+        .0
+        .lock()
+        .unwrap()
+        .push((stream, text.to_string(), span));
+    }
+  }
+
+  let source = "print \"a\", 1\neprint(\"b\", none)\n";
+
+  let lines = Lines::default();
+  let mut hebi = crate::public::Hebi::builder()
+    .print_hook(Hook(lines.clone()))
+    .finish();
+  hebi.eval(source).unwrap();
+  let lines = lines.lock().unwrap();
+  assert_eq!(lines.len(), 2);
+  assert_eq!((lines[0].0, lines[0].1.as_str()), (Stream::Stdout, "a 1"));
+  assert!(source[lines[0].2].starts_with("print"));
+  assert_eq!(
+    (lines[1].0, lines[1].1.as_str()),
+    (Stream::Stderr, "b none")
+  );
+  assert!(source[lines[1].2].starts_with("eprint"));
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .error_output(Vec::<u8>::new())
+    .finish();
+  hebi.eval(source).unwrap();
+  let mut global = hebi.global();
+  let output = |output: &dyn crate::internal::vm::global::Output| {
+    String::from_utf8(output.as_any().downcast_ref::<Vec<u8>>().unwrap().clone()).unwrap()
+  };
+  assert_eq!(output(&*global.output()), "a 1\n");
+  assert_eq!(output(&*global.error_output()), "b none\n");
+}
+
+#[test]
+fn security_policy() {
+  use std::sync::{Arc, Mutex};
+
+  use crate::public::{ModulePath, SecurityPolicy};
+
+  #[derive(Default)]
+  struct Policy {
+    calls: Arc<Mutex<Vec<String>>>,
+  }
+
+  impl SecurityPolicy for Policy {
+    fn check_import(&self, path: &ModulePath) -> Result<()> {
+      if path.as_str() == "net" {
+        fail!("module `net` is not allowed");
+      }
+      Ok(())
+    }
+
+    fn check_native_call(&self, name: &str) -> Result<()> {
+      self.calls.lock().unwrap().push(name.to_string());
+      Ok(())
+    }
+
+    fn check_global_write(&self, name: &str) -> Result<()> {
+      if name == "to_str" {
+        fail!("cannot overwrite `{name}`");
+      }
+      Ok(())
+    }
+  }
+
+  let policy = Policy::default();
+  let calls = policy.calls.clone();
+  let mut hebi = crate::public::Hebi::builder()
+    .security_policy(policy)
+    .finish();
+  hebi.register(
+    &NativeModule::builder("net")
+      .function("get", |_| ())
+      .finish(),
+  );
+  hebi.register(
+    &NativeModule::builder("util")
+      .function("answer", |_| 42i32)
+      .finish(),
+  );
+
+  let value = hebi
+    .eval("from util import answer\nv := answer()\nv")
+    .unwrap();
+  assert_eq!(value.as_int(), Some(42));
+  assert_eq!(*calls.lock().unwrap(), ["answer"]);
+
+  let e = hebi.eval("import net").unwrap_err();
+  assert!(e.to_string().contains("not allowed"), "{e}");
+  let e = hebi.eval("to_str = none").unwrap_err();
+  assert!(e.to_string().contains("cannot overwrite"), "{e}");
+}
+
+#[test]
+fn error_kinds() {
+  use crate::internal::error::ErrorKind;
+  use crate::public::Unbind;
+
+  fn reject(scope: Scope<'_>) -> Result<()> {
+    Err(Error::from_value(scope.param::<crate::public::Value>(0)?))
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &crate::public::NativeModule::builder("test")
+      .function("reject", reject)
+      .finish(),
+  );
+
+  let kind =
+    |hebi: &mut crate::public::Hebi, code: &str| hebi.eval(code).unwrap_err().kind().clone();
+  assert!(matches!(
+    kind(&mut hebi, "undefined_variable"),
+    ErrorKind::Name
+  ));
+  assert!(matches!(kind(&mut hebi, "[0][1]"), ErrorKind::Index));
+  assert!(matches!(kind(&mut hebi, "1 + none"), ErrorKind::Type));
+  assert!(matches!(
+    kind(&mut hebi, "fn f(a):\n  pass\nf()"),
+    ErrorKind::Arity
+  ));
+  assert!(matches!(kind(&mut hebi, "1 / 0"), ErrorKind::Other));
+
+  let e = hebi
+    .eval("from test import reject\nreject(404)")
+    .unwrap_err();
+  assert_eq!(e.to_string(), "404");
+  let ErrorKind::User(value) = e.kind() else {
+    panic!("unexpected error kind {:?}", e.kind());
+  };
+  assert_eq!(value.get(hebi.global()).as_int(), Some(404));
+
+  // the value is frozen, so it may be read by another VM on another thread
+  let e = hebi
+    .eval("from test import reject\nreject([1, \"a\"])")
+    .unwrap_err();
+  std::thread::spawn(move || {
+    let other = crate::public::Hebi::new();
+    let ErrorKind::User(value) = e.kind() else {
+      panic!("unexpected error kind {:?}", e.kind());
+    };
+    let items = value.frozen().as_tuple().unwrap();
+    assert_eq!(items[1].as_str(), Some("a"));
+    let thawed = value.get(other.global()).unbind();
+    let thawed = thawed
+      .to_object::<crate::internal::object::Tuple>()
+      .unwrap();
+    assert_eq!(thawed.as_slice()[0].to_i64(), Some(1));
+  })
+  .join()
+  .unwrap();
+}
+
+#[test]
+fn raise_error_class() {
+  use crate::internal::error::ErrorKind;
+
+  let mut hebi = crate::public::Hebi::new();
+
+  let e = hebi.eval("raise Error(\"oops\")").unwrap_err();
+  assert_eq!(e.to_string(), "Error: oops");
+
+  let e = hebi
+    .eval(indoc::indoc! {r#"
+      class NotFound(Error):
+        path = none
+        init(self, path):
+          self.message = "no such file"
+          self.path = path
+
+      fn open(path):
+        raise NotFound(path)
+
+      open("a.txt")
+    "#})
+    .unwrap_err();
+  assert_eq!(e.to_string(), "NotFound: no such file");
+  let ErrorKind::User(value) = e.kind() else {
+    panic!("unexpected error kind {:?}", e.kind());
+  };
+  let path = value.field(hebi.global(), "path").unwrap();
+  assert_eq!(path.to_string(), "a.txt");
+
+  let e = hebi.eval("class Foo:\n  pass\nraise Foo()").unwrap_err();
+  assert!(matches!(e.kind(), ErrorKind::Type), "{e}");
+  let e = hebi.eval("raise 1").unwrap_err();
+  assert!(e.to_string().contains("only instances of `Error`"), "{e}");
+}
+
+#[test]
+fn defer_stmt() {
+  let mut hebi = crate::public::Hebi::new();
+
+  let value = hebi
+    .eval(indoc::indoc! {r#"
+      log = []
+
+      fn f(n):
+        defer log.push("first")
+        defer log.push(n)
+        n = n + 1
+        if n > 1:
+          return n
+        log.push("body")
+        return n
+
+      fn g():
+        defer log.push("unwound")
+        raise Error("oops")
+
+      f(0)
+      f(5)
+      log.join(" ")
+    "#})
+    .unwrap();
+  // deferred calls run in reverse order, with the arguments they had when
+  // the `defer` statement ran
+  assert_eq!(value.to_string(), "body 0 first 5 first");
+
+  let e = hebi.eval("g()").unwrap_err();
+  assert_eq!(e.to_string(), "Error: oops");
+  let value = hebi.eval("log[5]").unwrap();
+  assert_eq!(value.to_string(), "unwound");
+
+  let e = hebi.eval("defer print(1)").unwrap_err();
+  assert!(e.to_string().contains("defer outside of function"), "{e}");
+}
+
+#[test]
+fn sort_by_raises() {
+  let mut hebi = crate::public::Hebi::new();
+
+  hebi
+    .eval(indoc::indoc! {r#"
+      l = [3, 1, 4, 1, 5, 9, 2, 6]
+      n = 0
+      fn cmp(a, b):
+        n = n + 1
+        if n > 5:
+          raise Error("bad comparison")
+        return a - b
+    "#})
+    .unwrap();
+
+  let e = hebi.eval("sort(l, by=cmp)").unwrap_err();
+  assert!(e.to_string().contains("bad comparison"), "{e}");
+  // the list is left as it was, and is still usable
+  let value = hebi.eval("l.join(\" \")").unwrap();
+  assert_eq!(value.to_string(), "3 1 4 1 5 9 2 6");
+  let value = hebi
+    .eval(indoc::indoc! {r#"
+      sort(l)
+      l.join(" ")
+    "#})
+    .unwrap();
+  assert_eq!(value.to_string(), "1 1 2 3 4 5 6 9");
+}
+
+#[test]
+fn sort_incomparable_items() {
+  use crate::ErrorKind;
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.eval(r#"l = [3, "a", 1, none, 2.5, [1]]"#).unwrap();
+
+  for sort in ["l.sort()", "sort(l)"] {
+    let e = hebi.eval(sort).unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::Type), "{e}");
+    assert!(e.to_string().contains("cannot compare"), "{e}");
+    let value = hebi.eval("l.len()").unwrap();
+    assert_eq!(value.as_int(), Some(6));
+    let value = hebi.eval("l[1]").unwrap();
+    assert_eq!(value.to_string(), "a");
+  }
+}
+
+#[cfg(feature = "profiler")]
+#[test]
+fn profiler() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval(indoc::indoc! {"
+      fn fib(n):
+        if n < 2: return n
+        return fib(n - 1) + fib(n - 2)
+
+      fn fail():
+        return undefined_variable
+
+      fib(10)
+      fail()
+    "})
+    .unwrap_err();
+
+  let report = hebi.profile_report();
+  let fib = report.functions.iter().find(|f| f.name == "fib").unwrap();
+  assert_eq!(fib.calls, 177);
+  // calls which unwound are counted, too
+  let fail = report.functions.iter().find(|f| f.name == "fail").unwrap();
+  assert_eq!(fail.calls, 1);
+  let ret = report
+    .opcodes
+    .iter()
+    .find(|op| op.name == "Return")
+    .unwrap();
+  assert!(ret.count >= 177, "{report}");
+  assert!(report.opcodes.windows(2).all(|w| w[0].count >= w[1].count));
+
+  hebi.reset_profile();
+  let report = hebi.profile_report();
+  assert!(report.opcodes.is_empty() && report.functions.is_empty());
+}
+
+#[cfg(feature = "coverage")]
+#[test]
+fn coverage() {
+  let mut hebi = crate::public::Hebi::builder()
+    .module_loader(TestModuleLoader::new(&[(
+      "util",
+      indoc::indoc! {"
+        fn used():
+          return 1
+
+        fn unused():
+          return 2
+      "},
+    )]))
+    .finish();
+  hebi
+    .eval(indoc::indoc! {"
+      from util import used
+      v := used()
+      if v > 1:
+        v = 0
+      v
+    "})
+    .unwrap();
+
+  let report = hebi.coverage();
+  let main = &report.modules["__main__"];
+  assert_eq!(main.executed, [1, 2, 3, 5].into());
+  assert!(main.instrumented.contains(&4));
+  let util = &report.modules["util"];
+  assert!(util.executed.contains(&2) && !util.executed.contains(&5));
+  assert!(util.instrumented.contains(&5));
+
+  let lcov = report.to_lcov();
+  assert!(lcov.contains("SF:util\n"), "{lcov}");
+  assert!(
+    lcov.contains("DA:4,0\n") && lcov.contains("DA:5,1\n"),
+    "{lcov}"
+  );
+}
+
+#[test]
+fn user_data() {
+  use std::cell::RefCell;
+
+  struct Log(RefCell<Vec<String>>);
+  struct Prefix(&'static str);
+
+  let module = crate::public::NativeModule::builder("log")
+    .function("write", |scope| {
+      let message = scope.param::<String>(0)?;
+      let prefix = scope.user_data::<Prefix>().map_or("", |prefix| prefix.0);
+      let Some(log) = scope.user_data::<Log>() else {
+        fail!("there is no log");
+      };
+      let mut log = log.0.borrow_mut();
+      log.push(format!("{prefix}{message}"));
+      crate::internal::error::Result::Ok(log.len() as i32)
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::builder().finish();
+  hebi.register(&module);
+
+  assert_eq!(
+    hebi
+      .eval("import log\nlog.write(\"a\")")
+      .unwrap_err()
+      .to_string(),
+    "there is no log"
+  );
+
+  assert!(hebi.set_user_data(Log(RefCell::new(vec![]))).is_none());
+  hebi.eval("import log\nlog.write(\"a\")").unwrap();
+  hebi.set_user_data(Prefix("> "));
+  let count = hebi.eval("import log\nlog.write(\"b\")").unwrap();
+  assert_eq!(count.as_int(), Some(2));
+  assert_eq!(*hebi.user_data::<Log>().unwrap().0.borrow(), ["a", "> b"]);
+
+  let previous = hebi.set_user_data(Prefix("# ")).unwrap();
+  assert_eq!(previous.0, "> ");
+  assert_eq!(hebi.remove_user_data::<Prefix>().unwrap().0, "# ");
+  assert!(hebi.remove_user_data::<Prefix>().is_none());
+  assert!(hebi.user_data::<Prefix>().is_none());
+
+  let log = hebi.remove_user_data::<Log>().unwrap();
+  assert_eq!(log.0.into_inner(), ["a", "> b"]);
+}
+
+#[test]
+fn native_call_context() {
+  let module = crate::public::NativeModule::builder("ctx")
+    .function("module", |scope| scope.module_name())
+    .function("check", |scope| {
+      let ok = scope.param::<bool>(0)?;
+      if !ok {
+        fail!(@scope.call_span(), "check failed");
+      }
+      crate::internal::error::Result::Ok(())
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::builder()
+    .module_loader(TestModuleLoader::new(&[(
+      "util",
+      indoc::indoc! {"
+        import ctx
+
+        fn whoami():
+          return ctx.module()
+      "},
+    )]))
+    .finish();
+  hebi.register(&module);
+
+  let value = hebi.eval("import ctx\nctx.module()").unwrap();
+  assert!(value.is_none());
+  let value = hebi.eval("from util import whoami\nwhoami()").unwrap();
+  assert_eq!(value.to_string(), "util");
+
+  let src = "import ctx\nctx.check(true)\nctx.check(false)";
+  let e = hebi.eval(src).unwrap_err();
+  let Error::Vm(e) = &e else {
+    panic!("expected a runtime error, got {e:?}");
+  };
+  assert_eq!(&src[e.span.range()], "ctx.check(false)");
+}
+
+#[test]
+fn number_parsing() {
+  let mut hebi = crate::public::Hebi::new();
+  let mut eval = |src: &str| match hebi.eval(src) {
+    Ok(v) => v.to_string(),
+    Err(e) => e.to_string(),
+  };
+
+  assert_eq!(eval("1_000_000 + 2_5"), "1000025");
+  assert_eq!(eval("1_000.5e1_0"), "10005000000000.0");
+  assert_eq!(eval(r#"parse_int("ff", 16)"#), "255");
+  assert_eq!(eval(r#"parse_int("-1010", 2)"#), "-10");
+  assert_eq!(eval(r#"parse_int("zz", 36)"#), "1295");
+  assert_eq!(eval(r#"parse_int("1_000")"#), "1000");
+  assert_eq!(eval(r#"parse_int("+42")"#), "42");
+  assert_eq!(
+    eval(r#"parse_int("12", 37)"#),
+    "radix must be an int between 2 and 36, got `37`"
+  );
+  assert_eq!(
+    eval(r#"parse_int("12", 1)"#),
+    "radix must be an int between 2 and 36, got `1`"
+  );
+  assert_eq!(
+    eval(r#"parse_int("fg", 16)"#),
+    "failed to parse `fg` as int in base 16: invalid digit found in string"
+  );
+  assert_eq!(
+    eval(r#"parse_int(" 1")"#),
+    "failed to parse ` 1` as int: invalid digit found in string"
+  );
+  assert_eq!(
+    eval(r#"parse_int("_1")"#),
+    "failed to parse `_1` as int: misplaced `_`"
+  );
+  assert_eq!(
+    eval(r#"parse_int(10, 16)"#),
+    "cannot parse `10` with a radix, because it is not a string"
+  );
+  assert_eq!(eval("parse_int(1.0 / 0.0)"), "could not parse `inf` as int");
+  assert_eq!(eval("to_int(0.0 / 0.0)"), "cannot convert `NaN` to an int");
+
+  assert_eq!(eval(r#"parse_float("1.5")"#), "1.5");
+  assert_eq!(eval(r#"parse_float("-2e3")"#), "-2000.0");
+  assert_eq!(eval(r#"parse_float("1_000.25")"#), "1000.25");
+  assert_eq!(eval("parse_float(3)"), "3.0");
+  assert_eq!(
+    eval(r#"parse_float("1,5")"#),
+    "failed to parse `1,5` as float: invalid float literal"
+  );
+  assert_eq!(
+    eval(r#"parse_float("1__")"#),
+    "failed to parse `1__` as float: misplaced `_`"
+  );
+  assert_eq!(eval("parse_float(none)"), "could not parse `none` as float");
+}
+
+#[test]
+fn native_bool_method() {
+  use std::cell::RefCell;
+
+  struct Queue(RefCell<Vec<i32>>);
+
+  let module = crate::public::NativeModule::builder("queue")
+    .class::<Queue>("Queue", |class| {
+      class
+        .init(|_| Ok(Queue(RefCell::new(vec![]))))
+        .method("push", |scope, this| {
+          this.0.borrow_mut().push(scope.param(0)?);
+          crate::internal::error::Result::Ok(())
+        })
+        .method("__bool__", |_, this| !this.0.borrow().is_empty())
+        .finish()
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  let mut eval = |src: &str| match hebi.eval(src) {
+    Ok(value) => value.to_string(),
+    Err(e) => e.to_string(),
+  };
+
+  assert_eq!(
+    eval(
+      r#"
+from queue import Queue
+q := Queue()
+a := q || "empty"
+q.push(1)
+b := q && "full"
+c := 0
+if !q: c = 1
+to_str(a) + " " + to_str(b) + " " + to_str(c) + " " + to_str(to_bool(q))
+"#
+    ),
+    "empty full 0 true"
+  );
+}
@@ -38,7 +38,7 @@ macro_rules! check {
       assert_snapshot!(snapshot);
     }
   };
-  (module $name:ident, { $($module:ident: $code:literal),* }, $source:literal) => {
+  (module $name:ident, { $($module:ident $(.$nested:ident)*: $code:literal),* }, $source:literal) => {
     #[tokio::test]
     #[allow(non_snake_case)]
     async fn $name() {
@@ -47,7 +47,10 @@ macro_rules! check {
         .output(Vec::<u8>::new())
         .module_loader(
           TestModuleLoader::new(&[
-            $((stringify!($module), indoc::indoc!($code))),*
+            $((
+              concat!(stringify!($module) $(, ".", stringify!($nested))*),
+              indoc::indoc!($code)
+            )),*
           ])
         )
         .finish();
@@ -1,40 +1,57 @@
 #[macro_use]
 mod macros;
 
+pub mod cache;
 pub mod util;
 
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
-use std::mem::take;
+use std::mem::{replace, take};
 use std::ptr::NonNull;
 
+use futures_util::TryFutureExt;
 use indexmap::IndexMap;
 
+use self::cache::{FieldCache, FieldCaches};
 use self::util::*;
 use super::dispatch::{dispatch, Call, ControlFlow, Handler, LoadFrame, Return};
-use super::global::Global;
+use super::global::{BuiltinKind, Global, Stream};
 use crate::internal::bytecode::opcode as op;
-use crate::internal::error::{Error, Result};
-use crate::internal::object::class::{ClassInstance, ClassProxy};
+use crate::internal::error::{Error, ErrorKind, ErrorValue, Result};
+use crate::internal::object::builtin::{BuiltinAsyncFunction, BuiltinFunction};
+use crate::internal::object::class::{self, ClassInstance, ClassProxy, NativeClassProxy};
 use crate::internal::object::function::Params;
-use crate::internal::object::module::{ModuleId, ModuleKind};
-use crate::internal::object::native::LocalBoxFuture;
+use crate::internal::object::int::int;
+use crate::internal::object::module::{LoadResult, ModuleId, ModuleKind, ModulePath};
+use crate::internal::object::native::{
+  bind_builtin_method, LocalBoxFuture, NativeBoundFunction, NativeClass, NativeClassInstance,
+  NativeFunction,
+};
+use crate::internal::object::protocol::Protocol;
+use crate::internal::object::table::table_key;
 use crate::internal::object::{
-  function, Any, ClassDescriptor, ClassType, Function, FunctionDescriptor, List, Module, Object,
-  Ptr, Str, Table, Type,
+  function, Any, BoundFunction, Bytes, ClassDescriptor, ClassType, ConstantPool, Function,
+  FunctionDescriptor, List, Module, Object, Ptr, Str, StringBuilder, Table, Tuple, Type,
 };
 use crate::internal::value::constant::Constant;
 use crate::internal::value::Value;
 use crate::internal::{codegen, syntax};
 use crate::public::Scope;
+use crate::span::Span;
 use crate::util::JoinIter;
 
 pub struct Thread {
   pub(crate) global: Global,
   pub(crate) stack: NonNull<Stack>,
-  acc: Value,
+  /// The value returned by a call from the host, or yielded by a coroutine.
+  result: Value,
   pub(crate) pc: usize,
   poll: Option<AsyncFrame>,
+  /// The result of the `__bool__` method which a branch suspended the thread
+  /// to call, see [`Thread::branch_truthiness`].
+  truth: Option<bool>,
+  /// Whether `yield` may suspend this thread.
+  in_coroutine: bool,
 }
 
 impl Clone for Thread {
@@ -42,46 +59,114 @@ impl Clone for Thread {
     Self {
       global: self.global.clone(),
       stack: self.stack,
-      acc: self.acc.clone(),
+      result: self.result.clone(),
       pc: self.pc,
       poll: None,
+      truth: None,
+      in_coroutine: self.in_coroutine,
     }
   }
 }
 
+/// The outcome of running a coroutine until it stops.
+pub(crate) enum Resumed {
+  Yield(Value),
+  Return(Value),
+}
+
+/// The call stack and registers shared by all threads of a VM.
+///
+/// Neither is ever shrunk, so once the stack has grown to fit the deepest
+/// call, further calls do not allocate.
 #[derive(Debug)]
 pub struct Stack {
   pub(crate) frames: Vec<Frame>,
   pub(crate) regs: Vec<Value>,
+  /// The emptied `defer` lists of popped frames, which are handed out to new
+  /// frames so that calls to functions which use `defer` reuse them.
+  pub(crate) deferred_pool: Vec<Vec<Deferred>>,
 }
 
 impl Stack {
   pub fn new() -> Self {
+    Self::with_capacity(DEFAULT_STACK_RESERVE, DEFAULT_FRAME_RESERVE)
+  }
+
+  pub fn with_capacity(regs: usize, frames: usize) -> Self {
     Self {
-      frames: Vec::with_capacity(8),
-      regs: Vec::with_capacity(64),
+      frames: Vec::with_capacity(frames),
+      regs: Vec::with_capacity(regs),
+      deferred_pool: Vec::new(),
+    }
+  }
+
+  /// Push a frame for a call to `f`, with its registers starting at the
+  /// current top of the stack.
+  pub(crate) fn push_frame(&mut self, f: &Function, return_addr: Option<usize>) {
+    let mut frame = Frame::new(f, self.regs.len(), return_addr);
+    if let Some(deferred) = self.deferred_pool.pop() {
+      frame.deferred = deferred;
+    }
+    self.frames.push(frame);
+  }
+
+  /// Keep the memory of a frame which was popped, so that it can be reused
+  /// by the next call.
+  fn recycle_frame(&mut self, frame: Frame) {
+    let mut deferred = frame.deferred;
+    if deferred.capacity() > 0 {
+      deferred.clear();
+      self.deferred_pool.push(deferred);
     }
   }
 }
 
+pub const DEFAULT_STACK_RESERVE: usize = 64;
+pub const DEFAULT_FRAME_RESERVE: usize = 8;
+
 impl Thread {
   pub fn new(global: Global, stack: NonNull<Stack>) -> Self {
     Thread {
       global,
 
       stack,
-      acc: Value::none(),
+      result: Value::none(),
       pc: 0,
 
       poll: None,
+      truth: None,
+      in_coroutine: false,
     }
   }
 
-  fn unwind_stack(&mut self, stop_at_index: Option<usize>) {
-    let stack = unsafe { self.stack.as_mut() };
+  /// Create a thread for a coroutine which owns `stack` and stopped at `pc`.
+  pub(crate) fn coroutine(global: Global, stack: NonNull<Stack>, pc: usize) -> Self {
+    Thread {
+      in_coroutine: true,
+      pc,
+      ..Thread::new(global, stack)
+    }
+  }
+
+  /// Pop every frame above `stop_at_index` after an error.
+  ///
+  /// Functions deferred in each frame are called after it is popped, most
+  /// recently deferred first. Errors raised by them are ignored, because the
+  /// error which caused the unwind is the one reported.
+  async fn unwind_stack(&mut self, stop_at_index: Option<usize>) {
     let start = stop_at_index.map(|v| v + 1).unwrap_or(0);
-    for frame in stack.frames.drain(start..).rev() {
-      stack.regs.truncate(frame.stack_base);
+    while call_frames!(self).len() > start {
+      let mut frame = call_frames_mut!(self).pop().unwrap();
+      self.truncate_stack(frame.stack_base);
+      #[cfg(feature = "profiler")]
+      self
+        .global
+        .profiler()
+        .record_call(&frame.descriptor, frame.entered.elapsed());
+      while let Some(deferred) = frame.deferred.pop() {
+        let _ = Box::pin(self.call(deferred.function, &deferred.args)).await;
+      }
+      unsafe { self.stack.as_mut() }.recycle_frame(frame);
     }
   }
 
@@ -89,7 +174,7 @@ impl Thread {
     Function::prepare_call_empty_unchecked(main.clone(), self, None);
     loop {
       if let Err(e) = self.run() {
-        self.unwind_stack(None);
+        self.unwind_stack(None).await;
         if !unsafe { self.stack.as_ref().regs.is_empty() } {
           eprintln!("{self:?}");
           panic!("stack is not empty upon exit from vm.entry");
@@ -99,13 +184,10 @@ impl Thread {
       if let Some(frame) = self.poll.take() {
         let result = frame.fut.await;
         self.truncate_stack(frame.stack_base);
-        match result {
-          Ok(value) => {
-            self.acc = value;
-            continue;
-          }
+        match result.and_then(|value| self.resume(frame.on_ready, value)) {
+          Ok(()) => continue,
           Err(e) => {
-            self.unwind_stack(None);
+            self.unwind_stack(None).await;
             if !unsafe { self.stack.as_ref().regs.is_empty() } {
               eprintln!("{self:?}");
               panic!("stack is not empty upon exit from vm.entry");
@@ -114,7 +196,7 @@ impl Thread {
           }
         }
       } else {
-        let value = take(&mut self.acc);
+        let value = take(&mut self.result);
         if !unsafe { self.stack.as_ref().regs.is_empty() } {
           eprintln!("{self:?}");
           panic!("stack is not empty upon exit from vm.entry");
@@ -139,24 +221,27 @@ impl Thread {
         CallResult::Dispatch => {
           // the call pushed a frame onto the call stack,
           // so all we have to do is enter the interpreter
-          loop {
+          // at the start of that frame
+          let pc = replace(&mut self.pc, 0);
+          let result = loop {
             if let Err(e) = self.run() {
               break Err(e);
             }
             if let Some(frame) = self.poll.take() {
               let result = frame.fut.await;
               self.truncate_stack(frame.stack_base);
-              match result {
-                Ok(value) => {
-                  self.acc = value;
-                  continue;
-                }
+              match result.and_then(|value| self.resume(frame.on_ready, value)) {
+                Ok(()) => continue,
                 Err(e) => break Err(e),
               };
             } else {
-              break Ok(take(&mut self.acc));
+              break Ok(take(&mut self.result));
             }
-          }
+          };
+          // `pc` still points at the call into the native function which is
+          // calling this one, which is used for its span
+          self.pc = pc;
+          result
         }
       },
       Err(e) => Err(e),
@@ -168,12 +253,144 @@ impl Thread {
         Ok(value)
       }
       Err(e) => {
-        self.unwind_stack(Some(current_frame_index));
+        self.unwind_stack(Some(current_frame_index)).await;
         Err(e)
       }
     }
   }
 
+  /// Run the finalizers of objects which are no longer referenced, or of all
+  /// objects which have one if `all` is set.
+  ///
+  /// Errors raised by finalizers are ignored, because there is nothing which
+  /// could handle them.
+  pub(crate) async fn run_finalizers(&mut self, all: bool) {
+    loop {
+      let objects = self.global.take_finalizable(all);
+      if objects.is_empty() {
+        break;
+      }
+      self.finalize(objects).await;
+      // finalizers which run when the VM is dropped only run once, even if
+      // they create new objects which have finalizers
+      if all {
+        break;
+      }
+    }
+  }
+
+  async fn finalize(&mut self, objects: Vec<Ptr<Any>>) {
+    for object in objects {
+      if let Some(finalizer) = self.finalizer(object) {
+        let _ = Box::pin(self.call(finalizer, &[])).await;
+      }
+    }
+  }
+
+  /// Suspend the thread to run the finalizers of objects which are no longer
+  /// referenced, if there are any. This is called at safepoints, so objects
+  /// dropped by a long running script are finalized before it returns.
+  /// `on_ready` is called once they have run.
+  fn poll_finalizers(&mut self, on_ready: OnReady) -> bool {
+    let objects = self.global.take_finalizable_at_safepoint();
+    if objects.is_empty() {
+      return false;
+    }
+    let mut thread = Thread::new(self.global.clone(), self.stack);
+    self.poll = Some(AsyncFrame {
+      fut: Box::pin(async move {
+        thread.finalize(objects).await;
+        Ok(Value::none())
+      }),
+      stack_base: stack!(self).len(),
+      on_ready: Some(on_ready),
+    });
+    true
+  }
+
+  /// The `__del__` method of a class instance, or the finalizer of a native
+  /// class instance, bound to `object`.
+  fn finalizer(&self, object: Ptr<Any>) -> Option<Ptr<Any>> {
+    if let Some(instance) = object.clone_cast::<ClassInstance>() {
+      let method = instance.class.methods.get("__del__")?.clone();
+      let bound = BoundFunction::new(instance.into_any(), method);
+      Some(self.global.alloc(bound).into_any())
+    } else if let Some(instance) = object.clone_cast::<NativeClassInstance>() {
+      let finalizer = instance.class.finalizer.clone()?.into_any();
+      let bound = NativeBoundFunction::new(Value::object(instance), finalizer);
+      Some(self.global.alloc(bound).into_any())
+    } else {
+      None
+    }
+  }
+
+  /// Call `callable` at the bottom of the stack of a coroutine, and run it
+  /// until it either returns or yields.
+  pub(crate) async fn start_coroutine(
+    &mut self,
+    callable: Ptr<Any>,
+    args: &[Value],
+  ) -> Result<Resumed> {
+    debug_assert!(self.in_coroutine);
+    debug_assert!(call_frames!(self).is_empty());
+
+    let args = self.push_args(args);
+    let result = match callable.call(self.get_scope(args), None) {
+      Ok(CallResult::Return(value)) => Ok(Resumed::Return(value)),
+      Ok(CallResult::Poll(frame)) => frame.fut.await.map(Resumed::Return),
+      Ok(CallResult::Dispatch) => self.run_coroutine().await,
+      Err(e) => Err(e),
+    };
+    self.finish_coroutine(result).await
+  }
+
+  /// Continue running a coroutine from where it last yielded.
+  pub(crate) async fn resume_coroutine(&mut self) -> Result<Resumed> {
+    debug_assert!(self.in_coroutine);
+    debug_assert!(!call_frames!(self).is_empty());
+
+    let result = self.run_coroutine().await;
+    self.finish_coroutine(result).await
+  }
+
+  async fn run_coroutine(&mut self) -> Result<Resumed> {
+    loop {
+      self.run()?;
+      if let Some(frame) = self.poll.take() {
+        let result = frame.fut.await;
+        self.truncate_stack(frame.stack_base);
+        result.and_then(|value| self.resume(frame.on_ready, value))?;
+        continue;
+      }
+      let value = take(&mut self.result);
+      if call_frames!(self).is_empty() {
+        break Ok(Resumed::Return(value));
+      } else {
+        break Ok(Resumed::Yield(value));
+      }
+    }
+  }
+
+  /// A coroutine which did not yield is dead, so its stack is cleared.
+  async fn finish_coroutine(&mut self, result: Result<Resumed>) -> Result<Resumed> {
+    if !matches!(result, Ok(Resumed::Yield(_))) {
+      self.unwind_stack(None).await;
+      self.truncate_stack(0);
+    }
+    result
+  }
+
+  /// Resume execution after an `AsyncFrame` has completed with `value`.
+  fn resume(&mut self, on_ready: Option<OnReady>, value: Value) -> Result<()> {
+    match on_ready {
+      Some(on_ready) => on_ready(self, value),
+      None => {
+        self.set_register(current_call_frame!(self).return_dst, value);
+        Ok(())
+      }
+    }
+  }
+
   fn run(&mut self) -> Result<()> {
     let instructions = current_call_frame_mut!(self).instructions;
     let pc = self.pc;
@@ -194,7 +411,11 @@ impl Thread {
     let start = stack!(self).len();
     let count = args.len();
     stack_mut!(self).extend_from_slice(args);
-    Args { start, count }
+    Args {
+      start,
+      count,
+      kw: false,
+    }
   }
 
   pub(crate) fn pop_args(&mut self, args: Args) {
@@ -206,6 +427,7 @@ impl Thread {
   }
 
   fn do_call(&mut self, function: Ptr<Any>, args: Args, return_addr: usize) -> Result<Call> {
+    self.global.safepoint()?;
     if function.is::<Function>() {
       let function = unsafe { function.cast_unchecked::<Function>() };
       match Function::prepare_call(function, self, args, Some(return_addr)) {
@@ -214,10 +436,12 @@ impl Thread {
       };
     }
 
+    // lets native functions find the span of the call
+    self.pc = return_addr;
     match function.call(self.get_scope(args), Some(return_addr)) {
       Ok(call) => match call {
         CallResult::Return(value) => {
-          self.acc = value;
+          self.set_register(current_call_frame!(self).return_dst, value);
           Ok(Call::Continue)
         }
         CallResult::Poll(frame) => {
@@ -252,30 +476,72 @@ impl Thread {
     }
     let upvalues = self.global.alloc(List::from(upvalues));
 
+    let frame = current_call_frame!(self);
     self.global.alloc(Function::new(
       desc,
+      frame.pool.clone(),
       upvalues,
-      current_call_frame!(self).module_id,
+      frame.module_id,
     ))
   }
 
+  /// Mixins are applied in order after the parent, so their members
+  /// override the parent's and those of earlier mixins, and the class' own
+  /// members override all of them. `init` is only inherited from the parent.
+  ///
+  /// Members which are not found in the class are looked up in the instance
+  /// of `native`, if it is derived from a native class.
   fn make_class(
     &mut self,
     desc: Ptr<ClassDescriptor>,
     fields: Option<Ptr<Table>>,
     parent: Option<Ptr<ClassType>>,
-  ) -> Ptr<ClassType> {
+    native: Option<Ptr<NativeClass>>,
+    mixins: Vec<Ptr<ClassType>>,
+  ) -> Result<Ptr<ClassType>> {
     let mut init = desc.init.as_ref().map(|init| self.make_fn(init.clone()));
     let fields = fields.unwrap_or_else(|| self.global.alloc(Table::new()));
     let mut methods = IndexMap::with_capacity(desc.methods.len());
 
+    let hints = if parent
+      .iter()
+      .chain(mixins.iter())
+      .all(|c| c.hints.is_empty())
+    {
+      desc.hints.clone()
+    } else {
+      let hints = self.global.alloc(Table::new());
+      for class in parent.iter().chain(mixins.iter()) {
+        for (key, hint) in class.hints.entries() {
+          hints.insert(key, hint);
+        }
+      }
+      for (key, hint) in desc.hints.entries() {
+        hints.insert(key, hint);
+      }
+      hints
+    };
+    // defaults of `none` are allowed, because fields are often only
+    // initialized in `init`
+    if self.global.type_checks() {
+      for (key, hint) in hints.entries() {
+        let (Some(name), Some(hint)) = (key.as_str(), hint.to_object::<Str>()) else {
+          continue;
+        };
+        if let Some(value) = fields.get(name).filter(|value| !value.is_none()) {
+          check_hint(&value, hint.as_str(), format_args!("field `{name}`"))?;
+        }
+      }
+    }
+
     // inherit `init` and methods
     if let Some(parent) = parent.as_ref() {
       if init.is_none() {
         init = parent.init.clone();
       }
-
-      for (key, method) in parent.methods.iter() {
+    }
+    for class in parent.iter().chain(mixins.iter()) {
+      for (key, method) in class.methods.iter() {
         methods.insert(key.clone(), method.clone());
       }
     }
@@ -284,32 +550,173 @@ impl Thread {
       methods.insert(key.clone(), self.make_fn(desc.clone()));
     }
 
-    self.global.alloc(ClassType::new(
-      desc.name.clone(),
-      init,
-      fields,
-      methods,
-      parent,
-    ))
+    let statics = match parent.as_ref() {
+      Some(parent) => self.global.alloc(parent.statics.copy()),
+      None => self.global.alloc(Table::new()),
+    };
+    for mixin in mixins.iter() {
+      for (key, value) in mixin.statics.entries() {
+        statics.insert(key, value);
+      }
+    }
+    for key in desc.static_fields.keys() {
+      statics.insert(key, Value::none());
+    }
+    for (key, desc) in desc.static_methods.iter() {
+      statics.insert(key.clone(), Value::object(self.make_fn(desc.clone())));
+    }
+
+    Ok(
+      self.global.alloc(
+        ClassType::new(
+          desc.name.clone(),
+          init,
+          fields,
+          methods,
+          parent,
+          hints,
+          statics,
+        )
+        .with_mixins(mixins)
+        .with_native(native)
+        .with_doc(desc.doc.clone()),
+      ),
+    )
+  }
+
+  /// A class may be derived from a script class or a native class. Classes
+  /// derived from a script class inherit its native class, if it has one.
+  fn class_parent(parent: Value) -> Result<ClassParent> {
+    if let Some(native) = parent.clone().to_object::<NativeClass>() {
+      return Ok((None, Some(native)));
+    }
+    let Some(parent) = parent.clone().to_object::<ClassType>() else {
+      fail!(Type: "{parent} is not a class");
+    };
+    let native = parent.native.clone();
+    Ok((Some(parent), native))
+  }
+
+  /// Read the fields and mixins of a class from `parts`, which holds the
+  /// parent if the class is `derived`, then the field defaults, then the
+  /// mixins.
+  fn class_parts(
+    &self,
+    desc: &ClassDescriptor,
+    parts: op::Register,
+    derived: bool,
+    parent: Option<&Ptr<ClassType>>,
+  ) -> Result<(Ptr<Table>, Vec<Ptr<ClassType>>)> {
+    let offset = derived as usize;
+
+    let mut mixins = Vec::with_capacity(desc.mixins);
+    for i in 0..desc.mixins {
+      let mixin = self.get_register(parts.offset(offset + desc.fields.len() + i));
+      let Some(mixin) = mixin.clone().to_object::<ClassType>() else {
+        fail!(Type: "{mixin} is not a class");
+      };
+      mixins.push(mixin);
+    }
+
+    let fields = match parent {
+      Some(parent) => self.global.alloc(parent.fields.copy()),
+      None => self.global.alloc(Table::with_capacity(desc.fields.len())),
+    };
+    for mixin in mixins.iter() {
+      for (key, value) in mixin.fields.entries() {
+        fields.insert(key, value);
+      }
+    }
+    for (i, key) in desc.fields.keys().enumerate() {
+      fields.insert(key, self.get_register(parts.offset(offset + i)));
+    }
+
+    Ok((fields, mixins))
   }
 
-  fn load_module(&mut self, path: Ptr<Str>, return_addr: usize) -> Result<Call> {
+  fn load_module(&mut self, path: ModulePath, span: Span, return_addr: usize) -> Result<Call> {
     if let Some((module_id, module)) = self.global.get_module_by_name(path.as_str()) {
       // module is in cache
       if self.global.is_module_visited(module_id) {
-        fail!("attempted to import partially initialized module {path}");
+        return Err(self.circular_import_error(module_id, span));
       }
-      self.acc = Value::object(module);
+      if let ModuleKind::Native { init } = &module.kind {
+        init.run(self.get_empty_scope(), module.module_vars.clone())?;
+      }
+      self.set_register(current_call_frame!(self).return_dst, Value::object(module));
       return Ok(Call::Continue);
     }
 
     // module is not in cache, actually load it
+    let source = match self.global.load_module(&path)? {
+      LoadResult::Ready(source) => source,
+      LoadResult::Pending(fut) => {
+        // suspend until the source is available, then continue the import
+        // as if the module had been loaded synchronously
+        let global = self.global.clone();
+        let fut = fut.map_ok(move |source| Value::object(global.alloc(Str::owned(source))));
+        self.poll = Some(AsyncFrame {
+          fut: Box::pin(fut),
+          stack_base: stack!(self).len(),
+          on_ready: Some(Box::new(move |thread, source| {
+            let source = unsafe { source.to_object_unchecked::<Str>() };
+            let LoadFrame { pc, .. } =
+              thread.compile_module(path, span, source.as_str(), return_addr)?;
+            thread.pc = pc;
+            Ok(())
+          })),
+        });
+        return Ok(Call::Yield);
+      }
+    };
+
+    let frame = self.compile_module(path, span, &source, return_addr)?;
+    Ok(Call::LoadFrame(frame))
+  }
+
+  /// Describe the import cycle which is closed by importing `module_id` from
+  /// the import statement at `span`.
+  ///
+  /// Each span is relative to the source of the module which contains the
+  /// import statement.
+  fn circular_import_error(&self, module_id: ModuleId, span: Span) -> Error {
+    let mut chain = self.global.import_chain_from(module_id);
+    let Some((first, _)) = chain.first().cloned() else {
+      return error!("attempted to import partially initialized module").into();
+    };
+    chain.push((first, span));
+
+    let mut message = format!(
+      "circular import: {}",
+      chain.iter().map(|(name, _)| name.as_str()).join(" -> ")
+    );
+    for ((importer, _), (imported, span)) in chain.iter().zip(chain.iter().skip(1)) {
+      message += &format!("\n  `{importer}` imports `{imported}` at {span}");
+    }
+    error!(message).into()
+  }
+
+  /// Compile and define the module at `path`, and push its root function
+  /// onto the call stack.
+  fn compile_module(
+    &mut self,
+    path: ModulePath,
+    import_span: Span,
+    source: &str,
+    return_addr: usize,
+  ) -> Result<LoadFrame> {
+    let path = self.global.intern(path.as_str().to_owned());
     let module_id = self.global.next_module_id();
-    let module = self.global.load_module(path.as_str())?.to_string();
-    let module = syntax::parse(self.global.clone(), &module).map_err(Error::Syntax)?;
+    let module = syntax::parse(self.global.clone(), source).map_err(Error::Syntax)?;
     let module = codegen::emit(self.global.clone(), &module, path.as_str(), false);
+    #[cfg(feature = "coverage")]
+    self
+      .global
+      .coverage()
+      .add_module(path.as_str(), source, &module.root);
     let main = self.global.alloc(Function::new(
       module.root.clone(),
+      module.pool.clone(),
       self.global.alloc(List::new()),
       module_id,
     ));
@@ -320,19 +727,89 @@ impl Thread {
       &module.module_vars,
       module_id,
     ));
-    self.global.define_module(module_id, path, module.clone());
+    self
+      .global
+      .define_module(module_id, path, module.clone(), import_span);
 
     let ModuleKind::Script { root } = &module.kind else {
       fail!("expected module kind to be `script`");
     };
 
     <Function as Object>::call(self.get_empty_scope(), root.clone(), Some(return_addr))?;
-    Ok(Call::LoadFrame(LoadFrame {
+    Ok(LoadFrame {
       bytecode: root.descriptor.instructions,
       pc: 0,
+    })
+  }
+
+  /// The span of the instruction before `pc` in the current call frame.
+  ///
+  /// In a native function, this is the span of the call to it.
+  pub(crate) fn call_span(&self) -> Span {
+    match unsafe { self.stack.as_ref() }.frames.last() {
+      Some(frame) if self.pc > 0 => frame.span_at(self.pc - 1),
+      _ => Span::default(),
+    }
+  }
+
+  /// The name of the module which the current call frame belongs to, or
+  /// `None` for code which was passed to `eval` directly.
+  pub(crate) fn call_module_name(&self) -> Option<Ptr<Str>> {
+    let frame = unsafe { self.stack.as_ref() }.frames.last()?;
+    let module = self.global.get_module_by_id(frame.module_id)?;
+    Some(module.name.clone())
+  }
+
+  /// `lhs + rhs`
+  fn add(&mut self, lhs: Value, rhs: Value) -> Result<Value> {
+    Ok(binary!(lhs + rhs {
+      int => int_binop(
+        &self.global,
+        "+",
+        lhs,
+        rhs,
+        i64::checked_add,
+        |lhs, rhs| lhs + rhs,
+      )?,
+      f64 => Value::float(lhs + rhs),
+      any => lhs.add(self.get_empty_scope(), rhs)?,
     }))
   }
 
+  /// Whether the condition of a branch is truthy.
+  ///
+  /// If `cond` has a `__bool__` method, the thread is suspended to call it,
+  /// and this returns `None`. The branch then runs again once the thread is
+  /// resumed, and gets the result of the call from `self.truth`.
+  fn branch_truthiness(&mut self, cond: Value) -> Option<bool> {
+    if let Some(truth) = self.truth.take() {
+      return Some(truth);
+    }
+    let Some(method) = bool_method(&self.global, &cond) else {
+      return Some(is_truthy(cond));
+    };
+    self.poll_bool_method(
+      method,
+      Box::new(|thread, value| {
+        thread.truth = value.to_bool();
+        Ok(())
+      }),
+    );
+    None
+  }
+
+  /// Suspend the thread to call the `__bool__` method `method`. `on_ready` is
+  /// called with its result, which is always a bool.
+  fn poll_bool_method(&mut self, method: Ptr<Any>, on_ready: OnReady) {
+    let scope = self.get_empty_scope();
+    let mut scope = unsafe { std::mem::transmute::<Scope<'_>, Scope<'static>>(scope) };
+    self.poll = Some(AsyncFrame {
+      fut: Box::pin(async move { call_bool_method(&mut scope, method).await.map(Value::bool) }),
+      stack_base: stack!(self).len(),
+      on_ready: Some(on_ready),
+    });
+  }
+
   fn get_empty_scope(&self) -> Scope {
     self.get_scope(Args::empty())
   }
@@ -362,7 +839,11 @@ impl Thread {
       stack_mut!(self).extend((0..frame_size - count).map(|_| Value::none()));
     }
 
-    let args = Args { start, count };
+    let args = Args {
+      start,
+      count,
+      kw: false,
+    };
 
     Scope::new(self, stack_base, args)
   }
@@ -378,7 +859,7 @@ impl Thread {
   // idk what should be the public API for this
   pub(crate) fn check_equality(scope: Scope<'_>, lhs: Value, rhs: Value) -> Result<bool> {
     Ok(binary!(lhs, rhs {
-      i32 => lhs == rhs,
+      int => lhs == rhs,
       f64 => lhs == rhs,
       any => lhs.eq(scope, rhs)?,
       bool => lhs == rhs,
@@ -386,6 +867,17 @@ impl Thread {
       incompatible_types => false,
     }))
   }
+
+  pub(crate) fn compare_values(scope: Scope<'_>, lhs: Value, rhs: Value) -> Result<Ordering> {
+    Ok(binary!(lhs, rhs {
+      int => lhs.cmp(&rhs),
+      f64 => lhs.total_cmp(&rhs),
+      any => lhs.cmp(scope, rhs)?,
+      bool => lhs.cmp(&rhs),
+      none => Ordering::Equal,
+      incompatible_types => fail!(Type: "cannot compare `{lhs}` and `{rhs}`"),
+    }))
+  }
 }
 
 pub enum CallResult {
@@ -424,11 +916,18 @@ impl Slot0 {
 pub struct Args {
   pub start: usize,
   pub count: usize,
+  /// Whether the args were laid out by `call_kw`, and hold a value for every
+  /// keyword-only param of the callee after its positional params.
+  pub kw: bool,
 }
 
 impl Args {
   pub fn empty() -> Self {
-    Self { start: 0, count: 0 }
+    Self {
+      start: 0,
+      count: 0,
+      kw: false,
+    }
   }
 }
 
@@ -443,9 +942,11 @@ impl Debug for Thread {
     f.debug_struct("Thread")
       .field("global", &self.global)
       .field("stack", &unsafe { self.stack.as_ref() })
-      .field("acc", &self.acc)
+      .field("result", &self.result)
       .field("pc", &self.pc)
       .field("poll", &self.poll)
+      .field("truth", &self.truth)
+      .field("in_coroutine", &self.in_coroutine)
       .finish()
   }
 }
@@ -453,13 +954,22 @@ impl Debug for Thread {
 pub struct AsyncFrame {
   pub fut: LocalBoxFuture<'static, Result<Value>>,
   pub stack_base: usize,
+  /// Called with the result of `fut` instead of writing it to the register
+  /// which the current frame is waiting on.
+  pub on_ready: Option<OnReady>,
 }
 
+pub type OnReady = Box<dyn FnOnce(&mut Thread, Value) -> Result<()>>;
+
+/// The script class and the native class which a class is derived from.
+type ClassParent = (Option<Ptr<ClassType>>, Option<Ptr<NativeClass>>);
+
 impl Debug for AsyncFrame {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("AsyncFrame")
       .field("fut", &"<...>")
       .field("stack_base", &self.stack_base)
+      .field("on_ready", &self.on_ready.as_ref().map(|_| "<...>"))
       .finish()
   }
 }
@@ -467,11 +977,32 @@ impl Debug for AsyncFrame {
 pub(crate) struct Frame {
   instructions: NonNull<[u8]>,
   constants: NonNull<[Constant]>,
+  spans: NonNull<[Span]>,
+  field_caches: NonNull<FieldCaches>,
+  /// Keeps `constants` alive.
+  pool: Ptr<ConstantPool>,
   upvalues: Ptr<List>,
   stack_base: usize,
   frame_size: usize,
   return_addr: Option<usize>,
+  /// The register which receives the result of the call or import made by
+  /// this frame which is currently in progress.
+  return_dst: op::Register,
   module_id: ModuleId,
+  #[cfg(any(feature = "profiler", feature = "coverage"))]
+  descriptor: Ptr<FunctionDescriptor>,
+  #[cfg(feature = "profiler")]
+  entered: std::time::Instant,
+  /// Calls registered by `defer` statements, in the order they ran.
+  deferred: Vec<Deferred>,
+}
+
+/// A call registered by a `defer` statement, with its arguments already
+/// evaluated.
+#[derive(Debug)]
+pub(crate) struct Deferred {
+  function: Ptr<Any>,
+  args: Vec<Value>,
 }
 
 impl Debug for Frame {
@@ -483,24 +1014,60 @@ impl Debug for Frame {
       .field("stack_base", &self.stack_base)
       .field("frame_size", &self.frame_size)
       .field("return_addr", &self.return_addr)
+      .field("return_dst", &self.return_dst)
       .field("module_id", &self.module_id)
       .finish()
   }
 }
 
 impl Frame {
-  pub(crate) fn new(f: &Function, stack_base: usize, return_addr: Option<usize>) -> Self {
+  fn new(f: &Function, stack_base: usize, return_addr: Option<usize>) -> Self {
     let desc = f.descriptor.as_ref();
 
     Self {
       instructions: desc.instructions,
-      constants: desc.constants,
+      constants: desc.constants.get(),
+      spans: desc.spans,
+      field_caches: NonNull::from(&desc.field_caches),
+      pool: f.pool.clone(),
       upvalues: f.upvalues.clone(),
       stack_base,
       frame_size: desc.frame_size,
       return_addr,
+      return_dst: op::Register(0),
       module_id: f.module_id,
+      #[cfg(any(feature = "profiler", feature = "coverage"))]
+      descriptor: f.descriptor.clone(),
+      #[cfg(feature = "profiler")]
+      entered: std::time::Instant::now(),
+      deferred: Vec::new(),
+    }
+  }
+
+  /// Returns the inline cache of the field access instruction at `pc`.
+  fn field_cache<'a>(&self, pc: usize) -> Option<&'a FieldCache> {
+    // the descriptor outlives the frame
+    unsafe { self.field_caches.as_ref() }.get(pc)
+  }
+
+  /// Returns the span of the instruction which contains the byte at `offset`.
+  fn span_at(&self, offset: usize) -> Span {
+    let (mut bytecode, spans) = unsafe { (self.instructions.as_ref(), self.spans.as_ref()) };
+    let mut end = 0;
+    for span in spans {
+      if bytecode.is_empty() {
+        break;
+      }
+      let Some((_, remainder)) = op::symbolic::decode(bytecode) else {
+        break;
+      };
+      end += bytecode.len() - remainder.len();
+      if offset < end {
+        return *span;
+      }
+      bytecode = remainder;
     }
+    Span::default()
   }
 }
 
@@ -514,6 +1081,29 @@ impl Thread {
     unsafe { object.to_any_unchecked().cast_unchecked::<T>() }
   }
 
+  /// Load a field of a class instance using the inline cache of the
+  /// instruction at `pc`.
+  ///
+  /// Returns `None` if `object` is not a class instance, or if it has no field
+  /// called `name`, in which case the field should be loaded without the cache.
+  fn load_field_cached(&self, pc: usize, object: &Ptr<Any>, name: &Ptr<Str>) -> Option<Value> {
+    let cache = current_call_frame!(self).field_cache(pc)?;
+    let instance = object.clone().cast::<ClassInstance>().ok()?;
+    ClassInstance::named_field_cached(self.get_empty_scope(), instance, name, cache)
+  }
+
+  /// Primitives only have the methods registered by the host.
+  fn primitive_field(&self, receiver: Value, name: &Ptr<Str>) -> Option<Value> {
+    let kind = if receiver.is_int() {
+      BuiltinKind::Int
+    } else if receiver.is_float() {
+      BuiltinKind::Float
+    } else {
+      return None;
+    };
+    bind_builtin_method(&self.get_empty_scope(), kind, receiver, name)
+  }
+
   fn get_register(&self, reg: op::Register) -> Value {
     debug_assert!(
       self.stack_base() + reg.index() < stack!(self).len(),
@@ -542,7 +1132,6 @@ impl Thread {
     let base = current_call_frame!(self).stack_base;
     let stack = &stack!(self)[base..];
     println!("  stack: [{}]", stack.iter().join(", "));
-    println!("  acc: {}", self.acc);
   }
   #[cfg(feature = "__disable_verbose_logs")]
   fn print_stack(&self) {}
@@ -560,39 +1149,42 @@ macro_rules! vprintln {
 impl Handler for Thread {
   type Error = crate::internal::vm::Error;
 
-  fn op_load(&mut self, reg: op::Register) -> Result<()> {
-    self.print_stack();
-    vprintln!("load {reg}");
-
-    let value = self.get_register(reg);
-    self.acc = value;
+  #[cfg(feature = "profiler")]
+  #[inline]
+  fn profile_op(&mut self, opcode: op::Opcode) {
+    self.global.profiler().record_op(opcode);
+  }
 
-    Ok(())
+  #[cfg(feature = "coverage")]
+  #[inline]
+  fn cover(&mut self, pc: usize) {
+    let frame = current_call_frame!(self);
+    self.global.coverage().record(&frame.descriptor, pc);
   }
 
-  fn op_store(&mut self, reg: op::Register) -> Result<()> {
+  fn op_mov(&mut self, dst: op::Register, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("store {reg}");
+    vprintln!("mov {dst}, {src}");
 
-    let value = take(&mut self.acc);
-    self.set_register(reg, value);
+    let value = self.get_register(src);
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_load_const(&mut self, idx: op::Constant) -> Result<()> {
+  fn op_load_const(&mut self, dst: op::Register, idx: op::Constant) -> Result<()> {
     self.print_stack();
-    vprintln!("load_const {idx}");
+    vprintln!("load_const {dst}, {idx}");
 
     let value = self.get_constant(idx).into_value();
-    self.acc = value;
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_load_upvalue(&mut self, idx: op::Upvalue) -> Result<()> {
+  fn op_load_upvalue(&mut self, dst: op::Register, idx: op::Upvalue) -> Result<()> {
     self.print_stack();
-    vprintln!("load_upvalue {idx}");
+    vprintln!("load_upvalue {dst}, {idx}");
 
     let call_frame = current_call_frame!(self);
     let upvalues = &call_frame.upvalues;
@@ -601,14 +1193,14 @@ impl Handler for Thread {
       "upvalue index is out of bounds {idx:?}"
     );
     let value = unsafe { call_frame.upvalues.get_unchecked(idx.index()) };
-    self.acc = value;
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_store_upvalue(&mut self, idx: op::Upvalue) -> Result<()> {
+  fn op_store_upvalue(&mut self, idx: op::Upvalue, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("store_upvalue {idx}");
+    vprintln!("store_upvalue {idx}, {src}");
 
     let call_frame = current_call_frame!(self);
     let upvalues = &call_frame.upvalues;
@@ -616,15 +1208,15 @@ impl Handler for Thread {
       idx.index() < upvalues.len(),
       "upvalue index is out of bounds {idx:?}"
     );
-    let value = take(&mut self.acc);
+    let value = self.get_register(src);
     unsafe { call_frame.upvalues.set_unchecked(idx.index(), value) };
 
     Ok(())
   }
 
-  fn op_load_module_var(&mut self, idx: op::ModuleVar) -> Result<()> {
+  fn op_load_module_var(&mut self, dst: op::Register, idx: op::ModuleVar) -> Result<()> {
     self.print_stack();
-    vprintln!("load_module_var {idx}");
+    vprintln!("load_module_var {dst}, {idx}");
 
     let module_id = current_call_frame!(self).module_id;
     let module = match self.global.get_module_by_id(module_id) {
@@ -641,14 +1233,14 @@ impl Handler for Thread {
       }
     };
 
-    self.acc = value;
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_store_module_var(&mut self, idx: op::ModuleVar) -> Result<()> {
+  fn op_store_module_var(&mut self, idx: op::ModuleVar, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("store_module_var {idx}");
+    vprintln!("store_module_var {idx}, {src}");
 
     let module_id = current_call_frame!(self).module_id;
     let module = match self.global.get_module_by_id(module_id) {
@@ -658,7 +1250,7 @@ impl Handler for Thread {
       }
     };
 
-    let value = take(&mut self.acc);
+    let value = self.get_register(src);
 
     let success = module.module_vars.set_index(idx.index(), value.clone());
     if !success {
@@ -668,86 +1260,126 @@ impl Handler for Thread {
     Ok(())
   }
 
-  fn op_load_global(&mut self, name: op::Constant) -> Result<()> {
+  fn op_load_global(&mut self, dst: op::Register, name: op::Constant) -> Result<()> {
     self.print_stack();
-    vprintln!("load_global {name}");
+    vprintln!("load_global {dst}, {name}");
 
     let name = self.get_constant_object::<Str>(name);
     let value = match self.global.get(&name) {
       Some(value) => value,
-      None => fail!("undefined global {name}"),
+      None => fail!(Name: "undefined global {name}"),
     };
-    self.acc = value;
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_store_global(&mut self, name: op::Constant) -> Result<()> {
+  fn op_store_global(&mut self, name: op::Constant, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("store_global {name}");
+    vprintln!("store_global {name}, {src}");
 
     let name = self.get_constant_object::<Str>(name);
-    let value = take(&mut self.acc);
+    if let Some(policy) = self.global.security_policy() {
+      policy.check_global_write(name.as_str())?;
+    }
+    let value = self.get_register(src);
     self.global.set(name, value);
 
     Ok(())
   }
 
-  fn op_load_field(&mut self, name: op::Constant) -> Result<()> {
+  fn op_load_field(
+    &mut self,
+    pc: usize,
+    dst: op::Register,
+    obj: op::Register,
+    name: op::Constant,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("load_field {name}");
+    vprintln!("load_field {dst}, {obj}, {name}");
 
     let name = self.get_constant_object::<Str>(name);
-    let receiver = take(&mut self.acc);
+    let receiver = self.get_register(obj);
 
     // native class fields
     // native class methods
     // class methods
 
-    if let Some(object) = receiver.to_any() {
-      self.acc = object.named_field(self.get_empty_scope(), name)?;
+    let value = if let Some(object) = receiver.clone().to_any() {
+      match self.load_field_cached(pc, &object, &name) {
+        Some(value) => value,
+        None => object.named_field(self.get_empty_scope(), name)?,
+      }
     } else {
-      // TODO: fields on primitives
-      todo!("fields on primitives")
-    }
+      self
+        .primitive_field(receiver.clone(), &name)
+        .ok_or_else(|| error!(Name: "`{receiver}` has no field `{name}`"))?
+    };
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_load_field_opt(&mut self, name: op::Constant) -> Result<()> {
+  fn op_load_field_opt(
+    &mut self,
+    pc: usize,
+    dst: op::Register,
+    obj: op::Register,
+    name: op::Constant,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("load_field_opt {name}");
+    vprintln!("load_field_opt {dst}, {obj}, {name}");
 
     let name = self.get_constant_object::<Str>(name);
-    let receiver = take(&mut self.acc);
+    let receiver = self.get_register(obj);
 
     if receiver.is_none() {
-      self.acc = Value::none();
+      self.set_register(dst, Value::none());
       return Ok(());
     }
 
-    if let Some(object) = receiver.to_any() {
-      self.acc = object
-        .named_field_opt(self.get_empty_scope(), name)?
-        .unwrap_or_else(Value::none);
+    let value = if let Some(object) = receiver.clone().to_any() {
+      match self.load_field_cached(pc, &object, &name) {
+        Some(value) => value,
+        None => object
+          .named_field_opt(self.get_empty_scope(), name)?
+          .unwrap_or_else(Value::none),
+      }
     } else {
-      // TODO: fields on primitives
-      todo!("fields on primitives")
-    }
+      self
+        .primitive_field(receiver, &name)
+        .unwrap_or_else(Value::none)
+    };
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_store_field(&mut self, obj: op::Register, name: op::Constant) -> Result<()> {
+  fn op_store_field(
+    &mut self,
+    pc: usize,
+    obj: op::Register,
+    name: op::Constant,
+    src: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("store_field {obj}, {name}");
+    vprintln!("store_field {obj}, {name}, {src}");
 
     let name = self.get_constant_object::<Str>(name);
     let receiver = self.get_register(obj);
-    let value = take(&mut self.acc);
+    let value = self.get_register(src);
 
     if let Some(object) = receiver.to_any() {
-      object.set_named_field(self.get_empty_scope(), name, value)?;
+      let cache = current_call_frame!(self).field_cache(pc);
+      let stored = match (object.clone().cast::<ClassInstance>(), cache) {
+        (Ok(instance), Some(cache)) => {
+          ClassInstance::set_named_field_cached(instance, &name, value.clone(), cache)?
+        }
+        _ => false,
+      };
+      if !stored {
+        object.set_named_field(self.get_empty_scope(), name, value)?;
+      }
     } else {
       // TODO: fields on primitives
       todo!()
@@ -756,15 +1388,21 @@ impl Handler for Thread {
     Ok(())
   }
 
-  fn op_load_index(&mut self, obj: op::Register) -> Result<()> {
+  fn op_load_index(
+    &mut self,
+    dst: op::Register,
+    obj: op::Register,
+    key: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("load_index {obj}");
+    vprintln!("load_index {dst}, {obj}, {key}");
 
     let object = self.get_register(obj);
-    let key = take(&mut self.acc);
+    let key = self.get_register(key);
 
     if let Some(object) = object.to_any() {
-      self.acc = object.keyed_field(self.get_empty_scope(), key)?;
+      let value = object.keyed_field(self.get_empty_scope(), key)?;
+      self.set_register(dst, value);
     } else {
       // TODO: fields on primitives
       todo!()
@@ -773,22 +1411,28 @@ impl Handler for Thread {
     Ok(())
   }
 
-  fn op_load_index_opt(&mut self, obj: op::Register) -> Result<()> {
+  fn op_load_index_opt(
+    &mut self,
+    dst: op::Register,
+    obj: op::Register,
+    key: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("load_index_opt {obj}");
+    vprintln!("load_index_opt {dst}, {obj}, {key}");
 
     let object = self.get_register(obj);
-    let key = take(&mut self.acc);
+    let key = self.get_register(key);
 
     if object.is_none() {
-      self.acc = Value::none();
+      self.set_register(dst, Value::none());
       return Ok(());
     }
 
     if let Some(object) = object.to_any() {
-      self.acc = object
+      let value = object
         .keyed_field_opt(self.get_empty_scope(), key)?
         .unwrap_or_else(Value::none);
+      self.set_register(dst, value);
     } else {
       // TODO: fields on primitives
       todo!()
@@ -797,13 +1441,18 @@ impl Handler for Thread {
     Ok(())
   }
 
-  fn op_store_index(&mut self, obj: op::Register, key: op::Register) -> Result<()> {
+  fn op_store_index(
+    &mut self,
+    obj: op::Register,
+    key: op::Register,
+    src: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("store_index {obj}, {key}");
+    vprintln!("store_index {obj}, {key}, {src}");
 
     let object = self.get_register(obj);
     let key = self.get_register(key);
-    let value = take(&mut self.acc);
+    let value = self.get_register(src);
 
     if let Some(object) = object.to_any() {
       object.set_keyed_field(self.get_empty_scope(), key, value)?;
@@ -815,9 +1464,9 @@ impl Handler for Thread {
     Ok(())
   }
 
-  fn op_load_self(&mut self) -> Result<()> {
+  fn op_load_self(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("load_self");
+    vprintln!("load_self {dst}");
 
     let this = self.get_register(op::Register(0));
 
@@ -826,205 +1475,279 @@ impl Handler for Thread {
       Err(value) => value,
     };
 
-    self.acc = this;
+    self.set_register(dst, this);
     Ok(())
   }
 
-  fn op_load_super(&mut self) -> Result<()> {
+  fn op_load_super(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("load_super");
+    vprintln!("load_super {dst}");
 
     let this = self.get_register(op::Register(0));
 
     let Some(this) = this.to_any() else {
-      fail!("`self` is not a class instance");
+      fail!(Type: "`self` is not a class instance");
     };
 
-    let proxy = if let Some(proxy) = this.clone_cast::<ClassProxy>() {
-      ClassProxy {
-        this: proxy.this.clone(),
-        class: proxy.class.parent.clone().unwrap(),
-      }
+    let (this, class) = if let Some(proxy) = this.clone_cast::<ClassProxy>() {
+      (proxy.this.clone(), proxy.class.clone())
     } else if let Some(this) = this.clone_cast::<ClassInstance>() {
-      ClassProxy {
-        this: this.clone(),
-        class: this.parent.clone().unwrap(),
-      }
+      (this.clone(), this.class.clone())
     } else {
-      fail!("{this} is not a class");
+      fail!(Type: "{this} is not a class");
     };
 
-    self.acc = Value::object(self.global.alloc(proxy));
+    let value = match (class.parent.clone(), class.native.clone()) {
+      (Some(parent), _) => Value::object(self.global.alloc(ClassProxy {
+        this,
+        class: parent,
+      })),
+      (None, Some(native)) => Value::object(self.global.alloc(NativeClassProxy {
+        this,
+        class: native,
+      })),
+      (None, None) => fail!(Type: "class `{}` has no parent", class.name),
+    };
+    self.set_register(dst, value);
 
     Ok(())
   }
 
-  fn op_load_none(&mut self) -> Result<()> {
+  fn op_load_none(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("load_none");
+    vprintln!("load_none {dst}");
 
-    self.acc = Value::none();
+    self.set_register(dst, Value::none());
 
     Ok(())
   }
 
-  fn op_load_true(&mut self) -> Result<()> {
+  fn op_load_true(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("load_true");
+    vprintln!("load_true {dst}");
 
-    self.acc = Value::bool(true);
+    self.set_register(dst, Value::bool(true));
 
     Ok(())
   }
 
-  fn op_load_false(&mut self) -> Result<()> {
+  fn op_load_false(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("load_false");
+    vprintln!("load_false {dst}");
 
-    self.acc = Value::bool(false);
+    self.set_register(dst, Value::bool(false));
 
     Ok(())
   }
 
-  fn op_load_smi(&mut self, smi: op::Smi) -> Result<()> {
+  fn op_load_smi(&mut self, dst: op::Register, smi: op::Smi) -> Result<()> {
     self.print_stack();
-    vprintln!("load_smi {smi}");
+    vprintln!("load_smi {dst}, {smi}");
 
-    self.acc = Value::int(smi.value());
+    self.set_register(dst, Value::int(smi.value()));
 
     Ok(())
   }
 
-  fn op_make_fn(&mut self, desc: op::Constant) -> Result<()> {
+  fn op_make_fn(&mut self, dst: op::Register, desc: op::Constant) -> Result<()> {
     self.print_stack();
-    vprintln!("make_fn {desc}");
+    vprintln!("make_fn {dst}, {desc}");
 
     let desc = self.get_constant_object::<FunctionDescriptor>(desc);
 
     // fetch upvalues
     let f = self.make_fn(desc);
 
-    self.acc = Value::object(f);
+    self.set_register(dst, Value::object(f));
 
     Ok(())
   }
 
-  fn op_make_class(&mut self, desc: op::Constant) -> Result<()> {
+  fn op_make_class(&mut self, dst: op::Register, desc: op::Constant) -> Result<()> {
     self.print_stack();
-    vprintln!("make_class {desc}");
+    vprintln!("make_class {dst}, {desc}");
 
     let desc = self.get_constant_object::<ClassDescriptor>(desc);
 
-    let class = self.make_class(desc, None, None);
+    let class = self.make_class(desc, None, None, None, vec![])?;
 
-    self.acc = Value::object(class);
+    self.set_register(dst, Value::object(class));
 
     Ok(())
   }
 
-  fn op_make_class_derived(&mut self, desc: op::Constant) -> Result<()> {
+  fn op_make_class_derived(
+    &mut self,
+    dst: op::Register,
+    desc: op::Constant,
+    parent: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("make_class_derived {desc}");
+    vprintln!("make_class_derived {dst}, {desc}, {parent}");
 
     let desc = self.get_constant_object::<ClassDescriptor>(desc);
-    let parent = take(&mut self.acc);
+    let (parent, native) = Self::class_parent(self.get_register(parent))?;
 
-    let Some(parent) = parent.clone().to_object::<ClassType>() else {
-      fail!("{parent} is not a class");
+    let fields = match parent.as_ref() {
+      Some(parent) => self.global.alloc(parent.fields.copy()),
+      None => self.global.alloc(Table::new()),
     };
-    let fields = self.global.alloc(parent.fields.copy());
-    let class = self.make_class(desc, Some(fields), Some(parent));
+    let class = self.make_class(desc, Some(fields), parent, native, vec![])?;
 
-    self.acc = Value::object(class);
+    self.set_register(dst, Value::object(class));
 
     Ok(())
   }
 
-  fn op_make_data_class(&mut self, desc: op::Constant, parts: op::Register) -> Result<()> {
+  fn op_make_data_class(
+    &mut self,
+    dst: op::Register,
+    desc: op::Constant,
+    parts: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("make_data_class {desc}, {parts}");
+    vprintln!("make_data_class {dst}, {desc}, {parts}");
 
     let desc = self.get_constant_object::<ClassDescriptor>(desc);
 
-    let fields = self.global.alloc(Table::with_capacity(desc.fields.len()));
-    for (offset, key) in desc.fields.keys().enumerate() {
-      let value = self.get_register(parts.offset(offset));
-      fields.insert(key, value);
-    }
-    let class = self.make_class(desc, Some(fields), None);
+    let (fields, mixins) = self.class_parts(&desc, parts, false, None)?;
+    let class = self.make_class(desc, Some(fields), None, None, mixins)?;
 
-    self.acc = Value::object(class);
+    self.set_register(dst, Value::object(class));
 
     Ok(())
   }
 
-  fn op_make_data_class_derived(&mut self, desc: op::Constant, parts: op::Register) -> Result<()> {
+  fn op_make_data_class_derived(
+    &mut self,
+    dst: op::Register,
+    desc: op::Constant,
+    parts: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("make_data_class_derived {desc}, {parts}");
+    vprintln!("make_data_class_derived {dst}, {desc}, {parts}");
 
     let desc = self.get_constant_object::<ClassDescriptor>(desc);
-    let parent = self.get_register(parts);
-
-    let Some(parent) = parent.clone().to_object::<ClassType>() else {
-      fail!("{parent} is not a class");
-    };
+    let (parent, native) = Self::class_parent(self.get_register(parts))?;
 
-    let fields = self.global.alloc(parent.fields.copy());
-    for (offset, key) in desc.fields.keys().enumerate() {
-      let value = self.get_register(parts.offset(1 + offset));
-      fields.insert(key, value);
-    }
-    let class = self.make_class(desc, Some(fields), Some(parent));
+    let (fields, mixins) = self.class_parts(&desc, parts, true, parent.as_ref())?;
+    let class = self.make_class(desc, Some(fields), parent, native, mixins)?;
 
-    self.acc = Value::object(class);
+    self.set_register(dst, Value::object(class));
 
     Ok(())
   }
 
-  fn op_make_list(&mut self, start: op::Register, count: op::Count) -> Result<()> {
+  fn op_make_list(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("make_list {start}, {count}");
+    vprintln!("make_list {dst}, {start}, {count}");
 
     let list = List::with_capacity(count.value());
     for reg in start.iter(count, 1) {
       list.push(self.get_register(reg));
     }
-    self.acc = Value::object(self.global.alloc(list));
+    self.set_register(dst, Value::object(self.global.alloc(list)));
+    Ok(())
+  }
+
+  fn op_make_list_empty(&mut self, dst: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("make_list_empty {dst}");
+
+    self.set_register(dst, Value::object(self.global.alloc(List::new())));
+    Ok(())
+  }
+
+  fn op_make_tuple(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<()> {
+    self.print_stack();
+    vprintln!("make_tuple {dst}, {start}, {count}");
+
+    let items = start
+      .iter(count, 1)
+      .map(|reg| self.get_register(reg))
+      .collect::<Vec<_>>();
+    self.set_register(dst, Value::object(self.global.alloc(Tuple::from(items))));
+    Ok(())
+  }
+
+  fn op_make_tuple_empty(&mut self, dst: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("make_tuple_empty {dst}");
+
+    self.set_register(dst, Value::object(self.global.alloc(Tuple::from(vec![]))));
     Ok(())
   }
 
-  fn op_make_list_empty(&mut self) -> Result<()> {
+  fn op_unpack(&mut self, start: op::Register, count: op::Count, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("make_list_empty");
+    vprintln!("unpack {start}, {count}, {src}");
 
-    self.acc = Value::object(self.global.alloc(List::new()));
+    let value = self.get_register(src);
+    let items = if let Some(tuple) = value.clone().to_object::<Tuple>() {
+      tuple.as_slice().to_vec()
+    } else if let Some(list) = value.clone().to_object::<List>() {
+      list.iter().collect::<Vec<_>>()
+    } else {
+      fail!(Type: "cannot unpack `{value}`, only tuples and lists may be unpacked");
+    };
+    if items.len() != count.value() {
+      let (len, count) = (items.len(), count.value());
+      fail!("cannot unpack {len} values into {count} variables");
+    }
+    for (reg, item) in start.iter(count, 1).zip(items) {
+      self.set_register(reg, item);
+    }
     Ok(())
   }
 
-  fn op_make_table(&mut self, start: op::Register, count: op::Count) -> Result<()> {
+  fn op_make_table(
+    &mut self,
+    dst: op::Register,
+    start: op::Register,
+    count: op::Count,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("make_table {start}, {count}");
+    vprintln!("make_table {dst}, {start}, {count}");
 
     let table = Table::with_capacity(count.value());
     for reg in start.iter(count, 2) {
       let key = self.get_register(reg);
       let value = self.get_register(reg.offset(1));
 
-      let Some(key) = key.clone().to_any().and_then(|v| v.cast::<Str>().ok()) else {
-        fail!("`{key}` is not a string");
-      };
-
-      table.insert(key, value);
+      table.insert(table_key(&key)?, value);
     }
-    self.acc = Value::object(self.global.alloc(table));
+    self.set_register(dst, Value::object(self.global.alloc(table)));
     Ok(())
   }
 
-  fn op_make_table_empty(&mut self) -> Result<()> {
+  fn op_make_table_empty(&mut self, dst: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("make_table_empty");
+    vprintln!("make_table_empty {dst}");
 
-    self.acc = Value::object(self.global.alloc(Table::new()));
+    self.set_register(dst, Value::object(self.global.alloc(Table::new())));
+    Ok(())
+  }
+
+  fn op_make_bytes(&mut self, dst: op::Register, data: op::Constant) -> Result<()> {
+    self.print_stack();
+    vprintln!("make_bytes {dst}, {data}");
+
+    // the constant is only a template, each evaluation gets a fresh copy
+    let data = self.get_constant_object::<Bytes>(data);
+    self.set_register(
+      dst,
+      Value::object(self.global.alloc(Bytes::from(data.to_vec()))),
+    );
     Ok(())
   }
 
@@ -1045,90 +1768,142 @@ impl Handler for Thread {
     Ok(offset)
   }
 
-  fn op_jump_loop(&mut self, offset: op::Offset) -> Result<op::Offset> {
+  fn op_jump_loop(&mut self, offset: op::Offset) -> Result<super::dispatch::Jump> {
     self.print_stack();
     vprintln!("jump_loop {offset}");
 
-    Ok(offset)
+    self.global.safepoint()?;
+    if self.poll_finalizers(Box::new(|_, _| Ok(()))) {
+      return Ok(super::dispatch::Jump::Yield);
+    }
+    Ok(super::dispatch::Jump::Move(offset))
   }
 
-  fn op_jump_if_false(&mut self, offset: op::Offset) -> Result<super::dispatch::Jump> {
+  fn op_jump_if_false(
+    &mut self,
+    offset: op::Offset,
+    cond: op::Register,
+  ) -> Result<super::dispatch::Jump> {
     self.print_stack();
-    vprintln!("jump_if_false {offset}");
+    vprintln!("jump_if_false {offset}, {cond}");
 
-    match is_truthy(take(&mut self.acc)) {
-      true => Ok(super::dispatch::Jump::Skip),
-      false => Ok(super::dispatch::Jump::Move(offset)),
+    match self.branch_truthiness(self.get_register(cond)) {
+      Some(true) => Ok(super::dispatch::Jump::Skip),
+      Some(false) => Ok(super::dispatch::Jump::Move(offset)),
+      None => Ok(super::dispatch::Jump::Yield),
     }
   }
 
-  fn op_jump_if_false_const(&mut self, idx: op::Constant) -> Result<super::dispatch::Jump> {
+  fn op_jump_if_false_const(
+    &mut self,
+    idx: op::Constant,
+    cond: op::Register,
+  ) -> Result<super::dispatch::Jump> {
     self.print_stack();
-    vprintln!("jump_if_false_const {idx}");
+    vprintln!("jump_if_false_const {idx}, {cond}");
 
     let offset = self.get_constant(idx).as_offset().cloned();
     debug_assert!(offset.is_some());
     let offset = unsafe { offset.unwrap_unchecked() };
 
-    match is_truthy(take(&mut self.acc)) {
-      true => Ok(super::dispatch::Jump::Skip),
-      false => Ok(super::dispatch::Jump::Move(offset)),
+    match self.branch_truthiness(self.get_register(cond)) {
+      Some(true) => Ok(super::dispatch::Jump::Skip),
+      Some(false) => Ok(super::dispatch::Jump::Move(offset)),
+      None => Ok(super::dispatch::Jump::Yield),
+    }
+  }
+
+  fn op_switch(&mut self, src: op::Register, table: op::Constant) -> Result<super::dispatch::Jump> {
+    self.print_stack();
+    vprintln!("switch {src}, {table}");
+
+    let Some(src) = self.get_register(src).to_object::<Str>() else {
+      return Ok(super::dispatch::Jump::Skip);
+    };
+    let table = self.get_constant(table);
+    debug_assert!(table.as_jump_table().is_some());
+    let table = unsafe { table.as_jump_table().unwrap_unchecked() };
+
+    match table.get(src.as_str()) {
+      Some(offset) => Ok(super::dispatch::Jump::Move(offset)),
+      None => Ok(super::dispatch::Jump::Skip),
     }
   }
 
-  fn op_add(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_add(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("add {lhs}");
+    vprintln!("add {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
-    let value = binary!(lhs + rhs {
-      i32 => Value::int(lhs + rhs),
-      f64 => Value::float(lhs + rhs),
-      any => lhs.add(self.get_empty_scope(), rhs)?,
-    });
-    self.acc = value;
+    let rhs = self.get_register(rhs);
+    let value = self.add(lhs, rhs)?;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_sub(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_sub(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("sub {lhs}");
+    vprintln!("sub {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs - rhs {
-      i32 => Value::int(lhs - rhs),
+      int => int_binop(
+        &self.global,
+        "-",
+        lhs,
+        rhs,
+        i64::checked_sub,
+        |lhs, rhs| lhs - rhs,
+      )?,
       f64 => Value::float(lhs - rhs),
       any => lhs.subtract(self.get_empty_scope(), rhs)?,
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_mul(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_mul(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("mul {lhs}");
+    vprintln!("mul {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
+    let duration = duration_and_number(&lhs, &rhs).or_else(|| duration_and_number(&rhs, &lhs));
+    if let Some((duration, factor)) = duration {
+      let value = Value::object(self.global.alloc(duration.mul_f64(factor)?));
+      self.set_register(dst, value);
+      return Ok(());
+    }
     let value = binary!(lhs * rhs {
-      i32 => Value::int(lhs * rhs),
+      int => int_binop(
+        &self.global,
+        "*",
+        lhs,
+        rhs,
+        i64::checked_mul,
+        |lhs, rhs| lhs * rhs,
+      )?,
       f64 => Value::float(lhs * rhs),
       any => lhs.multiply(self.get_empty_scope(), rhs)?,
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_div(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_div(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("div {lhs}");
+    vprintln!("div {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
+    if let Some((duration, divisor)) = duration_and_number(&lhs, &rhs) {
+      let value = Value::object(self.global.alloc(duration.div_f64(divisor)?));
+      self.set_register(dst, value);
+      return Ok(());
+    }
     let value = binary!(lhs / rhs {
-      i32 => {
+      int => {
         if rhs != 0 {
           Value::float(lhs as f64 / rhs as f64)
         } else {
@@ -1138,305 +1913,482 @@ impl Handler for Thread {
       f64 => Value::float(lhs / rhs),
       any => lhs.divide(self.get_empty_scope(), rhs)?,
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_rem(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_floor_div(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<()> {
     self.print_stack();
-    vprintln!("rem {lhs}");
+    vprintln!("floor_div {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
+    // `//` would start a comment, so the messages are spelled out
+    let value = binary!(lhs, rhs {
+      int => {
+        if rhs != 0 {
+          int_binop(
+            &self.global,
+            "//",
+            lhs,
+            rhs,
+            checked_floor_div,
+            |lhs, rhs| (lhs / rhs).floor(),
+          )?
+        } else {
+          fail!("cannot divide int by zero")
+        }
+      },
+      f64 => Value::float((lhs / rhs).floor()),
+      any => lhs.floor_divide(self.get_empty_scope(), rhs)?,
+      bool => fail!(Type: "cannot `//` `bool`"),
+      none => fail!(Type: "cannot `//` `none`"),
+      incompatible_types => fail!(Type: "operands must have the same type: `{}`, `{}`", lhs, rhs),
+    });
+    self.set_register(dst, value);
+    Ok(())
+  }
+
+  fn op_rem(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("rem {dst}, {lhs}, {rhs}");
+
+    let lhs = self.get_register(lhs);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs % rhs {
-      i32 => {
+      int => {
         if rhs != 0 {
-          Value::float(lhs as f64 % rhs as f64)
+          int(&self.global, floor_rem(lhs, rhs))
         } else {
           fail!("cannot divide int by zero")
         }
       },
-      f64 => Value::float(lhs % rhs),
+      f64 => Value::float(floor_rem_f64(lhs, rhs)),
       any => lhs.remainder(self.get_empty_scope(), rhs)?,
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_pow(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_pow(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("pow {lhs}");
+    vprintln!("pow {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs ** rhs {
-      i32 => Value::float((lhs as f64).powf(rhs as f64)),
+      int => {
+        if rhs >= 0 {
+          int_binop(
+            &self.global,
+            "**",
+            lhs,
+            rhs,
+            |lhs, rhs| lhs.checked_pow(u32::try_from(rhs).ok()?),
+            f64::powf,
+          )?
+        } else {
+          Value::float((lhs as f64).powf(rhs as f64))
+        }
+      },
       f64 => Value::float(lhs.powf(rhs)),
       any => lhs.pow(self.get_empty_scope(), rhs)?,
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_inv(&mut self) -> Result<()> {
+  fn op_inv(&mut self, dst: op::Register, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("inv");
+    vprintln!("inv {dst}, {src}");
 
-    let value = take(&mut self.acc);
-    let value = if value.is_int() {
-      let value = unsafe { value.to_int_unchecked() };
-      Value::int(-value)
+    let value = self.get_register(src);
+    let value = if let Some(value) = value.to_i64() {
+      int_binop(&self.global, "-", 0, value, i64::checked_sub, |lhs, rhs| {
+        lhs - rhs
+      })?
     } else if value.is_float() {
       let value = unsafe { value.to_float_unchecked() };
       Value::float(-value)
     } else if value.is_bool() {
-      fail!("cannot invert `bool`")
+      fail!(Type: "cannot invert `bool`")
     } else if value.is_none() {
-      fail!("cannot invert `none`")
+      fail!(Type: "cannot invert `none`")
     } else if value.is_object() {
       let value = unsafe { value.to_any_unchecked() };
       value.invert(self.get_empty_scope())?
     } else {
       unreachable!()
     };
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_not(&mut self) -> Result<()> {
+  fn op_not(&mut self, dst: op::Register, src: op::Register) -> Result<Call> {
     self.print_stack();
-    vprintln!("not");
-    let value = take(&mut self.acc);
+    vprintln!("not {dst}, {src}");
+    let value = self.get_register(src);
+    if let Some(method) = bool_method(&self.global, &value) {
+      self.poll_bool_method(
+        method,
+        Box::new(move |thread, value| {
+          thread.set_register(dst, Value::bool(!unsafe { value.to_bool_unchecked() }));
+          Ok(())
+        }),
+      );
+      return Ok(Call::Yield);
+    }
     let value = Value::bool(!is_truthy(value));
-    self.acc = value;
-    Ok(())
+    self.set_register(dst, value);
+    Ok(Call::Continue)
   }
 
-  fn op_cmp_eq(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_eq(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_eq {lhs}");
+    vprintln!("cmp_eq {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = Value::bool(Self::check_equality(self.get_empty_scope(), lhs, rhs)?);
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_ne(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_ne(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_ne {lhs}");
+    vprintln!("cmp_ne {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = Value::bool(!Self::check_equality(self.get_empty_scope(), lhs, rhs)?);
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_gt(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_gt(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_gt {lhs}");
+    vprintln!("cmp_gt {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs > rhs {
-      i32 => Value::bool(lhs > rhs),
+      int => Value::bool(lhs > rhs),
       f64 => Value::bool(lhs > rhs),
       any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Greater)),
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_ge(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_ge(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_ge {lhs}");
+    vprintln!("cmp_ge {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs >= rhs {
-      i32 => Value::bool(lhs >= rhs),
+      int => Value::bool(lhs >= rhs),
       f64 => Value::bool(lhs >= rhs),
       any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Greater | Ordering::Equal)),
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_lt(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_lt(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_lt {lhs}");
+    vprintln!("cmp_lt {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs < rhs {
-      i32 => Value::bool(lhs < rhs),
+      int => Value::bool(lhs < rhs),
       f64 => Value::bool(lhs < rhs),
       any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Less)),
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_le(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_le(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_le {lhs}");
+    vprintln!("cmp_le {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
     let value = binary!(lhs <= rhs {
-      i32 => Value::bool(lhs <= rhs),
+      int => Value::bool(lhs <= rhs),
       f64 => Value::bool(lhs <= rhs),
       any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Less | Ordering::Equal)),
     });
-    self.acc = value;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_cmp_type(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_cmp_type(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("cmp_type {lhs}");
+    vprintln!("cmp_type {dst}, {lhs}, {rhs}");
 
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
-
-    let is_same_type = if lhs.is_object() && rhs.is_object() {
+    let rhs = self.get_register(rhs);
+
+    let is_int = |v: &Value| v.to_i64().is_some();
+    let is_same_type = if let Some(protocol) = rhs.clone().to_object::<Protocol>() {
+      protocol.is_implemented_by(self.get_empty_scope(), &lhs)
+    } else if is_int(&lhs) || is_int(&rhs) {
+      is_int(&lhs) && is_int(&rhs)
+    } else if lhs.is_object() && rhs.is_object() {
       let lhs = unsafe { lhs.to_any_unchecked() };
 
       lhs.instance_of(rhs)?
     } else {
-      (lhs.is_int() && rhs.is_int())
-        || (lhs.is_float() && rhs.is_float())
+      (lhs.is_float() && rhs.is_float())
         || (lhs.is_bool() && rhs.is_bool())
         || (lhs.is_none() && rhs.is_none())
     };
 
-    self.acc = Value::bool(is_same_type);
+    self.set_register(dst, Value::bool(is_same_type));
 
     Ok(())
   }
 
-  fn op_contains(&mut self, lhs: op::Register) -> Result<()> {
+  fn op_contains(&mut self, dst: op::Register, lhs: op::Register, rhs: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("contains {lhs}");
+    vprintln!("contains {dst}, {lhs}, {rhs}");
 
     // lhs in rhs
     let lhs = self.get_register(lhs);
-    let rhs = take(&mut self.acc);
+    let rhs = self.get_register(rhs);
 
     let Some(rhs) = rhs.clone().to_any() else {
       fail!("`{rhs}` is not an object");
     };
 
     let result = rhs.contains(self.get_empty_scope(), lhs)?;
-    self.acc = Value::bool(result);
+    self.set_register(dst, Value::bool(result));
     Ok(())
   }
 
-  fn op_is_none(&mut self) -> Result<()> {
+  fn op_is_none(&mut self, dst: op::Register, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("is_none");
+    vprintln!("is_none {dst}, {src}");
+
+    self.set_register(dst, Value::bool(self.get_register(src).is_none()));
+    Ok(())
+  }
+
+  fn op_append_str(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+  ) -> Result<()> {
+    self.print_stack();
+    vprintln!("append_str {dst}, {lhs}, {rhs}");
+
+    let lhs = self.get_register(lhs);
+    let rhs = self.get_register(rhs);
+    if let Some(rhs) = rhs.clone().to_object::<Str>() {
+      if let Some(builder) = lhs.clone().to_object::<StringBuilder>() {
+        if builder.is_implicit() {
+          builder.push_str(rhs.as_str());
+          self.set_register(dst, Value::object(builder));
+          return Ok(());
+        }
+      }
+      if let Some(lhs) = lhs.clone().to_object::<Str>() {
+        let data = [lhs.as_str(), rhs.as_str()].concat();
+        let builder = self.global.alloc(StringBuilder::implicit(data));
+        self.set_register(dst, Value::object(builder));
+        return Ok(());
+      }
+    }
 
-    self.acc = Value::bool(self.acc.is_none());
+    // anything else is a plain `+`
+    let lhs = build_str(&self.global, lhs);
+    let value = self.add(lhs, rhs)?;
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_print(&mut self) -> Result<()> {
+  fn op_build_str(&mut self, dst: op::Register, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("print");
+    vprintln!("build_str {dst}, {src}");
 
-    let mut output = self.global.io().output.borrow_mut();
-    writeln!(&mut output, "{}", take(&mut self.acc)).map_err(Error::user)?;
+    let value = build_str(&self.global, self.get_register(src));
+    self.set_register(dst, value);
     Ok(())
   }
 
-  fn op_print_n(&mut self, start: op::Register, count: op::Count) -> Result<()> {
+  fn op_print(&mut self, pc: usize, src: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("print {src}");
+
+    let text = self.get_register(src).to_string();
+    let span = current_call_frame!(self).span_at(pc);
+    self.global.io().print(Stream::Stdout, &text, span)
+  }
+
+  fn op_print_n(&mut self, pc: usize, start: op::Register, count: op::Count) -> Result<()> {
     self.print_stack();
     vprintln!("print_n {start}, {count}");
 
     debug_assert!(self.stack_base() + start.index() + count.value() <= stack!(self).len());
 
-    let mut output = self.global.io().output.borrow_mut();
-    let values = stack!(self)[start.index()..start.index() + count.value()].iter();
-    writeln!(&mut output, "{}", values.join(" ")).map_err(Error::user)?;
-
-    Ok(())
+    let start = self.stack_base() + start.index();
+    let values = stack!(self)[start..start + count.value()].iter();
+    let text = values.join(" ").to_string();
+    let span = current_call_frame!(self).span_at(pc);
+    self.global.io().print(Stream::Stdout, &text, span)
   }
 
-  fn op_call(&mut self, return_addr: usize, callee: op::Register, args: op::Count) -> Result<Call> {
+  fn op_call(
+    &mut self,
+    return_addr: usize,
+    dst: op::Register,
+    callee: op::Register,
+    args: op::Count,
+  ) -> Result<Call> {
     self.print_stack();
-    vprintln!("call {callee}, {args} (ret={return_addr})");
+    vprintln!("call {dst}, {callee}, {args} (ret={return_addr})");
 
     let function = self.get_register(callee);
     let args = Args {
       start: self.stack_base() + callee.index() + 1,
       count: args.value(),
+      kw: false,
     };
 
     let Some(function) = function.clone().to_any() else {
-      fail!("`{function}` is not callable");
+      fail!(Type: "`{function}` is not callable");
     };
 
+    current_call_frame_mut!(self).return_dst = dst;
     self.do_call(function, args, return_addr)
   }
 
-  fn op_call0(&mut self, return_addr: usize) -> Result<Call> {
+  fn op_call_kw(
+    &mut self,
+    return_addr: usize,
+    dst: op::Register,
+    callee: op::Register,
+    args: op::Count,
+    names: op::Constant,
+  ) -> Result<Call> {
     self.print_stack();
-    vprintln!("call0 (ret={return_addr})");
+    vprintln!("call_kw {dst}, {callee}, {args}, {names} (ret={return_addr})");
 
-    let function = take(&mut self.acc);
-    let args = Args {
-      start: stack!(self).len(),
-      count: 0,
+    let function = self.get_register(callee);
+    let Some(function) = function.clone().to_any() else {
+      fail!(Type: "`{function}` is not callable");
+    };
+    let Constant::KwNames(names) = self.get_constant(names) else {
+      unreachable!("`call_kw` with constant which is not a list of keyword names");
     };
 
-    let Some(function) = function.clone().to_any() else {
-      fail!("`{function}` is not callable");
+    // the args are laid out in a new slice at the end of the stack, because
+    // there may be more params than args
+    let start = self.stack_base() + callee.index() + 1;
+    let values = &stack!(self)[start..start + args.value()];
+    let values =
+      if let Some((descriptor, has_implicit_receiver)) = function::descriptor_of(&function) {
+        resolve_kw_args(&descriptor.params, has_implicit_receiver, values, &names)?
+      } else if let Some(params) = builtin_params(&function) {
+        resolve_builtin_kw_args(params, values, &names)?
+      } else {
+        fail!(Type: "`{function}` does not accept keyword arguments");
+      };
+    let frame = current_call_frame_mut!(self);
+    frame.return_dst = dst;
+    // drop the arguments of the previous call
+    let frame_end = frame.stack_base + frame.frame_size;
+    self.truncate_stack(frame_end);
+    let args = Args {
+      kw: true,
+      ..self.push_args(&values)
     };
 
     self.do_call(function, args, return_addr)
   }
 
-  fn op_import(&mut self, path: op::Constant, return_addr: usize) -> Result<Call> {
+  fn op_import(
+    &mut self,
+    dst: op::Register,
+    path: op::Constant,
+    return_addr: usize,
+  ) -> Result<Call> {
     self.print_stack();
-    vprintln!("import {path} (ret={return_addr})");
+    vprintln!("import {dst}, {path} (ret={return_addr})");
 
     let path = self.get_constant_object::<Str>(path);
-    self.load_module(path, return_addr)
+    let base = current_call_frame!(self).module_id;
+    let base = self
+      .global
+      .get_module_by_id(base)
+      .map(|module| module.name.clone());
+    let path = ModulePath::resolve(base.as_ref().map(|name| name.as_str()), path.as_str())?;
+    if let Some(policy) = self.global.security_policy() {
+      policy.check_import(&path)?;
+    }
+    // `return_addr` points just past the `import` instruction
+    let span = current_call_frame!(self).span_at(return_addr - 1);
+    current_call_frame_mut!(self).return_dst = dst;
+    self.load_module(path, span, return_addr)
   }
 
-  fn op_finalize_module(&mut self) -> Result<(), Self::Error> {
+  fn op_finalize_module(&mut self, dst: op::Register) -> Result<(), Self::Error> {
     self.print_stack();
-    vprintln!("finalize_module");
+    vprintln!("finalize_module {dst}");
 
     let module_id = current_call_frame!(self).module_id;
     self.global.finish_module(module_id, true);
 
     let module = unsafe { self.global.get_module_by_id(module_id).unwrap_unchecked() };
-    self.acc = Value::object(module);
+    self.set_register(dst, Value::object(module));
 
     Ok(())
   }
 
-  fn op_return(&mut self) -> Result<Return> {
+  fn op_return(&mut self, src: op::Register) -> Result<Return> {
     self.print_stack();
-    vprintln!("return");
-
-    // return value is in the accumulator
+    vprintln!("return {src}");
 
+    let value = self.get_register(src);
     let stack = unsafe { self.stack.as_mut() };
 
     // pop frame
     debug_assert!(!stack.frames.is_empty());
     let frame = unsafe { stack.frames.pop().unwrap_unchecked() };
+    #[cfg(feature = "profiler")]
+    self
+      .global
+      .profiler()
+      .record_call(&frame.descriptor, frame.entered.elapsed());
 
     // truncate stack
     stack.regs.truncate(frame.stack_base);
+    let return_addr = frame.return_addr;
+    stack.recycle_frame(frame);
 
     if let Some(current_frame) = stack.frames.last() {
-      if let Some(return_addr) = frame.return_addr {
+      if let Some(return_addr) = return_addr {
+        let slot = current_frame.stack_base + current_frame.return_dst.index();
+        debug_assert!(slot < stack.regs.len(), "register out of bounds");
+        unsafe { *stack.regs.get_unchecked_mut(slot) = value };
         self.pc = return_addr;
+        // the frame's registers are gone, which may have dropped the last
+        // reference to objects with finalizers
+        if self.poll_finalizers(Box::new(move |thread, _| {
+          thread.pc = return_addr;
+          Ok(())
+        })) {
+          return Ok(Return::Yield);
+        }
         return Ok(Return::LoadFrame(LoadFrame {
           bytecode: current_frame.instructions,
           pc: self.pc,
@@ -1444,13 +2396,119 @@ impl Handler for Thread {
       }
     }
 
+    self.result = value;
     Ok(Return::Yield)
   }
 
-  fn op_yield(&mut self) -> Result<()> {
+  fn op_yield(&mut self, src: op::Register) -> Result<()> {
     self.print_stack();
-    vprintln!("yield");
+    vprintln!("yield {src}");
 
-    todo!()
+    // the dispatch loop returns to `run_coroutine` with the `pc` to continue
+    // from, which picks up the yielded value
+    if !self.in_coroutine {
+      fail!("cannot yield outside of a coroutine");
+    }
+    self.result = self.get_register(src);
+
+    Ok(())
+  }
+
+  fn op_raise(&mut self, src: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("raise {src}");
+
+    let value = self.get_register(src);
+    let Some(instance) = value
+      .clone()
+      .to_object::<ClassInstance>()
+      .filter(|instance| class::is_error(&self.global, instance))
+    else {
+      fail!(Type: "cannot raise `{value}`, only instances of `Error` may be raised");
+    };
+
+    let message = match instance.fields.get("message") {
+      Some(message) if !message.is_none() => format!("{}: {message}", instance.name),
+      _ => instance.name.to_string(),
+    };
+    Err(
+      error!(message)
+        .with_kind(ErrorKind::User(ErrorValue::new(&value)))
+        .into(),
+    )
+  }
+
+  fn op_defer(&mut self, callee: op::Register, args: op::Count) -> Result<()> {
+    self.print_stack();
+    vprintln!("defer {callee}, {args}");
+
+    let function = self.get_register(callee);
+    let Some(function) = function.clone().to_any() else {
+      fail!(Type: "`{function}` is not callable");
+    };
+    let args = callee
+      .offset(1)
+      .iter(args, 1)
+      .map(|reg| self.get_register(reg))
+      .collect();
+
+    current_call_frame_mut!(self)
+      .deferred
+      .push(Deferred { function, args });
+
+    Ok(())
+  }
+
+  fn op_has_deferred(&mut self, dst: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("has_deferred {dst}");
+
+    self.set_register(
+      dst,
+      Value::bool(!current_call_frame!(self).deferred.is_empty()),
+    );
+
+    Ok(())
+  }
+
+  fn op_call_deferred(&mut self, return_addr: usize, dst: op::Register) -> Result<Call> {
+    self.print_stack();
+    vprintln!("call_deferred {dst} (ret={return_addr})");
+
+    let frame = current_call_frame_mut!(self);
+    frame.return_dst = dst;
+    let Deferred { function, args } = frame.deferred.pop().unwrap();
+    // drop the arguments of the previous deferred call
+    let frame_end = frame.stack_base + frame.frame_size;
+    self.truncate_stack(frame_end);
+    let args = self.push_args(&args);
+
+    self.do_call(function, args, return_addr)
+  }
+
+  fn op_check_param(
+    &mut self,
+    param: op::Register,
+    name: op::Constant,
+    hint: op::Constant,
+  ) -> Result<()> {
+    self.print_stack();
+    vprintln!("check_param {param}, {name}, {hint}");
+
+    let value = self.get_register(param);
+    let name = self.get_constant_object::<Str>(name);
+    let hint = self.get_constant_object::<Str>(hint);
+    check_hint(&value, hint.as_str(), format_args!("param `{name}`"))
+  }
+
+  fn op_cmp_lt_jump_if_false(
+    &mut self,
+    dst: op::Register,
+    lhs: op::Register,
+    rhs: op::Register,
+    offset: op::Offset,
+  ) -> Result<super::dispatch::Jump> {
+    self.op_cmp_lt(dst, lhs, rhs)?;
+    self.op_jump_if_false(offset, dst)
   }
 }
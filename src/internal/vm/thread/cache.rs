@@ -0,0 +1,80 @@
+//! Inline caches for instructions which access fields by name.
+
+use std::cell::Cell;
+
+use crate::internal::bytecode::opcode::symbolic::AnyInstruction;
+use crate::internal::bytecode::operands::Width;
+use crate::internal::object::shape::ShapeId;
+
+/// An inline cache for a single field access instruction.
+///
+/// It remembers the shape of the object the instruction accessed the last
+/// time it ran, and the slot the field was found in. The slot is only used
+/// for objects with the same shape, so the cache misses whenever the layout
+/// is different, e.g. because the object is an instance of a different class.
+#[derive(Default)]
+pub struct FieldCache {
+  entry: Cell<Option<(ShapeId, u32)>>,
+}
+
+impl FieldCache {
+  /// The cached slot, if the cache was filled for `shape`.
+  pub fn get(&self, shape: ShapeId) -> Option<usize> {
+    match self.entry.get() {
+      Some((cached, slot)) if cached == shape => Some(slot as usize),
+      _ => None,
+    }
+  }
+
+  pub fn set(&self, shape: ShapeId, slot: usize) {
+    self
+      .entry
+      .set(u32::try_from(slot).ok().map(|slot| (shape, slot)));
+  }
+}
+
+/// The field caches of a function, one for each `load_field`,
+/// `load_field_opt`, and `store_field` instruction in its bytecode.
+#[derive(Default)]
+pub struct FieldCaches {
+  /// Sorted by the offset of the instruction.
+  sites: Box<[(usize, FieldCache)]>,
+}
+
+impl FieldCaches {
+  pub fn new(bytecode: &[u8]) -> Self {
+    let mut sites = Vec::new();
+    let mut remainder = bytecode;
+    while !remainder.is_empty() {
+      let Some((instruction, next)) = AnyInstruction::decode(remainder) else {
+        break;
+      };
+      if matches!(
+        instruction,
+        AnyInstruction::LoadField(_)
+          | AnyInstruction::LoadFieldOpt(_)
+          | AnyInstruction::StoreField(_)
+      ) {
+        // the dispatch loop identifies instructions by the offset of their
+        // opcode, which comes after the width prefix
+        let offset = bytecode.len() - remainder.len();
+        let pc = match Width::decode(remainder) {
+          Width::Normal => offset,
+          _ => offset + 1,
+        };
+        sites.push((pc, FieldCache::default()));
+      }
+      remainder = next;
+    }
+
+    Self {
+      sites: sites.into_boxed_slice(),
+    }
+  }
+
+  /// Returns the cache of the instruction at `pc`.
+  pub fn get(&self, pc: usize) -> Option<&FieldCache> {
+    let index = self.sites.binary_search_by_key(&pc, |(pc, _)| *pc).ok()?;
+    Some(&self.sites[index].1)
+  }
+}
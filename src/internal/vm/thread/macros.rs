@@ -52,35 +52,35 @@ macro_rules! current_call_frame_mut {
 
 macro_rules! binary {
   ($lhs:ident ** $rhs:ident {
-    i32 => $i32_expr:expr,
+    int => $int_expr:expr,
     f64 => $f64_expr:expr,
     any => $any_expr:expr,
   }) => {{
     binary!($lhs, $rhs {
-      i32 => $i32_expr,
+      int => $int_expr,
       f64 => $f64_expr,
       any => $any_expr,
-      bool => fail!("cannot `**` `bool`"),
-      none => fail!("cannot `**` `none`"),
-      incompatible_types => fail!("operands must have the same type: `{}`, `{}`", $lhs, $rhs),
+      bool => fail!(Type: "cannot `**` `bool`"),
+      none => fail!(Type: "cannot `**` `none`"),
+      incompatible_types => fail!(Type: "operands must have the same type: `{}`, `{}`", $lhs, $rhs),
     })
   }};
   ($lhs:ident $op:tt $rhs:ident {
-    i32 => $i32_expr:expr,
+    int => $int_expr:expr,
     f64 => $f64_expr:expr,
     any => $any_expr:expr,
   }) => {{
     binary!($lhs, $rhs {
-      i32 => $i32_expr,
+      int => $int_expr,
       f64 => $f64_expr,
       any => $any_expr,
-      bool => fail!("cannot `{}` `bool`", stringify!($op)),
-      none => fail!("cannot `{}` `none`", stringify!($op)),
-      incompatible_types => fail!("operands must have the same type: `{}`, `{}`", $lhs, $rhs),
+      bool => fail!(Type: "cannot `{}` `bool`", stringify!($op)),
+      none => fail!(Type: "cannot `{}` `none`", stringify!($op)),
+      incompatible_types => fail!(Type: "operands must have the same type: `{}`, `{}`", $lhs, $rhs),
     })
   }};
   ($lhs:ident, $rhs:ident {
-    i32 => $i32_expr:expr,
+    int => $int_expr:expr,
     f64 => $f64_expr:expr,
     any => $any_expr:expr,
     bool => $bool_expr:expr,
@@ -88,20 +88,17 @@ macro_rules! binary {
     incompatible_types => $on_different_object_types_expr:expr,
   }) => {{
     if $lhs.is_int() && $rhs.is_int() {
-      let $lhs = unsafe { $lhs.to_int_unchecked() };
-      let $rhs = unsafe { $rhs.to_int_unchecked() };
-      $i32_expr
+      let $lhs = unsafe { $lhs.to_int_unchecked() } as i64;
+      let $rhs = unsafe { $rhs.to_int_unchecked() } as i64;
+      $int_expr
     } else if $lhs.is_float() && $rhs.is_float() {
       let $lhs = unsafe { $lhs.to_float_unchecked() };
       let $rhs = unsafe { $rhs.to_float_unchecked() };
       $f64_expr
-    } else if $lhs.is_float() && $rhs.is_int() {
-      let $lhs = unsafe { $lhs.to_float_unchecked() };
-      let $rhs = unsafe { $rhs.to_int_unchecked() } as f64;
-      $f64_expr
-    } else if $lhs.is_int() && $rhs.is_float() {
-      let $lhs = unsafe { $lhs.to_int_unchecked() } as f64;
-      let $rhs = unsafe { $rhs.to_float_unchecked() };
+    } else if let (Some($lhs), Some($rhs)) = ($lhs.to_i64(), $rhs.to_i64()) {
+      // at least one of the operands is a boxed int
+      $int_expr
+    } else if let (Some($lhs), Some($rhs)) = ($lhs.to_number(), $rhs.to_number()) {
       $f64_expr
     } else if $lhs.is_bool() && $rhs.is_bool() {
       #[allow(unused_variables)]
@@ -1,5 +1,17 @@
 use super::*;
+use crate::internal::object::duration::Duration;
+use crate::internal::object::int::int;
+use crate::internal::value::constant::KwNames;
+use crate::internal::vm::IntOverflow;
 
+/// Whether `value` takes the `then` branch of a condition.
+///
+/// `false`, `none`, `0`, `0.0` and `NaN` are falsy, everything else is truthy.
+/// This is only used where a decision is made, e.g. `if`, `while` and `!`;
+/// `&&` and `||` evaluate to one of their operands, not to a `bool`.
+///
+/// Class instances and native class instances may override this with a
+/// `__bool__` method, see [`truthiness`].
 pub fn is_truthy(value: Value) -> bool {
   if value.is_bool() {
     return unsafe { value.to_bool_unchecked() };
@@ -22,6 +34,74 @@ pub fn is_truthy(value: Value) -> bool {
   true
 }
 
+/// The method `name` of a class instance or native class instance, bound to
+/// `value`.
+fn special_method(global: &Global, value: &Value, name: &str) -> Option<Ptr<Any>> {
+  let object = value.clone().to_any()?;
+  if let Some(instance) = object.clone_cast::<ClassInstance>() {
+    let method = instance.class.methods.get(name)?.clone();
+    let bound = BoundFunction::new(instance.into_any(), method);
+    Some(global.alloc(bound).into_any())
+  } else if let Some(instance) = object.clone_cast::<NativeClassInstance>() {
+    let method = instance.class.methods.get(name)?.clone();
+    let bound = NativeBoundFunction::new(Value::object(instance), method);
+    Some(global.alloc(bound).into_any())
+  } else {
+    None
+  }
+}
+
+/// The `__bool__` method of a class instance or native class instance, bound
+/// to `value`.
+pub fn bool_method(global: &Global, value: &Value) -> Option<Ptr<Any>> {
+  special_method(global, value, "__bool__")
+}
+
+/// Call a `__bool__` method returned by [`bool_method`].
+pub async fn call_bool_method(scope: &mut Scope<'_>, method: Ptr<Any>) -> Result<bool> {
+  let value = scope.thread.call(method, &[]).await?;
+  match value.clone().to_bool() {
+    Some(value) => Ok(value),
+    None => fail!(Type: "`__bool__` must return a bool, got `{value}`"),
+  }
+}
+
+/// Like [`is_truthy`], but calls the `__bool__` method of `value` if it has
+/// one.
+pub async fn truthiness(scope: &mut Scope<'_>, value: Value) -> Result<bool> {
+  match bool_method(&scope.thread.global, &value) {
+    Some(method) => call_bool_method(scope, method).await,
+    None => Ok(is_truthy(value)),
+  }
+}
+
+/// Compare `lhs` to `rhs`.
+///
+/// If `lhs` is a class instance or native class instance with a `__cmp__`
+/// method, it is called with `rhs`, and returns a number which is less than,
+/// equal to, or greater than zero if `lhs` is ordered before, the same as, or
+/// after `rhs`. Otherwise the values are compared with the same rules as `<`,
+/// and values which can't be compared are a type error.
+pub async fn compare(scope: &mut Scope<'_>, lhs: Value, rhs: Value) -> Result<Ordering> {
+  let Some(method) = special_method(&scope.thread.global, &lhs, "__cmp__") else {
+    return Thread::compare_values(scope.clone(), lhs, rhs);
+  };
+  let result = scope.thread.call(method, &[rhs]).await?;
+  match result.clone().to_number().and_then(|n| n.partial_cmp(&0.0)) {
+    Some(ordering) => Ok(ordering),
+    None => fail!(Type: "`__cmp__` must return a number, got `{result}`"),
+  }
+}
+
+/// Turn `value` back into a string if it is a builder created by
+/// `AppendStr`, see [`StringBuilder::implicit`].
+pub fn build_str(global: &Global, value: Value) -> Value {
+  match value.clone().to_object::<StringBuilder>() {
+    Some(builder) if builder.is_implicit() => Value::object(global.alloc(builder.build())),
+    _ => value,
+  }
+}
+
 pub fn clone_from_raw_slice<T: Clone>(ptr: *mut [T], index: usize) -> T {
   #[allow(dead_code)]
   struct Components<T> {
@@ -36,24 +116,233 @@ pub fn clone_from_raw_slice<T: Clone>(ptr: *mut [T], index: usize) -> T {
   std::mem::ManuallyDrop::into_inner(value.clone())
 }
 
+/// Check the number of args passed to a function with `params`, when none of
+/// them are passed by name.
 pub fn check_args(params: &Params, has_implicit_receiver: bool, num_args: usize) -> Result<()> {
+  check_positional_args(params, has_implicit_receiver, num_args)?;
+  if let Some(param) = params.kw.iter().find(|param| param.required) {
+    fail!(Arity: "missing keyword argument `{}`", param.name);
+  }
+  Ok(())
+}
+
+fn check_positional_args(
+  params: &Params,
+  has_implicit_receiver: bool,
+  num_args: usize,
+) -> Result<()> {
   let has_explicit_self_param = params.has_self && !has_implicit_receiver;
 
   let min = params.min as usize + has_explicit_self_param as usize;
   let max = params.max as usize + has_explicit_self_param as usize;
 
   if min > num_args || num_args > max {
+    // positional args can't be used for keyword-only params
+    let kw = match &params.kw[..] {
+      [] => String::new(),
+      [param] => format!(" (`{}` is keyword-only)", param.name),
+      kw => format!(
+        " ({} are keyword-only)",
+        kw.iter()
+          .map(|param| format!("`{}`", param.name))
+          .join(", ")
+      ),
+    };
     if min == max {
       let plural = if min != 1 { "s" } else { "" };
-      fail!("expected {min} arg{plural}, got {num_args}")
+      fail!(Arity: "expected {min} arg{plural}, got {num_args}{kw}")
     } else if num_args < min {
       let plural = if min != 1 { "s" } else { "" };
-      fail!("expected at least {min} arg{plural}, got {num_args}")
+      fail!(Arity: "expected at least {min} arg{plural}, got {num_args}")
     } else {
       let plural = if max != 1 { "s" } else { "" };
-      fail!("expected at most {max} arg{plural}, got {num_args}")
+      fail!(Arity: "expected at most {max} arg{plural}, got {num_args}{kw}")
     };
   }
 
   Ok(())
 }
+
+/// Lay out the `values` of a call to a function with `params`, where the
+/// last `names.len()` of them are passed by name.
+///
+/// The result has a value for every positional param, followed by one for
+/// every keyword-only param, and params which were not passed are `none`.
+pub fn resolve_kw_args(
+  params: &Params,
+  has_implicit_receiver: bool,
+  values: &[Value],
+  names: &KwNames,
+) -> Result<Vec<Value>> {
+  let (positional, named) = values.split_at(values.len() - names.len());
+  check_positional_args(params, has_implicit_receiver, positional.len())?;
+
+  let has_explicit_self_param = params.has_self && !has_implicit_receiver;
+  let max = params.max as usize + has_explicit_self_param as usize;
+  let mut args = positional.to_vec();
+  args.resize_with(max + params.kw.len(), Value::none);
+
+  let mut passed = vec![false; params.kw.len()];
+  for (name, value) in names.iter().zip(named) {
+    let Some(index) = params
+      .kw
+      .iter()
+      .position(|param| param.name.as_str() == name)
+    else {
+      fail!(Arity: "unexpected keyword argument `{name}`");
+    };
+    args[max + index] = value.clone();
+    passed[index] = true;
+  }
+  for (param, passed) in params.kw.iter().zip(passed) {
+    if param.required && !passed {
+      fail!(Arity: "missing keyword argument `{}`", param.name);
+    }
+  }
+
+  Ok(args)
+}
+
+/// The params of a builtin or native function which may be passed by name,
+/// if it has any.
+pub fn builtin_params(function: &Ptr<Any>) -> Option<&'static [&'static str]> {
+  let params = if let Some(builtin) = function.clone_cast::<BuiltinFunction>() {
+    builtin.params
+  } else if let Some(builtin) = function.clone_cast::<BuiltinAsyncFunction>() {
+    builtin.params
+  } else {
+    function.clone_cast::<NativeFunction>()?.params
+  };
+  Some(params).filter(|params| !params.is_empty())
+}
+
+/// Like [`resolve_kw_args`], but for a builtin function which may be passed
+/// any of `params` by name. Each of them is placed at the index of its name,
+/// and params before it which were not passed are `none`.
+pub fn resolve_builtin_kw_args(
+  params: &[&str],
+  values: &[Value],
+  names: &KwNames,
+) -> Result<Vec<Value>> {
+  let (positional, named) = values.split_at(values.len() - names.len());
+  let mut args = positional.to_vec();
+  for (name, value) in names.iter().zip(named) {
+    let Some(index) = params.iter().position(|param| *param == name) else {
+      fail!(Arity: "unexpected keyword argument `{name}`");
+    };
+    if index < positional.len() {
+      fail!(Arity: "argument `{name}` is passed both by position and by name");
+    }
+    if index >= args.len() {
+      args.resize_with(index + 1, Value::none);
+    }
+    args[index] = value.clone();
+  }
+  Ok(args)
+}
+
+/// Fail if `value` does not match the type hint `hint`.
+///
+/// A hint matches values for which `type_of` returns the same name, and
+/// class instances also match the names of their class and its parents.
+/// `any` matches every value. `what` names the checked param or field.
+pub fn check_hint(value: &Value, hint: &str, what: impl Display) -> Result<()> {
+  if hint == "any" {
+    return Ok(());
+  }
+
+  let name = match value.clone().to_object::<ClassInstance>() {
+    Some(instance) => {
+      let mut parent = instance.parent.clone();
+      while let Some(class) = parent {
+        if class.name.as_str() == hint {
+          return Ok(());
+        }
+        parent = class.parent.clone();
+      }
+      instance.name.as_str().to_string()
+    }
+    None if value.is_float() => "float".to_string(),
+    None if value.to_i64().is_some() => "int".to_string(),
+    None if value.is_bool() => "bool".to_string(),
+    None if value.is_none() => "none".to_string(),
+    None => unsafe { value.clone().to_any_unchecked() }
+      .type_name()
+      .to_string(),
+  };
+  if name != hint {
+    fail!(Type: "{what} expected `{hint}`, got `{name}`");
+  }
+  Ok(())
+}
+
+/// Divide `lhs` by `rhs`, rounding towards negative infinity.
+///
+/// Returns `None` if `rhs` is zero or the result overflows.
+pub fn checked_floor_div(lhs: i64, rhs: i64) -> Option<i64> {
+  let quotient = lhs.checked_div(rhs)?;
+  if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+    Some(quotient - 1)
+  } else {
+    Some(quotient)
+  }
+}
+
+/// The remainder of [`checked_floor_div`], which has the sign of `rhs`, so
+/// that `lhs == floor_div(lhs, rhs) * rhs + floor_rem(lhs, rhs)`.
+///
+/// Panics if `rhs` is zero.
+pub fn floor_rem(lhs: i64, rhs: i64) -> i64 {
+  let rem = lhs.wrapping_rem(rhs);
+  if rem != 0 && (rem < 0) != (rhs < 0) {
+    rem + rhs
+  } else {
+    rem
+  }
+}
+
+/// Like [`floor_rem`], but for floats.
+pub fn floor_rem_f64(lhs: f64, rhs: f64) -> f64 {
+  let rem = lhs % rhs;
+  if rem != 0.0 && (rem < 0.0) != (rhs < 0.0) {
+    rem + rhs
+  } else {
+    rem
+  }
+}
+
+/// If one of `lhs` and `rhs` is a [`Duration`] and the other is a number,
+/// returns both, which is the only mix of types that `*` and `/` accept.
+pub fn duration_and_number(lhs: &Value, rhs: &Value) -> Option<(Ptr<Duration>, f64)> {
+  if let (Some(duration), Some(n)) = (lhs.clone().to_object::<Duration>(), rhs.to_number()) {
+    return Some((duration, n));
+  }
+  None
+}
+
+/// Apply an integer binary operator, handling overflow according to the
+/// configured [`IntOverflow`] mode.
+///
+/// `checked` computes the result in the `i64` range, returning `None` on
+/// overflow, and `wide` computes the same operation on floats.
+pub fn int_binop(
+  global: &Global,
+  op: &str,
+  lhs: i64,
+  rhs: i64,
+  checked: impl FnOnce(i64, i64) -> Option<i64>,
+  wide: impl FnOnce(f64, f64) -> f64,
+) -> Result<Value> {
+  if let Some(value) = checked(lhs, rhs) {
+    return Ok(int(global, value));
+  }
+
+  match global.int_overflow() {
+    IntOverflow::Promote => Ok(Value::float(wide(lhs as f64, rhs as f64))),
+    IntOverflow::Saturate => {
+      // float to int casts saturate
+      Ok(int(global, wide(lhs as f64, rhs as f64) as i64))
+    }
+    IntOverflow::Error => fail!("integer overflow: `{lhs} {op} {rhs}`"),
+  }
+}
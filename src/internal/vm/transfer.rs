@@ -0,0 +1,175 @@
+//! Copying values between VMs.
+//!
+//! Every object belongs to the VM it was allocated in, so a value can only be
+//! moved to another VM by recreating it there. This works like the structured
+//! clone algorithm: objects which are reachable through more than one path are
+//! copied once, so both shared references and cycles are preserved.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::global::Global;
+use crate::internal::error::Result;
+use crate::internal::object::class::{ClassInstance, ClassType};
+use crate::internal::object::int::{self, BoxedInt};
+use crate::internal::object::table::{self, table_key};
+use crate::internal::object::{Any, Bytes, Function, List, Ptr, Str, Table, Tuple};
+use crate::internal::value::Value;
+
+/// Copy `value` into the VM which owns `target`.
+///
+/// Class instances are recreated from the class with the same name in the
+/// globals of `target`, which must have a method for every method of the
+/// original. Their other fields are copied. Functions, native objects and
+/// anything else which is tied to the VM it was created in can't be copied.
+pub fn deep_clone(value: &Value, target: &Global) -> Result<Value> {
+  Transfer::new(target, false).value(value)
+}
+
+pub struct Transfer<'a> {
+  target: &'a Global,
+  copies: HashMap<Key, Value>,
+  /// Set when copying within the VM which owns `target`. Objects which can't
+  /// be copied are then shared with the original instead of failing.
+  local: bool,
+}
+
+/// Identifies an object by its address.
+pub struct Key(pub Ptr<Any>);
+
+impl Hash for Key {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.0.ptr_hash(state)
+  }
+}
+
+impl PartialEq for Key {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.ptr_eq(&other.0)
+  }
+}
+
+impl Eq for Key {}
+
+impl<'a> Transfer<'a> {
+  /// Values copied by the same `Transfer` keep any references between them.
+  pub fn new(target: &'a Global, local: bool) -> Self {
+    Self {
+      target,
+      copies: HashMap::new(),
+      local,
+    }
+  }
+
+  pub fn value(&mut self, value: &Value) -> Result<Value> {
+    let Some(object) = value.clone().to_any() else {
+      // numbers, bools and `none` are stored inline
+      return Ok(value.clone());
+    };
+    if let Some(copy) = self.copies.get(&Key(object.clone())) {
+      return Ok(copy.clone());
+    }
+
+    if let Some(v) = object.clone_cast::<Str>() {
+      let copy = Value::object(self.target.alloc(Str::owned(v.as_str())));
+      return Ok(self.remember(object, copy));
+    }
+    if let Some(v) = object.clone_cast::<BoxedInt>() {
+      return Ok(int::int(self.target, v.value()));
+    }
+    #[cfg(feature = "bigint")]
+    if let Some(v) = object.clone_cast::<crate::internal::object::bigint::BigInt>() {
+      let copy = crate::internal::object::bigint::BigInt::new(v.value().clone());
+      return Ok(Value::object(self.target.alloc(copy)));
+    }
+    if let Some(v) = object.clone_cast::<Bytes>() {
+      let copy = Value::object(self.target.alloc(Bytes::from(v.to_vec())));
+      return Ok(self.remember(object, copy));
+    }
+    if let Some(v) = object.clone_cast::<List>() {
+      let list = self.target.alloc(List::with_capacity(v.len()));
+      self.remember(object, Value::object(list.clone()));
+      for item in v.iter() {
+        list.push(self.value(&item)?);
+      }
+      return Ok(Value::object(list));
+    }
+    if let Some(v) = object.clone_cast::<Tuple>() {
+      let items = v
+        .as_slice()
+        .iter()
+        .map(|item| self.value(item))
+        .collect::<Result<Vec<_>>>()?;
+      let copy = Value::object(self.target.alloc(Tuple::from(items)));
+      return Ok(self.remember(object, copy));
+    }
+    if let Some(v) = object.clone_cast::<Table>() {
+      let table = self.target.alloc(Table::with_capacity(v.len()));
+      self.remember(object, Value::object(table.clone()));
+      for (key, value) in v.entries() {
+        let key = match key.as_str() {
+          Some(key) => table::Key::Str(self.target.intern(key.as_str().to_string())),
+          None => table_key(&self.value(&key.to_value())?)?,
+        };
+        table.insert(key, self.value(&value)?);
+      }
+      return Ok(Value::object(table));
+    }
+    if let Some(v) = object.clone_cast::<ClassInstance>() {
+      return self.instance(object, v);
+    }
+
+    if self.local {
+      return Ok(value.clone());
+    }
+    fail!("cannot copy `{object}` to another VM")
+  }
+
+  fn instance(&mut self, object: Ptr<Any>, v: Ptr<ClassInstance>) -> Result<Value> {
+    let class = match self.local {
+      true => Some(v.class.clone()),
+      false => self
+        .target
+        .get(v.name.as_str())
+        .and_then(|class| class.to_object::<ClassType>()),
+    };
+    let Some(class) = class else {
+      fail!(
+        "cannot copy `{v}` to another VM, because it has no class named `{}`",
+        v.name
+      );
+    };
+
+    let instance = self
+      .target
+      .alloc(ClassInstance::new(self.target.clone(), class.clone()));
+    if class.methods.contains_key("__del__") {
+      self.target.register_finalizer(instance.clone().into_any());
+    }
+    self.remember(object, Value::object(instance.clone()));
+    for (key, value) in v.fields.entries() {
+      // fields are only ever set by name
+      let Some(key) = key.as_str() else {
+        continue;
+      };
+      let key = self.target.intern(key.as_str().to_string());
+      if !self.local && value.clone().to_object::<Function>().is_some() {
+        let has_method = instance
+          .fields
+          .get(&key)
+          .is_some_and(|method| method.to_object::<Function>().is_some());
+        if !has_method {
+          fail!("cannot copy `{v}` to another VM, because its class has no method `{key}`");
+        }
+        continue;
+      }
+      instance.insert_field(key, self.value(&value)?);
+    }
+    Ok(Value::object(instance))
+  }
+
+  fn remember(&mut self, object: Ptr<Any>, copy: Value) -> Value {
+    self.copies.insert(Key(object), copy.clone());
+    copy
+  }
+}
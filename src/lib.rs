@@ -1,5 +1,8 @@
 #![allow(dead_code)] // TEMP
 
+// the code generated by `hebi_derive` refers to `::hebi`
+extern crate self as hebi;
+
 #[macro_use]
 pub mod macros;
 
@@ -34,5 +37,5 @@ pub mod prelude {
   pub use super::serde::ValueDeserializer;
 }
 
-pub use internal::error::{Error, Result};
+pub use internal::error::{Error, ErrorKind, ErrorValue, Result};
 pub use public::*;
@@ -1,5 +1,8 @@
 #[macro_export]
 macro_rules! error {
+  ($kind:ident: $fmt:literal $(,$($arg:tt)*)?) => {
+    $crate::error!($fmt $(, $($arg)*)?).with_kind($crate::ErrorKind::$kind)
+  };
   ($fmt:literal $(,$($arg:tt)*)?) => {
     $crate::span::SpannedError::new(format!($fmt $(, $($arg)*)?), None)
   };
@@ -16,6 +19,9 @@ macro_rules! error {
 
 #[macro_export]
 macro_rules! fail {
+  ($kind:ident: $fmt:literal $(,$($arg:tt)*)?) => {
+    return Err($crate::error!($kind: $fmt $(,$($arg)*)?).into())
+  };
   ($fmt:literal $(,$($arg:tt)*)?) => {
     return Err($crate::error!($fmt $(,$($arg)*)?).into())
   };
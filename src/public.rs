@@ -3,45 +3,81 @@
 #[macro_use]
 mod macros;
 
-use std::cell::RefMut;
+use std::cell::{Ref, RefMut};
 use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
 use futures_util::TryFutureExt;
 
-use self::value::FromValuePack;
+use self::value::{FromValuePack, IntoValuePack};
 use crate::internal::error::{Error, Result};
 use crate::internal::object::function::Disassembly;
-use crate::internal::object::native::NativeClassInstance;
+use crate::internal::object::iter::NativeIter;
+use crate::internal::object::module::AnyModuleLoader;
+use crate::internal::object::native::{NativeClassInstance, NativeFunction};
 use crate::internal::object::{table, Ptr, Type};
 use crate::internal::value::Value as OwnedValue;
 use crate::internal::vm;
 use crate::internal::vm::global::{Input, Output};
-use crate::internal::vm::thread::{Args, Slot0, Thread};
+use crate::internal::vm::thread::{
+  Args, Slot0, Thread, DEFAULT_FRAME_RESERVE, DEFAULT_STACK_RESERVE,
+};
 use crate::internal::vm::{global, Config, Vm};
+use crate::span::Span;
 use crate::Cow;
 
 // public API
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+#[path = "public/derive.rs"]
+pub mod __derive;
+#[cfg(feature = "analysis")]
+pub mod analysis;
+pub mod bytecode;
+pub mod fmt;
 pub mod module;
 pub mod object;
+pub mod stdlib;
 pub mod value;
 
 pub use crate::fail;
-pub use crate::internal::object::module::ModuleLoader;
+pub use crate::internal::object::channel::Channel;
+pub use crate::internal::object::iter::{FromIter, NativeIterator};
+pub use crate::internal::object::module::{AsyncModuleLoader, ModuleLoader, ModulePath};
 pub use crate::internal::object::native::LocalBoxFuture;
+pub use crate::internal::vm::allocator::Allocator;
+pub use crate::internal::vm::cancel::CancellationToken;
+#[cfg(feature = "coverage")]
+pub use crate::internal::vm::coverage::{CoverageReport, ModuleCoverage};
+pub use crate::internal::vm::frozen::Frozen;
+pub use crate::internal::vm::global::{PrintHook, Stream};
+pub use crate::internal::vm::host::HostDispatcher;
+pub use crate::internal::vm::policy::{FsAccess, SecurityPolicy};
+#[cfg(feature = "profiler")]
+pub use crate::internal::vm::profiler::{FunctionProfile, OpcodeProfile, ProfileReport};
+pub use crate::internal::vm::program::CompiledProgram;
+pub use crate::internal::vm::safepoint::SafepointHook;
+pub use crate::internal::vm::IntOverflow;
 pub use crate::public::module::NativeModule;
+pub use crate::public::object::bytes::Bytes;
+pub use crate::public::object::function::TypedFunction;
 pub use crate::public::object::list::List;
 pub use crate::public::object::string::Str;
 pub use crate::public::object::table::Table;
-pub use crate::public::object::Any;
+pub use crate::public::object::{Any, BuiltinType};
 pub use crate::public::value::{FromValue, IntoValue, Value};
+#[cfg(feature = "derive")]
+pub use hebi_derive::{FromHebi, ToHebi};
 
 #[derive(Default)]
 pub struct Hebi {
   vm: Vm,
+  program: Option<Arc<CompiledProgram>>,
 }
 
 // # Safety
@@ -60,18 +96,18 @@ pub struct Hebi {
 // will never be accessed from two or more threads at the same time.
 unsafe impl Send for Hebi {}
 
-struct ForceSendFuture<F: Future<Output = Result<OwnedValue>>> {
+struct ForceSendFuture<F: Future> {
   fut: F,
 }
-impl<F: Future<Output = Result<OwnedValue>>> ForceSendFuture<F> {
+impl<F: Future> ForceSendFuture<F> {
   pub unsafe fn new(fut: F) -> Self {
     Self { fut }
   }
 }
-unsafe impl<F: Future<Output = Result<OwnedValue>>> Send for ForceSendFuture<F> {}
+unsafe impl<F: Future> Send for ForceSendFuture<F> {}
 impl<F> Future for ForceSendFuture<F>
 where
-  F: Future<Output = Result<OwnedValue>>,
+  F: Future,
 {
   type Output = F::Output;
 
@@ -86,9 +122,24 @@ where
 }
 
 pub struct HebiBuilder<M, I, O> {
-  module_loader: Option<Box<dyn crate::internal::object::module::ModuleLoader>>,
+  module_loader: Option<AnyModuleLoader>,
   input: Option<Box<dyn crate::internal::vm::global::Input>>,
   output: Option<Box<dyn crate::internal::vm::global::Output>>,
+  error_output: Option<Box<dyn crate::internal::vm::global::Output>>,
+  print_hook: Option<Box<dyn PrintHook>>,
+  safepoint_hook: Option<Box<dyn SafepointHook>>,
+  security_policy: Option<Box<dyn SecurityPolicy>>,
+  host_dispatcher: Option<Box<dyn HostDispatcher>>,
+  allocator: Option<Box<dyn Allocator>>,
+  int_overflow: IntOverflow,
+  optimize: bool,
+  type_checks: bool,
+  stack_reserve: usize,
+  frame_reserve: usize,
+  builtins: bool,
+  fs: bool,
+  os: bool,
+  args: Vec<String>,
   __: PhantomData<(M, I, O)>,
 }
 
@@ -101,9 +152,56 @@ impl<I, O> HebiBuilder<(), I, O> {
     module_loader: impl ModuleLoader + 'static,
   ) -> HebiBuilder<HasModuleLoader, I, O> {
     HebiBuilder {
-      module_loader: Some(Box::new(module_loader)),
+      module_loader: Some(AnyModuleLoader::Sync(Box::new(module_loader))),
       input: self.input,
       output: self.output,
+      error_output: self.error_output,
+      print_hook: self.print_hook,
+      safepoint_hook: self.safepoint_hook,
+      security_policy: self.security_policy,
+      host_dispatcher: self.host_dispatcher,
+      allocator: self.allocator,
+      int_overflow: self.int_overflow,
+      optimize: self.optimize,
+      type_checks: self.type_checks,
+      stack_reserve: self.stack_reserve,
+      frame_reserve: self.frame_reserve,
+      builtins: self.builtins,
+      fs: self.fs,
+      os: self.os,
+      args: self.args,
+      __: PhantomData,
+    }
+  }
+
+  /// Like [`HebiBuilder::module_loader`], but the loader returns a future.
+  ///
+  /// Importing a module which is not yet loaded suspends the VM until the
+  /// future resolves, so this should be used together with
+  /// [`Hebi::eval_async`] or [`Hebi::run_async`].
+  pub fn async_module_loader(
+    self,
+    module_loader: impl AsyncModuleLoader + 'static,
+  ) -> HebiBuilder<HasModuleLoader, I, O> {
+    HebiBuilder {
+      module_loader: Some(AnyModuleLoader::Async(Box::new(module_loader))),
+      input: self.input,
+      output: self.output,
+      error_output: self.error_output,
+      print_hook: self.print_hook,
+      safepoint_hook: self.safepoint_hook,
+      security_policy: self.security_policy,
+      host_dispatcher: self.host_dispatcher,
+      allocator: self.allocator,
+      int_overflow: self.int_overflow,
+      optimize: self.optimize,
+      type_checks: self.type_checks,
+      stack_reserve: self.stack_reserve,
+      frame_reserve: self.frame_reserve,
+      builtins: self.builtins,
+      fs: self.fs,
+      os: self.os,
+      args: self.args,
       __: PhantomData,
     }
   }
@@ -118,6 +216,21 @@ impl<M, O> HebiBuilder<M, (), O> {
       module_loader: self.module_loader,
       input: Some(Box::new(input)),
       output: self.output,
+      error_output: self.error_output,
+      print_hook: self.print_hook,
+      safepoint_hook: self.safepoint_hook,
+      security_policy: self.security_policy,
+      host_dispatcher: self.host_dispatcher,
+      allocator: self.allocator,
+      int_overflow: self.int_overflow,
+      optimize: self.optimize,
+      type_checks: self.type_checks,
+      stack_reserve: self.stack_reserve,
+      frame_reserve: self.frame_reserve,
+      builtins: self.builtins,
+      fs: self.fs,
+      os: self.os,
+      args: self.args,
       __: PhantomData,
     }
   }
@@ -132,26 +245,205 @@ impl<M, I> HebiBuilder<M, I, ()> {
       module_loader: self.module_loader,
       input: self.input,
       output: Some(Box::new(output)),
+      error_output: self.error_output,
+      print_hook: self.print_hook,
+      safepoint_hook: self.safepoint_hook,
+      security_policy: self.security_policy,
+      host_dispatcher: self.host_dispatcher,
+      allocator: self.allocator,
+      int_overflow: self.int_overflow,
+      optimize: self.optimize,
+      type_checks: self.type_checks,
+      stack_reserve: self.stack_reserve,
+      frame_reserve: self.frame_reserve,
+      builtins: self.builtins,
+      fs: self.fs,
+      os: self.os,
+      args: self.args,
       __: PhantomData,
     }
   }
 }
 
 impl<M, I, O> HebiBuilder<M, I, O> {
+  /// Set where the `eprint` builtin writes to.
+  ///
+  /// Defaults to [`std::io::stderr`].
+  pub fn error_output(mut self, error_output: impl Output + 'static) -> Self {
+    self.error_output = Some(Box::new(error_output));
+    self
+  }
+
+  /// Pass each line printed by a script to `hook`, instead of writing it to
+  /// the output or error output.
+  pub fn print_hook(mut self, hook: impl PrintHook + 'static) -> Self {
+    self.print_hook = Some(Box::new(hook));
+    self
+  }
+
+  /// Call `hook` at every safepoint, which is the back-edge of every loop
+  /// and every call made by a script.
+  pub fn safepoint_hook(mut self, hook: impl SafepointHook) -> Self {
+    self.safepoint_hook = Some(Box::new(hook));
+    self
+  }
+
+  /// Consult `policy` before scripts import modules, call native functions,
+  /// or write to globals.
+  ///
+  /// By default, scripts are allowed to do all of these.
+  pub fn security_policy(mut self, policy: impl SecurityPolicy + 'static) -> Self {
+    self.security_policy = Some(Box::new(policy));
+    self
+  }
+
+  /// Route `host.call(name, ...args)` in scripts to `dispatcher`.
+  ///
+  /// Without a dispatcher, `host.call` fails.
+  pub fn host_dispatcher(mut self, dispatcher: impl HostDispatcher) -> Self {
+    self.host_dispatcher = Some(Box::new(dispatcher));
+    self
+  }
+
+  /// Allocate script objects using `allocator`, instead of the global
+  /// allocator.
+  pub fn allocator(mut self, allocator: impl Allocator) -> Self {
+    self.allocator = Some(Box::new(allocator));
+    self
+  }
+
+  /// Set what happens when integer arithmetic overflows.
+  ///
+  /// Defaults to [`IntOverflow::Promote`].
+  pub fn int_overflow(mut self, int_overflow: IntOverflow) -> Self {
+    self.int_overflow = int_overflow;
+    self
+  }
+
+  /// Run an optimization pass over the emitted bytecode, which folds
+  /// constant expressions and removes redundant instructions.
+  ///
+  /// Defaults to `false`.
+  pub fn optimize(mut self, optimize: bool) -> Self {
+    self.optimize = optimize;
+    self
+  }
+
+  /// Check the type hints of params and class fields at runtime, and raise
+  /// a type error when a value does not match its hint.
+  ///
+  /// Only code compiled after this is set is checked.
+  ///
+  /// Defaults to `false`.
+  pub fn type_checks(mut self, type_checks: bool) -> Self {
+    self.type_checks = type_checks;
+    self
+  }
+
+  /// Set the number of registers to allocate up front.
+  ///
+  /// The register stack grows as needed, and is never shrunk, so this only
+  /// avoids reallocating it while it grows to its final size.
+  ///
+  /// Defaults to `64`.
+  pub fn stack_reserve(mut self, registers: usize) -> Self {
+    self.stack_reserve = registers;
+    self
+  }
+
+  /// Set the number of call frames to allocate up front.
+  ///
+  /// Like [`HebiBuilder::stack_reserve`], but for the call stack.
+  ///
+  /// Defaults to `8`.
+  pub fn frame_reserve(mut self, frames: usize) -> Self {
+    self.frame_reserve = frames;
+    self
+  }
+
+  /// Register the native modules in [`stdlib`], such as `math`, `time` and `json`.
+  ///
+  /// Only the modules enabled by the crate's features are registered.
+  pub fn with_builtins(mut self) -> Self {
+    self.builtins = true;
+    self
+  }
+
+  /// Register the [`stdlib::fs`] module, which gives scripts access to the
+  /// file system. Every access is still checked by
+  /// [`SecurityPolicy::check_fs`].
+  ///
+  /// Defaults to `false`.
+  #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+  pub fn allow_fs(mut self, allow: bool) -> Self {
+    self.fs = allow;
+    self
+  }
+
+  /// Register the [`stdlib::os`] module and the `env` builtin, which give
+  /// scripts access to the environment, and let them run other programs if
+  /// the `process` feature is enabled. Reading a variable is still checked
+  /// by [`SecurityPolicy::check_env`], and running a program by
+  /// [`SecurityPolicy::check_process`].
+  ///
+  /// Defaults to `false`.
+  #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+  pub fn allow_os(mut self, allow: bool) -> Self {
+    self.os = allow;
+    self
+  }
+
+  /// Set the arguments passed to scripts, which they access through the
+  /// `argv` builtin list.
+  ///
+  /// Defaults to an empty list.
+  pub fn args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+    self.args = args.into_iter().map(Into::into).collect();
+    self
+  }
+
   pub fn finish(self) -> Hebi {
-    Hebi {
+    let mut hebi = Hebi {
       vm: Vm::with_config(Config {
         module_loader: self.module_loader,
         input: self.input,
         output: self.output,
+        error_output: self.error_output,
+        print_hook: self.print_hook,
+        safepoint_hook: self.safepoint_hook,
+        security_policy: self.security_policy,
+        host_dispatcher: self.host_dispatcher,
+        allocator: self.allocator,
+        int_overflow: self.int_overflow,
+        optimize: self.optimize,
+        type_checks: self.type_checks,
+        stack_reserve: self.stack_reserve,
+        frame_reserve: self.frame_reserve,
+        args: self.args,
       }),
+      program: None,
+    };
+    if self.builtins {
+      for module in stdlib::modules() {
+        hebi.register(&module);
+      }
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    if self.fs {
+      hebi.register(&stdlib::fs::module());
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    if self.os {
+      hebi.register(&stdlib::os::module());
+      crate::internal::object::builtin::register_env(&hebi.vm.root.global);
     }
+    hebi
   }
 }
 
 impl Hebi {
   pub fn new() -> Self {
-    Self { vm: Vm::default() }
+    Self::default()
   }
 
   pub fn builder() -> HebiBuilder<(), (), ()> {
@@ -159,6 +451,21 @@ impl Hebi {
       module_loader: None,
       input: None,
       output: None,
+      error_output: None,
+      print_hook: None,
+      safepoint_hook: None,
+      security_policy: None,
+      host_dispatcher: None,
+      allocator: None,
+      int_overflow: IntOverflow::default(),
+      optimize: false,
+      type_checks: false,
+      stack_reserve: DEFAULT_STACK_RESERVE,
+      frame_reserve: DEFAULT_FRAME_RESERVE,
+      builtins: false,
+      fs: false,
+      os: false,
+      args: Vec::new(),
       __: PhantomData,
     }
   }
@@ -181,6 +488,61 @@ impl Hebi {
     unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
   }
 
+  /// Evaluate `code`, stopping with an error of kind
+  /// [`ErrorKind::Cancelled`][crate::ErrorKind::Cancelled] if `token` is
+  /// cancelled before it finishes.
+  ///
+  /// `token` may also be a [`Duration`][std::time::Duration], which limits
+  /// how long the evaluation may run for. See [`CancellationToken`].
+  pub fn eval_with_cancellation<'cx, 'src>(
+    &'cx mut self,
+    code: &'src str,
+    token: impl Into<CancellationToken>,
+  ) -> Result<Value<'cx>>
+  where
+    'src: 'cx,
+  {
+    let global = self.vm.global.clone();
+    let previous = global.set_cancellation(Some(token.into()));
+    let result = pollster::block_on(self.vm.eval(code));
+    global.set_cancellation(previous);
+    result.map(|value| unsafe { value.bind_raw::<'cx>() })
+  }
+
+  /// Start evaluating `code`, without running any of it yet.
+  ///
+  /// The evaluation is driven by calling [`Hebi::poll_eval`] until it returns
+  /// [`Poll::Ready`], which lets the VM be ticked by a custom executor or a
+  /// game loop. Other code may not be evaluated until it finishes.
+  pub fn start_eval(&mut self, code: &str) -> Result<()> {
+    let chunk = self.vm.compile(code)?;
+    self.vm.start(chunk)
+  }
+
+  /// Run the evaluation started by [`Hebi::start_eval`] until it finishes, or
+  /// until it awaits a native future which is not ready.
+  ///
+  /// In that case, `Poll::Pending` is returned, and the waker in `cx` is woken
+  /// once the future can make progress.
+  ///
+  /// ```rust
+  /// use std::task::{Context, Poll, Waker};
+  ///
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.start_eval("1 + 2").unwrap();
+  /// let mut cx = Context::from_waker(Waker::noop());
+  /// let Poll::Ready(value) = hebi.poll_eval(&mut cx) else {
+  ///   unreachable!()
+  /// };
+  /// assert_eq!(value.unwrap().as_int(), Some(3));
+  /// ```
+  pub fn poll_eval(&mut self, cx: &mut Context<'_>) -> Poll<Result<Value<'_>>> {
+    self
+      .vm
+      .poll(cx)
+      .map_ok(|value| unsafe { value.bind_raw::<'_>() })
+  }
+
   pub fn compile<'cx>(&self, code: &str) -> Result<Chunk<'cx>> {
     self.vm.compile(code).map(|chunk| Chunk {
       inner: chunk,
@@ -200,6 +562,74 @@ impl Hebi {
     unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
   }
 
+  /// Compile `code` into a program which can be shared between VMs, including
+  /// ones running on other threads.
+  pub fn compile_program(&self, code: &str) -> Result<CompiledProgram> {
+    self
+      .vm
+      .compile(code)
+      .map(|chunk| CompiledProgram::new(&chunk))
+  }
+
+  /// Create a VM with the default configuration which runs `program` when
+  /// [`Hebi::run_compiled`] is called.
+  ///
+  /// ```rust
+  /// use std::sync::Arc;
+  ///
+  /// use hebi::Hebi;
+  ///
+  /// let program = Arc::new(Hebi::new().compile_program("1 + 2").unwrap());
+  /// let handles = (0..4)
+  ///   .map(|_| {
+  ///     let program = program.clone();
+  ///     std::thread::spawn(move || {
+  ///       let mut hebi = Hebi::from_compiled(program);
+  ///       hebi.run_compiled().unwrap().as_int()
+  ///     })
+  ///   })
+  ///   .collect::<Vec<_>>();
+  /// for handle in handles {
+  ///   assert_eq!(handle.join().unwrap(), Some(3));
+  /// }
+  /// ```
+  pub fn from_compiled(program: Arc<CompiledProgram>) -> Self {
+    Self {
+      vm: Vm::default(),
+      program: Some(program),
+    }
+  }
+
+  /// Link `program` into this VM, so that it can be executed with
+  /// [`Hebi::run`].
+  pub fn load<'cx>(&self, program: &CompiledProgram) -> Chunk<'cx> {
+    Chunk {
+      inner: program.link(&self.vm.global),
+      lifetime: PhantomData,
+    }
+  }
+
+  /// Run the program this VM was created with in [`Hebi::from_compiled`].
+  pub fn run_compiled(&mut self) -> Result<Value<'_>> {
+    pollster::block_on(self.run_compiled_async())
+  }
+
+  pub fn run_compiled_async<'cx>(
+    &'cx mut self,
+  ) -> impl Future<Output = Result<Value<'cx>>> + Send + 'cx {
+    let chunk = self
+      .program
+      .clone()
+      .map(|program| program.link(&self.vm.global));
+    let fut = async move {
+      match chunk {
+        Some(chunk) => self.vm.entry(chunk).await,
+        None => fail!("this VM was not created from a compiled program"),
+      }
+    };
+    unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
+  }
+
   pub fn global(&self) -> Global {
     Global {
       inner: self.vm.root.global.clone(),
@@ -210,6 +640,180 @@ impl Hebi {
   pub fn register(&mut self, module: &NativeModule) {
     self.vm.register(module)
   }
+
+  /// Get a handle to the global function `name`, which may be called with
+  /// arguments of type `Args`, and returns a value of type `Ret`.
+  ///
+  /// Fails if `name` is not a function, or if it is a script function which
+  /// cannot be called with as many arguments as there are in `Args`.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("fn add(a, b):\n  return a + b").unwrap();
+  /// let mut add = hebi.get_function::<(i32, i32), i32>("add").unwrap();
+  /// assert_eq!(add.call((1, 2)).unwrap(), 3);
+  /// ```
+  pub fn get_function<Args, Ret>(&mut self, name: &str) -> Result<TypedFunction<'_, Args, Ret>>
+  where
+    Args: for<'a> IntoValuePack<'a>,
+    Ret: for<'a> FromValue<'a>,
+  {
+    TypedFunction::new(self, name)
+  }
+
+  /// Add a method called `name` to every value of the builtin type `T`, which
+  /// is one of [`Str`], [`List`], [`Table`], `i32` or `f64`.
+  ///
+  /// `f` receives the value which the method was called on, and the rest of
+  /// the arguments through the [`Scope`]. A method which the type already has
+  /// is not replaced.
+  ///
+  /// ```rust
+  /// use hebi::{Hebi, Str};
+  ///
+  /// let mut hebi = Hebi::new();
+  /// hebi.extend_builtin("shout", |_, this: Str| this.as_str().to_uppercase());
+  /// assert_eq!(hebi.eval(r#""hi".shout()"#).unwrap().to_string(), "HI");
+  /// ```
+  pub fn extend_builtin<'cx, T, R>(
+    &mut self,
+    name: impl ToString,
+    f: impl Fn(Scope<'cx>, T) -> R + Send + Sync + 'static,
+  ) where
+    T: BuiltinType<'cx> + 'static,
+    R: IntoValue<'cx> + 'static,
+  {
+    let global = &self.vm.global;
+    let function = global.alloc(NativeFunction {
+      name: global.intern(name.to_string()),
+      cb: module::wrap_builtin_method(f),
+      params: &[],
+    });
+    global.register_builtin_method(T::kind(), function);
+  }
+
+  /// Store `value` in the VM, so that native functions can access it through
+  /// [`Scope::user_data`]. The VM holds at most one value of each type, so
+  /// this returns the value of type `T` which was stored before, if any.
+  ///
+  /// This is meant for state which belongs to the host application, such as
+  /// a database connection pool, which would otherwise have to be a global
+  /// static or captured by every native function. Native functions only get
+  /// a shared reference to it, so any state they modify should use interior
+  /// mutability.
+  ///
+  /// ```rust
+  /// use std::cell::Cell;
+  ///
+  /// use hebi::prelude::*;
+  ///
+  /// struct Counter(Cell<i32>);
+  ///
+  /// fn bump(scope: Scope<'_>) -> hebi::Result<i32> {
+  ///   let Some(counter) = scope.user_data::<Counter>() else {
+  ///     hebi::fail!("no counter");
+  ///   };
+  ///   counter.0.set(counter.0.get() + 1);
+  ///   Ok(counter.0.get())
+  /// }
+  ///
+  /// let mut hebi = Hebi::new();
+  /// hebi.set_user_data(Counter(Cell::new(0)));
+  /// hebi.register(&NativeModule::builder("counter").function("bump", bump).finish());
+  /// hebi.eval("import counter\ncounter.bump()\ncounter.bump()").unwrap();
+  /// assert_eq!(hebi.user_data::<Counter>().unwrap().0.get(), 2);
+  /// ```
+  ///
+  /// Panics if a native function is still borrowing user data, which may
+  /// happen while an async native function is suspended.
+  pub fn set_user_data<T: Send + 'static>(&mut self, value: T) -> Option<T> {
+    self.vm.global.set_user_data(value)
+  }
+
+  /// Borrow the value of type `T` stored by [`Hebi::set_user_data`].
+  pub fn user_data<T: Send + 'static>(&self) -> Option<Ref<'_, T>> {
+    self.vm.global.user_data::<T>()
+  }
+
+  /// Remove the value of type `T` stored by [`Hebi::set_user_data`], and
+  /// return it.
+  ///
+  /// Panics under the same conditions as [`Hebi::set_user_data`].
+  pub fn remove_user_data<T: Send + 'static>(&mut self) -> Option<T> {
+    self.vm.global.remove_user_data::<T>()
+  }
+
+  /// Save a copy of the global variables, and of the variables of every
+  /// loaded module, which can later be passed to [`Hebi::restore_globals`].
+  ///
+  /// Lists, tables, tuples and class instances are copied. Functions, classes,
+  /// modules and native objects are shared with the snapshot, so changes to
+  /// them are not undone by restoring it.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("scores := [1, 2]").unwrap();
+  /// let snapshot = hebi.snapshot_globals();
+  /// hebi.eval("scores.push(3)").unwrap();
+  /// hebi.restore_globals(&snapshot).unwrap();
+  /// assert_eq!(hebi.eval("scores.len()").unwrap().as_int(), Some(2));
+  /// ```
+  pub fn snapshot_globals(&self) -> GlobalsSnapshot {
+    let global = &self.vm.global;
+    let snapshot = vm::snapshot::Snapshot::take(global);
+    let (id, released) = global.snapshots().insert(snapshot);
+    GlobalsSnapshot { id, released }
+  }
+
+  /// Replace the global variables and module variables with the ones saved
+  /// in `snapshot`. Globals defined after the snapshot was taken are removed.
+  ///
+  /// The snapshot is not consumed, so it may be restored more than once.
+  /// Fails if `snapshot` was taken from a different [`Hebi`] instance.
+  pub fn restore_globals(&mut self, snapshot: &GlobalsSnapshot) -> Result<()> {
+    let global = &self.vm.global;
+    let mut snapshots = global.snapshots();
+    let Some(snapshot) = snapshots.get(snapshot.id, &snapshot.released) else {
+      fail!("cannot restore a snapshot taken from a different VM");
+    };
+    snapshot.restore(global);
+    Ok(())
+  }
+
+  /// Returns how many times each instruction was dispatched and how much time
+  /// was spent in each script function since the VM was created, or since the
+  /// last call to [`Hebi::reset_profile`].
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("fn f():\n  return 1\nf()\nf()").unwrap();
+  /// let report = hebi.profile_report();
+  /// let f = report.functions.iter().find(|f| f.name == "f").unwrap();
+  /// assert_eq!(f.calls, 2);
+  /// ```
+  #[cfg(feature = "profiler")]
+  pub fn profile_report(&self) -> ProfileReport {
+    self.vm.global.profiler().report()
+  }
+
+  /// Clear all data collected by the profiler.
+  #[cfg(feature = "profiler")]
+  pub fn reset_profile(&self) {
+    self.vm.global.profiler().reset()
+  }
+
+  /// Returns the lines of each script module which were executed so far.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("v := 0\nif v > 0:\n  v = 1\nv").unwrap();
+  /// let main = &hebi.coverage().modules["__main__"];
+  /// assert!(main.executed.contains(&1) && !main.executed.contains(&3));
+  /// ```
+  #[cfg(feature = "coverage")]
+  pub fn coverage(&self) -> CoverageReport {
+    self.vm.global.coverage().report()
+  }
 }
 
 impl Debug for Hebi {
@@ -218,6 +822,30 @@ impl Debug for Hebi {
   }
 }
 
+/// A copy of the global state of a [`Hebi`] instance, created by
+/// [`Hebi::snapshot_globals`].
+///
+/// The copied values are stored in the VM they were taken from, and are freed
+/// when this handle is dropped.
+pub struct GlobalsSnapshot {
+  id: u64,
+  released: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Drop for GlobalsSnapshot {
+  fn drop(&mut self) {
+    if let Ok(mut released) = self.released.lock() {
+      released.push(self.id);
+    }
+  }
+}
+
+impl Debug for GlobalsSnapshot {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("GlobalsSnapshot").field(&self.id).finish()
+  }
+}
+
 #[derive(Clone)]
 pub struct Chunk<'cx> {
   pub(crate) inner: vm::Chunk,
@@ -228,6 +856,12 @@ impl<'cx> Chunk<'cx> {
   pub fn disassemble(&self) -> Disassembly {
     self.inner.disassemble()
   }
+
+  /// Like [`Chunk::disassemble`], but each block of instructions is preceded
+  /// by the line of source code it was compiled from.
+  pub fn disassemble_with_source(&self) -> Disassembly<'_> {
+    self.inner.disassemble_with_source()
+  }
 }
 
 #[derive(Clone)]
@@ -248,6 +882,18 @@ impl<'cx> Global<'cx> {
     self.inner.set(key.unbind(), value.unbind());
   }
 
+  /// Define a global protocol called `name`. Any value which has all of
+  /// `methods` is considered an instance of it by the `is` operator.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.global().register_protocol("Sized", &["len"]);
+  /// assert_eq!(hebi.eval("[] is Sized").unwrap().as_bool(), Some(true));
+  /// ```
+  pub fn register_protocol(&self, name: &str, methods: &[&str]) {
+    self.inner.register_protocol(name, methods);
+  }
+
   pub fn print(&self, f: impl Display) -> Result<()> {
     write!(&mut self.inner.io().output.borrow_mut(), "{f}").map_err(Error::user)
   }
@@ -262,10 +908,32 @@ impl<'cx> Global<'cx> {
     })
   }
 
+  pub fn error_output(&mut self) -> RefMut<'_, dyn Output> {
+    RefMut::map(self.inner.io().error_output.borrow_mut(), |output| {
+      output.as_mut()
+    })
+  }
+
   pub fn input(&mut self) -> RefMut<'_, dyn Input> {
     RefMut::map(self.inner.io().input.borrow_mut(), |input| input.as_mut())
   }
 
+  /// The names of all modules which have been imported or registered.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.register(&hebi::NativeModule::builder("m").finish());
+  /// assert_eq!(hebi.global().module_names(), ["m"]);
+  /// ```
+  pub fn module_names(&self) -> Vec<String> {
+    self
+      .inner
+      .module_names()
+      .into_iter()
+      .map(|name| name.as_str().to_string())
+      .collect()
+  }
+
   pub fn entries<'a>(&'a self) -> GlobalEntries<'a, 'cx> {
     GlobalEntries {
       entries: self.inner.entries(),
@@ -283,10 +951,11 @@ impl<'a, 'cx> Iterator for GlobalEntries<'a, 'cx> {
   type Item = (Str<'cx>, Value<'cx>);
 
   fn next(&mut self) -> Option<Self::Item> {
-    self
-      .entries
-      .next()
-      .map(|(key, value)| unsafe { (key.bind_raw::<'cx>(), value.bind_raw::<'cx>()) })
+    // globals are only ever set by name
+    self.entries.find_map(|(key, value)| {
+      let key = key.as_str()?.clone();
+      Some(unsafe { (key.bind_raw::<'cx>(), value.bind_raw::<'cx>()) })
+    })
   }
 }
 
@@ -301,7 +970,8 @@ pub struct Scope<'cx> {
 impl<'cx> Scope<'cx> {
   pub(crate) fn new(parent: &Thread, stack_base: usize, args: Args) -> Self {
     debug_assert!(unsafe { parent.stack.as_ref() }.regs.len() >= args.start + args.count);
-    let thread = Thread::new(parent.global.clone(), parent.stack);
+    let mut thread = Thread::new(parent.global.clone(), parent.stack);
+    thread.pc = parent.pc;
     Scope {
       thread,
       stack_base,
@@ -332,11 +1002,55 @@ impl<'cx> Scope<'cx> {
     self.args.count
   }
 
+  /// The span of the call to the native function in the script which made
+  /// it. Errors raised at this span point at the call:
+  ///
+  /// ```rust
+  /// use hebi::prelude::*;
+  ///
+  /// fn positive(scope: Scope<'_>) -> hebi::Result<i32> {
+  ///   let n = scope.param::<i32>(0)?;
+  ///   if n <= 0 {
+  ///     hebi::fail!(@scope.call_span(), "expected a positive number, got {n}");
+  ///   }
+  ///   Ok(n)
+  /// }
+  ///
+  /// let mut hebi = Hebi::new();
+  /// hebi.register(&NativeModule::builder("check").function("positive", positive).finish());
+  /// let src = "import check\ncheck.positive(-1)";
+  /// let e = hebi.eval(src).unwrap_err();
+  /// assert!(e.report(src, false).contains("check.positive(-1)"));
+  /// ```
+  ///
+  /// The span is empty if the function was called by the host.
+  pub fn call_span(&self) -> Span {
+    self.thread.call_span()
+  }
+
+  /// The name of the module which called the native function, or `None` if
+  /// it was called from code passed to [`Hebi::eval`] directly.
+  pub fn module_name(&self) -> Option<String> {
+    self
+      .thread
+      .call_module_name()
+      .map(|name| name.as_str().to_string())
+  }
+
+  /// Borrow the value of type `T` which the host stored with
+  /// [`Hebi::set_user_data`], or `None` if there is no such value.
+  ///
+  /// The borrow should not be held across an `.await`, because the host
+  /// can't replace the value while it is borrowed.
+  pub fn user_data<T: Send + 'static>(&self) -> Option<Ref<'_, T>> {
+    self.thread.global.user_data::<T>()
+  }
+
   pub fn params<T: FromValuePack<'cx>>(&self) -> Result<T::Output> {
     let stack = unsafe { self.thread.stack.as_ref() };
     let range = self.args.start..self.args.start + self.args.count;
     let Some(args) = stack.regs.get(range) else {
-      fail!("expected {} args, got {}", T::len(), self.args.count);
+      fail!(Arity: "expected {} args, got {}", T::len(), self.args.count);
     };
     T::from_value_pack(args, self.global())
   }
@@ -345,12 +1059,62 @@ impl<'cx> Scope<'cx> {
     let stack = unsafe { self.thread.stack.as_ref() };
     let index = self.args.start + n;
     let Some(value) = stack.regs.get(index).cloned() else {
-      fail!("missing argument {n}");
+      fail!(Arity: "missing argument {n}");
     };
     let value = unsafe { value.bind_raw::<'cx>() };
     T::from_value(value, self.global())
   }
 
+  /// Borrow the string passed as the `n`th argument, without copying it.
+  ///
+  /// The arguments keep the string alive until the call returns, so it is
+  /// borrowed from the scope. [`Scope::call`] needs a mutable reference to
+  /// the scope, so the borrow has to end before calling back into the VM:
+  ///
+  /// ```rust,compile_fail
+  /// use hebi::prelude::*;
+  ///
+  /// async fn greet(mut scope: Scope<'_>) -> hebi::Result<()> {
+  ///   let name = scope.param_str(0)?;
+  ///   let callback = scope.param::<Any>(1)?;
+  ///   scope.call(callback, &[]).await?;
+  ///   println!("hello, {name}");
+  ///   Ok(())
+  /// }
+  /// ```
+  pub fn param_str(&self, n: usize) -> Result<&str> {
+    let value = self.arg(n)?;
+    let Some(str) = value.clone().to_object::<crate::internal::object::Str>() else {
+      fail!("value is not a string");
+    };
+    // SAFETY: the string is immutable, and the register which holds the
+    // argument keeps it alive for at least as long as `self`
+    Ok(unsafe { &*(str.as_str() as *const str) })
+  }
+
+  /// Borrow the bytes passed as the `n`th argument, without copying them.
+  ///
+  /// The same rules as for [`Scope::param_str`] apply. Bytes are mutable, so
+  /// modifying them through another handle while they are borrowed panics.
+  pub fn param_bytes(&self, n: usize) -> Result<Ref<'_, [u8]>> {
+    let value = self.arg(n)?;
+    let Some(bytes) = value.clone().to_object::<crate::internal::object::Bytes>() else {
+      fail!("value is not bytes");
+    };
+    // SAFETY: the register which holds the argument keeps the bytes alive for
+    // at least as long as `self`
+    let bytes = unsafe { &*(bytes.deref() as *const crate::internal::object::Bytes) };
+    Ok(bytes.as_slice())
+  }
+
+  fn arg(&self, n: usize) -> Result<&OwnedValue> {
+    if n >= self.args.count {
+      fail!(Arity: "missing argument {n}");
+    }
+    let stack = unsafe { self.thread.stack.as_ref() };
+    Ok(&stack.regs[self.args.start + n])
+  }
+
   // TODO: does this also need to be force-Send?
   pub async fn call<'a>(
     &'a mut self,
@@ -391,18 +1155,40 @@ impl<'cx> Scope<'cx> {
   ) -> Result<bool> {
     Thread::check_equality(self.clone(), lhs, rhs)
   }
+
+  pub(crate) fn compare(
+    &self,
+    lhs: crate::internal::value::Value,
+    rhs: crate::internal::value::Value,
+  ) -> Result<std::cmp::Ordering> {
+    Thread::compare_values(self.clone(), lhs, rhs)
+  }
 }
 
 impl<'cx> Global<'cx> {
+  /// Create a script value which refers to `channel`, see [`Channel`].
+  pub fn new_channel(&self, channel: &Channel) -> Value<'cx> {
+    let channel = self.inner.alloc(channel.clone());
+    OwnedValue::object(channel).bind(self.clone())
+  }
+
+  /// Create a script iterator which gets its items from `iter`, see
+  /// [`NativeIterator`].
+  pub fn new_iterator(&self, iter: impl NativeIterator) -> Value<'cx> {
+    let iter = self.inner.alloc(NativeIter::new(iter));
+    OwnedValue::object(iter).bind(self.clone())
+  }
+
   pub fn new_instance<T: Send + 'static>(&self, value: T) -> Result<Value<'cx>> {
     let instance = match self.inner.get_type::<T>() {
-      Some(ty) => NativeClassInstance {
-        instance: Box::new(value),
-        class: ty,
-      },
+      Some(ty) => NativeClassInstance::new(&self.inner, ty, Box::new(value))?,
       None => fail!("`{}` is not a registered type", std::any::type_name::<T>()),
     };
-    let instance = OwnedValue::object(self.inner.alloc(instance));
+    let instance = self.inner.alloc(instance);
+    if instance.class.finalizer.is_some() {
+      self.inner.register_finalizer(instance.clone().into_any());
+    }
+    let instance = OwnedValue::object(instance);
     Ok(unsafe { instance.bind_raw::<'cx>() })
   }
 }
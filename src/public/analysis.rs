@@ -0,0 +1,38 @@
+//! The syntax tree and symbol table of a script, which is available without
+//! compiling or running it. This is meant for tools such as language servers.
+//!
+//! ```rust
+//! use hebi::analysis::{analyze, SymbolKind};
+//!
+//! let src = "fn f(a):\n  return a + b\n";
+//! let analysis = analyze(src);
+//! assert!(analysis.diagnostics.is_empty());
+//!
+//! // `a` in the body refers to the parameter
+//! let offset = src.find("a +").unwrap();
+//! let id = analysis.symbols.symbol_at(offset).unwrap();
+//! assert_eq!(analysis.symbols.get(id).kind, SymbolKind::Parameter);
+//!
+//! // `b` is not defined in the script
+//! let offset = src.find("b\n").unwrap();
+//! assert!(analysis.symbols.symbol_at(offset).is_none());
+//! ```
+//!
+//! The analysis may also be checked for likely mistakes using [`lint`]:
+//!
+//! ```rust
+//! use hebi::analysis::{analyze, lint, LintConfig, Rule};
+//!
+//! let analysis = analyze("fn f():\n  unused := 0\n");
+//! let lints = lint(&analysis, &LintConfig::new());
+//! assert_eq!(lints[0].rule, Rule::UnusedVariable);
+//!
+//! let config = LintConfig::new().allow(Rule::UnusedVariable);
+//! assert!(lint(&analysis, &config).is_empty());
+//! ```
+
+pub use crate::internal::syntax::analysis::{
+  analyze, Analysis, Reference, Symbol, SymbolId, SymbolKind, SymbolTable,
+};
+pub use crate::internal::syntax::ast;
+pub use crate::internal::syntax::lint::{lint, Lint, LintConfig, Rule};
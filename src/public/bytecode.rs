@@ -0,0 +1,292 @@
+//! Build functions out of bytecode instructions, without going through the
+//! parser. This is meant for other language frontends and macro systems which
+//! want to run on the VM.
+//!
+//! A [`FunctionBuilder`] hands out registers, jump labels and constants, and
+//! checks that the finished bytecode is well-formed, so that the VM never
+//! accesses a register or constant which does not exist.
+//!
+//! ```rust
+//! use hebi::bytecode::{op, FunctionBuilder};
+//! use hebi::Hebi;
+//!
+//! let mut hebi = Hebi::new();
+//!
+//! // fn add(a, b): return a + b
+//! let mut add = FunctionBuilder::new(&hebi, "add").params(2);
+//! let (a, b) = (add.param(0), add.param(1));
+//! let sum = add.register();
+//! add.emit(op::Add { dst: sum, lhs: a, rhs: b });
+//! add.emit(op::Return { src: sum });
+//! let add = add.finish_function().unwrap();
+//!
+//! // return add(1, 2)
+//! let mut main = FunctionBuilder::new(&hebi, "main");
+//! let desc = main.function(add);
+//! let callee = main.registers(3);
+//! main.emit(op::MakeFn { dst: callee, desc });
+//! main.emit(op::LoadSmi { dst: callee.offset(1), value: op::Smi(1) });
+//! main.emit(op::LoadSmi { dst: callee.offset(2), value: op::Smi(2) });
+//! main.emit(op::Call { dst: callee, callee, args: op::Count(2) });
+//! main.emit(op::Return { src: callee });
+//!
+//! let chunk = main.finish().unwrap();
+//! assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(3));
+//! ```
+//!
+//! Functions built this way can't capture variables, values are shared
+//! between them through globals instead.
+
+use std::marker::PhantomData;
+
+use super::{Chunk, Hebi};
+use crate::internal::bytecode::builder::{self, BytecodeBuilder};
+use crate::internal::bytecode::opcode::Instruction;
+use crate::internal::bytecode::verify::verify;
+use crate::internal::error::Result;
+use crate::internal::object::function::Params;
+use crate::internal::object::int::BoxedInt;
+use crate::internal::object::module::ModuleId;
+use crate::internal::object::{self, ConstantPool, Function, List, Ptr};
+use crate::internal::value::constant::NonNaNFloat;
+use crate::internal::vm::{self, global};
+
+/// The instructions, and the types of their operands.
+pub mod op {
+  pub use crate::internal::bytecode::opcode::symbolic::*;
+  pub use crate::internal::bytecode::opcode::{
+    check_encoding, Constant, Count, EncodingError, Instruction, ModuleVar, Offset, Register, Smi,
+    Upvalue, Width,
+  };
+}
+
+/// Builds the bytecode of a single function.
+///
+/// Register `r0` holds the function which is being called, and its params
+/// are in the registers after it. The rest of the registers are allocated
+/// with [`FunctionBuilder::register`] and [`FunctionBuilder::registers`].
+pub struct FunctionBuilder<'cx> {
+  global: global::Global,
+  name: String,
+  params: u16,
+  registers: usize,
+  builder: BytecodeBuilder,
+  lifetime: PhantomData<&'cx ()>,
+}
+
+/// The target of forward jumps, see [`FunctionBuilder::label`].
+pub struct Label(builder::MultiLabel);
+
+/// The target of backward jumps, see [`FunctionBuilder::loop_header`].
+pub struct LoopHeader(builder::LoopHeader);
+
+/// A finished function, which may be instantiated by another function using
+/// [`FunctionBuilder::function`].
+pub struct FunctionDescriptor {
+  inner: Ptr<object::FunctionDescriptor>,
+}
+
+impl<'cx> FunctionBuilder<'cx> {
+  /// Start building a function called `name`, which will run in `hebi`.
+  pub fn new(hebi: &Hebi, name: impl Into<String>) -> Self {
+    Self {
+      global: hebi.vm.global.clone(),
+      name: name.into(),
+      params: 0,
+      registers: 1,
+      builder: BytecodeBuilder::new(),
+      lifetime: PhantomData,
+    }
+  }
+
+  /// Set the number of params the function has.
+  ///
+  /// Panics if any registers were already allocated.
+  pub fn params(mut self, count: u16) -> Self {
+    assert_eq!(
+      self.registers,
+      1 + self.params as usize,
+      "params must be set before any registers are allocated"
+    );
+    self.params = count;
+    self.registers = 1 + count as usize;
+    self
+  }
+
+  /// The register which holds the param at `index`.
+  ///
+  /// Panics if the function does not have that many params.
+  pub fn param(&self, index: u16) -> op::Register {
+    assert!(
+      index < self.params,
+      "function `{}` only has {} params",
+      self.name,
+      self.params
+    );
+    op::Register(1 + index as u32)
+  }
+
+  /// Allocate a register.
+  pub fn register(&mut self) -> op::Register {
+    self.registers(1)
+  }
+
+  /// Allocate `count` contiguous registers, and return the first one.
+  ///
+  /// These are used for instructions which operate on a range of registers,
+  /// such as [`op::Call`] and [`op::MakeList`].
+  pub fn registers(&mut self, count: usize) -> op::Register {
+    let first = op::Register(self.registers as u32);
+    self.registers += count;
+    first
+  }
+
+  /// Emit an instruction.
+  ///
+  /// Panics if `instruction` is a jump, those are emitted with
+  /// [`FunctionBuilder::jump`], [`FunctionBuilder::jump_if_false`] and
+  /// [`FunctionBuilder::jump_loop`] instead.
+  pub fn emit(&mut self, instruction: impl Instruction) {
+    assert!(
+      !instruction.is_jump(),
+      "jumps must be emitted using `jump`, `jump_if_false` or `jump_loop`"
+    );
+    self.builder.emit(instruction, 0..0);
+  }
+
+  /// Create a label, which may be the target of any number of forward jumps.
+  ///
+  /// The label must be bound using [`FunctionBuilder::bind_label`] before the
+  /// function is finished.
+  pub fn label(&self) -> Label {
+    Label(self.builder.multi_label("label"))
+  }
+
+  /// Make `label` point at the next instruction.
+  pub fn bind_label(&mut self, label: Label) {
+    self.builder.bind_label(label.0)
+  }
+
+  /// Jump to `label`.
+  pub fn jump(&mut self, label: &Label) {
+    self.builder.emit_jump(&label.0, 0..0)
+  }
+
+  /// Jump to `label` if the value in `cond` is falsey.
+  pub fn jump_if_false(&mut self, label: &Label, cond: op::Register) {
+    self.builder.emit_jump_if_false(&label.0, cond, 0..0)
+  }
+
+  /// Mark the next instruction as the target of backward jumps.
+  pub fn loop_header(&mut self) -> LoopHeader {
+    let header = self.builder.loop_header();
+    self.builder.bind_loop_header(&header);
+    LoopHeader(header)
+  }
+
+  /// Jump back to `header`.
+  pub fn jump_loop(&mut self, header: &LoopHeader) {
+    self.builder.emit_jump_loop(&header.0, 0..0)
+  }
+
+  /// Add a string to the constant pool.
+  pub fn string(&mut self, value: impl Into<String>) -> op::Constant {
+    let value = self.global.intern(value.into());
+    self.builder.constant_pool_builder().insert(value)
+  }
+
+  /// Add a float to the constant pool.
+  ///
+  /// Panics if `value` is NaN.
+  pub fn float(&mut self, value: f64) -> op::Constant {
+    self
+      .builder
+      .constant_pool_builder()
+      .insert(NonNaNFloat::from(value))
+  }
+
+  /// Add an integer to the constant pool.
+  ///
+  /// Integers which fit in an [`op::Smi`] are better loaded using
+  /// [`op::LoadSmi`].
+  pub fn int(&mut self, value: i64) -> op::Constant {
+    let value = self.global.alloc(BoxedInt::new(value));
+    self.builder.constant_pool_builder().insert(value)
+  }
+
+  /// Add a function to the constant pool, which may be instantiated using
+  /// [`op::MakeFn`].
+  pub fn function(&mut self, function: FunctionDescriptor) -> op::Constant {
+    self.builder.constant_pool_builder().insert(function.inner)
+  }
+
+  /// Finish the function, so that it can be used by another function.
+  ///
+  /// Fails if the bytecode is not well-formed.
+  pub fn finish_function(self) -> Result<FunctionDescriptor> {
+    Ok(FunctionDescriptor {
+      inner: self.build()?,
+    })
+  }
+
+  /// Finish the function, and use it as the entrypoint of a [`Chunk`] which
+  /// can be executed using [`Hebi::run`].
+  ///
+  /// Fails if the bytecode is not well-formed.
+  ///
+  /// ```rust
+  /// use hebi::bytecode::{op, FunctionBuilder};
+  ///
+  /// let hebi = hebi::Hebi::new();
+  /// let mut main = FunctionBuilder::new(&hebi, "main");
+  /// // `r1` was never allocated
+  /// main.emit(op::Return { src: op::Register(1) });
+  /// assert!(main.finish().is_err());
+  /// ```
+  pub fn finish(self) -> Result<Chunk<'cx>> {
+    let global = self.global.clone();
+    let main = self.build()?;
+    let pool = ConstantPool::share(&global, &main);
+    let upvalues = global.alloc(List::new());
+    let main = global.alloc(Function::new(main, pool, upvalues, ModuleId::global()));
+    Ok(Chunk {
+      inner: vm::Chunk::from_function(main),
+      lifetime: PhantomData,
+    })
+  }
+
+  fn build(self) -> Result<Ptr<object::FunctionDescriptor>> {
+    if self.builder.has_unbound_jumps() {
+      fail!(
+        "function `{}` jumps to a label which was never bound",
+        self.name
+      );
+    }
+
+    let (bytecode, constants, spans) = self.builder.finish_with_spans();
+    if let Err(e) = verify(&bytecode, &constants, &spans, self.registers, 0) {
+      fail!("function `{}` is not well-formed: {e}", self.name);
+    }
+
+    Ok(
+      self.global.alloc(
+        object::FunctionDescriptor::new(
+          self.global.intern(self.name),
+          false,
+          Params {
+            has_self: false,
+            min: self.params,
+            max: self.params,
+            hints: Box::new([]),
+            kw: Box::new([]),
+          },
+          vec![],
+          self.registers,
+          bytecode,
+          constants,
+        )
+        .with_spans(spans),
+      ),
+    )
+  }
+}
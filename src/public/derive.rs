@@ -0,0 +1,32 @@
+//! Helpers used by the code generated by `#[derive(ToHebi, FromHebi)]`.
+
+use crate::internal::error::{Error, Result};
+use crate::internal::object::class::ClassInstance;
+use crate::internal::object::Table;
+use crate::internal::value;
+use crate::public::{Bind, FromValue, Global, Unbind, Value};
+
+/// Read the field `name` of the table or class instance `value`, and convert
+/// it to `T`. A missing field is read as `none`.
+pub fn field<'cx, T: FromValue<'cx>>(
+  value: &Value<'cx>,
+  global: &Global<'cx>,
+  ty: &str,
+  name: &str,
+) -> Result<T> {
+  let object = value.clone().unbind().to_any();
+  let field = if let Some(table) = object.clone().and_then(|o| o.clone_cast::<Table>()) {
+    table.get(name)
+  } else if let Some(instance) = object.and_then(|o| o.clone_cast::<ClassInstance>()) {
+    instance.fields.get(name)
+  } else {
+    fail!("cannot convert `{value}` to `{ty}`, because it is not a table or a class instance")
+  };
+  let field = field.unwrap_or_else(value::Value::none);
+  T::from_value(unsafe { field.bind_raw::<'cx>() }, global.clone())
+    .map_err(|e| error!("invalid field `{name}` of `{ty}`: {e}").into())
+}
+
+pub fn unknown_variant(ty: &str, tag: &str) -> Error {
+  error!("`{ty}` has no variant `{tag}`").into()
+}
@@ -0,0 +1,17 @@
+//! Formatting of scripts in a canonical style.
+//!
+//! ```rust
+//! let src = "fn f(a,b):  return a+b # sum\n";
+//! assert_eq!(
+//!   hebi::fmt::format(src).unwrap(),
+//!   "fn f(a, b):\n  return a + b # sum\n",
+//! );
+//! ```
+
+use crate::internal::error::{Error, Result};
+use crate::internal::syntax::fmt;
+
+/// Format `src`, keeping its comments. Fails if `src` contains syntax errors.
+pub fn format(src: &str) -> Result<String> {
+  fmt::format(src).map_err(Error::Syntax)
+}
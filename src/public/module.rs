@@ -11,12 +11,15 @@ use indexmap::IndexMap;
 
 use crate::internal::error::Result;
 use crate::internal::object::native::{
-  AsyncCallback, NativeClassDescriptor, NativeClassInstance, NativeFieldDescriptor,
+  AsyncCallback, InitCallback, NativeClassDescriptor, NativeClassInstance, NativeFieldDescriptor,
   NativeMethodDescriptor, SyncCallback,
 };
 use crate::internal::value::Value as OwnedValue;
 use crate::internal::vm::thread::Args;
-use crate::public::{FromValue, IntoValue, Scope, This, Unbind, Value};
+use crate::public::object::BuiltinType;
+use crate::public::{
+  Bind, FromValue, IntoValue, NativeIterator, Scope, Table, This, Unbind, Value,
+};
 
 #[derive(Clone)]
 pub struct NativeModule {
@@ -29,8 +32,10 @@ impl NativeModule {
       data: NativeModuleData {
         name: name.to_string(),
         fns: IndexMap::new(),
+        fn_params: IndexMap::new(),
         async_fns: IndexMap::new(),
         classes: IndexMap::new(),
+        init: None,
       },
     }
   }
@@ -39,8 +44,11 @@ impl NativeModule {
 pub(crate) struct NativeModuleData {
   pub(crate) name: StdString,
   pub(crate) fns: IndexMap<StdString, SyncCallback>,
+  /// The params of functions in `fns` which may be passed by name.
+  pub(crate) fn_params: IndexMap<StdString, &'static [&'static str]>,
   pub(crate) async_fns: IndexMap<StdString, AsyncCallback>,
   pub(crate) classes: IndexMap<StdString, NativeClassDescriptor>,
+  pub(crate) init: Option<InitCallback>,
 }
 
 pub struct NativeModuleBuilder {
@@ -60,6 +68,24 @@ impl NativeModuleBuilder {
     self
   }
 
+  /// Like [`NativeModuleBuilder::function`], but any of `params` may also be
+  /// passed by name, such as `pretty` in `json.stringify(v, pretty=true)`.
+  ///
+  /// An argument passed by name is at the index of its name in `params`, and
+  /// the params before it which were not passed at all are `none`.
+  pub fn function_with_params<'cx, R>(
+    mut self,
+    name: impl ToString,
+    params: &'static [&'static str],
+    f: impl Fn(Scope<'cx>) -> R + Send + Sync + 'static,
+  ) -> Self
+  where
+    R: IntoValue<'cx> + 'static,
+  {
+    self.data.fn_params.insert(name.to_string(), params);
+    self.function(name, f)
+  }
+
   pub fn async_function<'cx, Fut, R>(
     mut self,
     name: impl ToString,
@@ -89,6 +115,23 @@ impl NativeModuleBuilder {
     self
   }
 
+  /// Register a callback which runs the first time the module is imported.
+  ///
+  /// The callback receives the module's variables, and may insert additional
+  /// values into them. This is useful for state which is expensive to create,
+  /// such as connection pools or caches, as it is only created if a script
+  /// actually uses the module.
+  ///
+  /// If the callback returns an error, the import fails, and the callback
+  /// will run again on the next attempt to import the module.
+  pub fn init<'cx>(
+    mut self,
+    f: impl Fn(Scope<'cx>, Table<'cx>) -> Result<()> + Send + Sync + 'static,
+  ) -> Self {
+    self.data.init = Some(wrap_init(f));
+    self
+  }
+
   pub fn finish(self) -> NativeModule {
     NativeModule {
       data: Arc::new(self.data),
@@ -127,6 +170,8 @@ impl<const HAS_INIT: bool, T: Send + 'static> NativeClassBuilder<HAS_INIT, T> {
         fields: IndexMap::new(),
         methods: IndexMap::new(),
         static_methods: IndexMap::new(),
+        finalizer: None,
+        parent: None,
       },
       ty: PhantomData,
     }
@@ -212,6 +257,51 @@ impl<const HAS_INIT: bool, T: Send + 'static> NativeClassBuilder<HAS_INIT, T> {
       .insert(name.to_string(), NativeMethodDescriptor::Sync(wrap_fn(f)));
     self
   }
+
+  /// Give the class the `iter`, `next` and `done` methods of the iterator
+  /// protocol, implemented by `T`, see [`NativeIterator`].
+  pub fn iterator(self) -> Self
+  where
+    T: NativeIterator,
+  {
+    self
+      .method("iter", |scope, this| {
+        OwnedValue::object(this.inner.clone()).bind(scope.global())
+      })
+      .method("next", |_, this| NativeIterator::next(&*this))
+      .method("done", |_, this| NativeIterator::done(&*this))
+  }
+
+  /// Derive this class from the script class called `name`.
+  ///
+  /// Instances get the fields and methods of the parent, and are instances
+  /// of it for the purposes of `is`. The parent is looked up in the globals
+  /// the first time an instance is created, so it only has to be defined by
+  /// then. Its `init` is not called.
+  pub fn extends(mut self, name: impl ToString) -> Self {
+    self.descriptor.parent = Some(name.to_string());
+    self
+  }
+
+  /// Register a callback which runs once an instance is no longer
+  /// referenced, or when the VM is dropped, whichever happens first.
+  ///
+  /// Finalizers run at the next function return or loop iteration after the
+  /// last reference is dropped, rather than immediately. Use them to release
+  /// resources such as sockets or file handles deterministically.
+  ///
+  /// The finalizers of instances which are still alive when the VM is
+  /// dropped, including `__del__` methods of script classes, are run by
+  /// blocking the current thread on them with `pollster`. Async functions
+  /// which they call are polled without a runtime, so they must not wait on
+  /// I/O or timers driven by one, because they would never complete.
+  pub fn finalizer<'cx>(
+    mut self,
+    f: impl Fn(Scope<'cx>, This<'cx, T>) + Send + Sync + 'static,
+  ) -> Self {
+    self.descriptor.finalizer = Some(wrap_method(f));
+    self
+  }
 }
 
 fn wrap_fn<'cx, R>(f: impl Fn(Scope<'cx>) -> R + Send + Sync + 'static) -> SyncCallback
@@ -225,6 +315,16 @@ where
   })
 }
 
+fn wrap_init<'cx>(
+  f: impl Fn(Scope<'cx>, Table<'cx>) -> Result<()> + Send + Sync + 'static,
+) -> InitCallback {
+  Arc::new(move |scope, module_vars| {
+    let scope = unsafe { transmute::<Scope<'_>, Scope<'static>>(scope) };
+    let module_vars = module_vars.bind(scope.global());
+    f(scope, module_vars)
+  })
+}
+
 fn wrap_async_fn<'cx, Fut, R>(
   f: impl Fn(Scope<'cx>) -> Fut + Send + Sync + 'static,
 ) -> AsyncCallback
@@ -265,6 +365,7 @@ fn extract_this<T: Send + 'static>(scope: Scope<'_>) -> Result<(Scope<'_>, This<
     args: Args {
       start: scope.args.start + 1,
       count: scope.args.count - 1,
+      kw: scope.args.kw,
     },
     stack_base: scope.stack_base,
     lifetime: PhantomData,
@@ -312,6 +413,27 @@ where
   })
 }
 
+pub(crate) fn wrap_builtin_method<'cx, T, R>(
+  f: impl Fn(Scope<'cx>, T) -> R + Send + Sync + 'static,
+) -> SyncCallback
+where
+  T: BuiltinType<'cx> + 'static,
+  R: IntoValue<'cx> + 'static,
+{
+  Arc::new(move |scope| {
+    let scope = unsafe { transmute::<Scope<'_>, Scope<'static>>(scope) };
+    let this = scope.param::<T>(0)?;
+    let mut scope = scope;
+    scope.args.start += 1;
+    scope.args.count -= 1;
+
+    let global = scope.global();
+    f(scope, this)
+      .into_value(global)
+      .map(|value| value.unbind())
+  })
+}
+
 fn wrap_getter<'cx, T: Send + 'static, R>(
   f: impl Fn(Scope<'cx>, This<'cx, T>) -> R + Send + Sync + 'static,
 ) -> SyncCallback
@@ -1,3 +1,4 @@
+pub mod bytes;
 pub mod function;
 pub mod list;
 pub mod string;
@@ -6,7 +7,8 @@ pub mod table;
 use std::fmt::{Debug, Display};
 
 use crate::internal::object::{self, Ptr};
-use crate::public::{Bind, Global};
+use crate::internal::vm::global::BuiltinKind;
+use crate::public::{Bind, FromValue, Global};
 
 decl_ref! {
   struct Any(Ptr<object::Any>)
@@ -38,6 +40,45 @@ pub trait ObjectRef<'cx>: private::Sealed + Sized + Debug + Display {
   // TODO: add same methods as `Object` and delegate
 }
 
+/// A builtin type which may be extended with methods from Rust, using
+/// [`Hebi::extend_builtin`][crate::Hebi::extend_builtin].
+pub trait BuiltinType<'cx>: private::Sealed + FromValue<'cx> {
+  #[doc(hidden)]
+  fn kind() -> BuiltinKind;
+}
+
+impl<'cx> BuiltinType<'cx> for string::Str<'cx> {
+  fn kind() -> BuiltinKind {
+    BuiltinKind::Str
+  }
+}
+
+impl<'cx> BuiltinType<'cx> for list::List<'cx> {
+  fn kind() -> BuiltinKind {
+    BuiltinKind::List
+  }
+}
+
+impl<'cx> BuiltinType<'cx> for table::Table<'cx> {
+  fn kind() -> BuiltinKind {
+    BuiltinKind::Table
+  }
+}
+
+impl private::Sealed for i32 {}
+impl<'cx> BuiltinType<'cx> for i32 {
+  fn kind() -> BuiltinKind {
+    BuiltinKind::Int
+  }
+}
+
+impl private::Sealed for f64 {}
+impl<'cx> BuiltinType<'cx> for f64 {
+  fn kind() -> BuiltinKind {
+    BuiltinKind::Float
+  }
+}
+
 mod private {
   pub trait Sealed {}
 }
@@ -0,0 +1,69 @@
+use std::cell::Ref;
+
+use super::*;
+use crate::internal::object::{Bytes as OwnedBytes, Ptr};
+use crate::public::{Hebi, Scope};
+
+decl_ref! {
+  struct Bytes(Ptr<OwnedBytes>)
+}
+
+impl_object_ref!(Bytes, OwnedBytes);
+
+impl<'cx> Bytes<'cx> {
+  pub fn len(&self) -> usize {
+    self.inner.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.inner.is_empty()
+  }
+
+  pub fn get(&self, index: usize) -> Option<u8> {
+    self.inner.get(index)
+  }
+
+  #[must_use = "`set` returns false if index is out of bounds"]
+  pub fn set(&self, index: usize, value: u8) -> bool {
+    self.inner.set(index, value)
+  }
+
+  pub fn push(&self, value: u8) {
+    self.inner.push(value)
+  }
+
+  pub fn extend_from_slice(&self, other: &[u8]) {
+    self.inner.extend_from_slice(other)
+  }
+
+  /// Borrow the bytes without copying them. Modifying them while they are
+  /// borrowed panics.
+  pub fn as_slice(&self) -> Ref<'_, [u8]> {
+    self.inner.as_slice()
+  }
+
+  pub fn to_vec(&self) -> Vec<u8> {
+    self.inner.to_vec()
+  }
+}
+
+impl<'cx> Global<'cx> {
+  pub fn new_bytes(&self, data: impl Into<Vec<u8>>) -> Bytes<'cx> {
+    self
+      .inner
+      .alloc(OwnedBytes::from(data.into()))
+      .bind(self.clone())
+  }
+}
+
+impl<'cx> Scope<'cx> {
+  pub fn new_bytes(&self, data: impl Into<Vec<u8>>) -> Bytes<'cx> {
+    self.global().new_bytes(data)
+  }
+}
+
+impl Hebi {
+  pub fn new_bytes(&self, data: impl Into<Vec<u8>>) -> Bytes<'_> {
+    self.global().new_bytes(data)
+  }
+}
@@ -1,5 +1,12 @@
+use std::future::Future;
+use std::marker::PhantomData;
+
 use super::*;
-use crate::internal::object::{Function as OwnedFunction, Ptr};
+use crate::internal::error::Result;
+use crate::internal::object::{is_callable, Any as OwnedAny, Function as OwnedFunction, Ptr};
+use crate::internal::vm::thread::util::check_args;
+use crate::public::value::{FromValue, IntoValuePack};
+use crate::public::{CancellationToken, ForceSendFuture, Hebi};
 
 decl_ref! {
   struct Function(Ptr<OwnedFunction>)
@@ -8,3 +15,75 @@ decl_ref! {
 impl_object_ref!(Function, OwnedFunction);
 
 impl<'cx> Function<'cx> {}
+
+/// A handle to a global function, which converts its arguments from `Args`
+/// and its return value to `Ret`.
+///
+/// The number of arguments is checked once when the handle is created by
+/// [`Hebi::get_function`], so calling it in a loop only pays for the
+/// conversions.
+pub struct TypedFunction<'cx, Args, Ret> {
+  hebi: &'cx mut Hebi,
+  function: Ptr<OwnedAny>,
+  ty: PhantomData<fn(Args) -> Ret>,
+}
+
+impl<'cx, Args, Ret> TypedFunction<'cx, Args, Ret>
+where
+  Args: for<'a> IntoValuePack<'a>,
+  Ret: for<'a> FromValue<'a>,
+{
+  pub(crate) fn new(hebi: &'cx mut Hebi, name: &str) -> Result<Self> {
+    let Some(value) = hebi.vm.global.get(name) else {
+      fail!(Name: "function `{name}` is not defined");
+    };
+    let Some(function) = value.to_any().filter(is_callable) else {
+      fail!(Type: "`{name}` is not a function");
+    };
+
+    // native functions check their arguments when they are called
+    if let Some(f) = function.clone_cast::<OwnedFunction>() {
+      check_args(&f.descriptor.params, false, <Args as IntoValuePack>::len())?;
+    }
+
+    Ok(Self {
+      hebi,
+      function,
+      ty: PhantomData,
+    })
+  }
+
+  pub fn call(&mut self, args: Args) -> Result<Ret> {
+    pollster::block_on(self.call_async(args))
+  }
+
+  /// Call the function, stopping with an error of kind
+  /// [`ErrorKind::Cancelled`][crate::ErrorKind::Cancelled] if `token` is
+  /// cancelled before it returns. See [`CancellationToken`].
+  pub fn call_with_cancellation(
+    &mut self,
+    args: Args,
+    token: impl Into<CancellationToken>,
+  ) -> Result<Ret> {
+    let global = self.hebi.vm.global.clone();
+    let previous = global.set_cancellation(Some(token.into()));
+    let result = self.call(args);
+    global.set_cancellation(previous);
+    result
+  }
+
+  pub fn call_async(&mut self, args: Args) -> impl Future<Output = Result<Ret>> + Send + '_ {
+    let hebi = &mut *self.hebi;
+    let function = self.function.clone();
+    let fut = async move {
+      let global = Global {
+        inner: hebi.vm.global.clone(),
+        lifetime: PhantomData,
+      };
+      let args = args.into_value_pack(global.clone())?;
+      let value = hebi.vm.call(function, args.as_ref()).await?;
+      Ret::from_value(unsafe { value.bind_raw() }, global)
+    };
+    unsafe { ForceSendFuture::new(fut) }
+  }
+}
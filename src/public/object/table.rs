@@ -30,6 +30,15 @@ impl<'cx> Table<'cx> {
     self.inner.get(key).map(|v| unsafe { v.bind_raw::<'cx>() })
   }
 
+  /// Sort the entries of this table by key.
+  ///
+  /// Tables iterate in insertion order, this reorders them into
+  /// lexicographic order.
+  pub fn sort_keys(&self) {
+    self.inner.sort_keys()
+  }
+
+  /// Keys may be `none`, bools, numbers, strings, or tuples.
   pub fn keys<'a>(&'a self) -> Keys<'a, 'cx> {
     Keys {
       inner: self.inner.keys(),
@@ -58,10 +67,13 @@ pub struct Keys<'a, 'cx> {
 }
 
 impl<'a, 'cx> Iterator for Keys<'a, 'cx> {
-  type Item = Str<'cx>;
+  type Item = Value<'cx>;
 
   fn next(&mut self) -> Option<Self::Item> {
-    self.inner.next().map(|v| unsafe { v.bind_raw::<'cx>() })
+    self
+      .inner
+      .next()
+      .map(|key| unsafe { key.to_value().bind_raw::<'cx>() })
   }
 }
 
@@ -84,13 +96,13 @@ pub struct Entries<'a, 'cx> {
 }
 
 impl<'a, 'cx> Iterator for Entries<'a, 'cx> {
-  type Item = (Str<'cx>, Value<'cx>);
+  type Item = (Value<'cx>, Value<'cx>);
 
   fn next(&mut self) -> Option<Self::Item> {
     self
       .inner
       .next()
-      .map(|(key, value)| unsafe { (key.bind_raw::<'cx>(), value.bind_raw::<'cx>()) })
+      .map(|(key, value)| unsafe { (key.to_value().bind_raw::<'cx>(), value.bind_raw::<'cx>()) })
   }
 }
 
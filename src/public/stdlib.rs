@@ -0,0 +1,42 @@
+//! Native modules which ship with Hebi.
+//!
+//! They are not registered by default. Use [`HebiBuilder::with_builtins`]
+//! to register all of the modules enabled by the crate's features.
+//!
+//! The `fs` and `os` modules are not registered by `with_builtins`, because
+//! they give scripts access to the file system and the environment. Use
+//! [`HebiBuilder::allow_fs`] and [`HebiBuilder::allow_os`] to register them.
+//!
+//! The `time`, `fs` and `os` modules are not available on
+//! `wasm32-unknown-unknown`, which has no clock, threads or file system.
+//!
+//! [`HebiBuilder::with_builtins`]: crate::HebiBuilder::with_builtins
+//! [`HebiBuilder::allow_fs`]: crate::HebiBuilder::allow_fs
+//! [`HebiBuilder::allow_os`]: crate::HebiBuilder::allow_os
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod fs;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod math;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod os;
+#[cfg(feature = "regex")]
+pub mod re;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod time;
+
+use super::NativeModule;
+
+/// All of the builtin modules enabled by the crate's features.
+pub fn modules() -> Vec<NativeModule> {
+  #[allow(unused_mut)]
+  let mut modules = vec![math::module()];
+  #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+  modules.push(time::module());
+  #[cfg(feature = "json")]
+  modules.push(json::module());
+  #[cfg(feature = "regex")]
+  modules.push(re::module());
+  modules
+}
@@ -0,0 +1,119 @@
+//! The `fs` module.
+//!
+//! ```text
+//! import fs
+//!
+//! path := fs.join("out", "log.txt")      # out/log.txt
+//! print fs.split(path)                  # ("out", "log.txt")
+//! if !fs.exists(path):
+//!   fs.write(path, "hello")
+//! print fs.read_to_string(path)         # hello
+//! print fs.list_dir("out")              # ["log.txt"]
+//! ```
+//!
+//! Scripts only get access to the file system if the VM was created with
+//! [`HebiBuilder::allow_fs`], and every access is checked by
+//! [`SecurityPolicy::check_fs`] first. `join` and `split` only work with
+//! paths, so they are never checked.
+//!
+//! [`HebiBuilder::allow_fs`]: crate::HebiBuilder::allow_fs
+//! [`SecurityPolicy::check_fs`]: crate::SecurityPolicy::check_fs
+
+use std::path::{Path, PathBuf};
+
+use crate::internal::error::Result;
+use crate::internal::vm::policy::FsAccess;
+use crate::prelude::*;
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("fs")
+    .function("read_to_string", read_to_string)
+    .function("write", write)
+    .function("exists", exists)
+    .function("list_dir", list_dir)
+    .function("join", join)
+    .function("split", split)
+    .finish()
+}
+
+fn check(scope: &Scope<'_>, path: &str, access: FsAccess) -> Result<()> {
+  match scope.thread.global.security_policy() {
+    Some(policy) => policy.check_fs(path, access),
+    None => Ok(()),
+  }
+}
+
+fn read_to_string(scope: Scope<'_>) -> Result<String> {
+  let path = scope.param::<String>(0)?;
+  check(&scope, &path, FsAccess::Read)?;
+  match std::fs::read_to_string(&path) {
+    Ok(contents) => Ok(contents),
+    Err(e) => fail!("cannot read `{path}`: {e}"),
+  }
+}
+
+/// Creates the file if it does not exist, and replaces its contents
+/// otherwise.
+fn write(scope: Scope<'_>) -> Result<()> {
+  let (path, contents) = scope.params::<(String, String)>()?;
+  check(&scope, &path, FsAccess::Write)?;
+  match std::fs::write(&path, contents) {
+    Ok(()) => Ok(()),
+    Err(e) => fail!("cannot write `{path}`: {e}"),
+  }
+}
+
+fn exists(scope: Scope<'_>) -> Result<bool> {
+  let path = scope.param::<String>(0)?;
+  check(&scope, &path, FsAccess::Read)?;
+  Ok(Path::new(&path).exists())
+}
+
+/// The names of the entries in a directory, sorted so that the result does
+/// not depend on the platform.
+fn list_dir(scope: Scope<'_>) -> Result<Value<'_>> {
+  let path = scope.param::<String>(0)?;
+  check(&scope, &path, FsAccess::Read)?;
+  let entries = match std::fs::read_dir(&path) {
+    Ok(entries) => entries,
+    Err(e) => fail!("cannot list `{path}`: {e}"),
+  };
+  let mut names = vec![];
+  for entry in entries {
+    match entry {
+      Ok(entry) => names.push(entry.file_name().to_string_lossy().into_owned()),
+      Err(e) => fail!("cannot list `{path}`: {e}"),
+    }
+  }
+  names.sort();
+
+  let list = scope.new_list(names.len());
+  for name in names {
+    list.push(name.into_value(scope.global())?);
+  }
+  list.into_value(scope.global())
+}
+
+/// Join any number of path segments with the separator of the platform. A
+/// segment which is an absolute path replaces the ones before it.
+fn join(scope: Scope<'_>) -> Result<String> {
+  let mut path = PathBuf::new();
+  for n in 0..scope.num_args() {
+    path.push(scope.param::<String>(n)?);
+  }
+  Ok(path.to_string_lossy().into_owned())
+}
+
+/// Split a path into its parent directory and its last segment. Either of
+/// them is an empty string if the path doesn't have one.
+fn split(scope: Scope<'_>) -> Result<Value<'_>> {
+  let path = scope.param::<String>(0)?;
+  let path = Path::new(&path);
+  let parent = path.parent().unwrap_or(Path::new(""));
+  let name = path.file_name().unwrap_or_default();
+  let parts = vec![
+    Frozen::from(parent.to_string_lossy().into_owned()),
+    Frozen::from(name.to_string_lossy().into_owned()),
+  ];
+  Frozen::from(parts).into_value(scope.global())
+}
@@ -0,0 +1,43 @@
+//! The `json` module.
+//!
+//! ```text
+//! import json
+//!
+//! v := json.parse("{\"a\": [1, 2]}")
+//! print json.stringify(v)              # {"a":[1,2]}
+//! print json.stringify(v, pretty=true) # pretty-printed
+//! ```
+
+use serde::de::DeserializeSeed;
+
+use crate::internal::error::Result;
+use crate::prelude::*;
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("json")
+    .function("parse", parse)
+    .function_with_params("stringify", &["value", "pretty"], stringify)
+    .finish()
+}
+
+fn parse(scope: Scope<'_>) -> Result<Value<'_>> {
+  let source = scope.param::<Str>(0)?;
+  ValueDeserializer::new(scope.global())
+    .deserialize(&mut serde_json::Deserializer::from_str(source.as_str()))
+    .map_err(|e| error!("failed to parse json: {e}").into())
+}
+
+/// `stringify(value, pretty=false)`
+fn stringify(scope: Scope<'_>) -> Result<String> {
+  let value = scope.param::<Value>(0)?;
+  let pretty = match scope.num_args() {
+    0 | 1 => false,
+    _ => scope.param::<bool>(1)?,
+  };
+  let result = if pretty {
+    serde_json::to_string_pretty(&value)
+  } else {
+    serde_json::to_string(&value)
+  };
+  result.map_err(|e| error!("failed to stringify value: {e}").into())
+}
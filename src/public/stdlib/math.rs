@@ -0,0 +1,221 @@
+//! The `math` module.
+//!
+//! ```text
+//! import math
+//!
+//! print math.sqrt(2)            # 1.4142135623730951
+//! print math.floor(2.5)         # 2
+//! print math.clamp(15, 0, 10)   # 10
+//! print math.pi                 # 3.141592653589793
+//!
+//! rng := math.Rng(42)
+//! print rng.float()             # in [0, 1)
+//! print rng.int(1, 7)           # in [1, 7)
+//! ```
+//!
+//! All functions accept both ints and floats. `floor`, `ceil` and `round`
+//! return ints, `abs`, `min`, `max` and `clamp` return one of their arguments,
+//! and the rest always return floats.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::internal::error::Result;
+use crate::prelude::*;
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("math")
+    .function("floor", |scope| to_int(scope, f64::floor))
+    .function("ceil", |scope| to_int(scope, f64::ceil))
+    .function("round", |scope| to_int(scope, f64::round))
+    .function("abs", abs)
+    .function("sqrt", |scope| unary(scope, f64::sqrt))
+    .function("sin", |scope| unary(scope, f64::sin))
+    .function("cos", |scope| unary(scope, f64::cos))
+    .function("tan", |scope| unary(scope, f64::tan))
+    .function("asin", |scope| unary(scope, f64::asin))
+    .function("acos", |scope| unary(scope, f64::acos))
+    .function("atan", |scope| unary(scope, f64::atan))
+    .function("atan2", atan2)
+    .function("exp", |scope| unary(scope, f64::exp))
+    .function("log", log)
+    .function("min", |scope| extremum(scope, |a, b| b < a))
+    .function("max", |scope| extremum(scope, |a, b| b > a))
+    .function("clamp", clamp)
+    .class::<Rng>("Rng", |class| {
+      class
+        .init(|scope| {
+          let seed = match scope.num_args() {
+            0 => None,
+            _ => scope.param::<Option<i64>>(0)?,
+          };
+          Ok(Rng::new(seed.map(|seed| seed as u64)))
+        })
+        .method("float", |_, this| this.float())
+        .method("int", |scope, this| {
+          let (lo, hi) = scope.params::<(i64, i64)>()?;
+          this.int(lo, hi)
+        })
+        .finish()
+    })
+    .init(|scope, vars| {
+      vars.insert(
+        scope.new_string("pi"),
+        std::f64::consts::PI.into_value(scope.global())?,
+      );
+      vars.insert(
+        scope.new_string("e"),
+        std::f64::consts::E.into_value(scope.global())?,
+      );
+      Ok(())
+    })
+    .finish()
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+  Int(i64),
+  Float(f64),
+}
+
+impl Number {
+  fn to_f64(self) -> f64 {
+    match self {
+      Number::Int(v) => v as f64,
+      Number::Float(v) => v,
+    }
+  }
+}
+
+fn number<'cx>(scope: &Scope<'cx>, n: usize) -> Result<(Value<'cx>, Number)> {
+  let value = scope.param::<Value>(n)?;
+  let number = if let Some(v) = value.as_i64() {
+    Number::Int(v)
+  } else if let Some(v) = value.as_float() {
+    Number::Float(v)
+  } else {
+    fail!("expected a number, got `{value}`");
+  };
+  Ok((value, number))
+}
+
+fn float(scope: &Scope<'_>, n: usize) -> Result<f64> {
+  Ok(number(scope, n)?.1.to_f64())
+}
+
+fn unary(scope: Scope<'_>, f: fn(f64) -> f64) -> Result<f64> {
+  Ok(f(float(&scope, 0)?))
+}
+
+fn to_int(scope: Scope<'_>, f: fn(f64) -> f64) -> Result<i64> {
+  match number(&scope, 0)?.1 {
+    Number::Int(v) => Ok(v),
+    Number::Float(v) => {
+      let v = f(v);
+      if !v.is_finite() || v < i64::MIN as f64 || v >= i64::MAX as f64 {
+        fail!("`{v}` is out of range for an int");
+      }
+      Ok(v as i64)
+    }
+  }
+}
+
+fn abs(scope: Scope<'_>) -> Result<Value<'_>> {
+  let global = scope.global();
+  match number(&scope, 0)?.1 {
+    Number::Int(v) => match v.checked_abs() {
+      Some(v) => v.into_value(global),
+      None => (v as f64).abs().into_value(global),
+    },
+    Number::Float(v) => v.abs().into_value(global),
+  }
+}
+
+fn atan2(scope: Scope<'_>) -> Result<f64> {
+  let y = float(&scope, 0)?;
+  let x = float(&scope, 1)?;
+  Ok(y.atan2(x))
+}
+
+fn log(scope: Scope<'_>) -> Result<f64> {
+  let x = float(&scope, 0)?;
+  match scope.num_args() {
+    0 | 1 => Ok(x.ln()),
+    _ => Ok(x.log(float(&scope, 1)?)),
+  }
+}
+
+/// Returns the argument `v` for which `replace(current, v)` was last true.
+fn extremum(scope: Scope<'_>, replace: fn(f64, f64) -> bool) -> Result<Value<'_>> {
+  if scope.num_args() == 0 {
+    fail!(Arity: "expected at least 1 arg, got 0");
+  }
+  let (mut result, first) = number(&scope, 0)?;
+  let mut current = first.to_f64();
+  for n in 1..scope.num_args() {
+    let (value, number) = number(&scope, n)?;
+    if replace(current, number.to_f64()) {
+      result = value;
+      current = number.to_f64();
+    }
+  }
+  Ok(result)
+}
+
+fn clamp(scope: Scope<'_>) -> Result<Value<'_>> {
+  let (value, v) = number(&scope, 0)?;
+  let (min, lo) = number(&scope, 1)?;
+  let (max, hi) = number(&scope, 2)?;
+  if lo.to_f64() > hi.to_f64() {
+    fail!("min `{min}` is greater than max `{max}`");
+  }
+  if v.to_f64() < lo.to_f64() {
+    Ok(min)
+  } else if v.to_f64() > hi.to_f64() {
+    Ok(max)
+  } else {
+    Ok(value)
+  }
+}
+
+/// A seedable pseudo-random number generator (SplitMix64).
+///
+/// It is fast and has good statistical quality, but it is not suitable for
+/// cryptography.
+struct Rng {
+  state: Cell<u64>,
+}
+
+impl Rng {
+  fn new(seed: Option<u64>) -> Self {
+    let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+    Self {
+      state: Cell::new(seed),
+    }
+  }
+
+  fn next(&self) -> u64 {
+    let state = self.state.get().wrapping_add(0x9e3779b97f4a7c15);
+    self.state.set(state);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+
+  /// A float in `[0, 1)`.
+  fn float(&self) -> f64 {
+    (self.next() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// An int in `[lo, hi)`.
+  fn int(&self, lo: i64, hi: i64) -> Result<i64> {
+    if lo >= hi {
+      fail!("empty range {lo}..{hi}");
+    }
+    let span = hi.wrapping_sub(lo) as u64;
+    let offset = ((self.next() as u128 * span as u128) >> 64) as u64;
+    Ok(lo.wrapping_add(offset as i64))
+  }
+}
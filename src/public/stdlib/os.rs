@@ -0,0 +1,119 @@
+//! The `os` module.
+//!
+//! ```text
+//! import os
+//!
+//! print os.env("HOME")                 # /home/user, or none if it's not set
+//! print os.cwd()                       # /home/user/project
+//! out := os.run("git", ["status", "--short"])
+//! if out["status"] != 0:
+//!   print out["stderr"]
+//!   os.exit(1)
+//! print out["stdout"]
+//! ```
+//!
+//! Scripts only get access to this module if the VM was created with
+//! [`HebiBuilder::allow_os`]. `run` is only available with the `process`
+//! feature, and every command is checked by
+//! [`SecurityPolicy::check_process`] first. `env` is checked by
+//! [`SecurityPolicy::check_env`], like the `env` builtin.
+//!
+//! `exit` stops the script with an error of kind [`ErrorKind::Exit`], which
+//! the host decides how to handle.
+//!
+//! [`HebiBuilder::allow_os`]: crate::HebiBuilder::allow_os
+//! [`SecurityPolicy::check_process`]: crate::SecurityPolicy::check_process
+//! [`SecurityPolicy::check_env`]: crate::SecurityPolicy::check_env
+//! [`ErrorKind::Exit`]: crate::ErrorKind::Exit
+
+use crate::internal::error::Result;
+use crate::prelude::*;
+use crate::ErrorKind;
+
+pub fn module() -> NativeModule {
+  #[allow(unused_mut)]
+  let mut module = NativeModule::builder("os")
+    .function("env", env)
+    .function("cwd", cwd)
+    .function("exit", exit);
+  #[cfg(feature = "process")]
+  {
+    module = module.function("run", run);
+  }
+  module.finish()
+}
+
+fn env(scope: Scope<'_>) -> Result<Option<String>> {
+  let name = scope.param::<String>(0)?;
+  if let Some(policy) = scope.thread.global.security_policy() {
+    policy.check_env(&name)?;
+  }
+  Ok(std::env::var(name).ok())
+}
+
+fn cwd(_: Scope<'_>) -> Result<String> {
+  match std::env::current_dir() {
+    Ok(path) => Ok(path.to_string_lossy().into_owned()),
+    Err(e) => fail!("cannot get the current directory: {e}"),
+  }
+}
+
+/// `exit(code)`, where `code` defaults to `0`.
+fn exit(scope: Scope<'_>) -> Result<()> {
+  let code = match scope.num_args() {
+    0 => 0,
+    _ => scope.param::<i32>(0)?,
+  };
+  Err(
+    error!("the script exited with code {code}")
+      .with_kind(ErrorKind::Exit(code))
+      .into(),
+  )
+}
+
+/// `run(program, args)` runs `program` to completion, and returns a table
+/// with its exit `status`, and everything it wrote to `stdout` and `stderr`.
+///
+/// The `status` is `none` if the process was terminated by a signal.
+#[cfg(feature = "process")]
+fn run(scope: Scope<'_>) -> Result<Value<'_>> {
+  let global = scope.global();
+  let program = scope.param::<String>(0)?;
+  let mut args = vec![];
+  if scope.num_args() > 1 {
+    for arg in scope.param::<List>(1)?.iter() {
+      args.push(String::from_value(arg, global.clone())?);
+    }
+  }
+  if let Some(policy) = scope.thread.global.security_policy() {
+    policy.check_process(&program, &args)?;
+  }
+
+  let output = match std::process::Command::new(&program)
+    .args(&args)
+    .stdin(std::process::Stdio::null())
+    .output()
+  {
+    Ok(output) => output,
+    Err(e) => fail!("cannot run `{program}`: {e}"),
+  };
+
+  let table = scope.new_table(3);
+  table.insert(
+    scope.new_string("status"),
+    output.status.code().into_value(global.clone())?,
+  );
+  table.insert(
+    scope.new_string("stdout"),
+    String::from_utf8_lossy(&output.stdout)
+      .into_owned()
+      .into_value(global.clone())?,
+  );
+  table.insert(
+    scope.new_string("stderr"),
+    String::from_utf8_lossy(&output.stderr)
+      .into_owned()
+      .into_value(global.clone())?,
+  );
+  table.into_value(global)
+}
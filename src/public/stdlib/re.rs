@@ -0,0 +1,123 @@
+//! The `re` module.
+//!
+//! ```text
+//! import re
+//!
+//! r := re.compile(r"(?P<key>\w+)=(?P<value>\d+)")
+//! print r.is_match("a=1")                  # true
+//! print r.find("x a=1")["text"]            # a=1
+//! print r.captures("a=1")["named"]["value"] # 1
+//! print r.replace("a=1 b=2", "$value")     # 1 2
+//! ```
+//!
+//! The syntax is the one supported by the [`regex`] crate. Match positions
+//! are byte offsets into the string.
+
+use crate::internal::error::Result;
+use crate::prelude::*;
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("re")
+    .function("compile", |scope| {
+      let regex = Regex::new(&scope.param::<String>(0)?)?;
+      scope.new_instance(regex)
+    })
+    .class::<Regex>("Regex", |class| {
+      class
+        .init(|scope| Regex::new(&scope.param::<String>(0)?))
+        .method("is_match", |scope, this| {
+          let text = scope.param::<Str>(0)?;
+          Ok(this.0.is_match(text.as_str()))
+        })
+        .method("find", find)
+        .method("find_all", find_all)
+        .method("captures", captures)
+        .method("replace", |scope, this| {
+          let (text, replacement) = scope.params::<(Str, Str)>()?;
+          let result = this.0.replace_all(text.as_str(), replacement.as_str());
+          Ok(result.into_owned())
+        })
+        .finish()
+    })
+    .finish()
+}
+
+struct Regex(regex::Regex);
+
+impl Regex {
+  fn new(pattern: &str) -> Result<Self> {
+    match regex::Regex::new(pattern) {
+      Ok(regex) => Ok(Self(regex)),
+      Err(e) => fail!("invalid regex: {e}"),
+    }
+  }
+}
+
+/// A table with the `start`, `end` and `text` of a match.
+fn to_table<'cx>(scope: &Scope<'cx>, m: regex::Match<'_>) -> Result<Value<'cx>> {
+  let global = scope.global();
+  let table = scope.new_table(3);
+  table.insert(
+    scope.new_string("start"),
+    (m.start() as i64).into_value(global.clone())?,
+  );
+  table.insert(
+    scope.new_string("end"),
+    (m.end() as i64).into_value(global.clone())?,
+  );
+  table.insert(
+    scope.new_string("text"),
+    m.as_str().to_string().into_value(global.clone())?,
+  );
+  table.into_value(global)
+}
+
+fn find<'cx>(scope: Scope<'cx>, this: This<'cx, Regex>) -> Result<Value<'cx>> {
+  let text = scope.param::<Str>(0)?;
+  match this.0.find(text.as_str()) {
+    Some(m) => to_table(&scope, m),
+    None => ().into_value(scope.global()),
+  }
+}
+
+fn find_all<'cx>(scope: Scope<'cx>, this: This<'cx, Regex>) -> Result<Value<'cx>> {
+  let text = scope.param::<Str>(0)?;
+  let list = scope.new_list(0);
+  for m in this.0.find_iter(text.as_str()) {
+    list.push(to_table(&scope, m)?);
+  }
+  list.into_value(scope.global())
+}
+
+/// Returns `none` if there is no match. Otherwise returns a table where
+/// `groups` is a list of all groups (starting with the whole match), and
+/// `named` is a table of the named groups. Groups which did not participate
+/// in the match are `none`.
+fn captures<'cx>(scope: Scope<'cx>, this: This<'cx, Regex>) -> Result<Value<'cx>> {
+  let global = scope.global();
+  let text = scope.param::<Str>(0)?;
+  let Some(caps) = this.0.captures(text.as_str()) else {
+    return ().into_value(global);
+  };
+
+  let group = |m: Option<regex::Match<'_>>| m.map(|m| m.as_str().to_string());
+  let groups = scope.new_list(caps.len());
+  for m in caps.iter() {
+    groups.push(group(m).into_value(global.clone())?);
+  }
+  let named = scope.new_table(0);
+  for name in this.0.capture_names().flatten() {
+    named.insert(
+      scope.new_string(name),
+      group(caps.name(name)).into_value(global.clone())?,
+    );
+  }
+
+  let table = scope.new_table(2);
+  table.insert(
+    scope.new_string("groups"),
+    groups.into_value(global.clone())?,
+  );
+  table.insert(scope.new_string("named"), named.into_value(global.clone())?);
+  table.into_value(global)
+}
@@ -0,0 +1,148 @@
+//! The `time` module.
+//!
+//! ```text
+//! import time
+//!
+//! start := time.now()
+//! time.sleep(0.5)              # or `time.sleep(time.millis(500))`
+//! print time.now() - start     # ~500ms
+//! print time.unix()            # seconds since 1970-01-01
+//! print time.secs(1) + time.millis(250) # 1.25s
+//! print time.secs(1) * 3 / 2  # 1.5s
+//! ```
+//!
+//! `sleep` suspends the script without blocking the thread it runs on, so
+//! other tasks can make progress while it waits.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::internal::error::Result;
+use crate::internal::object::duration::Duration;
+use crate::internal::value;
+use crate::prelude::*;
+use crate::public::{Bind, Unbind};
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("time")
+    .function("now", now)
+    .function("unix", unix)
+    .function("secs", |scope| {
+      let secs = number(&scope, 0)?;
+      new_duration(&scope, Duration::from_secs(secs)?)
+    })
+    .function("millis", |scope| {
+      let millis = number(&scope, 0)?;
+      new_duration(&scope, Duration::from_secs(millis / 1000.0)?)
+    })
+    .async_function("sleep", sleep)
+    .finish()
+}
+
+fn new_duration<'cx>(scope: &Scope<'cx>, duration: Duration) -> Result<Value<'cx>> {
+  Ok(value::Value::object(scope.alloc(duration)).bind(scope.global()))
+}
+
+fn number(scope: &Scope<'_>, n: usize) -> Result<f64> {
+  let value = scope.param::<Value>(n)?.unbind();
+  match value.to_number() {
+    Some(v) => Ok(v),
+    None => fail!("expected a number, got `{value}`"),
+  }
+}
+
+/// The time since an arbitrary point, which is the same for all VMs in the
+/// process. It never goes backwards, so it is suitable for measuring elapsed
+/// time.
+fn now(scope: Scope<'_>) -> Result<Value<'_>> {
+  static ORIGIN: OnceLock<Instant> = OnceLock::new();
+  let origin = *ORIGIN.get_or_init(Instant::now);
+  new_duration(&scope, Duration(origin.elapsed()))
+}
+
+/// The number of seconds since the unix epoch, according to the system clock.
+fn unix(_: Scope<'_>) -> Result<f64> {
+  match SystemTime::now().duration_since(UNIX_EPOCH) {
+    Ok(v) => Ok(v.as_secs_f64()),
+    Err(e) => Ok(-e.duration().as_secs_f64()),
+  }
+}
+
+async fn sleep(scope: Scope<'_>) -> Result<()> {
+  let value = scope.param::<Value>(0)?.unbind();
+  let duration = match value.clone().to_object::<Duration>() {
+    Some(v) => v.0,
+    None => match value.to_number() {
+      Some(secs) => Duration::from_secs(secs)?.0,
+      None => fail!("expected a number or a duration, got `{value}`"),
+    },
+  };
+  let Some(sleep) = Sleep::new(duration) else {
+    fail!("cannot sleep for `{value}`");
+  };
+  sleep.await;
+  Ok(())
+}
+
+/// A future which completes after a deadline.
+///
+/// This doesn't depend on any particular async runtime. The first time it is
+/// polled, it spawns a thread which waits until the deadline, and then wakes
+/// the task.
+struct Sleep {
+  deadline: Instant,
+  timer: Option<Arc<Timer>>,
+}
+
+#[derive(Default)]
+struct Timer {
+  done: AtomicBool,
+  waker: Mutex<Option<Waker>>,
+}
+
+impl Sleep {
+  fn new(duration: std::time::Duration) -> Option<Self> {
+    Some(Self {
+      deadline: Instant::now().checked_add(duration)?,
+      timer: None,
+    })
+  }
+}
+
+impl Future for Sleep {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    if Instant::now() >= self.deadline {
+      return Poll::Ready(());
+    }
+
+    let deadline = self.deadline;
+    let timer = self.timer.get_or_insert_with(|| {
+      let timer = Arc::new(Timer::default());
+      std::thread::spawn({
+        let timer = timer.clone();
+        move || {
+          std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+          timer.done.store(true, Ordering::SeqCst);
+          if let Some(waker) = timer.waker.lock().unwrap().take() {
+            waker.wake();
+          }
+        }
+      });
+      timer
+    });
+
+    // the waker is stored before checking `done`, so a wake-up can't be missed
+    *timer.waker.lock().unwrap() = Some(cx.waker().clone());
+    if timer.done.load(Ordering::SeqCst) {
+      Poll::Ready(())
+    } else {
+      Poll::Pending
+    }
+  }
+}
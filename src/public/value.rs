@@ -1,7 +1,11 @@
+use std::collections::{BTreeMap, HashMap};
+
 use super::object::{Any, ObjectRef};
-use crate::internal::error::Result;
+use crate::internal::error::{Error, ErrorKind, ErrorValue, Result};
+use crate::internal::object::class::ClassInstance;
+use crate::internal::vm::{frozen, transfer};
 use crate::internal::{object, value};
-use crate::public::{Bind, Global, Unbind};
+use crate::public::{Bind, Channel, Frozen, Global, Hebi, List, Table, Unbind};
 
 decl_ref! {
   struct Value(value::Value)
@@ -24,6 +28,11 @@ impl<'cx> Value<'cx> {
     self.inner.is_int()
   }
 
+  /// Like [`Value::as_int`], but also returns ints outside of the `i32` range.
+  pub fn as_i64(&self) -> Option<i64> {
+    self.inner.to_i64()
+  }
+
   pub fn as_bool(&self) -> Option<bool> {
     self.inner.clone().to_bool()
   }
@@ -54,6 +63,125 @@ impl<'cx> Value<'cx> {
   pub fn is_object(&self) -> bool {
     self.inner.is_object()
   }
+
+  /// The names of the fields of this value which can be accessed with `.`,
+  /// such as the fields and methods of a class instance, the static members
+  /// of a class, or the variables of a module. Other values have no names.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// let value = hebi
+  ///   .eval("class T:\n  a = 0\n  fn m(self):\n    pass\nT()")
+  ///   .unwrap();
+  /// assert_eq!(value.field_names(), ["a", "m"]);
+  /// ```
+  pub fn field_names(&self) -> Vec<String> {
+    let Some(any) = self.inner.clone().to_any() else {
+      return vec![];
+    };
+    let table = if let Ok(module) = any.clone().cast::<object::Module>() {
+      module.module_vars.clone()
+    } else if let Ok(instance) = any.clone().cast::<ClassInstance>() {
+      instance.fields.clone()
+    } else if let Ok(class) = any.cast::<object::ClassType>() {
+      class.statics.clone()
+    } else {
+      return vec![];
+    };
+    let names = table
+      .keys()
+      .filter_map(|key| key.as_str().map(|key| key.as_str().to_string()))
+      .collect();
+    names
+  }
+
+  /// The `##` doc comment of this value, if it is a function, method or class
+  /// which has one. Class instances have the doc comment of their class.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// let value = hebi
+  ///   .eval("## Adds two numbers.\nfn add(a, b): a + b\nadd")
+  ///   .unwrap();
+  /// assert_eq!(value.doc().as_deref(), Some("Adds two numbers."));
+  /// ```
+  pub fn doc(&self) -> Option<String> {
+    object::function::doc_of(&self.inner).map(|doc| doc.as_str().to_string())
+  }
+
+  /// Copy this value into `other`, so that it can be used there.
+  ///
+  /// Lists, tables, strings and bytes are copied recursively, preserving any
+  /// shared references and cycles between them. Class instances are recreated
+  /// from the class with the same name in the globals of `other`. Functions
+  /// and native objects can't be copied, and result in an error.
+  pub fn deep_clone_into<'a>(&self, other: &'a Hebi) -> Result<Value<'a>> {
+    let global = &other.vm.global;
+    transfer::deep_clone(&self.inner, global).map(|value| value.bind(other.global()))
+  }
+
+  /// Make an immutable copy of this value, which may be sent to other
+  /// threads and used by any VM, see [`Frozen`].
+  pub fn freeze(&self) -> Result<Frozen> {
+    frozen::freeze(&self.inner)
+  }
+}
+
+impl Frozen {
+  /// Recreate this value in the VM which owns `global`.
+  pub fn thaw<'cx>(&self, global: Global<'cx>) -> Value<'cx> {
+    frozen::thaw(self, &global.inner).bind(global)
+  }
+}
+
+impl Error {
+  /// Create an error which carries `value`, so that it can be inspected by
+  /// whoever handles the error. The error message is the value formatted as
+  /// a string.
+  ///
+  /// ```rust
+  /// use hebi::prelude::*;
+  /// use hebi::ErrorKind;
+  ///
+  /// fn reject(scope: Scope) -> hebi::Result<()> {
+  ///   Err(hebi::Error::from_value(scope.param::<Value>(0)?))
+  /// }
+  ///
+  /// let mut hebi = Hebi::new();
+  /// hebi.register(&NativeModule::builder("http").function("reject", reject).finish());
+  ///
+  /// let e = hebi.eval("from http import reject\nreject(404)").unwrap_err();
+  /// let ErrorKind::User(value) = e.kind() else {
+  ///   panic!("expected an error with a value");
+  /// };
+  /// assert_eq!(value.get(hebi.global()).as_int(), Some(404));
+  /// ```
+  pub fn from_value(value: Value<'_>) -> Self {
+    let value = value.unbind();
+    crate::error!(value.to_string())
+      .with_kind(ErrorKind::User(ErrorValue::new(&value)))
+      .into()
+  }
+}
+
+impl ErrorValue {
+  /// Recreate the value in the VM which owns `global`, see [`Frozen::thaw`].
+  pub fn get<'cx>(&self, global: Global<'cx>) -> Value<'cx> {
+    self.0.value.thaw(global)
+  }
+
+  /// The frozen value, or its string form if it couldn't be frozen.
+  pub fn frozen(&self) -> &Frozen {
+    &self.0.value
+  }
+
+  /// Recreate the field `name` of the value in the VM which owns `global`, if
+  /// the value is a class instance, such as one raised with the `raise`
+  /// statement.
+  pub fn field<'cx>(&self, global: Global<'cx>, name: &str) -> Option<Value<'cx>> {
+    let (_, value) = self.0.fields.iter().find(|(field, _)| field == name)?;
+    Some(value.thaw(global))
+  }
 }
 
 pub trait FromValue<'cx>: Sized {
@@ -91,6 +219,42 @@ impl<'cx> FromValue<'cx> for i32 {
   }
 }
 
+impl<'cx> IntoValue<'cx> for i64 {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    Ok(object::int::int(&global.inner, self).bind(global))
+  }
+}
+
+impl<'cx> FromValue<'cx> for i64 {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    match value.as_i64() {
+      Some(value) => Ok(value),
+      None => crate::fail!("value is not an int"),
+    }
+  }
+}
+
+#[cfg(feature = "bigint")]
+impl<'cx> IntoValue<'cx> for num_bigint::BigInt {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let value = global.inner.alloc(object::bigint::BigInt::new(self));
+    Ok(value::Value::object(value).bind(global))
+  }
+}
+
+#[cfg(feature = "bigint")]
+impl<'cx> FromValue<'cx> for num_bigint::BigInt {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    if let Some(value) = value.as_i64() {
+      return Ok(value.into());
+    }
+    match value.unbind().to_object::<object::bigint::BigInt>() {
+      Some(value) => Ok(value.value().clone()),
+      None => crate::fail!("value is not a bigint"),
+    }
+  }
+}
+
 impl<'cx> IntoValue<'cx> for f64 {
   fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
     Ok(value::Value::float(self).bind(global))
@@ -159,6 +323,11 @@ where
   }
 }
 
+/// The error is raised in the script.
+///
+/// Only `hebi::Error` is supported, so that `Ok(..)` in a native function
+/// doesn't need a type annotation. Other errors may be converted with
+/// `map_err(hebi::Error::user)`, or `?` if they implement `Into<hebi::Error>`.
 impl<'cx, T> IntoValue<'cx> for Result<T>
 where
   T: IntoValue<'cx>,
@@ -216,13 +385,248 @@ pub trait FromValuePack<'cx> {
   fn len() -> usize;
 }
 
+impl<'cx> IntoValue<'cx> for Channel {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    Ok(global.new_channel(&self))
+  }
+}
+
+impl<'cx> FromValue<'cx> for Channel {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    match value.unbind().to_object::<object::channel::Channel>() {
+      Some(channel) => Ok(channel.as_ref().clone()),
+      None => fail!("value is not a channel"),
+    }
+  }
+}
+
+impl<'cx> IntoValue<'cx> for Frozen {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    Ok(self.thaw(global))
+  }
+}
+
+impl<'cx> FromValue<'cx> for Frozen {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    value.freeze()
+  }
+}
+
+impl<'cx, T> IntoValue<'cx> for Vec<T>
+where
+  T: IntoValue<'cx>,
+{
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let list = global.new_list(self.len());
+    for item in self {
+      list.push(item.into_value(global.clone())?);
+    }
+    list.into_value(global)
+  }
+}
+
+/// Both lists and tuples may be converted into a `Vec`.
+impl<'cx, T> FromValue<'cx> for Vec<T>
+where
+  T: FromValue<'cx>,
+{
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    items(value, global.clone(), "a list")?
+      .into_iter()
+      .map(|item| T::from_value(item, global.clone()))
+      .collect()
+  }
+}
+
+/// The items of a list or a tuple.
+fn items<'cx>(value: Value<'cx>, global: Global<'cx>, expected: &str) -> Result<Vec<Value<'cx>>> {
+  if let Some(tuple) = value.clone().unbind().to_object::<object::Tuple>() {
+    return Ok(
+      tuple
+        .as_slice()
+        .iter()
+        .map(|item| item.clone().bind(global.clone()))
+        .collect(),
+    );
+  }
+  match value.as_object::<List>(global) {
+    Some(list) => Ok(list.iter().collect()),
+    None => fail!("value is not {expected}"),
+  }
+}
+
+macro_rules! impl_map {
+  ($Map:ident $(, $bound:path)?) => {
+    impl<'cx, T> IntoValue<'cx> for $Map<String, T>
+    where
+      T: IntoValue<'cx>,
+    {
+      fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+        let table = global.new_table(self.len());
+        for (key, value) in self {
+          table.insert(global.new_string(key), value.into_value(global.clone())?);
+        }
+        table.into_value(global)
+      }
+    }
+
+    impl<'cx, T> FromValue<'cx> for $Map<String, T>
+    where
+      T: FromValue<'cx>,
+    {
+      fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+        let Some(table) = value.as_object::<Table>(global.clone()) else {
+          fail!("value is not a table");
+        };
+        let mut map = $Map::new();
+        for (key, value) in table.entries() {
+          let Some(key) = key.unbind().to_object::<object::Str>() else {
+            fail!("table has a key which is not a string");
+          };
+          map.insert(
+            key.as_str().to_string(),
+            T::from_value(value, global.clone())?,
+          );
+        }
+        Ok(map)
+      }
+    }
+  };
+}
+
+impl_map!(HashMap);
+impl_map!(BTreeMap);
+
+macro_rules! impl_tuple {
+  ($($T:ident),*) => {
+    /// Converted to and from a tuple. A list of the same length may also be
+    /// converted into it.
+    impl<'cx, $($T),*> IntoValue<'cx> for ($($T,)*)
+    where
+      $($T: IntoValue<'cx>,)*
+    {
+      #[allow(non_snake_case)]
+      fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+        let ($($T,)*) = self;
+        let items = vec![$($T.into_value(global.clone())?.unbind(),)*];
+        let tuple = global.inner.alloc(object::Tuple::from(items));
+        Ok(value::Value::object(tuple).bind(global))
+      }
+    }
+
+    impl<'cx, $($T),*> FromValue<'cx> for ($($T,)*)
+    where
+      $($T: FromValue<'cx>,)*
+    {
+      #[allow(non_snake_case)]
+      fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+        let len = __count!($($T)*);
+        let items = items(value, global.clone(), "a tuple")?;
+        if items.len() != len {
+          fail!("expected a tuple of {len} items, got {}", items.len());
+        }
+        let mut items = items.into_iter();
+        $(
+          let $T = <$T>::from_value(items.next().unwrap(), global.clone())?;
+        )*
+        Ok(($($T,)*))
+      }
+    }
+  };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+
+impl<'cx> IntoValue<'cx> for std::time::Duration {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let duration = global.inner.alloc(object::duration::Duration(self));
+    Ok(value::Value::object(duration).bind(global))
+  }
+}
+
+/// A number is converted into a duration of that many seconds, like in
+/// `time.sleep`.
+impl<'cx> FromValue<'cx> for std::time::Duration {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    let value = value.unbind();
+    if let Some(duration) = value.clone().to_object::<object::duration::Duration>() {
+      return Ok(duration.0);
+    }
+    match value.to_number() {
+      Some(secs) => Ok(object::duration::Duration::from_secs(secs)?.0),
+      None => fail!("value is not a duration"),
+    }
+  }
+}
+
+/// Converted to and from a `Duration`, which can't be negative.
+#[cfg(feature = "chrono")]
+impl<'cx> IntoValue<'cx> for chrono::TimeDelta {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    match self.to_std() {
+      Ok(duration) => duration.into_value(global),
+      Err(_) => fail!("`{self}` is negative, so it is not a valid duration"),
+    }
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl<'cx> FromValue<'cx> for chrono::TimeDelta {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let duration = std::time::Duration::from_value(value, global)?;
+    match chrono::TimeDelta::from_std(duration) {
+      Ok(delta) => Ok(delta),
+      Err(_) => fail!("duration is out of range"),
+    }
+  }
+}
+
+/// Converted to the number of seconds since 1970-01-01, like `time.unix()`.
+/// A number of seconds, or a string in the RFC 3339 format, may be converted
+/// into it.
+#[cfg(feature = "chrono")]
+impl<'cx> IntoValue<'cx> for chrono::DateTime<chrono::Utc> {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let secs = self.timestamp() as f64 + self.timestamp_subsec_nanos() as f64 / 1e9;
+    secs.into_value(global)
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl<'cx> FromValue<'cx> for chrono::DateTime<chrono::Utc> {
+  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+    let value = value.unbind();
+    if let Some(str) = value.clone().to_object::<object::Str>() {
+      return match chrono::DateTime::parse_from_rfc3339(str.as_str()) {
+        Ok(time) => Ok(time.to_utc()),
+        Err(e) => fail!("`{str}` is not a valid date: {e}"),
+      };
+    }
+    let Some(secs) = value.to_number() else {
+      fail!("value is not a date");
+    };
+    let nanos = (secs.rem_euclid(1.0) * 1e9) as u32;
+    match chrono::DateTime::from_timestamp(secs.floor() as i64, nanos) {
+      Some(time) => Ok(time),
+      None => fail!("`{secs}` is not a valid timestamp"),
+    }
+  }
+}
+
 impl<'cx> FromValuePack<'cx> for () {
   type Output = ();
 
   fn from_value_pack(args: &[value::Value], _: Global<'cx>) -> Result<Self::Output> {
     #[allow(clippy::len_zero)]
     if args.len() > 0 {
-      fail!("expected at most 0 args, got {}", args.len());
+      fail!(Arity: "expected at most 0 args, got {}", args.len());
     }
     Ok(())
   }
@@ -248,10 +652,10 @@ macro_rules! impl_from_value_pack {
         let expected_num_args = Self::len();
 
         if num_args > expected_num_args {
-          fail!("expected at most {expected_num_args} args, got {num_args}");
+          fail!(Arity: "expected at most {expected_num_args} args, got {num_args}");
         }
         if num_args < expected_num_args {
-          fail!("expected at least {expected_num_args} args, got {num_args}");
+          fail!(Arity: "expected at least {expected_num_args} args, got {num_args}");
         }
 
         let mut offset = 0;
@@ -286,6 +690,58 @@ impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J);
 impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J, K);
 impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J, K, L);
 
+/// Arguments of a call into a script function from Rust.
+pub trait IntoValuePack<'cx> {
+  type Output: AsRef<[value::Value]>;
+  fn into_value_pack(self, global: Global<'cx>) -> Result<Self::Output>;
+  fn len() -> usize;
+}
+
+impl<'cx> IntoValuePack<'cx> for () {
+  type Output = [value::Value; 0];
+
+  fn into_value_pack(self, _: Global<'cx>) -> Result<Self::Output> {
+    Ok([])
+  }
+
+  fn len() -> usize {
+    0
+  }
+}
+
+macro_rules! impl_into_value_pack {
+  ($($T:ident),*) => {
+    impl<'cx, $($T),*> IntoValuePack<'cx> for ($($T,)*)
+    where
+      $(
+        $T: IntoValue<'cx>,
+      )*
+    {
+      type Output = [$crate::internal::value::Value; __count!($($T)*)];
+
+      #[allow(non_snake_case)]
+      fn into_value_pack(self, global: Global<'cx>) -> Result<Self::Output> {
+        let ($($T,)*) = self;
+        Ok([$($T.into_value(global.clone())?.unbind(),)*])
+      }
+
+      #[inline]
+      fn len() -> usize {
+        __count!($($T)*)
+      }
+    }
+  };
+}
+
+impl_into_value_pack!(A);
+impl_into_value_pack!(A, B);
+impl_into_value_pack!(A, B, C);
+impl_into_value_pack!(A, B, C, D);
+impl_into_value_pack!(A, B, C, D, E);
+impl_into_value_pack!(A, B, C, D, E, F);
+impl_into_value_pack!(A, B, C, D, E, F, G);
+impl_into_value_pack!(A, B, C, D, E, F, G, H);
+
 #[cfg(feature = "serde")]
 mod serde {
   use ::serde::Serialize;
@@ -5,6 +5,8 @@ use std::error::Error as StdError;
 use std::fmt::{Debug, Display, Write};
 use std::ops::{Deref, DerefMut, Index, Range};
 
+use crate::internal::error::ErrorKind;
+
 /// Represents a span of bytes in some source string.
 ///
 /// This type is just like [`std::ops::Range<usize>`],
@@ -162,6 +164,36 @@ impl<T: std::fmt::Display> std::fmt::Display for Spanned<T> {
 pub struct SpannedError {
   pub span: Span,
   pub message: String,
+  pub kind: ErrorKind,
+  /// Other locations which are related to the error.
+  pub labels: Vec<Label>,
+  /// Printed after the snippets.
+  pub notes: Vec<Note>,
+}
+
+/// A secondary location in a [`SpannedError`], such as where a duplicate
+/// name was first defined.
+#[derive(Clone, Debug)]
+pub struct Label {
+  pub span: Span,
+  pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Note {
+  /// Additional context about the error.
+  Note(String),
+  /// A suggestion for how to fix the error.
+  Help(String),
+}
+
+impl Display for Note {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Note::Note(message) => write!(f, "note: {message}"),
+      Note::Help(message) => write!(f, "help: {message}"),
+    }
+  }
 }
 
 pub trait MaybeSpan {
@@ -194,58 +226,103 @@ impl SpannedError {
     Self {
       span: span.into_span(),
       message: message.to_string(),
+      kind: ErrorKind::Other,
+      labels: Vec::new(),
+      notes: Vec::new(),
     }
   }
 
+  pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+    self.kind = kind;
+    self
+  }
+
+  pub fn with_label(mut self, span: impl MaybeSpan, message: impl ToString) -> Self {
+    self.labels.push(Label {
+      span: span.into_span(),
+      message: message.to_string(),
+    });
+    self
+  }
+
+  pub fn with_note(mut self, message: impl ToString) -> Self {
+    self.notes.push(Note::Note(message.to_string()));
+    self
+  }
+
+  pub fn with_help(mut self, message: impl ToString) -> Self {
+    self.notes.push(Note::Help(message.to_string()));
+    self
+  }
+
   pub fn report(&self, src: &str, use_color: bool) -> String {
-    if self.span.is_empty() {
-      return self.message.clone();
+    let mut out = snippet(&self.message, self.span, src, use_color, "\x1b[4;31m");
+    for label in self.labels.iter() {
+      if !out.ends_with('\n') {
+        out.push('\n');
+      }
+      out += &snippet(&label.message, label.span, src, use_color, "\x1b[4;34m");
     }
-    if self.span.start > src.len() || self.span.end > src.len() {
-      // TODO: file database + interned spans will solve this
-      return self.message.clone();
-      // panic!("invalid span {self}");
+    for note in self.notes.iter() {
+      if !out.ends_with('\n') {
+        out.push('\n');
+      }
+      write!(&mut out, "{note}").unwrap();
     }
+    out
+  }
+}
 
-    let start = src[..self.span.start].rfind('\n').unwrap_or(0);
-    let end = src[self.span.end..]
-      .find('\n')
-      .map(|v| v + self.span.end)
-      .unwrap_or(src.len());
+/// Render `message` followed by the lines of `src` which contain `span`,
+/// with the contents of `span` highlighted using `color`.
+fn snippet(message: &str, span: Span, src: &str, use_color: bool, color: &str) -> String {
+  if span.is_empty() {
+    return message.to_string();
+  }
+  if span.start > src.len() || span.end > src.len() {
+    // TODO: file database + interned spans will solve this
+    return message.to_string();
+    // panic!("invalid span {self}");
+  }
 
-    // print snippet
-    let (r, c) = if use_color {
-      ("\x1b[0m", "\x1b[4;31m")
-    } else {
-      ("", "")
-    };
-
-    let pre = &src[start..self.span.start].trim_start();
-    let content = &src[self.span.start..self.span.end];
-    let post = &src[self.span.end..end].trim_end();
-
-    let mut out = String::new();
-    let f = &mut out;
-
-    writeln!(f, "{}", self.message).unwrap();
-    let mut lines = content.lines().peekable();
-    let line = lines.next().unwrap().or("_");
-    if lines.peek().is_some() {
-      writeln!(f, "| {pre}{c}{line}{r}").unwrap();
-      while let Some(line) = lines.next() {
-        let line = line.or("_");
-        if lines.peek().is_some() {
-          writeln!(f, "| {c}{line}{r}").unwrap();
-        } else {
-          write!(f, "| {c}{line}{r}{post}").unwrap();
-        }
+  let start = src[..span.start].rfind('\n').unwrap_or(0);
+  let end = src[span.end..]
+    .find('\n')
+    .map(|v| v + span.end)
+    .unwrap_or(src.len());
+
+  // print snippet
+  let (r, c) = if use_color {
+    ("\x1b[0m", color)
+  } else {
+    ("", "")
+  };
+
+  let pre = &src[start..span.start].trim_start();
+  let content = &src[span.start..span.end];
+  let post = &src[span.end..end].trim_end();
+
+  let mut out = String::new();
+  let f = &mut out;
+
+  writeln!(f, "{message}").unwrap();
+  let mut lines = content.lines().peekable();
+  let line = lines.next().unwrap().or("_");
+  if lines.peek().is_some() {
+    writeln!(f, "| {pre}{c}{line}{r}").unwrap();
+    while let Some(line) = lines.next() {
+      let line = line.or("_");
+      if lines.peek().is_some() {
+        writeln!(f, "| {c}{line}{r}").unwrap();
+      } else {
+        write!(f, "| {c}{line}{r}{post}").unwrap();
       }
-    } else {
-      writeln!(f, "| {pre}{c}{line}{r}{post}").unwrap();
     }
-
-    out
+  } else {
+    writeln!(f, "| {pre}{c}{line}{r}{post}").unwrap();
   }
+
+  out
 }
 
 trait EmptyOr {
@@ -60,6 +60,17 @@ fn emit_report_multi_line_edge_case_sandwiched_newline_2() {
   check!("\0\"\nl\n\n\n\n\\", 1..8);
 }
 
+#[test]
+fn emit_report_with_labels_and_notes() {
+  let src = "x := 0\ny := 1\nx := 2";
+  assert_snapshot!(SpannedError::new("error: test", 14..15)
+    .with_label(0..1, "label: first")
+    .with_label(7..13, "label: second")
+    .with_note("a note")
+    .with_help("a suggestion")
+    .report(src, true));
+}
+
 #[allow(clippy::no_effect)]
 #[test]
 fn test_spanned() {
@@ -0,0 +1,81 @@
+// A wrapper around the functions exported by `hebi_wasm.wasm`, see
+// `src/lib.rs` for how to build it.
+//
+//   const hebi = await Hebi.load("hebi_wasm.wasm");
+//   const { value, error, output } = hebi.eval("print 1\n[1, 2]");
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export class Hebi {
+  /** Instantiate the module at `url`, and create a VM using it. */
+  static async load(url) {
+    const { instance } = await WebAssembly.instantiateStreaming(fetch(url), {});
+    return new Hebi(instance.exports);
+  }
+
+  constructor(exports) {
+    this.exports = exports;
+    this.runtime = exports.hebi_new();
+  }
+
+  /** Free the VM. It may not be used afterwards. */
+  free() {
+    this.exports.hebi_drop(this.runtime);
+    this.runtime = 0;
+  }
+
+  /**
+   * Evaluate `source`. Returns an object with either a `value` or an
+   * `error`, and everything the script printed in `output` and `errorOutput`.
+   */
+  eval(source) {
+    this.#call("hebi_eval", source);
+    return this.#result();
+  }
+
+  /** Make `source` importable as the module `name`. */
+  addModule(name, source) {
+    this.#call("hebi_add_module", name, source);
+  }
+
+  /** The value of the global variable `name`, or `null`. */
+  getGlobal(name) {
+    this.#call("hebi_get_global", name);
+    return this.#result();
+  }
+
+  /** Set the global variable `name` to `value`, which must be JSON-serializable. */
+  setGlobal(name, value) {
+    this.#call("hebi_set_global", name, JSON.stringify(value));
+    const { error } = this.#result();
+    if (error) {
+      throw new Error(error);
+    }
+  }
+
+  #call(name, ...strings) {
+    const args = strings.map((string) => this.#alloc(string));
+    try {
+      this.exports[name](this.runtime, ...args.flat());
+    } finally {
+      for (const [ptr, len] of args) {
+        this.exports.hebi_free(ptr, len);
+      }
+    }
+  }
+
+  #alloc(string) {
+    const bytes = encoder.encode(string);
+    const ptr = this.exports.hebi_alloc(bytes.length);
+    new Uint8Array(this.exports.memory.buffer, ptr, bytes.length).set(bytes);
+    return [ptr, bytes.length];
+  }
+
+  #result() {
+    const ptr = this.exports.hebi_result_ptr(this.runtime);
+    const len = this.exports.hebi_result_len(this.runtime);
+    const bytes = new Uint8Array(this.exports.memory.buffer, ptr, len);
+    return JSON.parse(decoder.decode(bytes));
+  }
+}
@@ -0,0 +1,134 @@
+//! The functions exported from the WebAssembly module.
+//!
+//! Strings are passed as a pointer and a length, and must be valid UTF-8.
+//! The host writes them into memory allocated with [`hebi_alloc`], and frees
+//! it with [`hebi_free`] once the call returns.
+//!
+//! Functions which produce a result store it in the runtime as JSON, which
+//! the host reads using [`hebi_result_ptr`] and [`hebi_result_len`]. It is
+//! valid until the next call.
+
+use std::borrow::Cow;
+
+use serde_json::json;
+
+use crate::Runtime;
+
+/// Allocate `len` bytes.
+#[no_mangle]
+pub extern "C" fn hebi_alloc(len: usize) -> *mut u8 {
+  Box::into_raw(vec![0u8; len].into_boxed_slice()).cast()
+}
+
+/// Free memory allocated with [`hebi_alloc`].
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`hebi_alloc`] with the same `len`.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_free(ptr: *mut u8, len: usize) {
+  drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+#[no_mangle]
+pub extern "C" fn hebi_new() -> *mut Runtime {
+  Box::into_raw(Box::new(Runtime::new()))
+}
+
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`], and may not be used
+/// after this call.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_drop(runtime: *mut Runtime) {
+  drop(Box::from_raw(runtime));
+}
+
+/// See [`Runtime::add_module`].
+///
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`], and the strings must
+/// point to the given number of bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_add_module(
+  runtime: *mut Runtime,
+  name: *const u8,
+  name_len: usize,
+  source: *const u8,
+  source_len: usize,
+) {
+  let runtime = &mut *runtime;
+  runtime.add_module(&string(name, name_len), &string(source, source_len));
+}
+
+/// See [`Runtime::eval`].
+///
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`], and `source` must
+/// point to `source_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_eval(runtime: *mut Runtime, source: *const u8, source_len: usize) {
+  let runtime = &mut *runtime;
+  let result = runtime.eval(&string(source, source_len));
+  runtime.result = result.to_string();
+}
+
+/// See [`Runtime::get_global`].
+///
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`], and `name` must point
+/// to `name_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_get_global(runtime: *mut Runtime, name: *const u8, name_len: usize) {
+  let runtime = &mut *runtime;
+  let result = runtime.get_global(&string(name, name_len));
+  runtime.result = result.to_string();
+}
+
+/// See [`Runtime::set_global`]. The result is an object, with an `error` if
+/// the value couldn't be parsed.
+///
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`], and the strings must
+/// point to the given number of bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hebi_set_global(
+  runtime: *mut Runtime,
+  name: *const u8,
+  name_len: usize,
+  json: *const u8,
+  json_len: usize,
+) {
+  let runtime = &mut *runtime;
+  let result = match runtime.set_global(&string(name, name_len), &string(json, json_len)) {
+    Ok(()) => json!({}),
+    Err(e) => json!({ "error": e }),
+  };
+  runtime.result = result.to_string();
+}
+
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`].
+#[no_mangle]
+pub unsafe extern "C" fn hebi_result_ptr(runtime: *const Runtime) -> *const u8 {
+  let runtime = &*runtime;
+  runtime.result.as_ptr()
+}
+
+/// # Safety
+///
+/// `runtime` must have been returned by [`hebi_new`].
+#[no_mangle]
+pub unsafe extern "C" fn hebi_result_len(runtime: *const Runtime) -> usize {
+  let runtime = &*runtime;
+  runtime.result.len()
+}
+
+unsafe fn string<'a>(ptr: *const u8, len: usize) -> Cow<'a, str> {
+  String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len))
+}
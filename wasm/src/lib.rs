@@ -0,0 +1,144 @@
+//! Bindings for running Hebi in a browser, or any other WebAssembly host.
+//!
+//! ```text
+//! cargo build -p hebi-wasm --release --target wasm32-unknown-unknown
+//! ```
+//!
+//! The functions in [`ffi`] only take and return numbers, so they can be
+//! called from JavaScript without generated glue code. `hebi.js` wraps them
+//! in a class:
+//!
+//! ```text
+//! import { Hebi } from "./hebi.js";
+//!
+//! const hebi = await Hebi.load("hebi_wasm.wasm");
+//! hebi.addModule("greeting", "fn greet(name):\n  print \"hello\", name");
+//! hebi.setGlobal("names", ["a", "b"]);
+//! const { value, output } = hebi.eval("import greeting\ngreeting.greet(names[0])\nnames");
+//! ```
+//!
+//! Values are converted to and from JSON. Lists and tuples become arrays,
+//! and tables and class instances become objects. Values which have no JSON
+//! representation, such as functions, are converted to their string
+//! representation.
+//!
+//! Scripts can't read from stdin, and only import modules added with
+//! [`Runtime::add_module`] or the builtin modules.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use hebi::prelude::*;
+use hebi::span::SpannedError;
+use serde::de::DeserializeSeed;
+use serde_json::json;
+
+pub mod ffi;
+
+pub struct Runtime {
+  hebi: Hebi,
+  modules: Arc<Mutex<HashMap<String, String>>>,
+  /// The result of the last call through [`ffi`], as JSON.
+  result: String,
+}
+
+impl Runtime {
+  pub fn new() -> Self {
+    let modules = Arc::new(Mutex::new(HashMap::new()));
+    let hebi = Hebi::builder()
+      .module_loader(ModuleMap {
+        modules: modules.clone(),
+      })
+      .input(std::io::empty())
+      .output(Vec::<u8>::new())
+      .error_output(Vec::<u8>::new())
+      .with_builtins()
+      .finish();
+    Self {
+      hebi,
+      modules,
+      result: String::new(),
+    }
+  }
+
+  /// Make `source` importable as the module `name`.
+  pub fn add_module(&mut self, name: &str, source: &str) {
+    self
+      .modules
+      .lock()
+      .unwrap()
+      .insert(name.to_string(), source.to_string());
+  }
+
+  /// Evaluate `source`. The result has either a `value` or an `error`, and
+  /// everything printed by the script in `output` and `errorOutput`.
+  pub fn eval(&mut self, source: &str) -> serde_json::Value {
+    let mut result = match self.hebi.eval(source) {
+      Ok(value) => json!({ "value": to_json(&value) }),
+      Err(e) => json!({ "error": e.report(source, false) }),
+    };
+    let mut global = self.hebi.global();
+    result["output"] = take_output(global.output().as_any_mut()).into();
+    result["errorOutput"] = take_output(global.error_output().as_any_mut()).into();
+    result
+  }
+
+  /// The value of the global variable `name`, or `null` if it doesn't exist.
+  pub fn get_global(&self, name: &str) -> serde_json::Value {
+    match self.hebi.global().get(name) {
+      Some(value) => to_json(&value),
+      None => serde_json::Value::Null,
+    }
+  }
+
+  /// Set the global variable `name` to the value parsed from `json`.
+  pub fn set_global(&mut self, name: &str, json: &str) -> Result<(), String> {
+    let global = self.hebi.global();
+    let value = ValueDeserializer::new(global.clone())
+      .deserialize(&mut serde_json::Deserializer::from_str(json))
+      .map_err(|e| format!("failed to parse json: {e}"))?;
+    global.set(global.new_string(name), value);
+    Ok(())
+  }
+}
+
+impl Default for Runtime {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Convert `value` to JSON, or to its string representation if it has no
+/// JSON representation.
+fn to_json(value: &Value) -> serde_json::Value {
+  serde_json::to_value(value).unwrap_or_else(|_| value.to_string().into())
+}
+
+/// Take the contents of an output buffer.
+fn take_output(output: &mut dyn Any) -> String {
+  let buffer = output
+    .downcast_mut::<Vec<u8>>()
+    .map(std::mem::take)
+    .unwrap_or_default();
+  String::from_utf8_lossy(&buffer).into_owned()
+}
+
+struct ModuleMap {
+  modules: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ModuleLoader for ModuleMap {
+  fn load(&self, path: &ModulePath) -> hebi::Result<hebi::Cow<'static, str>> {
+    match self.modules.lock().unwrap().get(path.as_str()) {
+      Some(source) => Ok(hebi::Cow::owned(source.clone())),
+      None => Err(hebi::Error::Vm(SpannedError::new(
+        format!("module `{path}` not found"),
+        None,
+      ))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,72 @@
+use serde_json::json;
+
+use super::*;
+
+#[test]
+fn eval() {
+  let mut runtime = Runtime::new();
+  assert_eq!(
+    runtime.eval("print \"a\", 1\neprint(\"b\")\n{a: [1, 2.5, none], b: (true,)}"),
+    json!({
+      "value": {"a": [1, 2.5, null], "b": [true]},
+      "output": "a 1\n",
+      "errorOutput": "b\n",
+    })
+  );
+  assert_eq!(
+    runtime.eval("fn f():\n  pass\nf"),
+    json!({ "value": "<function `f`>", "output": "", "errorOutput": "" })
+  );
+
+  let result = runtime.eval("print 0\nundefined");
+  assert_eq!(result["output"], "0\n");
+  assert!(result["error"]
+    .as_str()
+    .unwrap()
+    .contains("undefined global undefined"));
+}
+
+#[test]
+fn modules() {
+  let mut runtime = Runtime::new();
+  runtime.add_module("m", "value := 100");
+  assert_eq!(runtime.eval("import m\nm.value")["value"], 100);
+  assert!(runtime.eval("import other")["error"]
+    .as_str()
+    .unwrap()
+    .contains("module `other` not found"));
+}
+
+#[test]
+fn globals() {
+  let mut runtime = Runtime::new();
+  runtime
+    .set_global("v", r#"{"a": [1, "b"], "c": null}"#)
+    .unwrap();
+  assert_eq!(runtime.eval("v[\"a\"][1]")["value"], "b");
+  assert_eq!(runtime.get_global("v"), json!({"a": [1, "b"], "c": null}));
+  assert_eq!(runtime.get_global("missing"), serde_json::Value::Null);
+  assert!(runtime.set_global("v", "{").is_err());
+}
+
+#[test]
+fn ffi() {
+  unsafe fn call_with(f: impl FnOnce(*const u8, usize), s: &str) {
+    let ptr = ffi::hebi_alloc(s.len());
+    std::ptr::copy_nonoverlapping(s.as_ptr(), ptr, s.len());
+    f(ptr, s.len());
+    ffi::hebi_free(ptr, s.len());
+  }
+
+  unsafe {
+    let runtime = ffi::hebi_new();
+    call_with(|ptr, len| ffi::hebi_eval(runtime, ptr, len), "1 + 1");
+    let result =
+      std::slice::from_raw_parts(ffi::hebi_result_ptr(runtime), ffi::hebi_result_len(runtime));
+    assert_eq!(
+      serde_json::from_slice::<serde_json::Value>(result).unwrap(),
+      json!({ "value": 2, "output": "", "errorOutput": "" })
+    );
+    ffi::hebi_drop(runtime);
+  }
+}
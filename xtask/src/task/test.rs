@@ -9,6 +9,14 @@ pub fn run(args: &[String]) -> Result<()> {
     .wait()?
     .check()?;
 
+  // `--all-features` replaces the portable dispatch loop
+  cargo("test")
+    .args(["--lib"])
+    .args(args)
+    .spawn()?
+    .wait()?
+    .check()?;
+
   super::examples::run(args)?;
 
   Ok(())